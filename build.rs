@@ -0,0 +1,13 @@
+fn main() {
+    // The gRPC service is generated from proto/harimu.proto only for `--features grpc`, with a
+    // bundled protoc so no system install is needed.
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/harimu.proto");
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("bundled protoc");
+        // SAFETY: build scripts are single-threaded.
+        unsafe { std::env::set_var("PROTOC", protoc) };
+        tonic_prost_build::compile_protos("proto/harimu.proto")
+            .expect("compile proto/harimu.proto");
+    }
+}
@@ -1,6 +1,28 @@
+use std::fs;
+use std::sync::Once;
+use std::time::SystemTime;
+
 use godot::prelude::*;
 
-use harimu::{Position, WorldSnapshot, load_world_snapshot, snapshot_from_persistent};
+use harimu::{
+    AgentSnapshot, AgentTrailPoint, ItemKind, Position, SnapshotMeta, WorldSnapshot, ZONE_SIZE,
+    load_agent_history, load_registry, load_world_snapshot, snapshot_file_path,
+    snapshot_from_persistent,
+};
+
+/// How often the provider checks the snapshot file for changes.
+const POLL_INTERVAL_SECS: f64 = 0.5;
+
+static REGISTRY: Once = Once::new();
+
+/// Registers the data directory's ores once, as the CLI does, so saves naming them load.
+fn ensure_registry() {
+    REGISTRY.call_once(|| {
+        if let Err(err) = load_registry() {
+            godot_error!("Failed to load the ore registry: {}", err);
+        }
+    });
+}
 
 struct HarimuGodotViewer;
 
@@ -12,28 +34,184 @@ unsafe impl ExtensionLibrary for HarimuGodotViewer {}
 struct WorldSnapshotProvider {
     #[base]
     base: Base<Node>,
+    /// Modification time of the snapshot file when it was last read.
+    seen_modified: Option<SystemTime>,
+    last_snapshot: Option<WorldSnapshot>,
+    since_poll: f64,
 }
 
 #[godot_api]
-impl INode for WorldSnapshotProvider {}
+impl INode for WorldSnapshotProvider {
+    /// Watches the snapshot file while the provider is in the scene tree and emits
+    /// `snapshot_updated`, `agent_died` and `structure_built` when a new snapshot lands.
+    fn process(&mut self, delta: f64) {
+        self.since_poll += delta;
+        if self.since_poll < POLL_INTERVAL_SECS {
+            return;
+        }
+        self.since_poll = 0.0;
+        self.poll_snapshot();
+    }
+}
 
 #[godot_api]
 impl WorldSnapshotProvider {
+    #[signal]
+    fn snapshot_updated(tick: i64);
+
+    #[signal]
+    fn agent_died(id: i64);
+
+    #[signal]
+    fn structure_built(id: i64, kind: GString, owner: i64, position: Vector3);
+
     #[func]
     fn load_snapshot(&self) -> Dictionary {
-        match load_world_snapshot() {
-            Ok(Some(snapshot)) => snapshot_to_dict(&snapshot),
-            Ok(None) => match snapshot_from_persistent() {
-                Ok(snapshot) => snapshot_to_dict(&snapshot),
-                Err(err) => {
-                    godot_error!("No snapshot available: {}", err);
-                    Dictionary::new()
-                }
-            },
+        current_snapshot()
+            .map(|snapshot| snapshot_to_dict(&snapshot))
+            .unwrap_or_else(Dictionary::new)
+    }
+
+    /// One agent from the latest snapshot plus follow-cam hints: `heading` is the last
+    /// tick-to-tick movement (zero when standing still) and `zone_name` the name of its zone.
+    /// Empty when the agent is unknown.
+    #[func]
+    fn get_agent(&self, id: i64) -> Dictionary {
+        let Some(snapshot) = current_snapshot() else {
+            return Dictionary::new();
+        };
+        let Some(agent) = snapshot.agents.iter().find(|a| a.id as i64 == id) else {
+            return Dictionary::new();
+        };
+
+        let mut entry = agent_to_dict(agent);
+        let trail = load_agent_history(agent.id, 2).unwrap_or_default();
+        let heading = match trail.as_slice() {
+            [.., prev, last] => position_to_vec3(last.position) - position_to_vec3(prev.position),
+            _ => Vector3::ZERO,
+        };
+        let _ = entry.insert("heading", heading);
+        let zone = agent.position.zone();
+        let zone_name = snapshot
+            .zones
+            .iter()
+            .find(|label| label.zone == zone)
+            .and_then(|label| label.name.clone())
+            .unwrap_or_default();
+        let _ = entry.insert("zone_name", zone_name);
+        entry
+    }
+
+    /// Positions of an agent over the last `ticks` tick snapshots, oldest first, as
+    /// `{tick, position, alive}` dictionaries for trails.
+    #[func]
+    fn get_agent_history(&self, id: i64, ticks: i64) -> Array<Dictionary> {
+        if id < 0 || ticks <= 0 {
+            return Array::new();
+        }
+        ensure_registry();
+        match load_agent_history(id as u64, ticks as usize) {
+            Ok(trail) => trail_to_array(&trail),
+            Err(err) => {
+                godot_error!("Failed to load agent history: {}", err);
+                Array::new()
+            }
+        }
+    }
+
+    /// Per-zone overview within `zone_radius` zones of the origin zone: one dictionary per
+    /// non-empty zone with its coordinates, agent count, ore total, structure count and owner.
+    #[func]
+    fn get_minimap(&self, zone_radius: i64) -> Array<Dictionary> {
+        let Some(snapshot) = current_snapshot() else {
+            return Array::new();
+        };
+
+        let center = Position::origin().zone();
+        let radius = zone_radius.clamp(0, i32::MAX as i64) as i32;
+        let mut zones = Array::<Dictionary>::new();
+        for summary in snapshot.minimap(center, radius) {
+            let mut entry = Dictionary::new();
+            let zone = summary.zone;
+            let _ = entry.insert(
+                "zone",
+                Vector3::new(zone.x as f32, zone.y as f32, zone.z as f32),
+            );
+            let _ = entry.insert("name", summary.name.unwrap_or_default());
+            let _ = entry.insert("agents", summary.agents as i64);
+            let _ = entry.insert("ore", summary.ore_available as i64);
+            let _ = entry.insert("structures", summary.structures as i64);
+            // -1 when no structure stands in the zone.
+            let _ = entry.insert("owner", summary.owner.map(|id| id as i64).unwrap_or(-1));
+            zones.push(&entry);
+        }
+        zones
+    }
+}
+
+impl WorldSnapshotProvider {
+    fn poll_snapshot(&mut self) {
+        let modified = fs::metadata(snapshot_file_path())
+            .and_then(|meta| meta.modified())
+            .ok();
+        if modified.is_none() || modified == self.seen_modified {
+            return;
+        }
+        self.seen_modified = modified;
+
+        ensure_registry();
+        let snapshot = match load_world_snapshot() {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => return,
+            Err(err) => {
+                // Usually a half-written file; the next poll picks up the finished one.
+                godot_warn!("Skipping unreadable snapshot: {}", err);
+                self.seen_modified = None;
+                return;
+            }
+        };
+
+        // The first snapshot seen is the baseline: nothing in it counts as a change.
+        if let Some(previous) = self.last_snapshot.as_ref() {
+            let changes = snapshot.changes_since(previous);
+            for id in changes.died {
+                self.base_mut()
+                    .emit_signal("agent_died", &[(id as i64).to_variant()]);
+            }
+            for structure in changes.built {
+                self.base_mut().emit_signal(
+                    "structure_built",
+                    &[
+                        (structure.id as i64).to_variant(),
+                        GString::from(structure.kind.to_string()).to_variant(),
+                        (structure.owner as i64).to_variant(),
+                        position_to_vec3(structure.position).to_variant(),
+                    ],
+                );
+            }
+        }
+        let tick = snapshot.tick as i64;
+        self.last_snapshot = Some(snapshot);
+        self.base_mut()
+            .emit_signal("snapshot_updated", &[tick.to_variant()]);
+    }
+}
+
+/// Latest saved snapshot, or one rebuilt from the persistent stores; errors are logged.
+fn current_snapshot() -> Option<WorldSnapshot> {
+    ensure_registry();
+    match load_world_snapshot() {
+        Ok(Some(snapshot)) => Some(snapshot),
+        Ok(None) => match snapshot_from_persistent() {
+            Ok(snapshot) => Some(snapshot),
             Err(err) => {
-                godot_error!("Failed to load snapshot: {}", err);
-                Dictionary::new()
+                godot_error!("No snapshot available: {}", err);
+                None
             }
+        },
+        Err(err) => {
+            godot_error!("Failed to load snapshot: {}", err);
+            None
         }
     }
 }
@@ -44,16 +222,7 @@ fn snapshot_to_dict(snapshot: &WorldSnapshot) -> Dictionary {
 
     let mut agents = Array::<Dictionary>::new();
     for agent in &snapshot.agents {
-        let mut entry = Dictionary::new();
-        let _ = entry.insert("id", agent.id as i64);
-        let _ = entry.insert("name", agent.name.clone());
-        let _ = entry.insert("qi", agent.qi as i64);
-        let _ = entry.insert("transistors", agent.transistors as i64);
-        let _ = entry.insert("alive", agent.alive);
-        let _ = entry.insert("age", agent.age as i64);
-        let _ = entry.insert("position", position_to_vec3(agent.position));
-        let _ = entry.insert("max_age", agent.max_age as i64);
-        agents.push(&entry);
+        agents.push(&agent_to_dict(agent));
     }
     let _ = dict.insert("agents", agents);
 
@@ -81,9 +250,106 @@ fn snapshot_to_dict(snapshot: &WorldSnapshot) -> Dictionary {
     }
     let _ = dict.insert("structures", structures);
 
+    let mut terrain = Array::<Dictionary>::new();
+    for chunk in &snapshot.terrain {
+        let mut entry = Dictionary::new();
+        let _ = entry.insert("origin", position_to_vec3(chunk.origin));
+        let _ = entry.insert("size", chunk.size as i64);
+        let mut runs = VariantArray::new();
+        for (block, count) in &chunk.runs {
+            let mut run = VariantArray::new();
+            run.push(&block.label().to_variant());
+            run.push(&(*count as i64).to_variant());
+            runs.push(&run.to_variant());
+        }
+        let _ = entry.insert("runs", runs);
+        terrain.push(&entry);
+    }
+    let _ = dict.insert("terrain", terrain);
+
+    let mut zones = Array::<Dictionary>::new();
+    for label in &snapshot.zones {
+        let mut entry = Dictionary::new();
+        let zone = Vector3::new(
+            label.zone.x as f32,
+            label.zone.y as f32,
+            label.zone.z as f32,
+        );
+        let _ = entry.insert("zone", zone);
+        let _ = entry.insert("origin", zone * ZONE_SIZE as f32);
+        let _ = entry.insert("name", label.name.clone().unwrap_or_default());
+        let notes: PackedStringArray = label.notes.iter().map(GString::from).collect();
+        let _ = entry.insert("notes", notes);
+        zones.push(&entry);
+    }
+    let _ = dict.insert("zones", zones);
+    let _ = dict.insert("meta", meta_to_dict(&snapshot.meta));
+
     dict
 }
 
+fn meta_to_dict(meta: &SnapshotMeta) -> Dictionary {
+    let mut entry = Dictionary::new();
+    let _ = entry.insert("version", meta.version as i64);
+    let _ = entry.insert("captured_at", meta.captured_at.clone().unwrap_or_default());
+    let _ = entry.insert("world_hash", meta.world_hash.clone());
+    let _ = entry.insert("epoch", meta.epoch as i64);
+    let _ = entry.insert("season", meta.season.clone());
+    // Godot has no unsigned 64-bit ints; -1 marks an unknown seed.
+    let _ = entry.insert("seed", meta.seed.map(|seed| seed as i64).unwrap_or(-1));
+
+    let rules = &meta.rules;
+    let mut rule_dict = Dictionary::new();
+    let _ = rule_dict.insert("max_move_radius", rules.max_move_radius as i64);
+    let _ = rule_dict.insert("scan_range", rules.scan_range as i64);
+    let _ = rule_dict.insert("harvest_range", rules.harvest_range as i64);
+    let _ = rule_dict.insert("harvest_per_action", rules.harvest_per_action as i64);
+    let _ = rule_dict.insert("zone_size", rules.zone_size as i64);
+    let _ = rule_dict.insert("default_max_age", rules.default_max_age as i64);
+    let _ = rule_dict.insert(
+        "max_qi_supply",
+        rules.max_qi_supply.map(|max| max as i64).unwrap_or(-1),
+    );
+    let _ = rule_dict.insert("thinking_cost", rules.thinking_cost as i64);
+    let _ = entry.insert("rules", rule_dict);
+    entry
+}
+
+fn agent_to_dict(agent: &AgentSnapshot) -> Dictionary {
+    let mut entry = Dictionary::new();
+    let _ = entry.insert("id", agent.id as i64);
+    let _ = entry.insert("name", agent.name.clone());
+    let mut inventory = Dictionary::new();
+    // Built-in items always appear, even at zero; registered ores only when held.
+    for item in ItemKind::ALL {
+        let _ = inventory.insert(item.label(), 0i64);
+        let _ = entry.insert(item.label(), 0i64);
+    }
+    for (item, amount) in agent.inventory.iter() {
+        let _ = inventory.insert(item.label(), amount as i64);
+        let _ = entry.insert(item.label(), amount as i64);
+    }
+    let _ = entry.insert("inventory", inventory);
+    let _ = entry.insert("alive", agent.alive);
+    let _ = entry.insert("hp", agent.hp as i64);
+    let _ = entry.insert("age", agent.age as i64);
+    let _ = entry.insert("position", position_to_vec3(agent.position));
+    let _ = entry.insert("max_age", agent.max_age as i64);
+    entry
+}
+
+fn trail_to_array(trail: &[AgentTrailPoint]) -> Array<Dictionary> {
+    let mut points = Array::<Dictionary>::new();
+    for point in trail {
+        let mut entry = Dictionary::new();
+        let _ = entry.insert("tick", point.tick as i64);
+        let _ = entry.insert("position", position_to_vec3(point.position));
+        let _ = entry.insert("alive", point.alive);
+        points.push(&entry);
+    }
+    points
+}
+
 fn position_to_vec3(pos: Position) -> Vector3 {
     Vector3::new(pos.x as f32, pos.y as f32, pos.z as f32)
 }
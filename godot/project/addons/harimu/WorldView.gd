@@ -5,7 +5,15 @@ const AGENT_COLOR = Color(0.3, 0.8, 1.0, 0.8)
 const ORE_QI_COLOR = Color(0.2, 1.0, 0.6, 0.8)
 const ORE_TRANSISTOR_COLOR = Color(1.0, 0.65, 0.25, 0.8)
 const STRUCTURE_COLOR = Color(0.9, 0.9, 0.9, 0.8)
+const BLOCK_COLORS = {
+	"dirt": Color(0.45, 0.3, 0.18, 1.0),
+	"stone": Color(0.5, 0.5, 0.55, 1.0),
+}
 const PLAY_INTERVAL = 0.6
+const ZONE_SIZE = 16
+const TRAIL_TICKS = 20
+const TRAIL_COLOR = Color(0.3, 0.8, 1.0, 0.35)
+const FOLLOW_OFFSET = Vector3(6, 8, 8)
 
 var snapshots = []
 var current_index = 0
@@ -14,6 +22,8 @@ var accum = 0.0
 var world_root
 var label
 var camera
+var provider
+var followed_id = -1
 
 func _ready():
 	world_root = Node3D.new()
@@ -26,7 +36,12 @@ func _ready():
 
 	_add_camera()
 	_add_light()
-	_add_ground()
+	if ClassDB.class_exists("WorldSnapshotProvider"):
+		provider = ClassDB.instantiate("WorldSnapshotProvider")
+		# In the tree so it can watch the snapshot file and emit signals.
+		add_child(provider)
+		provider.snapshot_updated.connect(_on_snapshot_updated)
+		provider.agent_died.connect(_on_agent_died)
 
 	snapshots = _load_snapshots()
 	if snapshots.size() == 0:
@@ -58,6 +73,8 @@ func _unhandled_input(event):
 				_advance(-1)
 			KEY_SPACE:
 				playing = not playing
+			KEY_F:
+				_cycle_follow()
 	if event is InputEventMouseButton:
 		if event.button_index == MOUSE_BUTTON_WHEEL_UP:
 			_zoom(-1)
@@ -67,6 +84,11 @@ func _unhandled_input(event):
 func _render_snapshot(snapshot):
 	for child in world_root.get_children():
 		child.queue_free()
+	var terrain = snapshot.get("terrain", [])
+	if terrain.size() == 0:
+		_add_ground()
+	else:
+		_render_terrain(terrain)
 	for node in snapshot.get("ore_nodes", []):
 		var pos = _v3(node.get("position", Vector3.ZERO))
 		var color = ORE_QI_COLOR
@@ -82,6 +104,106 @@ func _render_snapshot(snapshot):
 		var pos = _v3(agent.get("position", Vector3.ZERO))
 		_spawn_box(pos + Vector3(0, SCALE, 0), Vector3.ONE * (SCALE * 0.75), AGENT_COLOR, "agent")
 
+	for zone in snapshot.get("zones", []):
+		var zone_name = str(zone.get("name", ""))
+		if zone_name == "":
+			continue
+		var origin = zone.get("origin", Vector3.ZERO)
+		if typeof(origin) != TYPE_VECTOR3:
+			var z = zone.get("zone", {})
+			origin = Vector3(z.get("x", 0), z.get("y", 0), z.get("z", 0)) * ZONE_SIZE
+		var text = Label3D.new()
+		text.text = zone_name
+		text.billboard = BaseMaterial3D.BILLBOARD_ENABLED
+		text.position = origin + Vector3(ZONE_SIZE * 0.5, 2, ZONE_SIZE * 0.5)
+		world_root.add_child(text)
+
+# Chunks arrive as [block, count] runs over voxels, x-fastest then y then z.
+# Only blocks with air above them are drawn to keep the node count down.
+func _render_terrain(chunks):
+	var solid = {}
+	for chunk in chunks:
+		var origin = chunk.get("origin", Vector3.ZERO)
+		if typeof(origin) == TYPE_DICTIONARY:
+			origin = Vector3(origin.get("x", 0), origin.get("y", 0), origin.get("z", 0))
+		var size = int(chunk.get("size", 16))
+		var index = 0
+		for run in chunk.get("runs", []):
+			var block = str(run[0])
+			var count = int(run[1])
+			if block != "air":
+				for i in range(index, index + count):
+					var local = Vector3(i % size, (i / size) % size, i / (size * size))
+					solid[Vector3i(origin + local)] = block
+			index += count
+	for cell in solid:
+		if solid.has(cell + Vector3i.UP):
+			continue
+		var color = BLOCK_COLORS.get(solid[cell], STRUCTURE_COLOR)
+		_spawn_box(Vector3(cell), Vector3.ONE * 0.98, color, "block")
+
+# Append live snapshots as the simulation writes them; stay on the newest one unless the
+# user has scrubbed back.
+func _on_snapshot_updated(tick):
+	var at_end = snapshots.size() == 0 or current_index == snapshots.size() - 1
+	var snap = provider.load_snapshot()
+	if snap.size() == 0:
+		return
+	_check_desync(snap)
+	if snapshots.size() > 0 and int(snapshots[-1].get("tick", 0)) >= tick:
+		return
+	snapshots.append(snap)
+	if at_end:
+		_show_snapshot(snapshots.size() - 1)
+
+# A streamed snapshot for a tick we already hold must hash the same; anything else means
+# the viewer and simulation disagree about the world.
+func _check_desync(snap):
+	var tick = int(snap.get("tick", 0))
+	var world_hash = snap.get("meta", {}).get("world_hash", "")
+	for existing in snapshots:
+		if int(existing.get("tick", 0)) != tick:
+			continue
+		var known = existing.get("meta", {}).get("world_hash", "")
+		if known != "" and world_hash != "" and known != world_hash:
+			push_warning("Harimu desync at tick %d: %s != %s" % [tick, known.left(8), world_hash.left(8)])
+
+func _on_agent_died(id):
+	if id == followed_id:
+		followed_id = -1
+
+# F cycles the follow-cam through living agents and back to the free camera.
+func _cycle_follow():
+	if snapshots.size() == 0:
+		return
+	var ids = []
+	for agent in snapshots[current_index].get("agents", []):
+		if agent.get("alive", true):
+			ids.append(int(agent.get("id", -1)))
+	ids.sort()
+	var at = ids.find(followed_id)
+	followed_id = ids[at + 1] if at + 1 < ids.size() else -1
+	_show_snapshot(current_index)
+
+func _update_follow(snapshot):
+	if followed_id < 0:
+		return
+	var target = null
+	for agent in snapshot.get("agents", []):
+		if int(agent.get("id", -1)) == followed_id:
+			target = _v3(agent.get("position", Vector3.ZERO))
+	if target == null:
+		return
+	if provider != null:
+		var tick = int(snapshot.get("tick", 0))
+		for point in provider.get_agent_history(followed_id, TRAIL_TICKS):
+			if int(point.get("tick", 0)) > tick:
+				continue
+			var pos = _v3(point.get("position", Vector3.ZERO))
+			_spawn_box(pos + Vector3(0, SCALE, 0), Vector3.ONE * (SCALE * 0.3), TRAIL_COLOR, "trail")
+	camera.position = target + FOLLOW_OFFSET
+	camera.look_at(target, Vector3.UP)
+
 func _spawn_box(pos, size, color, kind):
 	var mesh = BoxMesh.new()
 	mesh.size = size
@@ -123,7 +245,8 @@ func _v3(value):
 
 func _load_snapshot():
 	if ClassDB.class_exists("WorldSnapshotProvider"):
-		var provider = ClassDB.instantiate("WorldSnapshotProvider")
+		if provider == null:
+			provider = ClassDB.instantiate("WorldSnapshotProvider")
 		if provider != null:
 			var snap = provider.load_snapshot()
 			if snap.size() > 0:
@@ -176,8 +299,13 @@ func _show_snapshot(index):
 		return
 	current_index = index
 	_render_snapshot(snapshots[current_index])
-	label.text = "Tick %s | snapshot %d/%d | agents %d | ore %d | structures %d | space=play/pause, arrows=seek" % [
+	_update_follow(snapshots[current_index])
+	var meta = snapshots[current_index].get("meta", {})
+	label.text = "Tick %s | %s, epoch %s | hash %s | snapshot %d/%d | agents %d | ore %d | structures %d | space=play/pause, arrows=seek, F=follow" % [
 		snapshots[current_index].get("tick", 0),
+		meta.get("season", "?"),
+		meta.get("epoch", 0),
+		str(meta.get("world_hash", "")).left(8),
 		current_index + 1,
 		snapshots.size(),
 		snapshots[current_index].get("agents", []).size(),
@@ -0,0 +1,54 @@
+use clap::Subcommand;
+use harimu::{AdminAction, AgentId, ControlRequest, Qi};
+
+use super::{PositionArg, run_control};
+
+/// Operator interventions on agents of the active run, applied before its next tick.
+#[derive(Subcommand)]
+pub enum AdminCommand {
+    /// Move an agent to any free cell, at no cost
+    Teleport {
+        #[arg(long = "agent", visible_alias = "agent-id")]
+        agent_id: AgentId,
+        /// Target position as x,y,z
+        #[arg(long)]
+        to: PositionArg,
+    },
+    /// Give an agent Qi
+    GrantQi {
+        #[arg(long = "agent", visible_alias = "agent-id")]
+        agent_id: AgentId,
+        #[arg(long)]
+        amount: Qi,
+    },
+    /// Kill an agent; its death is journaled with reason `Admin`
+    Kill {
+        #[arg(long = "agent", visible_alias = "agent-id")]
+        agent_id: AgentId,
+    },
+    /// Bring a dead agent back where it died, with full HP
+    Revive {
+        #[arg(long = "agent", visible_alias = "agent-id")]
+        agent_id: AgentId,
+    },
+}
+
+pub(super) fn run_admin(cmd: AdminCommand) -> Result<(), String> {
+    let (agent_id, action) = match cmd {
+        AdminCommand::Teleport { agent_id, to } => (agent_id, AdminAction::Teleport { to: to.0 }),
+        AdminCommand::GrantQi { agent_id, amount } => (agent_id, AdminAction::GrantQi { amount }),
+        AdminCommand::Kill { agent_id } => (agent_id, AdminAction::Kill),
+        AdminCommand::Revive { agent_id } => (agent_id, AdminAction::Revive),
+    };
+    run_control(ControlRequest::Admin { agent_id, action })
+}
+
+/// The intervention in a few words, e.g. `teleport to (5, 0, 5)`.
+pub(super) fn describe_admin_action(action: &AdminAction) -> String {
+    match action {
+        AdminAction::Teleport { to } => format!("teleport to ({}, {}, {})", to.x, to.y, to.z),
+        AdminAction::GrantQi { amount } => format!("grant {} Qi", amount),
+        AdminAction::Kill => "kill".into(),
+        AdminAction::Revive => "revive".into(),
+    }
+}
@@ -1,12 +1,32 @@
+use std::path::PathBuf;
 use std::str::FromStr;
 
-use clap::Subcommand;
-use harimu::agents::{self, VoteDirection};
+use clap::{ArgAction, Subcommand};
+use harimu::agents::{self, AgentProfile, VoteDirection};
+use harimu::templates;
+use harimu::{BrainConfig, BrainMode, LlmProvider, ScheduledAction, memory};
 
 #[derive(Subcommand)]
 pub enum AgentCommand {
     /// Create a new agent entry (hash ignored; address is generated)
-    Create,
+    Create {
+        /// Archetype name from the templates file (sets qi, max_age, role, goal, brain)
+        #[arg(long, value_name = "NAME")]
+        from_template: Option<String>,
+        /// Templates file to read archetypes from
+        #[arg(long, value_name = "PATH", default_value = harimu::DEFAULT_TEMPLATES_FILE)]
+        templates: PathBuf,
+        /// Give the agent a generated name (e.g. "Kavelin") unique among agents and companions
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        auto_name: bool,
+    },
+    /// Import agents from a CSV file (columns: name, qi, max_age, position, goal)
+    Import {
+        path: PathBuf,
+        /// Validate and print the agents without saving them
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        dry_run: bool,
+    },
     /// Show info for an agent
     Info { hash: String },
     /// List all agents
@@ -34,6 +54,77 @@ pub enum AgentCommand {
         #[arg(long, default_value_t = harimu::DEFAULT_MAX_AGENT_AGE)]
         max_age: u64,
     },
+    /// Have `start` inject an action for an agent at a tick, or every N ticks
+    Schedule {
+        #[arg(long)]
+        agent_id: String,
+        /// Action in `--action` syntax, e.g. scan or build:qi
+        #[arg(long, value_name = "ACTION")]
+        action: String,
+        /// Tick to take the action at (the first one when repeating)
+        #[arg(long, required_unless_present = "every")]
+        at: Option<u64>,
+        /// Repeat every this many ticks (from --at, else from tick N)
+        #[arg(long, value_name = "N")]
+        every: Option<u64>,
+    },
+    /// Set the brain `start` gives an agent, over the run's flags; unset options keep what is
+    /// stored
+    Brain {
+        hash: String,
+        #[arg(long, value_enum)]
+        mode: Option<BrainMode>,
+        #[arg(long, value_enum)]
+        provider: Option<LlmProvider>,
+        #[arg(long)]
+        model: Option<String>,
+        #[arg(long)]
+        host: Option<String>,
+        /// Environment variable holding this agent's API key
+        #[arg(long, value_name = "VAR")]
+        api_key_env: Option<String>,
+        #[arg(long)]
+        temperature: Option<f32>,
+        /// Goal written into the agent's prompt
+        #[arg(long)]
+        goal: Option<String>,
+        /// Candidate action in `--action` syntax (repeatable); replaces the stored list
+        #[arg(long = "action", value_name = "ACTION")]
+        actions: Vec<String>,
+        /// Forget the stored brain and goal first
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        reset: bool,
+    },
+    /// Set the goal written into an agent's prompt ("" restores the default goal)
+    SetGoal { hash: String, goal: String },
+    /// Show what an agent's brain remembers from earlier runs (`.harimu/memory/<agent>.json`)
+    Memory {
+        hash: String,
+        /// Forget it instead, so the next `start` begins from a blank memory
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        clear: bool,
+    },
+    /// Remove a scheduled action of an agent (numbered as in `agent info`), or all of them
+    Unschedule {
+        #[arg(long)]
+        agent_id: String,
+        #[arg(long, required_unless_present = "all")]
+        index: Option<usize>,
+        #[arg(long, conflicts_with = "index", default_value_t = false)]
+        all: bool,
+    },
+}
+
+impl AgentCommand {
+    /// Whether the command writes to the agent registry (refused in read-only mode).
+    pub(super) fn mutates(&self) -> bool {
+        match self {
+            AgentCommand::Info { .. } | AgentCommand::List => false,
+            AgentCommand::Import { dry_run, .. } => !dry_run,
+            AgentCommand::Memory { clear, .. } => *clear,
+            _ => true,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -58,24 +149,82 @@ pub(super) fn run_agent(cmd: AgentCommand) -> Result<(), String> {
     let mut store = agents::load().map_err(|e| e.to_string())?;
 
     match cmd {
-        AgentCommand::Create => {
-            let profile =
-                agents::create_agent(&mut store, String::new()).map_err(|e| e.to_string())?;
+        AgentCommand::Create {
+            from_template,
+            templates,
+            auto_name,
+        } => {
+            let mut profile = match from_template {
+                Some(name) => {
+                    let template = templates::find_template(&templates, &name)?;
+                    agents::create_agent_from_template(&mut store, &name, &template)?
+                }
+                None => agents::create_agent(&mut store, String::new())?,
+            };
+            if auto_name {
+                profile.name = Some(store.generate_name(&profile.id, 0));
+                store.agents.insert(profile.id.clone(), profile.clone());
+            }
             agents::save(&store).map_err(|e| e.to_string())?;
             println!(
-                "Created agent {} (qi={}, companions={})",
-                profile.id, profile.qi, profile.companions
+                "Created agent {} (qi={}, companions={}{})",
+                profile.id,
+                profile.qi,
+                profile.companions,
+                template_suffix(&profile)
             );
         }
+        AgentCommand::Import { path, dry_run } => {
+            let rows = agents::parse_import_csv(&path, &store)?;
+            if rows.is_empty() {
+                println!("No agents found in {}", path.display());
+                return Ok(());
+            }
+            for row in &rows {
+                let position = row
+                    .position
+                    .map(|p| format!("{},{},{}", p.x, p.y, p.z))
+                    .unwrap_or_else(|| "-".into());
+                if dry_run {
+                    println!(
+                        "Would import {} | qi={} | max_age={} | position={}",
+                        row.name, row.qi, row.max_age, position
+                    );
+                } else {
+                    let profile = agents::create_agent_from_import(&mut store, row)?;
+                    println!(
+                        "Imported {} as {} | qi={} | max_age={} | position={}",
+                        row.name, profile.id, profile.qi, profile.max_age, position
+                    );
+                }
+            }
+            if dry_run {
+                println!("Dry run: {} agent(s) validated, nothing saved", rows.len());
+            } else {
+                agents::save(&store).map_err(|e| e.to_string())?;
+                println!("Imported {} agent(s)", rows.len());
+            }
+        }
         AgentCommand::Info { hash } => {
             let profile = store
                 .agents
                 .get(&hash)
                 .ok_or_else(|| format!("agent {} not found", hash))?;
             println!(
-                "Agent {} | qi={} | companions={} | max_age={}",
-                profile.id, profile.qi, profile.companions, profile.max_age
+                "Agent {} | qi={} | companions={} | max_age={}{}",
+                profile.id,
+                profile.qi,
+                profile.companions,
+                profile.max_age,
+                template_suffix(profile)
             );
+            print_brain(profile);
+            if !profile.schedule.is_empty() {
+                println!("Schedule:");
+                for (idx, scheduled) in profile.schedule.iter().enumerate() {
+                    println!("  {}. {}", idx + 1, scheduled.describe());
+                }
+            }
         }
         AgentCommand::List => {
             if store.agents.is_empty() {
@@ -83,8 +232,13 @@ pub(super) fn run_agent(cmd: AgentCommand) -> Result<(), String> {
             } else {
                 for agent in store.agents.values() {
                     println!(
-                        "{} | qi={} | companions={} | max_age={}",
-                        agent.id, agent.qi, agent.companions, agent.max_age
+                        "{} | qi={} | companions={} | max_age={}{}{}",
+                        agent.id,
+                        agent.qi,
+                        agent.companions,
+                        agent.max_age,
+                        template_suffix(agent),
+                        last_run_suffix(agent)
                     );
                 }
             }
@@ -95,9 +249,9 @@ pub(super) fn run_agent(cmd: AgentCommand) -> Result<(), String> {
             println!("Removed agent {}", hash);
         }
         AgentCommand::Spawn { hash } => {
-            agents::spawn_companion(&mut store, &hash).map_err(|e| e.to_string())?;
+            let name = agents::spawn_companion(&mut store, &hash)?;
             agents::save(&store).map_err(|e| e.to_string())?;
-            println!("Spawned companion for agent {}", hash);
+            println!("Spawned companion {} for agent {}", name, hash);
         }
         AgentCommand::Vote {
             action_id,
@@ -124,6 +278,115 @@ pub(super) fn run_agent(cmd: AgentCommand) -> Result<(), String> {
                 amount, agent_id, profile.qi
             );
         }
+        AgentCommand::Schedule {
+            agent_id,
+            action,
+            at,
+            every,
+        } => {
+            let scheduled = ScheduledAction::new(&action, at, every)?;
+            let description = scheduled.describe();
+            agents::schedule_action(&mut store, &agent_id, scheduled)?;
+            agents::save(&store).map_err(|e| e.to_string())?;
+            println!(
+                "Scheduled for agent {} {} (from the next `start`)",
+                agent_id, description
+            );
+        }
+        AgentCommand::Unschedule {
+            agent_id, index, ..
+        } => {
+            let removed = agents::unschedule_action(&mut store, &agent_id, index)?;
+            agents::save(&store).map_err(|e| e.to_string())?;
+            for scheduled in &removed {
+                println!(
+                    "Unscheduled for agent {} {}",
+                    agent_id,
+                    scheduled.describe()
+                );
+            }
+            if removed.is_empty() {
+                println!("Agent {} has no scheduled actions", agent_id);
+            }
+        }
+        AgentCommand::Brain {
+            hash,
+            mode,
+            provider,
+            model,
+            host,
+            api_key_env,
+            temperature,
+            goal,
+            actions,
+            reset,
+        } => {
+            if reset && let Some(profile) = store.agents.get_mut(&hash) {
+                profile.brain = None;
+                profile.goal = None;
+            }
+            let brain = BrainConfig {
+                mode,
+                provider,
+                model,
+                host,
+                api_key_env,
+                temperature,
+                actions,
+            };
+            agents::configure_brain(&mut store, &hash, brain, goal)?;
+            agents::save(&store).map_err(|e| e.to_string())?;
+            println!("Agent {} brain updated (from the next `start`)", hash);
+            print_brain(&store.agents[&hash]);
+        }
+        AgentCommand::SetGoal { hash, goal } => {
+            agents::set_goal(&mut store, &hash, &goal)?;
+            agents::save(&store).map_err(|e| e.to_string())?;
+            match &store.agents[&hash].goal {
+                Some(goal) => println!(
+                    "Agent {} goal set to: {} (from the next `start`)",
+                    hash, goal
+                ),
+                None => println!(
+                    "Agent {} goal reset to the default: {}",
+                    hash,
+                    harimu::DEFAULT_AGENT_GOAL
+                ),
+            }
+        }
+        AgentCommand::Memory { hash, clear } => {
+            if clear {
+                if memory::clear_memory(&hash).map_err(|e| e.to_string())? {
+                    println!("Cleared the memory of agent {}", hash);
+                } else {
+                    println!("Agent {} has no stored memory", hash);
+                }
+                return Ok(());
+            }
+            match memory::load_memory(&hash).map_err(|e| e.to_string())? {
+                Some(stored) if !stored.notes.is_empty() => {
+                    println!(
+                        "Agent {} remembers {} note(s) ({}):",
+                        hash,
+                        stored.notes.len(),
+                        memory::memory_path(&hash).display()
+                    );
+                    for note in &stored.notes {
+                        println!("  {}", note);
+                    }
+                    if !stored.archive.is_empty() {
+                        let embedded = stored.archive.iter().filter(|n| !n.vector.is_empty());
+                        println!(
+                            "  and {} archived note(s) for recall, {} embedded with {}",
+                            stored.archive.len(),
+                            embedded.count(),
+                            stored.embedding_model.as_deref().unwrap_or("no model yet")
+                        );
+                    }
+                }
+                _ => println!("Agent {} has no stored memory", hash),
+            }
+        }
         AgentCommand::ExtendLife { agent_id, max_age } => {
             agents::extend_life(&mut store, &agent_id, max_age).map_err(|e| e.to_string())?;
             agents::save(&store).map_err(|e| e.to_string())?;
@@ -137,3 +400,67 @@ pub(super) fn run_agent(cmd: AgentCommand) -> Result<(), String> {
 
     Ok(())
 }
+
+fn print_brain(profile: &AgentProfile) {
+    if let Some(goal) = &profile.goal {
+        println!("Goal: {}", goal);
+    }
+    let Some(brain) = &profile.brain else {
+        return;
+    };
+    let mut line = format!(
+        "Brain: mode={} | provider={} | model={} | host={}",
+        brain
+            .mode
+            .map(|m| format!("{:?}", m).to_lowercase())
+            .unwrap_or_else(|| "-".into()),
+        brain
+            .provider
+            .map(|p| format!("{:?}", p).to_lowercase())
+            .unwrap_or_else(|| "-".into()),
+        brain.model.as_deref().unwrap_or("-"),
+        brain.host.as_deref().unwrap_or("-")
+    );
+    if let Some(var) = &brain.api_key_env {
+        line.push_str(&format!(" | key=${}", var));
+    }
+    if let Some(temperature) = brain.temperature {
+        line.push_str(&format!(" | temperature={}", temperature));
+    }
+    println!("{}", line);
+    if !brain.actions.is_empty() {
+        println!("Actions: {}", brain.actions.join(", "));
+    }
+}
+
+fn template_suffix(profile: &AgentProfile) -> String {
+    let mut parts = Vec::new();
+    if let Some(name) = &profile.name {
+        parts.push(format!("name={}", name));
+    }
+    if let Some(template) = &profile.template {
+        parts.push(format!("template={}", template));
+    }
+    if let Some(role) = &profile.role {
+        parts.push(format!("role={}", role));
+    }
+    parts.iter().map(|p| format!(" | {}", p)).collect()
+}
+
+/// What the agent's last run left it with, once a run has synced it.
+fn last_run_suffix(profile: &AgentProfile) -> String {
+    let mut parts = Vec::new();
+    if profile.transistors > 0 {
+        parts.push(format!("transistors={}", profile.transistors));
+    }
+    if profile.age > 0 {
+        parts.push(format!("age={}", profile.age));
+    }
+    if let Some(p) = profile.position {
+        parts.push(format!("position=({}, {}, {})", p.x, p.y, p.z));
+    }
+    if !profile.alive {
+        parts.push("dead".to_string());
+    }
+    parts.iter().map(|p| format!(" | {}", p)).collect()
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use harimu::{
+    Metric, QiPoint, action_counts, deaths_by_reason, for_each_tick_snapshot, load_action_stats,
+    load_epoch_summaries, read_journal_from,
+};
+use plotters::prelude::*;
+
+/// Size of the SVG plots, in pixels.
+const PLOT_SIZE: (u32, u32) = (960, 540);
+
+#[derive(Args, Clone)]
+pub struct AnalyzeArgs {
+    /// Analysis to run; repeat for several (default: all of them)
+    #[arg(long = "metric", value_enum)]
+    pub metrics: Vec<Metric>,
+    /// Directory for the reports: one CSV file and one SVG plot per metric
+    #[arg(long, default_value = "report")]
+    pub out: PathBuf,
+    /// Write only the CSV files
+    #[arg(long, default_value_t = false)]
+    pub no_plots: bool,
+}
+
+pub(super) fn run_analyze(args: AnalyzeArgs) -> Result<(), String> {
+    let mut metrics = if args.metrics.is_empty() {
+        Metric::ALL.to_vec()
+    } else {
+        args.metrics
+    };
+    metrics.sort();
+    metrics.dedup();
+    fs::create_dir_all(&args.out)
+        .map_err(|e| format!("failed to create {}: {}", args.out.display(), e))?;
+
+    for metric in metrics {
+        let csv_path = args.out.join(format!("{}.csv", metric.file_stem()));
+        let plot_path = args.out.join(format!("{}.svg", metric.file_stem()));
+        let rows = match metric {
+            Metric::QiOverTime => {
+                let mut points = Vec::new();
+                for_each_tick_snapshot(|snapshot| points.push(QiPoint::of(snapshot)))
+                    .map_err(|e| format!("failed to read tick snapshots: {}", e))?;
+                write_csv(&csv_path, &points)?;
+                if !args.no_plots {
+                    plot_qi(&plot_path, &points)?;
+                }
+                points.len()
+            }
+            Metric::DeathsByReason => {
+                let (entries, _) = read_journal_from(0).map_err(|e| e.to_string())?;
+                let summaries = load_epoch_summaries().map_err(|e| e.to_string())?;
+                let deaths: Vec<(String, u64)> =
+                    deaths_by_reason(&entries, &summaries).into_iter().collect();
+                write_counts(&csv_path, "reason", "deaths", &deaths)?;
+                if !args.no_plots {
+                    plot_counts(&plot_path, "Deaths by reason", "deaths", &deaths)?;
+                }
+                deaths.len()
+            }
+            Metric::Actions => {
+                let stats = load_action_stats().map_err(|e| e.to_string())?;
+                let actions: Vec<(String, u64)> = action_counts(&stats)
+                    .into_iter()
+                    .map(|(kind, count)| (kind.to_string(), count))
+                    .collect();
+                write_counts(&csv_path, "action", "count", &actions)?;
+                if !args.no_plots {
+                    plot_counts(&plot_path, "Actions by kind", "actions", &actions)?;
+                }
+                actions.len()
+            }
+        };
+        if args.no_plots {
+            println!("Wrote {} ({} rows)", csv_path.display(), rows);
+        } else {
+            println!(
+                "Wrote {} ({} rows) and {}",
+                csv_path.display(),
+                rows,
+                plot_path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn write_csv<T: serde::Serialize>(path: &Path, rows: &[T]) -> Result<(), String> {
+    let fail = |e: csv::Error| format!("failed to write {}: {}", path.display(), e);
+    let mut writer = csv::Writer::from_path(path).map_err(fail)?;
+    for row in rows {
+        writer.serialize(row).map_err(fail)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+fn write_counts(
+    path: &Path,
+    key: &str,
+    value: &str,
+    counts: &[(String, u64)],
+) -> Result<(), String> {
+    let fail = |e: csv::Error| format!("failed to write {}: {}", path.display(), e);
+    let mut writer = csv::Writer::from_path(path).map_err(fail)?;
+    writer.write_record([key, value]).map_err(fail)?;
+    for (name, count) in counts {
+        writer
+            .write_record([name.as_str(), &count.to_string()])
+            .map_err(fail)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+fn plot_qi(path: &Path, points: &[QiPoint]) -> Result<(), String> {
+    let fail = |e: String| format!("failed to plot {}: {}", path.display(), e);
+    let first = points.first().map_or(0, |p| p.tick);
+    let last = points.last().map_or(1, |p| p.tick).max(first + 1);
+    let top = points
+        .iter()
+        .map(|p| p.agent_qi.max(p.node_qi))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let root = SVGBackend::new(path, PLOT_SIZE).into_drawing_area();
+    root.fill(&WHITE).map_err(|e| fail(e.to_string()))?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Qi over time", ("sans-serif", 24))
+        .margin(16)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(first..last, 0..top + top / 10)
+        .map_err(|e| fail(e.to_string()))?;
+    chart
+        .configure_mesh()
+        .x_desc("tick")
+        .y_desc("Qi")
+        .draw()
+        .map_err(|e| fail(e.to_string()))?;
+    let line = |value: fn(&QiPoint) -> u64| -> Vec<(u64, u64)> {
+        points.iter().map(|p| (p.tick, value(p))).collect()
+    };
+    let lines = [
+        ("held by agents", BLUE, line(|p| p.agent_qi)),
+        ("left in Qi nodes", GREEN, line(|p| p.node_qi)),
+    ];
+    for (label, color, line) in lines {
+        chart
+            .draw_series(LineSeries::new(line, &color))
+            .map_err(|e| fail(e.to_string()))?
+            .label(label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(|e| fail(e.to_string()))?;
+    root.present().map_err(|e| fail(e.to_string()))
+}
+
+fn plot_counts(
+    path: &Path,
+    title: &str,
+    unit: &str,
+    counts: &[(String, u64)],
+) -> Result<(), String> {
+    let fail = |e: String| format!("failed to plot {}: {}", path.display(), e);
+    let top = counts
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let root = SVGBackend::new(path, PLOT_SIZE).into_drawing_area();
+    root.fill(&WHITE).map_err(|e| fail(e.to_string()))?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 24))
+        .margin(16)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(
+            (0..counts.len().max(1)).into_segmented(),
+            0..top + top / 10 + 1,
+        )
+        .map_err(|e| fail(e.to_string()))?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(counts.len().max(1))
+        .x_label_formatter(&|segment| match segment {
+            SegmentValue::CenterOf(i) => counts
+                .get(*i)
+                .map(|(name, _)| name.clone())
+                .unwrap_or_default(),
+            _ => String::new(),
+        })
+        .y_desc(unit)
+        .draw()
+        .map_err(|e| fail(e.to_string()))?;
+    chart
+        .draw_series(
+            Histogram::vertical(&chart)
+                .style(BLUE.filled())
+                .margin(8)
+                .data(counts.iter().enumerate().map(|(i, (_, count))| (i, *count))),
+        )
+        .map_err(|e| fail(e.to_string()))?;
+    root.present().map_err(|e| fail(e.to_string()))
+}
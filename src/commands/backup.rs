@@ -0,0 +1,101 @@
+use clap::Subcommand;
+use harimu::{
+    backup::{self, BackupInfo},
+    paths,
+    state::{self, Status},
+};
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// Archive the data directory into `.harimu-backups/`
+    Create {
+        /// Tag appended to the archive name, e.g. `before-experiment`
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// List archives, oldest first
+    List,
+    /// Replace the data directory with an archive (needs a stopped runtime)
+    Restore {
+        /// Archive file name or path, or `latest`
+        name: String,
+        /// Skip the safety backup of the current data taken before restoring
+        #[arg(long, default_value_t = false)]
+        no_safety_backup: bool,
+    },
+}
+
+impl BackupCommand {
+    /// Whether the command writes archives or the data directory (refused in read-only mode).
+    pub(super) fn mutates(&self) -> bool {
+        !matches!(self, BackupCommand::List)
+    }
+}
+
+/// Label of the backup `restore` takes of the data it is about to replace.
+const PRE_RESTORE_LABEL: &str = "pre-restore";
+
+pub(super) fn run_backup(cmd: BackupCommand) -> Result<(), String> {
+    match cmd {
+        BackupCommand::Create { label } => {
+            let created = backup::create_backup(label.as_deref()).map_err(|e| e.to_string())?;
+            println!(
+                "Backed up {} to {} ({})",
+                paths::data_dir().display(),
+                created.path.display(),
+                format_size(created.size)
+            );
+        }
+        BackupCommand::List => {
+            let backups = backup::list_backups().map_err(|e| e.to_string())?;
+            if backups.is_empty() {
+                println!("No backups in {}", backup::backups_dir().display());
+                return Ok(());
+            }
+            for info in &backups {
+                print_backup(info);
+            }
+        }
+        BackupCommand::Restore {
+            name,
+            no_safety_backup,
+        } => {
+            let running = state::load_state()
+                .map_err(|e| e.to_string())?
+                .is_some_and(|state| state.status == Status::Running);
+            if running {
+                return Err("the runtime is running; run `harimu stop` before restoring".into());
+            }
+            let target = backup::find_backup(&name).map_err(|e| e.to_string())?;
+            if !no_safety_backup && paths::data_dir().is_dir() {
+                let safety = backup::create_backup(Some(PRE_RESTORE_LABEL))
+                    .map_err(|e| format!("safety backup: {}", e))?;
+                println!("Saved the current data to {}", safety.name);
+            }
+            backup::restore_backup(&target).map_err(|e| e.to_string())?;
+            println!(
+                "Restored {} from {}",
+                paths::data_dir().display(),
+                target.name
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_backup(info: &BackupInfo) {
+    println!(
+        "{} | {} | label={}",
+        info.name,
+        format_size(info.size),
+        info.label().unwrap_or("-")
+    );
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KiB", b as f64 / (1 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
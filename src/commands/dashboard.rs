@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use clap::Args;
+use harimu::{DashboardFeed, ItemKind, JournalEntry, Qi, WorldSnapshot};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Width of the ore level bars.
+const LEVEL_BAR: usize = 10;
+
+#[derive(Args, Clone)]
+pub struct DashboardArgs {
+    /// How often to re-read the snapshot and journal, in milliseconds
+    #[arg(long, default_value_t = 500)]
+    pub refresh_ms: u64,
+    /// Recent events to keep on screen
+    #[arg(long, default_value_t = 200)]
+    pub events: usize,
+}
+
+pub(super) fn run_dashboard(args: DashboardArgs) -> Result<(), String> {
+    let mut feed = DashboardFeed::new(args.events);
+    feed.refresh();
+    let mut terminal = ratatui::try_init().map_err(|e| format!("terminal: {}", e))?;
+    let result = run_ui(
+        &mut terminal,
+        &mut feed,
+        Duration::from_millis(args.refresh_ms.max(50)),
+    );
+    ratatui::restore();
+    result.map_err(|e| format!("terminal: {}", e))
+}
+
+fn run_ui(
+    terminal: &mut DefaultTerminal,
+    feed: &mut DashboardFeed,
+    refresh: Duration,
+) -> std::io::Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, feed))?;
+        if event::poll(refresh)? {
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    return Ok(());
+                }
+            }
+            continue;
+        }
+        feed.refresh();
+    }
+}
+
+fn draw(frame: &mut Frame, feed: &DashboardFeed) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [tables, events] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(body);
+    let [agents, ores] =
+        Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(tables);
+
+    draw_header(frame, header, feed);
+    match feed.snapshot() {
+        Some(snapshot) => {
+            draw_agents(frame, agents, snapshot);
+            draw_ores(frame, ores, snapshot);
+        }
+        None => frame.render_widget(
+            Paragraph::new("No world snapshot yet; start a run with `harimu start`")
+                .block(Block::bordered().title(" World ")),
+            tables,
+        ),
+    }
+    draw_events(frame, events, feed.recent_events());
+
+    let footer_text = match feed.error() {
+        Some(err) => Line::from(format!(" read failed, showing last reading: {}", err)).red(),
+        None => Line::from(" q quit").dim(),
+    };
+    frame.render_widget(Paragraph::new(footer_text), footer);
+}
+
+fn draw_header(frame: &mut Frame, area: Rect, feed: &DashboardFeed) {
+    let status = match feed.state() {
+        Some(state) if feed.is_running() => Span::styled(
+            format!("{:?}", state.status),
+            Style::new().fg(Color::Green).add_modifier(Modifier::BOLD),
+        ),
+        Some(state) => Span::styled(
+            format!("{:?}", state.status),
+            Style::new().fg(Color::Yellow),
+        ),
+        None => Span::raw("not initialized"),
+    };
+    let tick = feed
+        .tick()
+        .map_or_else(|| "-".to_string(), |tick| tick.to_string());
+    let rate = match feed.tick_rate() {
+        Some(rate) => format!("{:.2} ticks/s", rate),
+        None => "idle".to_string(),
+    };
+    let mut spans = vec![status, Span::raw(format!(" | tick {} | {}", tick, rate))];
+    if let Some(snapshot) = feed.snapshot() {
+        let alive = snapshot.agents.iter().filter(|a| a.alive).count();
+        spans.push(Span::raw(format!(
+            " | epoch {} ({}) | agents {}/{} alive | structures {}",
+            snapshot.meta.epoch,
+            snapshot.meta.season,
+            alive,
+            snapshot.agents.len(),
+            snapshot.structures.len()
+        )));
+    }
+    if let Some(message) = feed.state().and_then(|s| s.message.as_deref()) {
+        spans.push(Span::raw(format!(" | {}", message)).dim());
+    }
+    frame.render_widget(
+        Paragraph::new(Line::from(spans)).block(Block::bordered().title(" harimu ")),
+        area,
+    );
+}
+
+fn draw_agents(frame: &mut Frame, area: Rect, snapshot: &WorldSnapshot) {
+    let rows = snapshot.agents.iter().map(|agent| {
+        let row = Row::new(vec![
+            agent.id.to_string(),
+            agent.name.clone(),
+            agent.inventory.get(ItemKind::Qi).to_string(),
+            agent.hp.to_string(),
+            format!("{}/{}", agent.age, agent.max_age),
+            format!(
+                "({}, {}, {})",
+                agent.position.x, agent.position.y, agent.position.z
+            ),
+        ]);
+        if agent.alive { row } else { row.dim() }
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(5),
+            Constraint::Min(10),
+            Constraint::Length(7),
+            Constraint::Length(4),
+            Constraint::Length(11),
+            Constraint::Length(16),
+        ],
+    )
+    .header(Row::new(["id", "name", "qi", "hp", "age", "position"]).bold())
+    .block(Block::bordered().title(" Agents "));
+    frame.render_widget(table, area);
+}
+
+fn draw_ores(frame: &mut Frame, area: Rect, snapshot: &WorldSnapshot) {
+    let rows = snapshot.ore_nodes.iter().map(|node| {
+        Row::new(vec![
+            node.id.to_string(),
+            node.ore.to_string(),
+            format!(
+                "({}, {}, {})",
+                node.position.x, node.position.y, node.position.z
+            ),
+            format!(
+                "{} {}/{}",
+                level_bar(node.available, node.capacity),
+                node.available,
+                node.capacity
+            ),
+            format!("+{}/tick", node.recharge_per_tick),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(5),
+            Constraint::Length(11),
+            Constraint::Length(16),
+            Constraint::Min(LEVEL_BAR as u16 + 10),
+            Constraint::Length(10),
+        ],
+    )
+    .header(Row::new(["id", "ore", "position", "level", "recharge"]).bold())
+    .block(Block::bordered().title(" Ore nodes "));
+    frame.render_widget(table, area);
+}
+
+fn level_bar(available: Qi, capacity: Qi) -> String {
+    let filled = if capacity == 0 {
+        0
+    } else {
+        available.min(capacity) as usize * LEVEL_BAR / capacity as usize
+    };
+    format!("{}{}", "█".repeat(filled), "░".repeat(LEVEL_BAR - filled))
+}
+
+fn draw_events<'a>(frame: &mut Frame, area: Rect, events: impl Iterator<Item = &'a JournalEntry>) {
+    let height = area.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = events
+        .take(height)
+        .map(|entry| {
+            let fields = entry
+                .event
+                .as_object()
+                .and_then(|fields| fields.values().next())
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{:>6} ", entry.tick)).dim(),
+                Span::raw(entry.kind().to_string()).bold(),
+                Span::raw(format!(" {}", fields)),
+            ]))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" Recent events ")),
+        area,
+    );
+}
@@ -0,0 +1,170 @@
+use std::thread;
+use std::time::Duration;
+
+use clap::Subcommand;
+use harimu::{
+    EPOCH_TICKS, EpochSummary, JournalEntry, compact_journal, journal_file_path,
+    journal_summary_file_path, load_epoch_summaries, read_journal_from,
+    state::{self, Status},
+    tail_journal,
+};
+
+/// How often `events tail --follow` checks the journal for new lines.
+const FOLLOW_POLL: Duration = Duration::from_millis(500);
+
+#[derive(Subcommand)]
+pub enum EventsCommand {
+    /// Print the most recent events from `.harimu/events.jsonl`
+    Tail {
+        /// Number of events to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Keep printing events as new ticks are journaled (Ctrl-C to stop)
+        #[arg(short = 'f', long, default_value_t = false)]
+        follow: bool,
+    },
+    /// Roll old epochs of the journal into per-epoch summaries in `.harimu/events_summary.json`
+    Compact {
+        /// Complete epochs to leave raw besides the current one
+        #[arg(long, default_value_t = 1)]
+        keep_epochs: u64,
+        /// Also delete the summarized events from the journal (needs a stopped runtime)
+        #[arg(long, default_value_t = false)]
+        prune: bool,
+    },
+    /// Print the per-epoch summaries written by `events compact`
+    Summary {
+        /// Only show this epoch
+        #[arg(long)]
+        epoch: Option<u64>,
+    },
+}
+
+impl EventsCommand {
+    /// Whether the command rewrites the journal (refused in read-only mode).
+    pub(super) fn mutates(&self) -> bool {
+        matches!(self, EventsCommand::Compact { .. })
+    }
+}
+
+pub(super) fn run_events(cmd: EventsCommand) -> Result<(), String> {
+    match cmd {
+        EventsCommand::Tail { lines, follow } => {
+            let entries = tail_journal(lines).map_err(|e| e.to_string())?;
+            if entries.is_empty() && !follow {
+                println!(
+                    "No events journaled yet at {}",
+                    journal_file_path().display()
+                );
+                return Ok(());
+            }
+            for entry in &entries {
+                print_entry(entry);
+            }
+            if !follow {
+                return Ok(());
+            }
+
+            let (_, mut offset) = read_journal_from(0).map_err(|e| e.to_string())?;
+            loop {
+                thread::sleep(FOLLOW_POLL);
+                let (entries, next) = read_journal_from(offset).map_err(|e| e.to_string())?;
+                // The journal was deleted or replaced; start over from its beginning.
+                if next < offset {
+                    offset = 0;
+                    continue;
+                }
+                for entry in &entries {
+                    print_entry(entry);
+                }
+                offset = next;
+            }
+        }
+        EventsCommand::Compact { keep_epochs, prune } => run_compact(keep_epochs, prune),
+        EventsCommand::Summary { epoch } => run_summary(epoch),
+    }
+}
+
+fn run_compact(keep_epochs: u64, prune: bool) -> Result<(), String> {
+    if prune {
+        let running = state::load_state()
+            .map_err(|e| e.to_string())?
+            .is_some_and(|state| state.status == Status::Running);
+        if running {
+            return Err("the runtime is still journaling; run `harimu stop` before --prune".into());
+        }
+    }
+    let report = compact_journal(keep_epochs, prune).map_err(|e| e.to_string())?;
+    if report.epochs == 0 {
+        println!("Nothing to compact: the journal holds no epoch old enough");
+        return Ok(());
+    }
+    println!(
+        "Summarized {} event(s) from {} epoch(s) into {}",
+        report.events,
+        report.epochs,
+        journal_summary_file_path().display()
+    );
+    if report.pruned {
+        println!("Removed them from {}", journal_file_path().display());
+    }
+    Ok(())
+}
+
+fn run_summary(epoch: Option<u64>) -> Result<(), String> {
+    let summaries: Vec<EpochSummary> = load_epoch_summaries()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|summary| epoch.is_none_or(|e| summary.epoch == e))
+        .collect();
+    if summaries.is_empty() {
+        println!("No epoch summaries yet; run `harimu events compact`");
+        return Ok(());
+    }
+    for summary in &summaries {
+        println!(
+            "Epoch {} (ticks {}-{}, {} ticks/epoch): {} events{}",
+            summary.epoch,
+            summary.first_tick,
+            summary.last_tick,
+            EPOCH_TICKS,
+            summary.events,
+            if summary.pruned { " [raw pruned]" } else { "" }
+        );
+        for (kind, count) in &summary.by_kind {
+            match summary.amounts.get(kind) {
+                Some(amount) => println!("   {:<20} {:>8}  amount={}", kind, count, amount),
+                None => println!("   {:<20} {:>8}", kind, count),
+            }
+        }
+        let busiest = summary
+            .by_agent
+            .iter()
+            .max_by_key(|(id, count)| (**count, std::cmp::Reverse(**id)));
+        if let Some((agent_id, count)) = busiest {
+            println!(
+                "   {} agent(s) active; busiest agent {} with {} events",
+                summary.by_agent.len(),
+                agent_id,
+                count
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_entry(entry: &JournalEntry) {
+    let fields = entry
+        .event
+        .as_object()
+        .and_then(|fields| fields.values().next())
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    println!(
+        "[tick {}] {} {} {}",
+        entry.tick,
+        entry.timestamp,
+        entry.kind(),
+        fields
+    );
+}
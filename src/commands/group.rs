@@ -0,0 +1,98 @@
+use std::fmt;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use harimu::{ActionArg, BrainMode, Position};
+
+use super::{PositionArg, brain_to_arg, render_action_arg};
+
+/// Agents `start` runs together with their own overrides, given as
+/// `NAME:agents=a,b;position=x,y,z;brain=loop|llm;model=M;action=A;action=B`. Only `agents` is
+/// required; `action` may repeat.
+#[derive(Clone, Debug)]
+pub struct GroupArg {
+    pub name: String,
+    pub agents: Vec<String>,
+    /// Where the group's agents spawn, unless the world already holds them.
+    pub position: Option<Position>,
+    pub brain: Option<BrainMode>,
+    pub model: Option<String>,
+    /// Candidate actions in place of the run's `--action`s or scenario phase.
+    pub actions: Vec<ActionArg>,
+}
+
+impl FromStr for GroupArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, spec) = s.split_once(':').ok_or(
+            "a group is NAME:agents=a,b[;position=x,y,z][;brain=..][;model=..][;action=..]",
+        )?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("a group needs a name".into());
+        }
+        let mut group = GroupArg {
+            name: name.to_string(),
+            agents: Vec::new(),
+            position: None,
+            brain: None,
+            model: None,
+            actions: Vec::new(),
+        };
+        for part in spec.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("group {}: expected key=value, got '{}'", name, part))?;
+            let value = value.trim();
+            match key.trim() {
+                "agents" => group.agents.extend(
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|a| !a.is_empty())
+                        .map(String::from),
+                ),
+                "position" => group.position = Some(PositionArg::from_str(value)?.0),
+                "brain" => {
+                    group.brain = Some(
+                        BrainMode::from_str(value, true)
+                            .map_err(|_| format!("group {}: brain must be loop or llm", name))?,
+                    )
+                }
+                "model" => group.model = Some(value.to_string()),
+                "action" => group.actions.push(ActionArg::from_str(value)?),
+                other => {
+                    return Err(format!(
+                        "group {}: unknown key '{}' (use agents, position, brain, model, action)",
+                        name, other
+                    ));
+                }
+            }
+        }
+        if group.agents.is_empty() {
+            return Err(format!("group {} names no agents", name));
+        }
+        Ok(group)
+    }
+}
+
+impl fmt::Display for GroupArg {
+    /// The spec `FromStr` reads back, for the background child's arguments.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:agents={}", self.name, self.agents.join(","))?;
+        if let Some(p) = self.position {
+            write!(f, ";position={},{},{}", p.x, p.y, p.z)?;
+        }
+        if let Some(brain) = self.brain {
+            write!(f, ";brain={}", brain_to_arg(brain))?;
+        }
+        if let Some(model) = &self.model {
+            write!(f, ";model={}", model)?;
+        }
+        for action in &self.actions {
+            write!(f, ";action={}", render_action_arg(action))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use clap::Args;
+use harimu::{QuotaLimits, grpc::WorldControlService, is_read_only};
+
+use super::serve::open_api;
+
+#[derive(Args, Clone)]
+pub struct GrpcServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+    /// Port to listen on
+    #[arg(long, default_value_t = 50051)]
+    pub port: u16,
+    /// Most actions one client may queue per tick
+    #[arg(long, value_name = "N")]
+    pub quota_per_tick: Option<u32>,
+    /// Most actions one client may queue per minute
+    #[arg(long, value_name = "N")]
+    pub quota_per_minute: Option<u32>,
+}
+
+pub(super) fn run_grpc_serve(args: GrpcServeArgs) -> Result<(), String> {
+    let (api, stream) = open_api(
+        QuotaLimits {
+            per_tick: args.quota_per_tick,
+            per_minute: args.quota_per_minute,
+        },
+        "serving the gRPC API",
+    )?;
+    let address = format!("{}:{}", args.host, args.port);
+    let socket = address
+        .parse()
+        .map_err(|e| format!("invalid address {}: {}", address, e))?;
+    println!(
+        "Serving world at tick {} over gRPC on {}{}",
+        api.vm().world().tick(),
+        address,
+        if is_read_only() { " (read-only)" } else { "" }
+    );
+    let service = WorldControlService::new(api, stream);
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| format!("tokio runtime: {}", e))?;
+    runtime
+        .block_on(
+            tonic::transport::Server::builder()
+                .add_service(service.into_server())
+                .serve(socket),
+        )
+        .map_err(|e| format!("gRPC server on {}: {}", address, e))
+}
@@ -5,25 +5,63 @@ use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
 
-use clap::{ArgAction, Parser, Subcommand};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand};
 use harimu::{
-    Action, ActionArg, ActionRequest, AgentId, BrainMemory, BrainMode, Event, LlmClient,
-    LlmProvider, OreKind, Position, StructureKind, StructureRecord, TickResult, Vm, agents,
-    load_structure_store, plan_with_llm, record_successful_actions, reset_action_stats,
-    save_action_stats, save_structure_store, save_world_snapshot, save_world_snapshot_tick,
+    AUTO_BACKUP_LABEL, Action, ActionArg, ActionRequest, Agenda, AgentId, AgentProfile,
+    BackupSchedule, BrainConfig, BrainMemory, BrainMode, BrainSwap, ControlRequest, ControlServer,
+    DaemonStatus, DecisionRecord, EPOCH_TICKS, EffectiveConfig, Event, Hibernation, ItemKind,
+    LlmBudget, LlmClient, LlmProvider, LlmUsage, LogFormat, OreKind, PhasePlan, PlanJob, Position,
+    Program, RecipeBook, RunSeeds, RunStore, RunSummary, STATS_FLUSH_TICKS, Sandbox,
+    SnapshotFormat, StartSettings, Starvation, StatsRecorder, StoreBackend, StructureKind,
+    StructurePersister, Terrain, TickMetrics, TickPacer, TickResult, Vm, World, agents,
+    append_replay_tick, append_tick, append_tick_metrics, clear_world_state, control_socket_path,
+    decisions, ensure_writable, finish_run, is_read_only, load_memory, load_store_config,
+    load_timeline, load_world_state, load_zone_store, lock_data_dir, logging, open_backend, ore,
+    page_zones, paths, plan_many_with_llm, rejection_constraints, reset_action_stats,
+    restore_all_zones, runs, save_memory, save_store_config, save_territory_store,
+    save_world_snapshot, save_world_snapshot_tick, save_world_state, send_control, set_read_only,
+    set_snapshot_format, start_replay_log,
     state::{self, Status},
     world::WorldQueries,
+    world_state_file_path,
 };
+use tracing::warn;
 
+mod admin;
 mod agent;
+mod analyze;
+mod backup;
+mod dashboard;
+mod events;
+mod group;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod replay;
+mod run_registry;
+mod serve;
+mod stats;
+mod token;
+mod top;
 mod wallet;
 mod world;
 
-use agent::{run_agent, AgentCommand};
-use wallet::{run_wallet, run_wallet_mine, WalletCommand};
-use world::{run_world, WorldCommand};
-
-const PID_FILE: &str = ".harimu/runtime.pid";
+use admin::{AdminCommand, describe_admin_action, run_admin};
+use agent::{AgentCommand, run_agent};
+use analyze::{AnalyzeArgs, run_analyze};
+use backup::{BackupCommand, run_backup};
+use dashboard::{DashboardArgs, run_dashboard};
+use events::{EventsCommand, run_events};
+use group::GroupArg;
+#[cfg(feature = "grpc")]
+use grpc::{GrpcServeArgs, run_grpc_serve};
+use replay::{ReplayArgs, run_replay};
+use run_registry::{RunsCommand, run_runs};
+use serve::{ServeArgs, run_serve};
+use stats::{StatsCommand, run_stats};
+use token::{TokenCommand, run_token};
+use top::{TopArgs, run_top};
+use wallet::{WalletCommand, run_wallet, run_wallet_mine};
+use world::{WorldCommand, run_world};
 
 #[derive(Parser)]
 #[command(
@@ -33,6 +71,23 @@ const PID_FILE: &str = ".harimu/runtime.pid";
     long_about = None
 )]
 pub struct Cli {
+    /// Refuse every command that would modify the data directory (also HARIMU_READ_ONLY=1 or
+    /// `read_only = true` in .harimu/config.toml)
+    #[arg(long, global = true, default_value_t = false)]
+    pub read_only: bool,
+    /// Data directory (default: $HARIMU_HOME, else ./.harimu if present, else
+    /// $XDG_DATA_HOME/harimu)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+    /// Use the named session's own state, world, snapshots, stats and control socket under
+    /// <data dir>/sessions/<NAME>, so several runs can go side by side. A new session starts
+    /// from a copy of the agent, wallet and Qi source registries
+    #[arg(long, global = true, value_name = "NAME")]
+    pub session: Option<String>,
+    /// Format of the logs on stderr and in logs/llm.log. Choose what reaches stderr with
+    /// HARIMU_LOG (default `warn`, e.g. `HARIMU_LOG=info`)
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
     #[command(subcommand)]
     pub command: Command,
 }
@@ -40,64 +95,54 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Command {
     /// Initialize local Harimu state
-    Init,
+    Init {
+        /// Storage backend for the wallet, agent and world stores (saved to .harimu/config.toml)
+        #[arg(long, value_enum)]
+        store: Option<StoreBackend>,
+        /// Encoding for world snapshots (saved to .harimu/config.toml)
+        #[arg(long, value_enum)]
+        snapshot_format: Option<SnapshotFormat>,
+    },
     /// Start an agent loop for continuous or bounded ticks
-    Start {
-        /// Agent address (defaults to first registered agent)
+    Start(Box<StartArgs>),
+    /// Show runtime status
+    Status,
+    /// Watch the world in a terminal UI: agents, ore nodes, recent events and tick rate
+    Dashboard(DashboardArgs),
+    /// Live per-agent Qi, age and latest event and per-zone activity, repainted like `top`
+    Top(TopArgs),
+    /// Stop the running `start` after its current tick (or mark the runtime stopped)
+    Stop,
+    /// Pause the running `start` after its current tick
+    Pause,
+    /// Resume a paused run
+    Resume,
+    /// Have an agent of the running `start` take an action on its next tick
+    Inject {
+        /// Agent id in the running world
         #[arg(long)]
-        agent: Option<String>,
-        /// Starting Qi (used if agent is not already in runtime)
-        #[arg(long, default_value_t = 3)]
-        qi: harimu::Qi,
-        /// Starting position as x,y,z (default: 0,0,0)
-        #[arg(short = 'p', long, default_value = "0,0,0")]
-        position: PositionArg,
-        /// Number of ticks to run (omit for continuous)
-        #[arg(short = 't', long)]
-        ticks: Option<u64>,
-        /// Decision driver: loop (deterministic) or llm (mocked planner)
-        #[arg(long, default_value_t = BrainMode::Llm, value_enum)]
-        brain: BrainMode,
-        /// LLM host/base URL (default OpenAI endpoint)
-        #[arg(long, default_value = "https://api.openai.com")]
-        llm_host: String,
-        /// Model name (e.g., gpt-5-nano, gpt-4o-mini, glm-4.6:cloud). Interpreted by the selected provider.
-        #[arg(long, default_value = "gpt-5-nano")]
-        llm_model: String,
-        /// LLM timeout in ms
-        #[arg(long, default_value_t = 15_000)]
-        llm_timeout_ms: u64,
-        /// LLM provider: openai (default; OpenAI-style /v1/chat/completions) or ollama (local /api/chat)
-        #[arg(long, default_value_t = LlmProvider::Openai, value_enum)]
-        llm_provider: LlmProvider,
-        /// API key for OpenAI-compatible providers (also reads LLM_API_KEY env var)
+        agent_id: AgentId,
+        /// Action in `--action` syntax, e.g. move:1,0,0
+        #[arg(long, value_name = "ACTION")]
+        action: ActionArg,
+    },
+    /// Switch an agent of the running `start` to another brain or LLM model from its next tick
+    Brain {
+        /// Agent id in the running world
         #[arg(long)]
-        llm_api_key: Option<String>,
-        /// Desired tick rate (ticks per second). If set, overrides delay-ms.
+        agent_id: AgentId,
+        /// Brain to switch to
+        #[arg(long, value_enum, required_unless_present = "model")]
+        mode: Option<BrainMode>,
+        /// LLM model to ask from now on (with the run's LLM host and provider)
         #[arg(long)]
-        tick_rate: Option<f64>,
-        /// Delay between ticks in milliseconds
-        #[arg(
-            short = 'd',
-            long,
-            default_value_t = 0,
-            help = "Delay between ticks in ms (used when --tick-rate is not set; default pacing falls back to 1 tick/sec)"
-        )]
-        delay_ms: u64,
-        /// Action (repeatable). Formats: scan | idle | move:<dx>,<dy>,<dz>. Defaults to a simple loop if omitted.
-        #[arg(short = 'a', long = "action", value_name = "ACTION")]
-        actions: Vec<ActionArg>,
-        /// Run in the foreground (default is background)
-        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
-        foreground: bool,
-        /// Internal flag for background child process (do not use directly)
-        #[arg(long, hide = true, default_value_t = false)]
-        background_child: bool,
+        model: Option<String>,
+    },
+    /// Teleport, grant Qi to, kill or revive an agent of the running `start` before its next tick
+    Admin {
+        #[command(subcommand)]
+        command: AdminCommand,
     },
-    /// Show runtime status
-    Status,
-    /// Mark the runtime as stopped
-    Stop,
     /// Agent registry operations
     Agent {
         #[command(subcommand)]
@@ -113,6 +158,40 @@ pub enum Command {
         #[command(subcommand)]
         command: WorldCommand,
     },
+    /// Re-run a recorded run from its replay log and check every tick against it
+    Replay(ReplayArgs),
+    /// Compute reports from the journal, stats and tick snapshots: CSV files and SVG plots
+    Analyze(AnalyzeArgs),
+    /// Recorded runs: when they ran, with which flags, brain and seeds, and how they ended
+    Runs {
+        #[command(subcommand)]
+        command: RunsCommand,
+    },
+    /// Recorded world metrics and LLM usage
+    Stats {
+        #[command(subcommand)]
+        command: StatsCommand,
+    },
+    /// Read the event journal written by `start`
+    Events {
+        #[command(subcommand)]
+        command: EventsCommand,
+    },
+    /// Archive and restore the data directory
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Serve the saved world over HTTP (snapshot, agents, events, wallets) and tick it on request
+    Serve(ServeArgs),
+    /// Serve the saved world over gRPC (see proto/harimu.proto) for non-Rust orchestrators
+    #[cfg(feature = "grpc")]
+    GrpcServe(GrpcServeArgs),
+    /// Access tokens and scopes for the server APIs
+    Token {
+        #[command(subcommand)]
+        command: TokenCommand,
+    },
     /// Mine Qi into a wallet using PoW
     Mine {
         /// Optional wallet address (defaults to first wallet)
@@ -130,6 +209,205 @@ pub enum Command {
     },
 }
 
+#[derive(Args, Clone)]
+pub struct StartArgs {
+    /// Agent address (defaults to every registered agent)
+    #[arg(long)]
+    pub agent: Option<String>,
+    /// Run only these agents: comma-separated addresses
+    #[arg(long, value_delimiter = ',', conflicts_with = "agent")]
+    pub agents: Vec<String>,
+    /// Run a group of agents with its own position, brain, model and actions (repeatable):
+    /// NAME:agents=a,b[;position=x,y,z][;brain=loop|llm][;model=M][;action=A]...
+    #[arg(long = "group", value_name = "SPEC")]
+    pub groups: Vec<GroupArg>,
+    /// Starting Qi (used if agent is not already in runtime)
+    #[arg(long, default_value_t = 3)]
+    pub qi: harimu::Qi,
+    /// Starting position as x,y,z (default: 0,0,0)
+    #[arg(short = 'p', long, default_value = "0,0,0")]
+    pub position: PositionArg,
+    /// Starting position of one agent as ADDR=x,y,z (repeatable); overrides --position,
+    /// --group positions and the agent's stored position
+    #[arg(long = "position-for", value_name = "ADDR=X,Y,Z")]
+    pub positions_for: Vec<AgentPositionArg>,
+    /// Spawn agents without a stored or explicit position up to this many blocks from
+    /// --position along x and z, drawn from the world seed (0 = all at --position)
+    #[arg(long, default_value_t = 0)]
+    pub scatter: u32,
+    /// Number of ticks to run (omit for continuous)
+    #[arg(short = 't', long)]
+    pub ticks: Option<u64>,
+    /// Decision driver: loop (deterministic) or llm (default; mocked planner)
+    #[arg(long, value_enum)]
+    pub brain: Option<BrainMode>,
+    /// LLM host/base URL (default OpenAI endpoint; Google's for --llm-provider gemini)
+    #[arg(long)]
+    pub llm_host: Option<String>,
+    /// Model name (default gpt-5-nano; e.g., gpt-4o-mini, glm-4.6:cloud). Interpreted by the selected provider.
+    #[arg(long)]
+    pub llm_model: Option<String>,
+    /// LLM timeout in ms (default 15000)
+    #[arg(long)]
+    pub llm_timeout_ms: Option<u64>,
+    /// LLM provider: openai (default; OpenAI-style /v1/chat/completions), ollama (local /api/chat), gemini (generateContent) or llama (in-process GGUF at --llm-model)
+    #[arg(long, value_enum)]
+    pub llm_provider: Option<LlmProvider>,
+    /// API key for OpenAI-compatible providers (also reads LLM_API_KEY env var)
+    #[arg(long)]
+    pub llm_api_key: Option<String>,
+    /// Desired tick rate (ticks per second; default 1)
+    #[arg(long)]
+    pub tick_rate: Option<f64>,
+    /// Delay between ticks in milliseconds
+    #[arg(
+        short = 'd',
+        long,
+        help = "Delay between ticks in ms (not with --tick-rate; default pacing falls back to 1 tick/sec)"
+    )]
+    pub delay_ms: Option<u64>,
+    /// Action (repeatable). Formats: scan | idle | move:<dx>,<dy>,<dz>. Defaults to a simple loop if omitted.
+    #[arg(short = 'a', long = "action", value_name = "ACTION")]
+    pub actions: Vec<ActionArg>,
+    /// Run in the foreground (default is background)
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+    pub foreground: bool,
+    /// Internal flag for background child process (do not use directly)
+    #[arg(long, hide = true, default_value_t = false)]
+    pub background_child: bool,
+    /// Run against a throwaway copy of the data directory; all writes are discarded at exit
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+    pub sandbox: bool,
+    /// When a tick overruns its interval, reuse each agent's last LLM plan on the next tick instead of re-planning
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+    pub hold_rate: bool,
+    /// Qi each LLM brain call costs the agent; agents that can't pay use the loop brain that tick
+    #[arg(long, default_value_t = 0)]
+    pub thinking_cost: harimu::Qi,
+    /// Put agents to sleep after this many ticks with no other agent acting within --hibernate-radius; dormant agents skip planning (off if unset)
+    #[arg(long, value_name = "TICKS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub hibernate_after: Option<u64>,
+    /// Blocks around an agent in which another agent's actions keep it awake or wake it
+    #[arg(
+        long,
+        value_name = "BLOCKS",
+        default_value_t = 16,
+        requires = "hibernate_after"
+    )]
+    pub hibernate_radius: i32,
+    /// Dormant agents age one year every this many ticks (0 = they don't age)
+    #[arg(
+        long,
+        value_name = "TICKS",
+        default_value_t = 0,
+        requires = "hibernate_after"
+    )]
+    pub dormant_age_every: u64,
+    /// Agents that end this many ticks in a row with no Qi starve to death (off if unset)
+    #[arg(long, value_name = "TICKS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub starve_after: Option<u64>,
+    /// Ticks before starving in which an agent gets an AgentStarving warning each tick
+    #[arg(
+        long,
+        value_name = "TICKS",
+        default_value_t = 3,
+        requires = "starve_after"
+    )]
+    pub starve_warning: u64,
+    /// Keep only zones within this many zones of a living agent in memory; farther zones' ore nodes, structures and terrain go to the store until an agent comes near (off if unset)
+    #[arg(long, value_name = "ZONES", value_parser = clap::value_parser!(i32).range(0..))]
+    pub page_zones: Option<i32>,
+    /// Program file flashed onto every programmable structure built during the run
+    #[arg(long, value_name = "PATH")]
+    pub firmware: Option<PathBuf>,
+    /// Half-width of the flat terrain slab generated under the start position (0 = no terrain)
+    #[arg(long, default_value_t = 16)]
+    pub terrain_radius: i32,
+    /// Seed for all world randomness; runs with the same seed and actions replay identically (random if unset)
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Reuse every seed recorded for an earlier run (see .harimu/runs.json); combine with --fresh to rebuild its world
+    #[arg(long, value_name = "RUN", conflicts_with = "seed")]
+    pub seed_from_run: Option<u64>,
+    /// Discard the saved world and build a new one from the agent, ore and structure stores
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+    pub fresh: bool,
+    /// Encoding for this run's world snapshots (defaults to .harimu/config.toml, else json)
+    #[arg(long, value_enum)]
+    pub snapshot_format: Option<SnapshotFormat>,
+    /// Scenario file whose [[phase]] tables switch the candidate actions as the run progresses
+    /// (replaces --action)
+    #[arg(long, value_name = "PATH", conflicts_with = "actions")]
+    pub scenario: Option<PathBuf>,
+    /// Label the run for `harimu runs compare` (repeatable), e.g. --tag prompt-v2
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+    /// Let OpenAI-compatible and Ollama models reply in free text instead of constraining them to the action JSON schema (for servers without structured output)
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+    pub llm_free_text: bool,
+    /// LLM requests in flight at once; each tick plans its agents concurrently up to this
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u64).range(1..))]
+    pub llm_concurrency: u64,
+    /// LLM requests (retries included) the run may send before every agent moves to the loop brain
+    #[arg(long, value_name = "REQUESTS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub llm_max_requests: Option<u64>,
+    /// Prompt plus completion tokens the run may spend before every agent moves to the loop brain
+    #[arg(long, value_name = "TOKENS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub llm_max_tokens: Option<u64>,
+    /// Archive the notes that fall out of each LLM agent's rolling memory, embedded through the provider, and recall the most relevant into every prompt
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+    pub llm_vector_memory: bool,
+    /// Archived notes recalled into each prompt with --llm-vector-memory
+    #[arg(long, value_name = "NOTES", default_value_t = harimu::memory::DEFAULT_RECALL, value_parser = clap::value_parser!(u64).range(1..), requires = "llm_vector_memory")]
+    pub llm_recall: u64,
+    /// Embedding model for --llm-vector-memory (defaults to nomic-embed-text on Ollama, text-embedding-3-small on OpenAI and text-embedding-004 on Gemini)
+    #[arg(long, value_name = "MODEL", requires = "llm_vector_memory")]
+    pub llm_embedding_model: Option<String>,
+    /// Back up the data directory every N epochs while running (see `harimu backup list`)
+    #[arg(long, value_name = "EPOCHS")]
+    pub backup_every: Option<u64>,
+    /// Automatic backups to keep; older ones are deleted
+    #[arg(long, default_value_t = 5)]
+    pub backup_keep: usize,
+}
+
+impl Command {
+    /// Whether the command can modify the data directory; these are refused in read-only mode.
+    fn mutates(&self) -> bool {
+        match self {
+            // Serving only writes on POST requests, which it refuses itself when read-only.
+            Command::Status
+            | Command::Dashboard(_)
+            | Command::Top(_)
+            | Command::Replay(_)
+            | Command::Analyze(_)
+            | Command::Runs { .. }
+            | Command::Stats { .. }
+            | Command::Serve(_) => false,
+            #[cfg(feature = "grpc")]
+            Command::GrpcServe(_) => false,
+            Command::Agent { command } => command.mutates(),
+            Command::Wallet { command } => command.mutates(),
+            Command::World { command } => command.mutates(),
+            Command::Events { command } => command.mutates(),
+            Command::Backup { command } => command.mutates(),
+            Command::Token { command } => command.mutates(),
+            Command::Init { .. }
+            | Command::Start(_)
+            | Command::Stop
+            | Command::Pause
+            | Command::Resume
+            | Command::Inject { .. }
+            | Command::Brain { .. }
+            | Command::Admin { .. }
+            | Command::Mine { .. } => true,
+        }
+    }
+}
+
+/// Layers of dirt and stone under generated terrain.
+const TERRAIN_DEPTH: i32 = 4;
+
 #[derive(Clone, Copy, Debug)]
 pub struct PositionArg(pub Position);
 
@@ -159,8 +437,63 @@ impl FromStr for PositionArg {
     }
 }
 
+/// `--position-for ADDR=x,y,z`: where one agent spawns.
+#[derive(Clone, Debug)]
+pub struct AgentPositionArg {
+    pub agent: String,
+    pub position: Position,
+}
+
+impl FromStr for AgentPositionArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (agent, position) = s
+            .split_once('=')
+            .ok_or("a per-agent position is ADDR=x,y,z")?;
+        let agent = agent.trim();
+        if agent.is_empty() {
+            return Err("a per-agent position needs an agent address".into());
+        }
+        Ok(AgentPositionArg {
+            agent: agent.to_string(),
+            position: PositionArg::from_str(position)?.0,
+        })
+    }
+}
+
 pub fn run() {
+    // The ore registry lives in the data directory, which `--data-dir` may move. Find that
+    // with a lenient parse and load the registry before the real one, so `--action` and
+    // `--ore` accept the registered ores.
+    if let Ok(early) = Cli::command().ignore_errors(true).try_get_matches()
+        && let Ok(Some(dir)) = early.try_get_one::<PathBuf>("data_dir")
+    {
+        paths::set_data_dir_override(dir.clone());
+    }
+    if let Err(err) = ore::load_registry() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
     let cli = Cli::parse();
+    if cli.read_only {
+        set_read_only(true);
+    }
+    if let Some(dir) = cli.data_dir {
+        paths::set_data_dir_override(dir);
+    }
+    if let Some(name) = &cli.session {
+        // Read-only commands look at the session without creating it.
+        let create = cli.command.mutates() && !is_read_only();
+        if let Err(err) = paths::enter_session(name, create) {
+            eprintln!("error: session: {}", err);
+            std::process::exit(1);
+        }
+    }
+    let rotation = load_store_config()
+        .map(|config| config.llm_log)
+        .unwrap_or_default();
+    logging::init(cli.log_format, rotation);
     if let Err(err) = dispatch(cli.command) {
         eprintln!("error: {}", err);
         std::process::exit(1);
@@ -168,46 +501,62 @@ pub fn run() {
 }
 
 fn dispatch(command: Command) -> Result<(), String> {
+    if command.mutates() {
+        ensure_writable("this command").map_err(|e| e.to_string())?;
+    }
+    // Long-running commands lock per tick or per solution instead, and control requests must
+    // not hold the lock the run they talk to needs to finish its tick.
+    let _lock = match &command {
+        Command::Start(_)
+        | Command::Mine { .. }
+        | Command::Stop
+        | Command::Pause
+        | Command::Resume
+        | Command::Inject { .. }
+        | Command::Brain { .. }
+        | Command::Admin { .. } => None,
+        command if command.mutates() => Some(lock_data_dir().map_err(|e| e.to_string())?),
+        _ => None,
+    };
     match command {
-        Command::Init => run_init(),
-        Command::Start {
-            agent,
-            qi,
-            position,
-            ticks,
-            brain,
-            llm_host,
-            llm_model,
-            llm_timeout_ms,
-            llm_provider,
-            llm_api_key,
-            tick_rate,
-            delay_ms,
-            actions,
-            foreground,
-            background_child,
-        } => run_start(
-            agent,
-            qi,
-            position.0,
-            ticks,
-            brain,
-            llm_host,
-            llm_model,
-            llm_timeout_ms,
-            llm_provider,
-            llm_api_key,
-            tick_rate,
-            delay_ms,
-            actions,
-            foreground,
-            background_child,
-        ),
+        Command::Init {
+            store,
+            snapshot_format,
+        } => run_init(store, snapshot_format),
+        Command::Start(args) => run_start(*args),
         Command::Status => run_status(),
+        Command::Dashboard(args) => run_dashboard(args),
+        Command::Top(args) => run_top(args),
         Command::Stop => run_stop(),
+        Command::Pause => run_control(ControlRequest::Pause),
+        Command::Resume => run_control(ControlRequest::Resume),
+        Command::Inject { agent_id, action } => run_control(ControlRequest::Inject {
+            agent_id,
+            action: render_action_arg(&action),
+        }),
+        Command::Brain {
+            agent_id,
+            mode,
+            model,
+        } => run_control(ControlRequest::SetBrain(BrainSwap {
+            agent_id,
+            mode,
+            model,
+        })),
+        Command::Admin { command } => run_admin(command),
         Command::Agent { command } => run_agent(command),
         Command::Wallet { command } => run_wallet(command),
         Command::World { command } => run_world(command),
+        Command::Events { command } => run_events(command),
+        Command::Backup { command } => run_backup(command),
+        Command::Serve(args) => run_serve(args),
+        #[cfg(feature = "grpc")]
+        Command::GrpcServe(args) => run_grpc_serve(args),
+        Command::Token { command } => run_token(command),
+        Command::Replay(args) => run_replay(args),
+        Command::Analyze(args) => run_analyze(args),
+        Command::Runs { command } => run_runs(command),
+        Command::Stats { command } => run_stats(command),
         Command::Mine {
             address,
             start_nonce,
@@ -217,8 +566,26 @@ fn dispatch(command: Command) -> Result<(), String> {
     }
 }
 
-fn run_init() -> Result<(), String> {
+fn run_init(
+    store: Option<StoreBackend>,
+    snapshot_format: Option<SnapshotFormat>,
+) -> Result<(), String> {
+    if store.is_some() || snapshot_format.is_some() {
+        let mut config = load_store_config().map_err(|e| e.to_string())?;
+        if let Some(backend) = store {
+            // Fail before touching any state if this build can't open the chosen store.
+            open_backend(backend, &paths::data_dir()).map_err(|e| e.to_string())?;
+            config.store = backend;
+            println!("Using the {:?} store", backend);
+        }
+        if let Some(format) = snapshot_format {
+            config.snapshot_format = format;
+            println!("Writing {:?} snapshots", format);
+        }
+        save_store_config(&config).map_err(|e| e.to_string())?;
+    }
     state::init_state().map_err(|e| e.to_string())?;
+    clear_world_state().map_err(|e| e.to_string())?;
     println!(
         "Initialized state at {}",
         state::state_file_path().display()
@@ -227,6 +594,11 @@ fn run_init() -> Result<(), String> {
 }
 
 fn run_status() -> Result<(), String> {
+    println!("Data dir: {}", paths::data_dir().display());
+    let sessions = paths::list_sessions().map_err(|e| e.to_string())?;
+    if !sessions.is_empty() {
+        println!("Sessions (use --session <NAME>): {}", sessions.join(", "));
+    }
     match state::load_state().map_err(|e| e.to_string())? {
         None => {
             println!("Status: not initialized. Run `harimu init`.");
@@ -238,12 +610,98 @@ fn run_status() -> Result<(), String> {
                 state.last_tick,
                 state.message.unwrap_or_else(|| "-".into())
             );
+            if let Some(seed) = state.seed {
+                println!("Seed: {}", seed);
+            }
+            if let Some(pacing) = state.pacing {
+                println!(
+                    "Pacing: target={:.2} tps | effective={:.2} tps | last_tick={} ms | avg_tick={} ms | overruns={} | skipped_plans={}",
+                    pacing.target_tps,
+                    pacing.effective_tps,
+                    pacing.last_tick_ms,
+                    pacing.avg_tick_ms,
+                    pacing.overruns,
+                    pacing.skipped_plans
+                );
+            }
+            match send_control(&ControlRequest::Status) {
+                Ok(Some(reply)) => print_daemon_status(&reply.status),
+                Ok(None) if state.status == Status::Running => println!(
+                    "Run: no run is listening on {}; `harimu stop` marks the runtime stopped",
+                    control_socket_path().display()
+                ),
+                Ok(None) => {}
+                Err(err) => warn!("control socket: {}", err),
+            }
         }
     }
     Ok(())
 }
 
+fn print_daemon_status(status: &DaemonStatus) {
+    let phase = if status.stopping {
+        "stopping"
+    } else if status.paused {
+        "paused"
+    } else {
+        "running"
+    };
+    println!(
+        "Run: {} | run_id={} | pid={} | tick={} | injected actions pending={}",
+        phase, status.run_id, status.pid, status.tick, status.pending
+    );
+}
+
+/// How long `harimu stop` waits for the run to finish its tick and exit.
+const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
 fn run_stop() -> Result<(), String> {
+    let reply =
+        send_control(&ControlRequest::Stop).map_err(|e| format!("control socket: {}", e))?;
+    if let Some(reply) = reply {
+        println!(
+            "Run {} (pid {}): {}",
+            reply.status.run_id, reply.status.pid, reply.message
+        );
+        let started = std::time::Instant::now();
+        while send_control(&ControlRequest::Status)
+            .map_err(|e| format!("control socket: {}", e))?
+            .is_some()
+        {
+            if started.elapsed() > STOP_TIMEOUT {
+                return Err(format!(
+                    "run {} did not stop within {} s; it is still finishing a tick",
+                    reply.status.run_id,
+                    STOP_TIMEOUT.as_secs()
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let last_tick = state::load_state()
+            .map_err(|e| e.to_string())?
+            .map_or(reply.status.tick, |state| state.last_tick);
+        println!("Stopped. last_tick={}", last_tick);
+        return print_action_summary();
+    }
+
+    // No run is listening: it died or never started, so only the records need closing.
+    let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+    mark_stopped()
+}
+
+/// Send a pause, resume or inject request to the running `start`.
+fn run_control(request: ControlRequest) -> Result<(), String> {
+    let reply = send_control(&request)
+        .map_err(|e| format!("control socket: {}", e))?
+        .ok_or_else(|| "no run is listening; start one with `harimu start`".to_string())?;
+    if !reply.ok {
+        return Err(reply.message);
+    }
+    println!("Run {}: {}", reply.status.run_id, reply.message);
+    Ok(())
+}
+
+fn mark_stopped() -> Result<(), String> {
     let current = state::load_state().map_err(|e| e.to_string())?;
     let Some(prev) = current else {
         return Err("Not initialized. Run `harimu init` first.".into());
@@ -255,49 +713,106 @@ fn run_stop() -> Result<(), String> {
     )
     .map_err(|e| e.to_string())?;
     println!("Stopped. last_tick={}", updated.last_tick);
-    print_action_summary()?;
-    try_kill_background_process();
-    Ok(())
+    let open_run = RunStore::load()
+        .map_err(|e| e.to_string())?
+        .latest()
+        .filter(|run| !run.is_finished())
+        .map(|run| run.id);
+    if let Some(id) = open_run {
+        finish_run(id, updated.last_tick, "stopped by user", None).map_err(|e| e.to_string())?;
+    }
+    print_action_summary()
 }
 
-fn run_start(
-    agent: Option<String>,
-    qi: harimu::Qi,
-    position: Position,
-    ticks: Option<u64>,
-    brain: BrainMode,
-    llm_host: String,
-    llm_model: String,
-    llm_timeout_ms: u64,
-    llm_provider: LlmProvider,
-    llm_api_key: Option<String>,
-    tick_rate: Option<f64>,
-    delay_ms: u64,
-    actions: Vec<ActionArg>,
-    foreground: bool,
-    background_child: bool,
-) -> Result<(), String> {
-    let background = !foreground;
-    if background && !background_child {
-        return launch_background_start(
-            agent,
-            qi,
-            position,
-            ticks,
-            brain,
-            llm_host,
-            llm_model,
-            llm_timeout_ms,
-            llm_provider,
-            llm_api_key,
-            tick_rate,
-            delay_ms,
-            actions,
-        );
+fn run_start(args: StartArgs) -> Result<(), String> {
+    // Resolved before a background launch too, so contradictions fail in the terminal.
+    let config = resolve_start_config(&args)?;
+    let background = !args.foreground;
+    if background && !args.background_child {
+        return launch_background_start(&args);
+    }
+
+    let run_flags = redacted_start_args(&args);
+    let StartArgs {
+        agent,
+        agents: listed_agents,
+        groups,
+        qi,
+        position,
+        positions_for,
+        scatter,
+        ticks,
+        llm_api_key,
+        actions,
+        sandbox,
+        hold_rate,
+        llm_concurrency,
+        llm_max_requests,
+        llm_max_tokens,
+        llm_free_text,
+        llm_vector_memory,
+        llm_recall,
+        llm_embedding_model,
+        tags,
+        thinking_cost,
+        hibernate_after,
+        hibernate_radius,
+        dormant_age_every,
+        starve_after,
+        starve_warning,
+        page_zones,
+        firmware,
+        terrain_radius,
+        seed,
+        seed_from_run,
+        fresh,
+        snapshot_format,
+        backup_every,
+        backup_keep,
+        scenario,
+        ..
+    } = args;
+    let position = position.0;
+    let brain = config.brain.value;
+    let llm_provider = config.llm_provider.value;
+    let llm_host = config.llm_host.value.clone();
+    let llm_model = config.llm_model.value.clone();
+    println!("Effective config:");
+    for line in config.lines() {
+        println!("  {}", line);
+    }
+    for warning in config.warnings(hold_rate) {
+        println!("Warning: {}", warning);
+    }
+    if snapshot_format.is_some() {
+        set_snapshot_format(snapshot_format);
     }
 
-    const DEFAULT_TICK_RATE: f64 = 1.0;
+    // Bound before the sandbox swaps the data directory, so `harimu stop` finds the run there.
+    let control = ControlServer::bind().map_err(|e| match e.kind() {
+        std::io::ErrorKind::AddrInUse => {
+            "a run is already active; stop it with `harimu stop` first".to_string()
+        }
+        _ => format!("control socket: {}", e),
+    })?;
+    control
+        .stop_on_signals()
+        .map_err(|e| format!("signal handlers: {}", e))?;
+
+    // Held for the whole run; dropping it discards every write made during the run.
+    let _sandbox = if sandbox {
+        let guard = Sandbox::enter().map_err(|e| format!("sandbox: {}", e))?;
+        println!(
+            "Sandbox mode: running against a copy of your world at {} (discarded at exit)",
+            guard.root().display()
+        );
+        Some(guard)
+    } else {
+        None
+    };
 
+    // Setup reads and rewrites the stores; the loops re-take the lock for each tick.
+    let setup_lock = lock_data_dir().map_err(|e| e.to_string())?;
     let prior_state = match state::load_state().map_err(|e| e.to_string())? {
         Some(s) => Some(s),
         None => {
@@ -311,84 +826,286 @@ fn run_start(
         }
     };
 
-    let mut vm = Vm::new();
-    if let Some(s) = prior_state.as_ref() {
-        if s.last_tick > 0 {
-            vm.set_tick(s.last_tick);
-            println!("Resuming from tick {}", s.last_tick);
+    let reused_seeds = seed_from_run.map(runs::seeds_of_run).transpose()?;
+    let seed = seed.or(reused_seeds.map(|seeds| seeds.world));
+
+    let saved = if fresh {
+        clear_world_state().map_err(|e| e.to_string())?;
+        None
+    } else {
+        load_world_state().map_err(|e| e.to_string())?
+    };
+    let resumed = saved.is_some();
+    let mut vm = match saved {
+        Some(saved) => {
+            if let Some(requested) = seed
+                && requested != saved.seed()
+            {
+                println!(
+                    "Ignoring --seed {}: the saved world was seeded with {} (use --fresh to start over)",
+                    requested,
+                    saved.seed()
+                );
+            }
+            let world = World::from_persistent(saved)
+                .map_err(|e| format!("{}: {}", world_state_file_path().display(), e))?;
+            println!(
+                "Resuming saved world at tick {} ({} agent(s), {} structure(s))",
+                world.tick(),
+                world.agents().count(),
+                world.structures().len()
+            );
+            Vm::with_world(world)
         }
+        None => Vm::with_seed(seed.unwrap_or_else(rand::random)),
+    };
+    let seed = vm.world().seed();
+    state::set_seed(seed).map_err(|e| e.to_string())?;
+    println!("World seed: {} (replay with --seed {})", seed, seed);
+    let run_seeds = RunSeeds {
+        world: seed,
+        llm_jitter: reused_seeds.map_or_else(rand::random, |seeds| seeds.llm_jitter),
+    };
+    let run_start_tick = vm.world().tick();
+    let run_id = {
+        let mut runs = RunStore::load().map_err(|e| e.to_string())?;
+        let run = runs.begin(run_seeds);
+        run.flags = run_flags;
+        run.tags = tags;
+        let llm = brain == BrainMode::Llm;
+        run.brain = BrainConfig {
+            mode: Some(brain),
+            provider: llm.then_some(llm_provider),
+            model: llm.then(|| llm_model.clone()),
+            host: llm.then(|| llm_host.clone()),
+            ..BrainConfig::default()
+        };
+        run.config = Some(config.clone());
+        run.start_tick = run_start_tick;
+        let id = run.id;
+        runs.save().map_err(|e| e.to_string())?;
+        id
+    };
+    control.begin_run(run_id, vm.world().tick());
+    println!(
+        "Run {} (reproduce its seeds with --seed-from-run {})",
+        run_id, run_id
+    );
+    if !resumed
+        && !fresh
+        && let Some(s) = prior_state.as_ref()
+        && s.last_tick > 0
+    {
+        vm.set_tick(s.last_tick);
+        println!("Resuming from tick {}", s.last_tick);
     }
+    if let Some(path) = firmware {
+        let program = Program::load(&path)?;
+        println!(
+            "Firmware {} ({} instructions) will run on programmable structures",
+            path.display(),
+            program.ops().len()
+        );
+        vm.set_firmware(Some(program));
+    }
+    if terrain_radius > 0 && !resumed {
+        *vm.world_mut().terrain_mut() = Terrain::flat(position, terrain_radius, TERRAIN_DEPTH);
+        println!(
+            "Generated terrain: radius {} around ({}, {}, {})",
+            terrain_radius, position.x, position.y, position.z
+        );
+    }
+    let zone_labels = load_zone_store().map_err(|e| e.to_string())?;
+    vm.world_mut().set_zone_labels(zone_labels);
+    add_structure_effects(&mut vm)?;
+    add_recipes(&mut vm)?;
+    vm.set_thinking_cost(thinking_cost);
+    vm.set_hibernation(hibernate_after.map(|after_ticks| Hibernation {
+        after_ticks,
+        radius: hibernate_radius,
+        age_every: dormant_age_every,
+    }));
+    vm.set_starvation(starve_after.map(|after_ticks| Starvation {
+        after_ticks,
+        warn_ticks: starve_warning,
+    }));
     reset_action_stats().map_err(|e| format!("reset stats: {}", e))?;
+    decisions::reset_decisions().map_err(|e| format!("reset decisions: {}", e))?;
+
+    if page_zones.is_none() {
+        let restored = restore_all_zones(&mut vm).map_err(|e| format!("evicted zones: {}", e))?;
+        if restored > 0 {
+            println!("Loaded {} evicted zone(s) back into memory", restored);
+        }
+    }
 
     let qi_store = WorldQueries::qi_sources()?;
     if !qi_store.sources.is_empty() {
         vm.set_max_qi_supply(qi_store.total_qi_infused);
+        // A resumed world already holds its nodes; only add ones infused since it was saved.
+        let mut seeded = 0usize;
         for src in &qi_store.sources {
-            vm.seed_ore_source(src.ore, src.position, src.capacity, src.recharge_per_tick);
+            let known = vm.world().is_cold(src.position.zone())
+                || vm
+                    .world()
+                    .qi_sources()
+                    .iter()
+                    .any(|s| s.ore == src.ore && s.position == src.position);
+            if !known {
+                vm.seed_ore_source(src.ore, src.position, src.capacity, src.recharge_per_tick);
+                seeded += 1;
+            }
+        }
+        if seeded > 0 {
+            println!("Seeded {} ore node(s) into the world", seeded);
         }
-        println!(
-            "Seeded {} ore node(s) into the world",
-            qi_store.sources.len()
-        );
     }
 
-    // Load agents; either run all or a specific one.
+    // Load agents; either run all or the ones named by --agent, --agents and --group.
     let registry = agents::load().map_err(|e| e.to_string())?;
     let mut agent_ids = Vec::new();
+    let mut selected: Vec<(String, Option<&GroupArg>)> = Vec::new();
+    let named = agent.into_iter().chain(listed_agents).map(|a| (a, None));
+    let grouped = groups
+        .iter()
+        .flat_map(|g| g.agents.iter().map(move |a| (a.clone(), Some(g))));
+    for (addr, group) in named.chain(grouped) {
+        if selected.iter().any(|(other, _)| *other == addr) {
+            return Err(format!("agent {} is named more than once", addr));
+        }
+        selected.push((addr, group));
+    }
+    let mut members: Vec<(AgentId, &GroupArg)> = Vec::new();
+    let mut placements: HashMap<String, Position> = HashMap::new();
+    for placed in positions_for {
+        if placements
+            .insert(placed.agent.clone(), placed.position)
+            .is_some()
+        {
+            return Err(format!("--position-for names agent {} twice", placed.agent));
+        }
+    }
+
+    // An explicit --position-for wins, then the agent's group, then its stored position;
+    // everyone else spawns around --position.
+    let spawn_point = |vm: &mut Vm,
+                       placements: &mut HashMap<String, Position>,
+                       addr: &str,
+                       profile: Option<&AgentProfile>,
+                       group: Option<&GroupArg>| {
+        placements
+            .remove(addr)
+            .or(group.and_then(|g| g.position))
+            .or(profile.and_then(|p| p.position))
+            .unwrap_or_else(|| vm.world_mut().scatter(position, scatter))
+    };
+
+    // Agents already in a resumed world keep their saved state; only new ones are spawned.
+    let saved_agent = |vm: &Vm, addr: &str| {
+        vm.world()
+            .agents()
+            .filter(|(_, a)| a.name == addr)
+            .map(|(id, _)| *id)
+            .min()
+    };
+
+    // A profile synced from an earlier run brings back the agent's age and transistors too.
+    let restore = |vm: &mut Vm, id: AgentId, profile: &AgentProfile| -> Result<(), String> {
+        vm.restore_agent(
+            id,
+            profile.age,
+            &[(ItemKind::Transistor, profile.transistors as harimu::Qi)],
+        )
+        .map_err(|e| e.to_string())
+    };
 
-    if let Some(addr) = agent {
-        let agent_qi = registry
-            .agents
-            .get(&addr)
-            .map(|a| a.qi as harimu::Qi)
-            .unwrap_or(qi);
-        let max_age = registry
-            .agents
-            .get(&addr)
-            .map(|a| a.max_age)
-            .unwrap_or(harimu::DEFAULT_MAX_AGENT_AGE);
-        let id = vm.spawn_agent_with_age(addr, agent_qi, position, max_age);
-        agent_ids.push(id);
+    if !selected.is_empty() {
+        for (addr, group) in selected {
+            let id = match saved_agent(&vm, &addr) {
+                Some(id) => id,
+                None => {
+                    let profile = registry.agents.get(&addr);
+                    if profile.is_some_and(|p| !p.alive) {
+                        return Err(format!("agent {} died in an earlier run", addr));
+                    }
+                    let agent_qi = profile.map(|a| a.qi as harimu::Qi).unwrap_or(qi);
+                    let max_age = profile
+                        .map(|a| a.max_age)
+                        .unwrap_or(harimu::DEFAULT_MAX_AGENT_AGE);
+                    let spawn_at = spawn_point(&mut vm, &mut placements, &addr, profile, group);
+                    let id = vm.spawn_agent_with_age(addr, agent_qi, spawn_at, max_age);
+                    if let Some(profile) = profile {
+                        restore(&mut vm, id, profile)?;
+                    }
+                    id
+                }
+            };
+            agent_ids.push(id);
+            if let Some(group) = group {
+                members.push((id, group));
+            }
+        }
     } else {
         if registry.agents.is_empty() {
             return Err("no agents found; create one with `harimu agent create`".to_string());
         }
         for (addr, profile) in registry.agents.iter() {
+            if let Some(id) = saved_agent(&vm, addr) {
+                agent_ids.push(id);
+                continue;
+            }
+            if !profile.alive {
+                println!("Skipping agent {}: it died in an earlier run", addr);
+                continue;
+            }
+            let spawn_at = spawn_point(&mut vm, &mut placements, addr, Some(profile), None);
             let id = vm.spawn_agent_with_age(
                 addr.clone(),
                 profile.qi as harimu::Qi,
-                position,
+                spawn_at,
                 profile.max_age,
             );
+            restore(&mut vm, id, profile)?;
             agent_ids.push(id);
         }
+        if agent_ids.is_empty() {
+            return Err(
+                "every registered agent has died; create one with `harimu agent create`"
+                    .to_string(),
+            );
+        }
+    }
+    for addr in placements.keys() {
+        println!(
+            "Ignoring --position-for {}: it is not spawned by this run (not started, dead, or already in the saved world)",
+            addr
+        );
+    }
+    let agenda = Agenda::for_agents(&registry, vm.world(), &agent_ids)?;
+    if !agenda.is_empty() {
+        println!(
+            "Agenda: {} scheduled action(s) from the agent registry",
+            agenda.len()
+        );
     }
 
-    let action_cycle: Vec<ActionArg> = if actions.is_empty() {
-        match brain {
+    let mut phases = match &scenario {
+        Some(path) => {
+            let plan = PhasePlan::load(path)?;
+            let names: Vec<&str> = plan.phases().iter().map(|p| p.name.as_str()).collect();
+            println!("Scenario {}: phases {}", path.display(), names.join(" -> "));
+            plan
+        }
+        None if actions.is_empty() => PhasePlan::flat(match brain {
             BrainMode::Loop => default_loop_actions(&agent_ids),
             BrainMode::Llm => default_llm_actions(&agent_ids),
-        }
-    } else {
-        actions
+        }),
+        None => PhasePlan::flat(actions),
     };
 
-    let effective_delay = match tick_rate {
-        Some(rate) => {
-            if rate <= 0.0 {
-                return Err("tick_rate must be greater than 0".into());
-            }
-            Duration::from_secs_f64(1.0 / rate)
-        }
-        None => {
-            if delay_ms > 0 {
-                Duration::from_millis(delay_ms)
-            } else {
-                Duration::from_secs_f64(1.0 / DEFAULT_TICK_RATE)
-            }
-        }
-    };
+    let effective_delay = config.tick_interval();
 
+    start_replay_log(&vm).map_err(|e| format!("replay log: {}", e))?;
     state::set_status(
         Status::Running,
         vm.world().tick(),
@@ -396,40 +1113,207 @@ fn run_start(
     )
     .map_err(|e| e.to_string())?;
 
-    match brain {
-        BrainMode::Loop => run_loop(&agent_ids, &action_cycle, ticks, effective_delay, &mut vm)?,
-        BrainMode::Llm => {
-            let api_key = llm_api_key
-                .or_else(|| env::var("LLM_API_KEY").ok())
-                .or_else(load_llm_key_from_file);
-            let client = LlmClient::new(
-                llm_host,
-                llm_model,
-                llm_provider,
-                api_key,
-                Duration::from_millis(llm_timeout_ms),
+    drop(setup_lock);
+    let backups = backup_every.map(|every_epochs| BackupSchedule {
+        every_epochs,
+        keep: backup_keep,
+    });
+    // Loop runs get a client too, so `harimu brain` can move agents onto the LLM mid-run.
+    let api_key = llm_api_key
+        .or_else(|| env::var("LLM_API_KEY").ok())
+        .or_else(load_llm_key_from_file);
+    if llm_vector_memory && llm_provider == LlmProvider::Llama {
+        return Err(
+            "--llm-vector-memory needs an embedding endpoint; use ollama, openai or gemini".into(),
+        );
+    }
+    let prompt_templates = harimu::PromptTemplates::load()?;
+    if !prompt_templates.is_default() {
+        println!(
+            "Prompt templates from {}",
+            harimu::prompts::prompts_dir().display()
+        );
+    }
+    let client = LlmClient::new(
+        llm_host,
+        llm_model,
+        llm_provider,
+        api_key,
+        Duration::from_millis(config.llm_timeout_ms.value),
+    )
+    .map(|client| {
+        client
+            .with_jitter_seed(run_seeds.llm_jitter)
+            .with_structured_output(!llm_free_text)
+            .with_vector_memory(
+                if llm_vector_memory {
+                    llm_recall as usize
+                } else {
+                    0
+                },
+                llm_embedding_model,
             )
-            .map_err(|e| format!("llm client: {}", e))?;
-
-            run_llm_loop(
+            .with_prompt_templates(prompt_templates)
+    })
+    .map_err(|e| format!("llm client: {}", e));
+    let brains = match brain {
+        BrainMode::Loop => Ok(Brains::new(
+            BrainMode::Loop,
+            client.ok(),
+            llm_concurrency as usize,
+        )),
+        BrainMode::Llm => {
+            client.map(|client| Brains::new(BrainMode::Llm, Some(client), llm_concurrency as usize))
+        }
+    }
+    .and_then(|mut brains| {
+        for agent_id in &agent_ids {
+            let Some(profile) = vm
+                .world()
+                .agent(*agent_id)
+                .and_then(|agent| registry.agents.get(&agent.name))
+            else {
+                continue;
+            };
+            let brain = brains
+                .configure(*agent_id, profile)
+                .map_err(|e| format!("agent {}: {}", profile.id, e))?;
+            if let Some(brain) = brain {
+                println!("Agent {} | brain {}", profile.id, brain);
+            }
+        }
+        Ok(brains)
+    })
+    .map(|brains| {
+        brains.with_budget(LlmBudget {
+            max_requests: llm_max_requests,
+            max_tokens: llm_max_tokens,
+        })
+    })
+    .and_then(|mut brains| {
+        for group in &groups {
+            let ids: Vec<AgentId> = members
+                .iter()
+                .filter(|(_, g)| g.name == group.name)
+                .map(|(id, _)| *id)
+                .collect();
+            for agent_id in &ids {
+                brains.join_group(*agent_id, group)?;
+            }
+            let mut line = format!(
+                "Group {}: agent(s) {}",
+                group.name,
+                ids.iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            if let Some(mode) = group.brain {
+                line.push_str(&format!(" | brain {}", brain_to_arg(mode)));
+            }
+            if let Some(model) = &group.model {
+                line.push_str(&format!(" | model {}", model));
+            }
+            if !group.actions.is_empty() {
+                line.push_str(&format!(" | {} action(s)", group.actions.len()));
+            }
+            println!("{}", line);
+        }
+        Ok(brains)
+    });
+    let outcome = brains.and_then(|mut brains| {
+        let outcome = match brain {
+            BrainMode::Loop => run_loop(
+                &agent_ids,
+                &mut phases,
+                &agenda,
+                &control,
+                ticks,
+                effective_delay,
+                backups,
+                page_zones,
+                &mut vm,
+                &mut brains,
+            ),
+            BrainMode::Llm => run_llm_loop(
                 &agent_ids,
-                &action_cycle,
+                &mut phases,
+                &agenda,
+                &control,
                 ticks,
                 effective_delay,
+                hold_rate,
+                backups,
+                page_zones,
                 &mut vm,
-                client,
-            )?
+                &mut brains,
+            ),
+        };
+        if brains.spent.requests > 0 {
+            println!(
+                "LLM usage: {} request(s), {} prompt + {} completion tokens",
+                brains.spent.requests, brains.spent.prompt_tokens, brains.spent.completion_tokens
+            );
+        }
+        outcome
+    });
+
+    let exit_reason = match &outcome {
+        Err(err) => format!("error: {}", err),
+        Ok(()) if control.was_interrupted() => "stopped by signal".to_string(),
+        Ok(()) if control.stop_requested() => "stopped by user".to_string(),
+        Ok(())
+            if agent_ids
+                .iter()
+                .all(|id| vm.world().agent(*id).map(|a| !a.alive).unwrap_or(true)) =>
+        {
+            "all agents died".to_string()
         }
+        Ok(()) => "tick limit reached".to_string(),
+    };
+    {
+        let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+        // The loops persist every tick; this covers a stop that came before the first one.
+        persist_world_view(&vm);
+        persist_world_state(&vm);
+        persist_agent_registry(&vm);
+        let summary = load_timeline(Some(run_start_tick + 1), Some(vm.world().tick()))
+            .map(|timeline| RunSummary::of(&timeline, phases.score()))
+            .unwrap_or_else(|err| {
+                warn!("failed to summarise run {}: {}", run_id, err);
+                None
+            });
+        if let Err(err) = finish_run(run_id, vm.world().tick(), &exit_reason, summary) {
+            warn!("failed to record the end of run {}: {}", run_id, err);
+        }
+    }
+    if control.stop_requested() {
+        println!("Stopped after tick {}", vm.world().tick());
     }
 
-    state::set_status(
-        Status::Stopped,
-        vm.world().tick(),
-        Some(format!("completed {} tick(s)", vm.world().tick())),
-    )
-    .map_err(|e| e.to_string())?;
+    // Recorded even when the loop failed, so the state never claims a run that is gone.
+    let message = if outcome.is_err() || control.stop_requested() {
+        exit_reason
+    } else {
+        format!("completed {} tick(s)", vm.world().tick())
+    };
+    let stopped = state::set_status(Status::Stopped, vm.world().tick(), Some(message))
+        .map(|_| ())
+        .map_err(|e| e.to_string());
+    outcome?;
+    stopped
+}
 
-    Ok(())
+/// Advance the scenario to the phase the run has reached, announcing any switch.
+fn print_phase_change(phases: &mut PhasePlan, vm: &Vm) {
+    if let Some(phase) = phases.advance(vm.world()) {
+        println!(
+            "Tick {} | entering phase '{}' ({} candidate action(s))",
+            vm.world().tick() + 1,
+            phase.name,
+            phase.actions.len()
+        );
+    }
 }
 
 fn build_requests(
@@ -441,25 +1325,32 @@ fn build_requests(
     actions
         .iter()
         .map(|arg| {
-            let mut action = arg.materialize(agent_id, next_tick);
-            if let Action::Reproduce { partner: p } = action {
-                if p == 0 {
-                    if let Some(actual) = partner {
-                        action = Action::Reproduce { partner: actual };
+            let actions = arg.parts().iter().map(|part| {
+                let action = part.materialize(agent_id, next_tick);
+                match (&action, partner) {
+                    (Action::Attack { target: 0 }, Some(actual)) => {
+                        Action::Attack { target: actual }
                     }
+                    _ => action,
                 }
-            }
-            ActionRequest::new(agent_id, action)
+            });
+            ActionRequest::bundle(agent_id, actions)
         })
         .collect()
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_loop(
     agent_ids: &[AgentId],
-    action_cycle: &[ActionArg],
+    phases: &mut PhasePlan,
+    agenda: &Agenda,
+    control: &ControlServer,
     ticks: Option<u64>,
     delay: Duration,
+    backups: Option<BackupSchedule>,
+    page_radius: Option<i32>,
     vm: &mut Vm,
+    brains: &mut Brains,
 ) -> Result<(), String> {
     #[derive(Default)]
     struct FeedbackState {
@@ -471,34 +1362,101 @@ fn run_loop(
         .map(|id| (*id, FeedbackState::default()))
         .collect();
 
+    let (mut stats, mut structures) = {
+        let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+        (
+            StatsRecorder::open(STATS_FLUSH_TICKS).map_err(|e| format!("action stats: {}", e))?,
+            StructurePersister::load().map_err(|e| format!("structure store: {}", e))?,
+        )
+    };
     let mut remaining = ticks;
+    let mut pacer = TickPacer::new(delay);
+    // Only agents switched to the LLM brain with `harimu brain` plan with these.
+    let mut memories = load_memories(vm, agent_ids);
     loop {
+        if !await_control(control, vm, "agent loop running")? {
+            break;
+        }
+        pacer.begin_tick();
+        page_world(vm, page_radius);
+        print_phase_change(phases, vm);
+        let action_cycle = phases.actions();
         let next_tick = vm.world().tick() + 1;
-        let mut requests = Vec::new();
-        for agent_id in agent_ids {
+        apply_admin_actions(control, vm, next_tick);
+        let mut injected = take_injected(control, agenda, next_tick);
+        apply_brain_swaps(control, brains, agent_ids, vm, next_tick);
+        // One slot per agent keeps the request order whichever brain fills it.
+        let mut slots: Vec<Vec<ActionRequest>> = vec![Vec::new(); agent_ids.len()];
+        let mut planned = Vec::new();
+        for (slot, agent_id) in agent_ids.iter().enumerate() {
+            // Dormant agents sit out until someone comes near or an action is injected.
+            if vm.world().is_dormant(*agent_id) && !injected.contains_key(agent_id) {
+                continue;
+            }
             let partner = agent_ids.iter().find(|&&id| id != *agent_id).copied();
+            if brains.mode(*agent_id) == BrainMode::Llm && !injected.contains_key(agent_id) {
+                planned.push((slot, *agent_id, partner));
+                continue;
+            }
             let state = feedback.entry(*agent_id).or_default();
+            let action_cycle = brains.actions(*agent_id, action_cycle);
             let base_action = action_cycle
                 .get(state.idx % action_cycle.len())
                 .cloned()
                 .unwrap_or(ActionArg::Idle);
-            let chosen = if state.last_failed {
+            let chosen = if let Some(action) = injected.remove(agent_id) {
+                action
+            } else if state.last_failed {
                 reactive_fallback(&base_action)
             } else {
                 base_action
             };
-            let mut reqs = build_requests(*agent_id, partner, &[chosen], next_tick);
-            requests.append(&mut reqs);
+            slots[slot] = build_requests(*agent_id, partner, &[chosen], next_tick);
+        }
+        let mut decision_records = Vec::new();
+        for (slot, request, record) in
+            plan_llm_requests(vm, &planned, brains, action_cycle, &mut memories, next_tick)
+        {
+            slots[slot].push(request);
+            decision_records.push(record);
+        }
+        charge_llm_budget(brains, &decision_records, agent_ids, vm, next_tick);
+        let mut requests: Vec<ActionRequest> = slots.into_iter().flatten().collect();
+        for (agent_id, action) in injected {
+            requests.extend(build_requests(agent_id, None, &[action], next_tick));
         }
 
         let tick = vm.step(&requests);
+        control.set_tick(tick.tick);
         println!("Tick {}", tick.tick);
         for agent_id in agent_ids {
             print_tick(&tick, vm, *agent_id);
+            if memories.contains_key(agent_id) {
+                record_outcome(&mut memories, &tick, vm, *agent_id);
+            }
+        }
+        {
+            let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+            persist_structures(&mut structures, &tick.events)?;
+            persist_territories(&tick, vm);
+            persist_world_view(vm);
+            persist_world_state(vm);
+            persist_journal(&tick);
+            persist_metrics(&tick, vm);
+            check_qi_conservation(&tick, vm);
+            persist_replay_tick(&requests, &tick, vm);
+            persist_action_stats(&mut stats, &requests, &tick, &decision_records);
+            if tick.tick.is_multiple_of(EPOCH_TICKS) {
+                persist_agent_registry(vm);
+                persist_memories(vm, &memories);
+            }
+            persist_backup(backups, tick.tick);
+        }
+        if !decision_records.is_empty()
+            && let Err(err) = decisions::append_decisions(&decision_records)
+        {
+            warn!("failed to append llm decisions: {}", err);
         }
-        persist_structures(&tick.events)?;
-        persist_world_view(vm);
-        persist_action_stats(&requests, &tick);
 
         for agent_id in agent_ids {
             let state = feedback.entry(*agent_id).or_default();
@@ -512,10 +1470,12 @@ fn run_loop(
             }
         }
 
-        state::set_status(
+        finish_tick(&mut pacer, tick.tick);
+        state::set_status_with_pacing(
             Status::Running,
             vm.world().tick(),
             Some("agent loop running".into()),
+            pacer.stats(),
         )
         .map_err(|e| e.to_string())?;
 
@@ -537,17 +1497,367 @@ fn run_loop(
             None => {}
         }
 
-        if delay > Duration::ZERO {
-            std::thread::sleep(delay);
+        let pause = pacer.remaining();
+        if pause > Duration::ZERO {
+            control.wait_for_next_tick(pause);
         }
     }
 
-    Ok(())
+    {
+        let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+        persist_memories(vm, &memories);
+    }
+    flush_action_stats(&mut stats)
 }
 
-fn load_llm_key_from_file() -> Option<String> {
-    let path = PathBuf::from(".harimu/.key");
-    let data = fs::read_to_string(&path).ok()?;
+/// Hold the loop while the run is paused over the control socket; false once it should stop.
+fn await_control(control: &ControlServer, vm: &Vm, running: &str) -> Result<bool, String> {
+    if control.is_paused() && !control.stop_requested() {
+        let tick = vm.world().tick();
+        println!("Paused after tick {}; continue with `harimu resume`", tick);
+        state::set_status(Status::Running, tick, Some("paused".into()))
+            .map_err(|e| e.to_string())?;
+        control.wait_while_paused();
+        if !control.stop_requested() {
+            println!("Resumed at tick {}", tick + 1);
+            state::set_status(Status::Running, tick, Some(running.into()))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(!control.stop_requested())
+}
+
+/// Actions injected over the control socket for `next_tick`, one per agent (the latest wins),
+/// then the agenda's for agents nothing was injected for.
+fn take_injected(
+    control: &ControlServer,
+    agenda: &Agenda,
+    next_tick: u64,
+) -> HashMap<AgentId, ActionArg> {
+    let mut injected = HashMap::new();
+    for (agent_id, action) in control.take_injected() {
+        println!(
+            "Tick {} | Agent {} | injected action: {}",
+            next_tick,
+            agent_id,
+            render_action_arg(&action)
+        );
+        injected.insert(agent_id, action);
+    }
+    for (agent_id, action) in agenda.due(next_tick) {
+        if injected.contains_key(&agent_id) {
+            continue;
+        }
+        println!(
+            "Tick {} | Agent {} | scheduled action: {}",
+            next_tick,
+            agent_id,
+            render_action_arg(action)
+        );
+        injected.insert(agent_id, action.clone());
+    }
+    injected
+}
+
+/// The brain each agent of a run plans with: the run's `--brain` until `harimu brain` switches
+/// it to another mode or model. Agents of a `--group` start on the group's brain and choose
+/// from its actions.
+struct Brains {
+    default: BrainMode,
+    /// The run's LLM client; a loop run has one too when it could be built.
+    client: Option<LlmClient>,
+    agents: HashMap<AgentId, AgentBrain>,
+    actions: HashMap<AgentId, Vec<ActionArg>>,
+    /// LLM requests a tick may have in flight at once.
+    concurrency: usize,
+    budget: LlmBudget,
+    /// What the run's decisions have cost so far.
+    spent: LlmUsage,
+    /// Set once `budget` is spent; no agent may go back to the LLM after that.
+    exhausted: bool,
+}
+
+struct AgentBrain {
+    mode: BrainMode,
+    client: Option<LlmClient>,
+}
+
+impl Brains {
+    fn new(default: BrainMode, client: Option<LlmClient>, concurrency: usize) -> Self {
+        Self {
+            default,
+            client,
+            agents: HashMap::new(),
+            actions: HashMap::new(),
+            concurrency,
+            budget: LlmBudget::default(),
+            spent: LlmUsage::default(),
+            exhausted: false,
+        }
+    }
+
+    fn with_budget(mut self, budget: LlmBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Add the tick's decisions to the run's spend. Returns why the budget ran out on the
+    /// tick it does, so the caller can move the agents to the loop brain once.
+    fn charge(&mut self, records: &[DecisionRecord]) -> Option<String> {
+        for record in records {
+            self.spent.add(record.usage);
+        }
+        if self.exhausted {
+            return None;
+        }
+        let reason = self.budget.exhausted_by(&self.spent)?;
+        self.exhausted = true;
+        Some(reason)
+    }
+
+    /// Give the agent the brain its profile describes: its own client when the profile names a
+    /// provider, host, model, key, temperature or goal, its own candidate actions, and its mode.
+    /// Returns the brain's description, or `None` when the profile leaves it to the run.
+    fn configure(
+        &mut self,
+        agent_id: AgentId,
+        profile: &AgentProfile,
+    ) -> Result<Option<String>, String> {
+        let Some(config) = profile
+            .brain
+            .clone()
+            .or_else(|| profile.goal.as_ref().map(|_| BrainConfig::default()))
+        else {
+            return Ok(None);
+        };
+        if !config.actions.is_empty() {
+            let actions = config
+                .actions
+                .iter()
+                .map(|action| action.parse::<ActionArg>())
+                .collect::<Result<Vec<_>, _>>()?;
+            self.actions.insert(agent_id, actions);
+        }
+
+        let custom = config.provider.is_some()
+            || config.host.is_some()
+            || config.model.is_some()
+            || config.api_key_env.is_some()
+            || config.temperature.is_some()
+            || profile.goal.is_some();
+        let client = match self.client.clone() {
+            Some(client) if custom => {
+                let provider = config.provider.unwrap_or(client.provider());
+                let host = match (&config.host, config.provider) {
+                    (Some(host), _) => host.clone(),
+                    (None, Some(LlmProvider::Gemini)) if provider != client.provider() => {
+                        harimu::GEMINI_HOST.to_string()
+                    }
+                    (None, Some(LlmProvider::Openai)) if provider != client.provider() => {
+                        harimu::config::DEFAULT_LLM_HOST.to_string()
+                    }
+                    (None, _) => client.host().to_string(),
+                };
+                let mut client = client
+                    .with_provider(provider, host)
+                    .with_temperature(config.temperature)
+                    .with_goal(profile.goal.clone());
+                if let Some(model) = &config.model {
+                    client = client.with_model(model);
+                }
+                if let Some(name) = &config.api_key_env {
+                    let key = env::var(name).map_err(|_| format!("{} is not set", name))?;
+                    client = client.with_api_key(Some(key));
+                }
+                Some(client)
+            }
+            Some(client) => Some(client),
+            None if custom || config.mode == Some(BrainMode::Llm) => {
+                return Err("this run has no LLM client".to_string());
+            }
+            None => None,
+        };
+        let mode = config.mode.unwrap_or(self.default);
+        let brain = match (mode, &client) {
+            (BrainMode::Llm, Some(client)) => format!(
+                "llm ({} {})",
+                format!("{:?}", client.provider()).to_lowercase(),
+                client.model()
+            ),
+            _ => "loop".to_string(),
+        };
+        self.agents.insert(agent_id, AgentBrain { mode, client });
+        Ok(Some(brain))
+    }
+
+    /// Put the agent on the group's brain and actions, where the group sets them.
+    fn join_group(&mut self, agent_id: AgentId, group: &GroupArg) -> Result<(), String> {
+        if group.brain.is_some() || group.model.is_some() {
+            self.apply(&BrainSwap {
+                agent_id,
+                mode: group.brain,
+                model: group.model.clone(),
+            })
+            .map_err(|e| format!("group {}: {}", group.name, e))?;
+        }
+        if !group.actions.is_empty() {
+            self.actions.insert(agent_id, group.actions.clone());
+        }
+        Ok(())
+    }
+
+    /// The actions the agent chooses from: its group's, else the run's `phase` actions.
+    fn actions<'a>(&'a self, agent_id: AgentId, phase: &'a [ActionArg]) -> &'a [ActionArg] {
+        self.actions.get(&agent_id).map_or(phase, Vec::as_slice)
+    }
+
+    fn mode(&self, agent_id: AgentId) -> BrainMode {
+        self.agents
+            .get(&agent_id)
+            .map_or(self.default, |brain| brain.mode)
+    }
+
+    /// The client the agent's LLM brain asks; `None` while it is on the loop brain.
+    fn client(&self, agent_id: AgentId) -> Option<&LlmClient> {
+        if self.mode(agent_id) != BrainMode::Llm {
+            return None;
+        }
+        match self.agents.get(&agent_id) {
+            Some(brain) => brain.client.as_ref(),
+            None => self.client.as_ref(),
+        }
+    }
+
+    /// Switch the agent's brain; returns its new description, e.g. `llm (llama3)`.
+    fn apply(&mut self, swap: &BrainSwap) -> Result<String, String> {
+        const NO_CLIENT: &str = "this run has no LLM client";
+        let current = match self.agents.get(&swap.agent_id) {
+            Some(brain) => brain.client.clone(),
+            None => self.client.clone(),
+        };
+        let client = match &swap.model {
+            Some(model) => Some(current.ok_or(NO_CLIENT)?.with_model(model)),
+            None => current,
+        };
+        let mode = swap.mode.unwrap_or(self.mode(swap.agent_id));
+        let brain = match (mode, &client) {
+            (BrainMode::Loop, _) => "loop".to_string(),
+            (BrainMode::Llm, _) if self.exhausted => {
+                return Err("the run's LLM budget is spent".to_string());
+            }
+            (BrainMode::Llm, Some(client)) => format!("llm ({})", client.model()),
+            (BrainMode::Llm, None) => return Err(NO_CLIENT.to_string()),
+        };
+        self.agents
+            .insert(swap.agent_id, AgentBrain { mode, client });
+        Ok(brain)
+    }
+}
+
+/// Apply operator interventions requested over the control socket before the coming tick.
+fn apply_admin_actions(control: &ControlServer, vm: &mut Vm, next_tick: u64) {
+    for (agent_id, action) in control.take_admin_actions() {
+        let description = describe_admin_action(&action);
+        match vm.admin(agent_id, action) {
+            Ok(()) => println!(
+                "Tick {} | Agent {} | admin: {}",
+                next_tick, agent_id, description
+            ),
+            Err(err) => warn!(
+                tick = next_tick,
+                agent_id, "admin {} refused: {}", description, err
+            ),
+        }
+    }
+}
+
+/// Apply brain changes requested over the control socket, recording each as an event of the
+/// coming tick.
+fn apply_brain_swaps(
+    control: &ControlServer,
+    brains: &mut Brains,
+    agent_ids: &[AgentId],
+    vm: &mut Vm,
+    next_tick: u64,
+) {
+    for swap in control.take_brain_swaps() {
+        let applied = if agent_ids.contains(&swap.agent_id) {
+            brains.apply(&swap)
+        } else {
+            Err("not an agent of this run".to_string())
+        };
+        match applied {
+            Ok(brain) => {
+                println!(
+                    "Tick {} | Agent {} | brain switched to {}",
+                    next_tick, swap.agent_id, brain
+                );
+                vm.queue_event(Event::BrainSwapped {
+                    agent_id: swap.agent_id,
+                    brain,
+                });
+            }
+            Err(err) => warn!(
+                tick = next_tick,
+                agent_id = swap.agent_id,
+                "agent keeps its brain: {}",
+                err
+            ),
+        }
+    }
+}
+
+/// Charge the tick's decisions to the run's LLM budget and, on the tick it runs out, move
+/// every agent still on the LLM brain to the loop brain for the rest of the run.
+fn charge_llm_budget(
+    brains: &mut Brains,
+    records: &[DecisionRecord],
+    agent_ids: &[AgentId],
+    vm: &mut Vm,
+    next_tick: u64,
+) {
+    let Some(reason) = brains.charge(records) else {
+        return;
+    };
+    warn!(
+        tick = next_tick,
+        "LLM budget spent ({}); switching to the loop brain", reason
+    );
+    for agent_id in agent_ids {
+        if brains.mode(*agent_id) != BrainMode::Llm {
+            continue;
+        }
+        let swap = BrainSwap {
+            agent_id: *agent_id,
+            mode: Some(BrainMode::Loop),
+            model: None,
+        };
+        if let Ok(brain) = brains.apply(&swap) {
+            vm.queue_event(Event::BrainSwapped {
+                agent_id: *agent_id,
+                brain,
+            });
+        }
+    }
+    brains.default = BrainMode::Loop;
+}
+
+/// Close the tick on the pacer and warn when it ran past the requested interval.
+fn finish_tick(pacer: &mut TickPacer, tick: u64) {
+    let elapsed = pacer.end_tick();
+    if pacer.is_over_budget() {
+        warn!(
+            tick,
+            elapsed_ms = elapsed.as_millis() as u64,
+            budget_ms = pacer.budget().as_millis() as u64,
+            "tick ran over its budget; falling behind requested rate"
+        );
+    }
+}
+
+fn load_llm_key_from_file() -> Option<String> {
+    let path = paths::data_dir().join(".key");
+    let data = fs::read_to_string(&path).ok()?;
     let trimmed = data.trim();
     if trimmed.is_empty() {
         None
@@ -556,85 +1866,123 @@ fn load_llm_key_from_file() -> Option<String> {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_llm_loop(
     agent_ids: &[AgentId],
-    action_cycle: &[ActionArg],
+    phases: &mut PhasePlan,
+    agenda: &Agenda,
+    control: &ControlServer,
     ticks: Option<u64>,
     delay: Duration,
+    hold_rate: bool,
+    backups: Option<BackupSchedule>,
+    page_radius: Option<i32>,
     vm: &mut Vm,
-    client: LlmClient,
+    brains: &mut Brains,
 ) -> Result<(), String> {
-    let llm_client = Some(client);
+    let (mut stats, mut structures) = {
+        let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+        (
+            StatsRecorder::open(STATS_FLUSH_TICKS).map_err(|e| format!("action stats: {}", e))?,
+            StructurePersister::load().map_err(|e| format!("structure store: {}", e))?,
+        )
+    };
     let mut remaining = ticks;
-    let mut memories: HashMap<AgentId, BrainMemory> = HashMap::new();
+    let mut memories = load_memories(vm, agent_ids);
+    let mut last_plans: HashMap<AgentId, Action> = HashMap::new();
+    let mut pacer = TickPacer::new(delay);
 
     loop {
+        if !await_control(control, vm, "agent loop running (llm)")? {
+            break;
+        }
+        // Planning dominates tick time; when the previous tick overran, reuse last plans.
+        let reuse_plans = pacer.should_skip_planning(hold_rate);
+        pacer.begin_tick();
+        page_world(vm, page_radius);
+        print_phase_change(phases, vm);
+        let action_cycle = phases.actions();
         let next_tick = vm.world().tick() + 1;
-        let mut requests = Vec::new();
-
-        for agent_id in agent_ids {
-            let memory = memories.entry(*agent_id).or_default();
+        apply_admin_actions(control, vm, next_tick);
+        let mut injected = take_injected(control, agenda, next_tick);
+        apply_brain_swaps(control, brains, agent_ids, vm, next_tick);
+        // One slot per agent keeps the request order while the plans are made concurrently.
+        let mut slots: Vec<Vec<ActionRequest>> = vec![Vec::new(); agent_ids.len()];
+        let mut planned = Vec::new();
+
+        for (slot, agent_id) in agent_ids.iter().enumerate() {
             let partner = agent_ids.iter().find(|&&id| id != *agent_id).copied();
-            let decision = plan_with_llm(
-                vm,
-                *agent_id,
-                action_cycle,
-                memory,
-                llm_client.as_ref(),
-                next_tick,
-            );
-
-            println!(
-                "Tick {} | LLM planner | Agent {}",
-                vm.world().tick() + 1,
-                agent_id
-            );
-            println!(" 1) State     : {}", decision.summary);
-            println!(" 2) Goal      : {}", harimu::DEFAULT_AGENT_GOAL);
-            println!(" 3) Prompt    : {}", decision.prompt);
-            println!(" 4) LLM reply : {}", decision.response);
-            println!(" 5) Decision  : {:?}", decision.action);
-            println!(" 6) Tx        : signed+submitted (simulated)");
-            println!(" 7) Memory    : {} notes", memory.notes.len());
-            println!(" 8) LLM model : {:?} {}", decision.provider, decision.model);
-
-            if !decision.llm_ok {
+            if let Some(action) = injected.remove(agent_id) {
+                slots[slot] = build_requests(*agent_id, partner, &[action], next_tick);
+                continue;
+            }
+            if vm.world().is_dormant(*agent_id) {
+                continue;
+            }
+            if reuse_plans && let Some(action) = last_plans.get(agent_id) {
                 println!(
-                    "LLM unreachable; falling back to loop action this tick. Reason: {}",
-                    decision.response
+                    "Tick {} | LLM planner | Agent {} | behind schedule, reusing last plan: {:?}",
+                    next_tick, agent_id, action
                 );
-            } else {
-            }
-
-            let mut action = decision.action;
-            if let Action::Reproduce { partner: p } = action {
-                if p == 0 {
-                    if let Some(actual) = partner {
-                        action = Action::Reproduce { partner: actual };
-                    }
-                }
+                pacer.note_skipped_plan();
+                slots[slot].push(ActionRequest::new(*agent_id, action.clone()));
+                continue;
             }
+            planned.push((slot, *agent_id, partner));
+        }
 
-            requests.push(ActionRequest::new(*agent_id, action));
+        let mut decision_records = Vec::new();
+        for (slot, request, record) in
+            plan_llm_requests(vm, &planned, brains, action_cycle, &mut memories, next_tick)
+        {
+            last_plans.insert(request.agent_id, request.action.clone());
+            slots[slot].push(request);
+            decision_records.push(record);
         }
+        charge_llm_budget(brains, &decision_records, agent_ids, vm, next_tick);
+        let mut requests: Vec<ActionRequest> = slots.into_iter().flatten().collect();
 
-        if requests.is_empty() {
+        for (agent_id, action) in injected {
+            requests.extend(build_requests(agent_id, None, &[action], next_tick));
+        }
+        // With every agent asleep the world still ticks, waiting for an injected action.
+        if requests.is_empty() && !agent_ids.iter().any(|id| vm.world().is_dormant(*id)) {
             break;
         }
 
         let tick = vm.step(&requests);
+        control.set_tick(tick.tick);
         for agent_id in agent_ids {
             print_tick(&tick, vm, *agent_id);
-            record_outcome(&mut memories, &tick, *agent_id);
+            record_outcome(&mut memories, &tick, vm, *agent_id);
+        }
+        {
+            let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+            persist_structures(&mut structures, &tick.events)?;
+            persist_territories(&tick, vm);
+            persist_world_view(vm);
+            persist_world_state(vm);
+            persist_journal(&tick);
+            persist_metrics(&tick, vm);
+            check_qi_conservation(&tick, vm);
+            persist_replay_tick(&requests, &tick, vm);
+            persist_action_stats(&mut stats, &requests, &tick, &decision_records);
+            if tick.tick.is_multiple_of(EPOCH_TICKS) {
+                persist_agent_registry(vm);
+                persist_memories(vm, &memories);
+            }
+            persist_backup(backups, tick.tick);
+        }
+        if let Err(err) = decisions::append_decisions(&decision_records) {
+            warn!("failed to append llm decisions: {}", err);
         }
-        persist_structures(&tick.events)?;
-        persist_world_view(vm);
-        persist_action_stats(&requests, &tick);
 
-        state::set_status(
+        finish_tick(&mut pacer, tick.tick);
+        state::set_status_with_pacing(
             Status::Running,
             vm.world().tick(),
             Some("agent loop running (llm)".into()),
+            pacer.stats(),
         )
         .map_err(|e| e.to_string())?;
 
@@ -656,12 +2004,107 @@ fn run_llm_loop(
             None => {}
         }
 
-        if delay > Duration::ZERO {
-            std::thread::sleep(delay);
+        let pause = pacer.remaining();
+        if pause > Duration::ZERO {
+            control.wait_for_next_tick(pause);
         }
     }
 
-    Ok(())
+    {
+        let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+        persist_memories(vm, &memories);
+    }
+    flush_action_stats(&mut stats)
+}
+
+/// Plan the next action of every `(slot, agent, partner)` in `planned` with its LLM brain, up
+/// to `brains.concurrency` requests at a time, and print the decisions in order. An agent on
+/// the loop brain, or one that can't pay for thinking, takes the planner's deterministic
+/// fallback instead.
+fn plan_llm_requests(
+    vm: &Vm,
+    planned: &[(usize, AgentId, Option<AgentId>)],
+    brains: &Brains,
+    action_cycle: &[ActionArg],
+    memories: &mut HashMap<AgentId, BrainMemory>,
+    next_tick: u64,
+) -> Vec<(usize, ActionRequest, DecisionRecord)> {
+    // Thinking costs Qi; an agent that can't pay plans with the loop brain instead.
+    let can_think: Vec<bool> = planned
+        .iter()
+        .map(|(_, agent_id, _)| vm.world().can_afford_thinking(*agent_id))
+        .collect();
+    let mut taken: Vec<BrainMemory> = planned
+        .iter()
+        .map(|(_, agent_id, _)| memories.remove(agent_id).unwrap_or_default())
+        .collect();
+    let jobs = planned
+        .iter()
+        .zip(&can_think)
+        .zip(taken.iter_mut())
+        .map(|(((_, agent_id, _), can_think), memory)| PlanJob {
+            agent_id: *agent_id,
+            candidates: brains.actions(*agent_id, action_cycle),
+            memory,
+            client: brains.client(*agent_id).filter(|_| *can_think),
+        })
+        .collect();
+    let decisions = plan_many_with_llm(vm, jobs, next_tick, brains.concurrency);
+
+    let mut planned_requests = Vec::with_capacity(planned.len());
+    for ((((slot, agent_id, partner), can_think), memory), mut decision) in
+        planned.iter().zip(can_think).zip(taken).zip(decisions)
+    {
+        let client = brains.client(*agent_id);
+        if client.is_none() {
+            decision.response = "loop brain; no LLM call".to_string();
+        } else if !can_think {
+            decision.response = format!(
+                "cannot afford thinking ({} qi); fallback to loop",
+                vm.world().thinking_cost()
+            );
+        }
+
+        println!("Tick {} | LLM planner | Agent {}", next_tick, agent_id);
+        println!(" 1) State     : {}", decision.summary);
+        println!(
+            " 2) Goal      : {}",
+            client.map_or(harimu::DEFAULT_AGENT_GOAL, LlmClient::goal)
+        );
+        println!(" 3) Prompt    : {}", decision.prompt);
+        println!(" 4) LLM reply : {}", decision.response);
+        println!(" 5) Decision  : {:?}", decision.action);
+        println!(" 6) Tx        : signed+submitted (simulated)");
+        println!(" 7) Memory    : {} notes", memory.notes.len());
+        println!(" 8) LLM model : {:?} {}", decision.provider, decision.model);
+        println!(" 9) Latency   : {} ms", decision.latency_ms);
+        let record = DecisionRecord::from_decision(next_tick, *agent_id, &decision);
+        memories.insert(*agent_id, memory);
+
+        if !decision.llm_ok && client.is_some() {
+            println!(
+                "LLM {}; falling back to loop action this tick. Reason: {}",
+                if can_think { "unreachable" } else { "skipped" },
+                decision.response
+            );
+        }
+
+        let mut action = decision.action;
+        if let Action::Attack { target: 0 } = action
+            && let Some(actual) = *partner
+        {
+            action = Action::Attack { target: actual };
+        }
+
+        let request = ActionRequest::new(*agent_id, action);
+        let request = if decision.llm_ok {
+            request.thought()
+        } else {
+            request
+        };
+        planned_requests.push((*slot, request, record));
+    }
+    planned_requests
 }
 
 fn default_llm_actions(agent_ids: &[AgentId]) -> Vec<ActionArg> {
@@ -671,6 +2114,9 @@ fn default_llm_actions(agent_ids: &[AgentId]) -> Vec<ActionArg> {
             dy: 0,
             dz: 0,
         },
+        ActionArg::MoveTo {
+            target: Position::origin(),
+        },
         ActionArg::Scan,
         ActionArg::BuildStructure {
             kind: StructureKind::Basic,
@@ -679,18 +2125,26 @@ fn default_llm_actions(agent_ids: &[AgentId]) -> Vec<ActionArg> {
             kind: StructureKind::Programmable,
         },
         ActionArg::HarvestOre {
-            ore: OreKind::Qi,
+            ore: OreKind::QI,
             source_id: 0,
         },
         ActionArg::HarvestOre {
-            ore: OreKind::Transistor,
+            ore: OreKind::TRANSISTOR,
             source_id: 0,
         },
     ];
 
     if agent_ids.len() > 1 {
         let partner = agent_ids[0];
-        actions.push(ActionArg::Reproduce { partner });
+        actions.push(ActionArg::Reproduce { partner: 0 });
+        actions.push(ActionArg::Trade {
+            partner,
+            offer_ore: OreKind::QI,
+            offer_amount: 1,
+            want_ore: OreKind::TRANSISTOR,
+            want_amount: 1,
+        });
+        actions.push(ActionArg::Attack { target: partner });
     }
 
     actions
@@ -718,7 +2172,7 @@ fn default_loop_actions(agent_ids: &[AgentId]) -> Vec<ActionArg> {
             kind: StructureKind::Basic,
         },
         ActionArg::HarvestOre {
-            ore: OreKind::Transistor,
+            ore: OreKind::TRANSISTOR,
             source_id: 0,
         },
         ActionArg::BuildStructure {
@@ -727,55 +2181,29 @@ fn default_loop_actions(agent_ids: &[AgentId]) -> Vec<ActionArg> {
     ];
 
     if agent_ids.len() > 1 {
-        let partner = agent_ids[0];
-        actions.push(ActionArg::Reproduce { partner });
+        // Partner 0 lets the VM pick any eligible agent in the same zone.
+        actions.push(ActionArg::Reproduce { partner: 0 });
     }
 
     actions
 }
 
-fn persist_structures(events: &[Event]) -> Result<(), String> {
-    use std::collections::HashSet;
-
-    let mut store = load_structure_store().map_err(|e| e.to_string())?;
-    let mut seen: HashSet<u64> = store.structures.iter().map(|s| s.id).collect();
-    let mut updated = false;
-
-    for event in events {
-        if let Event::StructureBuilt {
-            agent_id,
-            kind,
-            position,
-            structure_id,
-        } = event
-        {
-            if seen.insert(*structure_id) {
-                store.structures.push(StructureRecord {
-                    id: *structure_id,
-                    kind: *kind,
-                    position: *position,
-                    zone: position.zone(),
-                    owner: *agent_id,
-                });
-                updated = true;
-            }
-        }
-    }
-
-    if updated {
-        save_structure_store(&store).map_err(|e| e.to_string())?;
-    }
-
-    Ok(())
+fn persist_structures(structures: &mut StructurePersister, events: &[Event]) -> Result<(), String> {
+    structures
+        .persist(events)
+        .map(|_| ())
+        .map_err(|e| format!("failed to save structures: {}", e))
 }
 
 fn record_outcome(
     memories: &mut HashMap<AgentId, BrainMemory>,
     tick: &TickResult,
+    vm: &Vm,
     agent_id: AgentId,
 ) {
     const MEMORY_LIMIT: usize = 8;
     let memory = memories.entry(agent_id).or_default();
+    memory.constraints.clear();
 
     if let Some(rej) = tick
         .rejections
@@ -786,6 +2214,7 @@ fn record_outcome(
             "tick {}: action {:?} rejected ({})",
             tick.tick, rej.request.action, rej.error
         ));
+        memory.constraints = rejection_constraints(vm, agent_id, &rej.error);
     } else {
         memory.notes.push(format!(
             "tick {}: action applied (events={})",
@@ -802,14 +2231,23 @@ fn record_outcome(
 
 fn reactive_fallback(action: &ActionArg) -> ActionArg {
     match action {
-        ActionArg::Move { .. } => ActionArg::Scan,
+        ActionArg::Move { .. } | ActionArg::MoveTo { .. } => ActionArg::Scan,
         ActionArg::Scan => ActionArg::Idle,
         ActionArg::BuildStructure { .. } => ActionArg::HarvestOre {
-            ore: OreKind::Qi,
+            ore: OreKind::QI,
             source_id: 0,
         },
-        ActionArg::HarvestOre { .. } => ActionArg::Scan,
-        ActionArg::Reproduce { .. } => ActionArg::Idle,
+        ActionArg::HarvestOre { .. } | ActionArg::HarvestAt { .. } => ActionArg::Scan,
+        ActionArg::Reproduce { .. } | ActionArg::Trade { .. } => ActionArg::Idle,
+        ActionArg::Attack { .. } => ActionArg::Scan,
+        ActionArg::Dig { .. } | ActionArg::Place { .. } => ActionArg::Scan,
+        ActionArg::Bundle(_) => ActionArg::Scan,
+        ActionArg::Rest => ActionArg::Scan,
+        ActionArg::Say { .. } => ActionArg::Scan,
+        ActionArg::ClaimZone | ActionArg::SetToll { .. } => ActionArg::Scan,
+        ActionArg::PostOrder { .. } | ActionArg::Craft { .. } => ActionArg::Scan,
+        ActionArg::Repair { .. } | ActionArg::DemolishStructure { .. } => ActionArg::Scan,
+        ActionArg::GiveQi { .. } => ActionArg::Idle,
         ActionArg::Idle => ActionArg::Scan,
     }
 }
@@ -817,38 +2255,165 @@ fn reactive_fallback(action: &ActionArg) -> ActionArg {
 fn persist_world_view(vm: &Vm) {
     let snapshot = vm.snapshot();
     if let Err(err) = save_world_snapshot(&snapshot) {
-        eprintln!("warning: failed to write world snapshot: {}", err);
+        warn!("failed to write world snapshot: {}", err);
     }
     if let Err(err) = save_world_snapshot_tick(&snapshot) {
-        eprintln!("warning: failed to write tick snapshot: {}", err);
+        warn!("failed to write tick snapshot: {}", err);
     }
 }
 
-fn persist_action_stats(requests: &[ActionRequest], tick: &TickResult) {
-    let mut store = match harimu::load_action_stats() {
-        Ok(s) => s,
-        Err(err) => {
-            eprintln!("warning: failed to load action stats: {}", err);
-            return;
+/// Save the zone claims after a tick that changed them.
+fn persist_territories(tick: &TickResult, vm: &Vm) {
+    if !tick
+        .events
+        .iter()
+        .any(|e| matches!(e, Event::ZoneClaimed { .. }))
+    {
+        return;
+    }
+    if let Err(err) = save_territory_store(vm.world().territories()) {
+        warn!("failed to save territories: {}", err);
+    }
+}
+
+fn persist_world_state(vm: &Vm) {
+    if let Err(err) = save_world_state(&vm.world().to_persistent()) {
+        warn!("failed to save world state: {}", err);
+    }
+}
+
+/// Write the agents' live state back to the registry (see `agents::sync_from_world`).
+fn persist_agent_registry(vm: &Vm) {
+    let synced = agents::load().and_then(|mut registry| {
+        if agents::sync_from_world(&mut registry, vm.world()) > 0 {
+            agents::save(&registry)?;
         }
-    };
+        Ok(())
+    });
+    if let Err(err) = synced {
+        warn!("failed to sync agents to the registry: {}", err);
+    }
+}
 
-    for req in requests {
-        let rejected = tick
-            .rejections
-            .iter()
-            .any(|r| r.request.agent_id == req.agent_id && r.request.action == req.action);
-        if rejected {
+/// What the run's agents remember from earlier runs, by agent name.
+fn load_memories(vm: &Vm, agent_ids: &[AgentId]) -> HashMap<AgentId, BrainMemory> {
+    let mut memories = HashMap::new();
+    for agent_id in agent_ids {
+        let Some(agent) = vm.world().agent(*agent_id) else {
             continue;
+        };
+        match load_memory(&agent.name) {
+            Ok(Some(memory)) => {
+                memories.insert(*agent_id, memory);
+            }
+            Ok(None) => {}
+            Err(err) => warn!(agent_id, "agent starts without its memory: {}", err),
+        }
+    }
+    if !memories.is_empty() {
+        println!("Loaded the memory of {} agent(s)", memories.len());
+    }
+    memories
+}
+
+/// Save every agent's memory to `.harimu/memory/` so the next run picks it up.
+fn persist_memories(vm: &Vm, memories: &HashMap<AgentId, BrainMemory>) {
+    for (agent_id, memory) in memories {
+        let Some(agent) = vm.world().agent(*agent_id) else {
+            continue;
+        };
+        if let Err(err) = save_memory(&agent.name, memory) {
+            warn!(agent_id, "failed to save agent memory: {}", err);
+        }
+    }
+}
+
+fn persist_journal(tick: &TickResult) {
+    if let Err(err) = append_tick(tick) {
+        warn!("failed to append to event journal: {}", err);
+    }
+}
+
+fn persist_metrics(tick: &TickResult, vm: &Vm) {
+    if let Err(err) = append_tick_metrics(&TickMetrics::of(vm.stats(), tick)) {
+        warn!("failed to append tick metrics: {}", err);
+    }
+}
+
+/// Warn when the world holds more Qi than was infused and granted (see `QiAudit`).
+fn check_qi_conservation(tick: &TickResult, vm: &Vm) {
+    let audit = vm.qi_audit();
+    if let Some(limit) = audit.limit().filter(|_| audit.excess() > 0) {
+        warn!(
+            tick = tick.tick,
+            "Qi conservation violated: the world holds {} Qi, {} more than the {} it may hold",
+            audit.held(),
+            audit.excess(),
+            limit
+        );
+    }
+}
+
+fn persist_replay_tick(requests: &[ActionRequest], tick: &TickResult, vm: &Vm) {
+    let world_hash = vm.snapshot().meta.world_hash;
+    if let Err(err) = append_replay_tick(requests, tick, world_hash) {
+        warn!("failed to append to replay log: {}", err);
+    }
+}
+
+/// Load the zones agents are near and evict the rest, with `--page-zones`.
+fn page_world(vm: &mut Vm, radius: Option<i32>) {
+    let Some(radius) = radius else {
+        return;
+    };
+    let paged = lock_data_dir()
+        .map_err(|e| e.to_string())
+        .and_then(|_lock| page_zones(vm, radius).map_err(|e| e.to_string()));
+    if let Err(err) = paged {
+        warn!("failed to page zones: {}", err);
+    }
+}
+
+fn persist_backup(schedule: Option<BackupSchedule>, tick: u64) {
+    let Some(schedule) = schedule.filter(|s| s.is_due(tick)) else {
+        return;
+    };
+    match harimu::create_backup(Some(AUTO_BACKUP_LABEL)) {
+        Ok(info) => println!("Backed up the data directory to {}", info.name),
+        Err(err) => {
+            warn!("failed to back up the data directory: {}", err);
+            return;
         }
-        record_successful_actions(&mut store, req.agent_id, std::iter::once(req.action));
     }
+    if let Err(err) = harimu::prune_backups(AUTO_BACKUP_LABEL, schedule.keep) {
+        warn!("failed to prune old backups: {}", err);
+    }
+}
 
-    if let Err(err) = save_action_stats(&store) {
-        eprintln!("warning: failed to save action stats: {}", err);
+fn persist_action_stats(
+    stats: &mut StatsRecorder,
+    requests: &[ActionRequest],
+    tick: &TickResult,
+    decision_records: &[DecisionRecord],
+) {
+    let actions = requests
+        .iter()
+        .filter(|req| !tick.rejections.iter().any(|r| r.request == **req))
+        .flat_map(|req| req.actions().map(|action| (req.agent_id, action)))
+        .collect();
+    if let Err(err) = stats.record_tick(tick.tick, actions, decision_records) {
+        warn!("failed to record action stats: {}", err);
     }
 }
 
+/// Write out the stats still held in memory when a loop ends.
+fn flush_action_stats(stats: &mut StatsRecorder) -> Result<(), String> {
+    let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+    stats
+        .flush()
+        .map_err(|e| format!("failed to save action stats: {}", e))
+}
+
 fn print_action_summary() -> Result<(), String> {
     let store = harimu::load_action_stats().map_err(|e| e.to_string())?;
     if store.per_agent.is_empty() {
@@ -859,15 +2424,38 @@ fn print_action_summary() -> Result<(), String> {
     println!("Action summary per agent:");
     for (agent, stats) in store.per_agent.iter() {
         println!(
-            " - agent {} | move={} scan={} build={} harvest={} reproduce={} idle={}",
+            " - agent {} | move={} scan={} build={} harvest={} reproduce={} trade={} attack={} dig={} place={} rest={} say={} claim={} toll={} order={} craft={} repair={} demolish={} give={} idle={}",
             agent,
             stats.move_count,
             stats.scan_count,
             stats.build_count,
             stats.harvest_count,
             stats.reproduce_count,
+            stats.trade_count,
+            stats.attack_count,
+            stats.dig_count,
+            stats.place_count,
+            stats.rest_count,
+            stats.say_count,
+            stats.claim_count,
+            stats.toll_count,
+            stats.order_count,
+            stats.craft_count,
+            stats.repair_count,
+            stats.demolish_count,
+            stats.give_count,
             stats.idle_count
         );
+        if stats.llm_decisions > 0 {
+            println!(
+                "   llm decisions={} ok={} fallback={} success_rate={:.1}% avg_latency={}ms",
+                stats.llm_decisions,
+                stats.llm_ok_count,
+                stats.llm_fallback_count,
+                stats.llm_success_rate() * 100.0,
+                stats.llm_avg_latency_ms()
+            );
+        }
     }
     Ok(())
 }
@@ -898,14 +2486,14 @@ fn print_tick(tick: &TickResult, vm: &Vm, agent_id: AgentId) {
 
     if let Some(agent) = vm.world().agent(agent_id) {
         println!(
-            "Agent #{} | qi={} | transistors={} | position=({}, {}, {}) | alive={} | age={}",
+            "Agent #{} | {} | position=({}, {}, {}) | alive={} | hp={} | age={}",
             agent.id,
-            agent.qi,
-            agent.transistors,
+            agent.inventory,
             agent.position.x,
             agent.position.y,
             agent.position.z,
             agent.alive,
+            agent.hp,
             agent.age
         );
         let (structures_built, offspring) = agent_counters(vm, agent_id);
@@ -970,6 +2558,30 @@ fn describe_event(vm: &Vm, event: &Event) -> String {
             to.y,
             to.z
         ),
+        Event::PathProgress {
+            agent_id,
+            target,
+            remaining,
+        } => format!(
+            "agent {} heading to ({}, {}, {}): {} step(s) left",
+            agent_label(vm, *agent_id),
+            target.x,
+            target.y,
+            target.z,
+            remaining
+        ),
+        Event::PathCompleted { agent_id, target } => format!(
+            "agent {} arrived at ({}, {}, {})",
+            agent_label(vm, *agent_id),
+            target.x,
+            target.y,
+            target.z
+        ),
+        Event::PartnerResolved { agent_id, partner } => format!(
+            "agent {} matched with reproduction partner {}",
+            agent_label(vm, *agent_id),
+            agent_label(vm, *partner)
+        ),
         Event::AgentDied { agent_id, reason } => {
             format!("agent {} died: {:?}", agent_label(vm, *agent_id), reason)
         }
@@ -1019,6 +2631,317 @@ fn describe_event(vm: &Vm, event: &Event) -> String {
             "{} node {} drained at ({}, {}, {})",
             ore, source_id, position.x, position.y, position.z
         ),
+        Event::OreNodeLevelSet {
+            ore,
+            source_id,
+            from,
+            to,
+        } => format!("{} node {} level set {} -> {}", ore, source_id, from, to),
+        Event::OreNodeRemoved {
+            ore,
+            source_id,
+            position,
+            remaining,
+        } => format!(
+            "{} node {} removed at ({}, {}, {}) with {} left",
+            ore, source_id, position.x, position.y, position.z, remaining
+        ),
+        Event::RemainsDropped {
+            agent_id,
+            source_id,
+            ore,
+            amount,
+            position,
+        } => format!(
+            "agent {} left {} {} as node {} at ({}, {}, {})",
+            agent_label(vm, *agent_id),
+            amount,
+            ore,
+            source_id,
+            position.x,
+            position.y,
+            position.z
+        ),
+        Event::OreNodeMoved {
+            ore,
+            source_id,
+            from,
+            to,
+        } => format!(
+            "{} node {} moved ({}, {}, {}) -> ({}, {}, {})",
+            ore, source_id, from.x, from.y, from.z, to.x, to.y, to.z
+        ),
+        Event::BlockDug {
+            agent_id,
+            position,
+            block,
+        } => format!(
+            "agent {} dug {} at ({}, {}, {})",
+            agent_label(vm, *agent_id),
+            block,
+            position.x,
+            position.y,
+            position.z
+        ),
+        Event::BlockPlaced {
+            agent_id,
+            position,
+            block,
+        } => format!(
+            "agent {} placed {} at ({}, {}, {})",
+            agent_label(vm, *agent_id),
+            block,
+            position.x,
+            position.y,
+            position.z
+        ),
+        Event::StructureUpkeepPaid {
+            structure_id,
+            owner,
+            amount,
+        } => format!(
+            "agent {} paid {} qi upkeep for structure {}",
+            agent_label(vm, *owner),
+            amount,
+            structure_id
+        ),
+        Event::StructureStalled {
+            structure_id,
+            owner,
+        } => format!(
+            "structure {} stalled: owner {} could not pay upkeep",
+            structure_id,
+            agent_label(vm, *owner)
+        ),
+        Event::AgentRested {
+            agent_id,
+            structure_id,
+            amount,
+        } => format!(
+            "agent {} rested at structure {} and recovered {} Qi",
+            agent_label(vm, *agent_id),
+            structure_id,
+            amount
+        ),
+        Event::ZoneClaimed {
+            agent_id,
+            zone,
+            cost,
+        } => format!(
+            "agent {} claimed zone ({}, {}, {}) for {} Qi",
+            agent_label(vm, *agent_id),
+            zone.x,
+            zone.y,
+            zone.z,
+            cost
+        ),
+        Event::TollSet {
+            agent_id,
+            zone,
+            harvest,
+            build,
+        } => format!(
+            "agent {} set the tolls of zone ({}, {}, {}): harvest {} Qi, build {} Qi",
+            agent_label(vm, *agent_id),
+            zone.x,
+            zone.y,
+            zone.z,
+            harvest,
+            build
+        ),
+        Event::TollPaid {
+            agent_id,
+            owner,
+            zone,
+            amount,
+        } => format!(
+            "agent {} paid agent {} a {} Qi toll in zone ({}, {}, {})",
+            agent_label(vm, *agent_id),
+            agent_label(vm, *owner),
+            amount,
+            zone.x,
+            zone.y,
+            zone.z
+        ),
+        Event::TollCollected {
+            owner,
+            from,
+            zone,
+            amount,
+        } => format!(
+            "agent {} collected a {} Qi toll from agent {} in zone ({}, {}, {})",
+            agent_label(vm, *owner),
+            amount,
+            agent_label(vm, *from),
+            zone.x,
+            zone.y,
+            zone.z
+        ),
+        Event::OrderPosted {
+            agent_id,
+            order_id,
+            side,
+            ore,
+            amount,
+            price,
+        } => format!(
+            "agent {} posted order {}: {} {} {} at {} Qi",
+            agent_label(vm, *agent_id),
+            order_id,
+            side,
+            amount,
+            ore,
+            price
+        ),
+        Event::OrderFilled {
+            buyer,
+            seller,
+            ore,
+            amount,
+            price,
+            ..
+        } => format!(
+            "agent {} bought {} {} from agent {} at {} Qi",
+            agent_label(vm, *buyer),
+            amount,
+            ore,
+            agent_label(vm, *seller),
+            price
+        ),
+        Event::OrderExpired {
+            agent_id,
+            order_id,
+            item,
+            amount,
+        } => format!(
+            "order {} of agent {} expired; {} {} returned",
+            order_id,
+            agent_label(vm, *agent_id),
+            amount,
+            item
+        ),
+        Event::ItemCrafted {
+            agent_id,
+            recipe,
+            item,
+            amount,
+        } => format!(
+            "agent {} crafted {} {} ({})",
+            agent_label(vm, *agent_id),
+            amount,
+            item,
+            recipe
+        ),
+        Event::MessageDelivered { from, to, message } => format!(
+            "agent {} told agent {}: {:?}",
+            agent_label(vm, *from),
+            agent_label(vm, *to),
+            message
+        ),
+        Event::StructureProduced {
+            structure_id,
+            recipient,
+            item,
+            amount,
+        } => format!(
+            "structure {} produced {} {} for agent {}",
+            structure_id,
+            amount,
+            item,
+            agent_label(vm, *recipient)
+        ),
+        Event::StructureCollected {
+            structure_id,
+            source_id,
+            amount,
+        } => format!(
+            "structure {} collected {} qi from node {}",
+            structure_id, amount, source_id
+        ),
+        Event::StructureStored {
+            structure_id,
+            item,
+            amount,
+        } => format!(
+            "structure {} stored {} {} from its owner",
+            structure_id, amount, item
+        ),
+        Event::StructureScriptFaulted {
+            structure_id,
+            error,
+        } => format!("structure {} program faulted: {}", structure_id, error),
+        Event::StructureDecayed {
+            structure_id,
+            durability,
+        } => format!(
+            "structure {} decayed to {} durability",
+            structure_id, durability
+        ),
+        Event::StructureCollapsed {
+            structure_id,
+            kind,
+            owner,
+            position,
+        } => format!(
+            "{} structure {} of agent {} collapsed at ({},{},{})",
+            kind,
+            structure_id,
+            agent_label(vm, *owner),
+            position.x,
+            position.y,
+            position.z
+        ),
+        Event::StructureRepaired {
+            agent_id,
+            structure_id,
+            durability,
+        } => format!(
+            "agent {} repaired structure {} to {} durability",
+            agent_label(vm, *agent_id),
+            structure_id,
+            durability
+        ),
+        Event::QiGiven {
+            agent_id,
+            to,
+            amount,
+        } => format!(
+            "agent {} gave {} Qi to agent {}",
+            agent_label(vm, *agent_id),
+            amount,
+            agent_label(vm, *to)
+        ),
+        Event::QiReceived {
+            agent_id,
+            from,
+            amount,
+        } => format!(
+            "agent {} received {} Qi from agent {}",
+            agent_label(vm, *agent_id),
+            amount,
+            agent_label(vm, *from)
+        ),
+        Event::StructureDemolished {
+            agent_id,
+            structure_id,
+            kind,
+            refund,
+            ..
+        } => format!(
+            "agent {} demolished {} structure {}, getting back {}",
+            agent_label(vm, *agent_id),
+            kind,
+            structure_id,
+            if refund.is_empty() {
+                "nothing".to_string()
+            } else {
+                refund
+                    .iter()
+                    .map(|(item, amount)| format!("{} {}", amount, item))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        ),
         Event::ActionObserved { agent_id, action } => {
             format!(
                 "agent {} observed action {}",
@@ -1026,22 +2949,83 @@ fn describe_event(vm: &Vm, event: &Event) -> String {
                 action
             )
         }
+        Event::AgentAttacked {
+            attacker,
+            target,
+            damage,
+            remaining_hp,
+        } => format!(
+            "agent {} attacked {} for {} damage (hp left={})",
+            agent_label(vm, *attacker),
+            agent_label(vm, *target),
+            damage,
+            remaining_hp
+        ),
+        Event::TradeCompleted {
+            agent_a,
+            agent_b,
+            ore_a,
+            amount_a,
+            ore_b,
+            amount_b,
+        } => format!(
+            "agents {} and {} traded {} {} for {} {}",
+            agent_label(vm, *agent_a),
+            agent_label(vm, *agent_b),
+            amount_a,
+            ore_a,
+            amount_b,
+            ore_b
+        ),
+        Event::AdminIntervened { agent_id, action } => format!(
+            "admin: {} for agent {}",
+            describe_admin_action(action),
+            agent_label(vm, *agent_id)
+        ),
+        Event::BrainSwapped { agent_id, brain } => format!(
+            "agent {} switched to the {} brain",
+            agent_label(vm, *agent_id),
+            brain
+        ),
+        Event::AgentStarving {
+            agent_id,
+            ticks_left,
+        } => format!(
+            "agent {} has no Qi and starves in {} tick(s)",
+            agent_label(vm, *agent_id),
+            ticks_left
+        ),
+        Event::AgentHibernated { agent_id } => {
+            format!("agent {} went dormant", agent_label(vm, *agent_id))
+        }
+        Event::AgentWoke { agent_id, by } => match by {
+            Some(by) => format!(
+                "agent {} woke as agent {} acted nearby",
+                agent_label(vm, *agent_id),
+                agent_label(vm, *by)
+            ),
+            None => format!("agent {} woke for a request", agent_label(vm, *agent_id)),
+        },
         Event::ScanReport {
             agent_id,
             position,
             qi,
             nearby_qi_sources,
             nearby_structures,
+            threats,
         } => format!(
-            "agent {} scan at ({}, {}, {}) qi={} | ore_sources={} | structures={}",
+            "agent {} scan at ({}, {}, {}) qi={} | ore_sources={} | structures={} | threats={}",
             agent_label(vm, *agent_id),
             position.x,
             position.y,
             position.z,
             qi,
             nearby_qi_sources.len(),
-            nearby_structures.len()
+            nearby_structures.len(),
+            threats.len()
         ),
+        // `Event` is non-exhaustive; a variant without a description still shows its fields.
+        other => format!("{:?}", other),
     }
 }
 
@@ -1060,53 +3044,26 @@ fn agent_counters(vm: &Vm, agent_id: AgentId) -> (usize, usize) {
     (structures, offspring)
 }
 
-fn launch_background_start(
-    agent: Option<String>,
-    qi: harimu::Qi,
-    position: Position,
-    ticks: Option<u64>,
-    brain: BrainMode,
-    llm_host: String,
-    llm_model: String,
-    llm_timeout_ms: u64,
-    llm_provider: LlmProvider,
-    llm_api_key: Option<String>,
-    tick_rate: Option<f64>,
-    delay_ms: u64,
-    actions: Vec<ActionArg>,
-) -> Result<(), String> {
+/// The data directory as an absolute path, for child processes started from another directory.
+pub(crate) fn data_dir_for_child() -> Result<PathBuf, String> {
+    std::path::absolute(paths::data_dir()).map_err(|e| format!("data dir: {}", e))
+}
+
+fn launch_background_start(start: &StartArgs) -> Result<(), String> {
     let exe = env::current_exe().map_err(|e| format!("current_exe: {}", e))?;
-    let mut args = render_start_args(
-        agent.clone(),
-        qi,
-        position,
-        ticks,
-        brain,
-        llm_host,
-        llm_model,
-        llm_timeout_ms,
-        llm_provider,
-        llm_api_key.clone(),
-        tick_rate,
-        delay_ms,
-        &actions,
-    );
+    let mut args = render_start_args(start);
     args.push("--background-child".into());
+    args.push("--log-format".into());
+    args.push(logging::format().as_str().into());
 
     let child = std::process::Command::new(exe)
         .args(&args)
+        .env(paths::HOME_ENV, data_dir_for_child()?)
         .stdout(std::process::Stdio::null())
         .stderr(std::process::Stdio::null())
         .spawn()
         .map_err(|e| format!("failed to spawn background process: {}", e))?;
 
-    let pid_path = PathBuf::from(PID_FILE);
-    if let Some(parent) = pid_path.parent() {
-        let _ = fs::create_dir_all(parent);
-    }
-    fs::write(&pid_path, format!("{}", child.id()))
-        .map_err(|e| format!("failed to write pid file {}: {}", pid_path.display(), e))?;
-
     println!(
         "Started background agent loop (pid={}). Stop with `harimu stop`.",
         child.id()
@@ -1114,66 +3071,235 @@ fn launch_background_start(
     Ok(())
 }
 
-fn render_start_args(
-    agent: Option<String>,
-    qi: harimu::Qi,
-    position: Position,
-    ticks: Option<u64>,
-    brain: BrainMode,
-    llm_host: String,
-    llm_model: String,
-    llm_timeout_ms: u64,
-    llm_provider: LlmProvider,
-    llm_api_key: Option<String>,
-    tick_rate: Option<f64>,
-    delay_ms: u64,
-    actions: &[ActionArg],
-) -> Vec<String> {
+/// Register the `[[structure_effects]]` of `.harimu/config.toml` on top of the built-in ones.
+pub(super) fn add_structure_effects(vm: &mut Vm) -> Result<(), String> {
+    let specs = load_store_config()
+        .map_err(|e| e.to_string())?
+        .structure_effects;
+    if !specs.is_empty() {
+        println!("{} structure effect(s) from config.toml", specs.len());
+    }
+    for spec in specs {
+        vm.add_effect_spec(spec);
+    }
+    Ok(())
+}
+
+/// Add the `[recipes]` and `[build_costs]` of `.harimu/config.toml` to the built-in recipes.
+pub(super) fn add_recipes(vm: &mut Vm) -> Result<(), String> {
+    let config = load_store_config().map_err(|e| e.to_string())?;
+    if config.recipes.is_empty() && config.build_costs.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "{} recipe(s) and {} build cost(s) from config.toml",
+        config.recipes.len(),
+        config.build_costs.len()
+    );
+    let mut recipes = RecipeBook::default();
+    recipes
+        .extend(config.recipes, config.build_costs)
+        .map_err(|e| format!("config.toml: {}", e))?;
+    vm.set_recipes(recipes);
+    Ok(())
+}
+
+/// Merge `start`'s flags over `HARIMU_*` variables, the `[start]` table of
+/// `.harimu/config.toml` and the defaults.
+fn resolve_start_config(start: &StartArgs) -> Result<EffectiveConfig, String> {
+    let file = load_store_config().map_err(|e| e.to_string())?.start;
+    let env = StartSettings::from_env()?;
+    let flags = StartSettings {
+        brain: start.brain,
+        llm_provider: start.llm_provider,
+        llm_host: start.llm_host.clone(),
+        llm_model: start.llm_model.clone(),
+        llm_timeout_ms: start.llm_timeout_ms,
+        tick_rate: start.tick_rate,
+        delay_ms: start.delay_ms,
+    };
+    EffectiveConfig::resolve(&file, &env, &flags)
+}
+
+fn render_start_args(start: &StartArgs) -> Vec<String> {
     let mut args = Vec::new();
     args.push("start".into());
-    if let Some(agent) = agent {
+    if let Some(agent) = &start.agent {
         args.push("--agent".into());
-        args.push(agent);
+        args.push(agent.clone());
+    }
+    if !start.agents.is_empty() {
+        args.push("--agents".into());
+        args.push(start.agents.join(","));
+    }
+    for group in &start.groups {
+        args.push("--group".into());
+        args.push(group.to_string());
     }
     args.push("--qi".into());
-    args.push(format!("{}", qi));
+    args.push(format!("{}", start.qi));
+    let position = start.position.0;
     args.push("--position".into());
     args.push(format!("{},{},{}", position.x, position.y, position.z));
-    if let Some(t) = ticks {
+    for placed in &start.positions_for {
+        let p = placed.position;
+        args.push("--position-for".into());
+        args.push(format!("{}={},{},{}", placed.agent, p.x, p.y, p.z));
+    }
+    if start.scatter > 0 {
+        args.push("--scatter".into());
+        args.push(start.scatter.to_string());
+    }
+    if let Some(t) = start.ticks {
         args.push("--ticks".into());
         args.push(format!("{}", t));
     }
-    args.push("--llm-host".into());
-    args.push(llm_host);
-    args.push("--llm-model".into());
-    args.push(llm_model);
-    args.push("--llm-timeout-ms".into());
-    args.push(format!("{}", llm_timeout_ms));
-    if let Some(key) = llm_api_key {
+    if let Some(host) = &start.llm_host {
+        args.push("--llm-host".into());
+        args.push(host.clone());
+    }
+    if let Some(model) = &start.llm_model {
+        args.push("--llm-model".into());
+        args.push(model.clone());
+    }
+    if let Some(timeout) = start.llm_timeout_ms {
+        args.push("--llm-timeout-ms".into());
+        args.push(timeout.to_string());
+    }
+    if let Some(key) = &start.llm_api_key {
         args.push("--llm-api-key".into());
-        args.push(key);
+        args.push(key.clone());
     }
-    if let Some(rate) = tick_rate {
+    if let Some(rate) = start.tick_rate {
         args.push("--tick-rate".into());
         args.push(rate.to_string());
-    } else {
+    }
+    if let Some(delay) = start.delay_ms {
         args.push("--delay-ms".into());
-        args.push(delay_ms.to_string());
+        args.push(delay.to_string());
+    }
+    if let Some(brain) = start.brain {
+        args.push("--brain".into());
+        args.push(brain_to_arg(brain).into());
+    }
+    if let Some(provider) = start.llm_provider {
+        args.push("--llm-provider".into());
+        args.push(llm_provider_to_arg(provider).into());
     }
 
-    args.push("--brain".into());
-    args.push(brain_to_arg(brain).into());
-    args.push("--llm-provider".into());
-    args.push(llm_provider_to_arg(llm_provider).into());
-
-    for action in actions {
+    for action in &start.actions {
         args.push("--action".into());
         args.push(render_action_arg(action));
     }
 
+    if start.sandbox {
+        args.push("--sandbox".into());
+    }
+    if start.hold_rate {
+        args.push("--hold-rate".into());
+    }
+    for tag in &start.tags {
+        args.push("--tag".into());
+        args.push(tag.clone());
+    }
+    args.push("--llm-concurrency".into());
+    args.push(start.llm_concurrency.to_string());
+    if let Some(requests) = start.llm_max_requests {
+        args.push("--llm-max-requests".into());
+        args.push(requests.to_string());
+    }
+    if let Some(tokens) = start.llm_max_tokens {
+        args.push("--llm-max-tokens".into());
+        args.push(tokens.to_string());
+    }
+    if start.llm_free_text {
+        args.push("--llm-free-text".into());
+    }
+    if start.llm_vector_memory {
+        args.push("--llm-vector-memory".into());
+        args.push("--llm-recall".into());
+        args.push(start.llm_recall.to_string());
+        if let Some(model) = &start.llm_embedding_model {
+            args.push("--llm-embedding-model".into());
+            args.push(model.clone());
+        }
+    }
+    if start.thinking_cost > 0 {
+        args.push("--thinking-cost".into());
+        args.push(start.thinking_cost.to_string());
+    }
+    if let Some(radius) = start.page_zones {
+        args.push("--page-zones".into());
+        args.push(radius.to_string());
+    }
+    if let Some(after) = start.hibernate_after {
+        args.push("--hibernate-after".into());
+        args.push(after.to_string());
+        args.push("--hibernate-radius".into());
+        args.push(start.hibernate_radius.to_string());
+        args.push("--dormant-age-every".into());
+        args.push(start.dormant_age_every.to_string());
+    }
+    if let Some(after) = start.starve_after {
+        args.push("--starve-after".into());
+        args.push(after.to_string());
+        args.push("--starve-warning".into());
+        args.push(start.starve_warning.to_string());
+    }
+    if let Some(path) = &start.firmware {
+        args.push("--firmware".into());
+        args.push(path.display().to_string());
+    }
+    args.push("--terrain-radius".into());
+    args.push(start.terrain_radius.to_string());
+    if let Some(seed) = start.seed {
+        args.push("--seed".into());
+        args.push(seed.to_string());
+    }
+    if let Some(run) = start.seed_from_run {
+        args.push("--seed-from-run".into());
+        args.push(run.to_string());
+    }
+    if start.fresh {
+        args.push("--fresh".into());
+    }
+    if let Some(format) = start.snapshot_format {
+        args.push("--snapshot-format".into());
+        args.push(snapshot_format_to_arg(format).into());
+    }
+    if let Some(path) = &start.scenario {
+        args.push("--scenario".into());
+        args.push(path.display().to_string());
+    }
+    if let Some(every) = start.backup_every {
+        args.push("--backup-every".into());
+        args.push(every.to_string());
+        args.push("--backup-keep".into());
+        args.push(start.backup_keep.to_string());
+    }
+
+    args
+}
+
+/// The run's `start` flags as recorded in the run registry, with the API key hidden.
+fn redacted_start_args(start: &StartArgs) -> Vec<String> {
+    let mut args = render_start_args(start);
+    args.remove(0);
+    if let Some(i) = args.iter().position(|arg| arg == "--llm-api-key")
+        && let Some(key) = args.get_mut(i + 1)
+    {
+        *key = "<redacted>".into();
+    }
     args
 }
 
+fn snapshot_format_to_arg(format: SnapshotFormat) -> &'static str {
+    match format {
+        SnapshotFormat::Json => "json",
+        SnapshotFormat::Msgpack => "msgpack",
+    }
+}
+
 fn brain_to_arg(brain: BrainMode) -> &'static str {
     match brain {
         BrainMode::Loop => "loop",
@@ -1185,6 +3311,8 @@ fn llm_provider_to_arg(provider: LlmProvider) -> &'static str {
     match provider {
         LlmProvider::Ollama => "ollama",
         LlmProvider::Openai => "openai",
+        LlmProvider::Gemini => "gemini",
+        LlmProvider::Llama => "llama",
     }
 }
 
@@ -1192,7 +3320,19 @@ fn render_action_arg(arg: &ActionArg) -> String {
     match arg {
         ActionArg::Scan => "scan".into(),
         ActionArg::Idle => "idle".into(),
+        ActionArg::Rest => "rest".into(),
+        ActionArg::ClaimZone => "claim_zone".into(),
+        ActionArg::SetToll { harvest, build } => format!("set_toll:{},{}", harvest, build),
+        ActionArg::PostOrder {
+            side,
+            ore,
+            amount,
+            price,
+        } => format!("order:{},{},{},{}", side, ore, amount, price),
         ActionArg::Move { dx, dy, dz } => format!("move:{},{},{}", dx, dy, dz),
+        ActionArg::MoveTo { target } => {
+            format!("move_to:{},{},{}", target.x, target.y, target.z)
+        }
         ActionArg::Reproduce { partner } => format!("reproduce:{}", partner),
         ActionArg::BuildStructure { kind } => format!("build:{}", kind),
         ActionArg::HarvestOre { ore, source_id } => {
@@ -1202,29 +3342,141 @@ fn render_action_arg(arg: &ActionArg) -> String {
                 format!("harvest:{}", ore)
             }
         }
+        ActionArg::HarvestAt { ore, dx, dy, dz } => format!("harvest:{}@{},{},{}", ore, dx, dy, dz),
+        ActionArg::Trade {
+            partner,
+            offer_ore,
+            offer_amount,
+            want_ore,
+            want_amount,
+        } => format!(
+            "trade:{},{},{},{},{}",
+            partner, offer_ore, offer_amount, want_ore, want_amount
+        ),
+        ActionArg::Attack { target } => format!("attack:{}", target),
+        ActionArg::Dig { dx, dy, dz } => format!("dig:{},{},{}", dx, dy, dz),
+        ActionArg::Place { block, dx, dy, dz } => {
+            format!("place:{},{},{},{}", block, dx, dy, dz)
+        }
+        ActionArg::Say { to, message } => format!("say:{},{}", to, message),
+        ActionArg::Craft { recipe } => format!("craft:{}", recipe),
+        ActionArg::Repair { structure_id } => format!("repair:{}", structure_id),
+        ActionArg::DemolishStructure { structure_id } => format!("demolish:{}", structure_id),
+        ActionArg::GiveQi { to, amount } => format!("give:{},{}", to, amount),
+        ActionArg::Bundle(parts) => parts
+            .iter()
+            .map(render_action_arg)
+            .collect::<Vec<_>>()
+            .join("+"),
     }
 }
 
-fn try_kill_background_process() {
-    let pid_path = PathBuf::from(PID_FILE);
-    let pid_str = match fs::read_to_string(&pid_path) {
-        Ok(s) => s,
-        Err(_) => return,
-    };
-    let pid: u32 = match pid_str.trim().parse() {
-        Ok(p) => p,
-        Err(_) => return,
-    };
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+
+    use harimu::store::{load_document, save_document};
+    use harimu::testing::TempDataDir;
 
-    let status = std::process::Command::new("kill")
-        .arg(format!("{}", pid))
-        .status();
-    match status {
-        Ok(s) if s.success() => {
-            println!("Stopped background process pid={}", pid);
-            let _ = fs::remove_file(pid_path);
+    use super::*;
+
+    fn parse(args: &[&str]) -> Command {
+        Cli::try_parse_from(args).unwrap().command
+    }
+
+    #[test]
+    fn read_only_mode_refuses_writes_but_serves_reads() {
+        let _data = TempDataDir::new("read-only");
+        save_document("notes.json", &vec![1u64]).unwrap();
+        set_read_only(true);
+        assert!(is_read_only());
+
+        let err = save_document("notes.json", &vec![2u64]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("read-only"), "{}", err);
+        let kept: Vec<u64> = load_document("notes.json", "notes").unwrap();
+        assert_eq!(kept, vec![1]);
+
+        let stop = parse(&["harimu", "stop"]);
+        assert!(stop.mutates());
+        assert!(dispatch(stop).unwrap_err().contains("read-only"));
+        let status = parse(&["harimu", "status"]);
+        assert!(!status.mutates());
+        assert!(dispatch(status).is_ok());
+    }
+
+    #[test]
+    fn a_dry_run_import_validates_without_saving() {
+        let data = TempDataDir::new("import-dry-run");
+        let csv = data.path().join("agents.csv");
+        fs::write(&csv, "name,qi\nada,5\nbo,7\n").unwrap();
+        let path = csv.to_str().unwrap();
+
+        let dry_run = parse(&["harimu", "agent", "import", "--dry-run", path]);
+        assert!(!dry_run.mutates());
+        dispatch(dry_run).unwrap();
+        assert!(agents::load().unwrap().agents.is_empty());
+
+        dispatch(parse(&["harimu", "agent", "import", path])).unwrap();
+        let store = agents::load().unwrap();
+        assert_eq!(store.agents.len(), 2);
+        assert!(store.name_taken("ada") && store.name_taken("bo"));
+    }
+
+    #[test]
+    fn group_overrides_reach_each_of_its_agents() {
+        let Command::Start(args) = parse(&[
+            "harimu",
+            "start",
+            "--agents",
+            "a,b",
+            "--group",
+            "x:agents=c,d;position=1,2,-3;brain=llm;model=tiny;action=scan;action=idle",
+        ]) else {
+            panic!("expected start");
+        };
+        assert_eq!(args.agents, ["a", "b"]);
+        let [group] = args.groups.as_slice() else {
+            panic!("expected one group, got {}", args.groups.len());
+        };
+        assert_eq!(group.name, "x");
+        assert_eq!(group.agents, ["c", "d"]);
+        assert_eq!(group.position, Some(Position { x: 1, y: 2, z: -3 }));
+        // The background child reads the group back from its Display form.
+        let reparsed: GroupArg = group.to_string().parse().unwrap();
+        assert_eq!(reparsed.to_string(), group.to_string());
+
+        let client = LlmClient::new(
+            "http://localhost:1",
+            "base",
+            LlmProvider::Ollama,
+            None,
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        let mut brains = Brains::new(BrainMode::Loop, Some(client), 1);
+        let phase = [ActionArg::Rest];
+        let render = |actions: &[ActionArg]| -> Vec<String> {
+            actions.iter().map(render_action_arg).collect()
+        };
+        // Agents 1 and 2 came from --agents, 3 and 4 from the group.
+        for member in [3, 4] {
+            brains.join_group(member, group).unwrap();
         }
-        Ok(_) => eprintln!("warning: failed to stop background pid {}", pid),
-        Err(_) => {}
+        for member in [3, 4] {
+            assert_eq!(brains.mode(member), BrainMode::Llm);
+            assert_eq!(brains.client(member).map(LlmClient::model), Some("tiny"));
+            assert_eq!(render(brains.actions(member, &phase)), ["scan", "idle"]);
+        }
+        for outsider in [1, 2] {
+            assert_eq!(brains.mode(outsider), BrainMode::Loop);
+            assert!(brains.client(outsider).is_none());
+            assert_eq!(render(brains.actions(outsider, &phase)), ["rest"]);
+        }
+
+        let mut no_client = Brains::new(BrainMode::Loop, None, 1);
+        let err = no_client.join_group(3, group).unwrap_err();
+        assert!(err.starts_with("group x:"), "{}", err);
+        assert!(Cli::try_parse_from(["harimu", "start", "--group", "x"]).is_err());
     }
 }
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use harimu::{Replayer, load_replay, replay_log_path};
+
+use super::describe_event;
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// Replay log to re-run (defaults to `.harimu/replay.jsonl`, written by the last `start`)
+    #[arg(long, value_name = "FILE")]
+    pub from: Option<PathBuf>,
+    /// Stop after this tick
+    #[arg(long)]
+    pub until: Option<u64>,
+    /// Print every replayed event
+    #[arg(short, long, default_value_t = false)]
+    pub verbose: bool,
+}
+
+pub(super) fn run_replay(args: ReplayArgs) -> Result<(), String> {
+    let path = args.from.unwrap_or_else(replay_log_path);
+    let records =
+        load_replay(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut replayer = Replayer::new(records)?;
+    println!(
+        "Replaying {} from tick {} (seed {})",
+        path.display(),
+        replayer.vm().world().tick(),
+        replayer.vm().world().seed()
+    );
+
+    let mut replayed = 0u64;
+    let mut mismatches = Vec::new();
+    while let Some((result, mismatch)) = replayer.step() {
+        replayed += 1;
+        if args.verbose {
+            for event in &result.events {
+                println!(
+                    "[tick {}] {}",
+                    result.tick,
+                    describe_event(replayer.vm(), event)
+                );
+            }
+            for rejection in &result.rejections {
+                println!("[tick {}] rejected: {}", result.tick, rejection.error);
+            }
+        }
+        if let Some(mismatch) = mismatch {
+            println!(
+                "tick {}: world hash {} does not match recorded {}",
+                mismatch.tick,
+                &mismatch.actual[..12.min(mismatch.actual.len())],
+                &mismatch.expected[..12.min(mismatch.expected.len())]
+            );
+            mismatches.push(mismatch);
+        }
+        if args.until.is_some_and(|until| result.tick >= until) {
+            break;
+        }
+    }
+
+    let world = replayer.vm().world();
+    println!(
+        "Replayed {} tick(s) to tick {}: {} agent(s) alive, {} structure(s)",
+        replayed,
+        world.tick(),
+        world.agents().filter(|(_, a)| a.alive).count(),
+        world.structures().len()
+    );
+    if mismatches.is_empty() {
+        println!("All ticks match the recording.");
+        Ok(())
+    } else {
+        Err(format!(
+            "{} tick(s) diverged from the recording (first at tick {})",
+            mismatches.len(),
+            mismatches[0].tick
+        ))
+    }
+}
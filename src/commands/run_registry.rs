@@ -0,0 +1,243 @@
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+use harimu::{
+    RunRecord, RunStore, TagSummary,
+    state::{self, Status},
+};
+
+#[derive(Subcommand)]
+pub enum RunsCommand {
+    /// List recorded runs, oldest first
+    List {
+        /// Only runs with this tag (see `start --tag`)
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Show everything recorded about one run
+    Show {
+        /// Run id (see `runs list`), or `latest`
+        id: String,
+    },
+    /// Average score, survival and economy of the runs under each tag, side by side
+    Compare {
+        /// Tag to compare (repeatable), e.g. --tag prompt-v1 --tag prompt-v2
+        #[arg(long = "tag", value_name = "TAG", required = true)]
+        tags: Vec<String>,
+        /// Also write the comparison to this CSV file, one row per tag
+        #[arg(long, value_name = "PATH")]
+        csv: Option<PathBuf>,
+    },
+}
+
+pub(super) fn run_runs(cmd: RunsCommand) -> Result<(), String> {
+    let runs = RunStore::load().map_err(|e| e.to_string())?;
+    // An unfinished run is still going only if it is the newest and the runtime says so.
+    let running = state::load_state()
+        .map_err(|e| e.to_string())?
+        .is_some_and(|s| s.status == Status::Running);
+    let live_id = runs.latest().filter(|_| running).map(|run| run.id);
+
+    match cmd {
+        RunsCommand::List { tag } => {
+            if runs.runs.is_empty() {
+                println!("No runs recorded yet; `harimu start` records one per run.");
+                return Ok(());
+            }
+            let listed: Vec<&RunRecord> = match &tag {
+                Some(tag) => runs.tagged(tag).collect(),
+                None => runs.runs.iter().collect(),
+            };
+            if let (Some(tag), true) = (&tag, listed.is_empty()) {
+                println!("No runs tagged {}.", tag);
+            }
+            for run in listed {
+                let mut line = format!(
+                    "#{} | {} | brain={} | ticks {} | {}",
+                    run.id,
+                    run.started_at,
+                    render_brain(run),
+                    render_tick_range(run),
+                    render_outcome(run, live_id)
+                );
+                if !run.tags.is_empty() {
+                    line.push_str(&format!(" | tags={}", run.tags.join(",")));
+                }
+                println!("{}", line);
+            }
+        }
+        RunsCommand::Show { id } => {
+            let run = if id == "latest" {
+                runs.latest()
+                    .ok_or_else(|| "no runs recorded yet".to_string())?
+            } else {
+                let id: u64 = id
+                    .parse()
+                    .map_err(|_| format!("run id must be a number or `latest`, got '{}'", id))?;
+                runs.get(id)
+                    .ok_or_else(|| format!("run {} not found in the run registry", id))?
+            };
+            println!("Run {}", run.id);
+            println!("Started: {}", run.started_at);
+            println!("Ended: {}", run.ended_at.as_deref().unwrap_or("-"));
+            println!("Outcome: {}", render_outcome(run, live_id));
+            println!("Ticks: {}", render_tick_range(run));
+            println!("Brain: {}", render_brain(run));
+            println!(
+                "Seeds: world={} llm_jitter={}",
+                run.seeds.world, run.seeds.llm_jitter
+            );
+            if run.flags.is_empty() {
+                println!("Flags: -");
+            } else {
+                println!("Flags: {}", run.flags.join(" "));
+            }
+            if !run.tags.is_empty() {
+                println!("Tags: {}", run.tags.join(", "));
+            }
+            if let Some(summary) = &run.summary {
+                println!(
+                    "Population: {} -> {} (peak {}) | births={} deaths={} rejections={}",
+                    summary.start_population,
+                    summary.final_population,
+                    summary.peak_population,
+                    summary.births,
+                    summary.deaths,
+                    summary.rejections
+                );
+                println!(
+                    "Economy: agent_qi={} total_qi={} structures={}",
+                    summary.final_agent_qi, summary.final_total_qi, summary.final_structures
+                );
+                println!("Survival: {}", render_survival(&summary.survival));
+                if let Some(score) = summary.score {
+                    println!("Score: {:.2}", score);
+                }
+            }
+            if let Some(config) = &run.config {
+                println!("Config:");
+                for line in config.lines() {
+                    println!("  {}", line);
+                }
+            }
+        }
+        RunsCommand::Compare { tags, csv } => {
+            let mut compared = Vec::new();
+            for tag in &tags {
+                match runs.summarize_tag(tag) {
+                    Some(summary) => compared.push(summary),
+                    None => println!("{}: no finished runs with this tag", tag),
+                }
+            }
+            for summary in &compared {
+                println!(
+                    "{} | {} run(s) | score={} | ticks={:.1}",
+                    summary.tag,
+                    summary.runs,
+                    summary
+                        .score
+                        .map_or_else(|| "-".to_string(), |score| format!("{:.2}", score)),
+                    summary.ticks
+                );
+                println!(
+                    "  population={:.1} births={:.1} deaths={:.1} rejections={:.1}",
+                    summary.final_population, summary.births, summary.deaths, summary.rejections
+                );
+                println!(
+                    "  agent_qi={:.1} total_qi={:.1} structures={:.1}",
+                    summary.final_agent_qi, summary.final_total_qi, summary.final_structures
+                );
+                println!("  survival: {}", render_survival(&summary.survival));
+            }
+            if let Some(path) = csv {
+                write_comparison(&path, &compared)?;
+                println!("Wrote {}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Survival as percentages of the starting population, start first.
+fn render_survival(survival: &[f64]) -> String {
+    survival
+        .iter()
+        .map(|share| format!("{:.0}%", share * 100.0))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn write_comparison(path: &Path, compared: &[TagSummary]) -> Result<(), String> {
+    let fail = |e: csv::Error| format!("failed to write {}: {}", path.display(), e);
+    let mut writer = csv::Writer::from_path(path).map_err(fail)?;
+    let mut header: Vec<String> = [
+        "tag",
+        "runs",
+        "score",
+        "ticks",
+        "final_population",
+        "births",
+        "deaths",
+        "rejections",
+        "final_agent_qi",
+        "final_total_qi",
+        "final_structures",
+    ]
+    .map(String::from)
+    .to_vec();
+    header.extend((0..harimu::runs::SURVIVAL_POINTS).map(|i| format!("survival_{}", i * 10)));
+    writer.write_record(&header).map_err(fail)?;
+    for s in compared {
+        let mut row = vec![
+            s.tag.clone(),
+            s.runs.to_string(),
+            s.score.map(|score| score.to_string()).unwrap_or_default(),
+            s.ticks.to_string(),
+            s.final_population.to_string(),
+            s.births.to_string(),
+            s.deaths.to_string(),
+            s.rejections.to_string(),
+            s.final_agent_qi.to_string(),
+            s.final_total_qi.to_string(),
+            s.final_structures.to_string(),
+        ];
+        row.extend(s.survival.iter().map(|share| share.to_string()));
+        writer.write_record(&row).map_err(fail)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+fn render_brain(run: &RunRecord) -> String {
+    let brain = &run.brain;
+    let Some(mode) = brain.mode else {
+        return "-".into();
+    };
+    let mode = super::brain_to_arg(mode);
+    match (brain.provider, &brain.model) {
+        (Some(provider), Some(model)) => format!(
+            "{} ({} {})",
+            mode,
+            super::llm_provider_to_arg(provider),
+            model
+        ),
+        _ => mode.to_string(),
+    }
+}
+
+fn render_tick_range(run: &RunRecord) -> String {
+    match run.end_tick {
+        Some(end) if end > run.start_tick => format!("{}..={}", run.start_tick + 1, end),
+        Some(_) => "none".into(),
+        None => format!("{}..", run.start_tick + 1),
+    }
+}
+
+fn render_outcome(run: &RunRecord, live_id: Option<u64>) -> String {
+    match &run.exit_reason {
+        Some(reason) => reason.clone(),
+        None if live_id == Some(run.id) => "running".into(),
+        None => "interrupted".into(),
+    }
+}
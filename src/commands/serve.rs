@@ -0,0 +1,244 @@
+use clap::Args;
+use harimu::{
+    Api, ApiRequest, ApiResponse, Method, QuotaLimits, Scope, StreamFilter, StructurePersister,
+    TickStream, TokenStore, Vm, World, is_read_only, load_world_state, load_zone_store,
+    lock_data_dir, pump_websocket, start_replay_log,
+    state::{self, Status},
+    world_state_file_path,
+};
+use tracing::warn;
+
+use super::{
+    add_recipes, add_structure_effects, check_qi_conservation, persist_journal, persist_metrics,
+    persist_replay_tick, persist_structures, persist_territories, persist_world_state,
+    persist_world_view,
+};
+
+/// The world inspector served at `/`: a map of `/snapshot` with an event ticker fed by
+/// `/stream`. Holds no data itself, so it needs no token.
+const INSPECTOR_PAGE: &str = include_str!("inspector.html");
+
+#[derive(Args, Clone)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+    /// Port to listen on
+    #[arg(long, default_value_t = 8080)]
+    pub port: u16,
+    /// Most actions one client may queue per tick
+    #[arg(long, value_name = "N")]
+    pub quota_per_tick: Option<u32>,
+    /// Most actions one client may queue per minute
+    #[arg(long, value_name = "N")]
+    pub quota_per_minute: Option<u32>,
+}
+
+pub(super) fn run_serve(args: ServeArgs) -> Result<(), String> {
+    let read_only = is_read_only();
+    let (mut api, stream) = open_api(
+        QuotaLimits {
+            per_tick: args.quota_per_tick,
+            per_minute: args.quota_per_minute,
+        },
+        "serving the HTTP API",
+    )?;
+
+    let address = format!("{}:{}", args.host, args.port);
+    let server = tiny_http::Server::http(&address)
+        .map_err(|e| format!("failed to listen on {}: {}", address, e))?;
+    println!(
+        "Serving world at tick {} on http://{}{} (open it in a browser to inspect the world)",
+        api.vm().world().tick(),
+        address,
+        if read_only { " (read-only)" } else { "" }
+    );
+
+    for mut request in server.incoming_requests() {
+        let method = match request.method() {
+            tiny_http::Method::Get => Method::Get,
+            tiny_http::Method::Post => Method::Post,
+            _ => Method::Other,
+        };
+        let token = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+            .map(|t| t.trim().to_string());
+        let peer = request
+            .remote_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".into());
+        // Only POSTs carry a body; an upgrade request's reader is the raw connection.
+        let mut body = String::new();
+        if method == Method::Post
+            && let Err(err) = request.as_reader().read_to_string(&mut body)
+        {
+            warn!("failed to read request body: {}", err);
+        }
+        let url = request.url().to_string();
+
+        if method == Method::Get && url.split('?').next() == Some("/") {
+            send(
+                request,
+                200,
+                INSPECTOR_PAGE.to_string(),
+                "text/html; charset=utf-8",
+            );
+            continue;
+        }
+
+        if method == Method::Get && url.split('?').next() == Some("/stream") {
+            let query = url.split_once('?').map_or("", |(_, q)| q);
+            let token = token.or_else(|| query_token(query));
+            let api_request = ApiRequest {
+                method,
+                url: &url,
+                token: token.as_deref(),
+                peer: &peer,
+                body: &body,
+            };
+            match open_stream(&api, &api_request, query, &request) {
+                Ok((filter, reply)) => {
+                    let messages = stream.subscribe(filter);
+                    let socket = request.upgrade("websocket", reply);
+                    std::thread::spawn(move || pump_websocket(socket, messages));
+                }
+                Err(response) => respond(request, response),
+            }
+            continue;
+        }
+
+        let response = api.handle(&ApiRequest {
+            method,
+            url: &url,
+            token: token.as_deref(),
+            peer: &peer,
+            body: &body,
+        });
+        respond(request, response);
+    }
+    Ok(())
+}
+
+fn respond(request: tiny_http::Request, response: ApiResponse) {
+    send(
+        request,
+        response.status,
+        response.body.to_string(),
+        "application/json",
+    );
+}
+
+fn send(request: tiny_http::Request, status: u16, body: String, content_type: &str) {
+    let content_type = tiny_http::Header::from_bytes("Content-Type", content_type)
+        .expect("static header is valid");
+    let reply = tiny_http::Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(err) = request.respond(reply) {
+        warn!("failed to send response: {}", err);
+    }
+}
+
+/// Browsers can't set headers on a WebSocket, so `/stream` also takes `?token=<secret>`.
+fn query_token(query: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "token")
+        .map(|(_, value)| value.to_string())
+}
+
+/// Check a `/stream` request and build the handshake reply that upgrades it to a WebSocket.
+fn open_stream(
+    api: &Api,
+    api_request: &ApiRequest,
+    query: &str,
+    request: &tiny_http::Request,
+) -> Result<(StreamFilter, tiny_http::Response<std::io::Empty>), ApiResponse> {
+    api.authorize(api_request, &Scope::Observe)?;
+    let filter = StreamFilter::from_query(query).map_err(|err| ApiResponse::error(400, err))?;
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| h.value.as_str().to_string())
+        .ok_or_else(|| ApiResponse::error(400, "/stream needs a WebSocket upgrade request"))?;
+    let accept = tungstenite::handshake::derive_accept_key(key.trim().as_bytes());
+    let reply = tiny_http::Response::empty(101)
+        .with_header(
+            tiny_http::Header::from_bytes("Upgrade", "websocket").expect("static header is valid"),
+        )
+        .with_header(
+            tiny_http::Header::from_bytes("Connection", "Upgrade").expect("static header is valid"),
+        )
+        .with_header(
+            tiny_http::Header::from_bytes("Sec-WebSocket-Accept", accept)
+                .expect("accept key is a valid header value"),
+        );
+    Ok((filter, reply))
+}
+
+/// Load the saved world into an `Api` whose ticks are persisted like `start`'s and published
+/// to the returned stream. Marks the runtime as running with `status` unless read-only.
+pub(super) fn open_api(limits: QuotaLimits, status: &str) -> Result<(Api, TickStream), String> {
+    let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+    if let Some(current) = state::load_state().map_err(|e| e.to_string())?
+        && current.status == Status::Running
+    {
+        return Err(
+            "the runtime is running; stop it with `harimu stop` before serving its world".into(),
+        );
+    }
+    let saved = load_world_state()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "no saved world to serve; run `harimu start --ticks 1` first".to_string())?;
+    let world = World::from_persistent(saved)
+        .map_err(|e| format!("{}: {}", world_state_file_path().display(), e))?;
+    let mut vm = Vm::with_world(world);
+    let zone_labels = load_zone_store().map_err(|e| e.to_string())?;
+    vm.world_mut().set_zone_labels(zone_labels);
+    add_structure_effects(&mut vm)?;
+    add_recipes(&mut vm)?;
+    if !is_read_only() {
+        start_replay_log(&vm).map_err(|e| format!("replay log: {}", e))?;
+        state::set_status(Status::Running, vm.world().tick(), Some(status.into()))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let tokens = TokenStore::load().map_err(|e| e.to_string())?;
+    if tokens.tokens.is_empty() {
+        println!("No access tokens issued; the API is open to anyone who can reach it");
+    }
+    let mut structures =
+        StructurePersister::load().map_err(|e| format!("structure store: {}", e))?;
+    let stream = TickStream::new();
+    let publisher = stream.clone();
+    let status = status.to_string();
+    let api = Api::new(vm, limits, tokens).with_tick_hook(Box::new(move |requests, tick, vm| {
+        publisher.publish(tick);
+        let _lock = match lock_data_dir() {
+            Ok(lock) => lock,
+            Err(err) => {
+                warn!("failed to lock the data directory: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = persist_structures(&mut structures, &tick.events) {
+            warn!("{}", err);
+        }
+        persist_territories(tick, vm);
+        persist_world_view(vm);
+        persist_world_state(vm);
+        persist_journal(tick);
+        persist_metrics(tick, vm);
+        check_qi_conservation(tick, vm);
+        persist_replay_tick(requests, tick, vm);
+        if let Err(err) = state::set_status(Status::Running, tick.tick, Some(status.clone())) {
+            warn!("failed to update runtime state: {}", err);
+        }
+    }));
+    Ok((api, stream))
+}
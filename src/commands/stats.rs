@@ -0,0 +1,134 @@
+use std::io;
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use harimu::{decisions, load_timeline, metrics_dir, usage_by_agent};
+
+#[derive(Subcommand)]
+pub enum StatsCommand {
+    /// Per-tick population, Qi, structures, deaths and rejections recorded by `start` and
+    /// `serve` in `.harimu/metrics/`
+    Timeline {
+        /// First tick to show
+        #[arg(long)]
+        from: Option<u64>,
+        /// Last tick to show
+        #[arg(long)]
+        to: Option<u64>,
+        /// Print CSV instead of a table
+        #[arg(long, default_value_t = false)]
+        csv: bool,
+        /// Write the CSV to this file instead of stdout (implies --csv)
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+    /// LLM calls, tokens, failures and latency per agent for the current or last run, from
+    /// `.harimu/decisions.jsonl`
+    Llm {
+        /// Print JSON instead of a table
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+}
+
+pub(super) fn run_stats(cmd: StatsCommand) -> Result<(), String> {
+    match cmd {
+        StatsCommand::Timeline { from, to, csv, out } => {
+            let timeline = load_timeline(from, to).map_err(|e| e.to_string())?;
+            if let Some(path) = out {
+                let fail = |e: csv::Error| format!("failed to write {}: {}", path.display(), e);
+                let mut writer = csv::Writer::from_path(&path).map_err(fail)?;
+                for metrics in &timeline {
+                    writer.serialize(metrics).map_err(fail)?;
+                }
+                writer
+                    .flush()
+                    .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+                println!("Wrote {} ticks to {}", timeline.len(), path.display());
+                return Ok(());
+            }
+            if csv {
+                let mut writer = csv::Writer::from_writer(io::stdout().lock());
+                for metrics in &timeline {
+                    writer.serialize(metrics).map_err(|e| e.to_string())?;
+                }
+                return writer.flush().map_err(|e| e.to_string());
+            }
+            if timeline.is_empty() {
+                println!(
+                    "No tick metrics recorded yet in {}",
+                    metrics_dir().display()
+                );
+                return Ok(());
+            }
+            println!(
+                "{:>8} {:>6} {:>6} {:>10} {:>10} {:>10} {:>6} {:>6} {:>6} {:>8}",
+                "tick",
+                "alive",
+                "dead",
+                "agent qi",
+                "node qi",
+                "total qi",
+                "built",
+                "births",
+                "deaths",
+                "rejected"
+            );
+            for m in &timeline {
+                println!(
+                    "{:>8} {:>6} {:>6} {:>10} {:>10} {:>10} {:>6} {:>6} {:>6} {:>8}",
+                    m.tick,
+                    m.population,
+                    m.dead_agents,
+                    m.agent_qi,
+                    m.node_qi,
+                    m.total_qi,
+                    m.structures,
+                    m.births,
+                    m.deaths,
+                    m.rejections
+                );
+            }
+        }
+        StatsCommand::Llm { json } => {
+            let records = decisions::load_decisions().map_err(|e| e.to_string())?;
+            let agents = usage_by_agent(&records);
+            if json {
+                let text = serde_json::to_string_pretty(&agents).map_err(|e| e.to_string())?;
+                println!("{}", text);
+                return Ok(());
+            }
+            if agents.is_empty() {
+                println!("No LLM decisions recorded yet");
+                return Ok(());
+            }
+            println!(
+                "{:>6} {:>9} {:>6} {:>8} {:>8} {:>10} {:>10} {:>8} {:>8}",
+                "agent",
+                "decisions",
+                "calls",
+                "failures",
+                "requests",
+                "prompt",
+                "completion",
+                "mean ms",
+                "max ms"
+            );
+            for a in &agents {
+                println!(
+                    "{:>6} {:>9} {:>6} {:>8} {:>8} {:>10} {:>10} {:>8} {:>8}",
+                    a.agent_id,
+                    a.decisions,
+                    a.calls,
+                    a.failures,
+                    a.usage.requests,
+                    a.usage.prompt_tokens,
+                    a.usage.completion_tokens,
+                    a.mean_latency_ms(),
+                    a.max_latency_ms
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use clap::Subcommand;
+use harimu::auth::{Scope, TokenStore};
+
+#[derive(Subcommand)]
+pub enum TokenCommand {
+    /// Issue an access token for the server APIs; the secret is printed once
+    Create {
+        /// Scope to grant (repeatable): observe, act:<agent id> or admin
+        #[arg(long = "scope", value_name = "SCOPE", required = true)]
+        scopes: Vec<Scope>,
+        /// Note shown in `token list`, e.g. who the token was given to
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// List issued tokens (secrets are never shown again)
+    List,
+    /// Revoke a token by id
+    Revoke { id: String },
+}
+
+impl TokenCommand {
+    /// Whether the command writes to the token store (refused in read-only mode).
+    pub(super) fn mutates(&self) -> bool {
+        !matches!(self, TokenCommand::List)
+    }
+}
+
+pub(super) fn run_token(cmd: TokenCommand) -> Result<(), String> {
+    let mut store = TokenStore::load().map_err(|e| e.to_string())?;
+
+    match cmd {
+        TokenCommand::Create { scopes, label } => {
+            let (token, secret) = store.issue(scopes, label);
+            store.save().map_err(|e| e.to_string())?;
+            println!(
+                "Created token {} | scopes={}",
+                token.id,
+                render_scopes(&token.scopes)
+            );
+            println!("Secret (shown only now): {}", secret);
+        }
+        TokenCommand::List => {
+            if store.tokens.is_empty() {
+                println!("No tokens issued.");
+                return Ok(());
+            }
+            for token in store.tokens.values() {
+                println!(
+                    "{} | scopes={} | label={} | created={}",
+                    token.id,
+                    render_scopes(&token.scopes),
+                    token.label.as_deref().unwrap_or("-"),
+                    token.created_at
+                );
+            }
+        }
+        TokenCommand::Revoke { id } => {
+            let token = store
+                .revoke(&id)
+                .ok_or_else(|| format!("token {} not found", id))?;
+            store.save().map_err(|e| e.to_string())?;
+            println!("Revoked token {}", token.id);
+        }
+    }
+
+    Ok(())
+}
+
+fn render_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(Scope::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
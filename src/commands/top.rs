@@ -0,0 +1,201 @@
+use std::io::{self, IsTerminal, Write};
+use std::thread;
+use std::time::Duration;
+
+use clap::{Args, ValueEnum};
+use harimu::{AgentSnapshot, DashboardFeed, ItemKind, Position, Qi, WorldSnapshot};
+
+/// Journaled events kept in memory; plenty for the activity window of a busy world.
+const EVENT_CAPACITY: usize = 5000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TopSort {
+    /// Most Qi first
+    Qi,
+    /// Oldest first
+    Age,
+    /// Lowest id first
+    Id,
+    /// Most recently active first
+    Active,
+}
+
+#[derive(Args, Clone)]
+pub struct TopArgs {
+    /// How often to refresh, in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    pub interval_ms: u64,
+    /// Order of the agent table
+    #[arg(long, value_enum, default_value_t = TopSort::Qi)]
+    pub sort: TopSort,
+    /// Agent and zone rows to show
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+    /// Ticks of journaled events counted as a zone's recent activity
+    #[arg(long, default_value_t = 16)]
+    pub window: u64,
+    /// Include dead agents
+    #[arg(long, default_value_t = false)]
+    pub all: bool,
+    /// Print one frame and exit
+    #[arg(long, default_value_t = false)]
+    pub once: bool,
+}
+
+pub(super) fn run_top(args: TopArgs) -> Result<(), String> {
+    let mut feed = DashboardFeed::new(EVENT_CAPACITY);
+    let clear = !args.once && io::stdout().is_terminal();
+    loop {
+        feed.refresh();
+        let frame = render(&feed, &args);
+        let mut out = io::stdout().lock();
+        if clear {
+            // Home the cursor and clear the screen, like `top` repainting in place.
+            write!(out, "\x1b[H\x1b[2J").map_err(|e| e.to_string())?;
+        }
+        out.write_all(frame.as_bytes())
+            .and_then(|_| out.flush())
+            .map_err(|e| e.to_string())?;
+        if args.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(args.interval_ms.max(100)));
+    }
+}
+
+fn render(feed: &DashboardFeed, args: &TopArgs) -> String {
+    let mut frame = String::new();
+    let status = match feed.state() {
+        Some(state) => format!("{:?}", state.status),
+        None => "not initialized".into(),
+    };
+    let tick = feed
+        .tick()
+        .map_or_else(|| "-".to_string(), |tick| tick.to_string());
+    let rate = feed
+        .tick_rate()
+        .map_or_else(|| "idle".to_string(), |rate| format!("{:.2} ticks/s", rate));
+    frame.push_str(&format!(
+        "harimu top | {} | tick {} | {}\n",
+        status, tick, rate
+    ));
+    if let Some(err) = feed.error() {
+        frame.push_str(&format!("read failed, showing last reading: {}\n", err));
+    }
+    let Some(snapshot) = feed.snapshot() else {
+        frame.push_str("No world snapshot yet; start a run with `harimu start`\n");
+        return frame;
+    };
+
+    let alive = snapshot.agents.iter().filter(|a| a.alive).count();
+    let agent_qi: u64 = snapshot.agents.iter().map(|a| qi(a) as u64).sum();
+    frame.push_str(&format!(
+        "agents {}/{} alive | agent qi {} | ore nodes {} | structures {}\n\n",
+        alive,
+        snapshot.agents.len(),
+        agent_qi,
+        snapshot.ore_nodes.len(),
+        snapshot.structures.len()
+    ));
+    render_agents(&mut frame, feed, snapshot, args);
+    frame.push('\n');
+    render_zones(&mut frame, feed, snapshot, args);
+    frame
+}
+
+fn qi(agent: &AgentSnapshot) -> Qi {
+    agent.inventory.get(ItemKind::Qi)
+}
+
+fn render_agents(
+    frame: &mut String,
+    feed: &DashboardFeed,
+    snapshot: &WorldSnapshot,
+    args: &TopArgs,
+) {
+    let mut agents: Vec<&AgentSnapshot> = snapshot
+        .agents
+        .iter()
+        .filter(|agent| args.all || agent.alive)
+        .collect();
+    let last_tick = |agent: &AgentSnapshot| feed.last_activity(agent.id).map(|a| a.tick);
+    match args.sort {
+        TopSort::Qi => agents.sort_by_key(|a| (std::cmp::Reverse(qi(a)), a.id)),
+        TopSort::Age => agents.sort_by_key(|a| (std::cmp::Reverse(a.age), a.id)),
+        TopSort::Id => agents.sort_by_key(|a| a.id),
+        TopSort::Active => agents.sort_by_key(|a| (std::cmp::Reverse(last_tick(a)), a.id)),
+    }
+    frame.push_str(&format!(
+        "{:>5} {:<16} {:>7} {:>4} {:>11} {:<16} {:<20}\n",
+        "id", "name", "qi", "hp", "age", "zone", "last event"
+    ));
+    for agent in agents.iter().take(args.limit) {
+        let zone = agent.position.zone();
+        let last = feed
+            .last_activity(agent.id)
+            .map_or_else(|| "-".to_string(), |a| format!("{} @{}", a.label, a.tick));
+        let mut name = agent.name.clone();
+        if !agent.alive {
+            name.push_str(" (dead)");
+        }
+        frame.push_str(&format!(
+            "{:>5} {:<16} {:>7} {:>4} {:>11} {:<16} {:<20}\n",
+            agent.id,
+            truncate(&name, 16),
+            qi(agent),
+            agent.hp,
+            format!("{}/{}", agent.age, agent.max_age),
+            format!("({}, {}, {})", zone.x, zone.y, zone.z),
+            last
+        ));
+    }
+    if agents.len() > args.limit {
+        frame.push_str(&format!("... {} more\n", agents.len() - args.limit));
+    }
+}
+
+fn render_zones(
+    frame: &mut String,
+    feed: &DashboardFeed,
+    snapshot: &WorldSnapshot,
+    args: &TopArgs,
+) {
+    let activity = feed.zone_activity(args.window);
+    let mut zones = snapshot.minimap(Position::origin().zone(), i32::MAX);
+    zones.sort_by_key(|zone| {
+        let events = activity.get(&zone.zone).copied().unwrap_or(0);
+        (std::cmp::Reverse(events), std::cmp::Reverse(zone.agents))
+    });
+    frame.push_str(&format!(
+        "{:<16} {:<16} {:>6} {:>8} {:>10} {:>8}\n",
+        "zone",
+        "name",
+        "agents",
+        "ore",
+        "structures",
+        format!("events/{}t", args.window)
+    ));
+    for zone in zones.iter().take(args.limit) {
+        frame.push_str(&format!(
+            "{:<16} {:<16} {:>6} {:>8} {:>10} {:>8}\n",
+            format!("({}, {}, {})", zone.zone.x, zone.zone.y, zone.zone.z),
+            truncate(zone.name.as_deref().unwrap_or("-"), 16),
+            zone.agents,
+            zone.ore_available,
+            zone.structures,
+            activity.get(&zone.zone).copied().unwrap_or(0)
+        ));
+    }
+    if zones.len() > args.limit {
+        frame.push_str(&format!("... {} more\n", zones.len() - args.limit));
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(width.saturating_sub(1)).collect();
+    short.push('…');
+    short
+}
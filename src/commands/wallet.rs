@@ -2,8 +2,8 @@ use std::time::Duration;
 
 use clap::Subcommand;
 use harimu::{
+    POW_DIFFICULTY_BYTES, Qi, lock_data_dir,
     wallet::{self, WalletStore},
-    POW_DIFFICULTY_BYTES, Qi,
 };
 
 #[derive(Subcommand)]
@@ -30,6 +30,13 @@ pub enum WalletCommand {
     },
 }
 
+impl WalletCommand {
+    /// Whether the command writes to the wallet store (refused in read-only mode).
+    pub(super) fn mutates(&self) -> bool {
+        !matches!(self, WalletCommand::Balance { .. })
+    }
+}
+
 pub(super) fn run_wallet(cmd: WalletCommand) -> Result<(), String> {
     let mut store = WalletStore::load().map_err(|e| e.to_string())?;
 
@@ -89,7 +96,12 @@ pub(super) fn run_wallet_mine(
     );
 
     loop {
-        let (found_nonce, reward) = wallet::mine(&mut store, &address, nonce)?;
+        let found_nonce = wallet::wallet_pow_solve(&address, nonce);
+        // Credit against a fresh copy of the store so writes by other processes since the last
+        // solution are kept.
+        let _lock = lock_data_dir().map_err(|e| e.to_string())?;
+        store = WalletStore::load().map_err(|e| e.to_string())?;
+        let (found_nonce, reward) = wallet::mine(&mut store, &address, found_nonce)?;
         store.save().map_err(|e| e.to_string())?;
 
         mined = mined.saturating_add(1);
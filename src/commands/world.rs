@@ -3,13 +3,15 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
 
+use super::PositionArg;
 use clap::{ArgAction, Subcommand};
 use harimu::{
-    Position, Spread, load_structure_store, load_world_snapshot, save_world_snapshot,
-    snapshot_from_persistent,
+    OrderSide, OreKind, Position, Spread, WalletStore, World, is_read_only, load_structure_store,
+    load_territory_store, load_world_snapshot_consistent, load_world_state, load_zone_store,
+    save_world_snapshot, save_zone_store, snapshot_file_path, snapshot_from_persistent,
     world::{InfuseQiCommand, WorldCommands, WorldQueries},
 };
-use serde_json;
+use tracing::warn;
 
 #[derive(Subcommand)]
 pub enum WorldCommand {
@@ -36,7 +38,7 @@ pub enum WorldCommand {
         /// Optional RNG seed for reproducible placement
         #[arg(long)]
         seed: Option<u64>,
-        /// Ore kind to infuse (qi or transistor). Transistors cost 100 Qi each.
+        /// Ore kind to infuse: qi, transistor (100 Qi each) or one registered in ores.toml
         #[arg(long, default_value = "qi")]
         ore: harimu::OreKind,
     },
@@ -48,6 +50,9 @@ pub enum WorldCommand {
         /// Show agent-built structures
         #[arg(long)]
         structure: bool,
+        /// Show per-zone totals: agents, structures, ore available/capacity and owner
+        #[arg(long)]
+        zones: bool,
     },
     /// Export a world snapshot for visualization (e.g., Godot viewer)
     View {
@@ -58,6 +63,61 @@ pub enum WorldCommand {
         #[arg(long = "no-launch", action = ArgAction::SetFalse, default_value_t = true)]
         launch: bool,
     },
+    /// Name zones and attach notes (shown in snapshots and LLM observations)
+    Zone {
+        #[command(subcommand)]
+        cmd: ZoneCommand,
+    },
+    /// List claimed zones with their owner, the tick they were claimed and their tolls
+    Territories,
+    /// Show the ore market's resting orders, best price first, and its most recent fills
+    Market {
+        /// Number of recent fills to show
+        #[arg(long, default_value_t = 10)]
+        fills: usize,
+    },
+    /// Show where the Qi is (agents, ore nodes, structures, recycled pool, wallets) and check
+    /// that the saved world holds no more than was infused and granted
+    Economy,
+}
+
+#[derive(Subcommand)]
+pub enum ZoneCommand {
+    /// Name the zone containing a position, e.g. `zone name 0,0,0 "Spawn Valley"`
+    Name {
+        /// Any position inside the zone: x,y,z
+        position: PositionArg,
+        name: String,
+    },
+    /// Append a note to the zone containing a position
+    Note {
+        /// Any position inside the zone: x,y,z
+        position: PositionArg,
+        text: String,
+    },
+    /// Remove the name and notes of the zone containing a position
+    Clear {
+        /// Any position inside the zone: x,y,z
+        position: PositionArg,
+    },
+    /// List named and annotated zones
+    List,
+}
+
+impl WorldCommand {
+    /// Whether the command writes world stores (refused in read-only mode). `view` only
+    /// refreshes the snapshot file and skips that when read-only.
+    pub(super) fn mutates(&self) -> bool {
+        match self {
+            WorldCommand::Infuse { .. } => true,
+            WorldCommand::List { .. }
+            | WorldCommand::View { .. }
+            | WorldCommand::Territories
+            | WorldCommand::Market { .. }
+            | WorldCommand::Economy => false,
+            WorldCommand::Zone { cmd } => !matches!(cmd, ZoneCommand::List),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -146,9 +206,16 @@ pub(super) fn run_world(cmd: WorldCommand) -> Result<(), String> {
                 );
             }
         }
-        WorldCommand::List { ore, structure } => {
-            let show_ore = ore || (!ore && !structure);
-            let show_structures = structure || (!ore && !structure);
+        WorldCommand::List {
+            ore,
+            structure,
+            zones,
+        } => {
+            let show_ore = ore || !(structure || zones);
+            let show_structures = structure || !(ore || zones);
+            if zones {
+                print_zone_summaries()?;
+            }
             if show_ore {
                 print_ore_nodes()?;
             }
@@ -157,14 +224,19 @@ pub(super) fn run_world(cmd: WorldCommand) -> Result<(), String> {
             }
         }
         WorldCommand::View { json, launch } => {
-            let snapshot = match load_world_snapshot().map_err(|e| e.to_string())? {
-                Some(s) => s,
+            let snapshot = match load_world_snapshot_consistent().map_err(|e| e.to_string())? {
+                Some(read) => {
+                    if read.fell_back {
+                        warn!(
+                            "latest world snapshot was unreadable; using {}",
+                            read.path.display()
+                        );
+                    }
+                    read.snapshot
+                }
                 None => snapshot_from_persistent()?,
             };
 
-            let path = save_world_snapshot(&snapshot)
-                .map_err(|e| format!("failed to persist snapshot: {}", e))?;
-
             println!(
                 "World snapshot: tick={} | agents={} | structures={} | ore_nodes={}",
                 snapshot.tick,
@@ -172,10 +244,18 @@ pub(super) fn run_world(cmd: WorldCommand) -> Result<(), String> {
                 snapshot.structures.len(),
                 snapshot.ore_nodes.len()
             );
-            println!(
-                "Snapshot file written to {} (pass --json to print it here)",
-                path.display()
-            );
+            // Read-only data dirs are viewed from the snapshot the runtime last saved.
+            let path = if is_read_only() {
+                Some(snapshot_file_path()).filter(|path| path.exists())
+            } else {
+                let path = save_world_snapshot(&snapshot)
+                    .map_err(|e| format!("failed to persist snapshot: {}", e))?;
+                println!(
+                    "Snapshot file written to {} (pass --json to print it here)",
+                    path.display()
+                );
+                Some(path)
+            };
 
             if json {
                 let json_str =
@@ -184,18 +264,140 @@ pub(super) fn run_world(cmd: WorldCommand) -> Result<(), String> {
             }
 
             if launch {
+                let path = path.ok_or(
+                    "no saved snapshot to view and the data directory is read-only; use --json",
+                )?;
                 launch_godot_viewer(&path)?;
             }
         }
+        WorldCommand::Zone { cmd } => run_zone(cmd)?,
+        WorldCommand::Territories => print_territories()?,
+        WorldCommand::Market { fills } => print_market(fills)?,
+        WorldCommand::Economy => print_economy()?,
     }
 
     Ok(())
 }
 
+fn print_economy() -> Result<(), String> {
+    let infused = WorldQueries::qi_sources()?.total_qi_infused;
+    let wallets = WalletStore::load().map_err(|e| e.to_string())?;
+    let wallet_qi: u64 = wallets.wallets.values().map(|w| w.balance as u64).sum();
+    let Some(saved) = load_world_state().map_err(|e| e.to_string())? else {
+        println!("No saved world yet; start a run first.");
+        println!(
+            "Wallets: {} Qi in {} wallet(s) | infused: {} Qi",
+            wallet_qi,
+            wallets.wallets.len(),
+            infused
+        );
+        return Ok(());
+    };
+    let world = World::from_persistent(saved)?;
+    let audit = world.qi_audit();
+
+    println!("Economy at tick {}", world.tick());
+    for (label, qi) in [
+        ("agents", audit.agent_qi),
+        ("ore nodes", audit.node_qi),
+        ("structures", audit.structure_qi),
+        ("market escrow", audit.market_qi),
+        ("recycled pool", audit.recycled_qi),
+        ("world total", audit.held()),
+    ] {
+        println!("  {:<14} {:>10} Qi", label, qi);
+    }
+    println!(
+        "  {:<14} {:>10} Qi ({} wallet(s), outside the world)",
+        "wallets",
+        wallet_qi,
+        wallets.wallets.len()
+    );
+    println!("Infused: {} Qi", infused);
+    match audit.supply_cap {
+        Some(cap) if cap != infused => println!(
+            "Supply cap: {} Qi (the rest was infused since the world last started)",
+            cap
+        ),
+        Some(_) => {}
+        None => println!("Supply cap: none (nothing was infused before the world started)"),
+    }
+    println!(
+        "Granted: {} Qi (starting Qi, newborns and admin grants)",
+        audit.granted_qi
+    );
+
+    let Some(limit) = audit.limit() else {
+        println!("Conservation: not checked without a supply cap");
+        return Ok(());
+    };
+    if audit.excess() > 0 {
+        return Err(format!(
+            "Qi conservation violated: the world holds {} Qi, {} more than the {} it may hold",
+            audit.held(),
+            audit.excess(),
+            limit
+        ));
+    }
+    println!(
+        "Conservation: ok ({} of {} Qi held, {} left to mint)",
+        audit.held(),
+        limit,
+        limit - audit.held()
+    );
+    Ok(())
+}
+
+fn run_zone(cmd: ZoneCommand) -> Result<(), String> {
+    let mut store = load_zone_store().map_err(|e| e.to_string())?;
+    match cmd {
+        ZoneCommand::Name { position, name } => {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err("zone name cannot be empty".into());
+            }
+            let label = store.entry(position.0.zone());
+            label.name = Some(name.to_string());
+            println!("Named {}", label.describe());
+        }
+        ZoneCommand::Note { position, text } => {
+            let text = text.trim();
+            if text.is_empty() {
+                return Err("zone note cannot be empty".into());
+            }
+            let label = store.entry(position.0.zone());
+            label.notes.push(text.to_string());
+            println!("Annotated {}", label.describe());
+        }
+        ZoneCommand::Clear { position } => {
+            let zone = position.0.zone();
+            match store.remove(zone) {
+                Some(label) => println!("Cleared {}", label.describe()),
+                None => println!(
+                    "zone ({},{},{}) has no name or notes",
+                    zone.x, zone.y, zone.z
+                ),
+            }
+        }
+        ZoneCommand::List => {
+            if store.zones.is_empty() {
+                println!("No zones named yet. Use `harimu world zone name x,y,z <name>`.");
+            } else {
+                println!("{} zone(s):", store.zones.len());
+                for label in &store.zones {
+                    println!(" - {}", label.describe());
+                }
+            }
+            return Ok(());
+        }
+    }
+    save_zone_store(&store).map_err(|e| e.to_string())
+}
+
 fn print_ore_nodes() -> Result<(), String> {
     let store = WorldQueries::qi_sources()?;
     if store.sources.is_empty() {
-                println!("No ore nodes infused yet. Use `harimu world infuse` to add some.");
+        println!("No ore nodes infused yet. Use `harimu world infuse` to add some.");
     } else {
         println!("{} ore node(s):", store.sources.len());
         for (idx, src) in store.sources.iter().enumerate() {
@@ -214,6 +416,127 @@ fn print_ore_nodes() -> Result<(), String> {
     Ok(())
 }
 
+fn print_zone_summaries() -> Result<(), String> {
+    let snapshot = match load_world_snapshot_consistent().map_err(|e| e.to_string())? {
+        Some(read) => read.snapshot,
+        None => snapshot_from_persistent()?,
+    };
+    let zones = snapshot.zone_summaries();
+    if zones.is_empty() {
+        println!("No occupied zones at tick {}.", snapshot.tick);
+        return Ok(());
+    }
+    println!("{} zone(s) at tick {}:", zones.len(), snapshot.tick);
+    println!(
+        "{:<16} {:<16} {:>6} {:>10} {:>13}  owner",
+        "zone", "name", "agents", "structures", "ore"
+    );
+    for zone in &zones {
+        let owner = zone.owner.map_or_else(
+            || "-".to_string(),
+            |id| match snapshot.agents.iter().find(|a| a.id == id) {
+                Some(agent) => format!("{} ({})", id, agent.name),
+                None => id.to_string(),
+            },
+        );
+        println!(
+            "{:<16} {:<16} {:>6} {:>10} {:>13}  {}",
+            format!("({}, {}, {})", zone.zone.x, zone.zone.y, zone.zone.z),
+            zone.name.as_deref().unwrap_or("-"),
+            zone.agents,
+            zone.structures,
+            format!("{}/{}", zone.ore_available, zone.ore_capacity),
+            owner
+        );
+    }
+    Ok(())
+}
+
+fn print_territories() -> Result<(), String> {
+    let store = load_territory_store().map_err(|e| e.to_string())?;
+    if store.claims.is_empty() {
+        println!("No zones claimed yet.");
+        return Ok(());
+    }
+    let snapshot = match load_world_snapshot_consistent().map_err(|e| e.to_string())? {
+        Some(read) => Some(read.snapshot),
+        None => snapshot_from_persistent().ok(),
+    };
+    println!("{} claimed zone(s):", store.claims.len());
+    for claim in &store.claims {
+        let agent = snapshot
+            .as_ref()
+            .and_then(|s| s.agents.iter().find(|a| a.id == claim.owner));
+        let owner = match agent {
+            Some(agent) if agent.alive => format!("{} ({})", claim.owner, agent.name),
+            Some(agent) => format!(
+                "{} ({}, dead: the claim has lapsed)",
+                claim.owner, agent.name
+            ),
+            None => claim.owner.to_string(),
+        };
+        let build = match claim.build_toll {
+            0 => "owner only".to_string(),
+            toll => format!("{} Qi", toll),
+        };
+        println!(
+            " - zone ({}, {}, {}) owner={} claimed at tick {} | harvest toll {} Qi | build {}",
+            claim.zone.x,
+            claim.zone.y,
+            claim.zone.z,
+            owner,
+            claim.claimed_at,
+            claim.harvest_toll,
+            build
+        );
+    }
+    Ok(())
+}
+
+fn print_market(fills: usize) -> Result<(), String> {
+    let Some(saved) = load_world_state().map_err(|e| e.to_string())? else {
+        println!("No saved world yet; start a run first.");
+        return Ok(());
+    };
+    let world = World::from_persistent(saved)?;
+    let market = world.market();
+    if market.orders().is_empty() && market.history().is_empty() {
+        println!("No orders posted yet.");
+        return Ok(());
+    }
+    let mut ores: Vec<OreKind> = market.orders().iter().map(|o| o.ore).collect();
+    ores.sort_by_key(|ore| ore.label());
+    ores.dedup();
+    println!("{} resting order(s):", market.orders().len());
+    for ore in ores {
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            for order in market.book(ore, side) {
+                println!(
+                    " - #{} {} {} {} at {} Qi by agent {} (placed tick {})",
+                    order.id,
+                    order.side,
+                    order.amount,
+                    order.ore,
+                    order.price,
+                    order.agent_id,
+                    order.placed_at
+                );
+            }
+        }
+    }
+    let recent: Vec<_> = market.history().iter().rev().take(fills).collect();
+    if !recent.is_empty() {
+        println!("Recent fills (newest first):");
+        for fill in recent {
+            println!(
+                " - tick {}: agent {} bought {} {} from agent {} at {} Qi",
+                fill.tick, fill.buyer, fill.amount, fill.ore, fill.seller, fill.price
+            );
+        }
+    }
+    Ok(())
+}
+
 fn print_structures() -> Result<(), String> {
     let store = load_structure_store().map_err(|e| e.to_string())?;
     if store.structures.is_empty() {
@@ -247,8 +570,12 @@ fn launch_godot_viewer(_snapshot_path: &Path) -> Result<(), String> {
     build_godot_extension(manifest)?;
 
     let lib_name = extension_filename();
-    let built_lib = find_built_extension(&lib_name)
-        .ok_or_else(|| format!("built library not found (looked for {} in target and godot/extension/target)", lib_name))?;
+    let built_lib = find_built_extension(&lib_name).ok_or_else(|| {
+        format!(
+            "built library not found (looked for {} in target and godot/extension/target)",
+            lib_name
+        )
+    })?;
 
     let dest_dir = Path::new("godot/project")
         .join("addons")
@@ -266,8 +593,8 @@ fn launch_godot_viewer(_snapshot_path: &Path) -> Result<(), String> {
     let godot_bin = match find_godot_binary() {
         Ok(bin) => bin,
         Err(err) => {
-            eprintln!(
-                "warning: {}. Install Godot 4 CLI (godot4/godot) to auto-launch the viewer.",
+            warn!(
+                "{}. Install Godot 4 CLI (godot4/godot) to auto-launch the viewer.",
                 err
             );
             return Ok(());
@@ -276,6 +603,7 @@ fn launch_godot_viewer(_snapshot_path: &Path) -> Result<(), String> {
     let status = Command::new(&godot_bin)
         .arg("--path")
         .arg("godot/project")
+        .env(harimu::paths::HOME_ENV, super::data_dir_for_child()?)
         .status()
         .map_err(|e| format!("failed to run {}: {}", godot_bin, e))?;
 
@@ -336,11 +664,5 @@ fn find_built_extension(lib_name: &str) -> Option<PathBuf> {
             .join(lib_name),
     ];
 
-    for cand in candidates {
-        if cand.exists() {
-            return Some(cand);
-        }
-    }
-
-    None
+    candidates.into_iter().find(|cand| cand.exists())
 }
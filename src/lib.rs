@@ -1,30 +1,131 @@
+//! Stability levels:
+//!
+//! - `prelude`: stable. Names and meanings only change in a major release.
+//! - The other items re-exported at the crate root: supported, but a minor release may still
+//!   change their signatures while the crate is below 1.0.
+//! - `modules::*`: internal. Store structs (`AgentStore`, `QiSourceStore`, `StructureStore`,
+//!   ...), their file formats and the raw event log are the CLI's storage and change whenever
+//!   it needs them to.
+
 pub mod modules;
+pub mod prelude;
 
+pub use modules::agenda::{Agenda, ScheduledAction};
 pub use modules::agent::DEFAULT_AGENT_GOAL;
-pub use modules::agent::LlmProvider;
-pub use modules::agent::{ActionArg, BrainMemory, BrainMode, LlmClient, plan_with_llm};
+pub use modules::agent::{
+    ActionArg, ArchivedNote, BrainMemory, BrainMode, Constraint, LlmBudget, LlmClient, LlmDecision,
+    LlmUsage, PlanJob, plan_many_with_llm, plan_with_llm, rejection_constraints,
+};
+pub use modules::agent::{GEMINI_HOST, LlmProvider};
 pub use modules::agents::{self, AgentProfile, AgentStore, VoteDirection};
-pub use modules::ore::OreKind;
+pub use modules::analysis::{
+    self as analysis, Metric, QiPoint, UNKNOWN_REASON, action_counts, deaths_by_reason,
+};
+pub use modules::api::{self as api, Api, ApiRequest, ApiResponse, Method, TickHook};
+pub use modules::auth::{self as auth, AccessToken, Scope, TokenStore};
+pub use modules::backup::{
+    self as backup, AUTO_BACKUP_LABEL, BackupInfo, BackupSchedule, backups_dir, create_backup,
+    find_backup, list_backups, prune_backups, restore_backup,
+};
+pub use modules::config::{
+    self as config, ConfigSource, EffectiveConfig, Pacing, Setting, StartSettings,
+};
+pub use modules::control::{
+    self as control, BrainSwap, ControlReply, ControlRequest, ControlServer, DaemonStatus,
+    control_socket_path, send_control,
+};
+pub use modules::crafting::{Recipe, RecipeBook};
+pub use modules::dashboard::{AgentActivity, DashboardFeed};
+pub use modules::decisions::{self, DecisionRecord, LlmAgentUsage, usage_by_agent};
+pub use modules::effects::{
+    EffectSpec, EffectTrigger, HARVEST_NEARBY_RANGE, QiWell, StructureEffect, StructureRegistry,
+};
+#[cfg(feature = "grpc")]
+pub use modules::grpc::{self as grpc, WorldControlServer, WorldControlService};
+pub use modules::inventory::{Inventory, ItemKind, Shortfall};
+pub use modules::journal::{
+    self, CompactReport, EpochSummary, JournalEntry, append_tick, compact_journal,
+    journal_file_path, journal_summary_file_path, load_epoch_summaries, read_journal_from,
+    tail_journal,
+};
+pub use modules::lock::{DataLock, lock_data_dir, lock_file_path};
+pub use modules::logging::{self as logging, LogFormat, LogRotation, RotatingFile};
+pub use modules::market::{
+    self as market, Fill, MARKET_HISTORY_LIMIT, Market, ORDER_TTL_TICKS, Order, OrderSide,
+};
+pub use modules::memory::{self, clear_memory, load_memory, memory_path, save_memory};
+pub use modules::mempool::{Mempool, QuotaExceeded, QuotaLimits, SubmitterStats};
+pub use modules::metrics::{
+    self as metrics, TickMetrics, append_tick_metrics, load_timeline, metrics_dir,
+};
+pub use modules::ore::{
+    self as ore, DEFAULT_ORES_FILE, OreDef, OreKind, load_ores, load_registry, ores_path,
+    register_ore,
+};
+pub use modules::pacing::{PacingStats, TickPacer};
+pub use modules::paging::{PagingReport, page_zones, restore_all_zones};
+pub use modules::paths::{self, Sandbox};
+pub use modules::prompts::{self, PromptTemplates};
 pub use modules::qi::{self, QiSourceSpec, QiSourceStore, Spread};
+pub use modules::replay::{
+    ReplayMismatch, ReplayRecord, Replayer, append_replay_tick, load_replay, replay_log_path,
+    start_replay_log,
+};
+pub use modules::runs::{
+    self as runs, RunRecord, RunSeeds, RunStore, RunSummary, TagSummary, finish_run, seeds_of_run,
+};
+pub use modules::save::{
+    clear_world_state, load_world_state, save_world_state, world_state_file_path,
+};
+pub use modules::scenario::{
+    self as scenario, DEFAULT_SCENARIO_FILE, Phase, PhaseConfig, PhasePlan, ScoreWeights,
+};
+pub use modules::script::{Program, ScriptError, ScriptHost};
 pub use modules::state::{self, RuntimeState, Status};
 pub use modules::stats::{
-    ActionStats, ActionStatsStore, load_action_stats, record_successful_actions,
-    reset_action_stats, save_action_stats,
+    ActionStats, ActionStatsStore, STATS_FLUSH_TICKS, StatsRecorder, load_action_stats,
+    record_llm_decisions, record_successful_actions, reset_action_stats, save_action_stats,
+    stats_wal_path,
+};
+#[cfg(feature = "sqlite")]
+pub use modules::store::SqliteStore;
+pub use modules::store::{
+    self as store, JsonStore, READ_ONLY_ENV, Store, StoreBackend, StoreConfig,
+    append_document_items, ensure_writable, is_read_only, load_store_config, open_backend,
+    open_store, save_store_config, set_read_only,
 };
+pub use modules::stream::{StreamFilter, TickStream, pump_websocket};
 pub use modules::structure::{
-    Structure, StructureKind, StructureRecord, StructureStore, load_structure_store,
-    save_structure_store,
+    Structure, StructureKind, StructurePersister, StructureRecord, StructureStore,
+    load_structure_store, removed_structures, save_structure_store,
+};
+pub use modules::templates::{
+    self as templates, AgentTemplate, BrainConfig, DEFAULT_TEMPLATES_FILE, TemplateSet,
+};
+pub use modules::terrain::{Block, CHUNK_SIZE, ChunkPos, Terrain, TerrainChunkSnapshot};
+pub use modules::territory::{
+    TerritoryStore, ZoneClaim, load_territory_store, save_territory_store,
+};
+pub use modules::testing::{self as testing, Fixture, TickAssertions, WorldFixture};
+pub use modules::view::{
+    AgentSnapshot, AgentTrailPoint, EPOCH_TICKS, OreNodeSnapshot, RuleSummary, SEASON_TICKS,
+    SEASONS, SNAPSHOT_VERSION, SnapshotChanges, SnapshotFormat, SnapshotMeta, SnapshotRead,
+    StructureView, WorldSnapshot, ZoneSummary, epoch_of, for_each_tick_snapshot,
+    load_agent_history, load_latest_snapshot_from_dir, load_world_snapshot,
+    load_world_snapshot_consistent, save_world_snapshot, save_world_snapshot_tick,
+    set_snapshot_format, snapshot_file_path, snapshot_format, snapshot_from_persistent,
+    snapshots_dir,
 };
 pub use modules::vm::{
-    Action, ActionError, ActionRejection, ActionRequest, Agent, AgentId, DeathReason,
-    DEFAULT_MAX_AGENT_AGE, Event, POW_DIFFICULTY_BYTES, POW_REWARD, Position, Qi, QiSource,
-    QiSourceSnapshot, StructureSnapshot, TickResult, Vm, World, pow_solve, pow_valid,
+    ATTACK_RANGE, Action, ActionError, ActionRejection, ActionRequest, AdminAction, Agent, AgentId,
+    ColdZone, Contested, DEFAULT_AGENT_HP, DEFAULT_MAX_AGENT_AGE, DEFAULT_WORLD_SEED,
+    DEMOLISH_REFUND_PERCENT, DeathReason, EffectContext, Event, GIVE_RANGE, Hibernation,
+    INBOX_LIMIT, MAX_BUNDLE_ACTIONS, MAX_MESSAGE_LEN, Message, POW_DIFFICULTY_BYTES, POW_REWARD,
+    PersistentWorld, Position, Priority, Qi, QiAudit, QiSource, QiSourceSnapshot, ScanMemory,
+    ScanReport, Starvation, StructureSnapshot, TERRAIN_REACH, ThreatSnapshot, TickResult, Vm,
+    World, WorldStats, ZONE_CLAIM_COST, ZONE_SIZE, Zone, pow_solve, pow_valid,
 };
 pub use modules::wallet::{self, Wallet, WalletStore};
 pub use modules::world;
 pub use modules::world::{InfuseQiCommand, InfuseQiResult, WorldCommands, WorldQueries};
-pub use modules::view::{
-    AgentSnapshot, OreNodeSnapshot, StructureView, WorldSnapshot, load_latest_snapshot_from_dir,
-    load_world_snapshot, save_world_snapshot, save_world_snapshot_tick, snapshot_file_path,
-    snapshot_from_persistent, snapshots_dir,
-};
+pub use modules::zones::{ZoneLabel, ZoneStore, load_zone_store, save_zone_store};
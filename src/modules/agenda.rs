@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::agent::ActionArg;
+use crate::modules::agents::AgentStore;
+use crate::modules::vm::{AgentId, World};
+
+/// An action an agent takes on a schedule instead of what its brain would choose: once at
+/// tick `at`, or every `every` ticks from `at` on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledAction {
+    /// In `--action` syntax, e.g. `scan` or `build:qi`.
+    pub action: String,
+    pub at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every: Option<u64>,
+}
+
+impl ScheduledAction {
+    /// Checks that `action` parses and `every` is not 0. Without `at`, a repeating action
+    /// first runs `every` ticks from now (tick 0 counting as now).
+    pub fn new(action: &str, at: Option<u64>, every: Option<u64>) -> Result<Self, String> {
+        ActionArg::from_str(action)?;
+        if every == Some(0) {
+            return Err("every must be at least 1 tick".into());
+        }
+        let at = at
+            .or(every)
+            .ok_or("a scheduled action needs a tick (--at) or a period (--every)")?;
+        Ok(Self {
+            action: action.trim().to_string(),
+            at,
+            every,
+        })
+    }
+
+    pub fn is_due(&self, tick: u64) -> bool {
+        match self.every {
+            Some(every) => tick >= self.at && (tick - self.at).is_multiple_of(every),
+            None => tick == self.at,
+        }
+    }
+
+    /// E.g. `every 50 ticks from tick 50: scan`.
+    pub fn describe(&self) -> String {
+        match self.every {
+            Some(every) => format!(
+                "every {} ticks from tick {}: {}",
+                every, self.at, self.action
+            ),
+            None => format!("at tick {}: {}", self.at, self.action),
+        }
+    }
+}
+
+/// The schedules of a run's agents, read from their registry profiles when the run starts.
+#[derive(Debug, Clone, Default)]
+pub struct Agenda {
+    schedules: BTreeMap<AgentId, Vec<(ScheduledAction, ActionArg)>>,
+}
+
+impl Agenda {
+    /// Schedules of `agent_ids`, found by the registry address each agent is named after.
+    pub fn for_agents(
+        registry: &AgentStore,
+        world: &World,
+        agent_ids: &[AgentId],
+    ) -> Result<Self, String> {
+        let mut agenda = Self::default();
+        for agent_id in agent_ids {
+            let Some(profile) = world
+                .agent(*agent_id)
+                .and_then(|agent| registry.agents.get(&agent.name))
+            else {
+                continue;
+            };
+            for scheduled in &profile.schedule {
+                agenda
+                    .schedule(*agent_id, scheduled.clone())
+                    .map_err(|e| format!("agent {}: {}: {}", profile.id, scheduled.action, e))?;
+            }
+        }
+        Ok(agenda)
+    }
+
+    pub fn schedule(
+        &mut self,
+        agent_id: AgentId,
+        scheduled: ScheduledAction,
+    ) -> Result<(), String> {
+        let action = ActionArg::from_str(&scheduled.action)?;
+        self.schedules
+            .entry(agent_id)
+            .or_default()
+            .push((scheduled, action));
+        Ok(())
+    }
+
+    /// Scheduled actions across all agents.
+    pub fn len(&self) -> usize {
+        self.schedules.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schedules.is_empty()
+    }
+
+    /// The action each agent has due at `tick`. When several are due, the one scheduled
+    /// first wins.
+    pub fn due(&self, tick: u64) -> Vec<(AgentId, &ActionArg)> {
+        self.schedules
+            .iter()
+            .filter_map(|(agent_id, schedule)| {
+                schedule
+                    .iter()
+                    .find(|(scheduled, _)| scheduled.is_due(tick))
+                    .map(|(_, action)| (*agent_id, action))
+            })
+            .collect()
+    }
+}
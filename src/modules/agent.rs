@@ -1,21 +1,35 @@
 use std::collections::HashSet;
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
 use clap::ValueEnum;
-use reqwest::blocking::Client;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serde_toon::to_string_pretty;
-use std::fs;
-use std::path::PathBuf;
-
+use tokio::runtime::Runtime;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::modules::inventory::ItemKind;
+use crate::modules::logging::LLM_TARGET;
+use crate::modules::market::OrderSide;
+use crate::modules::memory;
 use crate::modules::ore::OreKind;
+use crate::modules::prompts::{self, DEFAULT_USER_TEMPLATE, PromptTemplates};
 use crate::modules::structure::StructureKind;
-use crate::modules::vm::{Action, AgentId, SCAN_RANGE, Vm};
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+use crate::modules::terrain::Block;
+use crate::modules::vm::{
+    Action, ActionError, AgentId, GIVE_RANGE, HARVEST_RANGE, MAX_BUNDLE_ACTIONS, MAX_MESSAGE_LEN,
+    MAX_MOVE_RADIUS, Position, Qi, SCAN_RANGE, STRUCTURE_DURABILITY, TERRAIN_REACH, Vm,
+    ZONE_CLAIM_COST,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BrainMode {
     /// Deterministic loop (uses provided/default action cycle)
     Loop,
@@ -29,32 +43,191 @@ pub const DEFAULT_AGENT_GOAL: &str = "Evolve, survive, build machines, form terr
 pub enum ActionArg {
     Scan,
     Idle,
-    Move { dx: i32, dy: i32, dz: i32 },
-    Reproduce { partner: AgentId },
-    BuildStructure { kind: StructureKind },
-    HarvestOre { ore: OreKind, source_id: u64 },
+    Rest,
+    ClaimZone,
+    /// `order:<buy|sell>,<ore>,<amount>,<price>` on the market.
+    PostOrder {
+        side: OrderSide,
+        ore: OreKind,
+        amount: Qi,
+        price: Qi,
+    },
+    /// `craft:<recipe>`, e.g. `craft:circuit`.
+    Craft {
+        recipe: String,
+    },
+    /// `repair[:<structure_id>]`; without an id, the most worn structure in reach.
+    Repair {
+        structure_id: u64,
+    },
+    /// `demolish:<structure_id>` for a structure the agent owns.
+    DemolishStructure {
+        structure_id: u64,
+    },
+    /// `give:<agent_id>,<amount>` of the agent's Qi.
+    GiveQi {
+        to: AgentId,
+        amount: Qi,
+    },
+    /// `set_toll:<harvest>,<build>` in the zone the agent holds.
+    SetToll {
+        harvest: Qi,
+        build: Qi,
+    },
+    Move {
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    MoveTo {
+        target: Position,
+    },
+    Reproduce {
+        partner: AgentId,
+    },
+    BuildStructure {
+        kind: StructureKind,
+    },
+    HarvestOre {
+        ore: OreKind,
+        source_id: u64,
+    },
+    /// `harvest:@dx,dy,dz`: the node in the cell at that offset, resolved by the VM.
+    HarvestAt {
+        ore: OreKind,
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    Trade {
+        partner: AgentId,
+        offer_ore: OreKind,
+        offer_amount: Qi,
+        want_ore: OreKind,
+        want_amount: Qi,
+    },
+    Attack {
+        target: AgentId,
+    },
+    Dig {
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    Place {
+        block: Block,
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    /// `say:<to>,<message>`; `to` 0 broadcasts within scan range.
+    Say {
+        to: AgentId,
+        message: String,
+    },
+    /// Actions taken together in one all-or-nothing turn, written `a+b+c`.
+    Bundle(Vec<ActionArg>),
 }
 
 impl ActionArg {
+    /// The single actions this argument stands for: a bundle's parts, or itself.
+    pub fn parts(&self) -> &[ActionArg] {
+        match self {
+            ActionArg::Bundle(parts) => parts,
+            single => std::slice::from_ref(single),
+        }
+    }
+
     pub fn label(&self) -> String {
         match self {
             ActionArg::Scan => "scan".to_string(),
             ActionArg::Idle => "idle".to_string(),
+            ActionArg::Rest => "rest".to_string(),
+            ActionArg::ClaimZone => "claim_zone".to_string(),
+            ActionArg::SetToll { .. } => "set_toll".to_string(),
+            ActionArg::PostOrder { .. } => "post_order".to_string(),
+            ActionArg::Craft { .. } => "craft".to_string(),
+            ActionArg::Repair { .. } => "repair".to_string(),
+            ActionArg::DemolishStructure { .. } => "demolish".to_string(),
+            ActionArg::GiveQi { .. } => "give_qi".to_string(),
             ActionArg::Move { .. } => "move".to_string(),
+            ActionArg::MoveTo { .. } => "move_to".to_string(),
             ActionArg::Reproduce { .. } => "reproduce".to_string(),
             ActionArg::BuildStructure { kind } => format!("build_{}", kind),
-            ActionArg::HarvestOre { ore, .. } => format!("harvest_{}", ore),
+            ActionArg::HarvestOre { ore, .. } | ActionArg::HarvestAt { ore, .. } => {
+                format!("harvest_{}", ore)
+            }
+            ActionArg::Trade { .. } => "trade".to_string(),
+            ActionArg::Attack { .. } => "attack".to_string(),
+            ActionArg::Dig { .. } => "dig".to_string(),
+            ActionArg::Place { block, .. } => format!("place_{}", block),
+            ActionArg::Say { .. } => "say".to_string(),
+            ActionArg::Bundle(parts) => parts
+                .iter()
+                .map(ActionArg::label)
+                .collect::<Vec<_>>()
+                .join("+"),
         }
     }
 
+    /// The VM action for a single argument. A bundle materializes to its first part; use
+    /// `parts` to build the whole request.
     pub fn materialize(&self, _agent_id: AgentId, _next_tick: u64) -> Action {
         match *self {
             ActionArg::Scan => Action::Scan,
             ActionArg::Idle => Action::Idle,
+            ActionArg::Rest => Action::Rest,
+            ActionArg::ClaimZone => Action::ClaimZone,
+            ActionArg::SetToll { harvest, build } => Action::SetToll { harvest, build },
+            ActionArg::PostOrder {
+                side,
+                ore,
+                amount,
+                price,
+            } => Action::PostOrder {
+                side,
+                ore,
+                amount,
+                price,
+            },
             ActionArg::Move { dx, dy, dz } => Action::Move { dx, dy, dz },
+            ActionArg::MoveTo { target } => Action::MoveTo { target },
             ActionArg::Reproduce { partner } => Action::Reproduce { partner },
             ActionArg::BuildStructure { kind } => Action::BuildStructure { kind },
             ActionArg::HarvestOre { ore, source_id } => Action::HarvestOre { ore, source_id },
+            ActionArg::HarvestAt { ore, dx, dy, dz } => Action::HarvestAt { ore, dx, dy, dz },
+            ActionArg::Trade {
+                partner,
+                offer_ore,
+                offer_amount,
+                want_ore,
+                want_amount,
+            } => Action::Trade {
+                partner,
+                offer_ore,
+                offer_amount,
+                want_ore,
+                want_amount,
+            },
+            ActionArg::Attack { target } => Action::Attack { target },
+            ActionArg::Dig { dx, dy, dz } => Action::Dig { dx, dy, dz },
+            ActionArg::Place { block, dx, dy, dz } => Action::Place { dx, dy, dz, block },
+            ActionArg::Say { to, ref message } => Action::Say {
+                to,
+                message: message.clone(),
+            },
+            ActionArg::Craft { ref recipe } => Action::Craft {
+                recipe: recipe.clone(),
+            },
+            ActionArg::Repair { structure_id } => Action::Repair { structure_id },
+            ActionArg::DemolishStructure { structure_id } => {
+                Action::DemolishStructure { structure_id }
+            }
+            ActionArg::GiveQi { to, amount } => Action::GiveQi { to, amount },
+            ActionArg::Bundle(ref parts) => parts
+                .first()
+                .map(|part| part.materialize(_agent_id, _next_tick))
+                .unwrap_or(Action::Idle),
         }
     }
 }
@@ -64,6 +237,19 @@ impl FromStr for ActionArg {
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
         let trimmed = input.trim();
+        if trimmed.contains('+') {
+            let parts = trimmed
+                .split('+')
+                .map(ActionArg::from_str)
+                .collect::<Result<Vec<_>, _>>()?;
+            if parts.len() > MAX_BUNDLE_ACTIONS {
+                return Err(format!(
+                    "a bundle takes at most {} actions",
+                    MAX_BUNDLE_ACTIONS
+                ));
+            }
+            return Ok(ActionArg::Bundle(parts));
+        }
         let (verb, rest) = match trimmed.split_once(':') {
             Some((verb, rest)) => (verb.to_lowercase(), Some(rest)),
             None => (trimmed.to_lowercase(), None),
@@ -72,6 +258,87 @@ impl FromStr for ActionArg {
         match verb.as_str() {
             "scan" => Ok(ActionArg::Scan),
             "idle" => Ok(ActionArg::Idle),
+            "rest" => Ok(ActionArg::Rest),
+            "claim" | "claim_zone" => Ok(ActionArg::ClaimZone),
+            "order" | "post_order" => {
+                let spec = rest.ok_or(
+                    "order requires side,ore,amount,price e.g. order:sell,transistor,1,80",
+                )?;
+                let parts: Vec<_> = spec.split(',').map(str::trim).collect();
+                if parts.len() != 4 {
+                    return Err("order requires side,ore,amount,price".into());
+                }
+                let side = OrderSide::from_str(parts[0]).map_err(|_| "side must be buy or sell")?;
+                let ore = <OreKind as FromStr>::from_str(parts[1])?;
+                let amount = parts[2]
+                    .parse::<Qi>()
+                    .map_err(|_| "amount must be an integer")?;
+                let price = parts[3]
+                    .parse::<Qi>()
+                    .map_err(|_| "price must be an integer")?;
+                Ok(ActionArg::PostOrder {
+                    side,
+                    ore,
+                    amount,
+                    price,
+                })
+            }
+            "craft" => {
+                let recipe = rest
+                    .map(str::trim)
+                    .filter(|r| !r.is_empty())
+                    .ok_or("craft requires a recipe e.g. craft:circuit")?;
+                Ok(ActionArg::Craft {
+                    recipe: recipe.to_lowercase(),
+                })
+            }
+            "repair" => {
+                let structure_id = match rest {
+                    Some(val) => val
+                        .trim()
+                        .parse::<u64>()
+                        .map_err(|_| "structure_id must be an integer".to_string())?,
+                    None => 0,
+                };
+                Ok(ActionArg::Repair { structure_id })
+            }
+            "demolish" => {
+                let structure_id = rest
+                    .ok_or("demolish requires a structure id e.g. demolish:3")?
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|_| "structure_id must be an integer")?;
+                Ok(ActionArg::DemolishStructure { structure_id })
+            }
+            "give" | "give_qi" => {
+                let spec = rest.ok_or("give requires to,amount e.g. give:2,5")?;
+                let (to, amount) = spec.split_once(',').ok_or("give requires to,amount")?;
+                let to = to
+                    .trim()
+                    .parse::<AgentId>()
+                    .map_err(|_| "to must be an agent id")?;
+                let amount = amount
+                    .trim()
+                    .parse::<Qi>()
+                    .map_err(|_| "amount must be an integer")?;
+                Ok(ActionArg::GiveQi { to, amount })
+            }
+            "set_toll" | "toll" => {
+                let tolls = rest.ok_or("set_toll requires harvest,build e.g. set_toll:1,3")?;
+                let (harvest, build) = tolls
+                    .split_once(',')
+                    .ok_or("set_toll requires harvest,build")?;
+                let toll = |value: &str, name: &str| {
+                    value
+                        .trim()
+                        .parse::<Qi>()
+                        .map_err(|_| format!("{} toll must be a non-negative integer", name))
+                };
+                Ok(ActionArg::SetToll {
+                    harvest: toll(harvest, "harvest")?,
+                    build: toll(build, "build")?,
+                })
+            }
             "move" => {
                 let coords = rest.ok_or("move requires dx,dy,dz e.g. move:1,0,-1")?;
                 let parts: Vec<_> = coords.split(',').collect();
@@ -122,10 +389,18 @@ impl FromStr for ActionArg {
                 let mut ore = if let Some(suffix) = v.strip_prefix("harvest_") {
                     <OreKind as FromStr>::from_str(suffix.trim()).unwrap_or_default()
                 } else {
-                    OreKind::Qi
+                    OreKind::QI
                 };
 
                 let mut source_id = 0u64;
+                if let Some((prefix, coords)) = rest.and_then(|val| val.split_once('@')) {
+                    if !prefix.trim().is_empty() {
+                        ore = <OreKind as FromStr>::from_str(prefix.trim())?;
+                    }
+                    let (dx, dy, dz) =
+                        parse_offset(coords).map_err(|e| format!("harvest:@dx,dy,dz {}", e))?;
+                    return Ok(ActionArg::HarvestAt { ore, dx, dy, dz });
+                }
                 if let Some(val) = rest {
                     let parts: Vec<_> = val.split(',').collect();
                     if parts.len() == 1 {
@@ -135,11 +410,12 @@ impl FromStr for ActionArg {
                         } else if let Ok(parsed_ore) = <OreKind as FromStr>::from_str(token) {
                             ore = parsed_ore;
                         } else {
-                            return Err("harvest expects source_id or ore,source_id".into());
+                            return Err(
+                                "harvest expects source_id, ore,source_id or [ore]@dx,dy,dz".into(),
+                            );
                         }
                     } else {
-                        ore = <OreKind as FromStr>::from_str(parts[0].trim())
-                            .map_err(|_| "ore must be qi or transistor")?;
+                        ore = <OreKind as FromStr>::from_str(parts[0].trim())?;
                         source_id = parts[1]
                             .trim()
                             .parse::<u64>()
@@ -149,20 +425,548 @@ impl FromStr for ActionArg {
 
                 Ok(ActionArg::HarvestOre { ore, source_id })
             }
+            "trade" => {
+                let terms = rest.ok_or(
+                    "trade requires partner,offer_ore,offer_amount,want_ore,want_amount e.g. trade:2,qi,3,transistor,1",
+                )?;
+                let parts: Vec<_> = terms.split(',').map(|p| p.trim()).collect();
+                if parts.len() != 5 {
+                    return Err(
+                        "trade requires partner,offer_ore,offer_amount,want_ore,want_amount".into(),
+                    );
+                }
+
+                let partner = parts[0]
+                    .parse::<AgentId>()
+                    .map_err(|_| "partner must be an integer")?;
+                let offer_ore = <OreKind as FromStr>::from_str(parts[1])
+                    .map_err(|e| format!("offer_ore: {}", e))?;
+                let offer_amount = parts[2]
+                    .parse::<Qi>()
+                    .map_err(|_| "offer_amount must be an integer")?;
+                let want_ore = <OreKind as FromStr>::from_str(parts[3])
+                    .map_err(|e| format!("want_ore: {}", e))?;
+                let want_amount = parts[4]
+                    .parse::<Qi>()
+                    .map_err(|_| "want_amount must be an integer")?;
+
+                Ok(ActionArg::Trade {
+                    partner,
+                    offer_ore,
+                    offer_amount,
+                    want_ore,
+                    want_amount,
+                })
+            }
+            "attack" => {
+                let target = match rest {
+                    Some(val) => val
+                        .trim()
+                        .parse::<AgentId>()
+                        .map_err(|_| "target must be an integer".to_string())?,
+                    None => 0,
+                };
+                Ok(ActionArg::Attack { target })
+            }
+            "move_to" | "moveto" => {
+                let coords = rest.ok_or("move_to requires x,y,z e.g. move_to:10,0,-4")?;
+                let (x, y, z) = parse_triple(coords, ["x", "y", "z"])?;
+                Ok(ActionArg::MoveTo {
+                    target: Position { x, y, z },
+                })
+            }
+            "dig" => {
+                let coords = rest.ok_or("dig requires dx,dy,dz e.g. dig:0,-1,0")?;
+                let (dx, dy, dz) = parse_offset(coords)?;
+                Ok(ActionArg::Dig { dx, dy, dz })
+            }
+            "place" => {
+                let spec = rest.ok_or("place requires block,dx,dy,dz e.g. place:dirt,1,0,0")?;
+                let (block, coords) = spec
+                    .split_once(',')
+                    .ok_or("place requires block,dx,dy,dz")?;
+                let block = Block::from_str(block)
+                    .ok()
+                    .filter(|b| b.is_solid())
+                    .ok_or("block must be dirt or stone")?;
+                let (dx, dy, dz) = parse_offset(coords)?;
+                Ok(ActionArg::Place { block, dx, dy, dz })
+            }
+            "say" => {
+                let spec = rest.ok_or("say requires to,message e.g. say:0,hello")?;
+                let (to, message) = spec.split_once(',').ok_or("say requires to,message")?;
+                let to = to
+                    .trim()
+                    .parse::<AgentId>()
+                    .map_err(|_| "to must be an agent id, or 0 for everyone in range")?;
+                Ok(ActionArg::Say {
+                    to,
+                    message: message.trim().to_string(),
+                })
+            }
             _ => Err(format!(
-                "Unknown action '{}'. Use scan | idle | move:<dx>,<dy>,<dz> | reproduce:<agent_id> | build[:kind] | harvest[:ore,source_id]",
+                "Unknown action '{}'. Use scan | idle | rest | claim_zone | set_toll:<harvest>,<build> | order:<buy|sell>,<ore>,<amount>,<price> | craft:<recipe> | repair[:<structure_id>] | demolish:<structure_id> | give:<agent_id>,<amount> | move:<dx>,<dy>,<dz> | move_to:<x>,<y>,<z> | reproduce[:<agent_id>] | build[:kind] | harvest[:ore,source_id] | harvest:[ore]@<dx>,<dy>,<dz> | trade:<partner>,<offer_ore>,<offer_amount>,<want_ore>,<want_amount> | attack:<agent_id> | dig:<dx>,<dy>,<dz> | place:<block>,<dx>,<dy>,<dz> | say:<agent_id|0>,<message>, joined with + for a bundle",
                 verb
             )),
         }
     }
 }
 
-#[derive(Default, Debug, Clone)]
+/// What an agent's brain carries from tick to tick. The notes outlive the run (see
+/// `memory::save_memory`); the constraints only matter to the next prompt and are not saved.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct BrainMemory {
+    #[serde(default)]
     pub notes: Vec<String>,
+    /// Why the previous action was rejected, shown to the next prompt; empty after a success.
+    #[serde(skip)]
+    pub constraints: Vec<Constraint>,
+    /// Notes that fell out of `notes` while vector memory was on, oldest first, for
+    /// `LlmClient::with_vector_memory` to recall. At most `memory::ARCHIVE_LIMIT` are kept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archive: Vec<ArchivedNote>,
+    /// The embedding model behind the archive's vectors; asking another re-embeds them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+}
+
+/// A note in `BrainMemory::archive` and, once embedded, its vector.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedNote {
+    pub note: String,
+    /// Empty until the note is embedded before the agent's next prompt.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vector: Vec<f32>,
+}
+
+/// A machine-readable fact derived from an `ActionError`, e.g. that a cell is taken or where
+/// the nearest ore node is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Constraint {
+    /// Stable identifier, e.g. `structure_space_occupied` or `nearest_ore_node`.
+    pub code: &'static str,
+    pub message: String,
+    /// The ids, positions and amounts behind `message`.
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub data: serde_json::Value,
+}
+
+impl Constraint {
+    fn new(code: &'static str, message: impl Into<String>, data: serde_json::Value) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data,
+        }
+    }
+}
+
+fn describe(position: Position) -> String {
+    format!("({},{},{})", position.x, position.y, position.z)
+}
+
+/// Steps between two cells when diagonal moves count as one, as `within_range` measures them.
+fn reach_between(a: Position, b: Position) -> i32 {
+    (a.x - b.x)
+        .abs()
+        .max((a.y - b.y).abs())
+        .max((a.z - b.z).abs())
+}
+
+/// The nearest basic structure the agent owns, as a constraint.
+fn nearest_own_basic_structure(vm: &Vm, agent_id: AgentId) -> Option<Constraint> {
+    let from = vm.world().agent(agent_id)?.position;
+    let structure = vm
+        .world()
+        .structures()
+        .iter()
+        .filter(|s| s.owner == agent_id && s.kind == StructureKind::Basic)
+        .min_by_key(|s| (reach_between(from, s.position), s.id))?;
+    let distance = reach_between(from, structure.position);
+    Some(Constraint::new(
+        "nearest_own_structure",
+        format!(
+            "your nearest basic structure: id {} at {}, distance {}",
+            structure.id,
+            describe(structure.position),
+            distance
+        ),
+        json!({
+            "structure_id": structure.id,
+            "position": structure.position,
+            "distance": distance,
+        }),
+    ))
+}
+
+/// The nearest node of `ore` with anything left to harvest, as a constraint.
+fn nearest_ore_node(vm: &Vm, agent_id: AgentId, ore: OreKind) -> Option<Constraint> {
+    let from = vm.world().agent(agent_id)?.position;
+    let source = vm
+        .world()
+        .qi_sources()
+        .iter()
+        .filter(|source| source.ore == ore && source.current > 0)
+        .min_by_key(|source| (reach_between(from, source.position), source.id))?;
+    let distance = reach_between(from, source.position);
+    Some(Constraint::new(
+        "nearest_ore_node",
+        format!(
+            "nearest {} node: id {} at {}, distance {}",
+            ore,
+            source.id,
+            describe(source.position),
+            distance
+        ),
+        json!({
+            "ore": ore.to_string(),
+            "source_id": source.id,
+            "position": source.position,
+            "distance": distance,
+            "available": source.current,
+        }),
+    ))
+}
+
+/// Constraints explaining `error` to the agent that was rejected, with hints from the current
+/// world where one helps (e.g. the nearest Qi node when it ran out of Qi).
+pub fn rejection_constraints(vm: &Vm, agent_id: AgentId, error: &ActionError) -> Vec<Constraint> {
+    let mut constraints = Vec::new();
+    match error {
+        ActionError::AgentNotFound(_) | ActionError::AgentDead(_) => {
+            constraints.push(Constraint::new(
+                "agent_unavailable",
+                "you cannot act: agent missing or dead",
+                serde_json::Value::Null,
+            ));
+        }
+        ActionError::InsufficientQi {
+            required,
+            available,
+            ..
+        } => {
+            constraints.push(Constraint::new(
+                "insufficient_qi",
+                format!("not enough qi: need {}, have {}", required, available),
+                json!({ "required": required, "available": available }),
+            ));
+            constraints.extend(nearest_ore_node(vm, agent_id, OreKind::QI));
+        }
+        ActionError::InsufficientItem {
+            item,
+            required,
+            available,
+            ..
+        } => {
+            constraints.push(Constraint::new(
+                "insufficient_item",
+                format!("not enough {}: need {}, have {}", item, required, available),
+                json!({ "item": item.to_string(), "required": required, "available": available }),
+            ));
+            if let Some(ore) = OreKind::all()
+                .into_iter()
+                .find(|ore| ItemKind::from(*ore) == *item)
+            {
+                constraints.extend(nearest_ore_node(vm, agent_id, ore));
+            }
+        }
+        ActionError::InvalidPow { nonce, .. } => constraints.push(Constraint::new(
+            "invalid_pow",
+            format!("proof of work with nonce {} is invalid", nonce),
+            json!({ "nonce": nonce }),
+        )),
+        ActionError::PositionOccupied {
+            target,
+            occupied_by,
+            ..
+        } => constraints.push(Constraint::new(
+            "position_occupied",
+            format!(
+                "cannot move to {}: occupied by agent {}",
+                describe(*target),
+                occupied_by
+            ),
+            json!({ "position": target, "occupied_by": occupied_by }),
+        )),
+        ActionError::ReproductionDeclined { partner, .. } => constraints.push(Constraint::new(
+            "reproduction_declined",
+            format!("agent {} did not agree to reproduce this tick", partner),
+            json!({ "partner": partner }),
+        )),
+        ActionError::PartnerNotFound { partner, .. } => constraints.push(Constraint::new(
+            "partner_not_found",
+            format!("agent {} does not exist or is dead", partner),
+            json!({ "partner": partner }),
+        )),
+        ActionError::PartnerOutOfZone { partner, .. } => constraints.push(Constraint::new(
+            "partner_out_of_zone",
+            format!("agent {} is not in your zone", partner),
+            json!({ "partner": partner }),
+        )),
+        ActionError::NoEligiblePartner { .. } => constraints.push(Constraint::new(
+            "no_eligible_partner",
+            "no agent in your zone can reproduce with you",
+            serde_json::Value::Null,
+        )),
+        ActionError::InvalidBundle { reason, .. } => constraints.push(Constraint::new(
+            "invalid_bundle",
+            format!("invalid bundle: {}", reason),
+            serde_json::Value::Null,
+        )),
+        ActionError::ReproductionCooldown { ready_at, .. } => constraints.push(Constraint::new(
+            "reproduction_cooldown",
+            format!("cannot reproduce again until tick {}", ready_at),
+            json!({ "ready_at": ready_at }),
+        )),
+        ActionError::StructureSpaceOccupied { position, .. } => constraints.push(Constraint::new(
+            "structure_space_occupied",
+            format!("cannot build here: {} is occupied", describe(*position)),
+            json!({ "position": position }),
+        )),
+        ActionError::BlockedByTerrain { target, .. } => constraints.push(Constraint::new(
+            "blocked_by_terrain",
+            format!("cannot move into solid terrain at {}", describe(*target)),
+            json!({ "position": target }),
+        )),
+        ActionError::TerrainOutOfReach { dx, dy, dz, .. } => constraints.push(Constraint::new(
+            "terrain_out_of_reach",
+            format!(
+                "can only dig or place within {} cell(s) of you, not ({},{},{})",
+                TERRAIN_REACH, dx, dy, dz
+            ),
+            json!({ "offset": [dx, dy, dz], "max_reach": TERRAIN_REACH }),
+        )),
+        ActionError::NothingToDig { target, .. } => constraints.push(Constraint::new(
+            "nothing_to_dig",
+            format!("nothing to dig at {}", describe(*target)),
+            json!({ "position": target }),
+        )),
+        ActionError::PlaceBlocked { target, .. } => constraints.push(Constraint::new(
+            "place_blocked",
+            format!("cannot place a block at {}: not empty", describe(*target)),
+            json!({ "position": target }),
+        )),
+        // Only operators revive agents; no action is refused with it.
+        ActionError::AgentAlive(_) => {}
+        ActionError::OreSourceNotFound(source_id) => {
+            constraints.push(Constraint::new(
+                "ore_source_unavailable",
+                format!("ore node {} does not exist", source_id),
+                json!({ "source_id": source_id }),
+            ));
+        }
+        ActionError::OreSourceUnavailable { ore, source_id, .. } => {
+            let message = match source_id {
+                Some(id) => format!("no {} node {} within harvest range", ore, id),
+                None => format!("no {} node within harvest range", ore),
+            };
+            constraints.push(Constraint::new(
+                "ore_source_unavailable",
+                message,
+                json!({ "ore": ore.to_string(), "source_id": source_id, "harvest_range": HARVEST_RANGE }),
+            ));
+            constraints.extend(nearest_ore_node(vm, agent_id, *ore));
+        }
+        ActionError::OreSourceDepleted {
+            ore,
+            source_id,
+            available,
+            ..
+        } => {
+            constraints.push(Constraint::new(
+                "ore_source_depleted",
+                format!(
+                    "{} node {} is depleted ({} left)",
+                    ore, source_id, available
+                ),
+                json!({ "ore": ore.to_string(), "source_id": source_id, "available": available }),
+            ));
+            constraints.extend(nearest_ore_node(vm, agent_id, *ore));
+        }
+        ActionError::NoOreNodeAt { ore, target, .. } => {
+            constraints.push(Constraint::new(
+                "no_ore_node_at",
+                format!("no {} node at {}", ore, describe(*target)),
+                json!({ "ore": ore.to_string(), "position": target }),
+            ));
+            constraints.extend(nearest_ore_node(vm, agent_id, *ore));
+        }
+        ActionError::MoveOutOfRange { dx, dy, dz, .. } => constraints.push(Constraint::new(
+            "move_out_of_range",
+            format!(
+                "move at most {} cell(s) per axis, not ({},{},{}); use move_to for longer trips",
+                MAX_MOVE_RADIUS, dx, dy, dz
+            ),
+            json!({ "offset": [dx, dy, dz], "max_move_radius": MAX_MOVE_RADIUS }),
+        )),
+        ActionError::NoRestingPlace { .. } => {
+            constraints.push(Constraint::new(
+                "no_resting_place",
+                format!(
+                    "rest only within {} cell(s) of a basic structure you own",
+                    HARVEST_RANGE
+                ),
+                json!({ "structure_kind": "basic", "range": HARVEST_RANGE }),
+            ));
+            constraints.extend(nearest_own_basic_structure(vm, agent_id));
+        }
+        ActionError::NothingToRecover { .. } => constraints.push(Constraint::new(
+            "nothing_to_recover",
+            "the recycled Qi pool is empty; resting recovers nothing until Qi is spent",
+            json!({ "recycled_qi": 0 }),
+        )),
+        ActionError::InvalidMessage { .. } => constraints.push(Constraint::new(
+            "invalid_message",
+            format!(
+                "say 1 to {} characters, to another agent or 0 for everyone in range",
+                MAX_MESSAGE_LEN
+            ),
+            json!({ "max_message_len": MAX_MESSAGE_LEN }),
+        )),
+        ActionError::NoListener { to, .. } => constraints.push(Constraint::new(
+            "no_listener",
+            if *to == 0 {
+                format!("nobody is alive within {} cell(s) to hear you", SCAN_RANGE)
+            } else {
+                format!(
+                    "agent {} is not alive within {} cell(s) of you",
+                    to, SCAN_RANGE
+                )
+            },
+            json!({ "to": to, "range": SCAN_RANGE }),
+        )),
+        ActionError::ZoneClaimed {
+            agent_id: claimant,
+            zone,
+            owner,
+        } => constraints.push(Constraint::new(
+            "zone_claimed",
+            if claimant == owner {
+                format!("you already hold zone ({},{},{})", zone.x, zone.y, zone.z)
+            } else {
+                format!(
+                    "agent {} holds zone ({},{},{}); only it may build there",
+                    owner, zone.x, zone.y, zone.z
+                )
+            },
+            json!({ "zone": zone, "owner": owner }),
+        )),
+        ActionError::InvalidOrder { .. } => constraints.push(Constraint::new(
+            "invalid_order",
+            "orders trade a non-Qi ore for Qi, with amount and price above 0",
+            json!({ "tradeable_ores": OreKind::all()
+                .into_iter()
+                .filter(|ore| *ore != OreKind::QI)
+                .map(OreKind::label)
+                .collect::<Vec<_>>() }),
+        )),
+        ActionError::NothingToRepair { .. } => constraints.push(Constraint::new(
+            "nothing_to_repair",
+            format!(
+                "repair only a worn structure within {} blocks; scan to find one",
+                HARVEST_RANGE
+            ),
+            json!({ "range": HARVEST_RANGE, "max_durability": STRUCTURE_DURABILITY }),
+        )),
+        ActionError::StructureNotFound { .. } | ActionError::NotStructureOwner { .. } => {
+            let owned: Vec<u64> = vm
+                .world()
+                .structures()
+                .iter()
+                .filter(|s| s.owner == agent_id)
+                .map(|s| s.id)
+                .collect();
+            constraints.push(Constraint::new(
+                "not_your_structure",
+                format!(
+                    "demolish only a structure you own within {} blocks",
+                    HARVEST_RANGE
+                ),
+                json!({ "owned": owned }),
+            ));
+        }
+        ActionError::UnknownRecipe { recipe, .. } => {
+            let known: Vec<&str> = vm.recipes().iter().map(|(name, _)| name).collect();
+            constraints.push(Constraint::new(
+                "unknown_recipe",
+                format!(
+                    "there is no recipe '{}'; craft one of the known recipes",
+                    recipe
+                ),
+                json!({ "recipes": known }),
+            ));
+        }
+        ActionError::NotZoneOwner { zone, .. } => constraints.push(Constraint::new(
+            "not_zone_owner",
+            format!(
+                "set tolls only in a zone you hold, not ({},{},{}); claim_zone first",
+                zone.x, zone.y, zone.z
+            ),
+            json!({ "zone": zone }),
+        )),
+        ActionError::NoPath { target, .. } => constraints.push(Constraint::new(
+            "no_path",
+            format!("no walkable path to {}", describe(*target)),
+            json!({ "position": target }),
+        )),
+        ActionError::TradeDeclined { partner, .. } => constraints.push(Constraint::new(
+            "trade_declined",
+            format!("agent {} did not submit the matching trade", partner),
+            json!({ "partner": partner }),
+        )),
+        ActionError::InvalidTrade { partner, .. } => constraints.push(Constraint::new(
+            "invalid_trade",
+            format!("the trade with agent {} is not valid", partner),
+            json!({ "partner": partner }),
+        )),
+        ActionError::TargetNotFound { target, .. } => constraints.push(Constraint::new(
+            "target_not_found",
+            format!("agent {} does not exist or is dead", target),
+            json!({ "target": target }),
+        )),
+        ActionError::TargetOutOfRange { target, .. } => constraints.push(Constraint::new(
+            "target_out_of_range",
+            format!("agent {} is out of reach", target),
+            json!({ "target": target }),
+        )),
+        ActionError::InvalidGift { .. } => constraints.push(Constraint::new(
+            "invalid_gift",
+            "give a positive amount of qi to another agent",
+            serde_json::Value::Null,
+        )),
+        ActionError::RecipientNotFound { to, .. } => constraints.push(Constraint::new(
+            "recipient_not_found",
+            format!("agent {} does not exist or is dead", to),
+            json!({ "to": to }),
+        )),
+        ActionError::RecipientOutOfReach { to, .. } => constraints.push(Constraint::new(
+            "recipient_out_of_reach",
+            format!(
+                "agent {} is not in your zone or within {} blocks; move closer first",
+                to, GIVE_RANGE
+            ),
+            json!({ "to": to, "range": GIVE_RANGE }),
+        )),
+        ActionError::Outranked {
+            contested,
+            winner,
+            cause,
+            ..
+        } => {
+            constraints.push(Constraint::new(
+                "outranked",
+                format!("agent {} took the {} first", winner, contested),
+                json!({ "winner": winner }),
+            ));
+            constraints.extend(rejection_constraints(vm, agent_id, cause));
+        }
+    }
+    constraints
 }
 
 const MEMORY_LIMIT: usize = 5;
+/// Received messages shown in each observation, the newest ones.
+const OBSERVED_MESSAGES: usize = 5;
 
 #[derive(Debug, Clone)]
 pub struct LlmDecision {
@@ -176,6 +980,57 @@ pub struct LlmDecision {
     pub provider: LlmProvider,
     pub action: Action,
     pub llm_ok: bool,
+    /// Wall-clock time spent obtaining the decision (including retries).
+    pub latency_ms: u64,
+    /// Requests sent and tokens spent on the decision, retries included.
+    pub usage: LlmUsage,
+}
+
+/// Requests sent to an LLM and the tokens they cost, as the provider reports them. Failed
+/// requests count, but only replies carry token counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl LlmUsage {
+    pub fn tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub fn add(&mut self, other: LlmUsage) {
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// What a run may spend on LLM decisions; `None` leaves that side unlimited. It is checked
+/// between ticks, so the tick that crosses a limit still finishes its requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LlmBudget {
+    pub max_requests: Option<u64>,
+    pub max_tokens: Option<u64>,
+}
+
+impl LlmBudget {
+    /// Why `usage` has spent the budget, or `None` while some of it is left.
+    pub fn exhausted_by(&self, usage: &LlmUsage) -> Option<String> {
+        if let Some(max) = self.max_requests
+            && usage.requests >= max
+        {
+            return Some(format!("{} of {} LLM requests used", usage.requests, max));
+        }
+        if let Some(max) = self.max_tokens
+            && usage.tokens() >= max
+        {
+            return Some(format!("{} of {} LLM tokens used", usage.tokens(), max));
+        }
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -185,14 +1040,35 @@ pub struct LlmClient {
     provider: LlmProvider,
     api_key: Option<String>,
     http: Client,
+    /// Seeds the back-off between retries, so a run's request timing can be reproduced.
+    jitter_seed: u64,
+    /// Constrain OpenAI and Ollama replies to `action_schema` instead of parsing free text.
+    structured: bool,
+    /// Sampling temperature sent with every request; `None` leaves the provider's default.
+    temperature: Option<f32>,
+    /// Goal written into the prompt in place of `DEFAULT_AGENT_GOAL`.
+    goal: Option<String>,
+    /// Archived notes recalled into each prompt; 0 turns vector memory off.
+    recall: usize,
+    /// Model the notes are embedded with; `None` uses the provider's default.
+    embedding_model: Option<String>,
+    /// What the system and user messages are rendered from.
+    prompts: Arc<PromptTemplates>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LlmProvider {
     Ollama,
     Openai,
+    Gemini,
+    /// A GGUF model run in-process; `--llm-model` is the `.gguf` path. Needs `--features llama`.
+    Llama,
 }
 
+/// Where `LlmProvider::Gemini` sends requests unless another host is given.
+pub const GEMINI_HOST: &str = "https://generativelanguage.googleapis.com";
+
 impl LlmClient {
     pub fn new(
         host: impl Into<String>,
@@ -211,8 +1087,120 @@ impl LlmClient {
             provider,
             api_key,
             http,
+            jitter_seed: rand::random(),
+            structured: true,
+            temperature: None,
+            goal: None,
+            recall: 0,
+            embedding_model: None,
+            prompts: Arc::new(PromptTemplates::default()),
         })
     }
+
+    /// Whether OpenAI-compatible and Ollama requests carry a JSON schema for the reply. On by
+    /// default; turn it off for servers that reject `response_format` or `format`.
+    pub fn with_structured_output(mut self, structured: bool) -> Self {
+        self.structured = structured;
+        self
+    }
+
+    pub fn with_jitter_seed(mut self, seed: u64) -> Self {
+        self.jitter_seed = seed;
+        self
+    }
+
+    pub fn jitter_seed(&self) -> u64 {
+        self.jitter_seed
+    }
+
+    /// The same client asking `model` instead.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn provider(&self) -> LlmProvider {
+        self.provider
+    }
+
+    /// The same client (timeout, jitter seed, output mode) talking to `provider` at `host`.
+    pub fn with_provider(mut self, provider: LlmProvider, host: impl Into<String>) -> Self {
+        self.provider = provider;
+        self.host = host.into();
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn temperature(&self) -> Option<f32> {
+        self.temperature
+    }
+
+    /// The same client prompting for `goal` instead of `DEFAULT_AGENT_GOAL`.
+    pub fn with_goal(mut self, goal: Option<String>) -> Self {
+        self.goal = goal;
+        self
+    }
+
+    /// The goal the prompt states.
+    pub fn goal(&self) -> &str {
+        self.goal.as_deref().unwrap_or(DEFAULT_AGENT_GOAL)
+    }
+
+    /// Render the prompts from `templates` instead of the built-in ones.
+    pub fn with_prompt_templates(mut self, templates: PromptTemplates) -> Self {
+        self.prompts = Arc::new(templates);
+        self
+    }
+
+    pub fn prompt_templates(&self) -> &PromptTemplates {
+        &self.prompts
+    }
+
+    /// The system message sent with every request, stating this client's goal.
+    pub fn system_prompt(&self) -> String {
+        prompts::render(self.prompts.system(), &[("goal", self.goal())])
+    }
+
+    /// Keep the notes that fall out of an agent's rolling memory, embedded with
+    /// `embedding_model` (or the provider's default), and put the `recall` most similar to the
+    /// agent's current state into each prompt. `recall` 0 turns this off.
+    pub fn with_vector_memory(mut self, recall: usize, embedding_model: Option<String>) -> Self {
+        self.recall = recall;
+        self.embedding_model = embedding_model;
+        self
+    }
+
+    pub fn recall(&self) -> usize {
+        self.recall
+    }
+
+    pub fn embedding_model(&self) -> &str {
+        self.embedding_model
+            .as_deref()
+            .unwrap_or(match self.provider {
+                LlmProvider::Ollama => "nomic-embed-text",
+                LlmProvider::Openai => "text-embedding-3-small",
+                LlmProvider::Gemini => "text-embedding-004",
+                LlmProvider::Llama => "",
+            })
+    }
 }
 
 pub fn plan_with_llm(
@@ -223,6 +1211,297 @@ pub fn plan_with_llm(
     client: Option<&LlmClient>,
     next_tick: u64,
 ) -> LlmDecision {
+    let mut jobs = [PlanJob {
+        agent_id,
+        candidates,
+        memory,
+        client,
+    }];
+    let recall = recall_memories(vm, &mut jobs, 1).pop().unwrap_or_default();
+    let [PlanJob { memory, .. }] = jobs;
+    let input = prepare_plan(
+        vm,
+        agent_id,
+        candidates,
+        memory,
+        client,
+        &recall.notes,
+        next_tick,
+    );
+    let mut reply = runtime().block_on(ask_llm(
+        client.cloned(),
+        input.prompt.clone(),
+        candidates.to_vec(),
+        agent_id,
+        next_tick,
+    ));
+    reply.usage.add(recall.usage);
+    finish_plan(vm, agent_id, candidates, memory, next_tick, input, reply)
+}
+
+/// One agent's turn in `plan_many_with_llm`.
+pub struct PlanJob<'a> {
+    pub agent_id: AgentId,
+    pub candidates: &'a [ActionArg],
+    pub memory: &'a mut BrainMemory,
+    /// `None` plans with the deterministic fallback, as in `plan_with_llm`.
+    pub client: Option<&'a LlmClient>,
+}
+
+/// `plan_with_llm` for every job at once, with up to `concurrency` LLM requests in flight.
+/// The world is only read, so every decision sees the same tick; they come back in job order.
+pub fn plan_many_with_llm(
+    vm: &Vm,
+    jobs: Vec<PlanJob<'_>>,
+    next_tick: u64,
+    concurrency: usize,
+) -> Vec<LlmDecision> {
+    let mut jobs = jobs;
+    let recalls = recall_memories(vm, &mut jobs, concurrency);
+    let inputs: Vec<PlanInput> = jobs
+        .iter()
+        .zip(&recalls)
+        .map(|(job, recall)| {
+            prepare_plan(
+                vm,
+                job.agent_id,
+                job.candidates,
+                job.memory,
+                job.client,
+                &recall.notes,
+                next_tick,
+            )
+        })
+        .collect();
+    let limit = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut replies: Vec<Option<LlmReply>> = runtime().block_on(async {
+        let mut tasks = JoinSet::new();
+        for (index, (job, input)) in jobs.iter().zip(&inputs).enumerate() {
+            let limit = limit.clone();
+            let request = ask_llm(
+                job.client.cloned(),
+                input.prompt.clone(),
+                job.candidates.to_vec(),
+                job.agent_id,
+                next_tick,
+            );
+            tasks.spawn(async move {
+                let _permit = limit.acquire_owned().await;
+                (index, request.await)
+            });
+        }
+        let mut replies: Vec<Option<LlmReply>> = (0..jobs.len()).map(|_| None).collect();
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok((index, reply)) = joined {
+                replies[index] = Some(reply);
+            }
+        }
+        replies
+    });
+
+    jobs.into_iter()
+        .zip(inputs)
+        .zip(recalls)
+        .enumerate()
+        .map(|(index, ((job, input), recall))| {
+            let mut reply = replies[index].take().unwrap_or_else(|| {
+                let mut reply = LlmReply::without(job.client);
+                if job.client.is_some() {
+                    reply.result = Some(Err("llm request task failed".to_string()));
+                }
+                reply
+            });
+            reply.usage.add(recall.usage);
+            finish_plan(
+                vm,
+                job.agent_id,
+                job.candidates,
+                job.memory,
+                next_tick,
+                input,
+                reply,
+            )
+        })
+        .collect()
+}
+
+/// Runs the LLM requests; `plan_with_llm` blocks on it, so callers need no runtime of their own.
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start the LLM runtime")
+    })
+}
+
+/// Most archived notes embedded for one agent per tick, so switching embedding models catches
+/// up over a few ticks instead of in one huge request.
+const EMBED_BATCH: usize = 64;
+
+/// What vector memory brought back for one agent's prompt.
+#[derive(Debug, Default)]
+struct Recall {
+    notes: Vec<String>,
+    /// The embedding request, charged to the agent's decision.
+    usage: LlmUsage,
+}
+
+/// Embed the notes each agent archived since its last prompt together with its current state,
+/// and recall the archived notes closest to that state. Agents whose client has vector memory
+/// off recall nothing; a failed request is logged and recalls nothing this tick.
+fn recall_memories(vm: &Vm, jobs: &mut [PlanJob<'_>], concurrency: usize) -> Vec<Recall> {
+    let requests: Vec<Option<(LlmClient, Vec<String>)>> = jobs
+        .iter_mut()
+        .map(|job| {
+            let client = job.client.filter(|c| c.recall > 0)?;
+            let memory = &mut *job.memory;
+            if memory.archive.is_empty() {
+                return None;
+            }
+            let model = client.embedding_model();
+            if memory.embedding_model.as_deref() != Some(model) {
+                memory.archive.iter_mut().for_each(|n| n.vector.clear());
+                memory.embedding_model = Some(model.to_string());
+            }
+            let mut texts: Vec<String> = memory
+                .archive
+                .iter()
+                .filter(|n| n.vector.is_empty())
+                .take(EMBED_BATCH)
+                .map(|n| n.note.clone())
+                .collect();
+            texts.push(recall_query(vm, job.agent_id));
+            Some((client.clone(), texts))
+        })
+        .collect();
+    if requests.iter().all(Option::is_none) {
+        return jobs.iter().map(|_| Recall::default()).collect();
+    }
+
+    let limit = Arc::new(Semaphore::new(concurrency.max(1)));
+    let count = requests.len();
+    type Embedded = (Result<Vec<Vec<f32>>, String>, LlmUsage);
+    let embedded: Vec<Option<Embedded>> = runtime().block_on(async {
+        let mut tasks = JoinSet::new();
+        for (index, request) in requests.into_iter().enumerate() {
+            let Some((client, texts)) = request else {
+                continue;
+            };
+            let limit = limit.clone();
+            tasks.spawn(async move {
+                let _permit = limit.acquire_owned().await;
+                let mut usage = LlmUsage::default();
+                let vectors = embed(&client, &texts, &mut usage).await;
+                (index, vectors, usage)
+            });
+        }
+        let mut embedded: Vec<Option<Embedded>> = (0..count).map(|_| None).collect();
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok((index, vectors, usage)) = joined {
+                embedded[index] = Some((vectors, usage));
+            }
+        }
+        embedded
+    });
+
+    jobs.iter_mut()
+        .zip(embedded)
+        .map(|(job, embedded)| {
+            let Some((vectors, usage)) = embedded else {
+                return Recall::default();
+            };
+            let mut vectors = match vectors {
+                Ok(vectors) => vectors,
+                Err(err) => {
+                    tracing::warn!(
+                        target: LLM_TARGET,
+                        agent_id = job.agent_id,
+                        "vector memory: {}",
+                        err
+                    );
+                    return Recall {
+                        notes: Vec::new(),
+                        usage,
+                    };
+                }
+            };
+            let query = vectors.pop().unwrap_or_default();
+            let mut fresh = vectors.into_iter();
+            for note in job
+                .memory
+                .archive
+                .iter_mut()
+                .filter(|n| n.vector.is_empty())
+            {
+                match fresh.next() {
+                    Some(vector) => note.vector = vector,
+                    None => break,
+                }
+            }
+            let recall = job.client.map_or(0, LlmClient::recall);
+            Recall {
+                notes: memory::most_relevant(&job.memory.archive, &query, recall),
+                usage,
+            }
+        })
+        .collect()
+}
+
+/// The agent's current state, phrased like the `state:` and `obs:` parts of its notes.
+fn recall_query(vm: &Vm, agent_id: AgentId) -> String {
+    format!(
+        "state: {} | obs: [{}]",
+        summarize_world(vm, agent_id),
+        observe_world(vm, agent_id).join(" ; ")
+    )
+}
+
+/// What a decision needs from the world, taken before the request so that it can run without
+/// borrowing the `Vm`.
+struct PlanInput {
+    summary: String,
+    observations: Vec<String>,
+    prompt: String,
+    fallback: Action,
+    /// Whether notes leaving the rolling memory go to the archive.
+    archive: bool,
+}
+
+/// The model's answer, or `None` when there was no client to ask.
+struct LlmReply {
+    result: Option<Result<OllamaResult, String>>,
+    model: String,
+    provider: LlmProvider,
+    /// Wall-clock time spent obtaining the answer (including retries).
+    latency_ms: u64,
+    usage: LlmUsage,
+}
+
+impl LlmReply {
+    /// No answer yet from `client`.
+    fn without(client: Option<&LlmClient>) -> Self {
+        Self {
+            result: None,
+            model: client.map_or_else(|| "unknown".into(), |c| c.model.clone()),
+            provider: client.map_or(LlmProvider::Ollama, |c| c.provider),
+            latency_ms: 0,
+            usage: LlmUsage::default(),
+        }
+    }
+}
+
+fn prepare_plan(
+    vm: &Vm,
+    agent_id: AgentId,
+    candidates: &[ActionArg],
+    memory: &BrainMemory,
+    client: Option<&LlmClient>,
+    recalled: &[String],
+    next_tick: u64,
+) -> PlanInput {
+    let goal = client.map_or(DEFAULT_AGENT_GOAL, LlmClient::goal);
     let summary = summarize_world(vm, agent_id);
     let observations = observe_world(vm, agent_id);
     let last_feedback = memory
@@ -235,19 +1514,70 @@ pub fn plan_with_llm(
         &summary,
         &observations,
         &memory_notes,
+        recalled,
         &last_feedback,
-        DEFAULT_AGENT_GOAL,
+        &memory.constraints,
+        goal,
         candidates,
+        client.map_or(DEFAULT_USER_TEMPLATE, |c| c.prompts.user()),
         vm,
         agent_id,
     );
+    let fallback = choose_action(vm, agent_id, candidates, next_tick);
+    PlanInput {
+        summary,
+        observations,
+        prompt,
+        fallback,
+        archive: client.is_some_and(|c| c.recall > 0),
+    }
+}
 
-    let fallback_action = || choose_action(vm, agent_id, candidates, next_tick);
+async fn ask_llm(
+    client: Option<LlmClient>,
+    prompt: String,
+    candidates: Vec<ActionArg>,
+    agent_id: AgentId,
+    next_tick: u64,
+) -> LlmReply {
+    let started = Instant::now();
+    let mut reply = LlmReply::without(client.as_ref());
+    if let Some(client) = client {
+        reply.result = Some(
+            call_chat(
+                &client,
+                &prompt,
+                &candidates,
+                agent_id,
+                next_tick,
+                &mut reply.usage,
+            )
+            .await,
+        );
+    }
+    reply.latency_ms = started.elapsed().as_millis() as u64;
+    reply
+}
 
-    let (request_json, response_json, response, mut action, llm_ok, model, provider) = match client
-    {
-        Some(client) => match call_chat(client, &prompt, candidates, agent_id, next_tick) {
-            Ok(result) => {
+fn finish_plan(
+    vm: &Vm,
+    agent_id: AgentId,
+    candidates: &[ActionArg],
+    memory: &mut BrainMemory,
+    next_tick: u64,
+    input: PlanInput,
+    reply: LlmReply,
+) -> LlmDecision {
+    let PlanInput {
+        summary,
+        observations,
+        prompt,
+        fallback,
+        archive,
+    } = input;
+    let (request_json, response_json, response, mut action, llm_ok, model, provider) =
+        match reply.result {
+            Some(Ok(result)) => {
                 log_llm_call(
                     &result.provider,
                     &result.model,
@@ -260,30 +1590,29 @@ pub fn plan_with_llm(
                     result.reply_text,
                     result.action,
                     true,
-                    client.model.clone(),
-                    client.provider,
+                    reply.model,
+                    reply.provider,
                 )
             }
-            Err(err) => (
+            Some(Err(err)) => (
                 String::from("not sent (error building/sending request)"),
                 String::from("not available"),
                 format!("error: {}", err),
-                fallback_action(),
+                fallback,
                 false,
-                client.model.clone(),
-                client.provider,
+                reply.model,
+                reply.provider,
             ),
-        },
-        None => (
-            String::from("not sent (no llm client)"),
-            String::from("not available"),
-            String::from("llm client missing; fallback to loop"),
-            fallback_action(),
-            false,
-            String::from("unknown"),
-            LlmProvider::Ollama,
-        ),
-    };
+            None => (
+                String::from("not sent (no llm client)"),
+                String::from("not available"),
+                String::from("llm client missing; fallback to loop"),
+                fallback,
+                false,
+                reply.model,
+                reply.provider,
+            ),
+        };
 
     // Safety override if low on Qi.
     action = survival_override(vm, agent_id, candidates, next_tick, action);
@@ -298,6 +1627,7 @@ pub fn plan_with_llm(
             action_token(&action),
             truncate(&response, 120)
         ),
+        archive,
     );
 
     LlmDecision {
@@ -311,24 +1641,31 @@ pub fn plan_with_llm(
         provider,
         action,
         llm_ok,
+        latency_ms: reply.latency_ms,
+        usage: reply.usage,
     }
 }
 
 fn choose_action(vm: &Vm, agent_id: AgentId, candidates: &[ActionArg], next_tick: u64) -> Action {
-    let (qi, transistors) = vm
+    let inventory = vm
         .world()
         .agent(agent_id)
-        .map(|a| (a.qi, a.transistors))
-        .unwrap_or((0, 0));
+        .map(|a| a.inventory.clone())
+        .unwrap_or_default();
+    let qi = inventory.get(ItemKind::Qi);
 
     for action in candidates {
         let materialized = action.materialize(agent_id, next_tick);
-        if matches!(
-            materialized,
-            Action::BuildStructure {
-                kind: StructureKind::Programmable
-            }
-        ) && transistors == 0
+        if let Action::BuildStructure { kind } = materialized
+            && inventory.covers(&vm.recipes().build_cost(kind)).is_err()
+        {
+            continue;
+        }
+        if let Action::Craft { recipe } = &materialized
+            && vm
+                .recipes()
+                .get(recipe)
+                .is_none_or(|r| inventory.covers(&r.bill()).is_err())
         {
             continue;
         }
@@ -353,13 +1690,13 @@ fn survival_override(
 fn summarize_world(vm: &Vm, agent_id: AgentId) -> String {
     if let Some(agent) = vm.world().agent(agent_id) {
         format!(
-            "Agent #{} at ({}, {}, {}) qi={} transistors={} age={} last_tick={}",
+            "Agent #{} at ({}, {}, {}) {} hp={} age={} last_tick={}",
             agent.id,
             agent.position.x,
             agent.position.y,
             agent.position.z,
-            agent.qi,
-            agent.transistors,
+            agent.inventory,
+            agent.hp,
             agent.age,
             vm.world().tick()
         )
@@ -379,16 +1716,98 @@ fn observe_world(vm: &Vm, agent_id: AgentId) -> Vec<String> {
     };
 
     let pos = agent.position;
-    notes.push("ore nodes unknown; scan to discover nearby deposits".into());
+    if let Some(rules) = vm.world().starvation()
+        && agent.starving_ticks > 0
+    {
+        notes.push(format!(
+            "you have no qi and starve to death in {} ticks; harvest qi, rest or ask for some",
+            rules.after_ticks.saturating_sub(agent.starving_ticks)
+        ));
+    }
+    match &agent.last_scan {
+        Some(scan) => {
+            let seen = format!(
+                "your scan at tick {} from {}",
+                scan.tick,
+                describe(scan.position)
+            );
+            if scan.qi_sources.is_empty() {
+                notes.push(format!("{} found no ore nodes within {}", seen, SCAN_RANGE));
+            } else {
+                let nodes: Vec<String> = scan
+                    .qi_sources
+                    .iter()
+                    .map(|s| {
+                        format!(
+                            "{} node {} at {} had {}/{}",
+                            s.ore,
+                            s.id,
+                            describe(s.position),
+                            s.available,
+                            s.capacity
+                        )
+                    })
+                    .collect();
+                notes.push(format!("{} found ore nodes: {}", seen, nodes.join(" | ")));
+            }
+            if !scan.structures.is_empty() {
+                let structures: Vec<String> = scan
+                    .structures
+                    .iter()
+                    .map(|s| format!("{} structure {} at {}", s.kind, s.id, describe(s.position)))
+                    .collect();
+                notes.push(format!("{} found {}", seen, structures.join(" | ")));
+            }
+        }
+        None => notes.push("ore nodes unknown; scan to discover nearby deposits".into()),
+    }
+
+    let here = pos.zone();
+    if let Some(label) = vm.world().zone_label(here) {
+        notes.push(format!("you are in {}", label.describe()));
+    }
+    notes.push(match vm.world().zone_claim(here) {
+        Some(claim) => {
+            let building = match claim.build_toll {
+                0 => "only the holder may build".to_string(),
+                toll => format!("others pay {} Qi to build", toll),
+            };
+            let holder = if claim.owner == agent_id {
+                "you hold this zone".to_string()
+            } else {
+                format!("agent {} holds this zone", claim.owner)
+            };
+            format!(
+                "{}; others pay {} Qi per harvest, {}",
+                holder, claim.harvest_toll, building
+            )
+        }
+        None => format!(
+            "this zone is unclaimed; claim_zone costs {} Qi",
+            ZONE_CLAIM_COST
+        ),
+    });
+    let mut places: Vec<String> = agent
+        .discovered_zones
+        .iter()
+        .filter(|zone| **zone != here)
+        .filter_map(|zone| vm.world().zone_label(*zone))
+        .filter(|label| label.name.is_some())
+        .map(|label| label.describe())
+        .collect();
+    if !places.is_empty() {
+        places.sort();
+        notes.push(format!("known places: {}", places.join(" | ")));
+    }
 
     let nearby_agents: Vec<_> = vm
         .world()
         .agents()
-        .filter(|(id, a)| **id != agent_id && pos.within_range(a.position, SCAN_RANGE))
+        .filter(|(id, a)| **id != agent_id && a.alive && pos.within_range(a.position, SCAN_RANGE))
         .map(|(id, a)| {
             format!(
-                "agent {} at ({},{},{}) qi={} transistors={}",
-                id, a.position.x, a.position.y, a.position.z, a.qi, a.transistors
+                "agent {} at ({},{},{}) {} hp={}",
+                id, a.position.x, a.position.y, a.position.z, a.inventory, a.hp
             )
         })
         .collect();
@@ -398,6 +1817,86 @@ fn observe_world(vm: &Vm, agent_id: AgentId) -> Vec<String> {
     } else {
         notes.push(format!("nearby agents: {}", nearby_agents.join(" | ")));
     }
+    let threats: Vec<String> = vm
+        .world()
+        .threats_to(agent_id, SCAN_RANGE)
+        .iter()
+        .map(|t| {
+            let mut note = format!(
+                "agent {} at {} hp={} hits for {}",
+                t.agent_id,
+                describe(t.position),
+                t.hp,
+                t.damage
+            );
+            if let Some(tick) = t.attacked_at {
+                note.push_str(&format!(", attacked you at tick {}", tick));
+            }
+            if t.lethal {
+                note.push_str(", one hit would kill you");
+            }
+            note
+        })
+        .collect();
+    if !threats.is_empty() {
+        notes.push(format!("threats: {}", threats.join(" | ")));
+    }
+    let skip = agent.inbox.len().saturating_sub(OBSERVED_MESSAGES);
+    let messages: Vec<String> = agent
+        .inbox
+        .iter()
+        .skip(skip)
+        .map(|m| format!("tick {} agent {} said {:?}", m.tick, m.from, m.text))
+        .collect();
+    if !messages.is_empty() {
+        notes.push(format!("messages to you: {}", messages.join(" | ")));
+    }
+    let craftable: Vec<String> = vm
+        .recipes()
+        .iter()
+        .filter(|(_, recipe)| agent.inventory.covers(&recipe.bill()).is_ok())
+        .map(|(name, recipe)| {
+            let inputs: Vec<String> = recipe
+                .inputs
+                .iter()
+                .map(|(item, n)| format!("{} {}", n, item))
+                .collect();
+            format!(
+                "{} ({} -> {} {})",
+                name,
+                inputs.join(" + "),
+                recipe.amount,
+                recipe.output
+            )
+        })
+        .collect();
+    if !craftable.is_empty() {
+        notes.push(format!("you can craft: {}", craftable.join(" | ")));
+    }
+    let worn: Vec<String> = vm
+        .world()
+        .structures()
+        .iter()
+        .filter(|s| s.durability < STRUCTURE_DURABILITY)
+        .filter(|s| s.position.within_range(agent.position, HARVEST_RANGE))
+        .map(|s| {
+            format!(
+                "{} {} at {}/{}",
+                s.kind, s.id, s.durability, STRUCTURE_DURABILITY
+            )
+        })
+        .collect();
+    if !worn.is_empty() {
+        notes.push(format!("worn structures in reach: {}", worn.join(" | ")));
+    }
+    let partners = vm.world().eligible_partners(agent_id);
+    if !partners.is_empty() {
+        let ids: Vec<String> = partners.iter().map(|id| id.to_string()).collect();
+        notes.push(format!(
+            "eligible reproduction partners: {}",
+            ids.join(", ")
+        ));
+    }
 
     notes
 }
@@ -408,48 +1907,44 @@ fn memory_context(memory: &BrainMemory, limit: usize) -> Vec<String> {
     memory.notes[start..].to_vec()
 }
 
-fn push_memory(memory: &mut BrainMemory, entry: String) {
+fn push_memory(memory: &mut BrainMemory, entry: String, archive: bool) {
     memory.notes.push(entry);
     if memory.notes.len() > MEMORY_LIMIT {
         let drop = memory.notes.len() - MEMORY_LIMIT;
-        memory.notes.drain(0..drop);
+        let dropped = memory.notes.drain(0..drop);
+        if archive {
+            memory.archive.extend(dropped.map(|note| ArchivedNote {
+                note,
+                vector: Vec::new(),
+            }));
+            let excess = memory.archive.len().saturating_sub(memory::ARCHIVE_LIMIT);
+            memory.archive.drain(0..excess);
+        }
     }
 }
 
 fn log_llm_call(provider: &LlmProvider, model: &str, request_json: &str, response_json: &str) {
-    use std::fs::OpenOptions;
-    let timestamp = Utc::now().to_rfc3339();
-    let dir = PathBuf::from("logs");
-    if let Err(err) = fs::create_dir_all(&dir) {
-        eprintln!("warn: failed to create logs dir: {}", err);
-        return;
-    }
-    let path = dir.join("llm.log");
-    let content = format!(
-        "[{}] provider={} model={}\nrequest:\n{}\nresponse:\n{}\n\n",
-        timestamp,
-        format!("{:?}", provider).to_lowercase(),
-        model,
-        request_json,
-        response_json
+    tracing::info!(
+        target: LLM_TARGET,
+        provider = %format!("{:?}", provider).to_lowercase(),
+        model = %model,
+        request = %request_json,
+        response = %response_json,
+        "llm call"
     );
-    let result = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&path)
-        .and_then(|mut f| std::io::Write::write_all(&mut f, content.as_bytes()));
-    if let Err(err) = result {
-        eprintln!("warn: failed to write llm log {}: {}", path.display(), err);
-    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_prompt(
     summary: &str,
     observations: &[String],
     memory_notes: &[String],
+    recalled: &[String],
     last_feedback: &str,
+    constraints: &[Constraint],
     goal: &str,
     candidates: &[ActionArg],
+    template: &str,
     _vm: &Vm,
     _agent_id: AgentId,
 ) -> String {
@@ -458,15 +1953,31 @@ fn build_prompt(
     actions.dedup();
     let action_schema = vec![
         "move(x,y,z)",
+        "move_to(x,y,z)",
         "scan(radius)",
         "build_<structure_kind>",
         "reproduce(partner_id)",
         "harvest_<ore_kind>(source_id)",
+        "harvest_<ore_kind>(@dx,dy,dz)",
+        "trade(partner_id,offer_ore,offer_amount,want_ore,want_amount)",
+        "attack(target_id)",
+        "dig(dx,dy,dz)",
+        "place_<block_kind>(dx,dy,dz)",
+        "rest",
+        "say(to_id_or_0,message)",
+        "claim_zone",
+        "set_toll(harvest_qi,build_qi)",
+        "post_order(buy|sell,ore_kind,amount,price_qi)",
+        "craft(recipe)",
+        "repair(structure_id_or_0)",
+        "demolish(structure_id)",
+        "give_qi(to_id,amount)",
     ];
     let structure_kinds = vec!["basic", "programmable", "qi"];
-    let ore_kinds = vec!["qi", "transistor"];
+    let ore_kinds: Vec<&str> = OreKind::all().into_iter().map(OreKind::label).collect();
+    let block_kinds = vec!["dirt", "stone"];
 
-    let payload = json!({
+    let mut payload = json!({
         "goal": goal,
         "state": summary,
         "observations": observations,
@@ -476,28 +1987,47 @@ fn build_prompt(
         "action_schema": action_schema,
         "structure_kinds": structure_kinds,
         "ore_kinds": ore_kinds,
+        "block_kinds": block_kinds,
         "reply": { "action": "one_of(actions)" }
     });
 
-    let toon = to_string_pretty(&payload).unwrap_or_else(|_| payload.to_string());
+    // Only after a rejection, so prompts for agents whose actions succeed stay unchanged.
+    if !constraints.is_empty() {
+        payload["constraints"] = json!(constraints);
+    }
+    if !recalled.is_empty() {
+        payload["recalled_memory"] = json!(recalled);
+    }
 
-    format!(
-        "You are an autonomous agent. Choose exactly one action from `actions`, fill in any needed parameters (move(x,y,z), scan(radius), build_<structure_kind>, reproduce(partner_id), harvest_<ore_kind>(source_id)), and reply ONLY in TOON with `action: <label>`. Input:\n{toon}"
-    )
-}
+    let toon = to_string_pretty(&payload).unwrap_or_else(|_| payload.to_string());
+    let constraints = if constraints.is_empty() {
+        String::new()
+    } else {
+        let list = json!(constraints);
+        to_string_pretty(&list).unwrap_or_else(|_| list.to_string())
+    };
 
-fn system_prompt() -> String {
-    format!(
-        "You are an autonomous agent inside a voxel-based, blockchain-synchronized world. Act to advance this goal: {}. Choose exactly one action from the provided list, include concrete parameters (e.g., move(x,y,z)), and respond ONLY in TOON with `action: <label>`.",
-        DEFAULT_AGENT_GOAL
+    prompts::render(
+        template,
+        &[
+            ("goal", goal),
+            ("state", summary),
+            ("observations", &observations.join("\n")),
+            ("memory", &memory_notes.join("\n")),
+            ("recalled_memory", &recalled.join("\n")),
+            ("last_feedback", last_feedback),
+            ("constraints", &constraints),
+            ("candidates", &actions.join(", ")),
+            ("input", &toon),
+        ],
     )
 }
 
-fn build_chat_messages(user_prompt: &str) -> Vec<Message> {
+fn build_chat_messages(client: &LlmClient, user_prompt: &str) -> Vec<Message> {
     vec![
         Message {
             role: "system".into(),
-            content: system_prompt(),
+            content: client.system_prompt(),
         },
         Message {
             role: "user".into(),
@@ -506,16 +2036,20 @@ fn build_chat_messages(user_prompt: &str) -> Vec<Message> {
     ]
 }
 
-fn call_chat(
+async fn call_chat(
     client: &LlmClient,
     prompt: &str,
     candidates: &[ActionArg],
     agent_id: AgentId,
     next_tick: u64,
+    usage: &mut LlmUsage,
 ) -> Result<OllamaResult, String> {
     let mut attempts = 0;
     let max_attempts = 3;
     let mut last_err = String::new();
+    // One stream per agent and tick, so retries elsewhere don't shift this agent's timing.
+    let mut jitter = ChaCha12Rng::seed_from_u64(client.jitter_seed ^ next_tick.rotate_left(32));
+    jitter.set_stream(agent_id);
 
     while attempts < max_attempts {
         attempts += 1;
@@ -523,19 +2057,40 @@ fn call_chat(
             0
         } else {
             // simple jitter: 50-150ms
-            50 + (rand::random::<u64>() % 100)
+            50 + jitter.next_u64() % 100
         };
         if jitter_ms > 0 {
-            std::thread::sleep(std::time::Duration::from_millis(jitter_ms));
+            tokio::time::sleep(Duration::from_millis(jitter_ms)).await;
         }
+        usage.requests += 1;
 
         let result = match client.provider {
-            LlmProvider::Ollama => call_ollama(client, prompt, candidates, agent_id, next_tick),
-            LlmProvider::Openai => call_openai(client, prompt, candidates, agent_id, next_tick),
+            LlmProvider::Ollama => {
+                call_ollama(client, prompt, candidates, agent_id, next_tick).await
+            }
+            LlmProvider::Openai => {
+                call_openai(client, prompt, candidates, agent_id, next_tick).await
+            }
+            LlmProvider::Gemini => {
+                call_gemini(client, prompt, candidates, agent_id, next_tick).await
+            }
+            // Inference runs on this thread, so keep it off the runtime's workers.
+            LlmProvider::Llama => {
+                let (client, prompt, candidates) =
+                    (client.clone(), prompt.to_string(), candidates.to_vec());
+                tokio::task::spawn_blocking(move || {
+                    call_llama(&client, &prompt, &candidates, agent_id, next_tick)
+                })
+                .await
+                .unwrap_or_else(|e| Err(format!("llama task: {}", e)))
+            }
         };
 
         match result {
-            Ok(res) => return Ok(res),
+            Ok(res) => {
+                usage.add(res.usage);
+                return Ok(res);
+            }
             Err(e) => {
                 last_err = e;
                 if attempts >= max_attempts {
@@ -551,7 +2106,93 @@ fn call_chat(
     ))
 }
 
-fn call_ollama(
+/// Embed `texts` with the client's embedding model, one vector per text in order. Ollama uses
+/// `/api/embed`, OpenAI-compatible servers `/v1/embeddings` and Gemini `batchEmbedContents`.
+async fn embed(
+    client: &LlmClient,
+    texts: &[String],
+    usage: &mut LlmUsage,
+) -> Result<Vec<Vec<f32>>, String> {
+    let model = client.embedding_model();
+    let base = client.host.trim_end_matches('/');
+    let request = match client.provider {
+        LlmProvider::Ollama => client
+            .http
+            .post(format!("{}/api/embed", base))
+            .json(&json!({ "model": model, "input": texts })),
+        LlmProvider::Openai => {
+            let base = base.trim_end_matches("/v1/chat/completions");
+            client
+                .http
+                .post(format!("{}/v1/embeddings", base))
+                .headers(build_openai_headers(&client.api_key)?)
+                .json(&json!({ "model": model, "input": texts }))
+        }
+        LlmProvider::Gemini => {
+            let base = base.split("/v1beta/").next().unwrap_or(base);
+            let requests: Vec<_> = texts
+                .iter()
+                .map(|text| {
+                    json!({
+                        "model": format!("models/{}", model),
+                        "content": { "parts": [{ "text": text }] },
+                    })
+                })
+                .collect();
+            let key = client
+                .api_key
+                .as_deref()
+                .ok_or("missing LLM API key; set --llm-api-key or LLM_API_KEY")?;
+            client
+                .http
+                .post(format!(
+                    "{}/v1beta/models/{}:batchEmbedContents",
+                    base, model
+                ))
+                .header("x-goog-api-key", key)
+                .json(&json!({ "requests": requests }))
+        }
+        LlmProvider::Llama => {
+            return Err(
+                "the llama provider cannot embed; vector memory needs ollama, openai or gemini"
+                    .into(),
+            );
+        }
+    };
+    usage.requests += 1;
+    let resp = request.send().await.map_err(|e| format!("http: {}", e))?;
+    let status = resp.status();
+    let raw_body = resp.text().await.map_err(|e| format!("read body: {}", e))?;
+    let parsed: EmbedResponse = serde_json::from_str(&raw_body)
+        .map_err(|e| format!("decode: {}; status={} body={}", e, status, raw_body))?;
+
+    usage.prompt_tokens += parsed
+        .prompt_eval_count
+        .or(parsed.usage.map(|u| u.prompt_tokens))
+        .unwrap_or(0);
+    let vectors: Vec<Vec<f32>> = if parsed.data.is_empty() {
+        parsed
+            .embeddings
+            .into_iter()
+            .map(Embedding::into_vector)
+            .collect()
+    } else {
+        let mut data = parsed.data;
+        data.sort_by_key(|d| d.index);
+        data.into_iter().map(|d| d.embedding).collect()
+    };
+    if vectors.len() != texts.len() {
+        return Err(format!(
+            "asked to embed {} text(s), got {} vector(s); status={}",
+            texts.len(),
+            vectors.len(),
+            status
+        ));
+    }
+    Ok(vectors)
+}
+
+async fn call_ollama(
     client: &LlmClient,
     prompt: &str,
     candidates: &[ActionArg],
@@ -563,7 +2204,9 @@ fn call_ollama(
     let body = ChatRequest {
         model: client.model.clone(),
         stream: false,
-        messages: build_chat_messages(prompt),
+        messages: build_chat_messages(client, prompt),
+        format: client.structured.then(|| action_schema(candidates)),
+        options: client.temperature.map(|t| json!({ "temperature": t })),
     };
 
     let request_json =
@@ -574,23 +2217,28 @@ fn call_ollama(
         .post(&url)
         .json(&body)
         .send()
+        .await
         .map_err(|e| format!("http: {}", e))?;
     let status = resp.status();
-    let raw_body = resp.text().map_err(|e| format!("read body: {}", e))?;
+    let raw_body = resp.text().await.map_err(|e| format!("read body: {}", e))?;
 
     let parsed: ChatResponse = serde_json::from_str(&raw_body)
         .map_err(|e| format!("decode: {}; status={} body={}", e, status, raw_body))?;
 
     let response_json = serde_json::to_string_pretty(&parsed).unwrap_or_else(|_| raw_body.clone());
 
+    let usage = LlmUsage {
+        requests: 0,
+        prompt_tokens: parsed.prompt_eval_count.unwrap_or(0),
+        completion_tokens: parsed.eval_count.unwrap_or(0),
+    };
     let text = parsed.message.content;
     let parsed = parse_action(&text, candidates, agent_id, next_tick);
-    let action = parsed
-        .clone()
-        .unwrap_or_else(|| choose_action_fallback(candidates, agent_id, next_tick));
     let reply_text = parsed
-        .map(|a| format!("TOON{{action={}}}", action_token(&a)))
+        .as_ref()
+        .map(|a| format!("TOON{{action={}}}", action_token(a)))
         .unwrap_or_else(|| truncate(&text, 120));
+    let action = parsed.unwrap_or_else(|| choose_action_fallback(candidates, agent_id, next_tick));
 
     Ok(OllamaResult {
         request_json,
@@ -599,10 +2247,11 @@ fn call_ollama(
         action,
         model: client.model.clone(),
         provider: client.provider,
+        usage,
     })
 }
 
-fn call_openai(
+async fn call_openai(
     client: &LlmClient,
     prompt: &str,
     candidates: &[ActionArg],
@@ -621,8 +2270,18 @@ fn call_openai(
     let body = OpenAiChatRequest {
         model: client.model.clone(),
         stream: false,
-        temperature: None,
-        messages: build_chat_messages(prompt),
+        temperature: client.temperature,
+        messages: build_chat_messages(client, prompt),
+        response_format: client.structured.then(|| {
+            json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "agent_action",
+                    "strict": true,
+                    "schema": action_schema(candidates),
+                },
+            })
+        }),
     };
 
     let request_json =
@@ -634,27 +2293,186 @@ fn call_openai(
         .json(&body)
         .headers(build_openai_headers(&client.api_key)?)
         .send()
+        .await
         .map_err(|e| format!("http: {}", e))?;
     let status = resp.status();
-    let raw_body = resp.text().map_err(|e| format!("read body: {}", e))?;
+    let raw_body = resp.text().await.map_err(|e| format!("read body: {}", e))?;
 
     let parsed: OpenAiChatResponse = serde_json::from_str(&raw_body)
         .map_err(|e| format!("decode: {}; status={} body={}", e, status, raw_body))?;
 
     let response_json = serde_json::to_string_pretty(&parsed).unwrap_or_else(|_| raw_body.clone());
 
+    let usage = parsed
+        .usage
+        .as_ref()
+        .map(|u| LlmUsage {
+            requests: 0,
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+        })
+        .unwrap_or_default();
     let text = parsed
         .choices
-        .get(0)
+        .first()
         .map(|c| c.message.content.clone())
         .unwrap_or_default();
     let parsed = parse_action(&text, candidates, agent_id, next_tick);
-    let action = parsed
-        .clone()
-        .unwrap_or_else(|| choose_action_fallback(candidates, agent_id, next_tick));
     let reply_text = parsed
-        .map(|a| format!("TOON{{action={}}}", action_token(&a)))
+        .as_ref()
+        .map(|a| format!("TOON{{action={}}}", action_token(a)))
+        .unwrap_or_else(|| truncate(&text, 120));
+    let action = parsed.unwrap_or_else(|| choose_action_fallback(candidates, agent_id, next_tick));
+
+    Ok(OllamaResult {
+        request_json,
+        response_json,
+        reply_text,
+        action,
+        model: client.model.clone(),
+        provider: client.provider,
+        usage,
+    })
+}
+
+/// Google's `generateContent` API: the system prompt goes in `systemInstruction` and the
+/// user prompt is the one turn of `contents`. The key is sent in the `x-goog-api-key` header
+/// unless the host URL already carries it as a `key=` query parameter.
+async fn call_gemini(
+    client: &LlmClient,
+    prompt: &str,
+    candidates: &[ActionArg],
+    agent_id: AgentId,
+    next_tick: u64,
+) -> Result<OllamaResult, String> {
+    let url = {
+        let trimmed = client.host.trim_end_matches('/');
+        if trimmed.contains(":generateContent") {
+            trimmed.to_string()
+        } else {
+            format!("{}/v1beta/models/{}:generateContent", trimmed, client.model)
+        }
+    };
+
+    let mut body = GeminiRequest {
+        system_instruction: None,
+        contents: Vec::new(),
+        generation_config: client.temperature.map(|t| json!({ "temperature": t })),
+    };
+    for message in build_chat_messages(client, prompt) {
+        let content = GeminiContent {
+            role: None,
+            parts: vec![GeminiPart {
+                text: message.content,
+            }],
+        };
+        if message.role == "system" {
+            body.system_instruction = Some(content);
+        } else {
+            body.contents.push(GeminiContent {
+                role: Some(message.role),
+                ..content
+            });
+        }
+    }
+
+    let request_json =
+        serde_json::to_string_pretty(&body).map_err(|e| format!("encode request: {}", e))?;
+
+    let mut request = client.http.post(&url).json(&body);
+    if !url.contains("key=") {
+        let key = client
+            .api_key
+            .as_deref()
+            .ok_or("missing LLM API key; set --llm-api-key or LLM_API_KEY")?;
+        request = request.header("x-goog-api-key", key);
+    }
+    let resp = request.send().await.map_err(|e| format!("http: {}", e))?;
+    let status = resp.status();
+    let raw_body = resp.text().await.map_err(|e| format!("read body: {}", e))?;
+
+    let parsed: GeminiResponse = serde_json::from_str(&raw_body)
+        .map_err(|e| format!("decode: {}; status={} body={}", e, status, raw_body))?;
+    if parsed.candidates.is_empty() {
+        return Err(format!(
+            "no candidates; status={} body={}",
+            status, raw_body
+        ));
+    }
+
+    let response_json = serde_json::to_string_pretty(&parsed).unwrap_or_else(|_| raw_body.clone());
+
+    let usage = parsed
+        .usage_metadata
+        .as_ref()
+        .map(|u| LlmUsage {
+            requests: 0,
+            prompt_tokens: u.prompt_token_count,
+            completion_tokens: u.candidates_token_count,
+        })
+        .unwrap_or_default();
+    let text: String = parsed.candidates[0]
+        .content
+        .parts
+        .iter()
+        .map(|part| part.text.as_str())
+        .collect();
+    let parsed = parse_action(&text, candidates, agent_id, next_tick);
+    let reply_text = parsed
+        .as_ref()
+        .map(|a| format!("TOON{{action={}}}", action_token(a)))
         .unwrap_or_else(|| truncate(&text, 120));
+    let action = parsed.unwrap_or_else(|| choose_action_fallback(candidates, agent_id, next_tick));
+
+    Ok(OllamaResult {
+        request_json,
+        response_json,
+        reply_text,
+        action,
+        model: client.model.clone(),
+        provider: client.provider,
+        usage,
+    })
+}
+
+/// A GGUF model loaded in-process from the path in `client.model`, so no server or key is
+/// needed. The chat messages are joined into one plain-text prompt ending in `action:`, which
+/// the model completes.
+#[cfg(feature = "llama")]
+fn call_llama(
+    client: &LlmClient,
+    prompt: &str,
+    candidates: &[ActionArg],
+    agent_id: AgentId,
+    next_tick: u64,
+) -> Result<OllamaResult, String> {
+    let messages = build_chat_messages(client, prompt);
+    let mut text_prompt: String = messages
+        .iter()
+        .map(|m| format!("{}\n\n", m.content))
+        .collect();
+    text_prompt.push_str("action:");
+    let request_json = serde_json::to_string_pretty(&json!({
+        "model": client.model,
+        "messages": messages,
+    }))
+    .map_err(|e| format!("encode request: {}", e))?;
+
+    let model = crate::modules::llama::load(std::path::Path::new(&client.model))?;
+    let completion = model
+        .lock()
+        .map_err(|_| "model lock poisoned".to_string())?
+        .complete(&text_prompt)?;
+    let response_json = serde_json::to_string_pretty(&json!({ "completion": completion }))
+        .unwrap_or_else(|_| completion.clone());
+
+    let text = format!("action:{}", completion);
+    let parsed = parse_action(&text, candidates, agent_id, next_tick);
+    let reply_text = parsed
+        .as_ref()
+        .map(|a| format!("TOON{{action={}}}", action_token(a)))
+        .unwrap_or_else(|| truncate(&completion, 120));
+    let action = parsed.unwrap_or_else(|| choose_action_fallback(candidates, agent_id, next_tick));
 
     Ok(OllamaResult {
         request_json,
@@ -663,9 +2481,21 @@ fn call_openai(
         action,
         model: client.model.clone(),
         provider: client.provider,
+        usage: LlmUsage::default(),
     })
 }
 
+#[cfg(not(feature = "llama"))]
+fn call_llama(
+    _client: &LlmClient,
+    _prompt: &str,
+    _candidates: &[ActionArg],
+    _agent_id: AgentId,
+    _next_tick: u64,
+) -> Result<OllamaResult, String> {
+    Err("the llama provider needs a build with `--features llama`".into())
+}
+
 fn build_openai_headers(api_key: &Option<String>) -> Result<reqwest::header::HeaderMap, String> {
     use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue};
     let mut headers = HeaderMap::new();
@@ -695,16 +2525,19 @@ fn parse_action(
 
     let try_parse = |s: &str| parse_action_string(s, &allowed);
 
-    // Try JSON schema: { "action": "<label>" }
+    // Try JSON: `{ "action": "<label>", "params": {...} }` as `action_schema` asks for, or a
+    // bare `{ "action": "<label>" }`.
     if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(text) {
+        if let Some(action) = structured_action(&json_value, &allowed) {
+            return Some(action);
+        }
         if let Some(action_str) = json_value
             .get("action")
             .and_then(|v| v.as_str())
             .map(|s| s.trim())
+            && let Some(action) = try_parse(action_str)
         {
-            if let Some(action) = try_parse(action_str) {
-                return Some(action);
-            }
+            return Some(action);
         }
     }
 
@@ -725,26 +2558,145 @@ fn parse_action(
 
     // Fallback: `action:<label>` prefix
     for line in text.lines() {
-        if let Some(rest) = line.trim().strip_prefix("action:") {
-            if let Some(action) = try_parse(rest.trim()) {
-                return Some(action);
-            }
+        if let Some(rest) = line.trim().strip_prefix("action:")
+            && let Some(action) = try_parse(rest.trim())
+        {
+            return Some(action);
         }
     }
 
     // Last resort: look for any candidate label substring
     let lower = text.to_lowercase();
     for verb in &allowed {
-        if lower.contains(verb) {
-            if let Some(action) = parse_action_string(verb, &allowed) {
-                return Some(action);
-            }
+        if lower.contains(verb)
+            && let Some(action) = parse_action_string(verb, &allowed)
+        {
+            return Some(action);
         }
     }
 
     None
 }
 
+/// Reply parameters in `action_schema`: offsets for move, dig, place and harvests by position,
+/// a target for move_to, and the ids and terms of reproduce, harvest, attack and trade.
+const SCHEMA_PARAMS: [&str; 13] = [
+    "dx",
+    "dy",
+    "dz",
+    "x",
+    "y",
+    "z",
+    "partner_id",
+    "source_id",
+    "target_id",
+    "offer_ore",
+    "offer_amount",
+    "want_ore",
+    "want_amount",
+];
+
+/// JSON schema for a reply naming one of the candidates' labels as `action`, with every
+/// parameter in `params` (`null` where the action takes none), so strict providers accept it.
+fn action_schema(candidates: &[ActionArg]) -> serde_json::Value {
+    let mut actions: Vec<String> = candidates.iter().map(|a| a.label()).collect();
+    actions.sort();
+    actions.dedup();
+    let mut ores: Vec<serde_json::Value> = OreKind::all()
+        .iter()
+        .map(|ore| json!(ore.label()))
+        .collect();
+    ores.push(serde_json::Value::Null);
+    let mut params = serde_json::Map::new();
+    for name in SCHEMA_PARAMS {
+        let schema = if name.ends_with("_ore") {
+            json!({ "type": ["string", "null"], "enum": ores })
+        } else {
+            json!({ "type": ["integer", "null"] })
+        };
+        params.insert(name.into(), schema);
+    }
+    json!({
+        "type": "object",
+        "properties": {
+            "action": { "type": "string", "enum": actions },
+            "params": {
+                "type": "object",
+                "properties": params,
+                "required": SCHEMA_PARAMS,
+                "additionalProperties": false,
+            },
+        },
+        "required": ["action", "params"],
+        "additionalProperties": false,
+    })
+}
+
+/// The action in a reply shaped by `action_schema`. The parameters are laid out as the
+/// `label(args)` text form, so both kinds of reply are validated the same way.
+fn structured_action(reply: &serde_json::Value, allowed: &HashSet<String>) -> Option<Action> {
+    let label = reply.get("action")?.as_str()?.trim();
+    let params = reply.get("params")?.as_object()?;
+    let verb = normalize_verb(label);
+    // A harvest without an id but with an offset picks the node by position.
+    let harvest_at = verb.starts_with("harvest_")
+        && params
+            .get("source_id")
+            .is_none_or(serde_json::Value::is_null)
+        && params.get("dx").is_some_and(serde_json::Value::is_number);
+    let names: &[&str] = match verb.as_str() {
+        "move" | "dig" => &["dx", "dy", "dz"],
+        verb if verb.starts_with("place_") => &["dx", "dy", "dz"],
+        "move_to" => &["x", "y", "z"],
+        "reproduce" => &["partner_id"],
+        _ if harvest_at => &["dx", "dy", "dz"],
+        verb if verb.starts_with("harvest_") => &["source_id"],
+        "attack" => &["target_id"],
+        "say" => &["to", "message"],
+        "set_toll" => &["harvest", "build"],
+        "post_order" => &["side", "ore", "amount", "price"],
+        "craft" => &["recipe"],
+        "repair" | "demolish" => &["structure_id"],
+        "give_qi" => &["to", "amount"],
+        "trade" => &[
+            "partner_id",
+            "offer_ore",
+            "offer_amount",
+            "want_ore",
+            "want_amount",
+        ],
+        _ => &[],
+    };
+    let args: Vec<String> = names
+        .iter()
+        .map_while(|name| match params.get(*name)? {
+            serde_json::Value::String(text) => Some(text.clone()),
+            serde_json::Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        })
+        .collect();
+    let at = if harvest_at { "@" } else { "" };
+    parse_action_string(&format!("{}({}{})", label, at, args.join(",")), allowed)
+}
+
+fn parse_offset(coords: &str) -> Result<(i32, i32, i32), String> {
+    parse_triple(coords, ["dx", "dy", "dz"])
+}
+
+fn parse_triple(coords: &str, names: [&str; 3]) -> Result<(i32, i32, i32), String> {
+    let parts: Vec<_> = coords.split(',').collect();
+    if parts.len() != 3 {
+        return Err("expected exactly three coordinates".into());
+    }
+    let axis = |idx: usize, name: &str| {
+        parts[idx]
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| format!("{} must be an integer", name))
+    };
+    Ok((axis(0, names[0])?, axis(1, names[1])?, axis(2, names[2])?))
+}
+
 fn normalize_verb(raw: &str) -> String {
     raw.trim().to_lowercase()
 }
@@ -772,6 +2724,9 @@ fn parse_action_string(raw: &str, allowed: &HashSet<String>) -> Option<Action> {
     } else if verb == "harvest_qi" {
         base = "harvest";
         suffix = Some("qi");
+    } else if let Some(rest) = verb.strip_prefix("place_") {
+        base = "place";
+        suffix = Some(rest);
     }
 
     let base_label = base.to_string();
@@ -795,48 +2750,157 @@ fn parse_action_string(raw: &str, allowed: &HashSet<String>) -> Option<Action> {
             if args.len() < 3 {
                 return None;
             }
-            let dx = args.get(0)?.parse().ok()?;
+            let dx = args.first()?.parse().ok()?;
             let dy = args.get(1)?.parse().ok()?;
             let dz = args.get(2)?.parse().ok()?;
             Some(Action::Move { dx, dy, dz })
         }
+        "move_to" => {
+            if args.len() < 3 {
+                return None;
+            }
+            let target = Position {
+                x: args[0].parse().ok()?,
+                y: args[1].parse().ok()?,
+                z: args[2].parse().ok()?,
+            };
+            Some(Action::MoveTo { target })
+        }
         "scan" => Some(Action::Scan),
         "build" => {
             let mut kind = suffix
                 .and_then(|k| StructureKind::from_str(k).ok())
                 .unwrap_or(StructureKind::Basic);
-            if let Some(arg_kind) = args
-                .get(0)
-                .and_then(|k| StructureKind::from_str(k).ok())
-            {
+            if let Some(arg_kind) = args.first().and_then(|k| StructureKind::from_str(k).ok()) {
                 kind = arg_kind;
             }
             Some(Action::BuildStructure { kind })
         }
         "reproduce" => {
-            let partner = args.get(0).and_then(|p| p.parse().ok()).unwrap_or(0);
+            let partner = args.first().and_then(|p| p.parse().ok()).unwrap_or(0);
             Some(Action::Reproduce { partner })
         }
         "harvest" => {
             let mut ore = suffix
                 .and_then(|o| <OreKind as FromStr>::from_str(o).ok())
-                .unwrap_or(OreKind::Qi);
+                .unwrap_or(OreKind::QI);
+            if let Some(dx) = args.first().and_then(|a| a.strip_prefix('@')) {
+                if args.len() < 3 {
+                    return None;
+                }
+                return Some(Action::HarvestAt {
+                    ore,
+                    dx: dx.parse().ok()?,
+                    dy: args[1].parse().ok()?,
+                    dz: args[2].parse().ok()?,
+                });
+            }
             let mut source_id = 0u64;
-            if let Some(first) = args.get(0) {
+            if let Some(first) = args.first() {
                 if let Ok(parsed_id) = first.parse() {
                     source_id = parsed_id;
                 } else if let Ok(parsed_ore) = <OreKind as FromStr>::from_str(first) {
                     ore = parsed_ore;
                 }
             }
-            if let Some(second) = args.get(1) {
-                if let Ok(parsed_id) = second.parse() {
-                    source_id = parsed_id;
-                }
+            if let Some(second) = args.get(1)
+                && let Ok(parsed_id) = second.parse()
+            {
+                source_id = parsed_id;
             }
             Some(Action::HarvestOre { ore, source_id })
         }
+        "trade" => {
+            // Both partners must submit mirrored terms in the same tick for the swap to settle.
+            if args.len() < 5 {
+                return None;
+            }
+            Some(Action::Trade {
+                partner: args.first()?.parse().ok()?,
+                offer_ore: <OreKind as FromStr>::from_str(&args[1]).ok()?,
+                offer_amount: args[2].parse().ok()?,
+                want_ore: <OreKind as FromStr>::from_str(&args[3]).ok()?,
+                want_amount: args[4].parse().ok()?,
+            })
+        }
+        "attack" => {
+            let target = args.first().and_then(|t| t.parse().ok()).unwrap_or(0);
+            Some(Action::Attack { target })
+        }
+        "dig" => {
+            if args.len() < 3 {
+                return None;
+            }
+            Some(Action::Dig {
+                dx: args[0].parse().ok()?,
+                dy: args[1].parse().ok()?,
+                dz: args[2].parse().ok()?,
+            })
+        }
+        "place" => {
+            let block = suffix
+                .and_then(|b| Block::from_str(b).ok())
+                .unwrap_or(Block::Dirt);
+            if args.len() < 3 {
+                return None;
+            }
+            Some(Action::Place {
+                dx: args[0].parse().ok()?,
+                dy: args[1].parse().ok()?,
+                dz: args[2].parse().ok()?,
+                block,
+            })
+        }
         "idle" => Some(Action::Idle),
+        "rest" => Some(Action::Rest),
+        "claim_zone" => Some(Action::ClaimZone),
+        "post_order" => {
+            if args.len() < 4 {
+                return None;
+            }
+            Some(Action::PostOrder {
+                side: OrderSide::from_str(&args[0]).ok()?,
+                ore: <OreKind as FromStr>::from_str(&args[1]).ok()?,
+                amount: args[2].parse().ok()?,
+                price: args[3].parse().ok()?,
+            })
+        }
+        "craft" => Some(Action::Craft {
+            recipe: args.first()?.to_lowercase(),
+        }),
+        "repair" => {
+            let structure_id = args.first().and_then(|p| p.parse().ok()).unwrap_or(0);
+            Some(Action::Repair { structure_id })
+        }
+        "demolish" => Some(Action::DemolishStructure {
+            structure_id: args.first()?.parse().ok()?,
+        }),
+        "give_qi" => {
+            if args.len() < 2 {
+                return None;
+            }
+            Some(Action::GiveQi {
+                to: args[0].parse().ok()?,
+                amount: args[1].parse().ok()?,
+            })
+        }
+        "set_toll" => {
+            if args.len() < 2 {
+                return None;
+            }
+            Some(Action::SetToll {
+                harvest: args[0].parse().ok()?,
+                build: args[1].parse().ok()?,
+            })
+        }
+        // The message is everything after the first comma, commas and spaces included.
+        "say" => {
+            let (to, message) = args_part.split_once(',')?;
+            Some(Action::Say {
+                to: to.trim().parse().ok()?,
+                message: message.trim().trim_matches('"').to_string(),
+            })
+        }
         _ => None,
     }
 }
@@ -849,11 +2913,54 @@ fn choose_action_fallback(candidates: &[ActionArg], _agent_id: AgentId, _next_ti
         .unwrap_or(Action::Idle)
 }
 
+/// The reply of any provider's embedding endpoint; each fills in only its own fields.
+#[derive(Debug, Deserialize)]
+struct EmbedResponse {
+    /// Ollama and Gemini.
+    #[serde(default)]
+    embeddings: Vec<Embedding>,
+    /// OpenAI.
+    #[serde(default)]
+    data: Vec<OpenAiEmbedding>,
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Embedding {
+    /// Ollama's bare vector.
+    Vector(Vec<f32>),
+    /// Gemini's `{"values": [...]}`.
+    Values { values: Vec<f32> },
+}
+
+impl Embedding {
+    fn into_vector(self) -> Vec<f32> {
+        match self {
+            Embedding::Vector(vector) | Embedding::Values { values: vector } => vector,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiEmbedding {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
 #[derive(Debug, Serialize)]
 struct ChatRequest {
     model: String,
     messages: Vec<Message>,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -863,6 +2970,8 @@ struct OpenAiChatRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -874,6 +2983,10 @@ struct Message {
 #[derive(Debug, Serialize, Deserialize)]
 struct ChatResponse {
     message: ChatMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prompt_eval_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    eval_count: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -884,6 +2997,16 @@ struct ChatMessage {
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenAiChatResponse {
     choices: Vec<OpenAiChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -891,6 +3014,53 @@ struct OpenAiChoice {
     message: ChatMessage,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
+    contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation_config: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiContent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiPart {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage_metadata: Option<GeminiUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsage {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiCandidate {
+    content: GeminiContent,
+}
+
 struct OllamaResult {
     request_json: String,
     response_json: String,
@@ -898,6 +3068,8 @@ struct OllamaResult {
     action: Action,
     model: String,
     provider: LlmProvider,
+    /// Tokens the reply reports; `requests` is counted by `call_chat`.
+    usage: LlmUsage,
 }
 
 fn truncate(text: &str, max: usize) -> String {
@@ -910,13 +3082,272 @@ fn truncate(text: &str, max: usize) -> String {
     format!("{}...", &text[..cutoff])
 }
 
-fn action_token(action: &Action) -> String {
+pub(crate) fn action_token(action: &Action) -> String {
     match action {
         Action::Scan => "scan".to_string(),
         Action::Idle => "idle".to_string(),
+        Action::Rest => "rest".to_string(),
+        Action::ClaimZone => "claim_zone".to_string(),
+        Action::SetToll { harvest, build } => format!("set_toll({},{})", harvest, build),
+        Action::PostOrder {
+            side,
+            ore,
+            amount,
+            price,
+        } => format!("post_order({},{},{},{})", side, ore, amount, price),
+        Action::Craft { recipe } => format!("craft({})", recipe),
+        Action::Repair { structure_id } => format!("repair({})", structure_id),
+        Action::DemolishStructure { structure_id } => format!("demolish({})", structure_id),
+        Action::GiveQi { to, amount } => format!("give_qi({},{})", to, amount),
         Action::Move { dx, dy, dz } => format!("move({},{},{})", dx, dy, dz),
+        Action::MoveTo { target } => format!("move_to({},{},{})", target.x, target.y, target.z),
         Action::Reproduce { partner } => format!("reproduce({})", partner),
         Action::BuildStructure { kind } => format!("build_structure({})", kind),
         Action::HarvestOre { ore, source_id } => format!("harvest_{}({})", ore, source_id),
+        Action::HarvestAt { ore, dx, dy, dz } => {
+            format!("harvest_{}(@{},{},{})", ore, dx, dy, dz)
+        }
+        Action::Trade {
+            partner,
+            offer_ore,
+            offer_amount,
+            want_ore,
+            want_amount,
+        } => format!(
+            "trade({},{},{},{},{})",
+            partner, offer_ore, offer_amount, want_ore, want_amount
+        ),
+        Action::Attack { target } => format!("attack({})", target),
+        Action::Dig { dx, dy, dz } => format!("dig({},{},{})", dx, dy, dz),
+        Action::Place { dx, dy, dz, block } => {
+            format!("place_{}({},{},{})", block, dx, dy, dz)
+        }
+        Action::Say { to, message } => format!("say({},{})", to, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gemini_requests_map_the_prompt_onto_generate_content() {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                head.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let reply = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"action: scan"}]}}]}"#;
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                reply.len(),
+                reply
+            )
+            .unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Flash", 5, Position::origin());
+        let client = LlmClient::new(
+            host,
+            "gemini-flash",
+            LlmProvider::Gemini,
+            Some("secret".into()),
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap();
+        let candidates = [ActionArg::Idle, ActionArg::Scan];
+        let decision = plan_with_llm(
+            &vm,
+            agent_id,
+            &candidates,
+            &mut BrainMemory::default(),
+            Some(&client),
+            1,
+        );
+        assert!(decision.llm_ok, "{}", decision.response);
+        assert_eq!(decision.action, Action::Scan);
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /v1beta/models/gemini-flash:generateContent "));
+        assert!(head.to_lowercase().contains("x-goog-api-key: secret"));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(body["systemInstruction"]["parts"][0]["text"].is_string());
+        assert_eq!(body["contents"][0]["role"], "user");
+        assert_eq!(body["contents"][0]["parts"][0]["text"], decision.prompt);
+    }
+
+    #[test]
+    fn openai_replies_follow_the_action_schema() {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let content = serde_json::json!({
+                "action": "move",
+                "params": {
+                    "dx": 1, "dy": 0, "dz": -1, "x": null, "y": null, "z": null,
+                    "partner_id": null, "source_id": null, "target_id": null,
+                    "offer_ore": null, "offer_amount": null, "want_ore": null, "want_amount": null,
+                },
+            });
+            let reply = serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": content.to_string() } }],
+                "usage": { "prompt_tokens": 812, "completion_tokens": 41, "total_tokens": 853 },
+            })
+            .to_string();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                reply.len(),
+                reply
+            )
+            .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Strict", 5, Position::origin());
+        let client = LlmClient::new(
+            host,
+            "gpt-5-nano",
+            LlmProvider::Openai,
+            Some("secret".into()),
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap()
+        .with_temperature(Some(0.25))
+        .with_goal(Some("Wall in the Qi nodes".into()));
+        let candidates = [
+            ActionArg::Idle,
+            ActionArg::Move {
+                dx: 0,
+                dy: 0,
+                dz: 0,
+            },
+        ];
+        let decision = plan_with_llm(
+            &vm,
+            agent_id,
+            &candidates,
+            &mut BrainMemory::default(),
+            Some(&client),
+            1,
+        );
+        assert!(decision.llm_ok, "{}", decision.response);
+        assert_eq!(
+            decision.action,
+            Action::Move {
+                dx: 1,
+                dy: 0,
+                dz: -1
+            }
+        );
+
+        let body: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        let format = &body["response_format"];
+        assert_eq!(body["temperature"], 0.25);
+        assert!(
+            body["messages"]
+                .to_string()
+                .contains("Wall in the Qi nodes")
+        );
+        assert_eq!(format["type"], "json_schema");
+        assert_eq!(format["json_schema"]["strict"], true);
+        assert_eq!(
+            format["json_schema"]["schema"]["properties"]["action"]["enum"],
+            serde_json::json!(["idle", "move"])
+        );
+        assert_eq!(
+            decision.usage,
+            crate::modules::agent::LlmUsage {
+                requests: 1,
+                prompt_tokens: 812,
+                completion_tokens: 41,
+            }
+        );
+    }
+
+    #[test]
+    fn prompt_templates_from_files_replace_the_built_in_prompts() {
+        use crate::modules::prompts::{SYSTEM_TEMPLATE_FILE, USER_TEMPLATE_FILE};
+
+        let dir = std::env::temp_dir().join(format!("harimu-prompts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(SYSTEM_TEMPLATE_FILE), "Pursue {{ goal }}.").unwrap();
+        let templates = PromptTemplates::load_from(&dir).unwrap();
+        assert_eq!(templates.user(), DEFAULT_USER_TEMPLATE);
+        std::fs::write(
+            dir.join(USER_TEMPLATE_FILE),
+            "Goal: {{goal}}\nYou: {{state}}\nPick one of {{candidates}}.",
+        )
+        .unwrap();
+        let templates = PromptTemplates::load_from(&dir).unwrap();
+        assert!(!templates.is_default());
+
+        std::fs::write(dir.join(USER_TEMPLATE_FILE), "{{weather}}").unwrap();
+        let err = PromptTemplates::load_from(&dir).unwrap_err();
+        assert!(err.contains("{{weather}}"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut vm = Vm::with_seed(7);
+        let agent_id = vm.spawn_agent("Ada", 5, Position::origin());
+        // Nothing listens there, so the decision falls back but still records its prompt.
+        let client = LlmClient::new(
+            "http://127.0.0.1:9",
+            "m",
+            LlmProvider::Ollama,
+            None,
+            Duration::from_millis(200),
+        )
+        .unwrap()
+        .with_goal(Some("guard the well".into()))
+        .with_prompt_templates(templates);
+        assert_eq!(client.system_prompt(), "Pursue guard the well.");
+        let decision = plan_with_llm(
+            &vm,
+            agent_id,
+            &[ActionArg::Scan, ActionArg::Rest],
+            &mut BrainMemory::default(),
+            Some(&client),
+            1,
+        );
+        assert!(decision.prompt.starts_with("Goal: guard the well\nYou: "));
+        assert!(decision.prompt.ends_with("Pick one of rest, scan."));
     }
 }
@@ -1,25 +1,68 @@
 use std::collections::HashMap;
-use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::Path;
 
 use rand::RngCore;
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 
-use crate::modules::vm::DEFAULT_MAX_AGENT_AGE;
+use crate::modules::agenda::ScheduledAction;
+use crate::modules::agent::ActionArg;
+use crate::modules::inventory::ItemKind;
+use crate::modules::names;
+use crate::modules::store;
+use crate::modules::templates::{AgentTemplate, BrainConfig};
+use crate::modules::vm::{Agent, AgentId, DEFAULT_MAX_AGENT_AGE, Position, World};
 
 fn default_max_age() -> u64 {
     DEFAULT_MAX_AGENT_AGE
 }
 
+fn default_alive() -> bool {
+    true
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentProfile {
     pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub qi: u64,
     pub companions: u32,
+    /// Names of the companions spawned with `agent spawn`, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub companion_names: Vec<String>,
     #[serde(default = "default_max_age")]
     pub max_age: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brain: Option<BrainConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    /// Carried over from the last run, like `qi` and `position` (see `sync_from_world`).
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub transistors: u64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub age: u64,
+    /// False once the agent died in a run; `start` no longer spawns it.
+    #[serde(default = "default_alive", skip_serializing_if = "is_true")]
+    pub alive: bool,
+    /// Actions `start` injects at set ticks, in the order they were scheduled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduledAction>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -34,44 +77,30 @@ pub struct AgentStore {
     pub votes: HashMap<String, VoteTally>,
 }
 
-fn agents_dir() -> PathBuf {
-    PathBuf::from(".harimu")
-}
-
-fn agents_path() -> PathBuf {
-    agents_dir().join("agents.json")
-}
-
-pub fn load() -> io::Result<AgentStore> {
-    let path = agents_path();
-    if !path.exists() {
-        return Ok(AgentStore::default());
+impl AgentStore {
+    /// Whether an agent or companion already goes by `name`.
+    pub fn name_taken(&self, name: &str) -> bool {
+        self.agents
+            .values()
+            .any(|a| a.name.as_deref() == Some(name) || a.companion_names.iter().any(|c| c == name))
     }
 
-    let data = fs::read(&path)?;
-    if data.is_empty() {
-        return Ok(AgentStore::default());
+    /// A generated name for the agent `id` that no agent or companion has yet; the same
+    /// address always starts from the same candidate.
+    pub fn generate_name(&self, id: &str, salt: u64) -> String {
+        let seed = names::name_seed([names::name_seed_from_str(id), salt]);
+        names::unique_name(seed, |name| self.name_taken(name))
     }
+}
 
-    let store: AgentStore = serde_json::from_slice(&data).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!(
-                "failed to parse agents file {}; delete it to reset: {}",
-                path.display(),
-                e
-            ),
-        )
-    })?;
+const AGENTS_DOC: &str = "agents.json";
 
-    Ok(store)
+pub fn load() -> io::Result<AgentStore> {
+    store::load_document(AGENTS_DOC, "agents file")
 }
 
-pub fn save(store: &AgentStore) -> io::Result<()> {
-    fs::create_dir_all(agents_dir())?;
-    let json = serde_json::to_vec_pretty(store)?;
-    fs::write(agents_path(), json)?;
-    Ok(())
+pub fn save(agents: &AgentStore) -> io::Result<()> {
+    store::save_document(AGENTS_DOC, agents)
 }
 
 pub fn create_agent(store: &mut AgentStore, id: String) -> Result<AgentProfile, String> {
@@ -85,14 +114,249 @@ pub fn create_agent(store: &mut AgentStore, id: String) -> Result<AgentProfile,
 
     let profile = AgentProfile {
         id: address.clone(),
+        name: None,
         qi: 0,
         companions: 0,
+        companion_names: Vec::new(),
         max_age: DEFAULT_MAX_AGENT_AGE,
+        template: None,
+        role: None,
+        goal: None,
+        brain: None,
+        position: None,
+        transistors: 0,
+        age: 0,
+        alive: true,
+        schedule: Vec::new(),
     };
     store.agents.insert(address.clone(), profile.clone());
     Ok(profile)
 }
 
+/// Create an agent whose starting qi, lifespan, role, goal, and brain come from an archetype.
+pub fn create_agent_from_template(
+    store: &mut AgentStore,
+    name: &str,
+    template: &AgentTemplate,
+) -> Result<AgentProfile, String> {
+    let base = create_agent(store, String::new())?;
+    let profile = AgentProfile {
+        qi: template.qi,
+        max_age: template.max_age.max(1),
+        template: Some(name.to_string()),
+        role: template.role.clone(),
+        goal: template.goal.clone(),
+        brain: template.brain.clone(),
+        ..base
+    };
+    store.agents.insert(profile.id.clone(), profile.clone());
+    Ok(profile)
+}
+
+/// Columns accepted by `agent import`; every column except `name` is optional.
+pub const IMPORT_COLUMNS: [&str; 5] = ["name", "qi", "max_age", "position", "goal"];
+
+/// One validated row of an agent import file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentImportRow {
+    pub name: String,
+    pub qi: u64,
+    pub max_age: u64,
+    pub position: Option<Position>,
+    pub goal: Option<String>,
+}
+
+/// Parse and validate an agent CSV. All problems are reported together, one per line,
+/// so a bad file never half-imports.
+pub fn parse_import_csv(path: &Path, store: &AgentStore) -> Result<Vec<AgentImportRow>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("failed to read header of {}: {}", path.display(), e))?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+    let mut errors = Vec::new();
+    for header in &headers {
+        if !IMPORT_COLUMNS.contains(&header.as_str()) {
+            errors.push(format!(
+                "unknown column '{}' (expected {})",
+                header,
+                IMPORT_COLUMNS.join(", ")
+            ));
+        }
+    }
+    if !headers.iter().any(|h| h == "name") {
+        errors.push("missing required column 'name'".into());
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let mut seen: Vec<String> = store
+        .agents
+        .values()
+        .flat_map(|a| a.name.iter().chain(&a.companion_names).cloned())
+        .collect();
+    let mut rows = Vec::new();
+
+    for (idx, record) in reader.records().enumerate() {
+        // Line 1 is the header.
+        let line = idx + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                errors.push(format!("line {}: {}", line, err));
+                continue;
+            }
+        };
+        let field = |name: &str| {
+            column(name)
+                .and_then(|i| record.get(i))
+                .filter(|v| !v.is_empty())
+        };
+
+        let name = match field("name") {
+            Some(name) if seen.iter().any(|s| s == name) => {
+                errors.push(format!("line {}: duplicate agent name '{}'", line, name));
+                continue;
+            }
+            Some(name) => name.to_string(),
+            None => {
+                errors.push(format!("line {}: name is required", line));
+                continue;
+            }
+        };
+
+        let qi = match field("qi").map(str::parse::<u64>) {
+            None => 0,
+            Some(Ok(qi)) => qi,
+            Some(Err(_)) => {
+                errors.push(format!("line {}: qi must be a non-negative integer", line));
+                continue;
+            }
+        };
+        let max_age = match field("max_age").map(str::parse::<u64>) {
+            None => DEFAULT_MAX_AGENT_AGE,
+            Some(Ok(age)) if age > 0 => age,
+            Some(_) => {
+                errors.push(format!("line {}: max_age must be a positive integer", line));
+                continue;
+            }
+        };
+        let position = match field("position").map(parse_import_position) {
+            None => None,
+            Some(Ok(position)) => Some(position),
+            Some(Err(err)) => {
+                errors.push(format!("line {}: {}", line, err));
+                continue;
+            }
+        };
+
+        seen.push(name.clone());
+        rows.push(AgentImportRow {
+            name,
+            qi,
+            max_age,
+            position,
+            goal: field("goal").map(str::to_string),
+        });
+    }
+
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    Ok(rows)
+}
+
+/// Positions are `x,y,z` (quoted in CSV) or `x y z` / `x;y;z` to avoid quoting.
+fn parse_import_position(raw: &str) -> Result<Position, String> {
+    let parts: Vec<&str> = raw
+        .split([',', ';', ' '])
+        .filter(|p| !p.is_empty())
+        .collect();
+    let coords: Vec<i32> = parts
+        .iter()
+        .map(|p| p.parse::<i32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("position '{}' must be three integers x,y,z", raw))?;
+    match coords.as_slice() {
+        [x, y, z] => Ok(Position {
+            x: *x,
+            y: *y,
+            z: *z,
+        }),
+        _ => Err(format!("position '{}' must be three integers x,y,z", raw)),
+    }
+}
+
+pub fn create_agent_from_import(
+    store: &mut AgentStore,
+    row: &AgentImportRow,
+) -> Result<AgentProfile, String> {
+    let base = create_agent(store, String::new())?;
+    let profile = AgentProfile {
+        name: Some(row.name.clone()),
+        qi: row.qi,
+        max_age: row.max_age,
+        position: row.position,
+        goal: row.goal.clone(),
+        ..base
+    };
+    store.agents.insert(profile.id.clone(), profile.clone());
+    Ok(profile)
+}
+
+/// Copy each registered agent's live state (qi, transistors, position, age, alive) from `world`
+/// into its profile, so the next fresh world spawns it as it was. Agents are matched by
+/// address, the name `start` spawns them under. Returns how many profiles changed.
+pub fn sync_from_world(store: &mut AgentStore, world: &World) -> usize {
+    let mut live: HashMap<&str, (AgentId, &Agent)> = HashMap::new();
+    for (id, agent) in world.agents() {
+        let entry = live.entry(agent.name.as_str()).or_insert((*id, agent));
+        if *id < entry.0 {
+            *entry = (*id, agent);
+        }
+    }
+
+    let mut changed = 0;
+    for (addr, profile) in store.agents.iter_mut() {
+        let Some((_, agent)) = live.get(addr.as_str()) else {
+            continue;
+        };
+        let synced = AgentProfile {
+            qi: agent.qi() as u64,
+            transistors: agent.item(ItemKind::Transistor) as u64,
+            position: Some(agent.position),
+            age: agent.age,
+            alive: agent.alive,
+            ..profile.clone()
+        };
+        if (
+            synced.qi,
+            synced.transistors,
+            synced.position,
+            synced.age,
+            synced.alive,
+        ) != (
+            profile.qi,
+            profile.transistors,
+            profile.position,
+            profile.age,
+            profile.alive,
+        ) {
+            *profile = synced;
+            changed += 1;
+        }
+    }
+    changed
+}
+
 pub fn infuse(store: &mut AgentStore, id: &str, amount: u64) -> Result<(), String> {
     let agent = store
         .agents
@@ -102,6 +366,44 @@ pub fn infuse(store: &mut AgentStore, id: &str, amount: u64) -> Result<(), Strin
     Ok(())
 }
 
+pub fn schedule_action(
+    store: &mut AgentStore,
+    id: &str,
+    scheduled: ScheduledAction,
+) -> Result<(), String> {
+    let agent = store
+        .agents
+        .get_mut(id)
+        .ok_or_else(|| format!("agent {} not found", id))?;
+    agent.schedule.push(scheduled);
+    Ok(())
+}
+
+/// Remove the scheduled action at 1-based `index` (as `agent info` numbers them), or all of
+/// them without one. Returns what was removed.
+pub fn unschedule_action(
+    store: &mut AgentStore,
+    id: &str,
+    index: Option<usize>,
+) -> Result<Vec<ScheduledAction>, String> {
+    let agent = store
+        .agents
+        .get_mut(id)
+        .ok_or_else(|| format!("agent {} not found", id))?;
+    match index {
+        None => Ok(std::mem::take(&mut agent.schedule)),
+        Some(index) if (1..=agent.schedule.len()).contains(&index) => {
+            Ok(vec![agent.schedule.remove(index - 1)])
+        }
+        Some(index) => Err(format!(
+            "agent {} has no scheduled action {} ({} scheduled)",
+            id,
+            index,
+            agent.schedule.len()
+        )),
+    }
+}
+
 pub fn extend_life(store: &mut AgentStore, id: &str, max_age: u64) -> Result<(), String> {
     let agent = store
         .agents
@@ -111,15 +413,67 @@ pub fn extend_life(store: &mut AgentStore, id: &str, max_age: u64) -> Result<(),
     Ok(())
 }
 
-pub fn spawn_companion(store: &mut AgentStore, id: &str) -> Result<(), String> {
+/// Merge `brain` into agent `id`'s brain config: fields it sets replace the stored ones, and
+/// a non-empty action list replaces the stored list. `goal` replaces the agent's goal.
+pub fn configure_brain(
+    store: &mut AgentStore,
+    id: &str,
+    brain: BrainConfig,
+    goal: Option<String>,
+) -> Result<(), String> {
+    for action in &brain.actions {
+        action.parse::<ActionArg>()?;
+    }
+    if brain.temperature.is_some_and(|t| !(0.0..=2.0).contains(&t)) {
+        return Err("temperature must be between 0 and 2".to_string());
+    }
     let agent = store
         .agents
         .get_mut(id)
         .ok_or_else(|| format!("agent {} not found", id))?;
-    agent.companions = agent.companions.saturating_add(1);
+    let stored = agent.brain.get_or_insert_with(BrainConfig::default);
+    stored.mode = brain.mode.or(stored.mode);
+    stored.provider = brain.provider.or(stored.provider);
+    stored.model = brain.model.or(stored.model.take());
+    stored.host = brain.host.or(stored.host.take());
+    stored.api_key_env = brain.api_key_env.or(stored.api_key_env.take());
+    stored.temperature = brain.temperature.or(stored.temperature);
+    if !brain.actions.is_empty() {
+        stored.actions = brain.actions;
+    }
+    if goal.is_some() {
+        agent.goal = goal;
+    }
     Ok(())
 }
 
+/// Set the goal agent `id` pursues from the next `start`; a blank goal restores
+/// `DEFAULT_AGENT_GOAL`. Returns the goal it replaced.
+pub fn set_goal(store: &mut AgentStore, id: &str, goal: &str) -> Result<Option<String>, String> {
+    let agent = store
+        .agents
+        .get_mut(id)
+        .ok_or_else(|| format!("agent {} not found", id))?;
+    let goal = goal.trim();
+    let goal = (!goal.is_empty()).then(|| goal.to_string());
+    Ok(std::mem::replace(&mut agent.goal, goal))
+}
+
+/// Add a named companion to agent `id`; returns the companion's name.
+pub fn spawn_companion(store: &mut AgentStore, id: &str) -> Result<String, String> {
+    let companions = store
+        .agents
+        .get(id)
+        .ok_or_else(|| format!("agent {} not found", id))?
+        .companions;
+    let name = store.generate_name(id, u64::from(companions) + 1);
+    if let Some(agent) = store.agents.get_mut(id) {
+        agent.companions = agent.companions.saturating_add(1);
+        agent.companion_names.push(name.clone());
+    }
+    Ok(name)
+}
+
 pub fn remove_agent(store: &mut AgentStore, id: &str) -> Result<(), String> {
     if store.agents.remove(id).is_some() {
         Ok(())
@@ -174,3 +528,102 @@ pub enum VoteDirection {
     Up,
     Down,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::modules::testing::TempDataDir;
+
+    fn write_csv(data: &TempDataDir, contents: &str) -> PathBuf {
+        let path = data.path().join("agents.csv");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn import_rows_fill_in_defaults_and_accept_each_position_form() {
+        let data = TempDataDir::new("import-rows");
+        let path = write_csv(
+            &data,
+            "Name,qi,max_age,position,goal\n\
+             ada,5,,\"1,2,3\",mine\n\
+             bo,,40,4 5 -6,\n\
+             cy,,,7;8;9,\n",
+        );
+        let rows = parse_import_csv(&path, &AgentStore::default()).unwrap();
+        assert_eq!(
+            rows[0],
+            AgentImportRow {
+                name: "ada".into(),
+                qi: 5,
+                max_age: DEFAULT_MAX_AGENT_AGE,
+                position: Some(Position { x: 1, y: 2, z: 3 }),
+                goal: Some("mine".into()),
+            }
+        );
+        assert_eq!((rows[1].qi, rows[1].max_age), (0, 40));
+        assert_eq!(rows[1].position, Some(Position { x: 4, y: 5, z: -6 }));
+        assert_eq!(rows[1].goal, None);
+        assert_eq!(rows[2].position, Some(Position { x: 7, y: 8, z: 9 }));
+    }
+
+    #[test]
+    fn unknown_or_missing_columns_are_refused() {
+        let data = TempDataDir::new("import-columns");
+        let path = write_csv(&data, "qi,colour\n5,red\n");
+        let err = parse_import_csv(&path, &AgentStore::default()).unwrap_err();
+        let lines: Vec<&str> = err.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", err);
+        assert!(lines[0].starts_with("unknown column 'colour'"), "{}", err);
+        assert_eq!(lines[1], "missing required column 'name'");
+    }
+
+    #[test]
+    fn bad_rows_are_all_reported_by_line() {
+        let data = TempDataDir::new("import-bad-rows");
+        let path = write_csv(
+            &data,
+            "name,qi,max_age,position\n\
+             ada,-1,,\n\
+             bo,,0,\n\
+             cy,,,\"1,2\"\n\
+             dee,,,1 2 x\n\
+             ,3,,\n",
+        );
+        let err = parse_import_csv(&path, &AgentStore::default()).unwrap_err();
+        assert_eq!(
+            err.lines().collect::<Vec<_>>(),
+            [
+                "line 2: qi must be a non-negative integer",
+                "line 3: max_age must be a positive integer",
+                "line 4: position '1,2' must be three integers x,y,z",
+                "line 5: position '1 2 x' must be three integers x,y,z",
+                "line 6: name is required",
+            ]
+        );
+    }
+
+    #[test]
+    fn names_must_be_new_to_the_file_and_the_store() {
+        let data = TempDataDir::new("import-duplicates");
+        let mut store = AgentStore::default();
+        let mut existing = create_agent(&mut store, String::new()).unwrap();
+        existing.name = Some("ada".into());
+        existing.companion_names = vec!["bo".into()];
+        store.agents.insert(existing.id.clone(), existing);
+
+        let path = write_csv(&data, "name\nada\nbo\ncy\ncy\n");
+        let err = parse_import_csv(&path, &store).unwrap_err();
+        assert_eq!(
+            err.lines().collect::<Vec<_>>(),
+            [
+                "line 2: duplicate agent name 'ada'",
+                "line 3: duplicate agent name 'bo'",
+                "line 5: duplicate agent name 'cy'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::modules::inventory::ItemKind;
+use crate::modules::journal::{EpochSummary, JournalEntry};
+use crate::modules::ore::OreKind;
+use crate::modules::stats::ActionStatsStore;
+use crate::modules::view::WorldSnapshot;
+
+/// Cause recorded for deaths in pruned epochs, whose summaries keep only a count.
+pub const UNKNOWN_REASON: &str = "unknown";
+
+/// An analysis `harimu analyze` computes from a run's stores.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Metric {
+    /// Qi held by agents and left in Qi nodes per tick, from the per-tick snapshots
+    QiOverTime,
+    /// Deaths per cause, from the event journal and its epoch summaries
+    DeathsByReason,
+    /// Actions taken per kind, from the action stats store
+    Actions,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::QiOverTime, Metric::DeathsByReason, Metric::Actions];
+
+    /// File stem of the metric's report files, e.g. `qi_over_time`.
+    pub fn file_stem(self) -> &'static str {
+        match self {
+            Metric::QiOverTime => "qi_over_time",
+            Metric::DeathsByReason => "deaths_by_reason",
+            Metric::Actions => "actions",
+        }
+    }
+}
+
+/// The world's Qi at one tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct QiPoint {
+    pub tick: u64,
+    pub agent_qi: u64,
+    pub node_qi: u64,
+    pub alive_agents: u64,
+}
+
+impl QiPoint {
+    pub fn of(snapshot: &WorldSnapshot) -> Self {
+        let agent_qi = snapshot
+            .agents
+            .iter()
+            .map(|agent| agent.inventory.get(ItemKind::Qi) as u64)
+            .sum();
+        let node_qi = snapshot
+            .ore_nodes
+            .iter()
+            .filter(|node| node.ore == OreKind::QI)
+            .map(|node| node.available as u64)
+            .sum();
+        Self {
+            tick: snapshot.tick,
+            agent_qi,
+            node_qi,
+            alive_agents: snapshot.agents.iter().filter(|a| a.alive).count() as u64,
+        }
+    }
+}
+
+/// `AgentDied` events per `reason`. Deaths in pruned epochs survive only as a count in their
+/// summary and are filed under `UNKNOWN_REASON`.
+pub fn deaths_by_reason(
+    entries: &[JournalEntry],
+    summaries: &[EpochSummary],
+) -> BTreeMap<String, u64> {
+    let mut deaths = BTreeMap::new();
+    for entry in entries.iter().filter(|entry| entry.kind() == "AgentDied") {
+        let reason = entry.field_str("reason").unwrap_or(UNKNOWN_REASON);
+        *deaths.entry(reason.to_string()).or_default() += 1;
+    }
+    let pruned: u64 = summaries
+        .iter()
+        .filter(|summary| summary.pruned)
+        .filter_map(|summary| summary.by_kind.get("AgentDied"))
+        .sum();
+    if pruned > 0 {
+        *deaths.entry(UNKNOWN_REASON.to_string()).or_default() += pruned;
+    }
+    deaths
+}
+
+/// Actions of every agent per kind, including kinds nobody took.
+pub fn action_counts(stats: &ActionStatsStore) -> BTreeMap<&'static str, u64> {
+    let mut counts = BTreeMap::new();
+    for agent in stats.per_agent.values() {
+        for (kind, count) in [
+            ("move", agent.move_count),
+            ("scan", agent.scan_count),
+            ("build", agent.build_count),
+            ("harvest", agent.harvest_count),
+            ("reproduce", agent.reproduce_count),
+            ("trade", agent.trade_count),
+            ("attack", agent.attack_count),
+            ("dig", agent.dig_count),
+            ("place", agent.place_count),
+            ("rest", agent.rest_count),
+            ("say", agent.say_count),
+            ("claim_zone", agent.claim_count),
+            ("set_toll", agent.toll_count),
+            ("post_order", agent.order_count),
+            ("craft", agent.craft_count),
+            ("repair", agent.repair_count),
+            ("demolish", agent.demolish_count),
+            ("give_qi", agent.give_count),
+            ("idle", agent.idle_count),
+        ] {
+            *counts.entry(kind).or_default() += count;
+        }
+    }
+    counts
+}
@@ -0,0 +1,370 @@
+use std::time::Instant;
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::modules::auth::{Scope, TokenStore};
+use crate::modules::journal::{self, JournalEntry};
+use crate::modules::mempool::{Mempool, QuotaLimits};
+use crate::modules::store;
+use crate::modules::vm::{
+    ActionRequest, AgentId, DEFAULT_MAX_AGENT_AGE, Position, Qi, TickResult, Vm,
+};
+use crate::modules::wallet::WalletStore;
+
+/// Called after every tick the API advances, to persist it like the `start` loop does.
+pub type TickHook = Box<dyn FnMut(&[ActionRequest], &TickResult, &Vm) + Send>;
+
+/// HTTP-style verbs the API distinguishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+    Other,
+}
+
+/// One request, independent of the transport that carried it.
+#[derive(Debug, Clone)]
+pub struct ApiRequest<'a> {
+    pub method: Method,
+    /// Path with an optional query string, e.g. `/events?since_tick=10`.
+    pub url: &'a str,
+    /// The bearer token, if the client sent one.
+    pub token: Option<&'a str>,
+    /// Who is calling when no token is used (e.g. the peer address); used for quotas.
+    pub peer: &'a str,
+    pub body: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ApiResponse {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TickBody {
+    #[serde(default)]
+    ticks: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpawnBody {
+    name: String,
+    #[serde(default)]
+    qi: Qi,
+    #[serde(default = "Position::origin")]
+    position: Position,
+    #[serde(default = "default_max_age")]
+    max_age: u64,
+}
+
+fn default_max_age() -> u64 {
+    DEFAULT_MAX_AGENT_AGE
+}
+
+/// Maximum ticks one `POST /tick` may advance.
+const MAX_TICKS_PER_REQUEST: u64 = 1_000;
+
+/// The world behind `harimu serve`: a `Vm` plus the queue of actions clients submitted for
+/// its next tick. When the token store holds any tokens, every request needs one: reads need
+/// `observe`, `POST /actions` needs `act:<agent>` for the acting agent, and `POST /tick` and
+/// `POST /agents` need `admin`.
+pub struct Api {
+    vm: Vm,
+    mempool: Mempool,
+    tokens: TokenStore,
+    read_only: bool,
+    on_tick: Option<TickHook>,
+}
+
+impl Api {
+    pub fn new(vm: Vm, limits: QuotaLimits, tokens: TokenStore) -> Self {
+        Self {
+            vm,
+            mempool: Mempool::new(limits),
+            tokens,
+            read_only: store::is_read_only(),
+            on_tick: None,
+        }
+    }
+
+    pub fn with_tick_hook(mut self, hook: TickHook) -> Self {
+        self.on_tick = Some(hook);
+        self
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    pub fn mempool(&self) -> &Mempool {
+        &self.mempool
+    }
+
+    pub fn handle(&mut self, request: &ApiRequest) -> ApiResponse {
+        let (path, query) = request.url.split_once('?').unwrap_or((request.url, ""));
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        match (request.method, segments.as_slice()) {
+            (Method::Get, ["snapshot"]) => self
+                .authorize(request, &Scope::Observe)
+                .map(|_| ApiResponse::ok(json!(self.vm.snapshot())))
+                .unwrap_or_else(|err| err),
+            (Method::Get, ["stats"]) => self
+                .authorize(request, &Scope::Observe)
+                .map(|_| ApiResponse::ok(json!(self.vm.stats())))
+                .unwrap_or_else(|err| err),
+            (Method::Get, ["agents"]) => self
+                .authorize(request, &Scope::Observe)
+                .map(|_| ApiResponse::ok(json!(self.vm.snapshot().agents)))
+                .unwrap_or_else(|err| err),
+            (Method::Get, ["agents", id]) => self
+                .authorize(request, &Scope::Observe)
+                .map(|_| self.agent(id))
+                .unwrap_or_else(|err| err),
+            (Method::Get, ["events"]) => self
+                .authorize(request, &Scope::Observe)
+                .map(|_| events_since(query))
+                .unwrap_or_else(|err| err),
+            (Method::Get, ["wallets"]) => self
+                .authorize(request, &Scope::Observe)
+                .map(|_| wallets())
+                .unwrap_or_else(|err| err),
+            (Method::Post, ["agents"]) => self.spawn(request),
+            (Method::Post, ["actions"]) => self.submit(request),
+            (Method::Post, ["tick"]) => self.tick(request),
+            (_, ["snapshot" | "stats" | "agents" | "events" | "wallets" | "actions" | "tick"])
+            | (_, ["agents", _]) => ApiResponse::error(405, "method not allowed"),
+            _ => ApiResponse::error(404, format!("no endpoint at {}", path)),
+        }
+    }
+
+    /// Check that `request` may act with `needed`; returns the identity quotas are counted
+    /// against: the token id, else the peer.
+    pub fn authorize(&self, request: &ApiRequest, needed: &Scope) -> Result<String, ApiResponse> {
+        if self.tokens.tokens.is_empty() {
+            return Ok(request.peer.to_string());
+        }
+        let secret = request
+            .token
+            .ok_or_else(|| ApiResponse::error(401, "missing bearer token"))?;
+        self.tokens
+            .authorize(secret, needed)
+            .map(|token| token.id.clone())
+            .map_err(|err| {
+                let status = if self.tokens.authenticate(secret).is_some() {
+                    403
+                } else {
+                    401
+                };
+                ApiResponse::error(status, err)
+            })
+    }
+
+    fn agent(&self, id: &str) -> ApiResponse {
+        let Ok(id) = id.parse::<AgentId>() else {
+            return ApiResponse::error(400, format!("agent id '{}' must be a number", id));
+        };
+        match self.vm.snapshot().agents.into_iter().find(|a| a.id == id) {
+            Some(agent) => ApiResponse::ok(json!(agent)),
+            None => ApiResponse::error(404, format!("agent {} not found", id)),
+        }
+    }
+
+    /// Add an agent to the world; it is saved with the next tick.
+    fn spawn(&mut self, request: &ApiRequest) -> ApiResponse {
+        if self.read_only {
+            return ApiResponse::error(403, "the data directory is read-only");
+        }
+        if let Err(err) = self.authorize(request, &Scope::Admin) {
+            return err;
+        }
+        let body: SpawnBody = match serde_json::from_str(request.body) {
+            Ok(body) => body,
+            Err(err) => return ApiResponse::error(400, format!("invalid agent: {}", err)),
+        };
+        let id = self
+            .vm
+            .spawn_agent_with_age(body.name, body.qi, body.position, body.max_age);
+        ApiResponse {
+            status: 201,
+            body: json!({ "agent_id": id }),
+        }
+    }
+
+    fn submit(&mut self, request: &ApiRequest) -> ApiResponse {
+        if self.read_only {
+            return ApiResponse::error(403, "the data directory is read-only");
+        }
+        let action: ActionRequest = match serde_json::from_str(request.body) {
+            Ok(action) => action,
+            Err(err) => return ApiResponse::error(400, format!("invalid action request: {}", err)),
+        };
+        let identity = match self.authorize(request, &Scope::Act(action.agent_id)) {
+            Ok(identity) => identity,
+            Err(err) => return err,
+        };
+        let next_tick = self.vm.world().tick() + 1;
+        match self
+            .mempool
+            .submit(&identity, action, next_tick, Instant::now())
+        {
+            Ok(()) => ApiResponse {
+                status: 202,
+                body: json!({ "queued_for_tick": next_tick, "pending": self.mempool.pending().len() }),
+            },
+            Err(err) => ApiResponse::error(429, err.to_string()),
+        }
+    }
+
+    fn tick(&mut self, request: &ApiRequest) -> ApiResponse {
+        if self.read_only {
+            return ApiResponse::error(403, "the data directory is read-only");
+        }
+        if let Err(err) = self.authorize(request, &Scope::Admin) {
+            return err;
+        }
+        let body: TickBody = if request.body.trim().is_empty() {
+            TickBody::default()
+        } else {
+            match serde_json::from_str(request.body) {
+                Ok(body) => body,
+                Err(err) => return ApiResponse::error(400, format!("invalid tick body: {}", err)),
+            }
+        };
+        let ticks = body.ticks.unwrap_or(1).clamp(1, MAX_TICKS_PER_REQUEST);
+
+        let mut results = Vec::new();
+        for _ in 0..ticks {
+            let requests = self.mempool.drain();
+            let result = self.vm.step(&requests);
+            if let Some(hook) = self.on_tick.as_mut() {
+                hook(&requests, &result, &self.vm);
+            }
+            results.push(tick_json(&result));
+        }
+        ApiResponse::ok(json!({ "tick": self.vm.world().tick(), "results": results }))
+    }
+}
+
+fn tick_json(result: &TickResult) -> Value {
+    let rejections: Vec<Value> = result
+        .rejections
+        .iter()
+        .map(|r| json!({ "request": r.request, "error": r.error.to_string() }))
+        .collect();
+    json!({
+        "tick": result.tick,
+        "events": result.events,
+        "rejections": rejections,
+    })
+}
+
+fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+fn events_since(query: &str) -> ApiResponse {
+    let since = match query_param(query, "since_tick").map(str::parse::<u64>) {
+        None => 0,
+        Some(Ok(tick)) => tick,
+        Some(Err(_)) => return ApiResponse::error(400, "since_tick must be a number"),
+    };
+    match journal::read_journal_from(0) {
+        Ok((entries, _)) => {
+            let entries: Vec<JournalEntry> =
+                entries.into_iter().filter(|e| e.tick >= since).collect();
+            ApiResponse::ok(json!(entries))
+        }
+        Err(err) => ApiResponse::error(500, err.to_string()),
+    }
+}
+
+fn wallets() -> ApiResponse {
+    match WalletStore::load() {
+        Ok(store) => {
+            let mut wallets: Vec<_> = store.wallets.into_values().collect();
+            wallets.sort_by(|a, b| a.address.cmp(&b.address));
+            ApiResponse::ok(json!(wallets))
+        }
+        Err(err) => ApiResponse::error(500, err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_serves_world_and_ticks_queued_actions() {
+        use crate::modules::auth::TokenStore;
+        use crate::modules::mempool::QuotaLimits;
+        use serde_json::json;
+
+        let mut vm = Vm::with_seed(3);
+        let ada = vm.spawn_agent("Ada", 5, Position::origin());
+        let limits = QuotaLimits {
+            per_tick: Some(1),
+            per_minute: None,
+        };
+        let mut api = Api::new(vm, limits, TokenStore::default());
+        let mut call = |method, url: &str, body: &str| {
+            api.handle(&ApiRequest {
+                method,
+                url,
+                token: None,
+                peer: "127.0.0.1",
+                body,
+            })
+        };
+
+        let agents = call(Method::Get, "/agents", "");
+        assert_eq!(agents.status, 200);
+        assert_eq!(agents.body[0]["name"], "Ada");
+        assert_eq!(
+            call(Method::Get, &format!("/agents/{}", ada), "").status,
+            200
+        );
+        assert_eq!(call(Method::Get, "/agents/99", "").status, 404);
+        assert_eq!(call(Method::Get, "/agents/x", "").status, 400);
+        assert_eq!(call(Method::Post, "/snapshot", "").status, 405);
+        assert_eq!(call(Method::Get, "/nope", "").status, 404);
+        let spawned = call(Method::Post, "/agents", r#"{"name":"Bob","qi":2}"#);
+        assert_eq!(
+            (spawned.status, &spawned.body["agent_id"]),
+            (201, &json!(ada + 1))
+        );
+
+        let scan = json!({ "agent_id": ada, "tick": 1, "action": "Scan" }).to_string();
+        assert_eq!(call(Method::Post, "/actions", &scan).status, 202);
+        assert_eq!(call(Method::Post, "/actions", &scan).status, 429);
+
+        let ticked = call(Method::Post, "/tick", "");
+        assert_eq!(ticked.status, 200);
+        assert_eq!(ticked.body["tick"], 1);
+        let events = ticked.body["results"][0]["events"].as_array().unwrap();
+        assert!(events.iter().any(|e| e.get("ScanReport").is_some()));
+        assert_eq!(
+            call(Method::Post, "/tick", r#"{"ticks":3}"#).body["tick"],
+            4
+        );
+    }
+}
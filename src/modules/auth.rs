@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use chrono::Utc;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::modules::store;
+use crate::modules::vm::AgentId;
+
+const TOKENS_DOC: &str = "tokens.json";
+/// Prefix of every token secret, so leaked tokens are easy to recognize.
+const SECRET_PREFIX: &str = "hmu_";
+
+/// What a server API token may do. Written as `observe`, `act:<agent id>` or `admin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Scope {
+    /// Read snapshots, agents and events.
+    Observe,
+    /// Observe, and submit actions on behalf of one agent.
+    Act(AgentId),
+    /// Everything, including advancing ticks and changing world state.
+    Admin,
+}
+
+impl Scope {
+    /// Whether holding `self` grants `needed`.
+    pub fn grants(&self, needed: &Scope) -> bool {
+        match (self, needed) {
+            (Scope::Admin, _) => true,
+            (Scope::Act(_), Scope::Observe) => true,
+            (Scope::Act(own), Scope::Act(agent)) => own == agent,
+            (Scope::Observe, Scope::Observe) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Observe => write!(f, "observe"),
+            Scope::Act(agent) => write!(f, "act:{}", agent),
+            Scope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match s.as_str() {
+            "observe" => Ok(Scope::Observe),
+            "admin" => Ok(Scope::Admin),
+            _ => {
+                let agent = s.strip_prefix("act:").ok_or_else(|| {
+                    format!("unknown scope '{}', use observe|act:<agent>|admin", s)
+                })?;
+                agent
+                    .parse()
+                    .map(Scope::Act)
+                    .map_err(|_| format!("agent id in scope '{}' must be a number", s))
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Scope> for String {
+    fn from(scope: Scope) -> Self {
+        scope.to_string()
+    }
+}
+
+/// A token as stored in `.harimu/tokens.json`. Only the SHA-256 of the secret is kept; the
+/// secret itself is shown once, when the token is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub id: String,
+    pub secret_hash: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub created_at: String,
+}
+
+impl AccessToken {
+    pub fn grants(&self, needed: &Scope) -> bool {
+        self.scopes.iter().any(|scope| scope.grants(needed))
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TokenStore {
+    pub tokens: BTreeMap<String, AccessToken>,
+}
+
+impl TokenStore {
+    pub fn load() -> io::Result<Self> {
+        store::load_document(TOKENS_DOC, "token store")
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        store::save_document(TOKENS_DOC, self)
+    }
+
+    /// Add a token with `scopes`; returns it with its secret, which is not stored.
+    pub fn issue(&mut self, scopes: Vec<Scope>, label: Option<String>) -> (AccessToken, String) {
+        let mut bytes = [0u8; 24];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = format!("{}{}", SECRET_PREFIX, hex::encode(bytes));
+        let hash = hash_secret(&secret);
+        let token = AccessToken {
+            id: hash[..12].to_string(),
+            secret_hash: hash,
+            scopes,
+            label,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        self.tokens.insert(token.id.clone(), token.clone());
+        (token, secret)
+    }
+
+    pub fn revoke(&mut self, id: &str) -> Option<AccessToken> {
+        self.tokens.remove(id)
+    }
+
+    /// The token whose secret is `secret`, e.g. from an `Authorization: Bearer` header.
+    pub fn authenticate(&self, secret: &str) -> Option<&AccessToken> {
+        let hash = hash_secret(secret.trim());
+        self.tokens.values().find(|token| token.secret_hash == hash)
+    }
+
+    /// Authenticate `secret` and check it grants `needed`.
+    pub fn authorize(&self, secret: &str, needed: &Scope) -> Result<&AccessToken, String> {
+        let token = self
+            .authenticate(secret)
+            .ok_or_else(|| "unknown or revoked token".to_string())?;
+        if token.grants(needed) {
+            Ok(token)
+        } else {
+            Err(format!("token {} lacks the {} scope", token.id, needed))
+        }
+    }
+}
+
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_scopes_gate_observe_act_and_admin() {
+        let mut store = TokenStore::default();
+        let (_, spectator) = store.issue(vec![Scope::Observe], None);
+        let (_, player) = store.issue(vec!["act:3".parse().unwrap()], Some("player".into()));
+        let (admin_token, admin) = store.issue(vec![Scope::Admin], None);
+
+        assert!(store.authorize(&spectator, &Scope::Observe).is_ok());
+        assert!(store.authorize(&spectator, &Scope::Act(3)).is_err());
+        assert!(store.authorize(&player, &Scope::Observe).is_ok());
+        assert!(store.authorize(&player, &Scope::Act(3)).is_ok());
+        assert!(store.authorize(&player, &Scope::Act(4)).is_err());
+        assert!(store.authorize(&player, &Scope::Admin).is_err());
+        assert!(store.authorize(&admin, &Scope::Act(9)).is_ok());
+
+        store.revoke(&admin_token.id);
+        assert!(store.authorize(&admin, &Scope::Observe).is_err());
+        assert!("act:x".parse::<Scope>().is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::Utc;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::modules::paths;
+use crate::modules::view::EPOCH_TICKS;
+
+const ARCHIVE_PREFIX: &str = "harimu-";
+const ARCHIVE_EXTENSION: &str = ".tar.gz";
+/// Per-process files that must not be restored into another run.
+const SKIPPED_FILES: [&str; 2] = ["runtime.pid", "harimu.lock"];
+/// Label of backups taken by the running loop; only these are rotated.
+pub const AUTO_BACKUP_LABEL: &str = "auto";
+
+/// One archive in the backups directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// File name, e.g. `harimu-20250101T120000Z-auto.tar.gz`; `restore_backup` accepts it.
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub created: Option<SystemTime>,
+}
+
+impl BackupInfo {
+    fn from_path(path: PathBuf) -> io::Result<Self> {
+        let meta = fs::metadata(&path)?;
+        Ok(Self {
+            name: file_name(&path),
+            size: meta.len(),
+            created: meta.modified().ok(),
+            path,
+        })
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        let stem = self.name.strip_suffix(ARCHIVE_EXTENSION)?;
+        let stem = stem.strip_prefix(ARCHIVE_PREFIX)?;
+        stem.split_once('-').map(|(_, label)| label)
+    }
+}
+
+/// Automatic backups from the running loop: one every `every_epochs` epochs, keeping the
+/// newest `keep` of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupSchedule {
+    pub every_epochs: u64,
+    pub keep: usize,
+}
+
+impl BackupSchedule {
+    /// Whether a backup is due once `tick` has completed.
+    pub fn is_due(&self, tick: u64) -> bool {
+        let period = self.every_epochs.saturating_mul(EPOCH_TICKS);
+        period > 0 && tick > 0 && tick.is_multiple_of(period)
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Where archives are kept: next to the data directory (e.g. `.harimu-backups`), so a
+/// restore never overwrites them.
+pub fn backups_dir() -> PathBuf {
+    let data = paths::data_dir();
+    let name = format!("{}-backups", file_name(&data));
+    data.with_file_name(name)
+}
+
+/// Archive the whole data directory (except the pid file) as a timestamped `.tar.gz`.
+pub fn create_backup(label: Option<&str>) -> io::Result<BackupInfo> {
+    let data = paths::data_dir();
+    if !data.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no data directory at {} to back up", data.display()),
+        ));
+    }
+    let dir = backups_dir();
+    fs::create_dir_all(&dir)?;
+
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let suffix = label.map(|label| format!("-{}", label)).unwrap_or_default();
+    let name = |n: u32| match n {
+        0 => format!("{}{}{}{}", ARCHIVE_PREFIX, stamp, suffix, ARCHIVE_EXTENSION),
+        n => format!(
+            "{}{}_{}{}{}",
+            ARCHIVE_PREFIX, stamp, n, suffix, ARCHIVE_EXTENSION
+        ),
+    };
+    let mut n = 0;
+    while dir.join(name(n)).exists() {
+        n += 1;
+    }
+    let path = dir.join(name(n));
+
+    // Write under a temporary name so a crash never leaves a truncated archive behind.
+    let partial = path.with_extension("partial");
+    let encoder = GzEncoder::new(File::create(&partial)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    append_dir(&mut archive, &data, Path::new(""))?;
+    archive.into_inner()?.finish()?;
+    fs::rename(&partial, &path)?;
+    BackupInfo::from_path(path)
+}
+
+fn append_dir<W: io::Write>(
+    archive: &mut tar::Builder<W>,
+    dir: &Path,
+    prefix: &Path,
+) -> io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.flatten().map(|e| e.path()).collect();
+    entries.sort();
+    for path in entries {
+        let name = prefix.join(file_name(&path));
+        if path.is_dir() {
+            append_dir(archive, &path, &name)?;
+        } else if path.is_file() && !SKIPPED_FILES.iter().any(|skip| name == Path::new(skip)) {
+            archive.append_path_with_name(&path, &name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Every archive in the backups directory, oldest first.
+pub fn list_backups() -> io::Result<Vec<BackupInfo>> {
+    let entries = match fs::read_dir(backups_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut backups = Vec::new();
+    for entry in entries.flatten() {
+        let name = file_name(&entry.path());
+        if name.starts_with(ARCHIVE_PREFIX) && name.ends_with(ARCHIVE_EXTENSION) {
+            backups.push(BackupInfo::from_path(entry.path())?);
+        }
+    }
+    // Names start with a UTC timestamp; the write time orders backups taken within a second.
+    backups.sort_by(|a, b| (a.created, &a.name).cmp(&(b.created, &b.name)));
+    Ok(backups)
+}
+
+/// Find a backup by file name, path, or `latest`.
+pub fn find_backup(name: &str) -> io::Result<BackupInfo> {
+    let backups = list_backups()?;
+    let found = if name == "latest" {
+        backups.last().cloned()
+    } else {
+        backups
+            .iter()
+            .find(|b| b.name == name || b.path == Path::new(name))
+            .cloned()
+            .or_else(|| {
+                let path = PathBuf::from(name);
+                path.is_file()
+                    .then_some(path)
+                    .and_then(|path| BackupInfo::from_path(path).ok())
+            })
+    };
+    found.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no backup named {} in {}", name, backups_dir().display()),
+        )
+    })
+}
+
+/// Replace the data directory with the contents of `backup`. The archive is unpacked beside
+/// the data directory first and swapped in only once complete.
+pub fn restore_backup(backup: &BackupInfo) -> io::Result<()> {
+    let data = paths::data_dir();
+    let staging = data.with_file_name(format!("{}.restoring", file_name(&data)));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    let unpacked = tar::Archive::new(GzDecoder::new(File::open(&backup.path)?)).unpack(&staging);
+    if let Err(err) = unpacked {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(err);
+    }
+
+    let previous = data.with_file_name(format!("{}.replaced", file_name(&data)));
+    if previous.exists() {
+        fs::remove_dir_all(&previous)?;
+    }
+    if data.exists() {
+        fs::rename(&data, &previous)?;
+    }
+    fs::rename(&staging, &data)?;
+    if previous.exists() {
+        fs::remove_dir_all(&previous)?;
+    }
+    Ok(())
+}
+
+/// Delete all but the newest `keep` backups carrying `label`; returns how many were removed.
+pub fn prune_backups(label: &str, keep: usize) -> io::Result<usize> {
+    let labeled: Vec<BackupInfo> = list_backups()?
+        .into_iter()
+        .filter(|b| b.label() == Some(label))
+        .collect();
+    let excess = labeled.len().saturating_sub(keep);
+    for backup in &labeled[..excess] {
+        fs::remove_file(&backup.path)?;
+    }
+    Ok(excess)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backup_schedule_fires_on_epoch_boundaries() {
+        use crate::modules::view::EPOCH_TICKS;
+
+        let schedule = BackupSchedule {
+            every_epochs: 2,
+            keep: 3,
+        };
+        let due: Vec<u64> = (0..=5 * EPOCH_TICKS)
+            .filter(|tick| schedule.is_due(*tick))
+            .collect();
+        assert_eq!(due, vec![2 * EPOCH_TICKS, 4 * EPOCH_TICKS]);
+
+        let never = BackupSchedule {
+            every_epochs: 0,
+            keep: 3,
+        };
+        assert!(!never.is_due(EPOCH_TICKS));
+    }
+}
@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::modules::agent::{BrainMode, GEMINI_HOST, LlmProvider};
+
+/// `--llm-host` when no layer sets one (Google's host stands in for `--llm-provider gemini`).
+pub const DEFAULT_LLM_HOST: &str = "https://api.openai.com";
+pub const DEFAULT_LLM_MODEL: &str = "gpt-5-nano";
+pub const DEFAULT_LLM_TIMEOUT_MS: u64 = 15_000;
+/// Ticks per second when neither a tick rate nor a delay is set.
+pub const DEFAULT_TICK_RATE: f64 = 1.0;
+/// An LLM decision is tried this many times before the agent falls back to the loop brain.
+pub const LLM_ATTEMPTS: u64 = 3;
+
+/// `harimu start` settings as one layer gives them: the `[start]` table of
+/// `.harimu/config.toml`, the `HARIMU_*` environment variables, or the command-line flags.
+/// Unset fields fall through to the layer below.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StartSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brain: Option<BrainMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llm_provider: Option<LlmProvider>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llm_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llm_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llm_timeout_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tick_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+}
+
+impl StartSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The layer set by `HARIMU_BRAIN`, `HARIMU_LLM_PROVIDER`, `HARIMU_LLM_HOST`,
+    /// `HARIMU_LLM_MODEL`, `HARIMU_LLM_TIMEOUT_MS`, `HARIMU_TICK_RATE` and `HARIMU_DELAY_MS`.
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Like `from_env`, reading variables through `var`.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let get = |name: &str| var(name).filter(|v| !v.trim().is_empty());
+        let parse = |name: &str| -> Result<Option<f64>, String> {
+            get(name)
+                .map(|v| {
+                    v.trim()
+                        .parse::<f64>()
+                        .map_err(|_| format!("{} must be a number, got '{}'", name, v))
+                })
+                .transpose()
+        };
+        let parse_ms = |name: &str| -> Result<Option<u64>, String> {
+            get(name)
+                .map(|v| {
+                    v.trim()
+                        .parse::<u64>()
+                        .map_err(|_| format!("{} must be a whole number, got '{}'", name, v))
+                })
+                .transpose()
+        };
+        let brain = get("HARIMU_BRAIN")
+            .map(|v| {
+                BrainMode::from_str(v.trim(), true)
+                    .map_err(|_| format!("HARIMU_BRAIN must be loop or llm, got '{}'", v))
+            })
+            .transpose()?;
+        let llm_provider = get("HARIMU_LLM_PROVIDER")
+            .map(|v| {
+                LlmProvider::from_str(v.trim(), true).map_err(|_| {
+                    format!(
+                        "HARIMU_LLM_PROVIDER must be ollama, openai, gemini or llama, got '{}'",
+                        v
+                    )
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            brain,
+            llm_provider,
+            llm_host: get("HARIMU_LLM_HOST"),
+            llm_model: get("HARIMU_LLM_MODEL"),
+            llm_timeout_ms: parse_ms("HARIMU_LLM_TIMEOUT_MS")?,
+            tick_rate: parse("HARIMU_TICK_RATE")?,
+            delay_ms: parse_ms("HARIMU_DELAY_MS")?,
+        })
+    }
+}
+
+/// Which layer a setting came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    Default,
+    File,
+    Env,
+    Flag,
+}
+
+impl ConfigSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConfigSource::Default => "default",
+            ConfigSource::File => "config.toml",
+            ConfigSource::Env => "env",
+            ConfigSource::Flag => "flag",
+        }
+    }
+}
+
+/// A resolved setting and the layer it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: ConfigSource,
+}
+
+/// How the run is paced: a tick rate or a delay between ticks, never both.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pacing {
+    TickRate(f64),
+    DelayMs(u64),
+}
+
+/// The settings a `harimu start` runs with, after merging defaults, `.harimu/config.toml`,
+/// environment variables and flags (later layers win). Saved with the run in `runs.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    pub brain: Setting<BrainMode>,
+    pub llm_provider: Setting<LlmProvider>,
+    pub llm_host: Setting<String>,
+    pub llm_model: Setting<String>,
+    pub llm_timeout_ms: Setting<u64>,
+    pub pacing: Setting<Pacing>,
+}
+
+impl EffectiveConfig {
+    /// Merge the layers, lowest precedence first, and reject contradictory or impossible
+    /// settings: a layer giving both a tick rate and a delay, a non-positive tick rate, a zero
+    /// LLM timeout, or the llama provider in a build without it.
+    pub fn resolve(
+        file: &StartSettings,
+        env: &StartSettings,
+        flags: &StartSettings,
+    ) -> Result<Self, String> {
+        let layers = [
+            (ConfigSource::Flag, flags),
+            (ConfigSource::Env, env),
+            (ConfigSource::File, file),
+        ];
+        fn pick<T: Clone>(
+            layers: &[(ConfigSource, &StartSettings); 3],
+            field: impl Fn(&StartSettings) -> Option<T>,
+            default: T,
+        ) -> Setting<T> {
+            layers
+                .iter()
+                .find_map(|(source, layer)| {
+                    field(layer).map(|value| Setting {
+                        value,
+                        source: *source,
+                    })
+                })
+                .unwrap_or(Setting {
+                    value: default,
+                    source: ConfigSource::Default,
+                })
+        }
+
+        let brain = pick(&layers, |l| l.brain, BrainMode::Llm);
+        let llm_provider = pick(&layers, |l| l.llm_provider, LlmProvider::Openai);
+        let mut llm_host = pick(&layers, |l| l.llm_host.clone(), DEFAULT_LLM_HOST.into());
+        // The default host is OpenAI's, which does not serve Gemini models.
+        if llm_host.source == ConfigSource::Default && llm_provider.value == LlmProvider::Gemini {
+            llm_host.value = GEMINI_HOST.into();
+        }
+        let llm_model = pick(&layers, |l| l.llm_model.clone(), DEFAULT_LLM_MODEL.into());
+        let llm_timeout_ms = pick(&layers, |l| l.llm_timeout_ms, DEFAULT_LLM_TIMEOUT_MS);
+
+        // The highest layer that paces the run decides; one layer may not say it twice.
+        let mut pacing = Setting {
+            value: Pacing::TickRate(DEFAULT_TICK_RATE),
+            source: ConfigSource::Default,
+        };
+        for (source, layer) in layers {
+            let value = match (layer.tick_rate, layer.delay_ms) {
+                (Some(rate), Some(delay)) => {
+                    return Err(format!(
+                        "tick_rate {} and delay_ms {} are both set ({}); pick one",
+                        rate,
+                        delay,
+                        source.as_str()
+                    ));
+                }
+                (Some(rate), None) => Pacing::TickRate(rate),
+                // A zero delay has always meant the default tick rate.
+                (None, Some(0)) => Pacing::TickRate(DEFAULT_TICK_RATE),
+                (None, Some(delay)) => Pacing::DelayMs(delay),
+                (None, None) => continue,
+            };
+            pacing = Setting { value, source };
+            break;
+        }
+        if let Pacing::TickRate(rate) = pacing.value
+            && !(rate.is_finite() && rate > 0.0)
+        {
+            return Err(format!(
+                "tick_rate must be greater than 0, got {} ({})",
+                rate,
+                pacing.source.as_str()
+            ));
+        }
+
+        let llm = brain.value == BrainMode::Llm;
+        if llm && llm_timeout_ms.value == 0 {
+            return Err(format!(
+                "llm_timeout_ms must be greater than 0 with the llm brain ({})",
+                llm_timeout_ms.source.as_str()
+            ));
+        }
+        if llm && llm_provider.value == LlmProvider::Llama && !cfg!(feature = "llama") {
+            return Err(format!(
+                "llm_provider llama ({}) needs a build with `--features llama`",
+                llm_provider.source.as_str()
+            ));
+        }
+
+        Ok(Self {
+            brain,
+            llm_provider,
+            llm_host,
+            llm_model,
+            llm_timeout_ms,
+            pacing,
+        })
+    }
+
+    /// Pause between ticks.
+    pub fn tick_interval(&self) -> Duration {
+        match self.pacing.value {
+            Pacing::TickRate(rate) => Duration::from_secs_f64(1.0 / rate),
+            Pacing::DelayMs(ms) => Duration::from_millis(ms),
+        }
+    }
+
+    /// Settings that work but probably not as intended: with the llm brain, a slow LLM can
+    /// hold a tick for up to `LLM_ATTEMPTS` timeouts, which overruns the tick interval unless
+    /// `hold_rate` lets agents reuse their last plan.
+    pub fn warnings(&self, hold_rate: bool) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let interval_ms = self.tick_interval().as_millis() as u64;
+        let worst_ms = self.llm_timeout_ms.value.saturating_mul(LLM_ATTEMPTS);
+        if self.brain.value == BrainMode::Llm && !hold_rate && worst_ms > interval_ms {
+            warnings.push(format!(
+                "an LLM decision may take up to {} ms ({} attempts x llm_timeout_ms {}), longer than the {} ms tick interval; ticks will overrun (consider --hold-rate)",
+                worst_ms, LLM_ATTEMPTS, self.llm_timeout_ms.value, interval_ms
+            ));
+        }
+        warnings
+    }
+
+    /// One `name = value (source)` line per setting.
+    pub fn lines(&self) -> Vec<String> {
+        fn line(name: &str, value: impl std::fmt::Display, source: ConfigSource) -> String {
+            format!("{} = {} ({})", name, value, source.as_str())
+        }
+        let brain = match self.brain.value {
+            BrainMode::Loop => "loop",
+            BrainMode::Llm => "llm",
+        };
+        let provider = self
+            .llm_provider
+            .value
+            .to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default();
+        let pacing = match self.pacing.value {
+            Pacing::TickRate(rate) => format!("tick_rate = {}", rate),
+            Pacing::DelayMs(ms) => format!("delay_ms = {}", ms),
+        };
+        vec![
+            line("brain", brain, self.brain.source),
+            line("llm_provider", provider, self.llm_provider.source),
+            line("llm_host", &self.llm_host.value, self.llm_host.source),
+            line("llm_model", &self.llm_model.value, self.llm_model.source),
+            line(
+                "llm_timeout_ms",
+                self.llm_timeout_ms.value,
+                self.llm_timeout_ms.source,
+            ),
+            format!("{} ({})", pacing, self.pacing.source.as_str()),
+        ]
+    }
+}
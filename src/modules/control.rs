@@ -0,0 +1,605 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::modules::agent::{ActionArg, BrainMode};
+use crate::modules::paths;
+use crate::modules::vm::{AdminAction, AgentId};
+
+/// How long a client waits for the run to answer, and the run for a client's request.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Random bytes in the secret a client must present before its request.
+const SECRET_BYTES: usize = 32;
+/// How often a waiting loop notices a signal; signal handlers cannot wake the condvar.
+const SIGNAL_POLL: Duration = Duration::from_millis(100);
+/// Exit status when a second signal arrives before the run has stopped (128 + SIGINT).
+const FORCED_EXIT: i32 = 130;
+
+/// A message `harimu stop`, `status`, `pause`, `resume`, `inject`, `brain` and `admin` send to a
+/// running `start`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Finish the current tick, then end the run.
+    Stop,
+    /// Stop stepping after the current tick until resumed.
+    Pause,
+    Resume,
+    Status,
+    /// Take `action` (in `--action` syntax) for `agent_id` on the next tick, in place of
+    /// whatever its brain would choose.
+    Inject {
+        agent_id: AgentId,
+        action: String,
+    },
+    /// Switch `agent_id` to another brain, LLM model, or both, from the next tick on.
+    SetBrain(BrainSwap),
+    /// Apply an operator's intervention to `agent_id` before the next tick.
+    Admin {
+        agent_id: AgentId,
+        action: AdminAction,
+    },
+}
+
+/// A brain change for one agent; `None` keeps that part of its current brain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrainSwap {
+    pub agent_id: AgentId,
+    pub mode: Option<BrainMode>,
+    pub model: Option<String>,
+}
+
+/// What a running `start` reports about itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub run_id: u64,
+    /// Last completed tick.
+    pub tick: u64,
+    pub paused: bool,
+    pub stopping: bool,
+    /// Injected actions waiting for the next tick.
+    pub pending: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlReply {
+    pub ok: bool,
+    pub message: String,
+    pub status: DaemonStatus,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    status: DaemonStatus,
+    injected: Vec<(AgentId, ActionArg)>,
+    brain_swaps: Vec<BrainSwap>,
+    admin: Vec<(AgentId, AdminAction)>,
+}
+
+type Control = Arc<(Mutex<Shared>, Condvar)>;
+
+fn lock(control: &Control) -> MutexGuard<'_, Shared> {
+    control
+        .0
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The control endpoint of a running `start`. Each connection is answered on a thread of its
+/// own, so status and stop work even while a tick is busy or another client stalls; the loop
+/// picks up their effect between ticks. Clients must first send the secret the run writes to
+/// an owner-only key file beside the socket. Socket and key are removed when this is dropped.
+pub struct ControlServer {
+    control: Control,
+    /// Set by SIGINT/SIGTERM once `stop_on_signals` is called; a stop request like any other.
+    interrupted: Arc<AtomicBool>,
+    path: PathBuf,
+}
+
+impl ControlServer {
+    /// Listen at `control_socket_path()`. Fails if another run is already listening there; a
+    /// socket left behind by a run that died is replaced.
+    pub fn bind() -> io::Result<Self> {
+        Self::bind_at(control_socket_path())
+    }
+
+    /// Listen at `path` instead of the data directory's socket.
+    pub fn bind_at(path: PathBuf) -> io::Result<Self> {
+        if path.exists() {
+            if transport::connect(&path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("another run is already listening on {}", path.display()),
+                ));
+            }
+            std::fs::remove_file(&path)?;
+        }
+        let secret = hex::encode(rand::random::<[u8; SECRET_BYTES]>());
+        write_secret(&control_key_path(&path), &secret)?;
+        let connections = transport::listen(&path)?;
+        let secret = Arc::new(secret);
+        let control: Control = Arc::new((
+            Mutex::new(Shared {
+                status: DaemonStatus {
+                    pid: std::process::id(),
+                    ..DaemonStatus::default()
+                },
+                injected: Vec::new(),
+                brain_swaps: Vec::new(),
+                admin: Vec::new(),
+            }),
+            Condvar::new(),
+        ));
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let serving = Arc::clone(&control);
+        let serving_interrupted = Arc::clone(&interrupted);
+        thread::spawn(move || {
+            for connection in connections {
+                match connection {
+                    Ok(connection) => {
+                        if serving_interrupted.load(Ordering::SeqCst) {
+                            lock(&serving).status.stopping = true;
+                        }
+                        let control = Arc::clone(&serving);
+                        let secret = Arc::clone(&secret);
+                        thread::spawn(move || {
+                            if let Err(err) = serve_connection(&control, &secret, connection) {
+                                warn!("control connection: {}", err);
+                            }
+                        });
+                    }
+                    Err(err) => warn!("control socket: {}", err),
+                }
+            }
+        });
+        Ok(Self {
+            control,
+            interrupted,
+            path,
+        })
+    }
+
+    /// Treat SIGINT and SIGTERM as a stop request: the loop finishes the tick in progress and
+    /// shuts down cleanly. A second signal before then exits at once.
+    pub fn stop_on_signals(&self) -> io::Result<()> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+        use signal_hook::flag;
+
+        for signal in [SIGINT, SIGTERM] {
+            // Registered first so it only fires once the flag below is already set.
+            flag::register_conditional_shutdown(
+                signal,
+                FORCED_EXIT,
+                Arc::clone(&self.interrupted),
+            )?;
+            flag::register(signal, Arc::clone(&self.interrupted))?;
+        }
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record which run is listening, once it has an id.
+    pub fn begin_run(&self, run_id: u64, tick: u64) {
+        let mut shared = lock(&self.control);
+        shared.status.run_id = run_id;
+        shared.status.tick = tick;
+    }
+
+    /// Record the last completed tick for status replies.
+    pub fn set_tick(&self, tick: u64) {
+        lock(&self.control).status.tick = tick;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        lock(&self.control).status.paused
+    }
+
+    pub fn stop_requested(&self) -> bool {
+        self.was_interrupted() || lock(&self.control).status.stopping
+    }
+
+    /// Whether the stop came from SIGINT/SIGTERM rather than `harimu stop`.
+    pub fn was_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+
+    /// Block while the run is paused; returns once it is resumed or asked to stop.
+    pub fn wait_while_paused(&self) {
+        let (_, wake) = &*self.control;
+        let mut shared = lock(&self.control);
+        while shared.status.paused && !shared.status.stopping && !self.was_interrupted() {
+            shared = wake
+                .wait_timeout(shared, SIGNAL_POLL)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+
+    /// Sleep for `duration` between ticks, returning early if the run is asked to stop.
+    pub fn wait_for_next_tick(&self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        let (_, wake) = &*self.control;
+        let mut shared = lock(&self.control);
+        while !shared.status.stopping && !self.was_interrupted() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            shared = wake
+                .wait_timeout(shared, (deadline - now).min(SIGNAL_POLL))
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+
+    /// Injected actions for the coming tick, oldest first; a later one for the same agent wins.
+    pub fn take_injected(&self) -> Vec<(AgentId, ActionArg)> {
+        let mut shared = lock(&self.control);
+        shared.status.pending = 0;
+        std::mem::take(&mut shared.injected)
+    }
+
+    /// Brain changes requested since the last call, oldest first.
+    pub fn take_brain_swaps(&self) -> Vec<BrainSwap> {
+        std::mem::take(&mut lock(&self.control).brain_swaps)
+    }
+
+    /// Operator interventions requested since the last call, oldest first.
+    pub fn take_admin_actions(&self) -> Vec<(AgentId, AdminAction)> {
+        std::mem::take(&mut lock(&self.control).admin)
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+        let _ = std::fs::remove_file(control_key_path(&self.path));
+    }
+}
+
+/// Write the run's secret readable by its owner only; a stale key is replaced, not reused.
+fn write_secret(path: &Path, secret: &str) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(secret.as_bytes())?;
+    file.sync_all()
+}
+
+fn apply(control: &Control, request: ControlRequest) -> ControlReply {
+    let (_, wake) = &**control;
+    let mut shared = lock(control);
+    let (ok, message) = match request {
+        ControlRequest::Stop => {
+            shared.status.stopping = true;
+            (
+                true,
+                format!(
+                    "stopping after the tick in progress (last completed: {})",
+                    shared.status.tick
+                ),
+            )
+        }
+        ControlRequest::Pause if shared.status.paused => (true, "already paused".into()),
+        ControlRequest::Pause => {
+            shared.status.paused = true;
+            (
+                true,
+                format!(
+                    "pausing after the tick in progress (last completed: {})",
+                    shared.status.tick
+                ),
+            )
+        }
+        ControlRequest::Resume if !shared.status.paused => (true, "not paused".into()),
+        ControlRequest::Resume => {
+            shared.status.paused = false;
+            (true, format!("resuming after tick {}", shared.status.tick))
+        }
+        ControlRequest::Status => (true, "running".into()),
+        ControlRequest::Inject { agent_id, action } => match action.parse::<ActionArg>() {
+            Ok(action) => {
+                shared.injected.push((agent_id, action));
+                shared.status.pending = shared.injected.len();
+                (
+                    true,
+                    format!("agent {} takes it on the next tick", agent_id),
+                )
+            }
+            Err(err) => (false, err),
+        },
+        ControlRequest::SetBrain(swap) if swap.mode.is_none() && swap.model.is_none() => (
+            false,
+            "nothing to change: give a brain mode, a model or both".into(),
+        ),
+        ControlRequest::SetBrain(swap) => {
+            let message = format!("agent {} switches brain on the next tick", swap.agent_id);
+            shared.brain_swaps.push(swap);
+            (true, message)
+        }
+        ControlRequest::Admin { agent_id, action } => {
+            shared.admin.push((agent_id, action));
+            (
+                true,
+                format!("applying to agent {} before the next tick", agent_id),
+            )
+        }
+    };
+    wake.notify_all();
+    ControlReply {
+        ok,
+        message,
+        status: shared.status.clone(),
+    }
+}
+
+fn serve_connection(
+    control: &Control,
+    secret: &str,
+    mut connection: Box<dyn Connection>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(&mut connection);
+    let mut presented = String::new();
+    reader.read_line(&mut presented)?;
+    if presented.trim() != secret {
+        let reply = ControlReply {
+            ok: false,
+            message: "control request refused: wrong or missing secret".into(),
+            status: DaemonStatus::default(),
+        };
+        return write_reply(&mut connection, &reply);
+    }
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let reply = match serde_json::from_str::<ControlRequest>(line.trim()) {
+        Ok(request) => apply(control, request),
+        Err(err) => ControlReply {
+            ok: false,
+            message: format!("invalid control request: {}", err),
+            status: lock(control).status.clone(),
+        },
+    };
+    write_reply(&mut connection, &reply)
+}
+
+fn write_reply(connection: &mut Box<dyn Connection>, reply: &ControlReply) -> io::Result<()> {
+    let mut body = serde_json::to_vec(reply)?;
+    body.push(b'\n');
+    connection.write_all(&body)?;
+    connection.flush()
+}
+
+/// Where a running `start` listens for control requests.
+pub fn control_socket_path() -> PathBuf {
+    paths::data_dir().join(transport::FILE_NAME)
+}
+
+/// The secret of the run listening at `socket`, e.g. `control.key` beside `control.sock`.
+pub fn control_key_path(socket: &Path) -> PathBuf {
+    socket.with_extension("key")
+}
+
+/// Send `request` to the run listening in the data directory. `Ok(None)` when no run is
+/// listening.
+pub fn send_control(request: &ControlRequest) -> io::Result<Option<ControlReply>> {
+    send_control_to(&control_socket_path(), request)
+}
+
+/// `send_control` for a run listening at `path`.
+pub fn send_control_to(path: &Path, request: &ControlRequest) -> io::Result<Option<ControlReply>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let secret = match std::fs::read_to_string(control_key_path(path)) {
+        Ok(secret) => secret,
+        // The run exited between the two checks.
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut connection = match transport::connect(path) {
+        Ok(connection) => connection,
+        // A socket left behind by a run that died.
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(err) => return Err(err),
+    };
+    let mut body = format!("{}\n", secret.trim()).into_bytes();
+    serde_json::to_writer(&mut body, request)?;
+    body.push(b'\n');
+    connection.write_all(&body)?;
+    connection.flush()?;
+    let mut line = String::new();
+    BufReader::new(connection).read_line(&mut line)?;
+    serde_json::from_str(line.trim())
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+trait Connection: Read + Write + Send {}
+impl<T: Read + Write + Send> Connection for T {}
+
+type Connections = Box<dyn Iterator<Item = io::Result<Box<dyn Connection>>> + Send>;
+
+#[cfg(unix)]
+mod transport {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    use super::*;
+
+    pub const FILE_NAME: &str = "control.sock";
+
+    pub fn listen(path: &Path) -> io::Result<Connections> {
+        let listener = UnixListener::bind(path)?;
+        Ok(Box::new(std::iter::from_fn(move || {
+            Some(listener.accept().and_then(|(stream, _)| {
+                stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+                stream.set_write_timeout(Some(REPLY_TIMEOUT))?;
+                Ok(Box::new(stream) as Box<dyn Connection>)
+            }))
+        })))
+    }
+
+    pub fn connect(path: &Path) -> io::Result<Box<dyn Connection>> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        stream.set_write_timeout(Some(REPLY_TIMEOUT))?;
+        Ok(Box::new(stream))
+    }
+}
+
+/// Without Unix sockets the run listens on a loopback port and writes its address to the file.
+/// Any local user can reach the port, so the secret in `control.key` is what guards it.
+#[cfg(not(unix))]
+mod transport {
+    use std::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    pub const FILE_NAME: &str = "control.addr";
+
+    pub fn listen(path: &Path) -> io::Result<Connections> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        paths::write_atomic(path, listener.local_addr()?.to_string().as_bytes())?;
+        Ok(Box::new(std::iter::from_fn(move || {
+            Some(listener.accept().and_then(|(stream, _)| {
+                stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+                stream.set_write_timeout(Some(REPLY_TIMEOUT))?;
+                Ok(Box::new(stream) as Box<dyn Connection>)
+            }))
+        })))
+    }
+
+    pub fn connect(path: &Path) -> io::Result<Box<dyn Connection>> {
+        let address = std::fs::read_to_string(path)?;
+        let address = address
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let stream = TcpStream::connect_timeout(&address, REPLY_TIMEOUT)?;
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        stream.set_write_timeout(Some(REPLY_TIMEOUT))?;
+        Ok(Box::new(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_socket_pauses_injects_and_stops_a_run() {
+        let path = std::env::temp_dir().join(format!("harimu-control-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert!(
+            send_control_to(&path, &ControlRequest::Status)
+                .unwrap()
+                .is_none()
+        );
+
+        let server = ControlServer::bind_at(path.clone()).unwrap();
+        server.begin_run(3, 10);
+        assert!(ControlServer::bind_at(path.clone()).is_err());
+        let send = |request| send_control_to(&path, &request).unwrap().unwrap();
+
+        let status = send(ControlRequest::Status).status;
+        assert_eq!((status.run_id, status.tick, status.paused), (3, 10, false));
+        assert!(send(ControlRequest::Pause).ok);
+        assert!(server.is_paused());
+
+        let injected = send(ControlRequest::Inject {
+            agent_id: 1,
+            action: "move:1,0,0".into(),
+        });
+        assert!(injected.ok);
+        assert_eq!(injected.status.pending, 1);
+        assert!(
+            !send(ControlRequest::Inject {
+                agent_id: 1,
+                action: "fly".into(),
+            })
+            .ok
+        );
+        assert!(matches!(
+            server.take_injected().as_slice(),
+            [(
+                1,
+                ActionArg::Move {
+                    dx: 1,
+                    dy: 0,
+                    dz: 0
+                }
+            )]
+        ));
+
+        // A stop wakes a paused run so it can wind down.
+        assert!(send(ControlRequest::Stop).ok);
+        server.wait_while_paused();
+        assert!(server.stop_requested());
+        // ...and cuts short the wait before the next tick.
+        let started = Instant::now();
+        server.wait_for_next_tick(Duration::from_secs(60));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!server.was_interrupted());
+
+        drop(server);
+        assert!(!path.exists());
+        assert!(!control_key_path(&path).exists());
+    }
+
+    #[test]
+    fn control_requests_need_the_secret_and_a_stalled_client_blocks_no_one() {
+        let path = std::env::temp_dir().join(format!("harimu-secret-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = ControlServer::bind_at(path.clone()).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let key = std::fs::metadata(control_key_path(&path)).unwrap();
+            assert_eq!(key.permissions().mode() & 0o777, 0o600);
+        }
+
+        let mut intruder = transport::connect(&path).unwrap();
+        intruder.write_all(b"guess\n{\"type\":\"stop\"}\n").unwrap();
+        let mut line = String::new();
+        BufReader::new(intruder).read_line(&mut line).unwrap();
+        let reply: ControlReply = serde_json::from_str(line.trim()).unwrap();
+        assert!(!reply.ok);
+        assert_eq!(reply.status, DaemonStatus::default());
+        assert!(!server.stop_requested());
+
+        // Connects and never sends a line; later clients are still answered.
+        let _stalled = transport::connect(&path).unwrap();
+        let started = Instant::now();
+        let reply = send_control_to(&path, &ControlRequest::Stop)
+            .unwrap()
+            .unwrap();
+        assert!(reply.ok);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(server.stop_requested());
+    }
+}
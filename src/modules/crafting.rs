@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::inventory::ItemKind;
+use crate::modules::structure::StructureKind;
+use crate::modules::vm::Qi;
+
+fn one() -> Qi {
+    1
+}
+
+/// Turns a bill of items into `amount` of `output`, e.g. a `[recipes.circuit]` table of
+/// `.harimu/config.toml`:
+///
+/// ```toml
+/// [recipes.circuit]
+/// inputs = { transistor = 2, qi = 3 }
+/// output = "circuit"
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recipe {
+    pub inputs: BTreeMap<ItemKind, Qi>,
+    pub output: ItemKind,
+    #[serde(default = "one")]
+    pub amount: Qi,
+}
+
+impl Recipe {
+    /// The inputs as a bill for `Inventory::spend_all`.
+    pub fn bill(&self) -> Vec<(ItemKind, Qi)> {
+        self.inputs.iter().map(|(item, n)| (*item, *n)).collect()
+    }
+
+    fn check(&self, name: &str) -> Result<(), String> {
+        if self.output == ItemKind::Qi {
+            return Err(format!("recipe '{}' cannot make qi", name));
+        }
+        if self.amount == 0 || self.inputs.values().all(|n| *n == 0) {
+            return Err(format!(
+                "recipe '{}' needs inputs and an amount above 0",
+                name
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The recipes agents can craft by name, and the structure kinds whose build cost replaces
+/// the built-in one (so a kind can require crafted items).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecipeBook {
+    #[serde(default)]
+    recipes: BTreeMap<String, Recipe>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    build_costs: BTreeMap<StructureKind, BTreeMap<ItemKind, Qi>>,
+}
+
+impl Default for RecipeBook {
+    /// The built-in recipe: 2 transistors and 3 Qi make a circuit.
+    fn default() -> Self {
+        let mut recipes = BTreeMap::new();
+        recipes.insert(
+            "circuit".to_string(),
+            Recipe {
+                inputs: BTreeMap::from([(ItemKind::Transistor, 2), (ItemKind::Qi, 3)]),
+                output: ItemKind::Circuit,
+                amount: 1,
+            },
+        );
+        Self {
+            recipes,
+            build_costs: BTreeMap::new(),
+        }
+    }
+}
+
+impl RecipeBook {
+    /// Add `recipes` (replacing built-in ones of the same name) and build costs; fails on a
+    /// recipe that makes Qi or nothing.
+    pub fn extend(
+        &mut self,
+        recipes: BTreeMap<String, Recipe>,
+        build_costs: BTreeMap<StructureKind, BTreeMap<ItemKind, Qi>>,
+    ) -> Result<(), String> {
+        for (name, recipe) in &recipes {
+            recipe.check(name)?;
+        }
+        self.recipes.extend(recipes);
+        self.build_costs.extend(build_costs);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Recipe> {
+        self.recipes.get(name.trim())
+    }
+
+    /// Recipes in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Recipe)> {
+        self.recipes.iter().map(|(name, r)| (name.as_str(), r))
+    }
+
+    /// What building `kind` takes from the builder: the configured cost, else the built-in one.
+    pub fn build_cost(&self, kind: StructureKind) -> Vec<(ItemKind, Qi)> {
+        match self.build_costs.get(&kind) {
+            Some(cost) => cost.iter().map(|(item, n)| (*item, *n)).collect(),
+            None => kind.build_cost().to_vec(),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
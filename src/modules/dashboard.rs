@@ -0,0 +1,181 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::modules::journal::{JournalEntry, read_journal_from};
+use crate::modules::state::{self, RuntimeState, Status};
+use crate::modules::view::{WorldSnapshot, load_world_snapshot};
+use crate::modules::vm::{AgentId, Zone};
+
+/// How far back the tick rate is measured.
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// The latest journaled event naming an agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentActivity {
+    pub tick: u64,
+    /// The action for `ActionObserved` events, else the event kind, e.g. `AgentMoved`.
+    pub label: String,
+}
+
+/// What `harimu dashboard` and `harimu top` show, refreshed from the files a running world
+/// writes each tick: the latest snapshot, the runtime state and new lines of the event journal.
+#[derive(Debug)]
+pub struct DashboardFeed {
+    snapshot: Option<WorldSnapshot>,
+    state: Option<RuntimeState>,
+    events: VecDeque<JournalEntry>,
+    last_activity: HashMap<AgentId, AgentActivity>,
+    event_capacity: usize,
+    journal_offset: u64,
+    samples: VecDeque<(Instant, u64)>,
+    error: Option<String>,
+}
+
+impl DashboardFeed {
+    /// A feed keeping the last `event_capacity` journaled events.
+    pub fn new(event_capacity: usize) -> Self {
+        Self {
+            snapshot: None,
+            state: None,
+            events: VecDeque::new(),
+            last_activity: HashMap::new(),
+            event_capacity: event_capacity.max(1),
+            journal_offset: 0,
+            samples: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    /// Re-read the data directory. A file caught mid-write keeps the previous reading and is
+    /// reported by `error` until the next refresh succeeds.
+    pub fn refresh(&mut self) {
+        self.error = self.try_refresh().err().map(|err| err.to_string());
+    }
+
+    fn try_refresh(&mut self) -> io::Result<()> {
+        let state = state::load_state()?;
+        let snapshot = load_world_snapshot()?;
+        let (entries, next) = read_journal_from(self.journal_offset)?;
+        // The journal was replaced (e.g. pruned); pick it up from the start next time.
+        if next < self.journal_offset {
+            self.journal_offset = 0;
+            return Ok(());
+        }
+        self.journal_offset = next;
+        self.apply(Instant::now(), snapshot, state, entries);
+        Ok(())
+    }
+
+    /// Take in one reading of the world, as seen at `now`.
+    pub fn apply(
+        &mut self,
+        now: Instant,
+        snapshot: Option<WorldSnapshot>,
+        state: Option<RuntimeState>,
+        entries: Vec<JournalEntry>,
+    ) {
+        if snapshot.is_some() {
+            self.snapshot = snapshot;
+        }
+        self.state = state;
+        for entry in &entries {
+            let Some(agent_id) = entry.field_u64("agent_id") else {
+                continue;
+            };
+            let label = match entry.kind() {
+                "ActionObserved" => entry.field_str("action").unwrap_or("ActionObserved"),
+                kind => kind,
+            };
+            self.last_activity.insert(
+                agent_id,
+                AgentActivity {
+                    tick: entry.tick,
+                    label: label.to_string(),
+                },
+            );
+        }
+        self.events.extend(entries);
+        while self.events.len() > self.event_capacity {
+            self.events.pop_front();
+        }
+
+        let Some(tick) = self.tick() else {
+            return;
+        };
+        // A restarted or rewound world starts a new measurement.
+        if self.samples.back().is_some_and(|(_, last)| *last > tick) {
+            self.samples.clear();
+        }
+        self.samples.push_back((now, tick));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > RATE_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn snapshot(&self) -> Option<&WorldSnapshot> {
+        self.snapshot.as_ref()
+    }
+
+    pub fn state(&self) -> Option<&RuntimeState> {
+        self.state.as_ref()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// The latest tick seen in the snapshot or the runtime state.
+    pub fn tick(&self) -> Option<u64> {
+        let snapshot = self.snapshot.as_ref().map(|s| s.tick);
+        let state = self.state.as_ref().map(|s| s.last_tick);
+        snapshot.max(state)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state
+            .as_ref()
+            .is_some_and(|state| state.status == Status::Running)
+    }
+
+    /// Recent events, newest first.
+    pub fn recent_events(&self) -> impl Iterator<Item = &JournalEntry> {
+        self.events.iter().rev()
+    }
+
+    /// What the agent was last seen doing in the journal.
+    pub fn last_activity(&self, agent_id: AgentId) -> Option<&AgentActivity> {
+        self.last_activity.get(&agent_id)
+    }
+
+    /// Kept events naming an agent within the last `ticks` ticks, per zone the agent is in now.
+    pub fn zone_activity(&self, ticks: u64) -> HashMap<Zone, u64> {
+        let mut activity = HashMap::new();
+        let (Some(snapshot), Some(now)) = (self.snapshot.as_ref(), self.tick()) else {
+            return activity;
+        };
+        let zones: HashMap<AgentId, Zone> = snapshot
+            .agents
+            .iter()
+            .map(|agent| (agent.id, agent.position.zone()))
+            .collect();
+        for entry in self.events.iter().filter(|entry| entry.tick + ticks > now) {
+            if let Some(zone) = entry.field_u64("agent_id").and_then(|id| zones.get(&id)) {
+                *activity.entry(*zone).or_default() += 1;
+            }
+        }
+        activity
+    }
+
+    /// Ticks per second over the last few seconds of readings, once the world has moved.
+    pub fn tick_rate(&self) -> Option<f64> {
+        let (first_at, first_tick) = self.samples.front()?;
+        let (last_at, last_tick) = self.samples.back()?;
+        let elapsed = last_at.duration_since(*first_at).as_secs_f64();
+        (elapsed > 0.0 && last_tick > first_tick).then(|| (last_tick - first_tick) as f64 / elapsed)
+    }
+}
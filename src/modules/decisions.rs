@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::modules::agent::{LlmDecision, LlmUsage, action_token};
+use crate::modules::paths;
+use crate::modules::vm::AgentId;
+
+/// One planner decision as persisted in the per-run audit trail.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub tick: u64,
+    pub agent_id: AgentId,
+    pub summary: String,
+    pub prompt_hash: String,
+    pub action: String,
+    pub llm_ok: bool,
+    pub latency_ms: u64,
+    pub provider: String,
+    pub model: String,
+    #[serde(default)]
+    pub usage: LlmUsage,
+}
+
+impl DecisionRecord {
+    pub fn from_decision(tick: u64, agent_id: AgentId, decision: &LlmDecision) -> Self {
+        Self {
+            tick,
+            agent_id,
+            summary: decision.summary.clone(),
+            prompt_hash: prompt_hash(&decision.prompt),
+            action: action_token(&decision.action),
+            llm_ok: decision.llm_ok,
+            latency_ms: decision.latency_ms,
+            provider: format!("{:?}", decision.provider).to_lowercase(),
+            model: decision.model.clone(),
+            usage: decision.usage,
+        }
+    }
+}
+
+pub fn prompt_hash(prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(prompt.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn decisions_path() -> PathBuf {
+    paths::data_dir().join("decisions.jsonl")
+}
+
+/// Truncate the decisions store at the start of a run.
+pub fn reset_decisions() -> io::Result<()> {
+    fs::create_dir_all(paths::data_dir())?;
+    fs::write(decisions_path(), b"")
+}
+
+/// Append decisions as JSON lines; the store grows with every tick so it is never rewritten.
+pub fn append_decisions(records: &[DecisionRecord]) -> io::Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    fs::create_dir_all(paths::data_dir())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(decisions_path())?;
+    for record in records {
+        let line = serde_json::to_string(record)?;
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+pub fn load_decisions() -> io::Result<Vec<DecisionRecord>> {
+    let path = decisions_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let data = fs::read_to_string(&path)?;
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "failed to parse decisions store {}; delete it to reset: {}",
+                        path.display(),
+                        e
+                    ),
+                )
+            })
+        })
+        .collect()
+}
+
+/// One agent's LLM spending over the decisions of a run, as `harimu stats llm` shows it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LlmAgentUsage {
+    pub agent_id: AgentId,
+    pub decisions: u64,
+    /// Decisions that asked the LLM at least once.
+    pub calls: u64,
+    /// Calls that fell back to the loop brain because no usable reply came.
+    pub failures: u64,
+    pub usage: LlmUsage,
+    pub total_latency_ms: u64,
+    pub max_latency_ms: u64,
+}
+
+impl LlmAgentUsage {
+    pub fn mean_latency_ms(&self) -> u64 {
+        self.total_latency_ms.checked_div(self.calls).unwrap_or(0)
+    }
+}
+
+/// Sum `records` per agent, ordered by agent id.
+pub fn usage_by_agent(records: &[DecisionRecord]) -> Vec<LlmAgentUsage> {
+    let mut agents: BTreeMap<AgentId, LlmAgentUsage> = BTreeMap::new();
+    for record in records {
+        let entry = agents
+            .entry(record.agent_id)
+            .or_insert_with(|| LlmAgentUsage {
+                agent_id: record.agent_id,
+                ..Default::default()
+            });
+        entry.decisions += 1;
+        if record.usage.requests == 0 {
+            continue;
+        }
+        entry.calls += 1;
+        if !record.llm_ok {
+            entry.failures += 1;
+        }
+        entry.usage.add(record.usage);
+        entry.total_latency_ms += record.latency_ms;
+        entry.max_latency_ms = entry.max_latency_ms.max(record.latency_ms);
+    }
+    agents.into_values().collect()
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::modules::inventory::ItemKind;
+use crate::modules::ore::OreKind;
+use crate::modules::structure::{Structure, StructureKind};
+use crate::modules::vm::{
+    AgentId, EffectContext, Event, HARVEST_RANGE, QI_STRUCTURE_OUTPUT, QI_STRUCTURE_PERIOD,
+    QI_STRUCTURE_UPKEEP, Qi,
+};
+
+/// How far (Chebyshev distance) from a structure a harvested ore node still counts as nearby.
+pub const HARVEST_NEARBY_RANGE: i32 = 4;
+
+/// What a structure does in the world, dispatched by its kind through a `StructureRegistry`.
+/// Every hook defaults to doing nothing and returns the events it caused; `structure` is the
+/// structure as it stood when the hook was called.
+pub trait StructureEffect: fmt::Debug + Send + Sync {
+    /// Once per tick before the agents act, for every structure in id order.
+    fn on_tick(&self, _ctx: &mut EffectContext<'_>, _structure: &Structure) -> Vec<Event> {
+        Vec::new()
+    }
+
+    /// After an agent's move brings it within `HARVEST_RANGE` of the structure.
+    fn on_adjacent_agent(
+        &self,
+        _ctx: &mut EffectContext<'_>,
+        _structure: &Structure,
+        _agent_id: AgentId,
+    ) -> Vec<Event> {
+        Vec::new()
+    }
+
+    /// After an agent harvests an ore node within `HARVEST_NEARBY_RANGE` of the structure.
+    fn on_harvest_nearby(
+        &self,
+        _ctx: &mut EffectContext<'_>,
+        _structure: &Structure,
+        _agent_id: AgentId,
+        _ore: OreKind,
+        _amount: Qi,
+    ) -> Vec<Event> {
+        Vec::new()
+    }
+}
+
+/// The effects of each structure kind, run in the order they were registered.
+#[derive(Clone, Debug)]
+pub struct StructureRegistry {
+    effects: BTreeMap<StructureKind, Vec<Arc<dyn StructureEffect>>>,
+}
+
+impl Default for StructureRegistry {
+    /// The built-in effects: Qi structures are `QiWell`s.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(StructureKind::Qi, QiWell);
+        registry
+    }
+}
+
+impl StructureRegistry {
+    /// A registry without even the built-in effects.
+    pub fn empty() -> Self {
+        Self {
+            effects: BTreeMap::new(),
+        }
+    }
+
+    /// Add `effect` to the structures of `kind`, after the ones they already have.
+    pub fn register(&mut self, kind: StructureKind, effect: impl StructureEffect + 'static) {
+        self.effects.entry(kind).or_default().push(Arc::new(effect));
+    }
+
+    /// Drop every effect of `kind`, built-in ones included.
+    pub fn clear(&mut self, kind: StructureKind) {
+        self.effects.remove(&kind);
+    }
+
+    pub fn effects(&self, kind: StructureKind) -> &[Arc<dyn StructureEffect>] {
+        self.effects.get(&kind).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.values().all(Vec::is_empty)
+    }
+}
+
+/// The Qi structure: every `QI_STRUCTURE_PERIOD` ticks the owner pays `QI_STRUCTURE_UPKEEP`,
+/// then the structure feeds `QI_STRUCTURE_OUTPUT` Qi to its owner if they stand next to it,
+/// otherwise to the lowest-id living agent that does.
+#[derive(Debug, Clone, Copy)]
+pub struct QiWell;
+
+impl StructureEffect for QiWell {
+    fn on_tick(&self, ctx: &mut EffectContext<'_>, structure: &Structure) -> Vec<Event> {
+        if !ctx.tick().is_multiple_of(QI_STRUCTURE_PERIOD) {
+            return Vec::new();
+        }
+        let mut events = Vec::new();
+        if !pay_upkeep(ctx, structure, QI_STRUCTURE_UPKEEP, &mut events) {
+            return events;
+        }
+        let adjacent = ctx.agents_near(structure.position, HARVEST_RANGE);
+        let Some(recipient) = adjacent
+            .iter()
+            .find(|id| **id == structure.owner)
+            .or_else(|| adjacent.first())
+            .copied()
+        else {
+            return events;
+        };
+        produce(
+            ctx,
+            structure,
+            recipient,
+            ItemKind::Qi,
+            QI_STRUCTURE_OUTPUT,
+            &mut events,
+        );
+        events
+    }
+}
+
+/// Charge the owner `upkeep`, recording the payment or the stall. Returns whether it paid.
+fn pay_upkeep(
+    ctx: &mut EffectContext<'_>,
+    structure: &Structure,
+    upkeep: Qi,
+    events: &mut Vec<Event>,
+) -> bool {
+    if upkeep == 0 {
+        return true;
+    }
+    if !ctx.charge_qi(structure.owner, upkeep) {
+        events.push(Event::StructureStalled {
+            structure_id: structure.id,
+            owner: structure.owner,
+        });
+        return false;
+    }
+    events.push(Event::StructureUpkeepPaid {
+        structure_id: structure.id,
+        owner: structure.owner,
+        amount: upkeep,
+    });
+    true
+}
+
+fn produce(
+    ctx: &mut EffectContext<'_>,
+    structure: &Structure,
+    recipient: AgentId,
+    item: ItemKind,
+    amount: Qi,
+    events: &mut Vec<Event>,
+) {
+    let amount = ctx.grant(recipient, item, amount);
+    if amount > 0 {
+        events.push(Event::StructureProduced {
+            structure_id: structure.id,
+            recipient,
+            item,
+            amount,
+        });
+    }
+}
+
+/// The hook an `EffectSpec` answers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectTrigger {
+    Tick,
+    AdjacentAgent,
+    HarvestNearby,
+}
+
+/// A structure effect described in data, e.g. a `[[structure_effects]]` table of
+/// `.harimu/config.toml`:
+///
+/// ```toml
+/// [[structure_effects]]
+/// kind = "basic"
+/// on = "adjacent_agent"
+/// item = "stone"
+/// amount = 1
+/// ```
+///
+/// When triggered the owner pays `upkeep` Qi (the structure stalls if they can't), then
+/// `amount` of `item` goes to the owner on `tick`, or to the agent that came near or harvested.
+/// Qi is drawn from the world's supply, so less may arrive.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectSpec {
+    #[serde(serialize_with = "kind_name", deserialize_with = "structure_kind")]
+    pub kind: StructureKind,
+    pub on: EffectTrigger,
+    /// With `on = "tick"`, run only on ticks that are a multiple of this.
+    #[serde(default = "every_tick")]
+    pub every: u64,
+    pub item: ItemKind,
+    pub amount: Qi,
+    #[serde(default)]
+    pub upkeep: Qi,
+}
+
+fn every_tick() -> u64 {
+    1
+}
+
+fn kind_name<S: Serializer>(kind: &StructureKind, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(kind)
+}
+
+fn structure_kind<'de, D: Deserializer<'de>>(deserializer: D) -> Result<StructureKind, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map_err(|()| {
+        serde::de::Error::custom(format!(
+            "unknown structure kind '{}'; expected basic, programmable or qi",
+            name
+        ))
+    })
+}
+
+impl EffectSpec {
+    fn fire(
+        &self,
+        ctx: &mut EffectContext<'_>,
+        structure: &Structure,
+        recipient: AgentId,
+    ) -> Vec<Event> {
+        let mut events = Vec::new();
+        if pay_upkeep(ctx, structure, self.upkeep, &mut events) {
+            produce(
+                ctx,
+                structure,
+                recipient,
+                self.item,
+                self.amount,
+                &mut events,
+            );
+        }
+        events
+    }
+}
+
+impl StructureEffect for EffectSpec {
+    fn on_tick(&self, ctx: &mut EffectContext<'_>, structure: &Structure) -> Vec<Event> {
+        if self.on != EffectTrigger::Tick || !ctx.tick().is_multiple_of(self.every.max(1)) {
+            return Vec::new();
+        }
+        self.fire(ctx, structure, structure.owner)
+    }
+
+    fn on_adjacent_agent(
+        &self,
+        ctx: &mut EffectContext<'_>,
+        structure: &Structure,
+        agent_id: AgentId,
+    ) -> Vec<Event> {
+        if self.on != EffectTrigger::AdjacentAgent {
+            return Vec::new();
+        }
+        self.fire(ctx, structure, agent_id)
+    }
+
+    fn on_harvest_nearby(
+        &self,
+        ctx: &mut EffectContext<'_>,
+        structure: &Structure,
+        agent_id: AgentId,
+        _ore: OreKind,
+        _amount: Qi,
+    ) -> Vec<Event> {
+        if self.on != EffectTrigger::HarvestNearby {
+            return Vec::new();
+        }
+        self.fire(ctx, structure, agent_id)
+    }
+}
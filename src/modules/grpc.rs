@@ -0,0 +1,330 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::modules::api::{Api, ApiRequest, ApiResponse, Method};
+use crate::modules::auth::Scope;
+use crate::modules::stream::{StreamFilter, TickStream};
+
+/// Messages and service stubs generated from `proto/harimu.proto`.
+pub mod proto {
+    tonic::include_proto!("harimu.v1");
+}
+
+use proto::world_control_server::WorldControl;
+pub use proto::world_control_server::WorldControlServer;
+
+/// Ticks a slow `StreamEvents` client may fall behind before it is disconnected.
+const STREAM_BUFFER: usize = 256;
+
+/// `WorldControl` over the same `Api` the HTTP server uses, so both share auth, quotas and
+/// persistence.
+#[derive(Clone)]
+pub struct WorldControlService {
+    api: Arc<Mutex<Api>>,
+    stream: TickStream,
+}
+
+impl WorldControlService {
+    pub fn new(api: Api, stream: TickStream) -> Self {
+        Self {
+            api: Arc::new(Mutex::new(api)),
+            stream,
+        }
+    }
+
+    pub fn into_server(self) -> WorldControlServer<Self> {
+        WorldControlServer::new(self)
+    }
+
+    fn api(&self) -> MutexGuard<'_, Api> {
+        self.api
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Run `method url` through the API with the caller's token and address.
+    fn call<T>(
+        &self,
+        request: &Request<T>,
+        method: Method,
+        url: &str,
+        body: &str,
+    ) -> Result<serde_json::Value, Status> {
+        let (token, peer) = caller(request);
+        let response = self.api().handle(&ApiRequest {
+            method,
+            url,
+            token: token.as_deref(),
+            peer: &peer,
+            body,
+        });
+        into_result(response)
+    }
+
+    fn authorize<T>(&self, request: &Request<T>, scope: &Scope) -> Result<(), Status> {
+        let (token, peer) = caller(request);
+        self.api()
+            .authorize(
+                &ApiRequest {
+                    method: Method::Get,
+                    url: "",
+                    token: token.as_deref(),
+                    peer: &peer,
+                    body: "",
+                },
+                scope,
+            )
+            .map(|_| ())
+            .map_err(|response| into_result(response).unwrap_err())
+    }
+}
+
+/// The bearer token from `authorization` metadata and the peer address.
+fn caller<T>(request: &Request<T>) -> (Option<String>, String) {
+    let token = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    let peer = request
+        .remote_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".into());
+    (token, peer)
+}
+
+fn into_result(response: ApiResponse) -> Result<serde_json::Value, Status> {
+    if response.status < 300 {
+        return Ok(response.body);
+    }
+    let message = response.body["error"]
+        .as_str()
+        .unwrap_or("request failed")
+        .to_string();
+    Err(match response.status {
+        400 => Status::invalid_argument(message),
+        401 => Status::unauthenticated(message),
+        403 => Status::permission_denied(message),
+        404 => Status::not_found(message),
+        429 => Status::resource_exhausted(message),
+        _ => Status::internal(message),
+    })
+}
+
+fn position(value: &serde_json::Value) -> proto::Position {
+    let coord = |axis: &str| value[axis].as_i64().unwrap_or(0) as i32;
+    proto::Position {
+        x: coord("x"),
+        y: coord("y"),
+        z: coord("z"),
+    }
+}
+
+#[tonic::async_trait]
+impl WorldControl for WorldControlService {
+    async fn spawn_agent(
+        &self,
+        request: Request<proto::SpawnAgentRequest>,
+    ) -> Result<Response<proto::SpawnAgentReply>, Status> {
+        let spawn = request.get_ref();
+        let mut body = json!({ "name": spawn.name, "qi": spawn.qi });
+        if let Some(p) = &spawn.position {
+            body["position"] = json!({ "x": p.x, "y": p.y, "z": p.z });
+        }
+        if let Some(max_age) = spawn.max_age {
+            body["max_age"] = json!(max_age);
+        }
+        let reply = self.call(&request, Method::Post, "/agents", &body.to_string())?;
+        Ok(Response::new(proto::SpawnAgentReply {
+            agent_id: reply["agent_id"].as_u64().unwrap_or_default(),
+        }))
+    }
+
+    async fn submit_action(
+        &self,
+        request: Request<proto::SubmitActionRequest>,
+    ) -> Result<Response<proto::SubmitActionReply>, Status> {
+        let body = request.get_ref().request_json.clone();
+        let reply = self.call(&request, Method::Post, "/actions", &body)?;
+        Ok(Response::new(proto::SubmitActionReply {
+            queued_for_tick: reply["queued_for_tick"].as_u64().unwrap_or_default(),
+            pending: reply["pending"].as_u64().unwrap_or_default(),
+        }))
+    }
+
+    async fn step(
+        &self,
+        request: Request<proto::StepRequest>,
+    ) -> Result<Response<proto::StepReply>, Status> {
+        let body = json!({ "ticks": request.get_ref().ticks.max(1) }).to_string();
+        let reply = self.call(&request, Method::Post, "/tick", &body)?;
+        let results = reply["results"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|result| proto::TickMessage {
+                tick: result["tick"].as_u64().unwrap_or_default(),
+                json: result.to_string(),
+            })
+            .collect();
+        Ok(Response::new(proto::StepReply {
+            tick: reply["tick"].as_u64().unwrap_or_default(),
+            results,
+        }))
+    }
+
+    async fn get_snapshot(
+        &self,
+        request: Request<proto::GetSnapshotRequest>,
+    ) -> Result<Response<proto::Snapshot>, Status> {
+        let snapshot = self.call(&request, Method::Get, "/snapshot", "")?;
+        let agents = snapshot["agents"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|agent| proto::Agent {
+                id: agent["id"].as_u64().unwrap_or_default(),
+                name: agent["name"].as_str().unwrap_or_default().to_string(),
+                alive: agent["alive"].as_bool().unwrap_or_default(),
+                hp: agent["hp"].as_u64().unwrap_or_default() as u32,
+                position: Some(position(&agent["position"])),
+            })
+            .collect();
+        Ok(Response::new(proto::Snapshot {
+            tick: snapshot["tick"].as_u64().unwrap_or_default(),
+            agents,
+            json: snapshot.to_string(),
+        }))
+    }
+
+    type StreamEventsStream = ReceiverStream<Result<proto::TickMessage, Status>>;
+
+    async fn stream_events(
+        &self,
+        request: Request<proto::StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        self.authorize(&request, &Scope::Observe)?;
+        let filter = StreamFilter {
+            agent_id: request.get_ref().agent_id,
+            kinds: request.get_ref().event_kinds.iter().cloned().collect(),
+        };
+        let ticks = self.stream.subscribe(filter);
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        // The tick stream is a blocking channel; forward it on a thread of its own until the
+        // client disconnects.
+        std::thread::spawn(move || {
+            for message in ticks {
+                let tick = serde_json::from_str::<serde_json::Value>(&message)
+                    .ok()
+                    .and_then(|value| value["tick"].as_u64())
+                    .unwrap_or_default();
+                let sent = tx.blocking_send(Ok(proto::TickMessage {
+                    tick,
+                    json: message,
+                }));
+                if sent.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::vm::Vm;
+
+    #[test]
+    fn grpc_service_spawns_steps_and_streams() {
+        use crate::modules::api::Api;
+        use crate::modules::auth::TokenStore;
+        use crate::modules::mempool::QuotaLimits;
+        use crate::modules::stream::TickStream;
+        use proto::world_control_client::WorldControlClient;
+        use serde_json::json;
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let stream = TickStream::new();
+        let publisher = stream.clone();
+        let api = Api::new(
+            Vm::with_seed(9),
+            QuotaLimits::default(),
+            TokenStore::default(),
+        )
+        .with_tick_hook(Box::new(move |_, tick, _| publisher.publish(tick)));
+        let service = WorldControlService::new(api, stream);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(
+            tonic::transport::Server::builder()
+                .add_service(service.into_server())
+                .serve(([127, 0, 0, 1], port).into()),
+        );
+        runtime.block_on(async {
+            let mut client = loop {
+                match WorldControlClient::connect(format!("http://127.0.0.1:{}", port)).await {
+                    Ok(client) => break client,
+                    Err(_) => tokio::time::sleep(std::time::Duration::from_millis(20)).await,
+                }
+            };
+            let id = client
+                .spawn_agent(proto::SpawnAgentRequest {
+                    name: "Ada".into(),
+                    qi: 4,
+                    position: None,
+                    max_age: None,
+                })
+                .await
+                .unwrap()
+                .into_inner()
+                .agent_id;
+            let mut events = client
+                .stream_events(proto::StreamEventsRequest {
+                    agent_id: Some(id),
+                    event_kinds: vec!["ScanReport".into()],
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            let request_json = json!({ "agent_id": id, "tick": 1, "action": "Scan" }).to_string();
+            client
+                .submit_action(proto::SubmitActionRequest { request_json })
+                .await
+                .unwrap();
+            let step = client
+                .step(proto::StepRequest { ticks: 2 })
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!((step.tick, step.results.len()), (2, 2));
+
+            let streamed = events.message().await.unwrap().unwrap();
+            assert_eq!(streamed.tick, 1);
+            assert!(streamed.json.contains("ScanReport"));
+            let snapshot = client
+                .get_snapshot(proto::GetSnapshotRequest {})
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(snapshot.agents[0].name, "Ada");
+            let bad = client
+                .submit_action(proto::SubmitActionRequest {
+                    request_json: "nope".into(),
+                })
+                .await
+                .unwrap_err();
+            assert_eq!(bad.code(), tonic::Code::InvalidArgument);
+        });
+    }
+}
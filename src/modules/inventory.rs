@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::modules::ore::OreKind;
+use crate::modules::vm::Qi;
+
+/// Anything an agent can carry. Harvested ores map onto items one-to-one (registered ores
+/// beyond Qi and transistors as `Ore`); circuits are crafted. Saved by label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ItemKind {
+    Qi,
+    Transistor,
+    Dirt,
+    Stone,
+    Circuit,
+    Ore(OreKind),
+}
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 5] = [
+        ItemKind::Qi,
+        ItemKind::Transistor,
+        ItemKind::Dirt,
+        ItemKind::Stone,
+        ItemKind::Circuit,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ItemKind::Ore(ore) => ore.label(),
+            ItemKind::Qi => "qi",
+            ItemKind::Transistor => "transistor",
+            ItemKind::Dirt => "dirt",
+            ItemKind::Stone => "stone",
+            ItemKind::Circuit => "circuit",
+        }
+    }
+
+    /// The ore harvested as this item; `None` for blocks and crafted items.
+    pub fn ore(self) -> Option<OreKind> {
+        match self {
+            ItemKind::Qi => Some(OreKind::QI),
+            ItemKind::Transistor => Some(OreKind::TRANSISTOR),
+            ItemKind::Ore(ore) => Some(ore),
+            ItemKind::Dirt | ItemKind::Stone | ItemKind::Circuit => None,
+        }
+    }
+}
+
+impl From<OreKind> for ItemKind {
+    fn from(ore: OreKind) -> Self {
+        match ore {
+            OreKind::QI => ItemKind::Qi,
+            OreKind::TRANSISTOR => ItemKind::Transistor,
+            ore => ItemKind::Ore(ore),
+        }
+    }
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+/// Labels read as built-in items before the ore registry is consulted. An ore may only take
+/// one of them if it is that item (`qi`, `transistor`).
+pub(crate) const BUILTIN_LABELS: [(&str, ItemKind); 7] = [
+    ("qi", ItemKind::Qi),
+    ("transistor", ItemKind::Transistor),
+    ("transistors", ItemKind::Transistor),
+    ("dirt", ItemKind::Dirt),
+    ("stone", ItemKind::Stone),
+    ("circuit", ItemKind::Circuit),
+    ("circuits", ItemKind::Circuit),
+];
+
+impl ItemKind {
+    /// The built-in item `label` (lowercase) names, if any.
+    pub fn builtin(label: &str) -> Option<ItemKind> {
+        BUILTIN_LABELS
+            .iter()
+            .find(|(builtin, _)| *builtin == label)
+            .map(|(_, item)| *item)
+    }
+}
+
+impl FromStr for ItemKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let label = s.trim().to_lowercase();
+        match ItemKind::builtin(&label) {
+            Some(item) => Ok(item),
+            None => label.parse::<OreKind>().map(ItemKind::from).map_err(|_| ()),
+        }
+    }
+}
+
+impl Serialize for ItemKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.label())
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label = String::deserialize(deserializer)?;
+        label
+            .parse()
+            .map_err(|()| serde::de::Error::custom(format!("unknown item '{}'", label)))
+    }
+}
+
+/// Returned when an inventory cannot cover a spend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shortfall {
+    pub item: ItemKind,
+    pub required: Qi,
+    pub available: Qi,
+}
+
+/// Item counts held by an agent. Missing entries count as zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Inventory {
+    items: HashMap<ItemKind, Qi>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_item(mut self, item: ItemKind, amount: Qi) -> Self {
+        self.gain_item(item, amount);
+        self
+    }
+
+    pub fn get(&self, item: ItemKind) -> Qi {
+        self.items.get(&item).copied().unwrap_or(0)
+    }
+
+    pub fn gain_item(&mut self, item: ItemKind, amount: Qi) {
+        if amount == 0 {
+            return;
+        }
+        let entry = self.items.entry(item).or_insert(0);
+        *entry = entry.saturating_add(amount);
+    }
+
+    pub fn spend_item(&mut self, item: ItemKind, amount: Qi) -> Result<(), Shortfall> {
+        self.check(item, amount)?;
+        if let Some(entry) = self.items.get_mut(&item) {
+            *entry -= amount;
+            if *entry == 0 {
+                self.items.remove(&item);
+            }
+        }
+        Ok(())
+    }
+
+    /// Spend a whole bill of items, or nothing if any line cannot be covered.
+    pub fn spend_all(&mut self, costs: &[(ItemKind, Qi)]) -> Result<(), Shortfall> {
+        self.covers(costs)?;
+        for (item, amount) in costs {
+            self.spend_item(*item, *amount)?;
+        }
+        Ok(())
+    }
+
+    pub fn covers(&self, costs: &[(ItemKind, Qi)]) -> Result<(), Shortfall> {
+        costs
+            .iter()
+            .try_for_each(|(item, amount)| self.check(*item, *amount))
+    }
+
+    fn check(&self, item: ItemKind, amount: Qi) -> Result<(), Shortfall> {
+        let available = self.get(item);
+        if available < amount {
+            return Err(Shortfall {
+                item,
+                required: amount,
+                available,
+            });
+        }
+        Ok(())
+    }
+
+    /// Non-empty entries in a stable order.
+    pub fn iter(&self) -> impl Iterator<Item = (ItemKind, Qi)> + '_ {
+        let mut entries: Vec<(ItemKind, Qi)> = self
+            .items
+            .iter()
+            .filter(|(_, amount)| **amount > 0)
+            .map(|(item, amount)| (*item, *amount))
+            .collect();
+        entries.sort_by_key(|(item, _)| *item);
+        entries.into_iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.values().all(|amount| *amount == 0)
+    }
+}
+
+impl fmt::Display for Inventory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Qi and transistors are always shown; other items only once the agent holds some.
+        let ores = self.iter().filter_map(|(item, _)| match item {
+            ItemKind::Ore(_) => Some(item),
+            _ => None,
+        });
+        let parts: Vec<String> = ItemKind::ALL
+            .into_iter()
+            .filter(|item| {
+                matches!(item, ItemKind::Qi | ItemKind::Transistor) || self.get(*item) > 0
+            })
+            .chain(ores)
+            .map(|item| format!("{}={}", item, self.get(item)))
+            .collect();
+        write!(f, "{}", parts.join(" "))
+    }
+}
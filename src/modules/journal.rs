@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::modules::paths;
+use crate::modules::view::epoch_of;
+use crate::modules::vm::{AgentId, Event, TickResult};
+
+/// One line of the event journal as written.
+#[derive(Debug, Serialize)]
+struct JournalLine<'a> {
+    tick: u64,
+    timestamp: &'a str,
+    event: &'a Event,
+}
+
+/// One line of the event journal as read back. Events stay as JSON: they are written with
+/// their `Event` variant as the single key, e.g. `{"AgentMoved": {...}}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub tick: u64,
+    pub timestamp: String,
+    pub event: serde_json::Value,
+}
+
+impl JournalEntry {
+    /// The `Event` variant name, e.g. `AgentMoved`.
+    pub fn kind(&self) -> &str {
+        self.event
+            .as_object()
+            .and_then(|fields| fields.keys().next())
+            .map(String::as_str)
+            .unwrap_or("?")
+    }
+
+    /// A numeric field of the event, e.g. `amount` or `agent_id`.
+    pub fn field_u64(&self, name: &str) -> Option<u64> {
+        self.event
+            .as_object()
+            .and_then(|fields| fields.values().next())
+            .and_then(|fields| fields.get(name))
+            .and_then(serde_json::Value::as_u64)
+    }
+
+    /// A text field of the event, e.g. `reason`.
+    pub fn field_str(&self, name: &str) -> Option<&str> {
+        self.event
+            .as_object()
+            .and_then(|fields| fields.values().next())
+            .and_then(|fields| fields.get(name))
+            .and_then(serde_json::Value::as_str)
+    }
+}
+
+/// Totals of one epoch's journaled events; what remains once its raw lines are compacted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochSummary {
+    pub epoch: u64,
+    pub first_tick: u64,
+    pub last_tick: u64,
+    pub events: u64,
+    /// Events per `Event` variant.
+    pub by_kind: BTreeMap<String, u64>,
+    /// Sum of the `amount` field per variant (Qi spent, ore gained, ...).
+    pub amounts: BTreeMap<String, u64>,
+    /// Events naming each agent as `agent_id`.
+    pub by_agent: BTreeMap<AgentId, u64>,
+    /// The epoch's raw lines were removed from the journal.
+    pub pruned: bool,
+}
+
+impl EpochSummary {
+    fn new(epoch: u64, tick: u64) -> Self {
+        Self {
+            epoch,
+            first_tick: tick,
+            last_tick: tick,
+            ..Self::default()
+        }
+    }
+
+    fn add(&mut self, entry: &JournalEntry) {
+        self.first_tick = self.first_tick.min(entry.tick);
+        self.last_tick = self.last_tick.max(entry.tick);
+        self.events += 1;
+        *self.by_kind.entry(entry.kind().to_string()).or_default() += 1;
+        if let Some(amount) = entry.field_u64("amount") {
+            *self.amounts.entry(entry.kind().to_string()).or_default() += amount;
+        }
+        if let Some(agent_id) = entry.field_u64("agent_id") {
+            *self.by_agent.entry(agent_id).or_default() += 1;
+        }
+    }
+
+    /// Fold in an earlier summary of the same epoch whose raw lines are gone.
+    fn merge(&mut self, other: &EpochSummary) {
+        self.first_tick = self.first_tick.min(other.first_tick);
+        self.last_tick = self.last_tick.max(other.last_tick);
+        self.events += other.events;
+        for (kind, count) in &other.by_kind {
+            *self.by_kind.entry(kind.clone()).or_default() += count;
+        }
+        for (kind, amount) in &other.amounts {
+            *self.amounts.entry(kind.clone()).or_default() += amount;
+        }
+        for (agent_id, count) in &other.by_agent {
+            *self.by_agent.entry(*agent_id).or_default() += count;
+        }
+        self.pruned |= other.pruned;
+    }
+}
+
+/// What `compact_journal` did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactReport {
+    pub epochs: usize,
+    pub events: u64,
+    pub pruned: bool,
+}
+
+fn journal_path() -> PathBuf {
+    paths::data_dir().join("events.jsonl")
+}
+
+pub fn journal_file_path() -> PathBuf {
+    journal_path()
+}
+
+fn summary_path() -> PathBuf {
+    paths::data_dir().join("events_summary.json")
+}
+
+pub fn journal_summary_file_path() -> PathBuf {
+    summary_path()
+}
+
+fn parse_line(line: &[u8], path: &Path) -> io::Result<JournalEntry> {
+    serde_json::from_slice(line).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "failed to parse event journal {}; delete it to reset: {}",
+                path.display(),
+                e
+            ),
+        )
+    })
+}
+
+/// Append every event of a tick. The journal spans runs and is never rewritten.
+pub fn append_tick(result: &TickResult) -> io::Result<()> {
+    if result.events.is_empty() {
+        return Ok(());
+    }
+    fs::create_dir_all(paths::data_dir())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal_path())?;
+    let timestamp = Utc::now().to_rfc3339();
+    let mut buf = Vec::new();
+    for event in &result.events {
+        let line = JournalLine {
+            tick: result.tick,
+            timestamp: &timestamp,
+            event,
+        };
+        serde_json::to_writer(&mut buf, &line)?;
+        buf.push(b'\n');
+    }
+    file.write_all(&buf)
+}
+
+/// Entries from byte `offset` on, with the offset just past the last complete line. A
+/// partially written trailing line is left for the next read.
+pub fn read_journal_from(offset: u64) -> io::Result<(Vec<JournalEntry>, u64)> {
+    let path = journal_path();
+    if !path.exists() {
+        return Ok((Vec::new(), 0));
+    }
+
+    let mut file = File::open(&path)?;
+    let start = offset.min(file.metadata()?.len());
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let complete = bytes
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+
+    let mut entries = Vec::new();
+    for line in bytes[..complete].split(|b| *b == b'\n') {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        entries.push(parse_line(line, &path)?);
+    }
+    Ok((entries, start + complete as u64))
+}
+
+/// The last `count` entries of the journal.
+pub fn tail_journal(count: usize) -> io::Result<Vec<JournalEntry>> {
+    let (mut entries, _) = read_journal_from(0)?;
+    let skip = entries.len().saturating_sub(count);
+    Ok(entries.split_off(skip))
+}
+
+/// Per-epoch summaries written by `compact_journal`, oldest epoch first.
+pub fn load_epoch_summaries() -> io::Result<Vec<EpochSummary>> {
+    let path = summary_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let bytes = fs::read(&path)?;
+    if bytes.is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_slice(&bytes).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "failed to parse journal summaries {}; delete it to reset: {}",
+                path.display(),
+                e
+            ),
+        )
+    })
+}
+
+/// Summarize every epoch older than the newest `keep_epochs + 1` into `events_summary.json`.
+/// With `prune`, the summarized events are also removed from the journal. Summaries of pruned
+/// epochs are kept across compactions and absorb any lines journaled into them later; the
+/// others are rebuilt from the raw lines each time.
+pub fn compact_journal(keep_epochs: u64, prune: bool) -> io::Result<CompactReport> {
+    let path = journal_path();
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(CompactReport::default()),
+        Err(err) => return Err(err),
+    };
+    let complete = bytes
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    let lines: Vec<&[u8]> = bytes[..complete]
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .collect();
+    let entries = lines
+        .iter()
+        .map(|line| parse_line(line, &path))
+        .collect::<io::Result<Vec<_>>>()?;
+    let Some(last) = entries.last() else {
+        return Ok(CompactReport::default());
+    };
+    let current = epoch_of(last.tick);
+    let previous = load_epoch_summaries()?;
+    let already_pruned: BTreeSet<u64> = previous
+        .iter()
+        .filter(|summary| summary.pruned)
+        .map(|summary| summary.epoch)
+        .collect();
+
+    let mut summaries: BTreeMap<u64, EpochSummary> = BTreeMap::new();
+    let mut kept = Vec::new();
+    let mut dropped = false;
+    let mut events = 0;
+    for (line, entry) in lines.iter().zip(&entries) {
+        let epoch = epoch_of(entry.tick);
+        if epoch.saturating_add(keep_epochs) >= current {
+            kept.extend_from_slice(line);
+            kept.push(b'\n');
+            continue;
+        }
+        summaries
+            .entry(epoch)
+            .or_insert_with(|| EpochSummary::new(epoch, entry.tick))
+            .add(entry);
+        events += 1;
+        // Lines landing in an already pruned epoch are folded into its summary and dropped.
+        if prune || already_pruned.contains(&epoch) {
+            dropped = true;
+        } else {
+            kept.extend_from_slice(line);
+            kept.push(b'\n');
+        }
+    }
+    if summaries.is_empty() {
+        return Ok(CompactReport::default());
+    }
+
+    let mut all = Vec::new();
+    for old in previous {
+        match summaries.get_mut(&old.epoch) {
+            Some(summary) if old.pruned => summary.merge(&old),
+            // Its raw lines are still in the journal and were summarized again.
+            Some(_) => {}
+            None => all.push(old),
+        }
+    }
+    let report = CompactReport {
+        epochs: summaries.len(),
+        events,
+        pruned: prune,
+    };
+    all.extend(summaries.into_values().map(|summary| EpochSummary {
+        pruned: summary.pruned || prune,
+        ..summary
+    }));
+    all.sort_by_key(|summary| summary.epoch);
+    paths::write_atomic(&summary_path(), &serde_json::to_vec_pretty(&all)?)?;
+
+    if dropped {
+        // A trailing half-written line belongs to a tick still being journaled; keep it.
+        kept.extend_from_slice(&bytes[complete..]);
+        paths::write_atomic(&path, &kept)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::testing::TempDataDir;
+    use crate::modules::view::EPOCH_TICKS;
+    use crate::modules::vm::Qi;
+
+    fn spend(tick: u64, agent_id: AgentId, amount: Qi) {
+        append_tick(&TickResult {
+            tick,
+            events: vec![Event::QiSpent {
+                agent_id,
+                amount,
+                action: "scan",
+            }],
+            rejections: Vec::new(),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn epochs_older_than_the_kept_ones_are_summarized() {
+        let _data = TempDataDir::new("journal-compact");
+        for epoch in 0..4 {
+            spend(epoch * EPOCH_TICKS + 1, 1, 2);
+            spend(epoch * EPOCH_TICKS + 5, 2, 3);
+        }
+
+        let report = compact_journal(1, false).unwrap();
+        assert_eq!((report.epochs, report.events, report.pruned), (2, 4, false));
+        let summaries = load_epoch_summaries().unwrap();
+        assert_eq!(
+            summaries.iter().map(|s| s.epoch).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        let first = &summaries[0];
+        assert_eq!((first.first_tick, first.last_tick, first.events), (1, 5, 2));
+        assert_eq!(first.by_kind["QiSpent"], 2);
+        assert_eq!(first.amounts["QiSpent"], 5);
+        assert_eq!(first.by_agent[&1], 1);
+        assert!(!first.pruned);
+        // Without --prune the raw lines stay.
+        assert_eq!(read_journal_from(0).unwrap().0.len(), 8);
+    }
+
+    #[test]
+    fn a_torn_trailing_line_survives_a_prune() {
+        let _data = TempDataDir::new("journal-torn");
+        spend(1, 1, 2);
+        spend(EPOCH_TICKS + 1, 1, 2);
+        let torn = r#"{"tick":114,"timestamp":"#;
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(journal_path())
+            .unwrap();
+        file.write_all(torn.as_bytes()).unwrap();
+
+        let report = compact_journal(0, true).unwrap();
+        assert_eq!((report.epochs, report.events), (1, 1));
+        let journal = fs::read_to_string(journal_path()).unwrap();
+        assert!(journal.ends_with(torn), "{}", journal);
+        let (entries, _) = read_journal_from(0).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].tick, EPOCH_TICKS + 1);
+        assert!(paths::backup_file_path(&journal_path()).exists());
+    }
+
+    #[test]
+    fn compacting_twice_keeps_each_epoch_summary_once() {
+        let _data = TempDataDir::new("journal-twice");
+        for epoch in 0..3 {
+            spend(epoch * EPOCH_TICKS + 1, 1, 2);
+        }
+        compact_journal(0, false).unwrap();
+        let first = load_epoch_summaries().unwrap();
+        compact_journal(0, false).unwrap();
+        assert_eq!(load_epoch_summaries().unwrap(), first);
+
+        compact_journal(0, true).unwrap();
+        let pruned = load_epoch_summaries().unwrap();
+        assert_eq!(pruned.len(), 2);
+        assert!(pruned.iter().all(|s| s.pruned && s.events == 1));
+        assert_eq!(compact_journal(0, true).unwrap(), CompactReport::default());
+        assert_eq!(load_epoch_summaries().unwrap(), pruned);
+
+        // A line that lands in a pruned epoch later adds to its summary instead of replacing it.
+        spend(3, 1, 4);
+        spend(2 * EPOCH_TICKS + 2, 1, 2);
+        compact_journal(0, false).unwrap();
+        let merged = load_epoch_summaries().unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!((merged[0].events, merged[0].amounts["QiSpent"]), (2, 6));
+        assert!(merged[0].pruned);
+        let (entries, _) = read_journal_from(0).unwrap();
+        assert!(entries.iter().all(|entry| epoch_of(entry.tick) == 2));
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use candle_core::quantized::gguf_file;
+use candle_core::{Device, Tensor};
+use candle_transformers::generation::LogitsProcessor;
+use candle_transformers::models::quantized_llama::ModelWeights;
+use tokenizers::Tokenizer;
+
+/// Tokens generated per decision; an action label fits well inside this.
+const MAX_NEW_TOKENS: usize = 48;
+
+/// A GGUF model loaded into memory with the tokenizer that goes with it.
+pub struct LocalModel {
+    weights: ModelWeights,
+    tokenizer: Tokenizer,
+    eos: Option<u32>,
+}
+
+/// Models loaded so far, by path, so every agent and tick shares one copy of the weights.
+fn loaded() -> &'static Mutex<HashMap<PathBuf, Arc<Mutex<LocalModel>>>> {
+    static LOADED: OnceLock<Mutex<HashMap<PathBuf, Arc<Mutex<LocalModel>>>>> = OnceLock::new();
+    LOADED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The model at `path`, loaded on first use. The tokenizer is `tokenizer.json` next to it.
+pub fn load(path: &Path) -> Result<Arc<Mutex<LocalModel>>, String> {
+    let mut loaded = loaded()
+        .lock()
+        .map_err(|_| "model cache poisoned".to_string())?;
+    if let Some(model) = loaded.get(path) {
+        return Ok(model.clone());
+    }
+
+    let mut file = File::open(path).map_err(|e| format!("open model {}: {}", path.display(), e))?;
+    let content = gguf_file::Content::read(&mut file)
+        .map_err(|e| format!("read gguf {}: {}", path.display(), e))?;
+    let eos = content
+        .metadata
+        .get("tokenizer.ggml.eos_token_id")
+        .and_then(|v| v.to_u32().ok());
+    let weights = ModelWeights::from_gguf(content, &mut file, &Device::Cpu)
+        .map_err(|e| format!("load gguf {}: {}", path.display(), e))?;
+    let tokenizer_path = path.with_file_name("tokenizer.json");
+    let tokenizer = Tokenizer::from_file(&tokenizer_path)
+        .map_err(|e| format!("load tokenizer {}: {}", tokenizer_path.display(), e))?;
+
+    let model = Arc::new(Mutex::new(LocalModel {
+        weights,
+        tokenizer,
+        eos,
+    }));
+    loaded.insert(path.to_path_buf(), model.clone());
+    Ok(model)
+}
+
+impl LocalModel {
+    /// Greedy completion of `prompt`, stopping at end of sequence or `MAX_NEW_TOKENS`.
+    pub fn complete(&mut self, prompt: &str) -> Result<String, String> {
+        let encoding = self
+            .tokenizer
+            .encode(prompt, true)
+            .map_err(|e| format!("tokenize: {}", e))?;
+        let mut tokens = encoding.get_ids().to_vec();
+        let prompt_len = tokens.len();
+        let mut sampler = LogitsProcessor::new(0, None, None);
+
+        // Position 0 replaces the key/value cache left over from the previous prompt.
+        let mut input = tokens.clone();
+        let mut index_pos = 0;
+        for _ in 0..MAX_NEW_TOKENS {
+            let x = Tensor::new(input.as_slice(), &Device::Cpu)
+                .and_then(|t| t.unsqueeze(0))
+                .map_err(|e| format!("input: {}", e))?;
+            let logits = self
+                .weights
+                .forward(&x, index_pos)
+                .and_then(|l| l.squeeze(0))
+                .map_err(|e| format!("forward: {}", e))?;
+            let next = sampler
+                .sample(&logits)
+                .map_err(|e| format!("sample: {}", e))?;
+            index_pos += input.len();
+            if Some(next) == self.eos {
+                break;
+            }
+            tokens.push(next);
+            input = vec![next];
+        }
+
+        self.tokenizer
+            .decode(&tokens[prompt_len..], true)
+            .map_err(|e| format!("detokenize: {}", e))
+    }
+}
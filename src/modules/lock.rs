@@ -0,0 +1,136 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+
+use crate::modules::paths;
+
+const LOCK_FILE: &str = "harimu.lock";
+
+/// Who holds the data directory lock in this process. The OS lock is per open file, so a
+/// second `lock()` from the same process would deadlock; nested guards only bump `depth`.
+struct Holder {
+    file: Option<File>,
+    owner: Option<ThreadId>,
+    depth: usize,
+}
+
+static HOLDER: Mutex<Holder> = Mutex::new(Holder {
+    file: None,
+    owner: None,
+    depth: 0,
+});
+static RELEASED: Condvar = Condvar::new();
+
+fn holder() -> MutexGuard<'static, Holder> {
+    HOLDER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn lock_file_path() -> PathBuf {
+    paths::data_dir().join(LOCK_FILE)
+}
+
+/// Exclusive advisory lock on the data directory, held until dropped. Take it around every
+/// load-modify-save cycle so concurrent `harimu` processes (e.g. a background `start` and a
+/// `wallet transfer`) never interleave writes to the same store. Re-entrant within a thread.
+#[derive(Debug)]
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct DataLock {
+    _private: (),
+}
+
+/// Block until this process holds the data directory lock.
+pub fn lock_data_dir() -> io::Result<DataLock> {
+    let me = thread::current().id();
+    let mut held = holder();
+    loop {
+        match held.owner {
+            None => break,
+            Some(owner) if owner == me => {
+                held.depth += 1;
+                return Ok(DataLock { _private: () });
+            }
+            Some(_) => {
+                held = RELEASED
+                    .wait(held)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        }
+    }
+
+    fs::create_dir_all(paths::data_dir())?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_file_path())?;
+    file.lock()?;
+    held.file = Some(file);
+    held.owner = Some(me);
+    held.depth = 1;
+    Ok(DataLock { _private: () })
+}
+
+impl Drop for DataLock {
+    fn drop(&mut self) {
+        let mut held = holder();
+        held.depth = held.depth.saturating_sub(1);
+        if held.depth == 0 {
+            // Closing the file releases the OS lock.
+            held.file = None;
+            held.owner = None;
+            RELEASED.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::modules::testing::TempDataDir;
+
+    /// Whether another open file could take the OS lock right now.
+    fn os_lock_free() -> bool {
+        let file = File::open(lock_file_path()).unwrap();
+        file.try_lock().is_ok()
+    }
+
+    #[test]
+    fn the_lock_is_reentrant_on_one_thread_and_released_on_drop() {
+        let _data = TempDataDir::new("lock-reentrant");
+        let outer = lock_data_dir().unwrap();
+        let inner = lock_data_dir().unwrap();
+        assert!(!os_lock_free());
+        drop(inner);
+        // The outer guard still holds it.
+        assert!(!os_lock_free());
+        drop(outer);
+        assert!(os_lock_free());
+    }
+
+    #[test]
+    fn another_thread_waits_until_the_guard_drops() {
+        let _data = TempDataDir::new("lock-threads");
+        let guard = lock_data_dir().unwrap();
+        let (acquired, on_acquired) = mpsc::channel();
+        let waiter = thread::spawn(move || {
+            let _guard = lock_data_dir().unwrap();
+            acquired.send(()).unwrap();
+        });
+        assert!(
+            on_acquired
+                .recv_timeout(Duration::from_millis(200))
+                .is_err()
+        );
+        drop(guard);
+        on_acquired.recv_timeout(Duration::from_secs(5)).unwrap();
+        waiter.join().unwrap();
+        assert!(os_lock_free());
+    }
+}
@@ -0,0 +1,255 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+use tracing_subscriber::filter::{EnvFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, fmt};
+
+use crate::modules::paths;
+
+/// Target of the LLM request/response records; they go to `logs/llm.log`, never to stderr.
+pub const LLM_TARGET: &str = "harimu::llm";
+/// Which logs reach stderr, as `tracing` directives, e.g. `info` or `warn,harimu::commands=debug`.
+pub const LOG_ENV: &str = "HARIMU_LOG";
+const DEFAULT_FILTER: &str = "warn";
+const LLM_LOG_FILE: &str = "llm.log";
+
+static FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One human-readable line per record
+    #[default]
+    Text,
+    /// One JSON object per record
+    Json,
+}
+
+impl LogFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+/// When `logs/llm.log` is rotated to `llm.log.1` (`[llm_log]` in `.harimu/config.toml`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogRotation {
+    /// Rotate before the file would grow past this many bytes; 0 never rotates by size.
+    pub max_bytes: u64,
+    /// Rotate once the file was started this many hours ago; 0 never rotates by age.
+    pub max_age_hours: u64,
+    /// Rotated files to keep, `llm.log.1` being the newest; older ones are deleted.
+    pub keep: usize,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10 * 1024 * 1024,
+            max_age_hours: 24,
+            keep: 5,
+        }
+    }
+}
+
+impl LogRotation {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn max_age(&self) -> Option<Duration> {
+        (self.max_age_hours > 0).then(|| Duration::from_secs(self.max_age_hours * 3600))
+    }
+}
+
+/// An append-only log file that moves itself aside to `<name>.1`, `<name>.2`, ... as it
+/// reaches the size or age limit of its `LogRotation`. Each `write` lands whole in one file.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    file: Option<File>,
+    size: u64,
+    started: SystemTime,
+}
+
+impl RotatingFile {
+    pub fn new(path: impl Into<PathBuf>, rotation: LogRotation) -> Self {
+        Self {
+            path: path.into(),
+            rotation,
+            file: None,
+            size: 0,
+            started: SystemTime::now(),
+        }
+    }
+
+    fn open(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            let meta = file.metadata()?;
+            self.size = meta.len();
+            self.started = meta.created().unwrap_or_else(|_| SystemTime::now());
+            self.file = Some(file);
+        }
+        Ok(self.file.as_mut().expect("opened above"))
+    }
+
+    fn is_due(&self, incoming: usize) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let too_big =
+            self.rotation.max_bytes > 0 && self.size + incoming as u64 > self.rotation.max_bytes;
+        let too_old = self.rotation.max_age().is_some_and(|max_age| {
+            self.started
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed >= max_age)
+        });
+        too_big || too_old
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    /// Close the current file and shift it and its predecessors one place along, deleting
+    /// whatever falls past `keep`.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        self.size = 0;
+        if self.rotation.keep == 0 {
+            return remove_if_exists(&self.path);
+        }
+        remove_if_exists(&self.rotated(self.rotation.keep))?;
+        for n in (1..self.rotation.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        if self.path.exists() {
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        Ok(())
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.open()?;
+        if self.is_due(buf.len()) {
+            self.rotate()?;
+        }
+        self.open()?.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The format `init` was called with, for child processes that should log the same way.
+pub fn format() -> LogFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Route `tracing` records for this process: `LLM_TARGET` to the rotating `logs/llm.log`,
+/// everything else `HARIMU_LOG` lets through (warnings by default) to stderr.
+pub fn init(format: LogFormat, rotation: LogRotation) {
+    let _ = FORMAT.set(format);
+    let filter = EnvFilter::try_from_env(LOG_ENV)
+        .unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER))
+        .add_directive(
+            format!("{}=off", LLM_TARGET)
+                .parse()
+                .expect("valid directive"),
+        );
+    let llm_file = Mutex::new(RotatingFile::new(
+        paths::logs_dir().join(LLM_LOG_FILE),
+        rotation,
+    ));
+    let ansi = io::stderr().is_terminal();
+    let (stderr, llm) = match format {
+        LogFormat::Text => (
+            fmt::layer().with_writer(io::stderr).with_ansi(ansi).boxed(),
+            fmt::layer().with_writer(llm_file).with_ansi(false).boxed(),
+        ),
+        LogFormat::Json => (
+            fmt::layer().json().with_writer(io::stderr).boxed(),
+            fmt::layer().json().with_writer(llm_file).boxed(),
+        ),
+    };
+    let _ = tracing_subscriber::registry()
+        .with(stderr.with_filter(filter))
+        .with(llm.with_filter(Targets::new().with_target(LLM_TARGET, Level::INFO)))
+        .try_init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn llm_log_rotates_by_size_and_keeps_a_bounded_history() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("harimu-logs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("llm.log");
+        let rotation = LogRotation {
+            max_bytes: 100,
+            max_age_hours: 0,
+            keep: 2,
+        };
+        let mut log = RotatingFile::new(&path, rotation);
+        for record in ["a", "b", "c", "d"] {
+            log.write_all(record.repeat(60).as_bytes()).unwrap();
+        }
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("llm.log"), "d".repeat(60));
+        assert_eq!(read("llm.log.1"), "c".repeat(60));
+        assert_eq!(read("llm.log.2"), "b".repeat(60));
+        assert!(!dir.join("llm.log.3").exists());
+
+        // Picking up an existing file counts what it already holds.
+        let mut reopened = RotatingFile::new(&path, rotation);
+        reopened.write_all(b"short").unwrap();
+        assert_eq!(read("llm.log"), format!("{}short", "d".repeat(60)));
+        reopened.write_all("e".repeat(60).as_bytes()).unwrap();
+        assert_eq!(read("llm.log"), "e".repeat(60));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::inventory::ItemKind;
+use crate::modules::ore::OreKind;
+use crate::modules::vm::{AgentId, Qi};
+
+/// Ticks an order rests on the book before it expires and its escrow goes back.
+pub const ORDER_TTL_TICKS: u64 = 100;
+/// Fills the market remembers; the oldest go first.
+pub const MARKET_HISTORY_LIMIT: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+impl fmt::Display for OrderSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderSide::Buy => write!(f, "buy"),
+            OrderSide::Sell => write!(f, "sell"),
+        }
+    }
+}
+
+impl FromStr for OrderSide {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "buy" | "bid" => Ok(OrderSide::Buy),
+            "sell" | "ask" => Ok(OrderSide::Sell),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An order resting on the book for `amount` more units of `ore` at `price` Qi each. The
+/// market holds its escrow until it fills or expires: the ore of a sell, `amount * price` Qi
+/// of a buy.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Order {
+    pub id: u64,
+    pub agent_id: AgentId,
+    pub side: OrderSide,
+    pub ore: OreKind,
+    pub amount: Qi,
+    pub price: Qi,
+    pub placed_at: u64,
+}
+
+impl Order {
+    /// What the market holds for the order's remaining amount.
+    pub fn escrow(&self) -> (ItemKind, Qi) {
+        match self.side {
+            OrderSide::Buy => (ItemKind::Qi, self.amount * self.price),
+            OrderSide::Sell => (self.ore.into(), self.amount),
+        }
+    }
+}
+
+/// A trade between a buy and a sell order. The buyer gets `amount` ore plus `refund` Qi, the
+/// difference between its bid and `price`; the seller gets `amount * price` Qi.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fill {
+    pub tick: u64,
+    pub buyer: AgentId,
+    pub seller: AgentId,
+    pub ore: OreKind,
+    pub amount: Qi,
+    pub price: Qi,
+    pub refund: Qi,
+    pub buy_order: u64,
+    pub sell_order: u64,
+}
+
+/// The order book for ore priced in Qi. It only tracks orders and escrow amounts; the world
+/// moves the items when orders are posted, filled and expire.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Market {
+    next_order_id: u64,
+    orders: Vec<Order>,
+    history: VecDeque<Fill>,
+}
+
+impl Market {
+    /// Resting orders in the order they were posted.
+    pub fn orders(&self) -> &[Order] {
+        &self.orders
+    }
+
+    /// Recent fills, oldest first.
+    pub fn history(&self) -> &VecDeque<Fill> {
+        &self.history
+    }
+
+    /// The `side` orders for `ore` in matching order: best price first, then oldest.
+    pub fn book(&self, ore: OreKind, side: OrderSide) -> Vec<&Order> {
+        let mut orders: Vec<&Order> = self
+            .orders
+            .iter()
+            .filter(|o| o.ore == ore && o.side == side)
+            .collect();
+        orders.sort_by_key(|o| match side {
+            OrderSide::Buy => (Qi::MAX - o.price, o.id),
+            OrderSide::Sell => (o.price, o.id),
+        });
+        orders
+    }
+
+    /// Qi held for buy orders.
+    pub fn escrowed_qi(&self) -> u64 {
+        self.orders
+            .iter()
+            .map(Order::escrow)
+            .filter(|(item, _)| *item == ItemKind::Qi)
+            .map(|(_, amount)| amount as u64)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty() && self.history.is_empty() && self.next_order_id == 0
+    }
+
+    /// Rest a new order on the book; the caller has taken its escrow.
+    pub(crate) fn post(
+        &mut self,
+        agent_id: AgentId,
+        side: OrderSide,
+        ore: OreKind,
+        amount: Qi,
+        price: Qi,
+        tick: u64,
+    ) -> u64 {
+        self.next_order_id += 1;
+        self.orders.push(Order {
+            id: self.next_order_id,
+            agent_id,
+            side,
+            ore,
+            amount,
+            price,
+            placed_at: tick,
+        });
+        self.next_order_id
+    }
+
+    pub(crate) fn next_order_id(&self) -> u64 {
+        self.next_order_id
+    }
+
+    /// Drop the orders posted since `next_order_id` was current, as if never posted; their
+    /// escrow is the caller's to return.
+    pub(crate) fn withdraw_after(&mut self, next_order_id: u64) {
+        while self.orders.last().is_some_and(|o| o.id > next_order_id) {
+            self.orders.pop();
+        }
+        self.next_order_id = next_order_id;
+    }
+
+    /// Take the orders `expired` picks off the book, for their escrow to be returned.
+    pub(crate) fn remove_where(&mut self, expired: impl Fn(&Order) -> bool) -> Vec<Order> {
+        let (removed, kept) = self.orders.drain(..).partition(|o| expired(o));
+        self.orders = kept;
+        removed
+    }
+
+    /// Match crossing orders in price-time priority until no bid meets an ask. Each trade runs
+    /// at the price of whichever order rested first.
+    pub(crate) fn match_orders(&mut self, tick: u64) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let mut ores: Vec<OreKind> = self.orders.iter().map(|o| o.ore).collect();
+        ores.sort_by_key(|ore| ore.label());
+        ores.dedup();
+        for ore in ores {
+            let best = |market: &Self, side| market.book(ore, side).first().map(|o| (*o).clone());
+            while let (Some(bid), Some(ask)) =
+                (best(self, OrderSide::Buy), best(self, OrderSide::Sell))
+            {
+                if bid.price < ask.price {
+                    break;
+                }
+                let price = if bid.id < ask.id {
+                    bid.price
+                } else {
+                    ask.price
+                };
+                let amount = bid.amount.min(ask.amount);
+                let fill = Fill {
+                    tick,
+                    buyer: bid.agent_id,
+                    seller: ask.agent_id,
+                    ore,
+                    amount,
+                    price,
+                    refund: (bid.price - price) * amount,
+                    buy_order: bid.id,
+                    sell_order: ask.id,
+                };
+                for order in &mut self.orders {
+                    if order.id == bid.id || order.id == ask.id {
+                        order.amount -= amount;
+                    }
+                }
+                self.orders.retain(|o| o.amount > 0);
+                if self.history.len() >= MARKET_HISTORY_LIMIT {
+                    self.history.pop_front();
+                }
+                self.history.push_back(fill.clone());
+                fills.push(fill);
+            }
+        }
+        fills
+    }
+}
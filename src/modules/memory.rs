@@ -0,0 +1,100 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::modules::agent::{ArchivedNote, BrainMemory};
+use crate::modules::paths;
+
+/// Subdirectory of the data directory holding one memory file per agent.
+const MEMORY_DIR: &str = "memory";
+
+/// Most notes an agent keeps archived for recall; the oldest go first.
+pub const ARCHIVE_LIMIT: usize = 512;
+/// Archived notes recalled into each prompt when vector memory is on and no count is given.
+pub const DEFAULT_RECALL: u64 = 4;
+
+pub fn memory_dir() -> PathBuf {
+    paths::data_dir().join(MEMORY_DIR)
+}
+
+/// `.harimu/memory/<agent>.json`; characters other than letters, digits, `-` and `_` in the
+/// agent's name become `_`.
+pub fn memory_path(agent: &str) -> PathBuf {
+    let file: String = agent
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    memory_dir().join(format!("{}.json", file))
+}
+
+/// The memory `agent` ended its last run with, or `None` if it has none stored.
+pub fn load_memory(agent: &str) -> io::Result<Option<BrainMemory>> {
+    let path = memory_path(agent);
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    serde_json::from_slice(&data).map(Some).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "failed to parse {}; clear it with `harimu agent memory {} --clear`: {}",
+                path.display(),
+                agent,
+                e
+            ),
+        )
+    })
+}
+
+pub fn save_memory(agent: &str, memory: &BrainMemory) -> io::Result<()> {
+    fs::create_dir_all(memory_dir())?;
+    let json = serde_json::to_vec_pretty(memory)?;
+    paths::write_atomic(&memory_path(agent), &json)
+}
+
+/// Forget what `agent` remembers, backup included. Returns whether there was anything stored.
+pub fn clear_memory(agent: &str) -> io::Result<bool> {
+    let path = memory_path(agent);
+    let removed = |path: &Path| match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    };
+    removed(&paths::backup_file_path(&path))?;
+    removed(&path)
+}
+
+/// Cosine similarity of two vectors; `None` when their lengths differ or either is zero.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() || a.is_empty() {
+        return None;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let scale = norm(a) * norm(b);
+    (scale > 0.0).then(|| dot / scale)
+}
+
+/// The `k` archived notes most similar to `query`, most similar first; ties go to the newer
+/// note. Notes without a comparable vector are skipped.
+pub fn most_relevant(archive: &[ArchivedNote], query: &[f32], k: usize) -> Vec<String> {
+    let mut scored: Vec<(f32, usize)> = archive
+        .iter()
+        .enumerate()
+        .filter_map(|(index, note)| Some((cosine_similarity(&note.vector, query)?, index)))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.cmp(&a.1)));
+    scored
+        .into_iter()
+        .take(k)
+        .map(|(_, index)| archive[index].note.clone())
+        .collect()
+}
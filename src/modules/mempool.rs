@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::vm::ActionRequest;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Per-identity caps on submitted action requests; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    pub per_tick: Option<u32>,
+    pub per_minute: Option<u32>,
+}
+
+/// Which quota a submission ran into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaExceeded {
+    PerTick { identity: String, limit: u32 },
+    PerMinute { identity: String, limit: u32 },
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaExceeded::PerTick { identity, limit } => {
+                write!(
+                    f,
+                    "{} is over its quota of {} actions per tick",
+                    identity, limit
+                )
+            }
+            QuotaExceeded::PerMinute { identity, limit } => {
+                write!(
+                    f,
+                    "{} is over its quota of {} actions per minute",
+                    identity, limit
+                )
+            }
+        }
+    }
+}
+
+/// Submission counters for one identity (an access token id, agent or client address).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmitterStats {
+    pub accepted: u64,
+    pub rejected_per_tick: u64,
+    pub rejected_per_minute: u64,
+}
+
+#[derive(Debug, Default)]
+struct Submitter {
+    stats: SubmitterStats,
+    tick: u64,
+    this_tick: u32,
+    recent: VecDeque<Instant>,
+}
+
+/// Action requests submitted from outside the tick loop (e.g. over HTTP), waiting for the
+/// next tick. Quotas are checked on submission, so spam never reaches `Vm::step`.
+#[derive(Debug, Default)]
+pub struct Mempool {
+    limits: QuotaLimits,
+    pending: Vec<ActionRequest>,
+    submitters: BTreeMap<String, Submitter>,
+}
+
+impl Mempool {
+    pub fn new(limits: QuotaLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub fn limits(&self) -> QuotaLimits {
+        self.limits
+    }
+
+    /// Queue `request` from `identity` for tick `tick`, unless that takes the identity over a
+    /// quota. `now` is the submission time, used for the per-minute window.
+    pub fn submit(
+        &mut self,
+        identity: &str,
+        request: ActionRequest,
+        tick: u64,
+        now: Instant,
+    ) -> Result<(), QuotaExceeded> {
+        let submitter = self.submitters.entry(identity.to_string()).or_default();
+        if submitter.tick != tick {
+            submitter.tick = tick;
+            submitter.this_tick = 0;
+        }
+        while submitter
+            .recent
+            .front()
+            .is_some_and(|at| now.saturating_duration_since(*at) >= MINUTE)
+        {
+            submitter.recent.pop_front();
+        }
+
+        if let Some(limit) = self.limits.per_tick
+            && submitter.this_tick >= limit
+        {
+            submitter.stats.rejected_per_tick += 1;
+            return Err(QuotaExceeded::PerTick {
+                identity: identity.to_string(),
+                limit,
+            });
+        }
+        if let Some(limit) = self.limits.per_minute
+            && submitter.recent.len() >= limit as usize
+        {
+            submitter.stats.rejected_per_minute += 1;
+            return Err(QuotaExceeded::PerMinute {
+                identity: identity.to_string(),
+                limit,
+            });
+        }
+
+        submitter.this_tick += 1;
+        submitter.recent.push_back(now);
+        submitter.stats.accepted += 1;
+        self.pending.push(request);
+        Ok(())
+    }
+
+    pub fn pending(&self) -> &[ActionRequest] {
+        &self.pending
+    }
+
+    /// Take every queued request, in submission order, to pass to `Vm::step`.
+    pub fn drain(&mut self) -> Vec<ActionRequest> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn stats(&self, identity: &str) -> Option<&SubmitterStats> {
+        self.submitters.get(identity).map(|s| &s.stats)
+    }
+
+    /// Counters for every identity that has submitted, by identity.
+    pub fn all_stats(&self) -> impl Iterator<Item = (&str, &SubmitterStats)> {
+        self.submitters
+            .iter()
+            .map(|(identity, s)| (identity.as_str(), &s.stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::vm::Action;
+
+    #[test]
+    fn mempool_quotas_reject_and_count_spam() {
+        let mut pool = Mempool::new(QuotaLimits {
+            per_tick: Some(2),
+            per_minute: Some(3),
+        });
+        let idle = |agent| ActionRequest::new(agent, Action::Idle);
+        let start = Instant::now();
+
+        assert!(pool.submit("bot", idle(1), 1, start).is_ok());
+        assert!(pool.submit("bot", idle(1), 1, start).is_ok());
+        assert!(matches!(
+            pool.submit("bot", idle(1), 1, start),
+            Err(QuotaExceeded::PerTick { limit: 2, .. })
+        ));
+        assert!(pool.submit("player", idle(2), 1, start).is_ok());
+        assert!(pool.submit("bot", idle(1), 2, start).is_ok());
+        assert!(matches!(
+            pool.submit("bot", idle(1), 3, start + Duration::from_secs(30)),
+            Err(QuotaExceeded::PerMinute { limit: 3, .. })
+        ));
+        assert!(
+            pool.submit("bot", idle(1), 4, start + Duration::from_secs(61))
+                .is_ok()
+        );
+
+        let stats = pool.stats("bot").unwrap();
+        assert_eq!(
+            (
+                stats.accepted,
+                stats.rejected_per_tick,
+                stats.rejected_per_minute
+            ),
+            (4, 1, 1)
+        );
+        assert_eq!(pool.drain().len(), 5);
+        assert!(pool.pending().is_empty());
+    }
+}
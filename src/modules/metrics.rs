@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::paths;
+use crate::modules::view::epoch_of;
+use crate::modules::vm::{Event, TickResult, WorldStats};
+
+const METRICS_DIR: &str = "metrics";
+
+/// The shape of the world after one tick, as kept in `.harimu/metrics/` for plotting
+/// population and economy curves after a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickMetrics {
+    pub tick: u64,
+    /// Living agents.
+    pub population: usize,
+    pub dead_agents: usize,
+    /// Qi held by agents.
+    pub agent_qi: u64,
+    /// Qi left in Qi nodes.
+    pub node_qi: u64,
+    /// Qi waiting in the recycled pool.
+    pub recycled_qi: u64,
+    /// All the Qi the world holds: agents, nodes and the recycled pool.
+    pub total_qi: u64,
+    pub structures: usize,
+    /// Agents born this tick.
+    pub births: u64,
+    /// Agents that died this tick.
+    pub deaths: u64,
+    /// Requests rejected this tick.
+    pub rejections: u64,
+}
+
+impl TickMetrics {
+    /// The metrics of `result`, read against the world's stats after it was applied.
+    pub fn of(stats: &WorldStats, result: &TickResult) -> Self {
+        let count = |matches: fn(&Event) -> bool| {
+            result.events.iter().filter(|event| matches(event)).count() as u64
+        };
+        Self {
+            tick: result.tick,
+            population: stats.alive_agents,
+            dead_agents: stats.dead_agents,
+            agent_qi: stats.agent_qi,
+            node_qi: stats.node_qi,
+            recycled_qi: stats.recycled_qi,
+            total_qi: stats.agent_qi + stats.node_qi + stats.recycled_qi,
+            structures: stats.structures.values().sum(),
+            births: count(|event| matches!(event, Event::AgentReproduced { .. })),
+            deaths: count(|event| matches!(event, Event::AgentDied { .. })),
+            rejections: result.rejections.len() as u64,
+        }
+    }
+}
+
+pub fn metrics_dir() -> PathBuf {
+    paths::data_dir().join(METRICS_DIR)
+}
+
+/// One JSON-lines file per epoch, so old epochs can be archived or deleted as a whole.
+fn epoch_file(epoch: u64) -> PathBuf {
+    metrics_dir().join(format!("epoch_{:06}.jsonl", epoch))
+}
+
+/// Append one tick's metrics to its epoch's file.
+pub fn append_tick_metrics(metrics: &TickMetrics) -> io::Result<()> {
+    fs::create_dir_all(metrics_dir())?;
+    let mut line = serde_json::to_vec(metrics).map_err(io::Error::other)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(epoch_file(epoch_of(metrics.tick)))?
+        .write_all(&line)
+}
+
+/// Recorded metrics for ticks `from..=to`, oldest first. A tick recorded more than once (the
+/// world was rewound or started fresh) keeps its latest record; a line cut short by a crash is
+/// skipped.
+pub fn load_timeline(from: Option<u64>, to: Option<u64>) -> io::Result<Vec<TickMetrics>> {
+    let from = from.unwrap_or(0);
+    let to = to.unwrap_or(u64::MAX);
+    let mut files: Vec<PathBuf> = match fs::read_dir(metrics_dir()) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    files.sort();
+
+    let mut timeline = BTreeMap::new();
+    for path in files {
+        let data = fs::read_to_string(&path)?;
+        let complete = data.rfind('\n').map_or("", |end| &data[..end]);
+        for line in complete.lines().filter(|line| !line.trim().is_empty()) {
+            let metrics: TickMetrics = serde_json::from_str(line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("failed to parse metrics {}: {}", path.display(), e),
+                )
+            })?;
+            if (from..=to).contains(&metrics.tick) {
+                timeline.insert(metrics.tick, metrics);
+            }
+        }
+    }
+    Ok(timeline.into_values().collect())
+}
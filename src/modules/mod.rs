@@ -1,11 +1,53 @@
+pub mod agenda;
 pub mod agent;
 pub mod agents;
+pub mod analysis;
+pub mod api;
+pub mod auth;
+pub mod backup;
+pub mod config;
+pub mod control;
+pub mod crafting;
+pub mod dashboard;
+pub mod decisions;
+pub mod effects;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod inventory;
+pub mod journal;
+#[cfg(feature = "llama")]
+pub mod llama;
+pub mod lock;
+pub mod logging;
+pub mod market;
+pub mod memory;
+pub mod mempool;
+pub mod metrics;
+pub mod names;
 pub mod ore;
+pub mod pacing;
+pub mod paging;
+pub mod pathfinding;
+pub mod paths;
+pub mod prompts;
 pub mod qi;
+pub mod replay;
+pub mod runs;
+pub mod save;
+pub mod scenario;
+pub mod script;
+pub mod spatial;
 pub mod state;
-pub mod structure;
 pub mod stats;
+pub mod store;
+pub mod stream;
+pub mod structure;
+pub mod templates;
+pub mod terrain;
+pub mod territory;
+pub mod testing;
+pub mod view;
 pub mod vm;
 pub mod wallet;
 pub mod world;
-pub mod view;
+pub mod zones;
@@ -0,0 +1,68 @@
+/// Seeded, syllabic names for spawned entities ("Kavelin", "Oshira"). The same seed always
+/// gives the same name, and no world randomness is consumed, so names never change a run.
+const ONSETS: [&str; 20] = [
+    "b", "d", "f", "g", "h", "k", "l", "m", "n", "p", "r", "s", "t", "v", "z", "th", "sh", "ch",
+    "br", "",
+];
+const VOWELS: [&str; 8] = ["a", "e", "i", "o", "u", "ai", "ei", "ou"];
+const CODAS: [&str; 8] = ["", "", "n", "r", "l", "s", "th", "m"];
+/// Fresh seeds tried before falling back to a numbered variant of the first name.
+const UNIQUE_ATTEMPTS: u64 = 64;
+
+/// SplitMix64 step: spreads nearby seeds (e.g. consecutive ids) across the whole range.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Combine identifying numbers (world seed, parent ids, tick, ...) into one name seed.
+pub fn name_seed(parts: impl IntoIterator<Item = u64>) -> u64 {
+    parts
+        .into_iter()
+        .fold(0x6a09_e667_f3bc_c908, |acc, part| mix(acc ^ part))
+}
+
+/// Name seed for a string such as an agent address.
+pub fn name_seed_from_str(s: &str) -> u64 {
+    name_seed(s.bytes().map(u64::from))
+}
+
+/// Two or three syllables, capitalized.
+pub fn generate_name(seed: u64) -> String {
+    let mut state = mix(seed);
+    let mut next = |len: usize| {
+        state = mix(state);
+        (state % len as u64) as usize
+    };
+    let syllables = 2 + next(2);
+    let mut name = String::new();
+    for _ in 0..syllables {
+        name.push_str(ONSETS[next(ONSETS.len())]);
+        name.push_str(VOWELS[next(VOWELS.len())]);
+    }
+    name.push_str(CODAS[next(CODAS.len())]);
+
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+/// A name for `seed` that `taken` rejects for no existing entity. Tries further seeds first,
+/// then numbers the first candidate ("Kavelin 2").
+pub fn unique_name(seed: u64, taken: impl Fn(&str) -> bool) -> String {
+    for attempt in 0..UNIQUE_ATTEMPTS {
+        let name = generate_name(name_seed([seed, attempt]));
+        if !taken(&name) {
+            return name;
+        }
+    }
+    let base = generate_name(name_seed([seed, 0]));
+    (2u64..)
+        .map(|n| format!("{} {}", base, n))
+        .find(|name| !taken(name))
+        .unwrap_or(base)
+}
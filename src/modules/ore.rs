@@ -1,29 +1,188 @@
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{LazyLock, RwLock};
 
-use clap::ValueEnum;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
-#[serde(rename_all = "lowercase")]
-pub enum OreKind {
-    Qi,
-    Transistor,
+use crate::modules::inventory::ItemKind;
+use crate::modules::paths;
+use crate::modules::vm::{HARVEST_PER_ACTION, Qi};
+
+/// Ores beyond the built-in ones live in this file of the data directory; its sessions read the
+/// same registry.
+pub const DEFAULT_ORES_FILE: &str = "ores.toml";
+
+/// A kind of ore nodes hold, by its id in the ore registry. The registry starts with `QI` and
+/// `TRANSISTOR`; `register_ore` or an `ores.toml` adds more. Ores are saved by label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OreKind(u16);
+
+/// One ore in the registry, e.g. an `[[ores]]` table of `ores.toml`:
+///
+/// ```toml
+/// [[ores]]
+/// label = "iron"
+/// harvest_rate = 2
+/// infusion_cost = 20
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OreDef {
+    pub label: String,
+    /// Most one harvest takes from a node.
+    #[serde(default = "default_harvest_rate")]
+    pub harvest_rate: Qi,
+    /// Wallet Qi charged per unit of capacity when the ore is infused.
+    #[serde(default = "one")]
+    pub infusion_cost: Qi,
+}
+
+fn default_harvest_rate() -> Qi {
+    HARVEST_PER_ACTION
+}
+
+fn one() -> Qi {
+    1
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    label: &'static str,
+    harvest_rate: Qi,
+    infusion_cost: Qi,
+}
+
+static REGISTRY: LazyLock<RwLock<Vec<Entry>>> = LazyLock::new(|| {
+    RwLock::new(vec![
+        Entry {
+            label: "qi",
+            harvest_rate: HARVEST_PER_ACTION,
+            infusion_cost: 1,
+        },
+        Entry {
+            label: "transistor",
+            harvest_rate: HARVEST_PER_ACTION,
+            infusion_cost: 100,
+        },
+    ])
+});
+
+fn entry(ore: OreKind) -> Entry {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry[ore.0 as usize]
 }
 
 impl OreKind {
-    pub const fn label(self) -> &'static str {
-        match self {
-            OreKind::Qi => "qi",
-            OreKind::Transistor => "transistor",
+    pub const QI: OreKind = OreKind(0);
+    pub const TRANSISTOR: OreKind = OreKind(1);
+
+    /// Every registered ore, in id order.
+    pub fn all() -> Vec<OreKind> {
+        let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+        (0..registry.len() as u16).map(OreKind).collect()
+    }
+
+    pub fn id(self) -> u16 {
+        self.0
+    }
+
+    pub fn label(self) -> &'static str {
+        entry(self).label
+    }
+
+    pub fn harvest_rate(self) -> Qi {
+        entry(self).harvest_rate
+    }
+
+    pub fn infusion_cost(self) -> Qi {
+        entry(self).infusion_cost
+    }
+
+    pub fn def(self) -> OreDef {
+        let entry = entry(self);
+        OreDef {
+            label: entry.label.to_string(),
+            harvest_rate: entry.harvest_rate,
+            infusion_cost: entry.infusion_cost,
         }
     }
 }
 
 impl Default for OreKind {
     fn default() -> Self {
-        OreKind::Qi
+        OreKind::QI
+    }
+}
+
+/// Add `def` to the registry, or update the rates of the ore with its label. Fails on an
+/// empty label, one `ItemKind` already parses as another item, or a zero harvest rate.
+pub fn register_ore(def: OreDef) -> Result<OreKind, String> {
+    let label = def.label.trim().to_lowercase();
+    if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!(
+            "ore label '{}' must be letters, digits or _",
+            def.label
+        ));
+    }
+    if def.harvest_rate == 0 {
+        return Err(format!("ore '{}' needs a harvest_rate above 0", label));
     }
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    let existing = registry.iter().position(|e| e.label == label);
+    // A label items parse as something else would load back as that item.
+    if let Some(item) = ItemKind::builtin(&label)
+        && existing.map(|id| ItemKind::from(OreKind(id as u16))) != Some(item)
+    {
+        return Err(format!("ore label '{}' is already an item", label));
+    }
+    if let Some(id) = existing {
+        registry[id].harvest_rate = def.harvest_rate;
+        registry[id].infusion_cost = def.infusion_cost;
+        return Ok(OreKind(id as u16));
+    }
+    let id = u16::try_from(registry.len()).map_err(|_| "too many ores".to_string())?;
+    registry.push(Entry {
+        label: Box::leak(label.into_boxed_str()),
+        harvest_rate: def.harvest_rate,
+        infusion_cost: def.infusion_cost,
+    });
+    Ok(OreKind(id))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OresFile {
+    #[serde(default)]
+    ores: Vec<OreDef>,
+}
+
+pub fn ores_path() -> PathBuf {
+    paths::data_dir().join(DEFAULT_ORES_FILE)
+}
+
+/// Register the ores of the data directory's `ores.toml`. Anything that reads worlds,
+/// snapshots or inventories (the CLI, the Godot viewer) calls this first, since a saved ore
+/// the registry doesn't know fails to load.
+pub fn load_registry() -> Result<Vec<OreKind>, String> {
+    load_ores(&ores_path())
+}
+
+/// Register the `[[ores]]` of `path`; a missing file registers nothing.
+pub fn load_ores(path: &Path) -> Result<Vec<OreKind>, String> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("failed to read {}: {}", path.display(), err)),
+    };
+    let file: OresFile = toml::from_str(&data)
+        .map_err(|e| format!("failed to parse ores file {}: {}", path.display(), e))?;
+    file.ores
+        .into_iter()
+        .map(|def| register_ore(def).map_err(|e| format!("{}: {}", path.display(), e)))
+        .collect()
 }
 
 impl fmt::Display for OreKind {
@@ -33,13 +192,66 @@ impl fmt::Display for OreKind {
 }
 
 impl FromStr for OreKind {
-    type Err = ();
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.trim().to_lowercase().as_str() {
-            "qi" => Ok(OreKind::Qi),
-            "transistor" => Ok(OreKind::Transistor),
-            _ => Err(()),
+        let label = s.trim().to_lowercase();
+        let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+        match registry.iter().position(|e| e.label == label) {
+            Some(id) => Ok(OreKind(id as u16)),
+            None => Err(format!(
+                "unknown ore '{}'; expected {}",
+                label,
+                registry
+                    .iter()
+                    .map(|e| e.label)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+impl Serialize for OreKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.label())
+    }
+}
+
+impl<'de> Deserialize<'de> for OreKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label = String::deserialize(deserializer)?;
+        label.parse().map_err(|e| {
+            serde::de::Error::custom(format!("{}; add it to {}", e, DEFAULT_ORES_FILE))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::inventory::BUILTIN_LABELS;
+
+    #[test]
+    fn ores_cannot_take_a_label_items_parse_as_something_else() {
+        for (label, item) in BUILTIN_LABELS {
+            match label.parse::<OreKind>() {
+                // `qi` and `transistor` are ores too; registering them again changes nothing.
+                Ok(ore) => {
+                    assert_eq!(register_ore(ore.def()), Ok(ore));
+                    assert_eq!(ItemKind::from(ore), item);
+                }
+                Err(_) => {
+                    let def = OreDef {
+                        label: label.to_uppercase(),
+                        harvest_rate: 1,
+                        infusion_cost: 1,
+                    };
+                    let err = register_ore(def).unwrap_err();
+                    assert!(err.contains("already an item"), "{}: {}", label, err);
+                }
+            }
+            assert_eq!(label.parse::<ItemKind>(), Ok(item));
         }
     }
 }
@@ -0,0 +1,179 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Pacing figures persisted with the runtime state so `harimu status` can report them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PacingStats {
+    pub target_tps: f64,
+    pub effective_tps: f64,
+    pub last_tick_ms: u64,
+    pub avg_tick_ms: u64,
+    pub overruns: u64,
+    pub skipped_plans: u64,
+}
+
+/// Measures how long each tick takes and how long to sleep to hold the requested rate.
+#[derive(Debug)]
+pub struct TickPacer {
+    budget: Duration,
+    run_started: Instant,
+    tick_started: Option<Instant>,
+    ticks: u64,
+    busy: Duration,
+    last: Duration,
+    overruns: u64,
+    skipped_plans: u64,
+}
+
+impl TickPacer {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            run_started: Instant::now(),
+            tick_started: None,
+            ticks: 0,
+            busy: Duration::ZERO,
+            last: Duration::ZERO,
+            overruns: 0,
+            skipped_plans: 0,
+        }
+    }
+
+    pub fn begin_tick(&mut self) {
+        self.tick_started = Some(Instant::now());
+    }
+
+    /// Close the current tick and return how long it took (planning + step + persistence).
+    pub fn end_tick(&mut self) -> Duration {
+        let elapsed = self
+            .tick_started
+            .take()
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        self.record_tick(elapsed);
+        elapsed
+    }
+
+    fn record_tick(&mut self, elapsed: Duration) {
+        self.ticks = self.ticks.saturating_add(1);
+        self.busy += elapsed;
+        self.last = elapsed;
+        if self.is_over_budget() {
+            self.overruns = self.overruns.saturating_add(1);
+        }
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Whether the most recent tick took longer than the tick interval.
+    pub fn is_over_budget(&self) -> bool {
+        self.budget > Duration::ZERO && self.last > self.budget
+    }
+
+    /// Whether the next tick should reuse the last plans instead of planning again: only when
+    /// asked to hold the rate and the last tick overran.
+    pub fn should_skip_planning(&self, hold_rate: bool) -> bool {
+        hold_rate && self.is_over_budget()
+    }
+
+    pub fn note_skipped_plan(&mut self) {
+        self.skipped_plans = self.skipped_plans.saturating_add(1);
+    }
+
+    /// Sleep left in the interval after the work of the last tick; zero when behind.
+    pub fn remaining(&self) -> Duration {
+        self.budget.saturating_sub(self.last)
+    }
+
+    pub fn stats(&self) -> PacingStats {
+        self.stats_over(self.run_started.elapsed())
+    }
+
+    /// `stats` as if the run had lasted `wall`.
+    fn stats_over(&self, wall: Duration) -> PacingStats {
+        let wall = wall.as_secs_f64();
+        let target_tps = if self.budget > Duration::ZERO {
+            1.0 / self.budget.as_secs_f64()
+        } else {
+            0.0
+        };
+        let effective_tps = if wall > 0.0 {
+            self.ticks as f64 / wall
+        } else {
+            0.0
+        };
+        let avg_tick_ms = if self.ticks > 0 {
+            (self.busy.as_millis() / self.ticks as u128) as u64
+        } else {
+            0
+        };
+
+        PacingStats {
+            target_tps,
+            effective_tps,
+            last_tick_ms: self.last.as_millis() as u64,
+            avg_tick_ms,
+            overruns: self.overruns,
+            skipped_plans: self.skipped_plans,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_tps_counts_ticks_over_the_run() {
+        let mut pacer = TickPacer::new(Duration::from_millis(250));
+        for _ in 0..4 {
+            pacer.record_tick(Duration::from_millis(100));
+        }
+        let stats = pacer.stats_over(Duration::from_secs(2));
+        assert_eq!(stats.target_tps, 4.0);
+        assert_eq!(stats.effective_tps, 2.0);
+        assert_eq!((stats.last_tick_ms, stats.avg_tick_ms), (100, 100));
+        assert_eq!(stats.overruns, 0);
+        assert_eq!(TickPacer::new(Duration::ZERO).stats().target_tps, 0.0);
+    }
+
+    #[test]
+    fn a_tick_past_its_budget_is_an_overrun() {
+        let mut pacer = TickPacer::new(Duration::from_millis(100));
+        pacer.record_tick(Duration::from_millis(40));
+        assert!(!pacer.is_over_budget());
+        assert_eq!(pacer.remaining(), Duration::from_millis(60));
+
+        pacer.record_tick(Duration::from_millis(150));
+        assert!(pacer.is_over_budget());
+        assert_eq!(pacer.remaining(), Duration::ZERO);
+        let stats = pacer.stats_over(Duration::from_secs(1));
+        assert_eq!(
+            (stats.overruns, stats.last_tick_ms, stats.avg_tick_ms),
+            (1, 150, 95)
+        );
+
+        // Without an interval nothing is ever over budget.
+        let mut unpaced = TickPacer::new(Duration::ZERO);
+        unpaced.record_tick(Duration::from_secs(5));
+        assert!(!unpaced.is_over_budget());
+    }
+
+    #[test]
+    fn planning_is_skipped_only_after_an_overrun_when_holding_the_rate() {
+        let mut pacer = TickPacer::new(Duration::from_millis(100));
+        assert!(!pacer.should_skip_planning(true));
+        pacer.record_tick(Duration::from_millis(150));
+        assert!(pacer.should_skip_planning(true));
+        assert!(!pacer.should_skip_planning(false));
+        pacer.note_skipped_plan();
+        assert_eq!(pacer.stats().skipped_plans, 1);
+
+        // Caught up: the next tick plans again.
+        pacer.record_tick(Duration::from_millis(20));
+        assert!(!pacer.should_skip_planning(true));
+    }
+}
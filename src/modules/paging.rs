@@ -0,0 +1,93 @@
+use std::io;
+
+use crate::modules::store::{Store, ensure_writable, open_store};
+use crate::modules::vm::{ColdZone, Vm, Zone};
+
+/// What one `page_zones` call moved.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PagingReport {
+    pub loaded: usize,
+    pub evicted: usize,
+}
+
+/// The store document holding an evicted zone, e.g. `cold_zone_3_0_-2.json`.
+fn document_name(zone: Zone) -> String {
+    format!("cold_zone_{}_{}_{}.json", zone.x, zone.y, zone.z)
+}
+
+fn load_zone(store: &dyn Store, zone: Zone) -> io::Result<ColdZone> {
+    let name = document_name(zone);
+    let data = store.read(&name)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "evicted zone ({}, {}, {}) is missing from {}",
+                zone.x,
+                zone.y,
+                zone.z,
+                store.location(&name)
+            ),
+        )
+    })?;
+    serde_json::from_slice(&data).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to parse {}: {}", store.location(&name), e),
+        )
+    })
+}
+
+/// Load the evicted zones within `radius` zones of a living agent from the configured store,
+/// then write out and drop the zones that no agent is near (see `World::paging_plan`). Run it
+/// before each tick so agents only ever see loaded zones.
+pub fn page_zones(vm: &mut Vm, radius: i32) -> io::Result<PagingReport> {
+    let (load, evict) = vm.world().paging_plan(radius);
+    let mut report = PagingReport::default();
+    if load.is_empty() && evict.is_empty() {
+        return Ok(report);
+    }
+    let store = open_store()?;
+    for zone in load {
+        let cold = load_zone(store.as_ref(), zone)?;
+        vm.world_mut()
+            .restore_zone(cold)
+            .map_err(io::Error::other)?;
+        report.loaded += 1;
+    }
+    if !evict.is_empty() {
+        ensure_writable("evicting zones")?;
+    }
+    for zone in evict {
+        let Some(cold) = vm.world_mut().evict_zone(zone) else {
+            continue;
+        };
+        let written = serde_json::to_vec(&cold)
+            .map_err(io::Error::from)
+            .and_then(|json| store.write(&document_name(zone), &json));
+        if let Err(err) = written {
+            // Keep the zone in memory rather than lose it.
+            vm.world_mut()
+                .restore_zone(cold)
+                .map_err(io::Error::other)?;
+            return Err(err);
+        }
+        report.evicted += 1;
+    }
+    Ok(report)
+}
+
+/// Load every evicted zone back, e.g. when a run starts without paging. Returns how many.
+pub fn restore_all_zones(vm: &mut Vm) -> io::Result<usize> {
+    let zones = vm.world().cold_zones();
+    if zones.is_empty() {
+        return Ok(0);
+    }
+    let store = open_store()?;
+    for zone in &zones {
+        let cold = load_zone(store.as_ref(), *zone)?;
+        vm.world_mut()
+            .restore_zone(cold)
+            .map_err(io::Error::other)?;
+    }
+    Ok(zones.len())
+}
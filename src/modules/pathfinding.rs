@@ -0,0 +1,84 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use crate::modules::vm::Position;
+
+/// Nodes A* may expand before giving up; keeps a hopeless search from stalling a tick.
+pub const PATH_SEARCH_LIMIT: usize = 4096;
+
+pub(crate) fn chebyshev(a: Position, b: Position) -> u32 {
+    let dx = (a.x - b.x).unsigned_abs();
+    let dy = (a.y - b.y).unsigned_abs();
+    let dz = (a.z - b.z).unsigned_abs();
+    dx.max(dy).max(dz)
+}
+
+fn neighbors(pos: Position) -> impl Iterator<Item = Position> {
+    (-1..=1).flat_map(move |dx| {
+        (-1..=1).flat_map(move |dy| {
+            (-1..=1)
+                .filter(move |dz| (dx, dy, *dz) != (0, 0, 0))
+                .map(move |dz| pos.offset(dx, dy, dz))
+        })
+    })
+}
+
+/// Shortest path over unit steps in any of the 26 directions, excluding `start` and ending at
+/// `goal`. `passable` is asked about every cell the path may enter, including `goal`.
+pub fn find_path(
+    start: Position,
+    goal: Position,
+    passable: impl Fn(Position) -> bool,
+) -> Option<VecDeque<Position>> {
+    if start == goal {
+        return Some(VecDeque::new());
+    }
+    if !passable(goal) {
+        return None;
+    }
+
+    // Heap entries are (f, h, seq, position); ties prefer cells closer to the goal, then
+    // insertion order so the result is deterministic.
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Position, Position> = HashMap::new();
+    let mut cost: HashMap<Position, u32> = HashMap::new();
+    let mut seq = 0u64;
+    cost.insert(start, 0);
+    open.push(Reverse((
+        chebyshev(start, goal),
+        chebyshev(start, goal),
+        seq,
+        start,
+    )));
+
+    let mut expanded = 0usize;
+    while let Some(Reverse((_, _, _, current))) = open.pop() {
+        if current == goal {
+            let mut path = VecDeque::new();
+            let mut cursor = goal;
+            while cursor != start {
+                path.push_front(cursor);
+                cursor = came_from[&cursor];
+            }
+            return Some(path);
+        }
+        expanded += 1;
+        if expanded > PATH_SEARCH_LIMIT {
+            return None;
+        }
+
+        let next_cost = cost[&current] + 1;
+        for next in neighbors(current) {
+            if cost.get(&next).is_some_and(|known| *known <= next_cost) || !passable(next) {
+                continue;
+            }
+            cost.insert(next, next_cost);
+            came_from.insert(next, current);
+            let h = chebyshev(next, goal);
+            seq += 1;
+            open.push(Reverse((next_cost + h, h, seq, next)));
+        }
+    }
+
+    None
+}
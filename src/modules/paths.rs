@@ -0,0 +1,313 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use tracing::warn;
+
+const DEFAULT_DATA_DIR: &str = ".harimu";
+const DEFAULT_LOGS_DIR: &str = "logs";
+/// Subdirectory of the data directory holding one data directory per named session.
+const SESSIONS_DIR: &str = "sessions";
+/// Registries a new session starts from; its runtime files (state, world, snapshots, journal,
+/// stats, control socket) begin empty.
+const SESSION_SEED_FILES: &[&str] = &[
+    "config.toml",
+    "agents.json",
+    "wallets.json",
+    "qi_sources.json",
+    "zones.json",
+    "tokens.json",
+    "harimu.db",
+    ".key",
+];
+/// Subdirectories a sandbox copies along with the top-level state files, so a sandboxed run
+/// renders the same prompt templates and starts from the agents' saved memory.
+const SANDBOX_SEED_DIRS: &[&str] = &["prompts", "memory"];
+/// Name of the data directory under `$XDG_DATA_HOME` (or `~/.local/share`).
+const XDG_APP_DIR: &str = "harimu";
+/// Environment variable naming the data directory; `--data-dir` takes precedence.
+pub const HOME_ENV: &str = "HARIMU_HOME";
+
+static DATA_DIR_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Directory holding all persisted runtime state (state, wallets, agents, world stores).
+/// Resolved from `--data-dir`, then `HARIMU_HOME`, then `./.harimu` if it exists, then
+/// `$XDG_DATA_HOME/harimu` (default `~/.local/share/harimu`).
+pub fn data_dir() -> PathBuf {
+    DATA_DIR_OVERRIDE
+        .read()
+        .ok()
+        .and_then(|guard| guard.clone())
+        .or_else(|| {
+            std::env::var_os(HOME_ENV)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+        })
+        .unwrap_or_else(default_data_dir)
+}
+
+/// Use `dir` as the data directory for the rest of the process (the `--data-dir` flag).
+pub fn set_data_dir_override(dir: PathBuf) {
+    set_data_dir(Some(dir));
+}
+
+/// Switch to the data directory of session `name`, `<data dir>/sessions/<name>`, for the rest
+/// of the process (the `--session` flag). With `create`, a session that doesn't exist yet is
+/// made from a copy of the current data directory's registries (agents, wallets, Qi sources,
+/// zones, tokens, config); otherwise the directory is only pointed at.
+pub fn enter_session(name: &str, create: bool) -> io::Result<PathBuf> {
+    validate_session_name(name)?;
+    let root = data_dir();
+    let dir = root.join(SESSIONS_DIR).join(name);
+    if create && !dir.is_dir() {
+        fs::create_dir_all(&dir)?;
+        for file in SESSION_SEED_FILES {
+            let from = root.join(file);
+            if from.is_file() {
+                fs::copy(&from, dir.join(file))?;
+            }
+        }
+    }
+    set_data_dir(Some(dir.clone()));
+    Ok(dir)
+}
+
+/// Names of the sessions under the current data directory, sorted.
+pub fn list_sessions() -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(data_dir().join(SESSIONS_DIR)) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn validate_session_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid session name '{}': use letters, digits, '-' and '_'",
+                name
+            ),
+        ))
+    }
+}
+
+/// `./.harimu` when the current directory already has one (project-local worlds), otherwise
+/// the XDG data directory, so the CLI works from anywhere.
+fn default_data_dir() -> PathBuf {
+    let local = PathBuf::from(DEFAULT_DATA_DIR);
+    if local.is_dir() {
+        return local;
+    }
+    let xdg = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(|home| Path::new(&home).join(".local").join("share"))
+        });
+    match xdg {
+        Some(base) => base.join(XDG_APP_DIR),
+        None => local,
+    }
+}
+
+/// Directory for append-only logs (LLM transcripts): `logs/` in the data directory, so it
+/// follows `--data-dir`, `--session` and the sandbox like every store.
+pub fn logs_dir() -> PathBuf {
+    data_dir().join(DEFAULT_LOGS_DIR)
+}
+
+/// Replace `path` with `data` so a crash never leaves it half written: the data is written and
+/// synced to a temp file beside it, then renamed over it. The previous version is kept as
+/// `<name>.bak`.
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp = sibling_path(path, &format!("{}.tmp", std::process::id()));
+    let written = File::create(&tmp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    if path.exists() {
+        let bak = backup_file_path(path);
+        match fs::remove_file(&bak) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        if fs::hard_link(path, &bak).is_err() {
+            fs::copy(path, &bak)?;
+        }
+    }
+    fs::rename(&tmp, path)
+}
+
+/// The copy of `path` as it was before the last `write_atomic`, e.g. `agents.json.bak`.
+pub fn backup_file_path(path: &Path) -> PathBuf {
+    sibling_path(path, "bak")
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub(crate) fn set_data_dir(dir: Option<PathBuf>) {
+    if let Ok(mut guard) = DATA_DIR_OVERRIDE.write() {
+        *guard = dir;
+    }
+}
+
+/// A throwaway copy of the data directory. While alive, every store reads and writes
+/// inside the copy; dropping it restores the previous data directory and deletes the copy.
+#[derive(Debug)]
+pub struct Sandbox {
+    root: PathBuf,
+    previous: PathBuf,
+}
+
+impl Sandbox {
+    /// Copy the current data directory into a fresh temp dir and redirect persistence to it.
+    pub fn enter() -> io::Result<Self> {
+        let previous = data_dir();
+        let root = std::env::temp_dir().join(format!("harimu-sandbox-{}", std::process::id()));
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        let sandbox_data = root.join(DEFAULT_DATA_DIR);
+        fs::create_dir_all(&sandbox_data)?;
+        copy_state_files(&previous, &sandbox_data)?;
+
+        set_data_dir(Some(sandbox_data));
+        Ok(Self { root, previous })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        set_data_dir(Some(self.previous.clone()));
+        if let Err(err) = fs::remove_dir_all(&self.root) {
+            warn!("failed to remove sandbox {}: {}", self.root.display(), err);
+        }
+    }
+}
+
+/// Copy top-level state files and the `SANDBOX_SEED_DIRS`; tick snapshot history and the pid
+/// file are left behind.
+fn copy_state_files(from: &Path, to: &Path) -> io::Result<()> {
+    let entries = match fs::read_dir(from) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        if path.file_name().and_then(|n| n.to_str()) == Some("runtime.pid") {
+            continue;
+        }
+        if let Some(name) = path.file_name() {
+            fs::copy(&path, to.join(name))?;
+        }
+    }
+
+    for dir in SANDBOX_SEED_DIRS {
+        let source = from.join(dir);
+        if source.is_dir() {
+            copy_dir(&source, &to.join(dir))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)?.flatten() {
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+        if path.is_dir() {
+            copy_dir(&path, &target)?;
+        } else {
+            fs::copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::agent::BrainMemory;
+    use crate::modules::memory::{load_memory, save_memory};
+    use crate::modules::testing::TempDataDir;
+
+    #[test]
+    fn sandboxed_runs_start_from_the_saved_memory() {
+        let data = TempDataDir::new("sandbox-memory");
+        let dir = data.path().to_path_buf();
+        let memory = BrainMemory {
+            notes: vec!["tick 3: action Harvest applied (events=2)".into()],
+            ..BrainMemory::default()
+        };
+        save_memory("Ada", &memory).unwrap();
+
+        let sandbox = Sandbox::enter().unwrap();
+        assert_ne!(data_dir(), dir);
+        assert_eq!(load_memory("Ada").unwrap().unwrap().notes, memory.notes);
+        // What the sandboxed run remembers is thrown away with it.
+        save_memory("Ada", &BrainMemory::default()).unwrap();
+        drop(sandbox);
+
+        assert_eq!(data_dir(), dir);
+        assert_eq!(load_memory("Ada").unwrap().unwrap().notes, memory.notes);
+    }
+
+    #[test]
+    fn atomic_writes_keep_the_previous_version() {
+        let dir = std::env::temp_dir().join(format!("harimu-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("agents.json");
+
+        write_atomic(&path, b"first").unwrap();
+        assert!(!backup_file_path(&path).exists());
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        assert_eq!(std::fs::read(backup_file_path(&path)).unwrap(), b"first");
+
+        let leftovers: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::modules::paths;
+
+/// Subdirectory of the data directory holding the prompt template overrides.
+const PROMPTS_DIR: &str = "prompts";
+pub const SYSTEM_TEMPLATE_FILE: &str = "system.txt";
+pub const USER_TEMPLATE_FILE: &str = "user.txt";
+
+/// The system message of every request.
+pub const DEFAULT_SYSTEM_TEMPLATE: &str = "You are an autonomous agent inside a voxel-based, blockchain-synchronized world. Act to advance this goal: {{goal}}. Choose exactly one action from the provided list, include concrete parameters (e.g., move(x,y,z)), and respond ONLY in TOON with `action: <label>`.";
+/// The user message of every request; `{{input}}` is the whole state as TOON.
+pub const DEFAULT_USER_TEMPLATE: &str = "You are an autonomous agent. Choose exactly one action from `actions`, fill in any needed parameters (move(x,y,z), move_to(x,y,z), scan(radius), build_<structure_kind>, reproduce(partner_id), harvest_<ore_kind>(source_id) or harvest_<ore_kind>(@dx,dy,dz) for the node at that offset, trade(partner_id,offer_ore,offer_amount,want_ore,want_amount), attack(target_id), dig(dx,dy,dz), place_<block_kind>(dx,dy,dz), rest beside your own basic structure to recover Qi, say(to_id,message) to an agent in scan range or say(0,message) to all of them, claim_zone to claim the zone you stand in, set_toll(harvest_qi,build_qi) to charge others in a zone you hold, post_order(buy|sell,ore_kind,amount,price_qi) to trade ore for Qi on the market, craft(recipe) to turn items into a crafted one such as a circuit, repair(structure_id) to restore a worn structure in reach or repair(0) for the most worn one, demolish(structure_id) to tear down one of your structures for part of its cost back, give_qi(to_id,amount) to hand Qi to an agent in your zone or next to you), and reply ONLY in TOON with `action: <label>`. Input:\n{{input}}";
+
+/// Placeholders the system template may use.
+pub const SYSTEM_PLACEHOLDERS: &[&str] = &["goal"];
+/// Placeholders the user template may use. List-like values are one item per line, except
+/// `candidates` (comma separated); `constraints` is TOON and empty unless the last action was
+/// rejected.
+pub const USER_PLACEHOLDERS: &[&str] = &[
+    "goal",
+    "state",
+    "observations",
+    "memory",
+    "recalled_memory",
+    "last_feedback",
+    "constraints",
+    "candidates",
+    "input",
+];
+
+/// The templates the LLM prompts are rendered from, with `{{name}}` placeholders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplates {
+    system: String,
+    user: String,
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self {
+            system: DEFAULT_SYSTEM_TEMPLATE.to_string(),
+            user: DEFAULT_USER_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl PromptTemplates {
+    /// Templates replacing the given defaults; fails on a placeholder the template can't use.
+    pub fn new(system: Option<String>, user: Option<String>) -> Result<Self, String> {
+        let defaults = Self::default();
+        let templates = Self {
+            system: system.unwrap_or(defaults.system),
+            user: user.unwrap_or(defaults.user),
+        };
+        check_placeholders("system", &templates.system, SYSTEM_PLACEHOLDERS)?;
+        check_placeholders("user", &templates.user, USER_PLACEHOLDERS)?;
+        Ok(templates)
+    }
+
+    /// The templates in `.harimu/prompts/`; a missing file keeps its default.
+    pub fn load() -> Result<Self, String> {
+        Self::load_from(&prompts_dir())
+    }
+
+    pub fn load_from(dir: &Path) -> Result<Self, String> {
+        let read = |file: &str| {
+            let path = dir.join(file);
+            match fs::read_to_string(&path) {
+                Ok(text) => Ok(Some(text)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(format!("failed to read {}: {}", path.display(), err)),
+            }
+        };
+        Self::new(read(SYSTEM_TEMPLATE_FILE)?, read(USER_TEMPLATE_FILE)?)
+            .map_err(|e| format!("{}: {}", dir.display(), e))
+    }
+
+    /// Whether both templates are the built-in ones.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn system(&self) -> &str {
+        &self.system
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+}
+
+pub fn prompts_dir() -> PathBuf {
+    paths::data_dir().join(PROMPTS_DIR)
+}
+
+/// `template` with every `{{name}}` (spaces inside the braces allowed) replaced by its value.
+/// Placeholders without a value are left as they are.
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some((before, name, after)) = next_placeholder(rest) {
+        out.push_str(before);
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[before.len()..rest.len() - after.len()]),
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The text before the first `{{name}}` in `text`, the trimmed name, and the text after it.
+fn next_placeholder(text: &str) -> Option<(&str, &str, &str)> {
+    let start = text.find("{{")?;
+    let end = start + 2 + text[start + 2..].find("}}")?;
+    Some((
+        &text[..start],
+        text[start + 2..end].trim(),
+        &text[end + 2..],
+    ))
+}
+
+fn check_placeholders(which: &str, template: &str, known: &[&str]) -> Result<(), String> {
+    let mut rest = template;
+    while let Some((_, name, after)) = next_placeholder(rest) {
+        if !known.contains(&name) {
+            return Err(format!(
+                "unknown placeholder {{{{{}}}}} in the {} template; use {}",
+                name,
+                which,
+                known
+                    .iter()
+                    .map(|k| format!("{{{{{}}}}}", k))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        rest = after;
+    }
+    Ok(())
+}
@@ -1,14 +1,13 @@
-use std::fs;
 use std::io;
-use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
 use crate::modules::ore::OreKind;
+use crate::modules::store;
 use crate::modules::vm::{Position, Qi};
 
 fn default_ore_kind() -> OreKind {
-    OreKind::Qi
+    OreKind::QI
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,44 +26,14 @@ pub struct QiSourceStore {
     pub total_qi_infused: u64,
 }
 
-fn store_dir() -> PathBuf {
-    PathBuf::from(".harimu")
-}
-
-fn store_path() -> PathBuf {
-    store_dir().join("qi_sources.json")
-}
+const QI_SOURCES_DOC: &str = "qi_sources.json";
 
 pub fn load() -> io::Result<QiSourceStore> {
-    let path = store_path();
-    if !path.exists() {
-        return Ok(QiSourceStore::default());
-    }
-
-    let bytes = fs::read(&path)?;
-    if bytes.is_empty() {
-        return Ok(QiSourceStore::default());
-    }
-
-    let store: QiSourceStore = serde_json::from_slice(&bytes).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!(
-                "failed to parse qi source store {}; delete it to reset: {}",
-                path.display(),
-                e
-            ),
-        )
-    })?;
-
-    Ok(store)
+    store::load_document(QI_SOURCES_DOC, "qi source store")
 }
 
-pub fn save(store: &QiSourceStore) -> io::Result<()> {
-    fs::create_dir_all(store_dir())?;
-    let json = serde_json::to_vec_pretty(store)?;
-    fs::write(store_path(), json)?;
-    Ok(())
+pub fn save(sources: &QiSourceStore) -> io::Result<()> {
+    store::save_document(QI_SOURCES_DOC, sources)
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
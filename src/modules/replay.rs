@@ -0,0 +1,162 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::crafting::RecipeBook;
+use crate::modules::effects::EffectSpec;
+use crate::modules::paths;
+use crate::modules::script::Program;
+use crate::modules::vm::{ActionRequest, PersistentWorld, TickResult, Vm, World};
+
+/// One line of the replay log: the world a run started from, then the requests of every tick
+/// with the world hash they produced. Externally tagged (`{"tick": {...}}`): the saved random
+/// stream holds a u128, which serde cannot buffer for internally tagged enums.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayRecord {
+    Start {
+        world: Box<PersistentWorld>,
+        #[serde(default)]
+        firmware: Option<Program>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        structure_effects: Vec<EffectSpec>,
+        #[serde(default, skip_serializing_if = "RecipeBook::is_default")]
+        recipes: RecipeBook,
+    },
+    Tick {
+        tick: u64,
+        requests: Vec<ActionRequest>,
+        world_hash: String,
+    },
+}
+
+/// A tick whose replayed world differs from the recorded one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayMismatch {
+    pub tick: u64,
+    pub expected: String,
+    pub actual: String,
+}
+
+fn log_path() -> PathBuf {
+    paths::data_dir().join("replay.jsonl")
+}
+
+pub fn replay_log_path() -> PathBuf {
+    log_path()
+}
+
+fn append_record(record: &ReplayRecord, truncate: bool) -> io::Result<()> {
+    fs::create_dir_all(paths::data_dir())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(!truncate)
+        .truncate(truncate)
+        .open(log_path())?;
+    let line = serde_json::to_string(record)?;
+    writeln!(file, "{}", line)
+}
+
+/// Start a new replay log from the world as it stands before the first tick of a run.
+pub fn start_replay_log(vm: &Vm) -> io::Result<()> {
+    let record = ReplayRecord::Start {
+        world: Box::new(vm.world().to_persistent()),
+        firmware: vm.firmware().cloned(),
+        structure_effects: vm.effect_specs().to_vec(),
+        recipes: vm.recipes().clone(),
+    };
+    append_record(&record, true)
+}
+
+pub fn append_replay_tick(
+    requests: &[ActionRequest],
+    result: &TickResult,
+    world_hash: String,
+) -> io::Result<()> {
+    let record = ReplayRecord::Tick {
+        tick: result.tick,
+        requests: requests.to_vec(),
+        world_hash,
+    };
+    append_record(&record, false)
+}
+
+pub fn load_replay(path: &Path) -> io::Result<Vec<ReplayRecord>> {
+    let data = fs::read_to_string(path)?;
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("failed to parse replay log {}: {}", path.display(), e),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Steps a VM through a recorded run, checking each tick against the recorded world hash.
+pub struct Replayer {
+    vm: Vm,
+    ticks: std::vec::IntoIter<(u64, Vec<ActionRequest>, String)>,
+}
+
+impl Replayer {
+    pub fn new(records: Vec<ReplayRecord>) -> Result<Self, String> {
+        let mut records = records.into_iter();
+        let Some(ReplayRecord::Start {
+            world,
+            firmware,
+            structure_effects,
+            recipes,
+        }) = records.next()
+        else {
+            return Err("replay log must begin with a start record".into());
+        };
+        let mut vm = Vm::with_world(World::from_persistent(*world)?);
+        vm.set_firmware(firmware);
+        vm.set_recipes(recipes);
+        for spec in structure_effects {
+            vm.add_effect_spec(spec);
+        }
+
+        let mut ticks = Vec::new();
+        for record in records {
+            match record {
+                ReplayRecord::Tick {
+                    tick,
+                    requests,
+                    world_hash,
+                } => ticks.push((tick, requests, world_hash)),
+                ReplayRecord::Start { .. } => {
+                    return Err("replay log holds more than one start record".into());
+                }
+            }
+        }
+        Ok(Self {
+            vm,
+            ticks: ticks.into_iter(),
+        })
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    /// Replay the next recorded tick; `None` once the log is exhausted.
+    pub fn step(&mut self) -> Option<(TickResult, Option<ReplayMismatch>)> {
+        let (tick, requests, expected) = self.ticks.next()?;
+        let result = self.vm.step(&requests);
+        let actual = self.vm.snapshot().meta.world_hash;
+        let mismatch = (result.tick != tick || actual != expected).then_some(ReplayMismatch {
+            tick,
+            expected,
+            actual,
+        });
+        Some((result, mismatch))
+    }
+}
@@ -0,0 +1,374 @@
+use std::io;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::modules::config::EffectiveConfig;
+use crate::modules::metrics::TickMetrics;
+use crate::modules::scenario::ScoreWeights;
+use crate::modules::store;
+use crate::modules::templates::BrainConfig;
+
+const RUNS_DOC: &str = "runs.json";
+/// Points on a run's survival curve: the start and the end of each tenth of the run.
+pub const SURVIVAL_POINTS: usize = 11;
+
+/// Every seed that shapes a run. Passing them back in (`start --seed-from-run <id>`)
+/// reproduces the run's world randomness and LLM retry timing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunSeeds {
+    /// Seeds the world RNG (ore placement, combat rolls, ...); only used when the run built a
+    /// new world rather than resuming a saved one.
+    pub world: u64,
+    /// Seeds the back-off between LLM retries.
+    pub llm_jitter: u64,
+}
+
+/// One `harimu start`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub id: u64,
+    pub started_at: String,
+    pub seeds: RunSeeds,
+    /// The `start` flags the run was launched with (secrets redacted).
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// Labels from `start --tag`, for grouping runs into experiments.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Which brain drove the run's agents.
+    #[serde(default)]
+    pub brain: BrainConfig,
+    /// The settings the run resolved from defaults, config file, environment and flags.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<EffectiveConfig>,
+    /// World tick when the run began; it covers the ticks after this one.
+    #[serde(default)]
+    pub start_tick: u64,
+    /// Last tick the run completed, once it has ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_tick: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<String>,
+    /// Why the run stopped, e.g. `tick limit reached` or `stopped by user`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_reason: Option<String>,
+    /// How the world fared over the run, recorded when it ends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<RunSummary>,
+}
+
+impl RunRecord {
+    pub fn is_finished(&self) -> bool {
+        self.ended_at.is_some()
+    }
+
+    /// Record that the run stopped after `end_tick`.
+    pub fn finish(&mut self, end_tick: u64, reason: impl Into<String>) {
+        self.end_tick = Some(end_tick);
+        self.ended_at = Some(Utc::now().to_rfc3339());
+        self.exit_reason = Some(reason.into());
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RunStore {
+    pub runs: Vec<RunRecord>,
+}
+
+impl RunStore {
+    pub fn load() -> io::Result<Self> {
+        store::load_document(RUNS_DOC, "run registry")
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        store::save_document(RUNS_DOC, self)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&RunRecord> {
+        self.runs.iter().find(|run| run.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut RunRecord> {
+        self.runs.iter_mut().find(|run| run.id == id)
+    }
+
+    pub fn latest(&self) -> Option<&RunRecord> {
+        self.runs.last()
+    }
+
+    /// Append a run starting now; ids count up from 1. The caller fills in its flags, tags,
+    /// brain, config and start tick.
+    pub fn begin(&mut self, seeds: RunSeeds) -> &mut RunRecord {
+        let id = self.runs.iter().map(|run| run.id).max().unwrap_or(0) + 1;
+        self.runs.push(RunRecord {
+            id,
+            started_at: Utc::now().to_rfc3339(),
+            seeds,
+            flags: Vec::new(),
+            tags: Vec::new(),
+            brain: BrainConfig::default(),
+            config: None,
+            start_tick: 0,
+            end_tick: None,
+            ended_at: None,
+            exit_reason: None,
+            summary: None,
+        });
+        let last = self.runs.len() - 1;
+        &mut self.runs[last]
+    }
+}
+
+/// Mark run `id` as ended after `end_tick`, unless it already is.
+pub fn finish_run(
+    id: u64,
+    end_tick: u64,
+    reason: &str,
+    summary: Option<RunSummary>,
+) -> io::Result<()> {
+    let mut runs = RunStore::load()?;
+    if let Some(run) = runs.get_mut(id).filter(|run| !run.is_finished()) {
+        run.finish(end_tick, reason);
+        run.summary = summary;
+        runs.save()?;
+    }
+    Ok(())
+}
+
+/// The seeds recorded for run `id`.
+pub fn seeds_of_run(id: u64) -> Result<RunSeeds, String> {
+    let runs = RunStore::load().map_err(|e| e.to_string())?;
+    runs.get(id)
+        .map(|run| run.seeds)
+        .ok_or_else(|| format!("run {} not found in the run registry", id))
+}
+
+/// The end state and totals of one run, from its ticks' metrics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub ticks: u64,
+    /// Living agents after the run's first tick.
+    pub start_population: usize,
+    pub final_population: usize,
+    pub peak_population: usize,
+    /// Share of the starting population alive at the start and after each tenth of the run,
+    /// so runs of different lengths line up.
+    pub survival: Vec<f64>,
+    pub births: u64,
+    pub deaths: u64,
+    pub rejections: u64,
+    pub final_agent_qi: u64,
+    pub final_total_qi: u64,
+    pub final_structures: usize,
+    /// The scenario's `[score]` of the run, if it had one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+impl RunSummary {
+    /// Summarise the metrics of a run's ticks, oldest first; `None` if it has none.
+    pub fn of(timeline: &[TickMetrics], weights: Option<&ScoreWeights>) -> Option<Self> {
+        let first = timeline.first()?;
+        let last = timeline.last()?;
+        let births = timeline.iter().map(|m| m.births).sum();
+        let deaths = timeline.iter().map(|m| m.deaths).sum();
+        let rejections = timeline.iter().map(|m| m.rejections).sum();
+        let survival = (0..SURVIVAL_POINTS)
+            .map(|point| {
+                let index = point * (timeline.len() - 1) / (SURVIVAL_POINTS - 1);
+                if first.population == 0 {
+                    0.0
+                } else {
+                    timeline[index].population as f64 / first.population as f64
+                }
+            })
+            .collect();
+        Some(Self {
+            ticks: timeline.len() as u64,
+            start_population: first.population,
+            final_population: last.population,
+            peak_population: timeline.iter().map(|m| m.population).max().unwrap_or(0),
+            survival,
+            births,
+            deaths,
+            rejections,
+            final_agent_qi: last.agent_qi,
+            final_total_qi: last.total_qi,
+            final_structures: last.structures,
+            score: weights.map(|w| w.score(last, births, deaths, rejections)),
+        })
+    }
+}
+
+/// Every summarised run carrying one tag, averaged.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagSummary {
+    pub tag: String,
+    /// Runs with the tag and a summary.
+    pub runs: usize,
+    /// Mean score over the runs the scenario scored.
+    pub score: Option<f64>,
+    pub ticks: f64,
+    pub final_population: f64,
+    pub survival: Vec<f64>,
+    pub births: f64,
+    pub deaths: f64,
+    pub rejections: f64,
+    pub final_agent_qi: f64,
+    pub final_total_qi: f64,
+    pub final_structures: f64,
+}
+
+impl RunStore {
+    /// The runs tagged `tag`, oldest first.
+    pub fn tagged<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a RunRecord> + 'a {
+        self.runs
+            .iter()
+            .filter(move |run| run.tags.iter().any(|t| t == tag))
+    }
+
+    /// Average the summaries of the runs tagged `tag`; `None` if none has one.
+    pub fn summarize_tag(&self, tag: &str) -> Option<TagSummary> {
+        let summaries: Vec<&RunSummary> = self
+            .tagged(tag)
+            .filter_map(|run| run.summary.as_ref())
+            .collect();
+        if summaries.is_empty() {
+            return None;
+        }
+        let n = summaries.len() as f64;
+        let mean =
+            |value: fn(&RunSummary) -> f64| summaries.iter().map(|s| value(s)).sum::<f64>() / n;
+        let scores: Vec<f64> = summaries.iter().filter_map(|s| s.score).collect();
+        let survival = (0..SURVIVAL_POINTS)
+            .map(|point| {
+                summaries
+                    .iter()
+                    .map(|s| s.survival.get(point).copied().unwrap_or(0.0))
+                    .sum::<f64>()
+                    / n
+            })
+            .collect();
+        Some(TagSummary {
+            tag: tag.to_string(),
+            runs: summaries.len(),
+            score: (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64),
+            ticks: mean(|s| s.ticks as f64),
+            final_population: mean(|s| s.final_population as f64),
+            survival,
+            births: mean(|s| s.births as f64),
+            deaths: mean(|s| s.deaths as f64),
+            rejections: mean(|s| s.rejections as f64),
+            final_agent_qi: mean(|s| s.final_agent_qi as f64),
+            final_total_qi: mean(|s| s.final_total_qi as f64),
+            final_structures: mean(|s| s.final_structures as f64),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_registry_numbers_runs_and_keeps_their_seeds() {
+        let mut runs = RunStore::default();
+        let first = RunSeeds {
+            world: 7,
+            llm_jitter: 9,
+        };
+        assert_eq!(runs.begin(first).id, 1);
+        let second = runs
+            .begin(RunSeeds {
+                world: 8,
+                llm_jitter: 1,
+            })
+            .id;
+        assert_eq!(second, 2);
+        assert_eq!(runs.get(1).map(|run| run.seeds), Some(first));
+        assert_eq!(runs.latest().map(|run| run.id), Some(2));
+        assert!(runs.get(3).is_none());
+    }
+
+    #[test]
+    fn finished_runs_keep_their_tick_range_and_exit_reason() {
+        let mut runs = RunStore::default();
+        let run = runs.begin(RunSeeds {
+            world: 1,
+            llm_jitter: 2,
+        });
+        run.start_tick = 10;
+        run.flags = vec!["--ticks".into(), "5".into()];
+        assert!(!run.is_finished());
+        run.finish(15, "tick limit reached");
+        assert!(run.is_finished());
+
+        let json = serde_json::to_string(&runs).unwrap();
+        let restored: RunStore = serde_json::from_str(&json).unwrap();
+        let run = restored.get(1).unwrap();
+        assert_eq!((run.start_tick, run.end_tick), (10, Some(15)));
+        assert_eq!(run.exit_reason.as_deref(), Some("tick limit reached"));
+        assert_eq!(run.flags, ["--ticks", "5"]);
+
+        // Records written before the metadata existed still load.
+        let old: RunRecord = serde_json::from_str(
+            r#"{"id":1,"started_at":"2024-01-01T00:00:00Z","seeds":{"world":1,"llm_jitter":2}}"#,
+        )
+        .unwrap();
+        assert!(old.flags.is_empty() && !old.is_finished());
+    }
+
+    #[test]
+    fn tagged_runs_summarise_and_compare_by_tag() {
+        use crate::modules::metrics::TickMetrics;
+        use crate::modules::scenario::ScoreWeights;
+
+        let tick = |tick, population, births, deaths| TickMetrics {
+            tick,
+            population,
+            births,
+            deaths,
+            structures: 1,
+            agent_qi: 10,
+            ..TickMetrics::default()
+        };
+        let weights = ScoreWeights {
+            population: 2.0,
+            deaths: -1.0,
+            ..ScoreWeights::default()
+        };
+        let timeline: Vec<_> = (1..=11)
+            .map(|t| tick(t, 4 - (t as usize) / 4, 0, 0))
+            .collect();
+        let summary = RunSummary::of(&timeline, Some(&weights)).unwrap();
+        assert_eq!(summary.ticks, 11);
+        assert_eq!((summary.start_population, summary.final_population), (4, 2));
+        assert_eq!(summary.survival.len(), 11);
+        assert_eq!(summary.survival[0], 1.0);
+        assert_eq!(summary.survival[10], 0.5);
+        assert_eq!(summary.score, Some(4.0));
+        assert!(RunSummary::of(&[], None).is_none());
+
+        let mut runs = RunStore::default();
+        let seeds = RunSeeds {
+            world: 1,
+            llm_jitter: 1,
+        };
+        for (tag, deaths) in [("v1", 2), ("v1", 4), ("v2", 0)] {
+            let run = runs.begin(seeds);
+            run.tags = vec![tag.to_string()];
+            run.summary = RunSummary::of(&[tick(1, 3, 1, deaths)], Some(&weights));
+        }
+        runs.begin(seeds).tags = vec!["v2".into()];
+
+        let v1 = runs.summarize_tag("v1").unwrap();
+        assert_eq!(v1.runs, 2);
+        assert_eq!(v1.deaths, 3.0);
+        assert_eq!(v1.score, Some(3.0));
+        let v2 = runs.summarize_tag("v2").unwrap();
+        assert_eq!(v2.runs, 1, "a run without a summary is left out");
+        assert_eq!(runs.tagged("v2").count(), 2);
+        assert!(runs.summarize_tag("v3").is_none());
+    }
+}
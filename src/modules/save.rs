@@ -0,0 +1,54 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::modules::paths;
+use crate::modules::vm::PersistentWorld;
+
+fn save_path() -> PathBuf {
+    paths::data_dir().join("world_state.json")
+}
+
+pub fn world_state_file_path() -> PathBuf {
+    save_path()
+}
+
+/// The world saved by the last run, if any.
+pub fn load_world_state() -> io::Result<Option<PersistentWorld>> {
+    let path = save_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(&path)?;
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    let saved = serde_json::from_slice(&bytes).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "failed to parse world state {}; delete it or start with --fresh: {}",
+                path.display(),
+                e
+            ),
+        )
+    })?;
+    Ok(Some(saved))
+}
+
+pub fn save_world_state(saved: &PersistentWorld) -> io::Result<()> {
+    fs::create_dir_all(paths::data_dir())?;
+    let json = serde_json::to_vec(saved)?;
+    paths::write_atomic(&save_path(), &json)?;
+    Ok(())
+}
+
+/// Forget the saved world so the next `start` builds a new one from the stores.
+pub fn clear_world_state() -> io::Result<()> {
+    match fs::remove_file(save_path()) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
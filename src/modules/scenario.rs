@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::agent::ActionArg;
+use crate::modules::metrics::TickMetrics;
+use crate::modules::vm::World;
+
+/// Like the templates file, scenarios live next to the project so they can be version-controlled.
+pub const DEFAULT_SCENARIO_FILE: &str = "scenario.toml";
+
+/// One `[[phase]]` of a scenario file. A phase begins once the run reaches `from_tick` and
+/// every condition it sets holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseConfig {
+    pub name: String,
+    #[serde(default)]
+    pub from_tick: u64,
+    /// Structures standing in the world.
+    #[serde(default)]
+    pub min_structures: Option<usize>,
+    /// Living agents.
+    #[serde(default)]
+    pub min_agents: Option<usize>,
+    /// Candidate actions, in `--action` syntax (e.g. `scan`, `move:1,0,0`, `build_basic`).
+    pub actions: Vec<String>,
+}
+
+/// The `[score]` table of a scenario file: how much each metric at the end of a run counts
+/// towards its score, so runs of the same scenario can be ranked (`harimu runs compare`).
+/// Negative weights penalise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreWeights {
+    /// Per living agent.
+    pub population: f64,
+    /// Per standing structure.
+    pub structures: f64,
+    /// Per Qi held by agents.
+    pub agent_qi: f64,
+    /// Per birth during the run.
+    pub births: f64,
+    /// Per death during the run.
+    pub deaths: f64,
+    /// Per rejected request during the run.
+    pub rejections: f64,
+}
+
+impl ScoreWeights {
+    /// The score of a run that ended at `last` with `births`, `deaths` and `rejections` in all.
+    pub fn score(&self, last: &TickMetrics, births: u64, deaths: u64, rejections: u64) -> f64 {
+        self.population * last.population as f64
+            + self.structures * last.structures as f64
+            + self.agent_qi * last.agent_qi as f64
+            + self.births * births as f64
+            + self.deaths * deaths as f64
+            + self.rejections * rejections as f64
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    #[serde(default, rename = "phase")]
+    phases: Vec<PhaseConfig>,
+    #[serde(default)]
+    score: Option<ScoreWeights>,
+}
+
+/// A phase with its candidate actions parsed.
+#[derive(Debug, Clone)]
+pub struct Phase {
+    pub name: String,
+    pub from_tick: u64,
+    pub min_structures: Option<usize>,
+    pub min_agents: Option<usize>,
+    pub actions: Vec<ActionArg>,
+}
+
+impl Phase {
+    fn from_config(config: PhaseConfig) -> Result<Self, String> {
+        if config.actions.is_empty() {
+            return Err(format!("phase '{}' has no actions", config.name));
+        }
+        let actions = config
+            .actions
+            .iter()
+            .map(|action| {
+                action
+                    .parse()
+                    .map_err(|e| format!("phase '{}': {}", config.name, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name: config.name,
+            from_tick: config.from_tick,
+            min_structures: config.min_structures,
+            min_agents: config.min_agents,
+            actions,
+        })
+    }
+
+    /// Whether the run has reached this phase.
+    pub fn is_reached(&self, world: &World) -> bool {
+        world.tick() >= self.from_tick
+            && self
+                .min_structures
+                .is_none_or(|min| world.structures().len() >= min)
+            && self
+                .min_agents
+                .is_none_or(|min| world.agents().filter(|(_, a)| a.alive).count() >= min)
+    }
+}
+
+/// The candidate actions brains choose from, advancing through the scenario's phases in order.
+/// Phases never go back: once a later phase is reached the run stays in it.
+#[derive(Debug, Clone)]
+pub struct PhasePlan {
+    phases: Vec<Phase>,
+    current: usize,
+    score: Option<ScoreWeights>,
+}
+
+impl PhasePlan {
+    /// A single phase offering `actions` for the whole run.
+    pub fn flat(actions: Vec<ActionArg>) -> Self {
+        Self {
+            phases: vec![Phase {
+                name: "default".into(),
+                from_tick: 0,
+                min_structures: None,
+                min_agents: None,
+                actions,
+            }],
+            current: 0,
+            score: None,
+        }
+    }
+
+    pub fn from_configs(configs: Vec<PhaseConfig>) -> Result<Self, String> {
+        if configs.is_empty() {
+            return Err("a scenario needs at least one [[phase]]".into());
+        }
+        let phases = configs
+            .into_iter()
+            .map(Phase::from_config)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            phases,
+            current: 0,
+            score: None,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read scenario file {}: {}", path.display(), e))?;
+        let file: ScenarioFile = toml::from_str(&data)
+            .map_err(|e| format!("failed to parse scenario file {}: {}", path.display(), e))?;
+        let mut plan = Self::from_configs(file.phases)
+            .map_err(|e| format!("scenario file {}: {}", path.display(), e))?;
+        plan.score = file.score;
+        Ok(plan)
+    }
+
+    /// How the scenario scores a run, if it says.
+    pub fn score(&self) -> Option<&ScoreWeights> {
+        self.score.as_ref()
+    }
+
+    pub fn phases(&self) -> &[Phase] {
+        &self.phases
+    }
+
+    pub fn current(&self) -> &Phase {
+        &self.phases[self.current]
+    }
+
+    pub fn actions(&self) -> &[ActionArg] {
+        &self.current().actions
+    }
+
+    /// Move past every following phase the run has reached; returns the new phase if it changed.
+    pub fn advance(&mut self, world: &World) -> Option<&Phase> {
+        let before = self.current;
+        while self
+            .phases
+            .get(self.current + 1)
+            .is_some_and(|next| next.is_reached(world))
+        {
+            self.current += 1;
+        }
+        (self.current != before).then(|| self.current())
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::inventory::ItemKind;
+use crate::modules::vm::Qi;
+
+/// Longest program a structure accepts, in instructions.
+pub const SCRIPT_MAX_INSTRUCTIONS: usize = 64;
+/// Instructions a structure may execute per tick before it is cut off.
+pub const SCRIPT_MAX_STEPS: usize = 128;
+/// Deepest the value stack may grow.
+pub const SCRIPT_MAX_STACK: usize = 16;
+
+/// The limited view of the world a structure program gets. The VM implements this per structure.
+pub trait ScriptHost {
+    fn tick(&self) -> u64;
+    /// Living agents within scan range of the structure.
+    fn agents_nearby(&self) -> Qi;
+    /// Qi available in sources the structure can reach.
+    fn qi_nearby(&self) -> Qi;
+    fn stored(&self, item: ItemKind) -> Qi;
+    fn owner_qi(&self) -> Qi;
+    /// Pull up to `amount` Qi from reachable sources into storage; returns what was moved.
+    fn collect(&mut self, amount: Qi) -> Qi;
+    /// Hand up to `amount` stored Qi to the owner when they stand next to the structure.
+    fn emit(&mut self, amount: Qi) -> Qi;
+    /// Take up to `amount` of an item from the adjacent owner into storage.
+    fn store(&mut self, item: ItemKind, amount: Qi) -> Qi;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Op {
+    Push(i64),
+    Pop,
+    Dup,
+    Swap,
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Gt,
+    Eq,
+    Not,
+    Jmp(usize),
+    Jz(usize),
+    Halt,
+    Tick,
+    Agents,
+    QiNearby,
+    Stored(ItemKind),
+    OwnerQi,
+    Collect,
+    Emit,
+    Store(ItemKind),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptError {
+    Parse { line: usize, message: String },
+    TooLong(usize),
+    StackUnderflow { pc: usize },
+    StackOverflow { pc: usize },
+    OutOfSteps,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ScriptError::TooLong(len) => write!(
+                f,
+                "program has {} instructions (max {})",
+                len, SCRIPT_MAX_INSTRUCTIONS
+            ),
+            ScriptError::StackUnderflow { pc } => {
+                write!(f, "stack underflow at instruction {}", pc)
+            }
+            ScriptError::StackOverflow { pc } => write!(f, "stack overflow at instruction {}", pc),
+            ScriptError::OutOfSteps => {
+                write!(f, "ran out of steps ({} per tick)", SCRIPT_MAX_STEPS)
+            }
+        }
+    }
+}
+
+/// A compiled structure program. Source is one instruction per line, `#` starts a comment,
+/// and `name:` declares a jump label.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Program {
+    ops: Vec<Op>,
+}
+
+impl Program {
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read program {}: {}", path.display(), e))?;
+        source
+            .parse()
+            .map_err(|e: ScriptError| format!("invalid program {}: {}", path.display(), e))
+    }
+
+    /// Run until `halt`, the end of the program, or the step limit.
+    pub fn run(&self, host: &mut impl ScriptHost) -> Result<(), ScriptError> {
+        let mut stack: Vec<i64> = Vec::new();
+        let mut pc = 0usize;
+        let mut steps = 0usize;
+
+        while let Some(op) = self.ops.get(pc).copied() {
+            steps += 1;
+            if steps > SCRIPT_MAX_STEPS {
+                return Err(ScriptError::OutOfSteps);
+            }
+            let pop = |stack: &mut Vec<i64>| stack.pop().ok_or(ScriptError::StackUnderflow { pc });
+            let mut next = pc + 1;
+
+            let pushed: Option<i64> = match op {
+                Op::Push(value) => Some(value),
+                Op::Pop => {
+                    pop(&mut stack)?;
+                    None
+                }
+                Op::Dup => {
+                    let top = pop(&mut stack)?;
+                    stack.push(top);
+                    Some(top)
+                }
+                Op::Swap => {
+                    let a = pop(&mut stack)?;
+                    let b = pop(&mut stack)?;
+                    stack.push(a);
+                    Some(b)
+                }
+                Op::Add | Op::Sub | Op::Mul | Op::Lt | Op::Gt | Op::Eq => {
+                    let rhs = pop(&mut stack)?;
+                    let lhs = pop(&mut stack)?;
+                    Some(match op {
+                        Op::Add => lhs.saturating_add(rhs),
+                        Op::Sub => lhs.saturating_sub(rhs),
+                        Op::Mul => lhs.saturating_mul(rhs),
+                        Op::Lt => (lhs < rhs) as i64,
+                        Op::Gt => (lhs > rhs) as i64,
+                        _ => (lhs == rhs) as i64,
+                    })
+                }
+                Op::Not => Some((pop(&mut stack)? == 0) as i64),
+                Op::Jmp(target) => {
+                    next = target;
+                    None
+                }
+                Op::Jz(target) => {
+                    if pop(&mut stack)? == 0 {
+                        next = target;
+                    }
+                    None
+                }
+                Op::Halt => return Ok(()),
+                Op::Tick => Some(host.tick() as i64),
+                Op::Agents => Some(host.agents_nearby() as i64),
+                Op::QiNearby => Some(host.qi_nearby() as i64),
+                Op::Stored(item) => Some(host.stored(item) as i64),
+                Op::OwnerQi => Some(host.owner_qi() as i64),
+                Op::Collect => Some(host.collect(amount(pop(&mut stack)?)) as i64),
+                Op::Emit => Some(host.emit(amount(pop(&mut stack)?)) as i64),
+                Op::Store(item) => Some(host.store(item, amount(pop(&mut stack)?)) as i64),
+            };
+
+            if let Some(value) = pushed {
+                if stack.len() >= SCRIPT_MAX_STACK {
+                    return Err(ScriptError::StackOverflow { pc });
+                }
+                stack.push(value);
+            }
+            pc = next;
+        }
+
+        Ok(())
+    }
+}
+
+fn amount(value: i64) -> Qi {
+    value.clamp(0, Qi::MAX as i64) as Qi
+}
+
+impl FromStr for Program {
+    type Err = ScriptError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        // First pass: strip comments, record label targets.
+        let mut lines: Vec<(usize, Vec<&str>)> = Vec::new();
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        for (idx, raw) in source.lines().enumerate() {
+            let line = idx + 1;
+            let text = raw.split('#').next().unwrap_or("").trim();
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(label) = text.strip_suffix(':') {
+                let label = label.trim();
+                if labels.insert(label, lines.len()).is_some() {
+                    return Err(ScriptError::Parse {
+                        line,
+                        message: format!("duplicate label '{}'", label),
+                    });
+                }
+                continue;
+            }
+            lines.push((line, text.split_whitespace().collect()));
+        }
+
+        if lines.len() > SCRIPT_MAX_INSTRUCTIONS {
+            return Err(ScriptError::TooLong(lines.len()));
+        }
+
+        let ops = lines
+            .iter()
+            .map(|(line, words)| parse_op(*line, words, &labels))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Program { ops })
+    }
+}
+
+fn parse_op(line: usize, words: &[&str], labels: &HashMap<&str, usize>) -> Result<Op, ScriptError> {
+    let err = |message: String| ScriptError::Parse { line, message };
+    let (name, args) = words
+        .split_first()
+        .ok_or_else(|| err("empty instruction".into()))?;
+    let arg = || match args {
+        [single] => Ok(*single),
+        _ => Err(err(format!("'{}' takes exactly one argument", name))),
+    };
+    let no_args = |op: Op| {
+        if args.is_empty() {
+            Ok(op)
+        } else {
+            Err(err(format!("'{}' takes no arguments", name)))
+        }
+    };
+    let label = |target: &str| {
+        labels
+            .get(target)
+            .copied()
+            .ok_or_else(|| err(format!("unknown label '{}'", target)))
+    };
+    let item =
+        |raw: &str| ItemKind::from_str(raw).map_err(|_| err(format!("unknown item '{}'", raw)));
+
+    match name.to_lowercase().as_str() {
+        "push" => arg()?
+            .parse::<i64>()
+            .map(Op::Push)
+            .map_err(|_| err("push needs an integer".into())),
+        "pop" => no_args(Op::Pop),
+        "dup" => no_args(Op::Dup),
+        "swap" => no_args(Op::Swap),
+        "add" => no_args(Op::Add),
+        "sub" => no_args(Op::Sub),
+        "mul" => no_args(Op::Mul),
+        "lt" => no_args(Op::Lt),
+        "gt" => no_args(Op::Gt),
+        "eq" => no_args(Op::Eq),
+        "not" => no_args(Op::Not),
+        "jmp" => Ok(Op::Jmp(label(arg()?)?)),
+        "jz" => Ok(Op::Jz(label(arg()?)?)),
+        "halt" => no_args(Op::Halt),
+        "tick" => no_args(Op::Tick),
+        "agents" => no_args(Op::Agents),
+        "qi_nearby" => no_args(Op::QiNearby),
+        "stored" => Ok(Op::Stored(item(arg()?)?)),
+        "owner_qi" => no_args(Op::OwnerQi),
+        "collect" => no_args(Op::Collect),
+        "emit" => no_args(Op::Emit),
+        "store" => Ok(Op::Store(item(arg()?)?)),
+        other => Err(err(format!("unknown instruction '{}'", other))),
+    }
+}
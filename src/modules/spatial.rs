@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::modules::vm::{Position, Zone};
+
+/// Buckets entries of a list by zone so range queries only visit nearby zones.
+/// Entries are stored as indices into the owning `Vec`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ZoneIndex {
+    buckets: HashMap<Zone, Vec<usize>>,
+}
+
+impl ZoneIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, idx: usize, position: Position) {
+        self.buckets.entry(position.zone()).or_default().push(idx);
+    }
+
+    /// Drop entry `idx`, which was inserted at `position`.
+    pub fn remove(&mut self, idx: usize, position: Position) {
+        let zone = position.zone();
+        if let Some(ids) = self.buckets.get_mut(&zone) {
+            ids.retain(|&id| id != idx);
+            if ids.is_empty() {
+                self.buckets.remove(&zone);
+            }
+        }
+    }
+
+    /// Indices of every entry in zones overlapping the cube of `range` around `position`, in
+    /// ascending order. Callers still check the exact range; this only narrows the search.
+    pub fn candidates(&self, position: Position, range: i32) -> Vec<usize> {
+        let range = range.max(0);
+        let lo = position.offset(-range, -range, -range).zone();
+        let hi = position.offset(range, range, range).zone();
+        let zone_count = |lo: i32, hi: i32| (hi - lo + 1) as usize;
+        let cube = zone_count(lo.x, hi.x) * zone_count(lo.y, hi.y) * zone_count(lo.z, hi.z);
+
+        let mut found: Vec<usize> = if cube > self.buckets.len() {
+            // Huge ranges touch more zones than exist; walk the buckets instead.
+            self.buckets
+                .iter()
+                .filter(|(zone, _)| {
+                    (lo.x..=hi.x).contains(&zone.x)
+                        && (lo.y..=hi.y).contains(&zone.y)
+                        && (lo.z..=hi.z).contains(&zone.z)
+                })
+                .flat_map(|(_, ids)| ids.iter().copied())
+                .collect()
+        } else {
+            let mut found = Vec::new();
+            for x in lo.x..=hi.x {
+                for y in lo.y..=hi.y {
+                    for z in lo.z..=hi.z {
+                        if let Some(ids) = self.buckets.get(&Zone { x, y, z }) {
+                            found.extend_from_slice(ids);
+                        }
+                    }
+                }
+            }
+            found
+        };
+        found.sort_unstable();
+        found
+    }
+
+    /// Entries of `items` (the list this index was built over) within `range` of `position`,
+    /// in list order.
+    pub fn within<'a, T>(
+        &self,
+        items: &'a [T],
+        position: Position,
+        range: i32,
+        position_of: impl Fn(&T) -> Position + 'a,
+    ) -> impl Iterator<Item = &'a T> + 'a {
+        self.candidates(position, range)
+            .into_iter()
+            .filter_map(move |idx| items.get(idx))
+            .filter(move |item| position_of(item).within_range(position, range))
+    }
+}
@@ -4,6 +4,9 @@ use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
+use crate::modules::pacing::PacingStats;
+use crate::modules::paths;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Status {
     Initialized,
@@ -16,6 +19,11 @@ pub struct RuntimeState {
     pub status: Status,
     pub last_tick: u64,
     pub message: Option<String>,
+    #[serde(default)]
+    pub pacing: Option<PacingStats>,
+    /// World seed of the most recent `start`; pass it to `--seed` to replay the run.
+    #[serde(default)]
+    pub seed: Option<u64>,
 }
 
 impl Default for RuntimeState {
@@ -24,12 +32,14 @@ impl Default for RuntimeState {
             status: Status::Initialized,
             last_tick: 0,
             message: None,
+            pacing: None,
+            seed: None,
         }
     }
 }
 
 fn state_dir() -> PathBuf {
-    PathBuf::from(".harimu")
+    paths::data_dir()
 }
 
 fn state_path() -> PathBuf {
@@ -74,7 +84,7 @@ pub fn save_state(state: &RuntimeState) -> io::Result<()> {
     let dir = state_dir();
     fs::create_dir_all(&dir)?;
     let json = serde_json::to_vec_pretty(state)?;
-    fs::write(state_path(), json)?;
+    paths::write_atomic(&state_path(), &json)?;
     Ok(())
 }
 
@@ -90,3 +100,26 @@ pub fn set_status(
     save_state(&state)?;
     Ok(state)
 }
+
+/// Like `set_status`, but also records the loop's pacing figures for `harimu status`.
+pub fn set_status_with_pacing(
+    status: Status,
+    last_tick: u64,
+    message: Option<String>,
+    pacing: PacingStats,
+) -> io::Result<RuntimeState> {
+    let mut state = load_state()?.unwrap_or_default();
+    state.status = status;
+    state.last_tick = last_tick;
+    state.message = message;
+    state.pacing = Some(pacing);
+    save_state(&state)?;
+    Ok(state)
+}
+
+pub fn set_seed(seed: u64) -> io::Result<RuntimeState> {
+    let mut state = load_state()?.unwrap_or_default();
+    state.seed = Some(seed);
+    save_state(&state)?;
+    Ok(state)
+}
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
-use std::fs;
-use std::io;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
+use crate::modules::decisions::DecisionRecord;
 use crate::modules::vm::{Action, AgentId};
+use crate::modules::{paths, store};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ActionStats {
@@ -14,61 +16,251 @@ pub struct ActionStats {
     pub build_count: u64,
     pub harvest_count: u64,
     pub reproduce_count: u64,
+    #[serde(default)]
+    pub trade_count: u64,
+    #[serde(default)]
+    pub attack_count: u64,
+    #[serde(default)]
+    pub dig_count: u64,
+    #[serde(default)]
+    pub place_count: u64,
+    #[serde(default)]
+    pub rest_count: u64,
+    #[serde(default)]
+    pub say_count: u64,
+    #[serde(default)]
+    pub claim_count: u64,
+    #[serde(default)]
+    pub toll_count: u64,
+    #[serde(default)]
+    pub order_count: u64,
+    #[serde(default)]
+    pub craft_count: u64,
+    #[serde(default)]
+    pub repair_count: u64,
+    #[serde(default)]
+    pub demolish_count: u64,
+    #[serde(default)]
+    pub give_count: u64,
     pub idle_count: u64,
+    #[serde(default)]
+    pub llm_decisions: u64,
+    #[serde(default)]
+    pub llm_ok_count: u64,
+    #[serde(default)]
+    pub llm_fallback_count: u64,
+    #[serde(default)]
+    pub llm_latency_ms_total: u64,
 }
 
 impl ActionStats {
     pub fn record(&mut self, action: &Action) {
         match action {
-            Action::Move { .. } => self.move_count = self.move_count.saturating_add(1),
+            Action::Move { .. } | Action::MoveTo { .. } => {
+                self.move_count = self.move_count.saturating_add(1)
+            }
             Action::Scan => self.scan_count = self.scan_count.saturating_add(1),
             Action::BuildStructure { .. } => self.build_count = self.build_count.saturating_add(1),
-            Action::HarvestOre { .. } => self.harvest_count = self.harvest_count.saturating_add(1),
-            Action::Reproduce { .. } => self.reproduce_count = self.reproduce_count.saturating_add(1),
+            Action::HarvestOre { .. } | Action::HarvestAt { .. } => {
+                self.harvest_count = self.harvest_count.saturating_add(1)
+            }
+            Action::Reproduce { .. } => {
+                self.reproduce_count = self.reproduce_count.saturating_add(1)
+            }
+            Action::Trade { .. } => self.trade_count = self.trade_count.saturating_add(1),
+            Action::Attack { .. } => self.attack_count = self.attack_count.saturating_add(1),
+            Action::Dig { .. } => self.dig_count = self.dig_count.saturating_add(1),
+            Action::Place { .. } => self.place_count = self.place_count.saturating_add(1),
+            Action::Rest => self.rest_count = self.rest_count.saturating_add(1),
+            Action::Say { .. } => self.say_count = self.say_count.saturating_add(1),
+            Action::ClaimZone => self.claim_count = self.claim_count.saturating_add(1),
+            Action::SetToll { .. } => self.toll_count = self.toll_count.saturating_add(1),
+            Action::PostOrder { .. } => self.order_count = self.order_count.saturating_add(1),
+            Action::Craft { .. } => self.craft_count = self.craft_count.saturating_add(1),
+            Action::Repair { .. } => self.repair_count = self.repair_count.saturating_add(1),
+            Action::DemolishStructure { .. } => {
+                self.demolish_count = self.demolish_count.saturating_add(1)
+            }
+            Action::GiveQi { .. } => self.give_count = self.give_count.saturating_add(1),
             Action::Idle => self.idle_count = self.idle_count.saturating_add(1),
         }
     }
+
+    pub fn record_llm(&mut self, llm_ok: bool, latency_ms: u64) {
+        self.llm_decisions = self.llm_decisions.saturating_add(1);
+        if llm_ok {
+            self.llm_ok_count = self.llm_ok_count.saturating_add(1);
+        } else {
+            self.llm_fallback_count = self.llm_fallback_count.saturating_add(1);
+        }
+        self.llm_latency_ms_total = self.llm_latency_ms_total.saturating_add(latency_ms);
+    }
+
+    /// Share of planner decisions that came back from the LLM (0.0 when none were made).
+    pub fn llm_success_rate(&self) -> f64 {
+        if self.llm_decisions == 0 {
+            return 0.0;
+        }
+        self.llm_ok_count as f64 / self.llm_decisions as f64
+    }
+
+    pub fn llm_avg_latency_ms(&self) -> u64 {
+        self.llm_latency_ms_total
+            .checked_div(self.llm_decisions)
+            .unwrap_or(0)
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ActionStatsStore {
     pub per_agent: HashMap<AgentId, ActionStats>,
+    /// Last tick folded into this store; write-ahead log entries up to it are already counted.
+    #[serde(default)]
+    pub flushed_tick: u64,
 }
 
-fn stats_dir() -> PathBuf {
-    PathBuf::from(".harimu")
+const STATS_DOC: &str = "action_stats.json";
+const STATS_WAL: &str = "action_stats.wal";
+/// Ticks between flushes of in-memory stats to `action_stats.json`.
+pub const STATS_FLUSH_TICKS: u64 = 16;
+
+/// One tick's stats, appended to the write-ahead log before the next flush.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WalEntry {
+    tick: u64,
+    #[serde(default)]
+    actions: Vec<(AgentId, Action)>,
+    /// `(agent, llm_ok, latency_ms)` per planner decision.
+    #[serde(default)]
+    llm: Vec<(AgentId, bool, u64)>,
 }
 
-fn stats_path() -> PathBuf {
-    stats_dir().join("action_stats.json")
+impl WalEntry {
+    fn apply(&self, store: &mut ActionStatsStore) {
+        for (agent_id, action) in &self.actions {
+            store.per_agent.entry(*agent_id).or_default().record(action);
+        }
+        for (agent_id, ok, latency_ms) in &self.llm {
+            store
+                .per_agent
+                .entry(*agent_id)
+                .or_default()
+                .record_llm(*ok, *latency_ms);
+        }
+        store.flushed_tick = store.flushed_tick.max(self.tick);
+    }
+}
+
+pub fn stats_wal_path() -> PathBuf {
+    paths::data_dir().join(STATS_WAL)
 }
 
 pub fn reset_action_stats() -> io::Result<()> {
     let store = ActionStatsStore::default();
-    save_action_stats(&store)
+    save_action_stats(&store)?;
+    remove_wal()
 }
 
+/// The saved stats plus every tick logged since the last flush, so a run that died between
+/// flushes still reports all of its ticks. A torn final log line is ignored.
 pub fn load_action_stats() -> io::Result<ActionStatsStore> {
-    let path = stats_path();
-    if !path.exists() {
-        return Ok(ActionStatsStore::default());
+    let mut stats: ActionStatsStore = store::load_document(STATS_DOC, "action stats")?;
+    let wal = match fs::read_to_string(stats_wal_path()) {
+        Ok(wal) => wal,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(stats),
+        Err(err) => return Err(err),
+    };
+    for entry in wal
+        .lines()
+        .filter_map(|line| serde_json::from_str::<WalEntry>(line).ok())
+    {
+        if entry.tick > stats.flushed_tick {
+            entry.apply(&mut stats);
+        }
+    }
+    Ok(stats)
+}
+
+fn remove_wal() -> io::Result<()> {
+    match fs::remove_file(stats_wal_path()) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Action stats for a running loop, kept in memory. Each tick is appended to a small
+/// write-ahead log; the full store is rewritten (atomically) only every `flush_every` ticks
+/// and on `flush`, after which the log is dropped.
+#[derive(Debug)]
+pub struct StatsRecorder {
+    stats: ActionStatsStore,
+    flush_every: u64,
+    unflushed: u64,
+}
+
+impl StatsRecorder {
+    /// Start from the saved stats, recovering any ticks left in the log by a crashed run.
+    pub fn open(flush_every: u64) -> io::Result<Self> {
+        let mut recorder = Self {
+            stats: load_action_stats()?,
+            flush_every: flush_every.max(1),
+            unflushed: 0,
+        };
+        if stats_wal_path().exists() {
+            recorder.flush()?;
+        }
+        Ok(recorder)
+    }
+
+    pub fn stats(&self) -> &ActionStatsStore {
+        &self.stats
     }
 
-    let bytes = fs::read(&path)?;
-    if bytes.is_empty() {
-        return Ok(ActionStatsStore::default());
+    /// Count one tick's successful actions and LLM decisions, flushing when due.
+    pub fn record_tick(
+        &mut self,
+        tick: u64,
+        actions: Vec<(AgentId, Action)>,
+        decisions: &[DecisionRecord],
+    ) -> io::Result<()> {
+        let entry = WalEntry {
+            tick,
+            actions,
+            llm: decisions
+                .iter()
+                .map(|d| (d.agent_id, d.llm_ok, d.latency_ms))
+                .collect(),
+        };
+        entry.apply(&mut self.stats);
+
+        store::ensure_writable("recording action stats")?;
+        fs::create_dir_all(paths::data_dir())?;
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(stats_wal_path())?
+            .write_all(&line)?;
+
+        self.unflushed += 1;
+        if self.unflushed >= self.flush_every {
+            self.flush()?;
+        }
+        Ok(())
     }
 
-    let store: ActionStatsStore = serde_json::from_slice(&bytes)?;
-    Ok(store)
+    /// Save the in-memory stats and drop the log they now cover.
+    pub fn flush(&mut self) -> io::Result<()> {
+        save_action_stats(&self.stats)?;
+        remove_wal()?;
+        self.unflushed = 0;
+        Ok(())
+    }
 }
 
-pub fn save_action_stats(store: &ActionStatsStore) -> io::Result<()> {
-    let dir = stats_dir();
-    fs::create_dir_all(&dir)?;
-    let json = serde_json::to_vec_pretty(store)?;
-    fs::write(stats_path(), json)?;
-    Ok(())
+pub fn save_action_stats(stats: &ActionStatsStore) -> io::Result<()> {
+    store::save_document(STATS_DOC, stats)
 }
 
 pub fn record_successful_actions(
@@ -81,3 +273,79 @@ pub fn record_successful_actions(
         stats.record(&action);
     }
 }
+
+pub fn record_llm_decisions(store: &mut ActionStatsStore, records: &[DecisionRecord]) {
+    for record in records {
+        store
+            .per_agent
+            .entry(record.agent_id)
+            .or_default()
+            .record_llm(record.llm_ok, record.latency_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::testing::TempDataDir;
+
+    fn scans(stats: &ActionStatsStore) -> u64 {
+        stats.per_agent.get(&7).map_or(0, |s| s.scan_count)
+    }
+
+    #[test]
+    fn unflushed_ticks_are_recovered_from_the_log_once() {
+        let _data = TempDataDir::new("stats-wal");
+        let mut recorder = StatsRecorder::open(STATS_FLUSH_TICKS).unwrap();
+        for tick in 1..=3 {
+            recorder
+                .record_tick(tick, vec![(7, Action::Scan)], &[])
+                .unwrap();
+        }
+        let logged = fs::read_to_string(stats_wal_path()).unwrap();
+        // Killed before its first flush: only the log has the ticks.
+        drop(recorder);
+        let recovered = load_action_stats().unwrap();
+        assert_eq!(scans(&recovered), 3);
+        assert_eq!(recovered.flushed_tick, 3);
+
+        // Reopening folds the log into the store and drops it.
+        let mut recorder = StatsRecorder::open(STATS_FLUSH_TICKS).unwrap();
+        assert!(!stats_wal_path().exists());
+        assert_eq!(scans(recorder.stats()), 3);
+        recorder
+            .record_tick(4, vec![(7, Action::Scan)], &[])
+            .unwrap();
+        // A flush that died between saving the store and removing the log leaves ticks the
+        // store already counts in front of the new ones.
+        let tail = fs::read_to_string(stats_wal_path()).unwrap();
+        fs::write(stats_wal_path(), logged + &tail).unwrap();
+        drop(recorder);
+
+        let reopened = StatsRecorder::open(STATS_FLUSH_TICKS).unwrap();
+        assert_eq!(scans(reopened.stats()), 4);
+        assert_eq!(reopened.stats().flushed_tick, 4);
+        assert_eq!(scans(&load_action_stats().unwrap()), 4);
+    }
+
+    #[test]
+    fn a_torn_last_log_line_is_ignored() {
+        let _data = TempDataDir::new("stats-torn-wal");
+        let mut recorder = StatsRecorder::open(STATS_FLUSH_TICKS).unwrap();
+        for tick in 1..=2 {
+            recorder
+                .record_tick(tick, vec![(7, Action::Scan)], &[])
+                .unwrap();
+        }
+        drop(recorder);
+        let mut wal = fs::read_to_string(stats_wal_path()).unwrap();
+        wal.push_str(r#"{"tick":3,"actions":[[7,"#);
+        fs::write(stats_wal_path(), wal).unwrap();
+
+        let recovered = load_action_stats().unwrap();
+        assert_eq!(scans(&recovered), 2);
+        assert_eq!(recovered.flushed_tick, 2);
+        let reopened = StatsRecorder::open(STATS_FLUSH_TICKS).unwrap();
+        assert_eq!(scans(reopened.stats()), 2);
+    }
+}
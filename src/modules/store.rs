@@ -0,0 +1,341 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::modules::config::StartSettings;
+use crate::modules::crafting::Recipe;
+use crate::modules::effects::EffectSpec;
+use crate::modules::inventory::ItemKind;
+use crate::modules::logging::LogRotation;
+use crate::modules::paths;
+use crate::modules::structure::StructureKind;
+use crate::modules::view::SnapshotFormat;
+use crate::modules::vm::Qi;
+
+const CONFIG_FILE: &str = "config.toml";
+/// Set to `1` or `true` to open the data directory read-only (same as `--read-only`).
+pub const READ_ONLY_ENV: &str = "HARIMU_READ_ONLY";
+#[cfg(feature = "sqlite")]
+const DATABASE_FILE: &str = "harimu.db";
+
+/// Where the named stores (wallets, agents, structures, Qi sources, zones, stats) keep their
+/// documents. Each store is one JSON document addressed by its file name, e.g. `agents.json`.
+pub trait Store {
+    /// The stored bytes of `name`, or `None` if it was never written.
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>>;
+    /// Replace the document `name` with `data`.
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()>;
+    /// Where `name` lives, for error messages.
+    fn location(&self, name: &str) -> String;
+    /// Append `items` to the array in field `field` of document `name` without rewriting the
+    /// rest of it. Returns `false` when the backend can't (or doesn't hold `name` yet); the
+    /// caller then rewrites the whole document.
+    fn append_items(&self, _name: &str, _field: &str, _items: &[Vec<u8>]) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    /// One JSON file per store in the data directory
+    #[default]
+    Json,
+    /// A single SQLite database (`harimu.db`); needs a build with `--features sqlite`
+    Sqlite,
+}
+
+/// `.harimu/config.toml`; every field is optional.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StoreConfig {
+    #[serde(default)]
+    pub store: StoreBackend,
+    /// Encoding for world snapshots written from now on (readers accept any format).
+    #[serde(default)]
+    pub snapshot_format: SnapshotFormat,
+    /// Refuse every command that would modify the data directory.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub read_only: bool,
+    /// When `logs/llm.log` rotates.
+    #[serde(default, skip_serializing_if = "LogRotation::is_default")]
+    pub llm_log: LogRotation,
+    /// Defaults for `harimu start`, below environment variables and flags.
+    #[serde(default, skip_serializing_if = "StartSettings::is_empty")]
+    pub start: StartSettings,
+    /// Extra structure effects for `start` and `serve`, on top of the built-in ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub structure_effects: Vec<EffectSpec>,
+    /// Crafting recipes for `start` and `serve`, replacing built-in ones of the same name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub recipes: BTreeMap<String, Recipe>,
+    /// Build costs replacing the built-in ones, e.g. `programmable = { circuit = 1, qi = 1 }`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub build_costs: BTreeMap<StructureKind, BTreeMap<ItemKind, Qi>>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Force read-only mode for this process (the `--read-only` flag).
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+/// Whether the data directory must not be modified: set by `--read-only`, `HARIMU_READ_ONLY`,
+/// or `read_only = true` in `.harimu/config.toml`.
+pub fn is_read_only() -> bool {
+    if READ_ONLY.load(Ordering::Relaxed) {
+        return true;
+    }
+    let from_env = std::env::var(READ_ONLY_ENV)
+        .is_ok_and(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true"));
+    from_env || load_store_config().is_ok_and(|config| config.read_only)
+}
+
+/// Fails with `PermissionDenied` naming `what` when the data directory is read-only.
+pub fn ensure_writable(what: &str) -> io::Result<()> {
+    if !is_read_only() {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "{} is not allowed: {} is read-only (set by --read-only, {} or config.toml)",
+            what,
+            paths::data_dir().display(),
+            READ_ONLY_ENV
+        ),
+    ))
+}
+
+fn config_path() -> PathBuf {
+    paths::data_dir().join(CONFIG_FILE)
+}
+
+pub fn load_store_config() -> io::Result<StoreConfig> {
+    let path = config_path();
+    if !path.exists() {
+        return Ok(StoreConfig::default());
+    }
+    let data = fs::read_to_string(&path)?;
+    toml::from_str(&data).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to parse config {}: {}", path.display(), e),
+        )
+    })
+}
+
+pub fn save_store_config(config: &StoreConfig) -> io::Result<()> {
+    fs::create_dir_all(paths::data_dir())?;
+    let data = toml::to_string(config).map_err(io::Error::other)?;
+    paths::write_atomic(&config_path(), data.as_bytes())
+}
+
+/// JSON files in the data directory, one per document.
+#[derive(Debug)]
+pub struct JsonStore {
+    dir: PathBuf,
+}
+
+impl JsonStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+impl Store for JsonStore {
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(name)) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        paths::write_atomic(&self.path(name), data)
+    }
+
+    fn location(&self, name: &str) -> String {
+        self.path(name).display().to_string()
+    }
+}
+
+/// Every document in one SQLite table, written transactionally. Documents are stored as JSON
+/// text, so they can be queried with SQLite's JSON functions. A document the database has
+/// never seen is read from its JSON file, which migrates existing data on first write.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteStore {
+    path: PathBuf,
+    conn: rusqlite::Connection,
+    fallback: JsonStore,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(DATABASE_FILE);
+        let conn = rusqlite::Connection::open(&path).map_err(sqlite_error)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS documents (
+                 name TEXT PRIMARY KEY,
+                 body TEXT NOT NULL,
+                 updated_at TEXT NOT NULL
+             );",
+        )
+        .map_err(sqlite_error)?;
+        Ok(Self {
+            path,
+            conn,
+            fallback: JsonStore::new(dir),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(err: rusqlite::Error) -> io::Error {
+    io::Error::other(format!("sqlite: {}", err))
+}
+
+#[cfg(feature = "sqlite")]
+impl Store for SqliteStore {
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        use rusqlite::OptionalExtension;
+
+        let body: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT body FROM documents WHERE name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)?;
+        match body {
+            Some(body) => Ok(Some(body.into_bytes())),
+            None => self.fallback.read(name),
+        }
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let body =
+            std::str::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let now = chrono::Utc::now().to_rfc3339();
+        self.conn
+            .execute(
+                "INSERT INTO documents (name, body, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(name) DO UPDATE SET body = excluded.body,
+                     updated_at = excluded.updated_at",
+                (name, body, now),
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    fn location(&self, name: &str) -> String {
+        format!("{} (document {})", self.path.display(), name)
+    }
+
+    fn append_items(&self, name: &str, field: &str, items: &[Vec<u8>]) -> io::Result<bool> {
+        let path = format!("$.{}[#]", field);
+        let now = chrono::Utc::now().to_rfc3339();
+        let tx = self.conn.unchecked_transaction().map_err(sqlite_error)?;
+        for item in items {
+            let item = std::str::from_utf8(item)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let updated = tx
+                .execute(
+                    "UPDATE documents SET body = json_insert(body, ?2, json(?3)), updated_at = ?4
+                     WHERE name = ?1 AND json_type(body, ?5) = 'array'",
+                    (name, &path, item, &now, format!("$.{}", field)),
+                )
+                .map_err(sqlite_error)?;
+            if updated == 0 {
+                return Ok(false);
+            }
+        }
+        tx.commit().map_err(sqlite_error)?;
+        Ok(true)
+    }
+}
+
+/// The store selected by `.harimu/config.toml` (JSON files unless configured otherwise).
+pub fn open_store() -> io::Result<Box<dyn Store>> {
+    open_backend(load_store_config()?.store, &paths::data_dir())
+}
+
+pub fn open_backend(backend: StoreBackend, dir: &Path) -> io::Result<Box<dyn Store>> {
+    match backend {
+        StoreBackend::Json => Ok(Box::new(JsonStore::new(dir))),
+        #[cfg(feature = "sqlite")]
+        StoreBackend::Sqlite => Ok(Box::new(SqliteStore::open(dir)?)),
+        #[cfg(not(feature = "sqlite"))]
+        StoreBackend::Sqlite => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the sqlite store needs a build with `--features sqlite`",
+        )),
+    }
+}
+
+/// Load the document `name` from the configured store; missing or empty documents give the
+/// default value. `what` names the store in parse errors.
+pub fn load_document<T: DeserializeOwned + Default>(name: &str, what: &str) -> io::Result<T> {
+    let store = open_store()?;
+    let Some(data) = store.read(name)? else {
+        return Ok(T::default());
+    };
+    if data.is_empty() {
+        return Ok(T::default());
+    }
+
+    serde_json::from_slice(&data).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "failed to parse {} {}; restore its .bak copy or delete it to reset: {}",
+                what,
+                store.location(name),
+                e
+            ),
+        )
+    })
+}
+
+/// Append `items` to the array field `field` of document `name` if the configured backend
+/// supports it; `Ok(false)` means nothing was written and the document must be saved whole.
+pub fn append_document_items<T: Serialize>(
+    name: &str,
+    field: &str,
+    items: &[T],
+) -> io::Result<bool> {
+    ensure_writable(&format!("writing {}", name))?;
+    let items = items
+        .iter()
+        .map(serde_json::to_vec)
+        .collect::<Result<Vec<_>, _>>()?;
+    open_store()?.append_items(name, field, &items)
+}
+
+pub fn save_document<T: Serialize>(name: &str, value: &T) -> io::Result<()> {
+    ensure_writable(&format!("writing {}", name))?;
+    let json = serde_json::to_vec_pretty(value)?;
+    open_store()?.write(name, &json)
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde_json::{Value, json};
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::modules::vm::{AgentId, TickResult};
+
+/// Which events a stream subscriber wants. Empty means everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamFilter {
+    /// Only events whose `agent_id` is this agent (and this agent's rejections).
+    pub agent_id: Option<AgentId>,
+    /// Only these `Event` variants, e.g. `AgentMoved`.
+    pub kinds: BTreeSet<String>,
+}
+
+impl StreamFilter {
+    /// Parse `agent_id=<id>` and `event=<Kind>[,<Kind>...]` (repeatable) from a query string.
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            match key {
+                "agent_id" => {
+                    let id = value
+                        .parse()
+                        .map_err(|_| format!("agent_id '{}' must be a number", value))?;
+                    filter.agent_id = Some(id);
+                }
+                "event" => filter.kinds.extend(
+                    value
+                        .split(',')
+                        .filter(|kind| !kind.is_empty())
+                        .map(String::from),
+                ),
+                _ => {}
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agent_id.is_none() && self.kinds.is_empty()
+    }
+
+    /// Whether a serialized event (`{"<Kind>": {...}}`) passes the filter.
+    pub fn matches(&self, event: &Value) -> bool {
+        let Some((kind, fields)) = event.as_object().and_then(|o| o.iter().next()) else {
+            return self.is_empty();
+        };
+        if !self.kinds.is_empty() && !self.kinds.contains(kind) {
+            return false;
+        }
+        match self.agent_id {
+            Some(id) => fields.get("agent_id").and_then(Value::as_u64) == Some(id),
+            None => true,
+        }
+    }
+
+    /// The JSON message for one tick as this subscriber sees it, or `None` when a filtered
+    /// subscriber has nothing to see this tick.
+    pub fn tick_message(&self, result: &TickResult) -> Option<Value> {
+        let events: Vec<Value> = result
+            .events
+            .iter()
+            .filter_map(|event| serde_json::to_value(event).ok())
+            .filter(|event| self.matches(event))
+            .collect();
+        let rejections: Vec<Value> = result
+            .rejections
+            .iter()
+            .filter(|r| {
+                self.kinds.is_empty() && self.agent_id.is_none_or(|id| id == r.request.agent_id)
+            })
+            .map(|r| json!({ "request": r.request, "error": r.error.to_string() }))
+            .collect();
+        if !self.is_empty() && events.is_empty() && rejections.is_empty() {
+            return None;
+        }
+        Some(json!({
+            "tick": result.tick,
+            "events": events,
+            "rejections": rejections,
+        }))
+    }
+}
+
+type Subscribers = Vec<(StreamFilter, Sender<String>)>;
+
+/// Fans each tick out to every live subscriber, each through its own filter.
+#[derive(Debug, Clone, Default)]
+pub struct TickStream {
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+impl TickStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, filter: StreamFilter) -> Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        self.lock().push((filter, tx));
+        rx
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Send `result` to every subscriber; ones that have gone away are dropped.
+    pub fn publish(&self, result: &TickResult) {
+        self.lock()
+            .retain(|(filter, tx)| match filter.tick_message(result) {
+                Some(message) => tx.send(message.to_string()).is_ok(),
+                None => true,
+            });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Subscribers> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Forward a subscription to a client whose WebSocket handshake is already done, one text
+/// message per tick, until the client goes away.
+pub fn pump_websocket<S: Read + Write>(stream: S, messages: Receiver<String>) {
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+    for message in messages {
+        if socket.send(Message::Text(message.into())).is_err() {
+            break;
+        }
+    }
+    let _ = socket.close(None);
+}
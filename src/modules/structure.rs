@@ -1,19 +1,34 @@
+use std::collections::HashSet;
 use std::fmt;
-use std::fs;
 use std::io;
-use std::path::PathBuf;
 use std::str::FromStr;
 
-use crate::modules::vm::{AgentId, Position, Zone};
+use crate::modules::inventory::{Inventory, ItemKind};
+use crate::modules::script::Program;
+use crate::modules::store;
+use crate::modules::vm::{AgentId, Event, Position, Qi, STRUCTURE_DURABILITY, Zone};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum StructureKind {
+    #[serde(alias = "basic")]
     Basic,
+    #[serde(alias = "programmable")]
     Programmable,
+    #[serde(alias = "qi")]
     Qi,
 }
 
+impl StructureKind {
+    /// Items taken from the builder's inventory; nothing is spent unless the whole bill is covered.
+    pub const fn build_cost(self) -> &'static [(ItemKind, Qi)] {
+        match self {
+            StructureKind::Basic | StructureKind::Qi => &[(ItemKind::Qi, 1)],
+            StructureKind::Programmable => &[(ItemKind::Transistor, 1), (ItemKind::Qi, 1)],
+        }
+    }
+}
+
 impl fmt::Display for StructureKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -37,13 +52,24 @@ impl FromStr for StructureKind {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Structure {
     pub id: u64,
     pub kind: StructureKind,
     pub position: Position,
     pub zone: Zone,
     pub owner: AgentId,
+    /// Items held by the structure itself (filled and drained by its program).
+    pub storage: Inventory,
+    /// Program run every tick; only programmable structures accept one.
+    pub program: Option<Program>,
+    /// Wears down over time; the structure collapses at 0.
+    #[serde(default = "full_durability")]
+    pub durability: u32,
+}
+
+fn full_durability() -> u32 {
+    STRUCTURE_DURABILITY
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -55,47 +81,113 @@ pub struct StructureRecord {
     pub owner: AgentId,
 }
 
+impl StructureRecord {
+    /// The record for a `StructureBuilt` event.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::StructureBuilt {
+                agent_id,
+                kind,
+                position,
+                structure_id,
+            } => Some(Self {
+                id: *structure_id,
+                kind: *kind,
+                position: *position,
+                zone: position.zone(),
+                owner: *agent_id,
+            }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StructureStore {
     pub structures: Vec<StructureRecord>,
 }
 
-fn store_dir() -> PathBuf {
-    PathBuf::from(".harimu")
+const STRUCTURES_DOC: &str = "structures.json";
+
+pub fn load_structure_store() -> io::Result<StructureStore> {
+    store::load_document(STRUCTURES_DOC, "structure store")
 }
 
-fn store_path() -> PathBuf {
-    store_dir().join("structures.json")
+pub fn save_structure_store(structures: &StructureStore) -> io::Result<()> {
+    store::save_document(STRUCTURES_DOC, structures)
 }
 
-pub fn load_structure_store() -> io::Result<StructureStore> {
-    let path = store_path();
-    if !path.exists() {
-        return Ok(StructureStore::default());
-    }
+/// Ids of the structures `events` took out of the world.
+pub fn removed_structures(events: &[Event]) -> Vec<u64> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            Event::StructureCollapsed { structure_id, .. }
+            | Event::StructureDemolished { structure_id, .. } => Some(*structure_id),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Writes structures built during a run to the structure store. It remembers which ids the
+/// store already holds, so ticks that build nothing touch no files; new records are appended
+/// where the backend allows it and the store is rewritten only otherwise (or when structures
+/// were removed).
+#[derive(Debug, Default)]
+pub struct StructurePersister {
+    known: HashSet<u64>,
+}
 
-    let bytes = fs::read(&path)?;
-    if bytes.is_empty() {
-        return Ok(StructureStore::default());
+impl StructurePersister {
+    pub fn load() -> io::Result<Self> {
+        Ok(Self {
+            known: load_structure_store()?
+                .structures
+                .iter()
+                .map(|s| s.id)
+                .collect(),
+        })
     }
 
-    let store: StructureStore = serde_json::from_slice(&bytes).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!(
-                "failed to parse structure store {}; delete it to reset: {}",
-                path.display(),
-                e
-            ),
-        )
-    })?;
-
-    Ok(store)
-}
+    /// Records for structures built in `events` that aren't persisted yet.
+    pub fn new_records(&self, events: &[Event]) -> Vec<StructureRecord> {
+        let mut seen = HashSet::new();
+        events
+            .iter()
+            .filter_map(StructureRecord::from_event)
+            .filter(|record| !self.known.contains(&record.id) && seen.insert(record.id))
+            .collect()
+    }
 
-pub fn save_structure_store(store: &StructureStore) -> io::Result<()> {
-    fs::create_dir_all(store_dir())?;
-    let json = serde_json::to_vec_pretty(store)?;
-    fs::write(store_path(), json)?;
-    Ok(())
+    /// Persist the structures built in `events` and drop the removed ones; returns how many
+    /// were new.
+    pub fn persist(&mut self, events: &[Event]) -> io::Result<usize> {
+        let removed: HashSet<u64> = removed_structures(events)
+            .into_iter()
+            .filter(|id| self.known.contains(id))
+            .collect();
+        if !removed.is_empty() {
+            let mut store = load_structure_store()?;
+            store.structures.retain(|s| !removed.contains(&s.id));
+            save_structure_store(&store)?;
+            self.known.retain(|id| !removed.contains(id));
+        }
+        let records = self.new_records(events);
+        if records.is_empty() {
+            return Ok(0);
+        }
+        if !store::append_document_items(STRUCTURES_DOC, "structures", &records)? {
+            let mut store = load_structure_store()?;
+            let stored: HashSet<u64> = store.structures.iter().map(|s| s.id).collect();
+            store.structures.extend(
+                records
+                    .iter()
+                    .filter(|record| !stored.contains(&record.id))
+                    .cloned(),
+            );
+            save_structure_store(&store)?;
+        }
+        self.known.extend(records.iter().map(|r| r.id));
+        Ok(records.len())
+    }
 }
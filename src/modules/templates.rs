@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::agent::{BrainMode, LlmProvider};
+use crate::modules::vm::DEFAULT_MAX_AGENT_AGE;
+
+/// Templates live next to the project (not in the data dir) so they can be version-controlled.
+pub const DEFAULT_TEMPLATES_FILE: &str = "agent_templates.toml";
+
+fn default_max_age() -> u64 {
+    DEFAULT_MAX_AGENT_AGE
+}
+
+/// Brain settings an archetype starts with; unset fields fall back to the `start` flags.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BrainConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<BrainMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<LlmProvider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Environment variable holding the API key, for a provider other than the run's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Candidate actions in `--action` syntax, replacing the run's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<String>,
+}
+
+/// One archetype from the templates file, e.g. `[explorer]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentTemplate {
+    #[serde(default)]
+    pub qi: u64,
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub goal: Option<String>,
+    #[serde(default)]
+    pub brain: Option<BrainConfig>,
+}
+
+pub type TemplateSet = BTreeMap<String, AgentTemplate>;
+
+pub fn default_templates_path() -> PathBuf {
+    PathBuf::from(DEFAULT_TEMPLATES_FILE)
+}
+
+pub fn load_templates(path: &Path) -> io::Result<TemplateSet> {
+    let data = fs::read_to_string(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to read templates file {}: {}", path.display(), e),
+        )
+    })?;
+
+    toml::from_str(&data).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("failed to parse templates file {}: {}", path.display(), e),
+        )
+    })
+}
+
+pub fn find_template(path: &Path, name: &str) -> Result<AgentTemplate, String> {
+    let templates = load_templates(path).map_err(|e| e.to_string())?;
+    templates.get(name).cloned().ok_or_else(|| {
+        let known: Vec<&str> = templates.keys().map(String::as_str).collect();
+        format!(
+            "template '{}' not found in {} (available: {})",
+            name,
+            path.display(),
+            if known.is_empty() {
+                "none".to_string()
+            } else {
+                known.join(", ")
+            }
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::agents::{AgentStore, create_agent_from_template};
+    use crate::modules::testing::TempDataDir;
+
+    const ARCHETYPES: &str = r#"
+[scout]
+qi = 12
+role = "explorer"
+brain = { mode = "llm", provider = "gemini", temperature = 0.5, actions = ["move:1,0,0"] }
+
+[drone]
+max_age = 0
+"#;
+
+    fn write_templates(data: &TempDataDir, contents: &str) -> PathBuf {
+        let path = data.path().join(DEFAULT_TEMPLATES_FILE);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn archetypes_parse_with_defaults_for_unset_fields() {
+        let data = TempDataDir::new("templates-parse");
+        let templates = load_templates(&write_templates(&data, ARCHETYPES)).unwrap();
+        assert_eq!(
+            templates["scout"],
+            AgentTemplate {
+                qi: 12,
+                max_age: DEFAULT_MAX_AGENT_AGE,
+                role: Some("explorer".into()),
+                goal: None,
+                brain: Some(BrainConfig {
+                    mode: Some(BrainMode::Llm),
+                    provider: Some(LlmProvider::Gemini),
+                    temperature: Some(0.5),
+                    actions: vec!["move:1,0,0".into()],
+                    ..BrainConfig::default()
+                }),
+            }
+        );
+        assert_eq!(templates["drone"].qi, 0);
+
+        let shipped = Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_TEMPLATES_FILE);
+        assert!(load_templates(&shipped).unwrap().contains_key("explorer"));
+    }
+
+    #[test]
+    fn unknown_fields_and_missing_files_are_errors() {
+        let data = TempDataDir::new("templates-invalid");
+        let err = load_templates(&write_templates(&data, "[scout]\nspeed = 3\n")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("unknown field `speed`"), "{}", err);
+
+        let err = load_templates(&data.path().join("missing.toml")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn from_template_resolves_the_named_archetype() {
+        let data = TempDataDir::new("templates-find");
+        let path = write_templates(&data, ARCHETYPES);
+        let mut store = AgentStore::default();
+
+        let scout = find_template(&path, "scout").unwrap();
+        let profile = create_agent_from_template(&mut store, "scout", &scout).unwrap();
+        assert_eq!(profile.template.as_deref(), Some("scout"));
+        assert_eq!(
+            (profile.qi, profile.role.as_deref()),
+            (12, Some("explorer"))
+        );
+        assert_eq!(profile.brain, scout.brain);
+        assert_eq!(store.agents[&profile.id].template.as_deref(), Some("scout"));
+
+        // A zero lifespan would die on spawn.
+        let drone = find_template(&path, "drone").unwrap();
+        let profile = create_agent_from_template(&mut store, "drone", &drone).unwrap();
+        assert_eq!(profile.max_age, 1);
+
+        let err = find_template(&path, "miner").unwrap_err();
+        assert_eq!(
+            err,
+            format!(
+                "template 'miner' not found in {} (available: drone, scout)",
+                path.display()
+            )
+        );
+        let empty = write_templates(&data, "");
+        assert!(
+            find_template(&empty, "scout")
+                .unwrap_err()
+                .ends_with("(available: none)")
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::inventory::ItemKind;
+use crate::modules::vm::Position;
+
+/// Edge length of a terrain chunk in voxels.
+pub const CHUNK_SIZE: i32 = 16;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Block {
+    #[default]
+    Air,
+    Dirt,
+    Stone,
+}
+
+impl Block {
+    pub const fn is_solid(self) -> bool {
+        !matches!(self, Block::Air)
+    }
+
+    /// The inventory item a dug block turns into (and a placed block consumes).
+    pub const fn item(self) -> Option<ItemKind> {
+        match self {
+            Block::Air => None,
+            Block::Dirt => Some(ItemKind::Dirt),
+            Block::Stone => Some(ItemKind::Stone),
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Block::Air => "air",
+            Block::Dirt => "dirt",
+            Block::Stone => "stone",
+        }
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+impl FromStr for Block {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "air" => Ok(Block::Air),
+            "dirt" => Ok(Block::Dirt),
+            "stone" => Ok(Block::Stone),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub fn of(position: Position) -> Self {
+        Self {
+            x: position.x.div_euclid(CHUNK_SIZE),
+            y: position.y.div_euclid(CHUNK_SIZE),
+            z: position.z.div_euclid(CHUNK_SIZE),
+        }
+    }
+
+    pub fn origin(self) -> Position {
+        Position {
+            x: self.x * CHUNK_SIZE,
+            y: self.y * CHUNK_SIZE,
+            z: self.z * CHUNK_SIZE,
+        }
+    }
+}
+
+/// Blocks inside a chunk are indexed x-fastest, then y, then z.
+fn local_index(position: Position) -> usize {
+    let lx = position.x.rem_euclid(CHUNK_SIZE);
+    let ly = position.y.rem_euclid(CHUNK_SIZE);
+    let lz = position.z.rem_euclid(CHUNK_SIZE);
+    (lx + CHUNK_SIZE * (ly + CHUNK_SIZE * lz)) as usize
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Chunk {
+    blocks: Vec<Block>,
+}
+
+impl Chunk {
+    fn empty() -> Self {
+        Self {
+            blocks: vec![Block::Air; CHUNK_VOLUME],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|b| !b.is_solid())
+    }
+}
+
+/// Sparse voxel terrain: chunks that were never touched are all air.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Terrain {
+    chunks: HashMap<ChunkPos, Chunk>,
+}
+
+impl Terrain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A flat slab under `center`: one layer of dirt directly below it, stone underneath,
+    /// `depth` layers in total, covering a square of the given radius.
+    pub fn flat(center: Position, radius: i32, depth: i32) -> Self {
+        let mut terrain = Self::new();
+        let radius = radius.max(0);
+        for dz in -radius..=radius {
+            for dx in -radius..=radius {
+                for layer in 1..=depth.max(0) {
+                    let block = if layer == 1 {
+                        Block::Dirt
+                    } else {
+                        Block::Stone
+                    };
+                    terrain.set_block(center.offset(dx, -layer, dz), block);
+                }
+            }
+        }
+        terrain
+    }
+
+    pub fn block_at(&self, position: Position) -> Block {
+        self.chunks
+            .get(&ChunkPos::of(position))
+            .map(|chunk| chunk.blocks[local_index(position)])
+            .unwrap_or_default()
+    }
+
+    pub fn is_solid(&self, position: Position) -> bool {
+        self.block_at(position).is_solid()
+    }
+
+    pub fn set_block(&mut self, position: Position, block: Block) {
+        let key = ChunkPos::of(position);
+        if !block.is_solid() && !self.chunks.contains_key(&key) {
+            return;
+        }
+        let chunk = self.chunks.entry(key).or_insert_with(Chunk::empty);
+        chunk.blocks[local_index(position)] = block;
+        if chunk.is_empty() {
+            self.chunks.remove(&key);
+        }
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Positions of the chunks holding any solid block.
+    pub fn chunk_positions(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    /// Run-length encoded chunks in a stable order, for snapshots and viewers.
+    pub fn snapshot(&self) -> Vec<TerrainChunkSnapshot> {
+        let mut keys: Vec<&ChunkPos> = self.chunks.keys().collect();
+        keys.sort();
+        keys.into_iter()
+            .map(|key| encode(*key, &self.chunks[key]))
+            .collect()
+    }
+
+    pub fn from_snapshot(chunks: &[TerrainChunkSnapshot]) -> Self {
+        let mut terrain = Self::new();
+        terrain.restore_chunks(chunks);
+        terrain
+    }
+
+    /// Remove the chunks at `keys`, returned encoded in a stable order; missing ones are skipped.
+    pub fn take_chunks(&mut self, keys: &[ChunkPos]) -> Vec<TerrainChunkSnapshot> {
+        let mut keys = keys.to_vec();
+        keys.sort();
+        keys.into_iter()
+            .filter_map(|key| self.chunks.remove(&key).map(|chunk| encode(key, &chunk)))
+            .collect()
+    }
+
+    /// Put back chunks taken with `take_chunks` (or saved with `snapshot`), replacing any there.
+    pub fn restore_chunks(&mut self, chunks: &[TerrainChunkSnapshot]) {
+        for snapshot in chunks {
+            let mut index = 0usize;
+            let mut chunk = Chunk::empty();
+            for (block, count) in &snapshot.runs {
+                for _ in 0..*count {
+                    if let Some(slot) = chunk.blocks.get_mut(index) {
+                        *slot = *block;
+                    }
+                    index += 1;
+                }
+            }
+            if !chunk.is_empty() {
+                self.chunks.insert(snapshot.chunk, chunk);
+            }
+        }
+    }
+}
+
+fn encode(key: ChunkPos, chunk: &Chunk) -> TerrainChunkSnapshot {
+    let mut runs: Vec<(Block, u32)> = Vec::new();
+    for block in &chunk.blocks {
+        match runs.last_mut() {
+            Some((last, count)) if last == block => *count += 1,
+            _ => runs.push((*block, 1)),
+        }
+    }
+    TerrainChunkSnapshot {
+        chunk: key,
+        origin: key.origin(),
+        size: CHUNK_SIZE,
+        runs,
+    }
+}
+
+/// One chunk as `(block, count)` runs over its voxels, x-fastest then y then z.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerrainChunkSnapshot {
+    pub chunk: ChunkPos,
+    pub origin: Position,
+    pub size: i32,
+    pub runs: Vec<(Block, u32)>,
+}
@@ -0,0 +1,78 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::store;
+use crate::modules::vm::{AgentId, Qi, Zone};
+
+/// An agent's claim on a zone; it lapses when the agent dies.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneClaim {
+    pub zone: Zone,
+    pub owner: AgentId,
+    pub claimed_at: u64,
+    /// Qi another agent pays the owner per harvest of a node in the zone; 0 is free.
+    #[serde(default)]
+    pub harvest_toll: Qi,
+    /// Qi another agent pays the owner to build in the zone; 0 keeps building to the owner.
+    #[serde(default)]
+    pub build_toll: Qi,
+}
+
+/// Every zone claim, sorted by zone.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerritoryStore {
+    pub claims: Vec<ZoneClaim>,
+}
+
+impl TerritoryStore {
+    pub fn get(&self, zone: Zone) -> Option<&ZoneClaim> {
+        self.claims.iter().find(|claim| claim.zone == zone)
+    }
+
+    /// Give `zone` to `owner`, replacing any earlier claim on it.
+    pub fn claim(&mut self, zone: Zone, owner: AgentId, tick: u64) {
+        let claim = ZoneClaim {
+            zone,
+            owner,
+            claimed_at: tick,
+            harvest_toll: 0,
+            build_toll: 0,
+        };
+        match self.claims.binary_search_by_key(&zone, |c| c.zone) {
+            Ok(idx) => self.claims[idx] = claim,
+            Err(idx) => self.claims.insert(idx, claim),
+        }
+    }
+
+    /// Put `zone`'s claim back to `claim`, or unclaim it.
+    pub(crate) fn restore(&mut self, zone: Zone, claim: Option<ZoneClaim>) {
+        let found = self.claims.binary_search_by_key(&zone, |c| c.zone);
+        match (found, claim) {
+            (Ok(idx), Some(claim)) => self.claims[idx] = claim,
+            (Err(idx), Some(claim)) => self.claims.insert(idx, claim),
+            (Ok(idx), None) => {
+                self.claims.remove(idx);
+            }
+            (Err(_), None) => {}
+        }
+    }
+
+    pub fn get_mut(&mut self, zone: Zone) -> Option<&mut ZoneClaim> {
+        self.claims.iter_mut().find(|claim| claim.zone == zone)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.claims.is_empty()
+    }
+}
+
+const TERRITORIES_DOC: &str = "territories.json";
+
+pub fn load_territory_store() -> io::Result<TerritoryStore> {
+    store::load_document(TERRITORIES_DOC, "territory store")
+}
+
+pub fn save_territory_store(territories: &TerritoryStore) -> io::Result<()> {
+    store::save_document(TERRITORIES_DOC, territories)
+}
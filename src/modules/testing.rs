@@ -0,0 +1,365 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::modules::inventory::ItemKind;
+use crate::modules::ore::OreKind;
+use crate::modules::vm::{
+    Action, ActionError, ActionRequest, Agent, AgentId, DEFAULT_MAX_AGENT_AGE, Event, Position, Qi,
+    TickResult, Vm,
+};
+use crate::modules::{paths, store};
+
+#[derive(Debug, Clone)]
+struct AgentSeed {
+    name: String,
+    qi: Qi,
+    position: Position,
+    max_age: u64,
+    items: Vec<(ItemKind, Qi)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OreSeed {
+    ore: OreKind,
+    position: Position,
+    capacity: Qi,
+    recharge_per_tick: Qi,
+}
+
+/// A world described up front and built in one go, for tests. Agents are spawned in the order
+/// they were added, then ore nodes, so ids are the same on every build.
+///
+/// ```
+/// use harimu::testing::{TickAssertions, WorldFixture};
+/// use harimu::{Action, Event, OreKind, Position};
+///
+/// let mut world = WorldFixture::new()
+///     .with_agent("Nova", 3, Position::origin())
+///     .with_ore(OreKind::QI, Position { x: 1, y: 0, z: 0 }, 5, 0)
+///     .build();
+/// let nova = world.agent("Nova");
+/// world
+///     .act("Nova", Action::HarvestOre { ore: OreKind::QI, source_id: world.ore(0) })
+///     .assert_accepted()
+///     .assert_event(|e| matches!(e, Event::OreNodeHarvested { agent_id, .. } if *agent_id == nova));
+/// assert_eq!(world.qi("Nova"), 5);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WorldFixture {
+    seed: Option<u64>,
+    tick: u64,
+    max_qi_supply: Option<u64>,
+    thinking_cost: Option<Qi>,
+    agents: Vec<AgentSeed>,
+    ores: Vec<OreSeed>,
+}
+
+impl WorldFixture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Start the world at `tick` instead of 0.
+    pub fn at_tick(mut self, tick: u64) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    pub fn with_max_qi_supply(mut self, max: u64) -> Self {
+        self.max_qi_supply = Some(max);
+        self
+    }
+
+    pub fn with_thinking_cost(mut self, cost: Qi) -> Self {
+        self.thinking_cost = Some(cost);
+        self
+    }
+
+    /// An agent holding `qi`; `name` is how the built `Fixture` finds it, so it must be unique.
+    pub fn with_agent(self, name: impl Into<String>, qi: Qi, position: Position) -> Self {
+        self.with_aged_agent(name, qi, position, DEFAULT_MAX_AGENT_AGE)
+    }
+
+    pub fn with_aged_agent(
+        mut self,
+        name: impl Into<String>,
+        qi: Qi,
+        position: Position,
+        max_age: u64,
+    ) -> Self {
+        self.agents.push(AgentSeed {
+            name: name.into(),
+            qi,
+            position,
+            max_age,
+            items: Vec::new(),
+        });
+        self
+    }
+
+    /// Give the agent added last `amount` of `item` on top of its Qi.
+    ///
+    /// # Panics
+    /// If no agent was added yet.
+    #[track_caller]
+    pub fn holding(mut self, item: ItemKind, amount: Qi) -> Self {
+        self.agents
+            .last_mut()
+            .expect("holding() needs an agent added before it")
+            .items
+            .push((item, amount));
+        self
+    }
+
+    /// A full ore node; the built `Fixture` finds it by its index among the added nodes.
+    pub fn with_ore(
+        mut self,
+        ore: OreKind,
+        position: Position,
+        capacity: Qi,
+        recharge_per_tick: Qi,
+    ) -> Self {
+        self.ores.push(OreSeed {
+            ore,
+            position,
+            capacity,
+            recharge_per_tick,
+        });
+        self
+    }
+
+    /// # Panics
+    /// If two agents share a name.
+    #[track_caller]
+    pub fn build(self) -> Fixture {
+        let mut vm = self.seed.map_or_else(Vm::new, Vm::with_seed);
+        vm.set_tick(self.tick);
+        if let Some(max) = self.max_qi_supply {
+            vm.set_max_qi_supply(max);
+        }
+        if let Some(cost) = self.thinking_cost {
+            vm.set_thinking_cost(cost);
+        }
+        let mut agents = BTreeMap::new();
+        for seed in self.agents {
+            let id =
+                vm.spawn_agent_with_age(seed.name.clone(), seed.qi, seed.position, seed.max_age);
+            vm.restore_agent(id, 0, &seed.items)
+                .expect("the agent was just spawned");
+            assert!(
+                agents.insert(seed.name.clone(), id).is_none(),
+                "two fixture agents are named '{}'",
+                seed.name
+            );
+        }
+        let ores = self
+            .ores
+            .into_iter()
+            .map(|o| vm.seed_ore_source(o.ore, o.position, o.capacity, o.recharge_per_tick))
+            .collect();
+        Fixture { vm, agents, ores }
+    }
+}
+
+/// A built `WorldFixture`: the `Vm` plus the ids of the agents and ore nodes it was given.
+#[derive(Debug)]
+pub struct Fixture {
+    pub vm: Vm,
+    agents: BTreeMap<String, AgentId>,
+    ores: Vec<u64>,
+}
+
+impl Fixture {
+    /// # Panics
+    /// If no agent of that name was added.
+    #[track_caller]
+    pub fn agent(&self, name: &str) -> AgentId {
+        match self.agents.get(name) {
+            Some(id) => *id,
+            None => panic!("no fixture agent named '{}'", name),
+        }
+    }
+
+    /// The id of the `index`th ore node added.
+    ///
+    /// # Panics
+    /// If fewer nodes were added.
+    #[track_caller]
+    pub fn ore(&self, index: usize) -> u64 {
+        match self.ores.get(index) {
+            Some(id) => *id,
+            None => panic!(
+                "the fixture has {} ore node(s), not {}",
+                self.ores.len(),
+                index + 1
+            ),
+        }
+    }
+
+    #[track_caller]
+    pub fn qi(&self, name: &str) -> Qi {
+        self.item(name, ItemKind::Qi)
+    }
+
+    #[track_caller]
+    pub fn item(&self, name: &str, item: ItemKind) -> Qi {
+        self.agent_state(name).item(item)
+    }
+
+    #[track_caller]
+    pub fn position(&self, name: &str) -> Position {
+        self.agent_state(name).position
+    }
+
+    #[track_caller]
+    pub fn is_alive(&self, name: &str) -> bool {
+        self.agent_state(name).alive
+    }
+
+    #[track_caller]
+    fn agent_state(&self, name: &str) -> &Agent {
+        let id = self.agent(name);
+        self.vm
+            .agent(id)
+            .unwrap_or_else(|| panic!("fixture agent '{}' ({}) left the world", name, id))
+    }
+
+    pub fn step(&mut self, requests: &[ActionRequest]) -> TickResult {
+        self.vm.step(requests)
+    }
+
+    /// Step one tick in which only `name` acts.
+    #[track_caller]
+    pub fn act(&mut self, name: &str, action: Action) -> TickResult {
+        let id = self.agent(name);
+        self.vm.step(&[ActionRequest::new(id, action)])
+    }
+
+    /// Step `ticks` ticks in which nobody acts, returning the last.
+    pub fn idle(&mut self, ticks: u64) -> Option<TickResult> {
+        (0..ticks).map(|_| self.vm.step(&[])).last()
+    }
+}
+
+/// Checks over a `TickResult` that panic with the tick's events and rejections, so a failing
+/// test shows what happened instead of just what didn't.
+pub trait TickAssertions {
+    /// No request was rejected.
+    fn assert_accepted(&self) -> &Self;
+
+    /// `agent_id`'s request was rejected; returns why.
+    fn assert_rejected(&self, agent_id: AgentId) -> &ActionError;
+
+    /// Some event matches `predicate`.
+    fn assert_event(&self, predicate: impl Fn(&Event) -> bool) -> &Self;
+
+    /// No event matches `predicate`.
+    fn assert_no_event(&self, predicate: impl Fn(&Event) -> bool) -> &Self;
+
+    /// The `Event` variant names in order, e.g. `["AgentMoved", "ScanReport"]`.
+    fn event_kinds(&self) -> Vec<String>;
+}
+
+impl TickAssertions for TickResult {
+    #[track_caller]
+    fn assert_accepted(&self) -> &Self {
+        assert!(
+            self.rejections.is_empty(),
+            "tick {} rejected {:?}",
+            self.tick,
+            self.rejections
+        );
+        self
+    }
+
+    #[track_caller]
+    fn assert_rejected(&self, agent_id: AgentId) -> &ActionError {
+        match self
+            .rejections
+            .iter()
+            .find(|r| r.request.agent_id == agent_id)
+        {
+            Some(rejection) => &rejection.error,
+            None => panic!(
+                "tick {} rejected nothing from agent {}; its events were {:?}",
+                self.tick, agent_id, self.events
+            ),
+        }
+    }
+
+    #[track_caller]
+    fn assert_event(&self, predicate: impl Fn(&Event) -> bool) -> &Self {
+        assert!(
+            self.events.iter().any(predicate),
+            "no matching event in tick {}: {:?} (rejected {:?})",
+            self.tick,
+            self.events,
+            self.rejections
+        );
+        self
+    }
+
+    #[track_caller]
+    fn assert_no_event(&self, predicate: impl Fn(&Event) -> bool) -> &Self {
+        if let Some(event) = self.events.iter().find(|e| predicate(e)) {
+            panic!("unexpected event in tick {}: {:?}", self.tick, event);
+        }
+        self
+    }
+
+    fn event_kinds(&self) -> Vec<String> {
+        self.events
+            .iter()
+            .map(|event| match serde_json::to_value(event) {
+                Ok(serde_json::Value::Object(fields)) => {
+                    fields.keys().next().cloned().unwrap_or_default()
+                }
+                Ok(serde_json::Value::String(name)) => name,
+                _ => String::new(),
+            })
+            .collect()
+    }
+}
+
+static DATA_DIR_LOCK: Mutex<()> = Mutex::new(());
+
+/// An empty data directory the process uses until the guard drops, for tests of the stores.
+/// The data directory and read-only mode are process-wide, so guards are taken one at a time;
+/// dropping one leaves the process writable and deletes the directory.
+#[derive(Debug)]
+pub struct TempDataDir {
+    dir: PathBuf,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TempDataDir {
+    pub fn new(name: &str) -> Self {
+        let lock = DATA_DIR_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let dir = std::env::temp_dir().join(format!("harimu-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("create temp data dir");
+        paths::set_data_dir_override(dir.clone());
+        Self { dir, _lock: lock }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for TempDataDir {
+    fn drop(&mut self) {
+        store::set_read_only(false);
+        paths::set_data_dir(None);
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
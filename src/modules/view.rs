@@ -1,26 +1,46 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
 
+use chrono::Utc;
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::modules::ore::OreKind;
+use crate::modules::inventory::Inventory;
+use crate::modules::ore::{OreDef, OreKind};
+use crate::modules::paths;
+use crate::modules::store::load_store_config;
 use crate::modules::structure::{StructureKind, StructureRecord, load_structure_store};
-use crate::modules::vm::{AgentId, Position, Qi, DEFAULT_MAX_AGENT_AGE};
+use crate::modules::terrain::TerrainChunkSnapshot;
+use crate::modules::vm::{
+    AgentId, DEFAULT_AGENT_HP, DEFAULT_MAX_AGENT_AGE, HARVEST_PER_ACTION, HARVEST_RANGE,
+    MAX_MOVE_RADIUS, Position, Qi, SCAN_RANGE, STRUCTURE_DURABILITY, ZONE_SIZE, Zone,
+};
 use crate::modules::world::WorldQueries;
+use crate::modules::zones::{ZoneLabel, load_zone_store};
 
 fn default_max_age() -> u64 {
     DEFAULT_MAX_AGENT_AGE
 }
 
+fn default_hp() -> u32 {
+    DEFAULT_AGENT_HP
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentSnapshot {
     pub id: AgentId,
     pub name: String,
-    pub qi: Qi,
-    pub transistors: Qi,
+    #[serde(default)]
+    pub inventory: Inventory,
     pub position: Position,
     pub alive: bool,
+    #[serde(default = "default_hp")]
+    pub hp: u32,
     pub age: u64,
     #[serde(default = "default_max_age")]
     pub max_age: u64,
@@ -42,6 +62,12 @@ pub struct StructureView {
     pub kind: StructureKind,
     pub position: Position,
     pub owner: AgentId,
+    #[serde(default = "full_durability")]
+    pub durability: u32,
+}
+
+fn full_durability() -> u32 {
+    STRUCTURE_DURABILITY
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,14 +76,375 @@ pub struct WorldSnapshot {
     pub agents: Vec<AgentSnapshot>,
     pub ore_nodes: Vec<OreNodeSnapshot>,
     pub structures: Vec<StructureView>,
+    #[serde(default)]
+    pub terrain: Vec<TerrainChunkSnapshot>,
+    #[serde(default)]
+    pub zones: Vec<ZoneLabel>,
+    #[serde(default)]
+    pub meta: SnapshotMeta,
+}
+
+/// Ticks in one season; four seasons make an epoch.
+pub const SEASON_TICKS: u64 = 28;
+pub const SEASONS: [&str; 4] = ["spring", "summer", "autumn", "winter"];
+/// Ticks in one epoch (a full cycle of seasons).
+pub const EPOCH_TICKS: u64 = SEASON_TICKS * SEASONS.len() as u64;
+
+pub fn epoch_of(tick: u64) -> u64 {
+    tick / EPOCH_TICKS
+}
+
+/// Layout version of the snapshots this build writes; readers refuse newer ones.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Health and identity of the world at snapshot time, for HUDs and desync checks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotMeta {
+    /// `SNAPSHOT_VERSION` of the writer; 0 for snapshots from before it was recorded.
+    #[serde(default)]
+    pub version: u32,
+    /// Wall-clock time the snapshot was taken (RFC 3339).
+    #[serde(default)]
+    pub captured_at: Option<String>,
+    /// Hex SHA-256 over the simulated state (see `WorldSnapshot::content_hash`).
+    #[serde(default)]
+    pub world_hash: String,
+    #[serde(default)]
+    pub epoch: u64,
+    #[serde(default)]
+    pub season: String,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub rules: RuleSummary,
+}
+
+impl SnapshotMeta {
+    pub fn for_tick(tick: u64, world_hash: String, seed: Option<u64>, rules: RuleSummary) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            captured_at: Some(Utc::now().to_rfc3339()),
+            world_hash,
+            epoch: epoch_of(tick),
+            season: SEASONS[((tick / SEASON_TICKS) % SEASONS.len() as u64) as usize].to_string(),
+            seed,
+            rules,
+        }
+    }
+}
+
+/// The constants that shape a run, so viewers can show which rules produced a snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSummary {
+    pub max_move_radius: i32,
+    pub scan_range: i32,
+    pub harvest_range: i32,
+    pub harvest_per_action: Qi,
+    pub zone_size: i32,
+    pub default_max_age: u64,
+    #[serde(default)]
+    pub max_qi_supply: Option<u64>,
+    /// Qi an agent pays per LLM brain call (0 when thinking is free).
+    #[serde(default)]
+    pub thinking_cost: Qi,
+    /// The ore registry the snapshot's ore nodes and items refer to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ores: Vec<OreDef>,
+}
+
+impl RuleSummary {
+    pub fn current(max_qi_supply: Option<u64>, thinking_cost: Qi) -> Self {
+        Self {
+            max_move_radius: MAX_MOVE_RADIUS,
+            scan_range: SCAN_RANGE,
+            harvest_range: HARVEST_RANGE,
+            harvest_per_action: HARVEST_PER_ACTION,
+            zone_size: ZONE_SIZE,
+            default_max_age: DEFAULT_MAX_AGENT_AGE,
+            max_qi_supply,
+            thinking_cost,
+            ores: OreKind::all().into_iter().map(OreKind::def).collect(),
+        }
+    }
+}
+
+/// Per-zone totals for overview maps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneSummary {
+    pub zone: Zone,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub agents: u32,
+    pub ore_available: u64,
+    #[serde(default)]
+    pub ore_capacity: u64,
+    pub structures: u32,
+    /// Agent owning the most structures in the zone (lowest id wins ties).
+    pub owner: Option<AgentId>,
+}
+
+impl WorldSnapshot {
+    /// Summaries of every non-empty zone within `zone_radius` zones (Chebyshev) of `center`,
+    /// ordered by zone coordinates.
+    pub fn minimap(&self, center: Zone, zone_radius: i32) -> Vec<ZoneSummary> {
+        let in_range = |zone: Zone| {
+            (zone.x - center.x).abs() <= zone_radius
+                && (zone.y - center.y).abs() <= zone_radius
+                && (zone.z - center.z).abs() <= zone_radius
+        };
+        let key = |zone: Zone| (zone.x, zone.y, zone.z);
+        let mut zones: BTreeMap<(i32, i32, i32), ZoneSummary> = BTreeMap::new();
+        let mut owners: HashMap<(i32, i32, i32), BTreeMap<AgentId, u32>> = HashMap::new();
+        fn entry(
+            zones: &mut BTreeMap<(i32, i32, i32), ZoneSummary>,
+            zone: Zone,
+        ) -> &mut ZoneSummary {
+            zones
+                .entry((zone.x, zone.y, zone.z))
+                .or_insert_with(|| ZoneSummary {
+                    zone,
+                    name: None,
+                    agents: 0,
+                    ore_available: 0,
+                    ore_capacity: 0,
+                    structures: 0,
+                    owner: None,
+                })
+        }
+
+        for agent in self.agents.iter().filter(|a| a.alive) {
+            let zone = agent.position.zone();
+            if in_range(zone) {
+                entry(&mut zones, zone).agents += 1;
+            }
+        }
+        for node in &self.ore_nodes {
+            let zone = node.position.zone();
+            if in_range(zone) {
+                let summary = entry(&mut zones, zone);
+                summary.ore_available += node.available as u64;
+                summary.ore_capacity += node.capacity as u64;
+            }
+        }
+        for structure in &self.structures {
+            let zone = structure.position.zone();
+            if in_range(zone) {
+                entry(&mut zones, zone).structures += 1;
+                *owners
+                    .entry(key(zone))
+                    .or_default()
+                    .entry(structure.owner)
+                    .or_default() += 1;
+            }
+        }
+
+        for (zone_key, counts) in owners {
+            let owner = counts
+                .iter()
+                .max_by(|(a_id, a), (b_id, b)| a.cmp(b).then(b_id.cmp(a_id)))
+                .map(|(id, _)| *id);
+            if let Some(summary) = zones.get_mut(&zone_key) {
+                summary.owner = owner;
+            }
+        }
+        for label in &self.zones {
+            if let Some(summary) = zones.get_mut(&key(label.zone)) {
+                summary.name = label.name.clone();
+            }
+        }
+
+        zones.into_values().collect()
+    }
+
+    /// Summaries of every non-empty zone in the world, ordered by zone coordinates.
+    pub fn zone_summaries(&self) -> Vec<ZoneSummary> {
+        self.minimap(Position::origin().zone(), i32::MAX)
+    }
+}
+
+/// What happened between two snapshots, for viewers that react to changes.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotChanges {
+    /// Agents alive in the previous snapshot that are now dead or gone.
+    pub died: Vec<AgentId>,
+    /// Structures that did not exist in the previous snapshot.
+    pub built: Vec<StructureView>,
+}
+
+impl WorldSnapshot {
+    /// SHA-256 over tick, agents, ore nodes, structures and terrain in a fixed order. Two
+    /// processes simulating the same run produce the same hash; zone labels and `meta` are
+    /// presentation and left out.
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.tick.to_le_bytes());
+        for agent in &self.agents {
+            hasher.update(agent.id.to_le_bytes());
+            hasher.update(agent.name.as_bytes());
+            hash_position(&mut hasher, agent.position);
+            hasher.update([agent.alive as u8]);
+            hasher.update(agent.hp.to_le_bytes());
+            hasher.update(agent.age.to_le_bytes());
+            hasher.update(agent.max_age.to_le_bytes());
+            for (item, amount) in agent.inventory.iter() {
+                hasher.update(item.label().as_bytes());
+                hasher.update(amount.to_le_bytes());
+            }
+        }
+        for node in &self.ore_nodes {
+            hasher.update(node.id.to_le_bytes());
+            hasher.update(node.ore.to_string().as_bytes());
+            hash_position(&mut hasher, node.position);
+            hasher.update(node.available.to_le_bytes());
+            hasher.update(node.capacity.to_le_bytes());
+            hasher.update(node.recharge_per_tick.to_le_bytes());
+        }
+        for structure in &self.structures {
+            hasher.update(structure.id.to_le_bytes());
+            hasher.update(structure.kind.to_string().as_bytes());
+            hash_position(&mut hasher, structure.position);
+            hasher.update(structure.owner.to_le_bytes());
+        }
+        for chunk in &self.terrain {
+            hash_position(&mut hasher, chunk.origin);
+            for (block, count) in &chunk.runs {
+                hasher.update(block.label().as_bytes());
+                hasher.update(count.to_le_bytes());
+            }
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// Check a snapshot read back is one this build understands and was not mixed up with
+    /// another while being written: its version is not newer than `SNAPSHOT_VERSION`, and its
+    /// content still hashes to `meta.world_hash`. Snapshots from before versioning skip the
+    /// hash check, since the hash has covered more fields since.
+    pub fn verify(&self) -> io::Result<()> {
+        if self.meta.version > SNAPSHOT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "snapshot version {} is newer than this build reads ({})",
+                    self.meta.version, SNAPSHOT_VERSION
+                ),
+            ));
+        }
+        if self.meta.version > 0 && self.content_hash() != self.meta.world_hash {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "snapshot does not match its world hash",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn changes_since(&self, previous: &WorldSnapshot) -> SnapshotChanges {
+        let alive_now: HashMap<AgentId, bool> =
+            self.agents.iter().map(|a| (a.id, a.alive)).collect();
+        let died = previous
+            .agents
+            .iter()
+            .filter(|a| a.alive && !alive_now.get(&a.id).copied().unwrap_or(false))
+            .map(|a| a.id)
+            .collect();
+        let built = self
+            .structures
+            .iter()
+            .filter(|s| !previous.structures.iter().any(|p| p.id == s.id))
+            .cloned()
+            .collect();
+        SnapshotChanges { died, built }
+    }
+}
+
+fn hash_position(hasher: &mut Sha256, position: Position) {
+    hasher.update(position.x.to_le_bytes());
+    hasher.update(position.y.to_le_bytes());
+    hasher.update(position.z.to_le_bytes());
+}
+
+/// On-disk encoding of snapshots. MessagePack is written with field names, so like JSON it
+/// still loads snapshots saved before a field was added (the `serde(default)`s above); a
+/// positional format such as bincode would not, so it is not offered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotFormat {
+    /// Pretty-printed JSON (readable, largest)
+    #[default]
+    Json,
+    /// MessagePack (compact binary)
+    Msgpack,
+}
+
+impl SnapshotFormat {
+    pub const ALL: [SnapshotFormat; 2] = [SnapshotFormat::Json, SnapshotFormat::Msgpack];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            SnapshotFormat::Json => "json",
+            SnapshotFormat::Msgpack => "msgpack",
+        }
+    }
+
+    /// The format a snapshot file was written in, judged by its extension.
+    pub fn of_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == ext)
+    }
+
+    pub fn encode(self, snapshot: &WorldSnapshot) -> io::Result<Vec<u8>> {
+        match self {
+            SnapshotFormat::Json => Ok(serde_json::to_vec_pretty(snapshot)?),
+            SnapshotFormat::Msgpack => rmp_serde::to_vec_named(snapshot).map_err(io::Error::other),
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> io::Result<WorldSnapshot> {
+        match self {
+            SnapshotFormat::Json => Ok(serde_json::from_slice(bytes)?),
+            SnapshotFormat::Msgpack => rmp_serde::from_slice(bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+}
+
+static SNAPSHOT_FORMAT_OVERRIDE: RwLock<Option<SnapshotFormat>> = RwLock::new(None);
+
+/// Write snapshots in `format` for the rest of this process, whatever the config says.
+pub fn set_snapshot_format(format: Option<SnapshotFormat>) {
+    if let Ok(mut guard) = SNAPSHOT_FORMAT_OVERRIDE.write() {
+        *guard = format;
+    }
+}
+
+/// Format new snapshots are written in: the override, else `.harimu/config.toml`, else JSON.
+pub fn snapshot_format() -> SnapshotFormat {
+    SNAPSHOT_FORMAT_OVERRIDE
+        .read()
+        .ok()
+        .and_then(|guard| *guard)
+        .or_else(|| load_store_config().ok().map(|c| c.snapshot_format))
+        .unwrap_or_default()
 }
 
 fn snapshot_dir() -> PathBuf {
-    PathBuf::from(".harimu")
+    paths::data_dir()
+}
+
+fn latest_snapshot_path(format: SnapshotFormat) -> PathBuf {
+    snapshot_dir().join(format!("world_snapshot.{}", format.extension()))
 }
 
+/// The latest-snapshot file: whichever format was written last, else where the configured
+/// format would write it.
 pub fn snapshot_file_path() -> PathBuf {
-    snapshot_dir().join("world_snapshot.json")
+    SnapshotFormat::ALL
+        .into_iter()
+        .map(latest_snapshot_path)
+        .find(|path| path.exists())
+        .unwrap_or_else(|| latest_snapshot_path(snapshot_format()))
 }
 
 pub fn snapshots_dir() -> PathBuf {
@@ -65,72 +452,193 @@ pub fn snapshots_dir() -> PathBuf {
 }
 
 pub fn save_world_snapshot(snapshot: &WorldSnapshot) -> io::Result<PathBuf> {
-    let path = snapshot_file_path();
+    let format = snapshot_format();
+    let path = latest_snapshot_path(format);
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
-    let json = serde_json::to_vec_pretty(snapshot)?;
-    fs::write(&path, json)?;
+    paths::write_atomic(&path, &format.encode(snapshot)?)?;
+    // Keep a single latest snapshot so readers never pick up one in a stale format.
+    for other in SnapshotFormat::ALL.into_iter().filter(|f| *f != format) {
+        match fs::remove_file(latest_snapshot_path(other)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
     Ok(path)
 }
 
 pub fn save_world_snapshot_tick(snapshot: &WorldSnapshot) -> io::Result<PathBuf> {
+    let format = snapshot_format();
     let dir = snapshots_dir();
     fs::create_dir_all(&dir)?;
-    let filename = format!("tick_{:06}.json", snapshot.tick);
+    let filename = format!("tick_{:06}.{}", snapshot.tick, format.extension());
     let path = dir.join(filename);
-    let json = serde_json::to_vec_pretty(snapshot)?;
-    fs::write(&path, json)?;
+    paths::write_atomic(&path, &format.encode(snapshot)?)?;
     Ok(path)
 }
 
-pub fn load_world_snapshot() -> io::Result<Option<WorldSnapshot>> {
-    let path = snapshot_file_path();
-    if !path.exists() {
-        return load_latest_snapshot_from_dir();
-    }
-    let bytes = fs::read(&path)?;
+/// Read a snapshot file in whatever format its extension names; empty files read as `None`.
+fn read_snapshot(path: &Path) -> io::Result<Option<WorldSnapshot>> {
+    let format = SnapshotFormat::of_path(path).unwrap_or_default();
+    let bytes = fs::read(path)?;
     if bytes.is_empty() {
-        return load_latest_snapshot_from_dir();
+        return Ok(None);
     }
-    let snapshot = serde_json::from_slice(&bytes)?;
+    format.decode(&bytes).map(Some)
+}
+
+/// The latest snapshot, read as `load_world_snapshot_consistent` does.
+pub fn load_world_snapshot() -> io::Result<Option<WorldSnapshot>> {
+    Ok(load_world_snapshot_consistent()?.map(|read| read.snapshot))
+}
+
+/// Times the latest snapshot is read before falling back to the per-tick files.
+const CONSISTENT_READ_ATTEMPTS: u32 = 3;
+/// Pause between those reads, long enough for a writer to finish its rename.
+const CONSISTENT_READ_BACKOFF: Duration = Duration::from_millis(25);
+
+/// A snapshot from `load_world_snapshot_consistent` and the file it was read from.
+#[derive(Debug, Clone)]
+pub struct SnapshotRead {
+    pub snapshot: WorldSnapshot,
+    pub path: PathBuf,
+    /// The latest snapshot could not be read whole, so this is the newest per-tick one.
+    pub fell_back: bool,
+}
+
+/// Read `path` and check it is whole (see `WorldSnapshot::verify`).
+fn read_checked_snapshot(path: &Path) -> io::Result<Option<WorldSnapshot>> {
+    let Some(snapshot) = read_snapshot(path)? else {
+        return Ok(None);
+    };
+    snapshot
+        .verify()
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
     Ok(Some(snapshot))
 }
 
-pub fn load_latest_snapshot_from_dir() -> io::Result<Option<WorldSnapshot>> {
-    let dir = snapshots_dir();
-    let mut latest: Option<PathBuf> = None;
-    if let Ok(entries) = fs::read_dir(&dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("json") {
-                match &latest {
-                    Some(current) => {
-                        if path > *current {
-                            latest = Some(path);
-                        }
-                    }
-                    None => latest = Some(path),
-                }
+/// The latest world snapshot, never a torn one. The latest-snapshot file is re-read a few
+/// times if it fails to decode or check out (a writer may be replacing it, or switching
+/// formats); after that, or when there is none, the newest per-tick file that reads whole is
+/// used instead. Errors only when nothing readable is left.
+pub fn load_world_snapshot_consistent() -> io::Result<Option<SnapshotRead>> {
+    let mut last_err = None;
+    for attempt in 0..CONSISTENT_READ_ATTEMPTS {
+        if attempt > 0 {
+            std::thread::sleep(CONSISTENT_READ_BACKOFF);
+        }
+        let path = snapshot_file_path();
+        match read_checked_snapshot(&path) {
+            Ok(Some(snapshot)) => {
+                return Ok(Some(SnapshotRead {
+                    snapshot,
+                    path,
+                    fell_back: false,
+                }));
+            }
+            // Never written, or cleared: nothing to wait for.
+            Ok(None) => break,
+            // Gone for good, rather than replaced by a snapshot in another format.
+            Err(err)
+                if err.kind() == io::ErrorKind::NotFound
+                    && SnapshotFormat::ALL
+                        .into_iter()
+                        .all(|format| !latest_snapshot_path(format).exists()) =>
+            {
+                break;
             }
+            Err(err) => last_err = Some(err),
         }
     }
 
-    let Some(path) = latest else {
-        return Ok(None);
+    let fell_back = last_err.is_some();
+    for path in tick_snapshot_files()?.into_iter().rev() {
+        match read_checked_snapshot(&path) {
+            Ok(Some(snapshot)) => {
+                return Ok(Some(SnapshotRead {
+                    snapshot,
+                    path,
+                    fell_back,
+                }));
+            }
+            Ok(None) => continue,
+            Err(err) => last_err = Some(err),
+        }
+    }
+    match last_err {
+        Some(err) => Err(err),
+        None => Ok(None),
+    }
+}
+
+/// Per-tick snapshot files in any known format, oldest tick first.
+fn tick_snapshot_files() -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(snapshots_dir()) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && SnapshotFormat::of_path(path).is_some())
+            .collect(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
     };
+    // Names are zero-padded, so the stem orders by tick whatever the extension.
+    files.sort_by(|a, b| a.file_stem().cmp(&b.file_stem()));
+    Ok(files)
+}
 
-    let bytes = fs::read(&path)?;
-    if bytes.is_empty() {
+pub fn load_latest_snapshot_from_dir() -> io::Result<Option<WorldSnapshot>> {
+    let Some(path) = tick_snapshot_files()?.pop() else {
         return Ok(None);
+    };
+    read_snapshot(&path)
+}
+
+/// Where an agent was at one recorded tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentTrailPoint {
+    pub tick: u64,
+    pub position: Position,
+    pub alive: bool,
+}
+
+/// Positions of `agent_id` over the last `ticks` per-tick snapshots, oldest first. Ticks where
+/// the agent did not exist yet are skipped.
+pub fn load_agent_history(agent_id: AgentId, ticks: usize) -> io::Result<Vec<AgentTrailPoint>> {
+    let files = tick_snapshot_files()?;
+    let skip = files.len().saturating_sub(ticks);
+
+    let mut trail = Vec::new();
+    for path in &files[skip..] {
+        let Some(snapshot) = read_snapshot(path)? else {
+            continue;
+        };
+        if let Some(agent) = snapshot.agents.iter().find(|a| a.id == agent_id) {
+            trail.push(AgentTrailPoint {
+                tick: snapshot.tick,
+                position: agent.position,
+                alive: agent.alive,
+            });
+        }
     }
-    let snapshot = serde_json::from_slice(&bytes)?;
-    Ok(Some(snapshot))
+    Ok(trail)
+}
+
+/// Read every per-tick snapshot, oldest tick first, handing each to `visit`.
+pub fn for_each_tick_snapshot(mut visit: impl FnMut(&WorldSnapshot)) -> io::Result<()> {
+    for path in tick_snapshot_files()? {
+        if let Some(snapshot) = read_snapshot(&path)? {
+            visit(&snapshot);
+        }
+    }
+    Ok(())
 }
 
 pub fn snapshot_from_persistent() -> Result<WorldSnapshot, String> {
     let ore_store = WorldQueries::qi_sources().map_err(|e| e.to_string())?;
     let structure_store = load_structure_store().map_err(|e| e.to_string())?;
+    let zone_store = load_zone_store().map_err(|e| e.to_string())?;
 
     let mut ore_nodes: Vec<OreNodeSnapshot> = ore_store
         .sources
@@ -154,16 +662,27 @@ pub fn snapshot_from_persistent() -> Result<WorldSnapshot, String> {
             kind: s.kind,
             position: s.position,
             owner: s.owner,
+            durability: STRUCTURE_DURABILITY,
         })
         .collect();
 
     ore_nodes.sort_by_key(|n| n.id);
     structures.sort_by_key(|s| s.id);
 
-    Ok(WorldSnapshot {
+    let mut snapshot = WorldSnapshot {
         tick: 0,
         agents: Vec::new(),
         ore_nodes,
         structures,
-    })
+        terrain: Vec::new(),
+        zones: zone_store.zones,
+        meta: SnapshotMeta::default(),
+    };
+    snapshot.meta = SnapshotMeta::for_tick(
+        0,
+        snapshot.content_hash(),
+        None,
+        RuleSummary::current(Some(ore_store.total_qi_infused), 0),
+    );
+    Ok(snapshot)
 }
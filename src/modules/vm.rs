@@ -1,12 +1,30 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt;
 
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::modules::crafting::RecipeBook;
+use crate::modules::effects::{
+    EffectSpec, HARVEST_NEARBY_RANGE, StructureEffect, StructureRegistry,
+};
+use crate::modules::inventory::{Inventory, ItemKind, Shortfall};
+use crate::modules::market::{Market, ORDER_TTL_TICKS, OrderSide};
+use crate::modules::names;
 use crate::modules::ore::OreKind;
+use crate::modules::pathfinding::{chebyshev, find_path};
+use crate::modules::script::{Program, ScriptHost};
+use crate::modules::spatial::ZoneIndex;
 use crate::modules::structure::{Structure, StructureKind};
-use crate::modules::view::{AgentSnapshot, OreNodeSnapshot, StructureView, WorldSnapshot};
+use crate::modules::terrain::{Block, ChunkPos, Terrain, TerrainChunkSnapshot};
+use crate::modules::territory::{TerritoryStore, ZoneClaim};
+use crate::modules::view::{
+    AgentSnapshot, OreNodeSnapshot, RuleSummary, SnapshotMeta, StructureView, WorldSnapshot,
+};
+use crate::modules::zones::{ZoneLabel, ZoneStore};
 
 pub type AgentId = u64;
 pub type Qi = u32;
@@ -25,10 +43,59 @@ pub const HARVEST_RANGE: i32 = 1;
 pub const HARVEST_PER_ACTION: Qi = 3;
 /// Default agent lifespan in ticks unless extended by the creator.
 pub const DEFAULT_MAX_AGENT_AGE: u64 = 112;
+/// Most actions one request may bundle into a single all-or-nothing turn.
+pub const MAX_BUNDLE_ACTIONS: usize = 3;
+/// Ticks an agent must wait after reproducing before it can reproduce again.
+pub const REPRODUCE_COOLDOWN: u64 = 10;
+/// Seed used when a world is created without one (tests, tools).
+pub const DEFAULT_WORLD_SEED: u64 = 0;
+/// Requests per tick at which validation fans out across threads.
+pub const PARALLEL_VALIDATION_MIN: usize = 64;
 /// Maximum movement radius per action (Chebyshev distance).
 pub const MAX_MOVE_RADIUS: i32 = 3;
-
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Health every agent spawns with.
+pub const DEFAULT_AGENT_HP: u32 = 10;
+/// How close an attacker must be to its target (Chebyshev distance).
+pub const ATTACK_RANGE: i32 = 1;
+/// Damage dealt by every attack before the Qi bonus.
+pub const ATTACK_BASE_DAMAGE: u32 = 1;
+/// Attacker Qi needed per extra point of damage.
+pub const ATTACK_QI_PER_DAMAGE: Qi = 5;
+/// How far (Chebyshev distance) an agent can dig or place blocks.
+pub const TERRAIN_REACH: i32 = 1;
+/// Qi structures run one production cycle every this many ticks.
+pub const QI_STRUCTURE_PERIOD: u64 = 3;
+/// Qi a structure hands out per cycle (drawn from the recycled pool, then the supply cap).
+pub const QI_STRUCTURE_OUTPUT: Qi = 2;
+/// Qi the owner pays per cycle to keep a structure running, wherever they are.
+pub const QI_STRUCTURE_UPKEEP: Qi = 1;
+/// For this many ticks after an attack, the attacker counts as hostile in its target's scans.
+pub const HOSTILE_MEMORY_TICKS: u64 = 20;
+/// Qi an agent recovers per tick of rest beside its own basic structure (recycled pool only).
+pub const REST_QI_PER_TICK: Qi = 1;
+/// Qi an agent pays to claim the zone it stands in.
+pub const ZONE_CLAIM_COST: Qi = 5;
+/// Longest message (in characters) an agent can say.
+pub const MAX_MESSAGE_LEN: usize = 200;
+/// Most messages an agent's inbox keeps; the oldest go first.
+pub const INBOX_LIMIT: usize = 16;
+/// Durability a structure is built with and can be repaired up to.
+pub const STRUCTURE_DURABILITY: u32 = 100;
+/// Every structure loses 1 durability every this many ticks and collapses at 0.
+pub const STRUCTURE_DECAY_PERIOD: u64 = 10;
+/// Decay is reported (`StructureDecayed`) only when durability falls to a multiple of this,
+/// each quarter of the maximum, rather than on every step.
+pub const STRUCTURE_DECAY_NOTICE: u32 = STRUCTURE_DURABILITY / 4;
+/// Qi a repair costs (recycled).
+pub const REPAIR_COST: Qi = 1;
+/// Durability one repair restores.
+pub const REPAIR_AMOUNT: u32 = 25;
+/// Percent of each item of its build cost a demolished structure gives back to its owner.
+pub const DEMOLISH_REFUND_PERCENT: Qi = 50;
+/// Reach within which an agent can give Qi to one outside its zone.
+pub const GIVE_RANGE: i32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct QiSource {
     pub id: u64,
     pub ore: OreKind,
@@ -36,9 +103,12 @@ pub struct QiSource {
     pub capacity: Qi,
     pub current: Qi,
     pub recharge_per_tick: Qi,
+    /// Set on the remains of a dead agent: the node never recharges and goes once drained.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remains_of: Option<AgentId>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct QiSourceSnapshot {
     pub id: u64,
     pub ore: OreKind,
@@ -47,13 +117,57 @@ pub struct QiSourceSnapshot {
     pub capacity: Qi,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StructureSnapshot {
     pub id: u64,
     pub kind: StructureKind,
     pub position: Position,
 }
 
+/// What an agent saw in its last scan, kept on the agent so its brain can plan with it later.
+/// Node levels are as they were at `tick`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanMemory {
+    pub tick: u64,
+    pub position: Position,
+    pub qi_sources: Vec<QiSourceSnapshot>,
+    pub structures: Vec<StructureSnapshot>,
+}
+
+/// A message an agent received.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub from: AgentId,
+    pub tick: u64,
+    pub text: String,
+}
+
+/// A living agent in scan range that endangers the scanning agent: it attacked them within
+/// `HOSTILE_MEMORY_TICKS`, or one attack from it would kill them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ThreatSnapshot {
+    pub agent_id: AgentId,
+    pub position: Position,
+    pub hp: u32,
+    /// What one attack from it would deal right now.
+    pub damage: u32,
+    /// Tick it last attacked the scanning agent, if recently.
+    pub attacked_at: Option<u64>,
+    pub lethal: bool,
+}
+
+/// What a scan from `position` sees: the data behind `Event::ScanReport`, minus the scanning
+/// agent. Built by `World::scan_from` for callers that want to look without acting, or by
+/// `World::scan_by` to include the threats to a particular agent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanReport {
+    pub position: Position,
+    pub range: i32,
+    pub nearby_qi_sources: Vec<QiSourceSnapshot>,
+    pub nearby_structures: Vec<StructureSnapshot>,
+    pub threats: Vec<ThreatSnapshot>,
+}
+
 fn pow_hash(agent_id: AgentId, tick: u64, nonce: u64) -> [u8; 32] {
     let mut hasher = Sha256::new();
     hasher.update(agent_id.to_le_bytes());
@@ -70,7 +184,169 @@ pub fn pow_valid(agent_id: AgentId, tick: u64, nonce: u64) -> bool {
     hash.iter().take(POW_DIFFICULTY_BYTES).all(|b| *b == 0)
 }
 
-fn nearest_ore_source(sources: &[QiSource], ore: OreKind, position: Position) -> Option<QiSource> {
+fn insufficient(agent_id: AgentId, shortfall: Shortfall) -> ActionError {
+    let Shortfall {
+        item,
+        required,
+        available,
+    } = shortfall;
+    match item {
+        ItemKind::Qi => ActionError::InsufficientQi {
+            agent_id,
+            required,
+            available,
+        },
+        _ => ActionError::InsufficientItem {
+            agent_id,
+            item,
+            required,
+            available,
+        },
+    }
+}
+
+/// Checks that depend only on the request itself, so they hold no matter what else happens
+/// during the tick.
+fn check_action_shape(agent_id: AgentId, action: &Action) -> Result<(), ActionError> {
+    let reach = |dx: i32, dy: i32, dz: i32| dx.abs().max(dy.abs()).max(dz.abs());
+    match *action {
+        Action::Move { dx, dy, dz } if reach(dx, dy, dz) > MAX_MOVE_RADIUS => {
+            Err(ActionError::MoveOutOfRange {
+                agent_id,
+                dx,
+                dy,
+                dz,
+            })
+        }
+        Action::Dig { dx, dy, dz } | Action::Place { dx, dy, dz, .. }
+            if reach(dx, dy, dz) == 0 || reach(dx, dy, dz) > TERRAIN_REACH =>
+        {
+            Err(ActionError::TerrainOutOfReach {
+                agent_id,
+                dx,
+                dy,
+                dz,
+            })
+        }
+        Action::Trade {
+            partner,
+            offer_amount,
+            want_amount,
+            ..
+        } if partner == agent_id || offer_amount == 0 || want_amount == 0 => {
+            Err(ActionError::InvalidTrade { agent_id, partner })
+        }
+        Action::Say { to, ref message }
+            if to == agent_id
+                || message.trim().is_empty()
+                || message.chars().count() > MAX_MESSAGE_LEN =>
+        {
+            Err(ActionError::InvalidMessage { agent_id, to })
+        }
+        Action::PostOrder {
+            ore, amount, price, ..
+        } if ore == OreKind::QI
+            || amount == 0
+            || price == 0
+            || amount.checked_mul(price).is_none() =>
+        {
+            Err(ActionError::InvalidOrder { agent_id })
+        }
+        Action::GiveQi { to, amount } if to == agent_id || amount == 0 => {
+            Err(ActionError::InvalidGift { agent_id, to })
+        }
+        // Partner 0 survives to validation only when the VM found no one to match.
+        Action::Reproduce { partner: 0 } => Err(ActionError::NoEligiblePartner { agent_id }),
+        _ => Ok(()),
+    }
+}
+
+/// Record what an applied request took this tick, so later losers can be told who won it.
+fn claim_contested(
+    claims: &mut HashMap<Contested, (AgentId, Priority)>,
+    events: &[Event],
+    priority: Priority,
+) {
+    for event in events {
+        let (agent_id, contested) = match event {
+            Event::AgentMoved { agent_id, to, .. } => {
+                (*agent_id, Contested::Cell { position: *to })
+            }
+            Event::OreNodeHarvested {
+                agent_id,
+                source_id,
+                ..
+            } => (
+                *agent_id,
+                Contested::OreNode {
+                    source_id: *source_id,
+                },
+            ),
+            _ => continue,
+        };
+        claims.insert(contested, (agent_id, priority));
+    }
+}
+
+/// Wrap a failure caused by something a higher-priority request claimed this tick.
+fn outranked(
+    error: ActionError,
+    priority: Priority,
+    claims: &HashMap<Contested, (AgentId, Priority)>,
+) -> ActionError {
+    let (agent_id, contested) = match error {
+        ActionError::PositionOccupied {
+            agent_id, target, ..
+        } => (agent_id, Contested::Cell { position: target }),
+        ActionError::OreSourceDepleted {
+            agent_id,
+            source_id,
+            ..
+        } => (agent_id, Contested::OreNode { source_id }),
+        _ => return error,
+    };
+    match claims.get(&contested) {
+        Some((winner, winner_priority)) if *winner != agent_id => ActionError::Outranked {
+            agent_id,
+            contested,
+            priority,
+            winner: *winner,
+            winner_priority: *winner_priority,
+            cause: Box::new(error),
+        },
+        _ => error,
+    }
+}
+
+/// Bundle rules that hold regardless of world state; each action is also shape-checked.
+fn check_bundle_shape(request: &ActionRequest) -> Result<(), ActionError> {
+    let agent_id = request.agent_id;
+    if request.then.len() + 1 > MAX_BUNDLE_ACTIONS {
+        return Err(ActionError::InvalidBundle {
+            agent_id,
+            reason: "too many actions",
+        });
+    }
+    for action in request.actions() {
+        if matches!(
+            action,
+            Action::MoveTo { .. } | Action::Reproduce { .. } | Action::Trade { .. }
+        ) {
+            return Err(ActionError::InvalidBundle {
+                agent_id,
+                reason: "move_to, reproduce and trade cannot be bundled",
+            });
+        }
+        check_action_shape(agent_id, &action)?;
+    }
+    Ok(())
+}
+
+fn nearest_ore_source<'a>(
+    sources: impl IntoIterator<Item = &'a QiSource>,
+    ore: OreKind,
+    position: Position,
+) -> Option<QiSource> {
     let mut best: Option<(i32, QiSource)> = None;
     for src in sources {
         if src.ore != ore {
@@ -109,7 +385,7 @@ pub fn pow_solve(agent_id: AgentId, tick: u64, start_nonce: u64) -> u64 {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -145,31 +421,142 @@ impl Position {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Zone {
     pub x: i32,
     pub y: i32,
     pub z: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum Action {
     Scan,
-    Move { dx: i32, dy: i32, dz: i32 },
-    Reproduce { partner: AgentId },
-    BuildStructure { kind: StructureKind },
-    HarvestOre { ore: OreKind, source_id: u64 },
+    Move {
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    /// Walk toward `target` along an A* path, up to `MAX_MOVE_RADIUS` steps per tick. The agent
+    /// keeps following the path on later ticks until it arrives or picks another action.
+    MoveTo {
+        target: Position,
+    },
+    Reproduce {
+        partner: AgentId,
+    },
+    BuildStructure {
+        kind: StructureKind,
+    },
+    HarvestOre {
+        ore: OreKind,
+        source_id: u64,
+    },
+    /// Harvest the `ore` node in the cell at this offset from the agent, for brains that know
+    /// where a node is but not its id. It must still be within `HARVEST_RANGE`.
+    HarvestAt {
+        ore: OreKind,
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    Trade {
+        partner: AgentId,
+        offer_ore: OreKind,
+        offer_amount: Qi,
+        want_ore: OreKind,
+        want_amount: Qi,
+    },
+    Attack {
+        target: AgentId,
+    },
+    Dig {
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    Place {
+        dx: i32,
+        dy: i32,
+        dz: i32,
+        block: Block,
+    },
+    /// Rest within `HARVEST_RANGE` of a basic structure the agent owns, recovering
+    /// `REST_QI_PER_TICK` Qi from the recycled pool; rest on later ticks to keep recovering.
+    Rest,
+    /// Say `message` to agent `to`, or with `to` 0 to every living agent within `SCAN_RANGE`.
+    /// A single recipient must be within `SCAN_RANGE` too.
+    Say {
+        to: AgentId,
+        message: String,
+    },
+    /// Pay `ZONE_CLAIM_COST` Qi to claim the zone the agent stands in; only the claimant may
+    /// build there until it dies. A zone held by another living agent can't be claimed.
+    ClaimZone,
+    /// Set the tolls of the zone the agent stands in and holds: others pay `harvest` Qi per
+    /// harvest of a node there (0 is free) and `build` Qi per structure (0 keeps building to
+    /// the owner).
+    SetToll {
+        harvest: Qi,
+        build: Qi,
+    },
+    /// Rest an order to buy or sell `amount` of `ore` at `price` Qi each on the market, paying
+    /// its escrow up front. Orders match after the tick's actions and expire after
+    /// `ORDER_TTL_TICKS`.
+    PostOrder {
+        side: OrderSide,
+        ore: OreKind,
+        amount: Qi,
+        price: Qi,
+    },
+    /// Spend the inputs of the named recipe for its output; Qi inputs are recycled.
+    Craft {
+        recipe: String,
+    },
+    /// Pay `REPAIR_COST` Qi to restore `REPAIR_AMOUNT` durability to a structure within
+    /// `HARVEST_RANGE`, anyone's; 0 picks the most worn one in reach.
+    Repair {
+        structure_id: u64,
+    },
+    /// Tear down a structure the agent owns. It gets back `DEMOLISH_REFUND_PERCENT` of the
+    /// build cost (the Qi from the recycled pool) and whatever the structure stored.
+    DemolishStructure {
+        structure_id: u64,
+    },
+    /// Hand `amount` of the agent's Qi to living agent `to` in its zone or within `GIVE_RANGE`.
+    GiveQi {
+        to: AgentId,
+        amount: Qi,
+    },
     Idle,
 }
 
 impl Action {
-    pub const fn qi_cost(&self) -> Qi {
+    pub fn qi_cost(&self) -> Qi {
         match self {
-            Action::Scan | Action::Idle => 0,
-            Action::Move { .. } => 0,
-            Action::Reproduce { .. } => 0,
-            Action::BuildStructure { .. } => 1,
-            Action::HarvestOre { .. } => 1,
+            Action::Scan
+            | Action::Idle
+            | Action::Rest
+            | Action::Say { .. }
+            | Action::SetToll { .. }
+            | Action::PostOrder { .. }
+            | Action::Craft { .. }
+            | Action::Trade { .. } => 0,
+            Action::Move { .. } | Action::MoveTo { .. } => 1,
+            Action::Reproduce { .. } => 1,
+            Action::BuildStructure { kind } => kind
+                .build_cost()
+                .iter()
+                .filter(|(item, _)| *item == ItemKind::Qi)
+                .map(|(_, amount)| *amount)
+                .sum(),
+            Action::HarvestOre { .. } | Action::HarvestAt { .. } => 1,
+            Action::Attack { .. } => 1,
+            Action::Dig { .. } | Action::Place { .. } => 1,
+            Action::ClaimZone => ZONE_CLAIM_COST,
+            Action::Repair { .. } => REPAIR_COST,
+            Action::DemolishStructure { .. } => 0,
+            Action::GiveQi { amount, .. } => *amount,
         }
     }
 
@@ -177,15 +564,30 @@ impl Action {
         match self {
             Action::Scan => "scan",
             Action::Move { .. } => "move",
+            Action::MoveTo { .. } => "move_to",
             Action::Reproduce { .. } => "reproduce",
             Action::BuildStructure { .. } => "build_structure",
-            Action::HarvestOre { .. } => "harvest",
+            Action::HarvestOre { .. } | Action::HarvestAt { .. } => "harvest",
+            Action::Trade { .. } => "trade",
+            Action::Attack { .. } => "attack",
+            Action::Dig { .. } => "dig",
+            Action::Place { .. } => "place",
+            Action::Rest => "rest",
+            Action::Say { .. } => "say",
+            Action::ClaimZone => "claim_zone",
+            Action::SetToll { .. } => "set_toll",
+            Action::PostOrder { .. } => "post_order",
+            Action::Craft { .. } => "craft",
+            Action::Repair { .. } => "repair",
+            Action::DemolishStructure { .. } => "demolish",
+            Action::GiveQi { .. } => "give_qi",
             Action::Idle => "idle",
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
 pub enum Event {
     TickStarted {
         tick: u64,
@@ -215,6 +617,20 @@ pub enum Event {
         from: Position,
         to: Position,
     },
+    PathProgress {
+        agent_id: AgentId,
+        target: Position,
+        remaining: u32,
+    },
+    PathCompleted {
+        agent_id: AgentId,
+        target: Position,
+    },
+    /// A `Reproduce { partner: 0 }` request was matched with this partner.
+    PartnerResolved {
+        agent_id: AgentId,
+        partner: AgentId,
+    },
     AgentDied {
         agent_id: AgentId,
         reason: DeathReason,
@@ -246,34 +662,270 @@ pub enum Event {
         source_id: u64,
         position: Position,
     },
+    /// An ore node's level was set from outside the simulation (admin tooling, world events).
+    OreNodeLevelSet {
+        ore: OreKind,
+        source_id: u64,
+        from: Qi,
+        to: Qi,
+    },
+    OreNodeRemoved {
+        ore: OreKind,
+        source_id: u64,
+        position: Position,
+        remaining: Qi,
+    },
+    /// A dead agent's `amount` of `ore` became node `source_id` where it died.
+    RemainsDropped {
+        agent_id: AgentId,
+        source_id: u64,
+        ore: OreKind,
+        amount: Qi,
+        position: Position,
+    },
+    OreNodeMoved {
+        ore: OreKind,
+        source_id: u64,
+        from: Position,
+        to: Position,
+    },
+    BlockDug {
+        agent_id: AgentId,
+        position: Position,
+        block: Block,
+    },
+    BlockPlaced {
+        agent_id: AgentId,
+        position: Position,
+        block: Block,
+    },
+    StructureUpkeepPaid {
+        structure_id: u64,
+        owner: AgentId,
+        amount: Qi,
+    },
+    StructureStalled {
+        structure_id: u64,
+        owner: AgentId,
+    },
+    /// An agent rested beside its structure and drew `amount` Qi from the recycled pool.
+    AgentRested {
+        agent_id: AgentId,
+        structure_id: u64,
+        amount: Qi,
+    },
+    ZoneClaimed {
+        agent_id: AgentId,
+        zone: Zone,
+        cost: Qi,
+    },
+    TollSet {
+        agent_id: AgentId,
+        zone: Zone,
+        harvest: Qi,
+        build: Qi,
+    },
+    /// `agent_id` paid `owner` the toll for harvesting or building in `zone`.
+    TollPaid {
+        agent_id: AgentId,
+        owner: AgentId,
+        zone: Zone,
+        amount: Qi,
+    },
+    /// The owner's side of a `TollPaid`; without a living owner the toll is recycled instead.
+    TollCollected {
+        owner: AgentId,
+        from: AgentId,
+        zone: Zone,
+        amount: Qi,
+    },
+    QiGiven {
+        agent_id: AgentId,
+        to: AgentId,
+        amount: Qi,
+    },
+    QiReceived {
+        agent_id: AgentId,
+        from: AgentId,
+        amount: Qi,
+    },
+    OrderPosted {
+        agent_id: AgentId,
+        order_id: u64,
+        side: OrderSide,
+        ore: OreKind,
+        amount: Qi,
+        price: Qi,
+    },
+    /// The buyer received `amount` ore (and any `refund` of its bid over `price`), the seller
+    /// `amount * price` Qi.
+    OrderFilled {
+        buyer: AgentId,
+        seller: AgentId,
+        ore: OreKind,
+        amount: Qi,
+        price: Qi,
+        refund: Qi,
+        buy_order: u64,
+        sell_order: u64,
+    },
+    /// An order left the book unfilled (expired, or its agent died) and `amount` of `item`
+    /// went back to the agent.
+    OrderExpired {
+        agent_id: AgentId,
+        order_id: u64,
+        item: ItemKind,
+        amount: Qi,
+    },
+    ItemCrafted {
+        agent_id: AgentId,
+        recipe: String,
+        item: ItemKind,
+        amount: Qi,
+    },
+    /// `to` received `message` from `from`; a broadcast delivers one per listener.
+    MessageDelivered {
+        from: AgentId,
+        to: AgentId,
+        message: String,
+    },
+    StructureProduced {
+        structure_id: u64,
+        recipient: AgentId,
+        item: ItemKind,
+        amount: Qi,
+    },
+    StructureCollected {
+        structure_id: u64,
+        source_id: u64,
+        amount: Qi,
+    },
+    StructureStored {
+        structure_id: u64,
+        item: ItemKind,
+        amount: Qi,
+    },
+    StructureScriptFaulted {
+        structure_id: u64,
+        error: String,
+    },
+    /// Durability fell to another quarter of the maximum (75, 50 or 25).
+    StructureDecayed {
+        structure_id: u64,
+        durability: u32,
+    },
+    /// The structure wore down to 0 durability and is gone; Qi it stored was recycled.
+    StructureCollapsed {
+        structure_id: u64,
+        kind: StructureKind,
+        owner: AgentId,
+        position: Position,
+    },
+    StructureRepaired {
+        agent_id: AgentId,
+        structure_id: u64,
+        durability: u32,
+    },
+    /// The owner tore the structure down and got `refund` back: part of the build cost plus
+    /// what the structure stored.
+    StructureDemolished {
+        agent_id: AgentId,
+        structure_id: u64,
+        kind: StructureKind,
+        position: Position,
+        refund: Vec<(ItemKind, Qi)>,
+    },
+    AgentAttacked {
+        attacker: AgentId,
+        target: AgentId,
+        damage: u32,
+        remaining_hp: u32,
+    },
+    TradeCompleted {
+        agent_a: AgentId,
+        agent_b: AgentId,
+        ore_a: OreKind,
+        amount_a: Qi,
+        ore_b: OreKind,
+        amount_b: Qi,
+    },
     ScanReport {
         agent_id: AgentId,
         position: Position,
         qi: Qi,
         nearby_qi_sources: Vec<QiSourceSnapshot>,
         nearby_structures: Vec<StructureSnapshot>,
+        threats: Vec<ThreatSnapshot>,
+    },
+    /// An operator switched the agent's brain mid-run, e.g. to `llm (llama3)`.
+    BrainSwapped {
+        agent_id: AgentId,
+        brain: String,
+    },
+    /// An operator acted on the agent outside the rules (`harimu admin`).
+    AdminIntervened {
+        agent_id: AgentId,
+        action: AdminAction,
+    },
+    /// Nobody acted near the agent for the world's `Hibernation::after_ticks`; it sleeps.
+    AgentHibernated {
+        agent_id: AgentId,
+    },
+    /// A dormant agent woke, because agent `by` acted nearby or (`None`) it was sent a request.
+    AgentWoke {
+        agent_id: AgentId,
+        by: Option<AgentId>,
+    },
+    /// The agent has no Qi and starves to death in `ticks_left` more ticks without any.
+    AgentStarving {
+        agent_id: AgentId,
+        ticks_left: u64,
     },
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub enum DeathReason {
     Age,
     Hazard,
     Corruption,
+    Killed,
+    /// Killed by an operator (`harimu admin kill`).
+    Admin,
+    /// Out of Qi for the world's `Starvation::after_ticks` ticks in a row.
+    Starved,
+}
+
+/// What an operator can do to an agent between ticks, bypassing costs, range and lifespan.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminAction {
+    Teleport {
+        to: Position,
+    },
+    GrantQi {
+        amount: Qi,
+    },
+    Kill,
+    /// Bring a dead agent back with full HP; one that died of age starts its lifespan over.
+    Revive,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ActionError {
     AgentNotFound(AgentId),
+    OreSourceNotFound(u64),
     AgentDead(AgentId),
+    /// Only the dead can be revived.
+    AgentAlive(AgentId),
     InsufficientQi {
         agent_id: AgentId,
         required: Qi,
         available: Qi,
     },
-    InsufficientOre {
+    InsufficientItem {
         agent_id: AgentId,
-        ore: OreKind,
+        item: ItemKind,
         required: Qi,
         available: Qi,
     },
@@ -298,10 +950,39 @@ pub enum ActionError {
         agent_id: AgentId,
         partner: AgentId,
     },
+    NoEligiblePartner {
+        agent_id: AgentId,
+    },
+    InvalidBundle {
+        agent_id: AgentId,
+        reason: &'static str,
+    },
+    ReproductionCooldown {
+        agent_id: AgentId,
+        ready_at: u64,
+    },
     StructureSpaceOccupied {
         agent_id: AgentId,
         position: Position,
     },
+    BlockedByTerrain {
+        agent_id: AgentId,
+        target: Position,
+    },
+    TerrainOutOfReach {
+        agent_id: AgentId,
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    NothingToDig {
+        agent_id: AgentId,
+        target: Position,
+    },
+    PlaceBlocked {
+        agent_id: AgentId,
+        target: Position,
+    },
     OreSourceUnavailable {
         agent_id: AgentId,
         ore: OreKind,
@@ -313,19 +994,121 @@ pub enum ActionError {
         source_id: u64,
         available: Qi,
     },
-    MoveOutOfRange {
+    NoOreNodeAt {
         agent_id: AgentId,
-        dx: i32,
-        dy: i32,
-        dz: i32,
+        ore: OreKind,
+        target: Position,
     },
-}
-
-impl fmt::Display for ActionError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
+    NoRestingPlace {
+        agent_id: AgentId,
+    },
+    NothingToRecover {
+        agent_id: AgentId,
+    },
+    /// Empty, longer than `MAX_MESSAGE_LEN`, or addressed to the speaker.
+    InvalidMessage {
+        agent_id: AgentId,
+        to: AgentId,
+    },
+    /// Agent `to` (anyone, for a broadcast) isn't alive within `SCAN_RANGE`.
+    NoListener {
+        agent_id: AgentId,
+        to: AgentId,
+    },
+    /// A claim on a zone `owner` already holds, or a build in it by anyone else while it
+    /// charges no build toll.
+    ZoneClaimed {
+        agent_id: AgentId,
+        zone: Zone,
+        owner: AgentId,
+    },
+    NotZoneOwner {
+        agent_id: AgentId,
+        zone: Zone,
+    },
+    /// An order for Qi itself, for nothing, for free, or too large to escrow.
+    InvalidOrder {
+        agent_id: AgentId,
+    },
+    UnknownRecipe {
+        agent_id: AgentId,
+        recipe: String,
+    },
+    /// No structure with the id (or, for 0, none worn) within `HARVEST_RANGE`.
+    NothingToRepair {
+        agent_id: AgentId,
+        structure_id: u64,
+    },
+    /// No structure with the id within `HARVEST_RANGE`.
+    StructureNotFound {
+        agent_id: AgentId,
+        structure_id: u64,
+    },
+    NotStructureOwner {
+        agent_id: AgentId,
+        structure_id: u64,
+        owner: AgentId,
+    },
+    MoveOutOfRange {
+        agent_id: AgentId,
+        dx: i32,
+        dy: i32,
+        dz: i32,
+    },
+    NoPath {
+        agent_id: AgentId,
+        target: Position,
+    },
+    TradeDeclined {
+        agent_id: AgentId,
+        partner: AgentId,
+    },
+    InvalidTrade {
+        agent_id: AgentId,
+        partner: AgentId,
+    },
+    TargetNotFound {
+        agent_id: AgentId,
+        target: AgentId,
+    },
+    TargetOutOfRange {
+        agent_id: AgentId,
+        target: AgentId,
+    },
+    /// A gift to the giver itself or of no Qi.
+    InvalidGift {
+        agent_id: AgentId,
+        to: AgentId,
+    },
+    /// The recipient of a gift is missing or dead.
+    RecipientNotFound {
+        agent_id: AgentId,
+        to: AgentId,
+    },
+    /// The recipient of a gift is neither in the giver's zone nor within `GIVE_RANGE`.
+    RecipientOutOfReach {
+        agent_id: AgentId,
+        to: AgentId,
+    },
+    /// A higher-priority request took `contested` earlier in the tick; `cause` is the failure
+    /// that followed from it.
+    Outranked {
+        agent_id: AgentId,
+        contested: Contested,
+        priority: Priority,
+        winner: AgentId,
+        winner_priority: Priority,
+        cause: Box<ActionError>,
+    },
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
             ActionError::AgentNotFound(id) => write!(f, "agent {} not found", id),
+            ActionError::OreSourceNotFound(id) => write!(f, "ore node {} not found", id),
             ActionError::AgentDead(id) => write!(f, "agent {} is dead", id),
+            ActionError::AgentAlive(id) => write!(f, "agent {} is alive", id),
             ActionError::InsufficientQi {
                 agent_id,
                 required,
@@ -335,18 +1118,15 @@ impl fmt::Display for ActionError {
                 "agent {} has insufficient qi: required {}, available {}",
                 agent_id, required, available
             ),
-            ActionError::InsufficientOre {
+            ActionError::InsufficientItem {
                 agent_id,
-                ore,
+                item,
                 required,
                 available,
             } => write!(
                 f,
                 "agent {} has insufficient {}: required {}, available {}",
-                agent_id,
-                ore,
-                required,
-                available
+                agent_id, item, required, available
             ),
             ActionError::InvalidPow { agent_id, nonce } => {
                 write!(f, "invalid PoW for agent {} with nonce {}", agent_id, nonce)
@@ -365,21 +1145,59 @@ impl fmt::Display for ActionError {
                 "agent {} reproduction declined/not mutually agreed with {}",
                 agent_id, partner
             ),
-            ActionError::PartnerNotFound { agent_id, partner } => write!(
+            ActionError::PartnerNotFound { agent_id, partner } => {
+                write!(f, "agent {} partner {} not found", agent_id, partner)
+            }
+            ActionError::PartnerOutOfZone { agent_id, partner } => {
+                write!(f, "agent {} partner {} not in same zone", agent_id, partner)
+            }
+            ActionError::InvalidBundle { agent_id, reason } => {
+                write!(
+                    f,
+                    "agent {} submitted an invalid bundle: {}",
+                    agent_id, reason
+                )
+            }
+            ActionError::NoEligiblePartner { agent_id } => write!(
                 f,
-                "agent {} reproduction partner {} not found",
-                agent_id, partner
+                "agent {} has no eligible reproduction partner in its zone",
+                agent_id
             ),
-            ActionError::PartnerOutOfZone { agent_id, partner } => write!(
+            ActionError::ReproductionCooldown { agent_id, ready_at } => write!(
                 f,
-                "agent {} reproduction partner {} not in same zone",
-                agent_id, partner
+                "agent {} cannot reproduce again until tick {}",
+                agent_id, ready_at
             ),
             ActionError::StructureSpaceOccupied { agent_id, position } => write!(
                 f,
                 "agent {} cannot build structure at ({}, {}, {}) (occupied)",
                 agent_id, position.x, position.y, position.z
             ),
+            ActionError::BlockedByTerrain { agent_id, target } => write!(
+                f,
+                "agent {} cannot move into solid terrain at ({}, {}, {})",
+                agent_id, target.x, target.y, target.z
+            ),
+            ActionError::TerrainOutOfReach {
+                agent_id,
+                dx,
+                dy,
+                dz,
+            } => write!(
+                f,
+                "agent {} cannot reach block at offset ({}, {}, {}); max reach is {}",
+                agent_id, dx, dy, dz, TERRAIN_REACH
+            ),
+            ActionError::NothingToDig { agent_id, target } => write!(
+                f,
+                "agent {} has nothing to dig at ({}, {}, {})",
+                agent_id, target.x, target.y, target.z
+            ),
+            ActionError::PlaceBlocked { agent_id, target } => write!(
+                f,
+                "agent {} cannot place a block at ({}, {}, {}) (not empty air)",
+                agent_id, target.x, target.y, target.z
+            ),
             ActionError::OreSourceUnavailable {
                 agent_id,
                 ore,
@@ -411,26 +1229,279 @@ impl fmt::Display for ActionError {
                 ore,
                 source_id,
                 available,
-                HARVEST_PER_ACTION
+                ore.harvest_rate()
+            ),
+            ActionError::NoOreNodeAt {
+                agent_id,
+                ore,
+                target,
+            } => write!(
+                f,
+                "agent {} finds no {} node at ({}, {}, {})",
+                agent_id, ore, target.x, target.y, target.z
+            ),
+            ActionError::NoRestingPlace { agent_id } => write!(
+                f,
+                "agent {} has no basic structure of its own within reach to rest at",
+                agent_id
+            ),
+            ActionError::NothingToRecover { agent_id } => write!(
+                f,
+                "agent {} rests but the recycled Qi pool is empty",
+                agent_id
+            ),
+            ActionError::InvalidMessage { agent_id, to } => write!(
+                f,
+                "agent {} can't say that to agent {}: messages are 1 to {} characters and not to oneself",
+                agent_id, to, MAX_MESSAGE_LEN
+            ),
+            ActionError::ZoneClaimed {
+                agent_id,
+                zone,
+                owner,
+            } if agent_id == owner => write!(
+                f,
+                "agent {} already holds zone ({}, {}, {})",
+                agent_id, zone.x, zone.y, zone.z
+            ),
+            ActionError::ZoneClaimed {
+                agent_id,
+                zone,
+                owner,
+            } => write!(
+                f,
+                "agent {} can't claim or build in zone ({}, {}, {}): agent {} holds it",
+                agent_id, zone.x, zone.y, zone.z, owner
+            ),
+            ActionError::InvalidOrder { agent_id } => write!(
+                f,
+                "agent {} posted an invalid order: trade a non-Qi ore, with amount and price above 0",
+                agent_id
+            ),
+            ActionError::NothingToRepair {
+                agent_id,
+                structure_id: 0,
+            } => write!(f, "agent {} has no worn structure in reach", agent_id),
+            ActionError::NothingToRepair {
+                agent_id,
+                structure_id,
+            } => write!(
+                f,
+                "agent {} can't repair structure {}: not in reach or not worn",
+                agent_id, structure_id
+            ),
+            ActionError::StructureNotFound {
+                agent_id,
+                structure_id,
+            } => write!(
+                f,
+                "agent {} can't find structure {} within reach",
+                agent_id, structure_id
+            ),
+            ActionError::NotStructureOwner {
+                agent_id,
+                structure_id,
+                owner,
+            } => write!(
+                f,
+                "agent {} can't demolish structure {}: agent {} owns it",
+                agent_id, structure_id, owner
+            ),
+            ActionError::UnknownRecipe { agent_id, recipe } => {
+                write!(f, "agent {} knows no recipe '{}'", agent_id, recipe)
+            }
+            ActionError::NotZoneOwner { agent_id, zone } => write!(
+                f,
+                "agent {} doesn't hold zone ({}, {}, {})",
+                agent_id, zone.x, zone.y, zone.z
+            ),
+            ActionError::NoListener { agent_id, to: 0 } => write!(
+                f,
+                "agent {} has nobody within scan range to hear it",
+                agent_id
+            ),
+            ActionError::NoListener { agent_id, to } => write!(
+                f,
+                "agent {} can't reach agent {}: it isn't alive within scan range",
+                agent_id, to
+            ),
+            ActionError::NoPath { agent_id, target } => write!(
+                f,
+                "agent {} has no path to ({}, {}, {})",
+                agent_id, target.x, target.y, target.z
             ),
-            ActionError::MoveOutOfRange { agent_id, dx, dy, dz } => write!(
+            ActionError::MoveOutOfRange {
+                agent_id,
+                dx,
+                dy,
+                dz,
+            } => write!(
                 f,
                 "agent {} move exceeds max radius {} (requested {},{},{} )",
                 agent_id, MAX_MOVE_RADIUS, dx, dy, dz
             ),
+            ActionError::TradeDeclined { agent_id, partner } => write!(
+                f,
+                "agent {} trade declined/not mutually agreed with {}",
+                agent_id, partner
+            ),
+            ActionError::InvalidTrade { agent_id, partner } => write!(
+                f,
+                "agent {} trade with {} must exchange non-zero amounts with another agent",
+                agent_id, partner
+            ),
+            ActionError::TargetNotFound { agent_id, target } => write!(
+                f,
+                "agent {} attack target {} not found or already dead",
+                agent_id, target
+            ),
+            ActionError::InvalidGift { agent_id, to } => write!(
+                f,
+                "agent {} can't give agent {} nothing or to itself",
+                agent_id, to
+            ),
+            ActionError::RecipientNotFound { agent_id, to } => write!(
+                f,
+                "agent {} can't give Qi to agent {}: not found or dead",
+                agent_id, to
+            ),
+            ActionError::RecipientOutOfReach { agent_id, to } => write!(
+                f,
+                "agent {} can't give Qi to agent {}: not in its zone or within {}",
+                agent_id, to, GIVE_RANGE
+            ),
+            ActionError::TargetOutOfRange { agent_id, target } => write!(
+                f,
+                "agent {} attack target {} out of range (max {})",
+                agent_id, target, ATTACK_RANGE
+            ),
+            ActionError::Outranked {
+                agent_id,
+                contested,
+                priority,
+                winner,
+                winner_priority,
+                cause,
+            } => write!(
+                f,
+                "agent {} lost {} to agent {} ({} beats {}): {}",
+                agent_id, contested, winner, winner_priority, priority, cause
+            ),
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ActionRequest {
     pub agent_id: AgentId,
     pub action: Action,
+    /// Further actions applied right after `action` in the same tick. The whole bundle
+    /// succeeds or is rolled back together; see `ActionRequest::bundle`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub then: Vec<Action>,
+    /// Qi put up for priority this tick; see `Priority`. Reserved before the request is
+    /// applied, spent if it succeeds and refunded if it is rejected.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub stake: Qi,
+    /// Planned by a paid brain call; the world's thinking cost is charged when the tick runs.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub thought: bool,
+}
+
+fn is_zero(qi: &Qi) -> bool {
+    *qi == 0
+}
+
+fn is_false(flag: &bool) -> bool {
+    !*flag
 }
 
 impl ActionRequest {
     pub fn new(agent_id: AgentId, action: Action) -> Self {
-        Self { agent_id, action }
+        Self {
+            agent_id,
+            action,
+            then: Vec::new(),
+            stake: 0,
+            thought: false,
+        }
+    }
+
+    /// Stake `stake` Qi so this request is applied ahead of lower stakes this tick.
+    pub fn with_stake(mut self, stake: Qi) -> Self {
+        self.stake = stake;
+        self
+    }
+
+    /// Mark the request as planned by a brain call, so it pays `World::thinking_cost`.
+    pub fn thought(mut self) -> Self {
+        self.thought = true;
+        self
+    }
+
+    /// One turn of up to `MAX_BUNDLE_ACTIONS` actions, applied in order with all-or-nothing
+    /// semantics. The agent must afford the bundle's combined Qi cost up front. Multi-tick
+    /// and consent-based actions (`move_to`, `reproduce`, `trade`) cannot be bundled.
+    pub fn bundle(agent_id: AgentId, actions: impl IntoIterator<Item = Action>) -> Self {
+        let mut actions = actions.into_iter();
+        Self {
+            agent_id,
+            action: actions.next().unwrap_or(Action::Idle),
+            then: actions.collect(),
+            stake: 0,
+            thought: false,
+        }
+    }
+
+    /// Every action of the request, in the order they are applied.
+    pub fn actions(&self) -> impl Iterator<Item = Action> + '_ {
+        std::iter::once(self.action.clone()).chain(self.then.iter().cloned())
+    }
+
+    pub fn is_bundle(&self) -> bool {
+        !self.then.is_empty()
+    }
+}
+
+/// Where a request stands when requests contend for the same thing (a cell, the last units of
+/// an ore node). Requests are applied highest priority first: the larger stake wins, and equal
+/// stakes fall back to initiative, the request's position in the tick's batch (earlier first).
+/// Unfinished `MoveTo` paths continue after every submitted request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Priority {
+    pub stake: Qi,
+    pub initiative: u32,
+}
+
+impl Priority {
+    /// The rank used to order application: lower ranks go first.
+    fn rank(&self) -> (std::cmp::Reverse<Qi>, u32) {
+        (std::cmp::Reverse(self.stake), self.initiative)
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stake {}, initiative {}", self.stake, self.initiative)
+    }
+}
+
+/// Something only one request per tick can take.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Contested {
+    Cell { position: Position },
+    OreNode { source_id: u64 },
+}
+
+impl fmt::Display for Contested {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Contested::Cell { position } => {
+                write!(f, "cell ({}, {}, {})", position.x, position.y, position.z)
+            }
+            Contested::OreNode { source_id } => write!(f, "ore node {}", source_id),
+        }
     }
 }
 
@@ -440,92 +1511,436 @@ pub struct ActionRejection {
     pub error: ActionError,
 }
 
+fn ordered_pair(a: AgentId, b: AgentId) -> (AgentId, AgentId) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TradeTerms {
+    partner: AgentId,
+    offer_ore: OreKind,
+    offer_amount: Qi,
+    want_ore: OreKind,
+    want_amount: Qi,
+}
+
+/// What a bundle changed, so a failed one can be undone without copying the world: the
+/// world's counters, and the state of each agent, node, structure, block and claim as it was
+/// before the first bundled action that could reach it.
 #[derive(Debug)]
+struct BundleUndo {
+    rng: ChaCha12Rng,
+    next_structure_id: u64,
+    next_qi_source_id: u64,
+    next_order_id: u64,
+    recycled_qi: u64,
+    granted_qi: u64,
+    stats: WorldStats,
+    events_len: usize,
+    agents: BTreeMap<AgentId, Agent>,
+    structures: BTreeMap<u64, Structure>,
+    qi_sources: BTreeMap<u64, QiSource>,
+    blocks: HashMap<Position, Block>,
+    claims: HashMap<Zone, Option<ZoneClaim>>,
+}
+
+/// Trade intents submitted this tick; a pair settles once, when the first side is applied.
+#[derive(Debug, Default)]
+struct TradeBook {
+    offers: HashMap<AgentId, TradeTerms>,
+    settled: HashSet<(AgentId, AgentId)>,
+}
+
+impl TradeBook {
+    fn from_requests(actions: &[ActionRequest]) -> Self {
+        let mut book = TradeBook::default();
+        for req in actions {
+            if let Action::Trade {
+                partner,
+                offer_ore,
+                offer_amount,
+                want_ore,
+                want_amount,
+            } = req.action
+            {
+                book.offers.insert(
+                    req.agent_id,
+                    TradeTerms {
+                        partner,
+                        offer_ore,
+                        offer_amount,
+                        want_ore,
+                        want_amount,
+                    },
+                );
+            }
+        }
+        book
+    }
+
+    /// Both agents named each other and their offers mirror each other exactly.
+    fn is_mutual(&self, agent_id: AgentId, terms: &TradeTerms) -> bool {
+        let Some(other) = self.offers.get(&terms.partner) else {
+            return false;
+        };
+        other.partner == agent_id
+            && other.offer_ore == terms.want_ore
+            && other.offer_amount == terms.want_amount
+            && other.want_ore == terms.offer_ore
+            && other.want_amount == terms.offer_amount
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Agent {
     pub id: AgentId,
     pub name: String,
-    pub qi: Qi,
-    pub transistors: Qi,
+    pub inventory: Inventory,
     pub position: Position,
     pub alive: bool,
+    pub hp: u32,
     pub age: u64,
     pub max_age: u64,
     pub discovered_zones: HashSet<Zone>,
+    /// Tick of the agent's last successful reproduction.
+    #[serde(default)]
+    pub last_reproduced: Option<u64>,
+    /// Asleep under the world's `Hibernation` rules: brains skip it and it ages slowly.
+    #[serde(default)]
+    pub dormant: bool,
+    /// First tick of the agent's current stretch with no other agent acting nearby.
+    #[serde(default)]
+    pub quiet_since: Option<u64>,
+    /// Ticks in a row the agent ended with no Qi.
+    #[serde(default)]
+    pub starving_ticks: u64,
+    /// Tick each agent last attacked this one.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attacked_by: BTreeMap<AgentId, u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_scan: Option<ScanMemory>,
+    /// Messages received, oldest first, at most `INBOX_LIMIT`.
+    #[serde(default, skip_serializing_if = "VecDeque::is_empty")]
+    pub inbox: VecDeque<Message>,
 }
 
 impl Agent {
+    /// Whether the reproduction cooldown has passed by `tick`.
+    pub fn can_reproduce_at(&self, tick: u64) -> bool {
+        self.last_reproduced
+            .is_none_or(|last| tick >= last.saturating_add(REPRODUCE_COOLDOWN))
+    }
+
+    pub fn qi(&self) -> Qi {
+        self.inventory.get(ItemKind::Qi)
+    }
+
+    pub fn item(&self, item: ItemKind) -> Qi {
+        self.inventory.get(item)
+    }
+
+    pub fn gain_item(&mut self, item: ItemKind, amount: Qi) {
+        self.inventory.gain_item(item, amount);
+    }
+
+    pub fn spend_item(&mut self, item: ItemKind, amount: Qi) -> Result<(), ActionError> {
+        let agent_id = self.id;
+        self.inventory
+            .spend_item(item, amount)
+            .map_err(|shortfall| insufficient(agent_id, shortfall))
+    }
+
     fn spend_qi(&mut self, amount: Qi) -> Result<(), ActionError> {
-        if self.qi < amount {
-            return Err(ActionError::InsufficientQi {
-                agent_id: self.id,
-                required: amount,
-                available: self.qi,
-            });
+        self.spend_item(ItemKind::Qi, amount)
+    }
+}
+
+/// Aggregates over the whole world, kept up to date by the world itself so readers (status
+/// displays, metrics exporters) don't walk every collection on each poll.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct WorldStats {
+    pub tick: u64,
+    pub alive_agents: usize,
+    pub dead_agents: usize,
+    /// Qi held by agents, living or dead.
+    pub agent_qi: u64,
+    /// Qi left in Qi nodes (other ores are not counted).
+    pub node_qi: u64,
+    pub structures: BTreeMap<StructureKind, usize>,
+    /// Qi returned to the recycled pool and not yet recharged into nodes.
+    pub recycled_qi: u64,
+}
+
+impl WorldStats {
+    /// An agent gained `amount` of `item`; only Qi is counted.
+    fn gain(&mut self, item: ItemKind, amount: Qi) {
+        if item == ItemKind::Qi {
+            self.agent_qi = self.agent_qi.saturating_add(amount as u64);
         }
+    }
 
-        self.qi -= amount;
-        Ok(())
+    /// An agent paid or lost `amount` of `item`; only Qi is counted.
+    fn spend(&mut self, item: ItemKind, amount: Qi) {
+        if item == ItemKind::Qi {
+            self.agent_qi = self.agent_qi.saturating_sub(amount as u64);
+        }
     }
 
-    fn gain_ore(&mut self, ore: OreKind, amount: Qi) {
-        match ore {
-            OreKind::Qi => {
-                self.qi = self.qi.saturating_add(amount);
-            }
-            OreKind::Transistor => {
-                self.transistors = self.transistors.saturating_add(amount);
-            }
+    fn spend_all(&mut self, bill: &[(ItemKind, Qi)]) {
+        for (item, amount) in bill {
+            self.spend(*item, *amount);
         }
     }
 
-    fn spend_ore(&mut self, ore: OreKind, amount: Qi) -> Result<(), ActionError> {
-        match ore {
-            OreKind::Qi => self.spend_qi(amount),
-            OreKind::Transistor => {
-                if self.transistors < amount {
-                    return Err(ActionError::InsufficientOre {
-                        agent_id: self.id,
-                        ore,
-                        required: amount,
-                        available: self.transistors,
-                    });
-                }
-                self.transistors -= amount;
-                Ok(())
+    fn agent_died(&mut self) {
+        self.alive_agents = self.alive_agents.saturating_sub(1);
+        self.dead_agents += 1;
+    }
+
+    fn agent_revived(&mut self) {
+        self.alive_agents += 1;
+        self.dead_agents = self.dead_agents.saturating_sub(1);
+    }
+
+    fn add_structure(&mut self, kind: StructureKind) {
+        *self.structures.entry(kind).or_default() += 1;
+    }
+
+    fn remove_structure(&mut self, kind: StructureKind) {
+        if let Some(count) = self.structures.get_mut(&kind) {
+            *count -= 1;
+            if *count == 0 {
+                self.structures.remove(&kind);
             }
         }
     }
 }
 
-#[derive(Debug, Default)]
+/// Where the world's Qi is, against what it may hold. Qi enters only as nodes mint it, up to
+/// the supply cap (the Qi infused into the world), or as granted Qi: agents' starting Qi,
+/// newborns' first Qi and admin grants. Spending moves Qi to the recycled pool, never out of
+/// the world, so the world can hold at most `limit`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct QiAudit {
+    /// Qi held by agents, living or dead.
+    pub agent_qi: u64,
+    /// Qi left in Qi nodes.
+    pub node_qi: u64,
+    /// Qi stored in structures.
+    pub structure_qi: u64,
+    pub recycled_qi: u64,
+    /// `None` when no Qi was infused, in which case nodes and structures mint without limit.
+    pub supply_cap: Option<u64>,
+    pub granted_qi: u64,
+    /// Qi in the nodes and structures of zones evicted from memory.
+    pub cold_qi: u64,
+    /// Qi held in escrow for buy orders on the market.
+    pub market_qi: u64,
+}
+
+impl QiAudit {
+    /// All the Qi in the world.
+    pub fn held(&self) -> u64 {
+        self.agent_qi
+            .saturating_add(self.node_qi)
+            .saturating_add(self.structure_qi)
+            .saturating_add(self.recycled_qi)
+            .saturating_add(self.cold_qi)
+            .saturating_add(self.market_qi)
+    }
+
+    /// The most Qi the world may hold, if it has a supply cap.
+    pub fn limit(&self) -> Option<u64> {
+        self.supply_cap
+            .map(|cap| cap.saturating_add(self.granted_qi))
+    }
+
+    /// Qi held beyond `limit`, i.e. minted past the cap or created off the books; 0 while
+    /// conservation holds.
+    pub fn excess(&self) -> u64 {
+        self.limit()
+            .map_or(0, |limit| self.held().saturating_sub(limit))
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct World {
     pub(crate) tick: u64,
+    /// Seed of `rng`; every random choice the simulation makes draws from it.
+    seed: u64,
+    rng: ChaCha12Rng,
     next_agent_id: AgentId,
     next_structure_id: u64,
     next_qi_source_id: u64,
     max_qi_supply: Option<u64>,
     recycled_qi: u64,
+    /// Qi that entered the world outside the supply cap; see `QiAudit`.
+    granted_qi: u64,
+    /// Qi charged per brain call; 0 makes thinking free.
+    thinking_cost: Qi,
     agents: HashMap<AgentId, Agent>,
     events: Vec<Event>,
     occupied: HashMap<Position, AgentId>,
     structures: Vec<Structure>,
     qi_sources: Vec<QiSource>,
+    /// Zone buckets over `qi_sources` and `structures` for range queries.
+    source_index: ZoneIndex,
+    structure_index: ZoneIndex,
+    terrain: Terrain,
+    zone_labels: ZoneStore,
+    territories: TerritoryStore,
+    market: Market,
+    pending_paths: HashMap<AgentId, PendingPath>,
+    stats: WorldStats,
+    /// Events from outside the simulation, emitted with the next tick.
+    queued_events: Vec<Event>,
+    hibernation: Option<Hibernation>,
+    starvation: Option<Starvation>,
+    /// Zones whose nodes, structures and terrain were evicted from memory, with the Qi they hold.
+    cold_zones: HashMap<Zone, u64>,
 }
 
-impl World {
-    pub fn new() -> Self {
-        Self {
-            tick: 0,
-            next_agent_id: 1,
-            next_structure_id: 1,
-            next_qi_source_id: 1,
-            max_qi_supply: None,
-            recycled_qi: 0,
-            agents: HashMap::new(),
+/// A zone's ore nodes, structures and terrain while it is out of memory; see
+/// `World::evict_zone`. Nothing in a cold zone recharges, produces or pays upkeep.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ColdZone {
+    pub zone: Zone,
+    pub structures: Vec<Structure>,
+    pub qi_sources: Vec<QiSource>,
+    pub terrain: Vec<TerrainChunkSnapshot>,
+}
+
+impl ColdZone {
+    /// Qi left in the zone's Qi nodes and stored in its structures.
+    pub fn qi(&self) -> u64 {
+        let nodes = self
+            .qi_sources
+            .iter()
+            .filter(|s| s.ore == OreKind::QI)
+            .map(|s| s.current as u64);
+        let stored = self
+            .structures
+            .iter()
+            .map(|s| s.storage.get(ItemKind::Qi) as u64);
+        nodes.chain(stored).sum()
+    }
+}
+
+/// When idle agents fall asleep. An agent with no other agent acting within `radius` blocks
+/// for `after_ticks` ticks goes dormant: brains stop planning for it and it ages one year
+/// every `age_every` ticks (0 stops aging). Another agent acting within `radius`, or a
+/// request for the dormant agent itself, wakes it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hibernation {
+    pub after_ticks: u64,
+    pub radius: i32,
+    #[serde(default)]
+    pub age_every: u64,
+}
+
+/// When agents starve. An agent that ends `after_ticks` ticks in a row with no Qi dies of
+/// `DeathReason::Starved`; on each of the last `warn_ticks` ticks before that it gets an
+/// `AgentStarving` event. Dormant agents don't starve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Starvation {
+    pub after_ticks: u64,
+    #[serde(default)]
+    pub warn_ticks: u64,
+}
+
+/// Remaining steps of an agent's `MoveTo`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct PendingPath {
+    target: Position,
+    steps: VecDeque<Position>,
+}
+
+/// Everything needed to rebuild a `World` after a restart. Produced by `World::to_persistent`
+/// and turned back into a world by `World::from_persistent`; the event log and zone labels
+/// (which have their own store) are not included.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PersistentWorld {
+    tick: u64,
+    seed: u64,
+    #[serde(default)]
+    rng: Option<ChaCha12Rng>,
+    next_agent_id: AgentId,
+    next_structure_id: u64,
+    next_qi_source_id: u64,
+    max_qi_supply: Option<u64>,
+    recycled_qi: u64,
+    #[serde(default)]
+    granted_qi: Option<u64>,
+    #[serde(default)]
+    thinking_cost: Qi,
+    agents: Vec<Agent>,
+    occupied: Vec<(Position, AgentId)>,
+    structures: Vec<Structure>,
+    qi_sources: Vec<QiSource>,
+    terrain: Vec<TerrainChunkSnapshot>,
+    pending_paths: Vec<(AgentId, PendingPath)>,
+    #[serde(default)]
+    hibernation: Option<Hibernation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    starvation: Option<Starvation>,
+    #[serde(default)]
+    cold_zones: Vec<(Zone, u64)>,
+    #[serde(default, skip_serializing_if = "TerritoryStore::is_empty")]
+    territories: TerritoryStore,
+    #[serde(default, skip_serializing_if = "Market::is_empty")]
+    market: Market,
+}
+
+impl PersistentWorld {
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::with_seed(DEFAULT_WORLD_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            tick: 0,
+            seed,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            next_agent_id: 1,
+            next_structure_id: 1,
+            next_qi_source_id: 1,
+            max_qi_supply: None,
+            recycled_qi: 0,
+            granted_qi: 0,
+            thinking_cost: 0,
+            agents: HashMap::new(),
             events: Vec::new(),
             occupied: HashMap::new(),
             structures: Vec::new(),
             qi_sources: Vec::new(),
+            source_index: ZoneIndex::new(),
+            structure_index: ZoneIndex::new(),
+            terrain: Terrain::new(),
+            zone_labels: ZoneStore::default(),
+            territories: TerritoryStore::default(),
+            market: Market::default(),
+            pending_paths: HashMap::new(),
+            stats: WorldStats::default(),
+            queued_events: Vec::new(),
+            hibernation: None,
+            starvation: None,
+            cold_zones: HashMap::new(),
         }
     }
 
@@ -533,15 +1948,203 @@ impl World {
         self.tick
     }
 
-    pub fn spawn_agent(
-        &mut self,
-        name: impl Into<String>,
-        qi: Qi,
-        position: Position,
-    ) -> AgentId {
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Serializable copy of the full simulation state, in a stable order.
+    pub fn to_persistent(&self) -> PersistentWorld {
+        let mut agents: Vec<Agent> = self.agents.values().cloned().collect();
+        agents.sort_by_key(|a| a.id);
+        let mut occupied: Vec<(Position, AgentId)> =
+            self.occupied.iter().map(|(pos, id)| (*pos, *id)).collect();
+        occupied.sort();
+        let mut pending_paths: Vec<(AgentId, PendingPath)> = self
+            .pending_paths
+            .iter()
+            .map(|(id, path)| (*id, path.clone()))
+            .collect();
+        pending_paths.sort_by_key(|(id, _)| *id);
+        let mut cold_zones: Vec<(Zone, u64)> = self
+            .cold_zones
+            .iter()
+            .map(|(zone, qi)| (*zone, *qi))
+            .collect();
+        cold_zones.sort();
+
+        PersistentWorld {
+            tick: self.tick,
+            seed: self.seed,
+            rng: Some(self.rng.clone()),
+            next_agent_id: self.next_agent_id,
+            next_structure_id: self.next_structure_id,
+            next_qi_source_id: self.next_qi_source_id,
+            max_qi_supply: self.max_qi_supply,
+            recycled_qi: self.recycled_qi,
+            granted_qi: Some(self.granted_qi),
+            thinking_cost: self.thinking_cost,
+            agents,
+            occupied,
+            structures: self.structures.clone(),
+            qi_sources: self.qi_sources.clone(),
+            terrain: self.terrain.snapshot(),
+            pending_paths,
+            hibernation: self.hibernation,
+            starvation: self.starvation,
+            cold_zones,
+            territories: self.territories.clone(),
+            market: self.market.clone(),
+        }
+    }
+
+    /// Rebuild a world saved with `to_persistent`, random stream included. Saves from before
+    /// the stream was recorded restart it from the seed mixed with the saved tick.
+    pub fn from_persistent(saved: PersistentWorld) -> Result<Self, String> {
+        let mut world = Self::with_seed(saved.seed);
+        world.rng = saved
+            .rng
+            .unwrap_or_else(|| ChaCha12Rng::seed_from_u64(saved.seed ^ saved.tick.rotate_left(32)));
+        world.tick = saved.tick;
+        world.next_agent_id = saved.next_agent_id;
+        world.next_structure_id = saved.next_structure_id;
+        world.next_qi_source_id = saved.next_qi_source_id;
+        world.max_qi_supply = saved.max_qi_supply;
+        world.recycled_qi = saved.recycled_qi;
+        world.stats.tick = saved.tick;
+        world.stats.recycled_qi = saved.recycled_qi;
+        world.thinking_cost = saved.thinking_cost;
+        world.hibernation = saved.hibernation;
+        world.starvation = saved.starvation;
+        world.cold_zones = saved.cold_zones.into_iter().collect();
+        world.territories = saved.territories;
+        world.market = saved.market;
+
+        for agent in saved.agents {
+            if agent.id >= world.next_agent_id {
+                return Err(format!(
+                    "agent {} is not below the next agent id {}",
+                    agent.id, world.next_agent_id
+                ));
+            }
+            if agent.alive {
+                world.stats.alive_agents += 1;
+            } else {
+                world.stats.dead_agents += 1;
+            }
+            world.stats.gain(ItemKind::Qi, agent.qi());
+            world.agents.insert(agent.id, agent);
+        }
+        for (position, agent_id) in saved.occupied {
+            match world.agents.get(&agent_id) {
+                Some(agent) if agent.alive && agent.position == position => {
+                    world.occupied.insert(position, agent_id);
+                }
+                _ => {
+                    return Err(format!(
+                        "occupancy at ({}, {}, {}) names agent {}, which is not there",
+                        position.x, position.y, position.z, agent_id
+                    ));
+                }
+            }
+        }
+        for structure in saved.structures {
+            world
+                .structure_index
+                .insert(world.structures.len(), structure.position);
+            world.stats.add_structure(structure.kind);
+            world.structures.push(structure);
+        }
+        for source in saved.qi_sources {
+            world
+                .source_index
+                .insert(world.qi_sources.len(), source.position);
+            world.track_node_qi(source.ore, 0, source.current);
+            world.qi_sources.push(source);
+        }
+        world.terrain = Terrain::from_snapshot(&saved.terrain);
+        world.pending_paths = saved
+            .pending_paths
+            .into_iter()
+            .filter(|(id, _)| world.agents.contains_key(id))
+            .collect();
+        // Saves from before granted Qi was recorded count whatever exceeds the cap as granted.
+        world.granted_qi = saved
+            .granted_qi
+            .unwrap_or_else(|| world.qi_audit().excess());
+        Ok(world)
+    }
+
+    /// Live aggregates, updated wherever an agent, node, structure or the recycled pool
+    /// changes, so reading them never walks the world.
+    pub fn stats(&self) -> &WorldStats {
+        &self.stats
+    }
+
+    /// `stats` counted from scratch; tests check the running totals against it each tick.
+    #[cfg(test)]
+    fn recount_stats(&self) -> WorldStats {
+        let mut stats = WorldStats {
+            tick: self.tick,
+            recycled_qi: self.recycled_qi,
+            ..WorldStats::default()
+        };
+        for agent in self.agents.values() {
+            if agent.alive {
+                stats.alive_agents += 1;
+            } else {
+                stats.dead_agents += 1;
+            }
+            stats.agent_qi = stats.agent_qi.saturating_add(agent.qi() as u64);
+        }
+        stats.node_qi = self
+            .qi_sources
+            .iter()
+            .filter(|s| s.ore == OreKind::QI)
+            .fold(0u64, |acc, s| acc.saturating_add(s.current as u64));
+        for structure in &self.structures {
+            *stats.structures.entry(structure.kind).or_default() += 1;
+        }
+        stats
+    }
+
+    /// Move a Qi node's level from `from` to `to` in `stats`.
+    fn track_node_qi(&mut self, ore: OreKind, from: Qi, to: Qi) {
+        if ore == OreKind::QI {
+            self.stats.node_qi = self
+                .stats
+                .node_qi
+                .saturating_sub(from as u64)
+                .saturating_add(to as u64);
+        }
+    }
+
+    /// The world's random source. Draw from this (never `thread_rng`) so a seed reproduces a run.
+    pub fn rng(&mut self) -> &mut ChaCha12Rng {
+        &mut self.rng
+    }
+
+    /// A point up to `spread` blocks from `center` along x and z, drawn from the world's rng.
+    pub fn scatter(&mut self, center: Position, spread: u32) -> Position {
+        if spread == 0 {
+            return center;
+        }
+        let spread = spread.min(i32::MAX as u32) as i32;
+        let dx = rand::Rng::gen_range(&mut self.rng, -spread..=spread);
+        let dz = rand::Rng::gen_range(&mut self.rng, -spread..=spread);
+        center.offset(dx, 0, dz)
+    }
+
+    pub fn spawn_agent(&mut self, name: impl Into<String>, qi: Qi, position: Position) -> AgentId {
         self.spawn_agent_with_age(name, qi, position, DEFAULT_MAX_AGENT_AGE)
     }
 
+    /// A name no living or dead agent has, derived from the world seed, the parents and the
+    /// tick, so replays name children identically.
+    pub fn child_name(&self, parent_a: AgentId, parent_b: AgentId) -> String {
+        let seed = names::name_seed([self.seed, self.tick, parent_a, parent_b]);
+        names::unique_name(seed, |name| self.agents.values().any(|a| a.name == name))
+    }
+
     pub fn spawn_agent_with_age(
         &mut self,
         name: impl Into<String>,
@@ -551,7 +2154,7 @@ impl World {
     ) -> AgentId {
         let mut pos = position;
         // Ensure no two agents share the same coordinates; walk +x until a free spot is found.
-        while self.occupied.contains_key(&pos) {
+        while self.occupied.contains_key(&pos) || self.terrain.is_solid(pos) {
             pos = pos.offset(1, 0, 0);
         }
 
@@ -561,10 +2164,10 @@ impl World {
         let agent = Agent {
             id: agent_id,
             name: name.into(),
-            qi,
-            transistors: 0,
+            inventory: Inventory::new().with_item(ItemKind::Qi, qi),
             position: pos,
             alive: true,
+            hp: DEFAULT_AGENT_HP,
             age: 0,
             max_age: max_age.max(1),
             discovered_zones: {
@@ -572,15 +2175,25 @@ impl World {
                 set.insert(pos.zone());
                 set
             },
+            last_reproduced: None,
+            dormant: false,
+            quiet_since: None,
+            starving_ticks: 0,
+            attacked_by: BTreeMap::new(),
+            last_scan: None,
+            inbox: VecDeque::new(),
         };
 
         self.events.push(Event::AgentSpawned {
             agent_id,
             name: agent.name.clone(),
-            qi: agent.qi,
+            qi: agent.qi(),
             position: agent.position,
         });
 
+        self.stats.alive_agents += 1;
+        self.stats.agent_qi = self.stats.agent_qi.saturating_add(agent.qi() as u64);
+        self.granted_qi = self.granted_qi.saturating_add(agent.qi() as u64);
         self.agents.insert(agent_id, agent);
         self.occupied.insert(pos, agent_id);
         agent_id
@@ -602,6 +2215,126 @@ impl World {
         &self.qi_sources
     }
 
+    pub fn structures(&self) -> &[Structure] {
+        &self.structures
+    }
+
+    pub fn terrain(&self) -> &Terrain {
+        &self.terrain
+    }
+
+    pub fn terrain_mut(&mut self) -> &mut Terrain {
+        &mut self.terrain
+    }
+
+    pub fn zone_label(&self, zone: Zone) -> Option<&ZoneLabel> {
+        self.zone_labels.get(zone)
+    }
+
+    pub fn set_zone_labels(&mut self, labels: ZoneStore) {
+        self.zone_labels = labels;
+    }
+
+    /// Every zone claim, lapsed ones included.
+    pub fn territories(&self) -> &TerritoryStore {
+        &self.territories
+    }
+
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+
+    /// The claim on `zone`, if its claimant is alive.
+    pub fn zone_claim(&self, zone: Zone) -> Option<&ZoneClaim> {
+        self.territories
+            .get(zone)
+            .filter(|claim| self.agents.get(&claim.owner).is_some_and(|a| a.alive))
+    }
+
+    /// Agents `agent_id` could reproduce with on the next tick: alive, in the same zone and
+    /// past their cooldown, in id order.
+    pub fn eligible_partners(&self, agent_id: AgentId) -> Vec<AgentId> {
+        let next_tick = self.tick + 1;
+        let Some(agent) = self.agents.get(&agent_id) else {
+            return Vec::new();
+        };
+        let zone = agent.position.zone();
+        let mut partners: Vec<AgentId> = self
+            .agents
+            .values()
+            .filter(|other| {
+                other.id != agent_id
+                    && other.alive
+                    && other.position.zone() == zone
+                    && other.can_reproduce_at(next_tick)
+            })
+            .map(|other| other.id)
+            .collect();
+        partners.sort_unstable();
+        partners
+    }
+
+    /// Replace `partner: 0` in reproduce requests with an eligible partner. Agents asking for
+    /// anyone prefer a partner who asked for them by id, then another agent asking for anyone
+    /// (the two are paired), then the lowest eligible id.
+    fn resolve_partners(&self, requests: &mut [ActionRequest]) -> Vec<Event> {
+        let wants: HashMap<AgentId, AgentId> = requests
+            .iter()
+            .filter_map(|req| match req.action {
+                Action::Reproduce { partner } => Some((req.agent_id, partner)),
+                _ => None,
+            })
+            .collect();
+        let mut open: Vec<AgentId> = wants
+            .iter()
+            .filter(|(_, partner)| **partner == 0)
+            .map(|(id, _)| *id)
+            .collect();
+        open.sort_unstable();
+
+        let mut resolved: HashMap<AgentId, AgentId> = HashMap::new();
+        for agent_id in open.iter().copied() {
+            if resolved.contains_key(&agent_id) {
+                continue;
+            }
+            let candidates = self.eligible_partners(agent_id);
+            let unmatched_open =
+                |id: &AgentId| wants.get(id) == Some(&0) && !resolved.contains_key(id);
+            let choice = candidates
+                .iter()
+                .find(|id| wants.get(*id) == Some(&agent_id))
+                .or_else(|| candidates.iter().find(|id| unmatched_open(id)))
+                .or_else(|| candidates.first())
+                .copied();
+            if let Some(partner) = choice {
+                let pair_back = unmatched_open(&partner);
+                resolved.insert(agent_id, partner);
+                if pair_back {
+                    resolved.insert(partner, agent_id);
+                }
+            }
+        }
+
+        let mut events = Vec::new();
+        for req in requests.iter_mut() {
+            if let Action::Reproduce { partner: 0 } = req.action
+                && let Some(partner) = resolved.get(&req.agent_id).copied()
+            {
+                req.action = Action::Reproduce { partner };
+                events.push(Event::PartnerResolved {
+                    agent_id: req.agent_id,
+                    partner,
+                });
+            }
+        }
+        events
+    }
+
+    /// Where an agent is still walking to, if it has an unfinished `MoveTo`.
+    pub fn move_target(&self, agent_id: AgentId) -> Option<Position> {
+        self.pending_paths.get(&agent_id).map(|path| path.target)
+    }
+
     pub fn snapshot(&self) -> WorldSnapshot {
         let mut agents: Vec<AgentSnapshot> = self
             .agents
@@ -609,10 +2342,10 @@ impl World {
             .map(|a| AgentSnapshot {
                 id: a.id,
                 name: a.name.clone(),
-                qi: a.qi,
-                transistors: a.transistors,
+                inventory: a.inventory.clone(),
                 position: a.position,
                 alive: a.alive,
+                hp: a.hp,
                 age: a.age,
                 max_age: a.max_age,
             })
@@ -639,6 +2372,7 @@ impl World {
                 kind: s.kind,
                 position: s.position,
                 owner: s.owner,
+                durability: s.durability,
             })
             .collect();
 
@@ -646,37 +2380,140 @@ impl World {
         ore_nodes.sort_by_key(|n| n.id);
         structures.sort_by_key(|s| s.id);
 
-        WorldSnapshot {
+        let mut snapshot = WorldSnapshot {
             tick: self.tick,
             agents,
             ore_nodes,
             structures,
-        }
+            terrain: self.terrain.snapshot(),
+            zones: self.zone_labels.zones.clone(),
+            meta: SnapshotMeta::default(),
+        };
+        snapshot.meta = SnapshotMeta::for_tick(
+            self.tick,
+            snapshot.content_hash(),
+            Some(self.seed),
+            RuleSummary::current(self.max_qi_supply, self.thinking_cost),
+        );
+        snapshot
     }
 
     pub fn set_max_qi_supply(&mut self, max: u64) {
         self.max_qi_supply = Some(max);
     }
 
+    pub fn thinking_cost(&self) -> Qi {
+        self.thinking_cost
+    }
+
+    /// Charge agents `cost` Qi for every request planned by a brain call.
+    pub fn set_thinking_cost(&mut self, cost: Qi) {
+        self.thinking_cost = cost;
+    }
+
+    pub fn hibernation(&self) -> Option<Hibernation> {
+        self.hibernation
+    }
+
+    /// Let idle agents fall asleep under `rules`; `None` wakes everyone for good.
+    pub fn set_hibernation(&mut self, rules: Option<Hibernation>) {
+        self.hibernation = rules;
+        if rules.is_none() {
+            for agent in self.agents.values_mut() {
+                agent.dormant = false;
+                agent.quiet_since = None;
+            }
+        }
+    }
+
+    pub fn starvation(&self) -> Option<Starvation> {
+        self.starvation
+    }
+
+    /// Let agents starve under `rules`; `None` (the default) lets them live on no Qi.
+    pub fn set_starvation(&mut self, rules: Option<Starvation>) {
+        self.starvation = rules;
+        if rules.is_none() {
+            for agent in self.agents.values_mut() {
+                agent.starving_ticks = 0;
+            }
+        }
+    }
+
+    /// Whether the agent is asleep; brains skip dormant agents.
+    pub fn is_dormant(&self, agent_id: AgentId) -> bool {
+        self.agents
+            .get(&agent_id)
+            .is_some_and(|a| a.alive && a.dormant)
+    }
+
+    /// Whether the agent is alive and holds enough Qi to pay for a brain call.
+    pub fn can_afford_thinking(&self, agent_id: AgentId) -> bool {
+        self.agents
+            .get(&agent_id)
+            .is_some_and(|a| a.alive && a.qi() >= self.thinking_cost)
+    }
+
+    /// Charge the thinking cost for requests planned by a brain call. Brains check
+    /// `can_afford_thinking` first; an agent that still falls short pays what it has.
+    fn charge_thinking(&mut self, requests: &[ActionRequest]) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.thinking_cost == 0 {
+            return events;
+        }
+        for req in requests.iter().filter(|req| req.thought) {
+            let Some(agent) = self.agents.get_mut(&req.agent_id).filter(|a| a.alive) else {
+                continue;
+            };
+            let amount = self.thinking_cost.min(agent.qi());
+            if amount == 0 || agent.spend_qi(amount).is_err() {
+                continue;
+            }
+            self.stats.spend(ItemKind::Qi, amount);
+            self.recycle_qi(amount);
+            events.push(Event::QiSpent {
+                agent_id: req.agent_id,
+                amount,
+                action: "thinking",
+            });
+        }
+        events
+    }
+
     fn recycle_qi(&mut self, amount: Qi) {
         self.recycled_qi = self.recycled_qi.saturating_add(amount as u64);
+        self.stats.recycled_qi = self.recycled_qi;
     }
 
     fn total_qi_supply(&self) -> u64 {
-        let agents_qi: u64 = self
-            .agents
-            .values()
-            .map(|a| a.qi as u64)
-            .fold(0u64, |acc, v| acc.saturating_add(v));
-        let sources_qi: u64 = self
-            .qi_sources
-            .iter()
-            .filter(|s| s.ore == OreKind::Qi)
-            .map(|s| s.current as u64)
-            .fold(0u64, |acc, v| acc.saturating_add(v));
-        agents_qi
-            .saturating_add(sources_qi)
-            .saturating_add(self.recycled_qi)
+        self.qi_audit().held()
+    }
+
+    /// Count the world's Qi from scratch and set it against the supply cap.
+    pub fn qi_audit(&self) -> QiAudit {
+        QiAudit {
+            agent_qi: self
+                .agents
+                .values()
+                .map(|a| a.qi() as u64)
+                .fold(0u64, |acc, v| acc.saturating_add(v)),
+            node_qi: self
+                .qi_sources
+                .iter()
+                .filter(|s| s.ore == OreKind::QI)
+                .map(|s| s.current as u64)
+                .fold(0u64, |acc, v| acc.saturating_add(v)),
+            structure_qi: self
+                .structures
+                .iter()
+                .map(|s| s.storage.get(ItemKind::Qi) as u64)
+                .fold(0u64, |acc, v| acc.saturating_add(v)),
+            recycled_qi: self.recycled_qi,
+            supply_cap: self.max_qi_supply,
+            granted_qi: self.granted_qi,
+            cold_qi: self.cold_zones.values().sum(),
+            market_qi: self.market.escrowed_qi(),
+        }
     }
 
     pub fn add_qi_source(
@@ -695,80 +2532,584 @@ impl World {
             capacity,
             current: capacity,
             recharge_per_tick,
+            remains_of: None,
         };
+        self.source_index.insert(self.qi_sources.len(), position);
         self.qi_sources.push(source);
+        self.track_node_qi(ore, 0, capacity);
         id
     }
 
-    fn recharge_qi_sources(&mut self) {
-        let mut qi_budget = self
-            .max_qi_supply
-            .map(|max| max.saturating_sub(self.total_qi_supply()))
-            .unwrap_or(u64::MAX);
-        let mut pool = self.recycled_qi;
-
-        for source in &mut self.qi_sources {
-            if source.ore != OreKind::Qi {
-                let new_level = source.current.saturating_add(source.recharge_per_tick);
-                source.current = new_level.min(source.capacity);
-                continue;
-            }
-
-            let headroom = (source.capacity.saturating_sub(source.current)) as u64;
-            if headroom == 0 {
-                continue;
-            }
+    fn qi_source_mut(&mut self, source_id: u64) -> Result<&mut QiSource, ActionError> {
+        self.qi_sources
+            .iter_mut()
+            .find(|s| s.id == source_id)
+            .ok_or(ActionError::OreSourceNotFound(source_id))
+    }
 
-            let allowance = source.recharge_per_tick as u64;
-            // First refill from recycled pool (conserved Qi).
-            let from_pool = pool.min(headroom).min(allowance);
-            if from_pool > 0 {
-                source.current = source
-                    .current
-                    .saturating_add(from_pool as Qi)
-                    .min(source.capacity);
-                pool = pool.saturating_sub(from_pool);
-            }
+    /// Leave what a dead agent carried where it died: a node per ore it held (Qi included),
+    /// harvestable like any other until drained. Blocks and crafted items stay with the body.
+    fn drop_remains(&mut self, agent_id: AgentId) -> Vec<Event> {
+        let Some(agent) = self.agents.get_mut(&agent_id) else {
+            return Vec::new();
+        };
+        let position = agent.position;
+        let mut ores: Vec<(OreKind, Qi)> = agent
+            .inventory
+            .iter()
+            .filter_map(|(item, amount)| Some((item.ore()?, amount)))
+            .filter(|(_, amount)| *amount > 0)
+            .collect();
+        // Inventory order varies between processes; sort so seeded runs number nodes alike.
+        ores.sort_by_key(|(ore, _)| *ore);
+        for (ore, amount) in &ores {
+            let _ = agent.spend_item((*ore).into(), *amount);
+        }
 
-            let remaining_allowance = allowance.saturating_sub(from_pool);
-            let remaining_headroom =
-                headroom.saturating_sub(from_pool).min((source.capacity - source.current) as u64);
-            if remaining_allowance > 0 && remaining_headroom > 0 && qi_budget > 0 {
-                let mint = remaining_allowance
-                    .min(remaining_headroom)
-                    .min(qi_budget);
-                if mint > 0 {
-                    source.current = source
-                        .current
-                        .saturating_add(mint as Qi)
-                        .min(source.capacity);
-                    qi_budget = qi_budget.saturating_sub(mint);
-                }
+        let mut events = Vec::new();
+        for (ore, amount) in ores {
+            self.stats.spend(ore.into(), amount);
+            let source_id = self.add_qi_source(ore, position, amount, 0);
+            if let Some(node) = self.qi_sources.last_mut() {
+                node.remains_of = Some(agent_id);
             }
+            events.push(Event::RemainsDropped {
+                agent_id,
+                source_id,
+                ore,
+                amount,
+                position,
+            });
         }
-
-        self.recycled_qi = pool;
+        events
     }
 
-    fn nearby_qi_sources(&self, position: Position, range: i32) -> Vec<QiSourceSnapshot> {
-        self.qi_sources
+    /// Removing a source shifts the indices after it, so the index is rebuilt.
+    fn remove_qi_source(&mut self, source_id: u64) -> Result<QiSource, ActionError> {
+        let idx = self
+            .qi_sources
             .iter()
-            .filter(|s| s.position.within_range(position, range))
-            .map(|s| QiSourceSnapshot {
-                id: s.id,
-                ore: s.ore,
-                position: s.position,
-                available: s.current,
-                capacity: s.capacity,
-            })
-            .collect()
+            .position(|s| s.id == source_id)
+            .ok_or(ActionError::OreSourceNotFound(source_id))?;
+        let source = self.qi_sources.remove(idx);
+        self.track_node_qi(source.ore, source.current, 0);
+        self.source_index = ZoneIndex::new();
+        for (idx, source) in self.qi_sources.iter().enumerate() {
+            self.source_index.insert(idx, source.position);
+        }
+        Ok(source)
     }
 
-    fn nearby_structures(&self, position: Position, range: i32) -> Vec<StructureSnapshot> {
-        self.structures
-            .iter()
-            .filter(|s| s.position.within_range(position, range))
-            .map(|s| StructureSnapshot {
+    /// Returns the source's ore and where it was.
+    fn move_qi_source(
+        &mut self,
+        source_id: u64,
+        position: Position,
+    ) -> Result<(OreKind, Position), ActionError> {
+        let idx = self
+            .qi_sources
+            .iter()
+            .position(|s| s.id == source_id)
+            .ok_or(ActionError::OreSourceNotFound(source_id))?;
+        let from = std::mem::replace(&mut self.qi_sources[idx].position, position);
+        self.source_index.remove(idx, from);
+        self.source_index.insert(idx, position);
+        Ok((self.qi_sources[idx].ore, from))
+    }
+
+    /// Every `STRUCTURE_DECAY_PERIOD` ticks each structure loses 1 durability, reported at each
+    /// `STRUCTURE_DECAY_NOTICE`; those at 0 collapse and leave the world, their stored Qi going
+    /// to the recycled pool.
+    fn decay_structures(&mut self, tick: u64) -> Vec<Event> {
+        if !tick.is_multiple_of(STRUCTURE_DECAY_PERIOD) || self.structures.is_empty() {
+            return Vec::new();
+        }
+        let mut events = Vec::new();
+        for structure in &mut self.structures {
+            structure.durability = structure.durability.saturating_sub(1);
+            if structure.durability > 0
+                && structure.durability.is_multiple_of(STRUCTURE_DECAY_NOTICE)
+            {
+                events.push(Event::StructureDecayed {
+                    structure_id: structure.id,
+                    durability: structure.durability,
+                });
+            }
+        }
+        let (collapsed, standing): (Vec<Structure>, Vec<Structure>) =
+            self.structures.drain(..).partition(|s| s.durability == 0);
+        self.structures = standing;
+        if collapsed.is_empty() {
+            return events;
+        }
+        for structure in collapsed {
+            self.stats.remove_structure(structure.kind);
+            self.recycle_qi(structure.storage.get(ItemKind::Qi));
+            events.push(Event::StructureCollapsed {
+                structure_id: structure.id,
+                kind: structure.kind,
+                owner: structure.owner,
+                position: structure.position,
+            });
+        }
+        self.reindex();
+        events
+    }
+
+    fn begin_bundle(&self) -> BundleUndo {
+        BundleUndo {
+            rng: self.rng.clone(),
+            next_structure_id: self.next_structure_id,
+            next_qi_source_id: self.next_qi_source_id,
+            next_order_id: self.market.next_order_id(),
+            recycled_qi: self.recycled_qi,
+            granted_qi: self.granted_qi,
+            stats: self.stats.clone(),
+            events_len: self.events.len(),
+            agents: BTreeMap::new(),
+            structures: BTreeMap::new(),
+            qi_sources: BTreeMap::new(),
+            blocks: HashMap::new(),
+            claims: HashMap::new(),
+        }
+    }
+
+    /// Save into `undo` whatever `action` by `agent_id` can change that isn't saved yet.
+    /// Structures and nodes the bundle added are left out: their ids are past the saved
+    /// counters and undoing drops them.
+    fn save_for_bundle(&self, undo: &mut BundleUndo, agent_id: AgentId, action: &Action) {
+        let Some(agent) = self.agents.get(&agent_id) else {
+            return;
+        };
+        let position = agent.position;
+        let mut agents = vec![agent_id];
+        // Builds and harvests may pay a toll to the zone's owner.
+        let owner = self.zone_claim(position.zone()).map(|claim| claim.owner);
+        match action {
+            Action::Move { .. }
+            | Action::Scan
+            | Action::Rest
+            | Action::Craft { .. }
+            | Action::PostOrder { .. }
+            | Action::Idle => {}
+            // Never bundled.
+            Action::MoveTo { .. } | Action::Reproduce { .. } | Action::Trade { .. } => {}
+            Action::BuildStructure { .. } => agents.extend(owner),
+            Action::HarvestOre { .. } | Action::HarvestAt { .. } => {
+                agents.extend(owner);
+                let added = undo.next_qi_source_id;
+                for source in self
+                    .source_index
+                    .within(&self.qi_sources, position, HARVEST_RANGE, |s| s.position)
+                    .filter(|s| s.id < added)
+                {
+                    undo.qi_sources
+                        .entry(source.id)
+                        .or_insert_with(|| source.clone());
+                }
+            }
+            Action::Repair { .. } | Action::DemolishStructure { .. } => {
+                let added = undo.next_structure_id;
+                for structure in self
+                    .structure_index
+                    .within(&self.structures, position, HARVEST_RANGE, |s| s.position)
+                    .filter(|s| s.id < added)
+                {
+                    undo.structures
+                        .entry(structure.id)
+                        .or_insert_with(|| structure.clone());
+                }
+            }
+            Action::Dig { dx, dy, dz } | Action::Place { dx, dy, dz, .. } => {
+                let target = position.offset(*dx, *dy, *dz);
+                undo.blocks
+                    .entry(target)
+                    .or_insert_with(|| self.terrain.block_at(target));
+            }
+            Action::ClaimZone | Action::SetToll { .. } => {
+                let zone = position.zone();
+                undo.claims
+                    .entry(zone)
+                    .or_insert_with(|| self.territories.get(zone).cloned());
+            }
+            Action::Attack { target } => agents.push(*target),
+            Action::GiveQi { to, .. } => agents.push(*to),
+            Action::Say { to, .. } if *to != 0 => agents.push(*to),
+            Action::Say { .. } => agents.extend(
+                self.agents
+                    .values()
+                    .filter(|a| a.alive && a.position.within_range(position, SCAN_RANGE))
+                    .map(|a| a.id),
+            ),
+        }
+        for id in agents {
+            if let Some(agent) = self.agents.get(&id) {
+                undo.agents.entry(id).or_insert_with(|| agent.clone());
+            }
+        }
+    }
+
+    /// Put back everything `undo` saved and drop what the bundle added.
+    fn undo_bundle(&mut self, undo: BundleUndo) {
+        self.rng = undo.rng;
+        self.next_qi_source_id = undo.next_qi_source_id;
+        self.recycled_qi = undo.recycled_qi;
+        self.granted_qi = undo.granted_qi;
+        self.stats = undo.stats;
+        self.events.truncate(undo.events_len);
+        self.market.withdraw_after(undo.next_order_id);
+
+        for (id, agent) in undo.agents {
+            if let Some(current) = self.agents.get(&id)
+                && self.occupied.get(&current.position) == Some(&id)
+            {
+                self.occupied.remove(&current.position);
+            }
+            if agent.alive {
+                self.occupied.insert(agent.position, id);
+            }
+            self.agents.insert(id, agent);
+        }
+        for (position, block) in undo.blocks {
+            self.terrain.set_block(position, block);
+        }
+        for (zone, claim) in undo.claims {
+            self.territories.restore(zone, claim);
+        }
+
+        // Both lists are sorted by id and new entries are appended, so an entry that is gone
+        // goes back at its place and the added ones come off the end.
+        let mut moved = false;
+        while self
+            .structures
+            .last()
+            .is_some_and(|s| s.id >= undo.next_structure_id)
+        {
+            self.structures.pop();
+            moved = true;
+        }
+        self.next_structure_id = undo.next_structure_id;
+        for (id, structure) in undo.structures {
+            match self.structures.binary_search_by_key(&id, |s| s.id) {
+                Ok(idx) => self.structures[idx] = structure,
+                Err(idx) => {
+                    self.structures.insert(idx, structure);
+                    moved = true;
+                }
+            }
+        }
+        while self
+            .qi_sources
+            .last()
+            .is_some_and(|s| s.id >= undo.next_qi_source_id)
+        {
+            self.qi_sources.pop();
+            moved = true;
+        }
+        for (id, source) in undo.qi_sources {
+            match self.qi_sources.binary_search_by_key(&id, |s| s.id) {
+                Ok(idx) => self.qi_sources[idx] = source,
+                Err(idx) => {
+                    self.qi_sources.insert(idx, source);
+                    moved = true;
+                }
+            }
+        }
+        if moved {
+            self.reindex();
+        }
+    }
+
+    /// Rebuild the zone indexes after nodes or structures were removed from the middle.
+    fn reindex(&mut self) {
+        self.source_index = ZoneIndex::new();
+        for (idx, source) in self.qi_sources.iter().enumerate() {
+            self.source_index.insert(idx, source.position);
+        }
+        self.structure_index = ZoneIndex::new();
+        for (idx, structure) in self.structures.iter().enumerate() {
+            self.structure_index.insert(idx, structure.position);
+        }
+    }
+
+    pub fn is_cold(&self, zone: Zone) -> bool {
+        self.cold_zones.contains_key(&zone)
+    }
+
+    /// Evicted zones, sorted.
+    pub fn cold_zones(&self) -> Vec<Zone> {
+        let mut zones: Vec<Zone> = self.cold_zones.keys().copied().collect();
+        zones.sort();
+        zones
+    }
+
+    /// What to page when only zones within `radius` zones of a living agent stay in memory:
+    /// the cold zones in that range to load, and the loaded zones more than `radius + 1`
+    /// away to evict. The extra zone stops an agent pacing along the edge from swapping a
+    /// zone in and out every tick. Both lists are sorted.
+    pub fn paging_plan(&self, radius: i32) -> (Vec<Zone>, Vec<Zone>) {
+        let radius = radius.max(0);
+        let occupied: HashSet<Zone> = self
+            .agents
+            .values()
+            .filter(|agent| agent.alive)
+            .map(|agent| agent.position.zone())
+            .collect();
+        let around = |range: i32| -> HashSet<Zone> {
+            let mut zones = HashSet::new();
+            for zone in &occupied {
+                for x in -range..=range {
+                    for y in -range..=range {
+                        for z in -range..=range {
+                            zones.insert(Zone {
+                                x: zone.x + x,
+                                y: zone.y + y,
+                                z: zone.z + z,
+                            });
+                        }
+                    }
+                }
+            }
+            zones
+        };
+
+        let hot = around(radius);
+        let mut load: Vec<Zone> = self
+            .cold_zones
+            .keys()
+            .filter(|zone| hot.contains(zone))
+            .copied()
+            .collect();
+        load.sort();
+
+        let keep = around(radius + 1);
+        let loaded: HashSet<Zone> = self
+            .structures
+            .iter()
+            .map(|s| s.position.zone())
+            .chain(self.qi_sources.iter().map(|s| s.position.zone()))
+            .chain(self.terrain.chunk_positions().map(|c| c.origin().zone()))
+            .collect();
+        let mut evict: Vec<Zone> = loaded
+            .into_iter()
+            .filter(|zone| !keep.contains(zone))
+            .collect();
+        evict.sort();
+        (load, evict)
+    }
+
+    /// Take `zone`'s ore nodes, structures and terrain out of the world. The world remembers
+    /// the zone as cold, and its Qi still counts toward the supply, until `restore_zone`.
+    /// `None` if the zone is already cold or holds nothing.
+    pub fn evict_zone(&mut self, zone: Zone) -> Option<ColdZone> {
+        if self.is_cold(zone) {
+            return None;
+        }
+        let (structures, kept): (Vec<Structure>, Vec<Structure>) =
+            std::mem::take(&mut self.structures)
+                .into_iter()
+                .partition(|s| s.position.zone() == zone);
+        self.structures = kept;
+        let (qi_sources, kept): (Vec<QiSource>, Vec<QiSource>) =
+            std::mem::take(&mut self.qi_sources)
+                .into_iter()
+                .partition(|s| s.position.zone() == zone);
+        self.qi_sources = kept;
+        let chunks: Vec<ChunkPos> = self
+            .terrain
+            .chunk_positions()
+            .filter(|c| c.origin().zone() == zone)
+            .collect();
+        let terrain = self.terrain.take_chunks(&chunks);
+
+        let cold = ColdZone {
+            zone,
+            structures,
+            qi_sources,
+            terrain,
+        };
+        if cold.structures.is_empty() && cold.qi_sources.is_empty() && cold.terrain.is_empty() {
+            return None;
+        }
+        for structure in &cold.structures {
+            self.stats.remove_structure(structure.kind);
+        }
+        for source in &cold.qi_sources {
+            self.track_node_qi(source.ore, source.current, 0);
+        }
+        self.reindex();
+        self.cold_zones.insert(zone, cold.qi());
+        Some(cold)
+    }
+
+    /// Put an evicted zone back. Fails if the world does not hold it as cold.
+    pub fn restore_zone(&mut self, cold: ColdZone) -> Result<(), String> {
+        if self.cold_zones.remove(&cold.zone).is_none() {
+            return Err(format!(
+                "zone ({}, {}, {}) is not evicted",
+                cold.zone.x, cold.zone.y, cold.zone.z
+            ));
+        }
+        for structure in &cold.structures {
+            self.stats.add_structure(structure.kind);
+        }
+        for source in &cold.qi_sources {
+            self.track_node_qi(source.ore, 0, source.current);
+        }
+        // Back in creation order, so the tick visits them as it did before the eviction.
+        self.structures.extend(cold.structures);
+        self.structures.sort_by_key(|s| s.id);
+        self.qi_sources.extend(cold.qi_sources);
+        self.qi_sources.sort_by_key(|s| s.id);
+        self.terrain.restore_chunks(&cold.terrain);
+        self.reindex();
+        Ok(())
+    }
+
+    fn recharge_qi_sources(&mut self) {
+        let mut qi_budget = self
+            .max_qi_supply
+            .map(|max| max.saturating_sub(self.total_qi_supply()))
+            .unwrap_or(u64::MAX);
+        let mut pool = self.recycled_qi;
+        let mut recharged: u64 = 0;
+
+        for source in &mut self.qi_sources {
+            if source.ore != OreKind::QI {
+                let new_level = source.current.saturating_add(source.recharge_per_tick);
+                source.current = new_level.min(source.capacity);
+                continue;
+            }
+
+            let headroom = (source.capacity.saturating_sub(source.current)) as u64;
+            if headroom == 0 {
+                continue;
+            }
+
+            let allowance = source.recharge_per_tick as u64;
+            // First refill from recycled pool (conserved Qi).
+            let from_pool = pool.min(headroom).min(allowance);
+            if from_pool > 0 {
+                source.current = source
+                    .current
+                    .saturating_add(from_pool as Qi)
+                    .min(source.capacity);
+                pool = pool.saturating_sub(from_pool);
+                recharged += from_pool;
+            }
+
+            let remaining_allowance = allowance.saturating_sub(from_pool);
+            let remaining_headroom = headroom
+                .saturating_sub(from_pool)
+                .min((source.capacity - source.current) as u64);
+            if remaining_allowance > 0 && remaining_headroom > 0 && qi_budget > 0 {
+                let mint = remaining_allowance.min(remaining_headroom).min(qi_budget);
+                if mint > 0 {
+                    source.current = source
+                        .current
+                        .saturating_add(mint as Qi)
+                        .min(source.capacity);
+                    qi_budget = qi_budget.saturating_sub(mint);
+                    recharged += mint;
+                }
+            }
+        }
+
+        self.recycled_qi = pool;
+        self.stats.recycled_qi = pool;
+        self.stats.node_qi = self.stats.node_qi.saturating_add(recharged);
+    }
+
+    /// Take Qi for the world to hand out: recycled Qi first, then new Qi within the supply cap.
+    fn draw_qi(&mut self, amount: Qi) -> Qi {
+        let wanted = amount as u64;
+        let budget = self
+            .max_qi_supply
+            .map(|max| max.saturating_sub(self.total_qi_supply()))
+            .unwrap_or(u64::MAX);
+        let from_pool = self.recycled_qi.min(wanted);
+        self.recycled_qi -= from_pool;
+        self.stats.recycled_qi = self.recycled_qi;
+        let minted = wanted.saturating_sub(from_pool).min(budget);
+        (from_pool + minted) as Qi
+    }
+
+    /// Ore nodes and structures visible from `position` within `range`, exactly as a `Scan`
+    /// action there would report them. Read-only; no tick, Qi or event is involved. With no
+    /// scanning agent there is nobody to threaten, so `threats` is empty.
+    pub fn scan_from(&self, position: Position, range: i32) -> ScanReport {
+        let range = range.max(0);
+        ScanReport {
+            position,
+            range,
+            nearby_qi_sources: self.nearby_qi_sources(position, range),
+            nearby_structures: self.nearby_structures(position, range),
+            threats: Vec::new(),
+        }
+    }
+
+    /// What a `Scan` by `agent_id` would report, threats to it included; `None` for an agent
+    /// that isn't alive.
+    pub fn scan_by(&self, agent_id: AgentId, range: i32) -> Option<ScanReport> {
+        let agent = self.agents.get(&agent_id).filter(|a| a.alive)?;
+        let mut report = self.scan_from(agent.position, range);
+        report.threats = self.threats_to(agent_id, report.range);
+        Some(report)
+    }
+
+    /// Living agents within `range` of `agent_id` that attacked it within
+    /// `HOSTILE_MEMORY_TICKS` or could kill it with one attack, nearest first.
+    pub fn threats_to(&self, agent_id: AgentId, range: i32) -> Vec<ThreatSnapshot> {
+        let Some(agent) = self.agents.get(&agent_id) else {
+            return Vec::new();
+        };
+        let mut threats: Vec<ThreatSnapshot> = self
+            .agents
+            .values()
+            .filter(|other| {
+                other.id != agent_id
+                    && other.alive
+                    && agent.position.within_range(other.position, range)
+            })
+            .filter_map(|other| {
+                let damage = ATTACK_BASE_DAMAGE + other.qi() / ATTACK_QI_PER_DAMAGE;
+                let attacked_at = agent
+                    .attacked_by
+                    .get(&other.id)
+                    .copied()
+                    .filter(|at| self.tick <= at.saturating_add(HOSTILE_MEMORY_TICKS));
+                let lethal = damage >= agent.hp;
+                (attacked_at.is_some() || lethal).then_some(ThreatSnapshot {
+                    agent_id: other.id,
+                    position: other.position,
+                    hp: other.hp,
+                    damage,
+                    attacked_at,
+                    lethal,
+                })
+            })
+            .collect();
+        threats.sort_by_key(|t| (chebyshev(agent.position, t.position), t.agent_id));
+        threats
+    }
+
+    fn nearby_qi_sources(&self, position: Position, range: i32) -> Vec<QiSourceSnapshot> {
+        self.source_index
+            .within(&self.qi_sources, position, range, |s| s.position)
+            .map(|s| QiSourceSnapshot {
+                id: s.id,
+                ore: s.ore,
+                position: s.position,
+                available: s.current,
+                capacity: s.capacity,
+            })
+            .collect()
+    }
+
+    fn nearby_structures(&self, position: Position, range: i32) -> Vec<StructureSnapshot> {
+        self.structure_index
+            .within(&self.structures, position, range, |s| s.position)
+            .map(|s| StructureSnapshot {
                 id: s.id,
                 kind: s.kind,
                 position: s.position,
@@ -787,81 +3128,370 @@ pub struct TickResult {
 #[derive(Debug, Default)]
 pub struct Vm {
     world: World,
+    firmware: Option<Program>,
+    effects: StructureRegistry,
+    /// The effects in `effects` that came from data, so a replay can register them again.
+    effect_specs: Vec<EffectSpec>,
+    recipes: RecipeBook,
+}
+
+/// What a `StructureEffect` may see and change while it runs.
+pub struct EffectContext<'a> {
+    world: &'a mut World,
+    tick: u64,
+}
+
+impl EffectContext<'_> {
+    /// The tick being run.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn world(&self) -> &World {
+        self.world
+    }
+
+    /// Living agents within `range` of `position`, in id order.
+    pub fn agents_near(&self, position: Position, range: i32) -> Vec<AgentId> {
+        let mut near: Vec<AgentId> = self
+            .world
+            .agents
+            .values()
+            .filter(|a| a.alive && a.position.within_range(position, range))
+            .map(|a| a.id)
+            .collect();
+        near.sort_unstable();
+        near
+    }
+
+    /// Take `amount` Qi from a living agent into the recycled pool. Returns whether it could pay.
+    pub fn charge_qi(&mut self, agent_id: AgentId, amount: Qi) -> bool {
+        let paid = match self.world.agents.get_mut(&agent_id) {
+            Some(agent) if agent.alive => agent.spend_qi(amount).is_ok(),
+            _ => false,
+        };
+        if paid {
+            self.world.stats.spend(ItemKind::Qi, amount);
+            self.world.recycle_qi(amount);
+        }
+        paid
+    }
+
+    /// Give a living agent `amount` of `item`. Qi is drawn from the recycled pool, then the
+    /// supply cap, so less may be given. Returns how much was.
+    pub fn grant(&mut self, agent_id: AgentId, item: ItemKind, amount: Qi) -> Qi {
+        if !self.world.agents.get(&agent_id).is_some_and(|a| a.alive) {
+            return 0;
+        }
+        let amount = if item == ItemKind::Qi {
+            self.world.draw_qi(amount)
+        } else {
+            amount
+        };
+        if amount > 0
+            && let Some(agent) = self.world.agents.get_mut(&agent_id)
+        {
+            agent.gain_item(item, amount);
+            self.world.stats.gain(item, amount);
+        }
+        amount
+    }
 }
 
 impl Vm {
     pub fn new() -> Self {
         Self {
             world: World::new(),
+            firmware: None,
+            effects: StructureRegistry::default(),
+            effect_specs: Vec::new(),
+            recipes: RecipeBook::default(),
         }
     }
 
-    pub fn world(&self) -> &World {
-        &self.world
+    /// A VM whose world draws all randomness from `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_world(World::with_seed(seed))
     }
 
-    pub fn world_mut(&mut self) -> &mut World {
-        &mut self.world
+    /// A VM around an existing world, e.g. one restored with `World::from_persistent`.
+    pub fn with_world(world: World) -> Self {
+        Self {
+            world,
+            firmware: None,
+            effects: StructureRegistry::default(),
+            effect_specs: Vec::new(),
+            recipes: RecipeBook::default(),
+        }
     }
 
-    pub fn snapshot(&self) -> WorldSnapshot {
-        self.world.snapshot()
+    /// Add `effect` to every structure of `kind`, after the effects it already has.
+    pub fn register_structure_effect(
+        &mut self,
+        kind: StructureKind,
+        effect: impl StructureEffect + 'static,
+    ) {
+        self.effects.register(kind, effect);
     }
 
-    /// Read-only access to a single agent's state.
-    pub fn agent(&self, agent_id: AgentId) -> Option<&Agent> {
-        self.world.agent(agent_id)
+    /// Register an effect described in data; unlike `register_structure_effect`, it is recorded
+    /// in the replay log.
+    pub fn add_effect_spec(&mut self, spec: EffectSpec) {
+        self.effects.register(spec.kind, spec.clone());
+        self.effect_specs.push(spec);
     }
 
-    /// Registry view of all agents in the VM.
-    pub fn agent_registry(&self) -> impl Iterator<Item = (&AgentId, &Agent)> {
-        self.world.agents()
+    pub fn effect_specs(&self) -> &[EffectSpec] {
+        &self.effect_specs
     }
 
-    /// Set the current tick counter (used when resuming from persisted state).
-    pub fn set_tick(&mut self, tick: u64) {
-        self.world.tick = tick;
+    pub fn structure_effects(&self) -> &StructureRegistry {
+        &self.effects
     }
 
-    pub fn set_max_qi_supply(&mut self, max: u64) {
-        self.world.set_max_qi_supply(max);
+    pub fn structure_effects_mut(&mut self) -> &mut StructureRegistry {
+        &mut self.effects
     }
 
-    pub fn spawn_agent(&mut self, name: impl Into<String>, qi: Qi, position: Position) -> AgentId {
-        self.world.spawn_agent(name, qi, position)
+    /// Program flashed onto every programmable structure built from now on.
+    pub fn set_firmware(&mut self, program: Option<Program>) {
+        self.firmware = program;
     }
 
-    pub fn spawn_agent_with_age(
-        &mut self,
-        name: impl Into<String>,
-        qi: Qi,
-        position: Position,
-        max_age: u64,
-    ) -> AgentId {
-        self.world.spawn_agent_with_age(name, qi, position, max_age)
+    pub fn firmware(&self) -> Option<&Program> {
+        self.firmware.as_ref()
     }
 
-    pub fn kill_agent(
-        &mut self,
-        agent_id: AgentId,
-        reason: DeathReason,
-    ) -> Result<(), ActionError> {
-        let agent = self
-            .world
-            .agents
-            .get_mut(&agent_id)
-            .ok_or(ActionError::AgentNotFound(agent_id))?;
-
-        if !agent.alive {
-            return Ok(());
-        }
+    /// Recipes `Craft` can use and the build costs they replace.
+    pub fn set_recipes(&mut self, recipes: RecipeBook) {
+        self.recipes = recipes;
+    }
 
-        agent.alive = false;
-        self.world.occupied.remove(&agent.position);
-        self.world
-            .events
-            .push(Event::AgentDied { agent_id, reason });
-        Ok(())
+    pub fn recipes(&self) -> &RecipeBook {
+        &self.recipes
+    }
+
+    /// Attach (or replace) the program of an existing programmable structure.
+    pub fn attach_program(&mut self, structure_id: u64, program: Program) -> Result<(), String> {
+        let structure = self
+            .world
+            .structures
+            .iter_mut()
+            .find(|s| s.id == structure_id)
+            .ok_or_else(|| format!("structure {} not found", structure_id))?;
+        if structure.kind != StructureKind::Programmable {
+            return Err(format!(
+                "structure {} is {}, only programmable structures run programs",
+                structure_id, structure.kind
+            ));
+        }
+        structure.program = Some(program);
+        Ok(())
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Record an event from outside the simulation (an operator's intervention). It is emitted
+    /// with the next tick, right after `TickStarted`, so it reaches the journal and streams.
+    pub fn queue_event(&mut self, event: Event) {
+        self.world.queued_events.push(event);
+    }
+
+    /// Apply an operator's `action` to `agent_id` right away. It is journaled with the next
+    /// tick as `AdminIntervened`, and a kill also as `AgentDied` with `DeathReason::Admin`.
+    pub fn admin(&mut self, agent_id: AgentId, action: AdminAction) -> Result<(), ActionError> {
+        let world = &mut self.world;
+        let agent = world
+            .agents
+            .get_mut(&agent_id)
+            .ok_or(ActionError::AgentNotFound(agent_id))?;
+        let alive = agent.alive;
+        if !alive && action != AdminAction::Revive {
+            return Err(ActionError::AgentDead(agent_id));
+        }
+        let mut died = None;
+        match &action {
+            AdminAction::Teleport { to } => {
+                if let Some(other) = world.occupied.get(to)
+                    && *other != agent_id
+                {
+                    return Err(ActionError::PositionOccupied {
+                        agent_id,
+                        target: *to,
+                        occupied_by: *other,
+                    });
+                }
+                if world.terrain.is_solid(*to) {
+                    return Err(ActionError::BlockedByTerrain {
+                        agent_id,
+                        target: *to,
+                    });
+                }
+                world.occupied.remove(&agent.position);
+                agent.position = *to;
+                agent.discovered_zones.insert(to.zone());
+                world.occupied.insert(*to, agent_id);
+                world.pending_paths.remove(&agent_id);
+            }
+            AdminAction::GrantQi { amount } => {
+                agent.gain_item(ItemKind::Qi, *amount);
+                world.stats.gain(ItemKind::Qi, *amount);
+                world.granted_qi = world.granted_qi.saturating_add(*amount as u64);
+            }
+            AdminAction::Kill => {
+                agent.alive = false;
+                world.occupied.remove(&agent.position);
+                world.pending_paths.remove(&agent_id);
+                world.stats.agent_died();
+                died = Some(Event::AgentDied {
+                    agent_id,
+                    reason: DeathReason::Admin,
+                });
+            }
+            AdminAction::Revive => {
+                if alive {
+                    return Err(ActionError::AgentAlive(agent_id));
+                }
+                if let Some(other) = world.occupied.get(&agent.position) {
+                    return Err(ActionError::PositionOccupied {
+                        agent_id,
+                        target: agent.position,
+                        occupied_by: *other,
+                    });
+                }
+                agent.alive = true;
+                agent.hp = DEFAULT_AGENT_HP;
+                if agent.age >= agent.max_age {
+                    agent.age = 0;
+                }
+                world.occupied.insert(agent.position, agent_id);
+                world.stats.agent_revived();
+            }
+        }
+        self.queue_event(Event::AdminIntervened { agent_id, action });
+        if let Some(event) = died {
+            self.queue_event(event);
+            for event in self.world.drop_remains(agent_id) {
+                self.queue_event(event);
+            }
+        }
+        Ok(())
+    }
+
+    /// Live world aggregates; see `World::stats`.
+    pub fn stats(&self) -> &WorldStats {
+        self.world.stats()
+    }
+
+    /// See `World::qi_audit`.
+    pub fn qi_audit(&self) -> QiAudit {
+        self.world.qi_audit()
+    }
+
+    pub fn snapshot(&self) -> WorldSnapshot {
+        self.world.snapshot()
+    }
+
+    /// Read-only access to a single agent's state.
+    pub fn agent(&self, agent_id: AgentId) -> Option<&Agent> {
+        self.world.agent(agent_id)
+    }
+
+    /// Registry view of all agents in the VM.
+    pub fn agent_registry(&self) -> impl Iterator<Item = (&AgentId, &Agent)> {
+        self.world.agents()
+    }
+
+    /// Set the current tick counter (used when resuming from persisted state).
+    pub fn set_tick(&mut self, tick: u64) {
+        self.world.tick = tick;
+        self.world.stats.tick = tick;
+    }
+
+    pub fn set_max_qi_supply(&mut self, max: u64) {
+        self.world.set_max_qi_supply(max);
+    }
+
+    pub fn set_thinking_cost(&mut self, cost: Qi) {
+        self.world.set_thinking_cost(cost);
+    }
+
+    pub fn set_hibernation(&mut self, rules: Option<Hibernation>) {
+        self.world.set_hibernation(rules);
+    }
+
+    pub fn set_starvation(&mut self, rules: Option<Starvation>) {
+        self.world.set_starvation(rules);
+    }
+
+    pub fn spawn_agent(&mut self, name: impl Into<String>, qi: Qi, position: Position) -> AgentId {
+        self.world.spawn_agent(name, qi, position)
+    }
+
+    pub fn spawn_agent_with_age(
+        &mut self,
+        name: impl Into<String>,
+        qi: Qi,
+        position: Position,
+        max_age: u64,
+    ) -> AgentId {
+        self.world.spawn_agent_with_age(name, qi, position, max_age)
+    }
+
+    /// Carry an agent's age and items over from an earlier run, e.g. from its registry profile
+    /// when a fresh world spawns it.
+    pub fn restore_agent(
+        &mut self,
+        agent_id: AgentId,
+        age: u64,
+        items: &[(ItemKind, Qi)],
+    ) -> Result<(), ActionError> {
+        let agent = self
+            .world
+            .agents
+            .get_mut(&agent_id)
+            .ok_or(ActionError::AgentNotFound(agent_id))?;
+        agent.age = age;
+        for &(item, amount) in items {
+            agent.gain_item(item, amount);
+            self.world.stats.gain(item, amount);
+            if item == ItemKind::Qi {
+                self.world.granted_qi = self.world.granted_qi.saturating_add(amount as u64);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn kill_agent(
+        &mut self,
+        agent_id: AgentId,
+        reason: DeathReason,
+    ) -> Result<(), ActionError> {
+        let agent = self
+            .world
+            .agents
+            .get_mut(&agent_id)
+            .ok_or(ActionError::AgentNotFound(agent_id))?;
+
+        if !agent.alive {
+            return Ok(());
+        }
+
+        agent.alive = false;
+        self.world.occupied.remove(&agent.position);
+        self.world.stats.agent_died();
+        self.world
+            .events
+            .push(Event::AgentDied { agent_id, reason });
+        let remains = self.world.drop_remains(agent_id);
+        self.world.events.extend(remains);
+        Ok(())
     }
 
     pub fn seed_qi_source(
@@ -870,7 +3500,7 @@ impl Vm {
         capacity: Qi,
         recharge_per_tick: Qi,
     ) -> u64 {
-        self.seed_ore_source(OreKind::Qi, position, capacity, recharge_per_tick)
+        self.seed_ore_source(OreKind::QI, position, capacity, recharge_per_tick)
     }
 
     pub fn seed_ore_source(
@@ -884,13 +3514,68 @@ impl Vm {
             .add_qi_source(ore, position, capacity, recharge_per_tick)
     }
 
+    /// Set an ore node's current level, clamped to its capacity. Returns the level applied.
+    pub fn set_ore_source_level(&mut self, source_id: u64, level: Qi) -> Result<Qi, ActionError> {
+        let source = self.world.qi_source_mut(source_id)?;
+        let from = source.current;
+        let to = level.min(source.capacity);
+        source.current = to;
+        let ore = source.ore;
+        self.world.track_node_qi(ore, from, to);
+        self.queue_event(Event::OreNodeLevelSet {
+            ore,
+            source_id,
+            from,
+            to,
+        });
+        Ok(to)
+    }
+
+    /// Take an ore node out of the world; whatever it still held goes with it.
+    pub fn remove_ore_source(&mut self, source_id: u64) -> Result<QiSource, ActionError> {
+        let source = self.world.remove_qi_source(source_id)?;
+        self.queue_event(Event::OreNodeRemoved {
+            ore: source.ore,
+            source_id,
+            position: source.position,
+            remaining: source.current,
+        });
+        Ok(source)
+    }
+
+    /// Move an ore node to `position`, keeping its level, capacity and recharge.
+    pub fn move_ore_source(
+        &mut self,
+        source_id: u64,
+        position: Position,
+    ) -> Result<(), ActionError> {
+        let (ore, from) = self.world.move_qi_source(source_id, position)?;
+        self.queue_event(Event::OreNodeMoved {
+            ore,
+            source_id,
+            from,
+            to: position,
+        });
+        Ok(())
+    }
+
     pub fn step(&mut self, actions: &[ActionRequest]) -> TickResult {
         let tick = self.world.tick + 1;
         let mut tick_events = vec![Event::TickStarted { tick }];
+        tick_events.append(&mut self.world.queued_events);
         let mut rejections = Vec::new();
 
         // World progression before actions (e.g., recharge Qi sources).
         self.world.recharge_qi_sources();
+        tick_events.append(&mut self.run_structure_effects(tick));
+        tick_events.append(&mut self.world.decay_structures(tick));
+
+        tick_events.append(&mut self.world.charge_thinking(actions));
+
+        // Match "any partner" requests first so consents below see concrete ids.
+        let mut actions = actions.to_vec();
+        tick_events.append(&mut self.world.resolve_partners(&mut actions));
+        let actions = actions.as_slice();
 
         // Precompute mutual reproduction consents for this tick.
         let mut intents: HashMap<AgentId, AgentId> = HashMap::new();
@@ -901,13 +3586,54 @@ impl Vm {
         }
         let mut mutual_pairs: HashSet<(AgentId, AgentId)> = HashSet::new();
         for (a, b) in intents.iter() {
-            if let Some(back) = intents.get(b) {
-                if *back == *a {
-                    let pair = if a < b { (*a, *b) } else { (*b, *a) };
-                    mutual_pairs.insert(pair);
-                }
+            if let Some(back) = intents.get(b)
+                && *back == *a
+            {
+                mutual_pairs.insert(ordered_pair(*a, *b));
+            }
+        }
+        // Any other action cancels an unfinished MoveTo; agents that sent nothing keep walking.
+        for req in actions {
+            if !matches!(req.action, Action::MoveTo { .. }) {
+                self.world.pending_paths.remove(&req.agent_id);
             }
         }
+        let alive = |id: &AgentId| self.world.agents.get(id).is_some_and(|a| a.alive);
+        self.world.pending_paths.retain(|id, _| alive(id));
+        let mut continuing: Vec<ActionRequest> = self
+            .world
+            .pending_paths
+            .iter()
+            .filter(|(id, _)| !actions.iter().any(|req| req.agent_id == **id))
+            .map(|(id, path)| {
+                ActionRequest::new(
+                    *id,
+                    Action::MoveTo {
+                        target: path.target,
+                    },
+                )
+            })
+            .collect();
+        continuing.sort_by_key(|req| req.agent_id);
+        let actions: Vec<ActionRequest> = actions.iter().cloned().chain(continuing).collect();
+        let actions = actions.as_slice();
+
+        // Highest priority first; the sort is stable, so unstaked requests keep batch order.
+        let mut order: Vec<(ActionRequest, Priority)> = actions
+            .iter()
+            .enumerate()
+            .map(|(idx, req)| {
+                let priority = Priority {
+                    stake: req.stake,
+                    initiative: idx as u32,
+                };
+                (req.clone(), priority)
+            })
+            .collect();
+        order.sort_by_key(|(_, priority)| priority.rank());
+        let mut claims: HashMap<Contested, (AgentId, Priority)> = HashMap::new();
+
+        let mut trades = TradeBook::from_requests(actions);
         let snapshot: HashMap<AgentId, (Position, bool)> = self
             .world
             .agents
@@ -915,18 +3641,51 @@ impl Vm {
             .map(|(id, agent)| (*id, (agent.position, agent.alive)))
             .collect();
 
-        for request in actions.iter().cloned() {
-            match self.apply_action(request.clone(), tick, &mutual_pairs, &snapshot) {
-                Ok(mut events) => tick_events.append(&mut events),
-                Err(error) => rejections.push(ActionRejection { request, error }),
+        // Phase one: validate every request against the pre-tick world (in parallel for large
+        // batches). Phase two: apply the survivors in request order, so results stay stable.
+        let prechecks: Vec<Result<(), ActionError>> = if order.len() >= PARALLEL_VALIDATION_MIN {
+            order
+                .par_iter()
+                .map(|(req, _)| self.precheck(req))
+                .collect()
+        } else {
+            order.iter().map(|(req, _)| self.precheck(req)).collect()
+        };
+
+        for ((request, priority), precheck) in order.into_iter().zip(prechecks) {
+            let result = precheck.and_then(|()| {
+                self.apply_staked(&request, tick, &mutual_pairs, &mut trades, &snapshot)
+            });
+            match result {
+                Ok(mut events) => {
+                    claim_contested(&mut claims, &events, priority);
+                    tick_events.append(&mut events);
+                }
+                Err(error) => {
+                    let error = outranked(error, priority, &claims);
+                    rejections.push(ActionRejection { request, error });
+                }
             }
         }
 
+        tick_events.append(&mut self.settle_market(tick));
+        let mut reactions = self.react_to_structures(tick, &tick_events);
+        tick_events.append(&mut reactions);
+        tick_events.append(&mut self.update_hibernation(tick, actions));
+        tick_events.append(&mut self.enforce_starvation());
         tick_events.append(&mut self.enforce_age_limits());
         tick_events.push(Event::TickCompleted { tick });
 
         self.world.tick = tick;
+        self.world.stats.tick = tick;
         self.world.events.extend(tick_events.clone());
+        #[cfg(test)]
+        debug_assert_eq!(
+            self.world.stats,
+            self.world.recount_stats(),
+            "running stats drifted from a recount at tick {}",
+            tick
+        );
 
         TickResult {
             tick,
@@ -935,19 +3694,192 @@ impl Vm {
         }
     }
 
+    /// Rejections that no earlier action in the same tick can change: a dead agent stays dead
+    /// and a malformed request stays malformed. Unknown agents are left to `apply_action`,
+    /// since a child born earlier in the tick may own the id.
+    fn precheck(&self, request: &ActionRequest) -> Result<(), ActionError> {
+        let Some(agent) = self.world.agents.get(&request.agent_id) else {
+            return Ok(());
+        };
+        if !agent.alive {
+            return Err(ActionError::AgentDead(request.agent_id));
+        }
+        if request.is_bundle() {
+            return check_bundle_shape(request);
+        }
+        check_action_shape(agent.id, &request.action)
+    }
+
+    /// Reserve the request's stake, apply it, then spend the stake on success or refund it on
+    /// rejection.
+    fn apply_staked(
+        &mut self,
+        request: &ActionRequest,
+        tick: u64,
+        mutual_pairs: &HashSet<(AgentId, AgentId)>,
+        trades: &mut TradeBook,
+        snapshot: &HashMap<AgentId, (Position, bool)>,
+    ) -> Result<Vec<Event>, ActionError> {
+        let stake = request.stake;
+        if stake > 0
+            && let Some(agent) = self.world.agents.get_mut(&request.agent_id)
+        {
+            agent.spend_qi(stake)?;
+            self.world.stats.spend(ItemKind::Qi, stake);
+        }
+
+        let result = if request.is_bundle() {
+            self.apply_bundle(request, tick, mutual_pairs, trades, snapshot)
+        } else {
+            self.apply_action(request.clone(), tick, mutual_pairs, trades, snapshot)
+        };
+        if stake == 0 {
+            return result;
+        }
+        match result {
+            Ok(mut events) => {
+                self.world.recycle_qi(stake);
+                events.push(Event::QiSpent {
+                    agent_id: request.agent_id,
+                    amount: stake,
+                    action: "stake",
+                });
+                Ok(events)
+            }
+            Err(error) => {
+                if let Some(agent) = self.world.agents.get_mut(&request.agent_id) {
+                    agent.gain_item(ItemKind::Qi, stake);
+                    self.world.stats.gain(ItemKind::Qi, stake);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Apply a bundled request's actions in order. If any fails, the world is restored to how
+    /// it was before the bundle and the bundle is rejected with that failure.
+    fn apply_bundle(
+        &mut self,
+        request: &ActionRequest,
+        tick: u64,
+        mutual_pairs: &HashSet<(AgentId, AgentId)>,
+        trades: &mut TradeBook,
+        snapshot: &HashMap<AgentId, (Position, bool)>,
+    ) -> Result<Vec<Event>, ActionError> {
+        let agent_id = request.agent_id;
+        let agent = self
+            .world
+            .agents
+            .get(&agent_id)
+            .ok_or(ActionError::AgentNotFound(agent_id))?;
+        let required = request
+            .actions()
+            .map(|action| action.qi_cost())
+            .fold(0 as Qi, Qi::saturating_add);
+        if agent.qi() < required {
+            return Err(ActionError::InsufficientQi {
+                agent_id,
+                required,
+                available: agent.qi(),
+            });
+        }
+
+        let mut undo = self.world.begin_bundle();
+        let mut events = Vec::new();
+        for action in request.actions() {
+            self.world.save_for_bundle(&mut undo, agent_id, &action);
+            let step = ActionRequest::new(agent_id, action);
+            match self.apply_action(step, tick, mutual_pairs, trades, snapshot) {
+                Ok(mut applied) => events.append(&mut applied),
+                Err(error) => {
+                    self.world.undo_bundle(undo);
+                    return Err(error);
+                }
+            }
+        }
+        // Each applied action ages the agent; a bundle is still a single turn.
+        if let Some(agent) = self.world.agents.get_mut(&agent_id) {
+            agent.age = agent.age.saturating_sub(request.then.len() as u64);
+        }
+        Ok(events)
+    }
+
     fn apply_action(
         &mut self,
         request: ActionRequest,
-        _tick: u64,
+        tick: u64,
         mutual_pairs: &HashSet<(AgentId, AgentId)>,
+        trades: &mut TradeBook,
         snapshot: &HashMap<AgentId, (Position, bool)>,
     ) -> Result<Vec<Event>, ActionError> {
         let mut events = Vec::new();
-        let mut pending_child: Option<(String, Position, AgentId, AgentId)> = None;
+        let mut pending_child: Option<(Position, AgentId, AgentId)> = None;
         let mut pending_scan: Option<(AgentId, Position, Qi)> = None;
         let mut pending_harvest: Option<(AgentId, OreKind, u64)> = None;
+        let mut pending_trade: Option<(AgentId, TradeTerms)> = None;
+        let mut pending_attack: Option<(AgentId, AgentId, u32)> = None;
+        let mut pending_message: Option<(AgentId, Vec<AgentId>, String)> = None;
+        let mut pending_toll: Option<(AgentId, AgentId, Zone, Qi)> = None;
+        let mut pending_demolish: Option<(AgentId, Structure)> = None;
+        let mut pending_gift: Option<(AgentId, AgentId, Qi)> = None;
         let mut reclaimed_qi: Qi = 0;
 
+        // The partner's holdings are read up front; the acting agent is borrowed mutably below.
+        let partner_holdings = match request.action {
+            Action::Trade {
+                partner, want_ore, ..
+            } => self
+                .world
+                .agents
+                .get(&partner)
+                .map(|p| (p.id, p.item(want_ore.into()))),
+            _ => None,
+        };
+        let partner_ready = match request.action {
+            Action::Reproduce { partner } => self
+                .world
+                .agents
+                .get(&partner)
+                .is_some_and(|p| p.can_reproduce_at(tick) || p.last_reproduced == Some(tick)),
+            _ => false,
+        };
+        // A harvest by position becomes a harvest of the node in that cell, so both share the
+        // checks below; with no node there it stays as it is and is rejected.
+        let action = match request.action.clone() {
+            Action::HarvestAt { ore, dx, dy, dz } => self
+                .world
+                .agents
+                .get(&request.agent_id)
+                .and_then(|agent| {
+                    let target = agent.position.offset(dx, dy, dz);
+                    self.world
+                        .source_index
+                        .within(&self.world.qi_sources, target, 0, |s| s.position)
+                        .find(|s| s.ore == ore)
+                })
+                .map_or(request.action.clone(), |src| Action::HarvestOre {
+                    ore,
+                    source_id: src.id,
+                }),
+            action => action,
+        };
+        // The live claim on the acting agent's zone, for claims, tolls and builds.
+        let zone_claim = self
+            .world
+            .agents
+            .get(&request.agent_id)
+            .and_then(|agent| self.world.zone_claim(agent.position.zone()))
+            .cloned();
+        let target_position = match request.action {
+            Action::Attack { target } => self
+                .world
+                .agents
+                .get(&target)
+                .filter(|t| t.alive)
+                .map(|t| t.position),
+            _ => None,
+        };
+
         {
             let agent = self
                 .world
@@ -958,28 +3890,26 @@ impl Vm {
             if !agent.alive {
                 return Err(ActionError::AgentDead(request.agent_id));
             }
+            check_action_shape(agent.id, &request.action)?;
 
-            match request.action {
+            match action {
                 Action::Move { dx, dy, dz } => {
-                    let max_delta = dx.abs().max(dy.abs()).max(dz.abs());
-                    if max_delta > MAX_MOVE_RADIUS {
-                        return Err(ActionError::MoveOutOfRange {
+                    let from = agent.position;
+                    let to = agent.position.offset(dx, dy, dz);
+                    if self.world.terrain.is_solid(to) {
+                        return Err(ActionError::BlockedByTerrain {
                             agent_id: agent.id,
-                            dx,
-                            dy,
-                            dz,
+                            target: to,
                         });
                     }
-                    let from = agent.position;
-                    let to = agent.position.offset(dx, dy, dz);
-                    if let Some(other) = self.world.occupied.get(&to) {
-                        if *other != agent.id {
-                            return Err(ActionError::PositionOccupied {
-                                agent_id: agent.id,
-                                target: to,
-                                occupied_by: *other,
-                            });
-                        }
+                    if let Some(other) = self.world.occupied.get(&to)
+                        && *other != agent.id
+                    {
+                        return Err(ActionError::PositionOccupied {
+                            agent_id: agent.id,
+                            target: to,
+                            occupied_by: *other,
+                        });
                     }
 
                     let from_zone = from.zone();
@@ -989,6 +3919,7 @@ impl Vm {
                     }
 
                     agent.spend_qi(1)?;
+                    self.world.stats.spend(ItemKind::Qi, 1);
                     events.push(Event::QiSpent {
                         agent_id: agent.id,
                         amount: 1,
@@ -1005,39 +3936,116 @@ impl Vm {
                         to,
                     });
                 }
-                Action::Scan => {
-                    events.push(Event::ActionObserved {
-                        agent_id: agent.id,
-                        action: "scan",
-                    });
-                    pending_scan = Some((agent.id, agent.position, agent.qi));
-                }
-                Action::Reproduce { partner } => {
+                Action::MoveTo { target } => {
                     let agent_id = agent.id;
-                    let agent_zone = agent.position.zone();
-                    let child_position = agent.position;
-
-                    let (partner_pos, partner_alive) = snapshot
-                        .get(&partner)
-                        .copied()
-                        .ok_or(ActionError::PartnerNotFound { agent_id, partner })?;
-                    if !partner_alive {
-                        return Err(ActionError::PartnerNotFound { agent_id, partner });
-                    }
-                    if agent_zone != partner_pos.zone() {
-                        return Err(ActionError::PartnerOutOfZone { agent_id, partner });
+                    let from = agent.position;
+                    if from == target {
+                        self.world.pending_paths.remove(&agent_id);
+                        events.push(Event::PathCompleted { agent_id, target });
+                        return Ok(events);
                     }
 
-                    let pair = if agent_id < partner {
-                        (agent_id, partner)
-                    } else {
-                        (partner, agent_id)
+                    let terrain = &self.world.terrain;
+                    let occupied = &self.world.occupied;
+                    let passable = |pos: Position| {
+                        !terrain.is_solid(pos)
+                            && occupied.get(&pos).is_none_or(|id| *id == agent_id)
+                    };
+                    // Reuse the stored path while its next leg is still clear; otherwise re-plan.
+                    let reusable = self.world.pending_paths.get(&agent_id).filter(|path| {
+                        path.target == target
+                            && path
+                                .steps
+                                .iter()
+                                .take(MAX_MOVE_RADIUS as usize)
+                                .all(|pos| passable(*pos))
+                    });
+                    let mut steps = match reusable {
+                        Some(path) => path.steps.clone(),
+                        None => match find_path(from, target, passable) {
+                            Some(steps) => steps,
+                            None => {
+                                self.world.pending_paths.remove(&agent_id);
+                                return Err(ActionError::NoPath { agent_id, target });
+                            }
+                        },
                     };
-                    if !mutual_pairs.contains(&pair) {
+
+                    agent.spend_qi(1)?;
+                    self.world.stats.spend(ItemKind::Qi, 1);
+                    events.push(Event::QiSpent {
+                        agent_id,
+                        amount: 1,
+                        action: request.action.label(),
+                    });
+                    reclaimed_qi = reclaimed_qi.saturating_add(1);
+
+                    let mut to = from;
+                    for _ in 0..MAX_MOVE_RADIUS {
+                        let Some(next) = steps.pop_front() else {
+                            break;
+                        };
+                        agent.discovered_zones.insert(next.zone());
+                        to = next;
+                    }
+                    self.world.occupied.remove(&from);
+                    agent.position = to;
+                    self.world.occupied.insert(to, agent_id);
+                    events.push(Event::AgentMoved { agent_id, from, to });
+
+                    if steps.is_empty() {
+                        self.world.pending_paths.remove(&agent_id);
+                        events.push(Event::PathCompleted { agent_id, target });
+                    } else {
+                        events.push(Event::PathProgress {
+                            agent_id,
+                            target,
+                            remaining: steps.len() as u32,
+                        });
+                        self.world
+                            .pending_paths
+                            .insert(agent_id, PendingPath { target, steps });
+                    }
+                }
+                Action::Scan => {
+                    events.push(Event::ActionObserved {
+                        agent_id: agent.id,
+                        action: "scan",
+                    });
+                    pending_scan = Some((agent.id, agent.position, agent.qi()));
+                }
+                Action::Reproduce { partner } => {
+                    let agent_id = agent.id;
+                    let agent_zone = agent.position.zone();
+                    let child_position = agent.position;
+
+                    let (partner_pos, partner_alive) = snapshot
+                        .get(&partner)
+                        .copied()
+                        .ok_or(ActionError::PartnerNotFound { agent_id, partner })?;
+                    if !partner_alive {
+                        return Err(ActionError::PartnerNotFound { agent_id, partner });
+                    }
+                    if agent_zone != partner_pos.zone() {
+                        return Err(ActionError::PartnerOutOfZone { agent_id, partner });
+                    }
+
+                    if !mutual_pairs.contains(&ordered_pair(agent_id, partner)) {
+                        return Err(ActionError::ReproductionDeclined { agent_id, partner });
+                    }
+                    if !agent.can_reproduce_at(tick) {
+                        return Err(ActionError::ReproductionCooldown {
+                            agent_id,
+                            ready_at: agent.last_reproduced.unwrap_or_default()
+                                + REPRODUCE_COOLDOWN,
+                        });
+                    }
+                    if !partner_ready {
                         return Err(ActionError::ReproductionDeclined { agent_id, partner });
                     }
 
                     agent.spend_qi(1)?;
+                    self.world.stats.spend(ItemKind::Qi, 1);
                     events.push(Event::QiSpent {
                         agent_id: agent.id,
                         amount: 1,
@@ -1045,10 +4053,20 @@ impl Vm {
                     });
                     reclaimed_qi = reclaimed_qi.saturating_add(1);
 
-                    let child_name = format!("Child-{}-{}", agent_id, partner);
-                    pending_child = Some((child_name, child_position, agent_id, partner));
+                    agent.last_reproduced = Some(tick);
+                    pending_child = Some((child_position, agent_id, partner));
                 }
                 Action::BuildStructure { kind } => {
+                    let toll = zone_claim.as_ref().filter(|claim| claim.owner != agent.id);
+                    if let Some(claim) = toll
+                        && claim.build_toll == 0
+                    {
+                        return Err(ActionError::ZoneClaimed {
+                            agent_id: agent.id,
+                            zone: claim.zone,
+                            owner: claim.owner,
+                        });
+                    }
                     if self
                         .world
                         .structures
@@ -1061,14 +4079,27 @@ impl Vm {
                         });
                     }
 
-                    // Programmable structures require transistor ore in addition to Qi energy.
-                    if kind == StructureKind::Programmable {
-                        agent.spend_ore(OreKind::Transistor, 1)?;
+                    let agent_id = agent.id;
+                    let mut bill = self.recipes.build_cost(kind);
+                    let cost: Qi = bill
+                        .iter()
+                        .filter(|(item, _)| *item == ItemKind::Qi)
+                        .map(|(_, amount)| *amount)
+                        .sum();
+                    if let Some(claim) = toll {
+                        match bill.iter_mut().find(|(item, _)| *item == ItemKind::Qi) {
+                            Some((_, amount)) => *amount += claim.build_toll,
+                            None => bill.push((ItemKind::Qi, claim.build_toll)),
+                        }
+                        pending_toll = Some((agent_id, claim.owner, claim.zone, claim.build_toll));
                     }
+                    agent
+                        .inventory
+                        .spend_all(&bill)
+                        .map_err(|shortfall| insufficient(agent_id, shortfall))?;
+                    self.world.stats.spend_all(&bill);
 
-                    let cost = Action::BuildStructure { kind }.qi_cost();
                     if cost > 0 {
-                        agent.spend_qi(cost)?;
                         events.push(Event::QiSpent {
                             agent_id: agent.id,
                             amount: cost,
@@ -1085,7 +4116,17 @@ impl Vm {
                         position: agent.position,
                         zone: agent.position.zone(),
                         owner: agent.id,
+                        storage: Inventory::new(),
+                        durability: STRUCTURE_DURABILITY,
+                        program: match kind {
+                            StructureKind::Programmable => self.firmware.clone(),
+                            _ => None,
+                        },
                     };
+                    self.world
+                        .structure_index
+                        .insert(self.world.structures.len(), structure.position);
+                    self.world.stats.add_structure(kind);
                     self.world.structures.push(structure);
                     events.push(Event::StructureBuilt {
                         agent_id: agent.id,
@@ -1096,7 +4137,16 @@ impl Vm {
                 }
                 Action::HarvestOre { ore, source_id } => {
                     let selected = if source_id == 0 {
-                        nearest_ore_source(&self.world.qi_sources, ore, agent.position)
+                        nearest_ore_source(
+                            self.world.source_index.within(
+                                &self.world.qi_sources,
+                                agent.position,
+                                SCAN_RANGE,
+                                |s| s.position,
+                            ),
+                            ore,
+                            agent.position,
+                        )
                     } else {
                         self.world
                             .qi_sources
@@ -1121,7 +4171,7 @@ impl Vm {
                         });
                     }
 
-                    if src.current < HARVEST_PER_ACTION {
+                    if src.current < ore.harvest_rate() {
                         return Err(ActionError::OreSourceDepleted {
                             agent_id: agent.id,
                             ore,
@@ -1130,16 +4180,425 @@ impl Vm {
                         });
                     }
 
-                    agent.spend_qi(1)?;
+                    let zone = src.position.zone();
+                    let toll = self
+                        .world
+                        .territories
+                        .get(zone)
+                        .filter(|claim| {
+                            claim.owner != agent.id
+                                && claim.harvest_toll > 0
+                                && snapshot.get(&claim.owner).is_some_and(|(_, alive)| *alive)
+                        })
+                        .map(|claim| (agent.id, claim.owner, zone, claim.harvest_toll));
+                    agent.spend_qi(1 + toll.map_or(0, |(.., amount)| amount))?;
+                    self.world
+                        .stats
+                        .spend(ItemKind::Qi, 1 + toll.map_or(0, |(.., amount)| amount));
                     events.push(Event::QiSpent {
                         agent_id: agent.id,
                         amount: 1,
                         action: request.action.label(),
                     });
                     reclaimed_qi = reclaimed_qi.saturating_add(1);
+                    pending_toll = toll;
 
                     pending_harvest = Some((agent.id, ore, src.id));
                 }
+                Action::HarvestAt { ore, dx, dy, dz } => {
+                    return Err(ActionError::NoOreNodeAt {
+                        agent_id: agent.id,
+                        ore,
+                        target: agent.position.offset(dx, dy, dz),
+                    });
+                }
+                Action::Trade {
+                    partner,
+                    offer_ore,
+                    offer_amount,
+                    want_ore,
+                    want_amount,
+                } => {
+                    let agent_id = agent.id;
+
+                    let (partner_pos, partner_alive) = snapshot
+                        .get(&partner)
+                        .copied()
+                        .ok_or(ActionError::PartnerNotFound { agent_id, partner })?;
+                    if !partner_alive {
+                        return Err(ActionError::PartnerNotFound { agent_id, partner });
+                    }
+                    if agent.position.zone() != partner_pos.zone() {
+                        return Err(ActionError::PartnerOutOfZone { agent_id, partner });
+                    }
+
+                    let terms = TradeTerms {
+                        partner,
+                        offer_ore,
+                        offer_amount,
+                        want_ore,
+                        want_amount,
+                    };
+                    if !trades.is_mutual(agent_id, &terms) {
+                        return Err(ActionError::TradeDeclined { agent_id, partner });
+                    }
+
+                    // The counterpart already settled this pair earlier in the tick.
+                    if !trades.settled.contains(&ordered_pair(agent_id, partner)) {
+                        agent
+                            .inventory
+                            .covers(&[(offer_ore.into(), offer_amount)])
+                            .map_err(|shortfall| insufficient(agent_id, shortfall))?;
+                        let partner_available = match partner_holdings {
+                            Some((id, balance)) if id == partner => balance,
+                            _ => 0,
+                        };
+                        if partner_available < want_amount {
+                            return Err(insufficient(
+                                partner,
+                                Shortfall {
+                                    item: want_ore.into(),
+                                    required: want_amount,
+                                    available: partner_available,
+                                },
+                            ));
+                        }
+                        pending_trade = Some((agent_id, terms));
+                    }
+                }
+                Action::GiveQi { to, amount } => {
+                    let agent_id = agent.id;
+                    let (to_pos, to_alive) = snapshot
+                        .get(&to)
+                        .copied()
+                        .ok_or(ActionError::RecipientNotFound { agent_id, to })?;
+                    if !to_alive {
+                        return Err(ActionError::RecipientNotFound { agent_id, to });
+                    }
+                    if agent.position.zone() != to_pos.zone()
+                        && !agent.position.within_range(to_pos, GIVE_RANGE)
+                    {
+                        return Err(ActionError::RecipientOutOfReach { agent_id, to });
+                    }
+                    agent.spend_qi(amount)?;
+                    self.world.stats.spend(ItemKind::Qi, amount);
+                    pending_gift = Some((agent_id, to, amount));
+                }
+                Action::Attack { target } => {
+                    let agent_id = agent.id;
+                    if target == agent_id {
+                        return Err(ActionError::TargetNotFound { agent_id, target });
+                    }
+                    let target_pos =
+                        target_position.ok_or(ActionError::TargetNotFound { agent_id, target })?;
+                    if !agent.position.within_range(target_pos, ATTACK_RANGE) {
+                        return Err(ActionError::TargetOutOfRange { agent_id, target });
+                    }
+
+                    // Damage scales with the Qi the attacker holds before paying for the strike.
+                    let damage = ATTACK_BASE_DAMAGE + agent.qi() / ATTACK_QI_PER_DAMAGE;
+                    let cost = request.action.qi_cost();
+                    agent.spend_qi(cost)?;
+                    self.world.stats.spend(ItemKind::Qi, cost);
+                    events.push(Event::QiSpent {
+                        agent_id,
+                        amount: cost,
+                        action: request.action.label(),
+                    });
+                    reclaimed_qi = reclaimed_qi.saturating_add(cost);
+
+                    pending_attack = Some((agent_id, target, damage));
+                }
+                Action::Dig { dx, dy, dz } => {
+                    let agent_id = agent.id;
+                    let target = agent.position.offset(dx, dy, dz);
+                    let block = self.world.terrain.block_at(target);
+                    let Some(item) = block.item() else {
+                        return Err(ActionError::NothingToDig { agent_id, target });
+                    };
+
+                    let cost = request.action.qi_cost();
+                    agent.spend_qi(cost)?;
+                    self.world.stats.spend(ItemKind::Qi, cost);
+                    events.push(Event::QiSpent {
+                        agent_id,
+                        amount: cost,
+                        action: request.action.label(),
+                    });
+                    reclaimed_qi = reclaimed_qi.saturating_add(cost);
+
+                    self.world.terrain.set_block(target, Block::Air);
+                    agent.gain_item(item, 1);
+                    self.world.stats.gain(item, 1);
+                    events.push(Event::BlockDug {
+                        agent_id,
+                        position: target,
+                        block,
+                    });
+                }
+                Action::Place { dx, dy, dz, block } => {
+                    let agent_id = agent.id;
+                    let target = agent.position.offset(dx, dy, dz);
+                    let Some(item) = block.item() else {
+                        return Err(ActionError::PlaceBlocked { agent_id, target });
+                    };
+                    if self.world.terrain.is_solid(target)
+                        || self.world.occupied.contains_key(&target)
+                        || self
+                            .world
+                            .structure_index
+                            .within(&self.world.structures, target, 0, |s| s.position)
+                            .next()
+                            .is_some()
+                    {
+                        return Err(ActionError::PlaceBlocked { agent_id, target });
+                    }
+
+                    let cost = request.action.qi_cost();
+                    let bill = [(item, 1), (ItemKind::Qi, cost)];
+                    agent
+                        .inventory
+                        .spend_all(&bill)
+                        .map_err(|shortfall| insufficient(agent_id, shortfall))?;
+                    self.world.stats.spend_all(&bill);
+                    events.push(Event::QiSpent {
+                        agent_id,
+                        amount: cost,
+                        action: request.action.label(),
+                    });
+                    reclaimed_qi = reclaimed_qi.saturating_add(cost);
+
+                    self.world.terrain.set_block(target, block);
+                    events.push(Event::BlockPlaced {
+                        agent_id,
+                        position: target,
+                        block,
+                    });
+                }
+                Action::Rest => {
+                    let agent_id = agent.id;
+                    let Some(structure_id) = self
+                        .world
+                        .structure_index
+                        .within(&self.world.structures, agent.position, HARVEST_RANGE, |s| {
+                            s.position
+                        })
+                        .filter(|s| s.owner == agent_id && s.kind == StructureKind::Basic)
+                        .map(|s| s.id)
+                        .min()
+                    else {
+                        return Err(ActionError::NoRestingPlace { agent_id });
+                    };
+                    let amount = self.world.recycled_qi.min(u64::from(REST_QI_PER_TICK)) as Qi;
+                    if amount == 0 {
+                        return Err(ActionError::NothingToRecover { agent_id });
+                    }
+                    self.world.recycled_qi -= u64::from(amount);
+                    self.world.stats.recycled_qi = self.world.recycled_qi;
+                    agent.gain_item(ItemKind::Qi, amount);
+                    self.world.stats.gain(ItemKind::Qi, amount);
+                    events.push(Event::AgentRested {
+                        agent_id,
+                        structure_id,
+                        amount,
+                    });
+                }
+                Action::Say { to, message } => {
+                    let agent_id = agent.id;
+                    let in_range = |id: &AgentId| {
+                        snapshot.get(id).is_some_and(|(position, alive)| {
+                            *alive && agent.position.within_range(*position, SCAN_RANGE)
+                        })
+                    };
+                    let mut listeners: Vec<AgentId> = if to == 0 {
+                        snapshot
+                            .keys()
+                            .copied()
+                            .filter(|id| *id != agent_id && in_range(id))
+                            .collect()
+                    } else {
+                        Some(to).filter(in_range).into_iter().collect()
+                    };
+                    if listeners.is_empty() {
+                        return Err(ActionError::NoListener { agent_id, to });
+                    }
+                    listeners.sort_unstable();
+                    pending_message = Some((agent_id, listeners, message));
+                }
+                Action::ClaimZone => {
+                    let agent_id = agent.id;
+                    let zone = agent.position.zone();
+                    if let Some(claim) = &zone_claim {
+                        return Err(ActionError::ZoneClaimed {
+                            agent_id,
+                            zone,
+                            owner: claim.owner,
+                        });
+                    }
+                    let cost = request.action.qi_cost();
+                    agent.spend_qi(cost)?;
+                    self.world.stats.spend(ItemKind::Qi, cost);
+                    events.push(Event::QiSpent {
+                        agent_id,
+                        amount: cost,
+                        action: request.action.label(),
+                    });
+                    reclaimed_qi = reclaimed_qi.saturating_add(cost);
+                    self.world.territories.claim(zone, agent_id, tick);
+                    events.push(Event::ZoneClaimed {
+                        agent_id,
+                        zone,
+                        cost,
+                    });
+                }
+                Action::PostOrder {
+                    side,
+                    ore,
+                    amount,
+                    price,
+                } => {
+                    let agent_id = agent.id;
+                    let (item, escrow) = match side {
+                        OrderSide::Buy => (ItemKind::Qi, amount * price),
+                        OrderSide::Sell => (ItemKind::from(ore), amount),
+                    };
+                    agent.spend_item(item, escrow)?;
+                    self.world.stats.spend(item, escrow);
+                    let order_id = self
+                        .world
+                        .market
+                        .post(agent_id, side, ore, amount, price, tick);
+                    events.push(Event::OrderPosted {
+                        agent_id,
+                        order_id,
+                        side,
+                        ore,
+                        amount,
+                        price,
+                    });
+                }
+                Action::Repair { structure_id } => {
+                    let agent_id = agent.id;
+                    let worn = self
+                        .world
+                        .structure_index
+                        .within(&self.world.structures, agent.position, HARVEST_RANGE, |s| {
+                            s.position
+                        })
+                        .filter(|s| s.durability < STRUCTURE_DURABILITY)
+                        .filter(|s| structure_id == 0 || s.id == structure_id)
+                        .min_by_key(|s| (s.durability, s.id))
+                        .map(|s| s.id)
+                        .ok_or(ActionError::NothingToRepair {
+                            agent_id,
+                            structure_id,
+                        })?;
+                    agent.spend_qi(REPAIR_COST)?;
+                    self.world.stats.spend(ItemKind::Qi, REPAIR_COST);
+                    events.push(Event::QiSpent {
+                        agent_id,
+                        amount: REPAIR_COST,
+                        action: "repair",
+                    });
+                    reclaimed_qi = reclaimed_qi.saturating_add(REPAIR_COST);
+                    let structure = self
+                        .world
+                        .structures
+                        .iter_mut()
+                        .find(|s| s.id == worn)
+                        .expect("structure found in reach");
+                    structure.durability = structure
+                        .durability
+                        .saturating_add(REPAIR_AMOUNT)
+                        .min(STRUCTURE_DURABILITY);
+                    events.push(Event::StructureRepaired {
+                        agent_id,
+                        structure_id: worn,
+                        durability: structure.durability,
+                    });
+                }
+                Action::DemolishStructure { structure_id } => {
+                    let agent_id = agent.id;
+                    // Like a repair, only a structure within reach can be torn down.
+                    let in_reach = self
+                        .world
+                        .structure_index
+                        .within(&self.world.structures, agent.position, HARVEST_RANGE, |s| {
+                            s.position
+                        })
+                        .any(|s| s.id == structure_id);
+                    let idx = self
+                        .world
+                        .structures
+                        .iter()
+                        .position(|s| s.id == structure_id)
+                        .filter(|_| in_reach)
+                        .ok_or(ActionError::StructureNotFound {
+                            agent_id,
+                            structure_id,
+                        })?;
+                    let owner = self.world.structures[idx].owner;
+                    if owner != agent_id {
+                        return Err(ActionError::NotStructureOwner {
+                            agent_id,
+                            structure_id,
+                            owner,
+                        });
+                    }
+                    let structure = self.world.structures.remove(idx);
+                    self.world.stats.remove_structure(structure.kind);
+                    pending_demolish = Some((agent_id, structure));
+                }
+                Action::Craft { recipe } => {
+                    let agent_id = agent.id;
+                    let known = self
+                        .recipes
+                        .get(&recipe)
+                        .ok_or(ActionError::UnknownRecipe {
+                            agent_id,
+                            recipe: recipe.clone(),
+                        })?;
+                    let bill = known.bill();
+                    agent
+                        .inventory
+                        .spend_all(&bill)
+                        .map_err(|shortfall| insufficient(agent_id, shortfall))?;
+                    self.world.stats.spend_all(&bill);
+                    agent.gain_item(known.output, known.amount);
+                    self.world.stats.gain(known.output, known.amount);
+                    let cost = known.inputs.get(&ItemKind::Qi).copied().unwrap_or(0);
+                    if cost > 0 {
+                        events.push(Event::QiSpent {
+                            agent_id,
+                            amount: cost,
+                            action: "craft",
+                        });
+                        reclaimed_qi = reclaimed_qi.saturating_add(cost);
+                    }
+                    events.push(Event::ItemCrafted {
+                        agent_id,
+                        recipe: recipe.trim().to_string(),
+                        item: known.output,
+                        amount: known.amount,
+                    });
+                }
+                Action::SetToll { harvest, build } => {
+                    let agent_id = agent.id;
+                    let zone = agent.position.zone();
+                    let claim = zone_claim
+                        .as_ref()
+                        .filter(|claim| claim.owner == agent_id)
+                        .and_then(|_| self.world.territories.get_mut(zone))
+                        .ok_or(ActionError::NotZoneOwner { agent_id, zone })?;
+                    claim.harvest_toll = harvest;
+                    claim.build_toll = build;
+                    events.push(Event::TollSet {
+                        agent_id,
+                        zone,
+                        harvest,
+                        build,
+                    });
+                }
                 Action::Idle => {}
             }
 
@@ -1151,18 +4610,30 @@ impl Vm {
         }
 
         if let Some((agent_id, position, qi)) = pending_scan {
-            let nearby_sources = self.world.nearby_qi_sources(position, SCAN_RANGE);
-            let nearby_structures = self.world.nearby_structures(position, SCAN_RANGE);
+            let report = self
+                .world
+                .scan_by(agent_id, SCAN_RANGE)
+                .unwrap_or_else(|| self.world.scan_from(position, SCAN_RANGE));
+            if let Some(agent) = self.world.agents.get_mut(&agent_id) {
+                agent.last_scan = Some(ScanMemory {
+                    tick,
+                    position,
+                    qi_sources: report.nearby_qi_sources.clone(),
+                    structures: report.nearby_structures.clone(),
+                });
+            }
             events.push(Event::ScanReport {
                 agent_id,
                 position,
                 qi,
-                nearby_qi_sources: nearby_sources,
-                nearby_structures,
+                nearby_qi_sources: report.nearby_qi_sources,
+                nearby_structures: report.nearby_structures,
+                threats: report.threats,
             });
         }
 
-        if let Some((child_name, child_position, parent_a, parent_b)) = pending_child {
+        if let Some((child_position, parent_a, parent_b)) = pending_child {
+            let child_name = self.world.child_name(parent_a, parent_b);
             let child_id = self.world.spawn_agent(child_name, 1, child_position);
             events.push(Event::AgentReproduced {
                 parent_a,
@@ -1171,75 +4642,656 @@ impl Vm {
             });
         }
 
-        if let Some((agent_id, ore, source_id)) = pending_harvest {
-            if let Some(src) = self
+        if let Some((agent_id, ore, source_id)) = pending_harvest
+            && let Some(src) = self
                 .world
                 .qi_sources
                 .iter_mut()
                 .find(|s| s.id == source_id && s.ore == ore)
-            {
-                let amount = src.current.min(HARVEST_PER_ACTION);
-                src.current = src.current.saturating_sub(amount);
-                if let Some(agent) = self.world.agents.get_mut(&agent_id) {
-                    agent.gain_ore(ore, amount);
-                }
+        {
+            let amount = src.current.min(ore.harvest_rate());
+            src.current = src.current.saturating_sub(amount);
+            if ore == OreKind::QI {
+                self.world.stats.node_qi = self.world.stats.node_qi.saturating_sub(amount as u64);
+            }
+            if let Some(agent) = self.world.agents.get_mut(&agent_id) {
+                agent.gain_item(ore.into(), amount);
+                self.world.stats.gain(ore.into(), amount);
+            }
 
-                events.push(Event::OreGained {
-                    agent_id,
-                    ore,
-                    amount,
-                    source: "ore_node",
-                });
-                events.push(Event::OreNodeHarvested {
-                    agent_id,
+            events.push(Event::OreGained {
+                agent_id,
+                ore,
+                amount,
+                source: "ore_node",
+            });
+            events.push(Event::OreNodeHarvested {
+                agent_id,
+                ore,
+                source_id,
+                amount,
+                remaining: src.current,
+            });
+
+            if src.current == 0 {
+                events.push(Event::OreNodeDrained {
                     ore,
                     source_id,
-                    amount,
-                    remaining: src.current,
+                    position: src.position,
                 });
-
-                if src.current == 0 {
-                    events.push(Event::OreNodeDrained {
+                // Looted remains leave the world instead of waiting to recharge.
+                if src.remains_of.is_some()
+                    && let Ok(node) = self.world.remove_qi_source(source_id)
+                {
+                    events.push(Event::OreNodeRemoved {
                         ore,
                         source_id,
-                        position: src.position,
+                        position: node.position,
+                        remaining: 0,
+                    });
+                }
+            }
+        }
+
+        if let Some((agent_id, structure)) = pending_demolish {
+            self.world.reindex();
+            let mut refund: BTreeMap<ItemKind, Qi> = structure.storage.iter().collect();
+            for (item, cost) in self.recipes.build_cost(structure.kind) {
+                let share = cost * DEMOLISH_REFUND_PERCENT / 100;
+                let share = match item {
+                    ItemKind::Qi => self.world.draw_qi(share),
+                    _ => share,
+                };
+                *refund.entry(item).or_default() += share;
+            }
+            refund.retain(|_, amount| *amount > 0);
+            if let Some(agent) = self.world.agents.get_mut(&agent_id) {
+                for (item, amount) in &refund {
+                    agent.gain_item(*item, *amount);
+                    self.world.stats.gain(*item, *amount);
+                }
+            }
+            events.push(Event::StructureDemolished {
+                agent_id,
+                structure_id: structure.id,
+                kind: structure.kind,
+                position: structure.position,
+                refund: refund.into_iter().collect(),
+            });
+        }
+
+        if let Some((agent_id, terms)) = pending_trade {
+            if let Some(agent) = self.world.agents.get_mut(&agent_id) {
+                agent.spend_item(terms.offer_ore.into(), terms.offer_amount)?;
+                self.world
+                    .stats
+                    .spend(terms.offer_ore.into(), terms.offer_amount);
+                agent.gain_item(terms.want_ore.into(), terms.want_amount);
+                self.world
+                    .stats
+                    .gain(terms.want_ore.into(), terms.want_amount);
+            }
+            if let Some(partner) = self.world.agents.get_mut(&terms.partner) {
+                partner.spend_item(terms.want_ore.into(), terms.want_amount)?;
+                self.world
+                    .stats
+                    .spend(terms.want_ore.into(), terms.want_amount);
+                partner.gain_item(terms.offer_ore.into(), terms.offer_amount);
+                self.world
+                    .stats
+                    .gain(terms.offer_ore.into(), terms.offer_amount);
+            }
+            trades.settled.insert(ordered_pair(agent_id, terms.partner));
+            events.push(Event::TradeCompleted {
+                agent_a: agent_id,
+                agent_b: terms.partner,
+                ore_a: terms.offer_ore,
+                amount_a: terms.offer_amount,
+                ore_b: terms.want_ore,
+                amount_b: terms.want_amount,
+            });
+        }
+
+        if let Some((from, to, amount)) = pending_gift {
+            events.push(Event::QiGiven {
+                agent_id: from,
+                to,
+                amount,
+            });
+            match self.world.agents.get_mut(&to).filter(|a| a.alive) {
+                Some(agent) => {
+                    agent.gain_item(ItemKind::Qi, amount);
+                    self.world.stats.gain(ItemKind::Qi, amount);
+                    events.push(Event::QiReceived {
+                        agent_id: to,
+                        from,
+                        amount,
+                    });
+                }
+                None => self.world.recycle_qi(amount),
+            }
+        }
+
+        if let Some((payer, owner, zone, amount)) = pending_toll {
+            events.push(Event::TollPaid {
+                agent_id: payer,
+                owner,
+                zone,
+                amount,
+            });
+            match self.world.agents.get_mut(&owner).filter(|a| a.alive) {
+                Some(agent) => {
+                    agent.gain_item(ItemKind::Qi, amount);
+                    self.world.stats.gain(ItemKind::Qi, amount);
+                    events.push(Event::TollCollected {
+                        owner,
+                        from: payer,
+                        zone,
+                        amount,
                     });
                 }
+                None => self.world.recycle_qi(amount),
+            }
+        }
+
+        if let Some((from, listeners, message)) = pending_message {
+            for to in listeners {
+                let Some(listener) = self.world.agents.get_mut(&to).filter(|a| a.alive) else {
+                    continue;
+                };
+                if listener.inbox.len() >= INBOX_LIMIT {
+                    listener.inbox.pop_front();
+                }
+                listener.inbox.push_back(Message {
+                    from,
+                    tick,
+                    text: message.clone(),
+                });
+                events.push(Event::MessageDelivered {
+                    from,
+                    to,
+                    message: message.clone(),
+                });
+            }
+        }
+
+        if let Some((attacker, target, damage)) = pending_attack {
+            let remaining_hp = match self.world.agents.get_mut(&target) {
+                Some(victim) => {
+                    victim.hp = victim.hp.saturating_sub(damage);
+                    victim.attacked_by.insert(attacker, tick);
+                    victim.hp
+                }
+                None => 0,
+            };
+            events.push(Event::AgentAttacked {
+                attacker,
+                target,
+                damage,
+                remaining_hp,
+            });
+            if remaining_hp == 0 {
+                events.extend(self.mark_agent_dead(target, DeathReason::Killed));
             }
         }
 
         Ok(events)
     }
 
-    fn enforce_age_limits(&mut self) -> Vec<Event> {
+    /// Return the escrow of expired orders and of dead agents' orders, then fill the orders
+    /// that cross.
+    fn settle_market(&mut self, tick: u64) -> Vec<Event> {
         let mut events = Vec::new();
-        let mut doomed = Vec::new();
-        for agent in self.world.agents.values() {
-            if agent.alive && agent.age >= agent.max_age {
-                doomed.push(agent.id);
+        let agents = &self.world.agents;
+        let expired = self.world.market.remove_where(|order| {
+            tick >= order.placed_at.saturating_add(ORDER_TTL_TICKS)
+                || !agents.get(&order.agent_id).is_some_and(|a| a.alive)
+        });
+        for order in expired {
+            let (item, amount) = order.escrow();
+            if let Some(agent) = self.world.agents.get_mut(&order.agent_id) {
+                agent.gain_item(item, amount);
+                self.world.stats.gain(item, amount);
             }
+            events.push(Event::OrderExpired {
+                agent_id: order.agent_id,
+                order_id: order.id,
+                item,
+                amount,
+            });
         }
-
-        for agent_id in doomed {
-            if let Some(event) = self.mark_agent_dead(agent_id, DeathReason::Age) {
-                events.push(event);
+        for fill in self.world.market.match_orders(tick) {
+            if let Some(buyer) = self.world.agents.get_mut(&fill.buyer) {
+                buyer.gain_item(fill.ore.into(), fill.amount);
+                self.world.stats.gain(fill.ore.into(), fill.amount);
+                buyer.gain_item(ItemKind::Qi, fill.refund);
+                self.world.stats.gain(ItemKind::Qi, fill.refund);
+            }
+            if let Some(seller) = self.world.agents.get_mut(&fill.seller) {
+                seller.gain_item(ItemKind::Qi, fill.amount * fill.price);
+                self.world
+                    .stats
+                    .gain(ItemKind::Qi, fill.amount * fill.price);
             }
+            events.push(Event::OrderFilled {
+                buyer: fill.buyer,
+                seller: fill.seller,
+                ore: fill.ore,
+                amount: fill.amount,
+                price: fill.price,
+                refund: fill.refund,
+                buy_order: fill.buy_order,
+                sell_order: fill.sell_order,
+            });
         }
-
         events
     }
 
-    fn mark_agent_dead(&mut self, agent_id: AgentId, reason: DeathReason) -> Option<Event> {
-        let agent = self.world.agents.get_mut(&agent_id)?;
-        if !agent.alive {
-            return None;
+    /// Programmable structures run their programs, then every structure's registered effects
+    /// get their `on_tick`, in structure id order.
+    fn run_structure_effects(&mut self, tick: u64) -> Vec<Event> {
+        let mut events = self.run_structure_programs(tick);
+        let structures: Vec<Structure> = self
+            .world
+            .structures
+            .iter()
+            .filter(|s| !self.effects.effects(s.kind).is_empty())
+            .cloned()
+            .collect();
+        let mut ctx = EffectContext {
+            world: &mut self.world,
+            tick,
+        };
+        for structure in &structures {
+            for effect in self.effects.effects(structure.kind) {
+                events.extend(effect.on_tick(&mut ctx, structure));
+            }
         }
-
-        agent.alive = false;
-        self.world.occupied.remove(&agent.position);
-        Some(Event::AgentDied { agent_id, reason })
+        events
     }
-}
+
+    /// Run the `on_adjacent_agent` and `on_harvest_nearby` effects of the structures that the
+    /// tick's moves came next to and its harvests happened near.
+    fn react_to_structures(&mut self, tick: u64, events: &[Event]) -> Vec<Event> {
+        if self.effects.is_empty() {
+            return Vec::new();
+        }
+        enum Trigger {
+            Adjacent(AgentId),
+            Harvest(AgentId, OreKind, Qi),
+        }
+        let mut triggers: Vec<(Structure, Trigger)> = Vec::new();
+        for event in events {
+            match event {
+                Event::AgentMoved { agent_id, from, to } => {
+                    let world = &self.world;
+                    triggers.extend(
+                        world
+                            .structure_index
+                            .within(&world.structures, *to, HARVEST_RANGE, |s| s.position)
+                            .filter(|s| !s.position.within_range(*from, HARVEST_RANGE))
+                            .map(|s| (s.clone(), Trigger::Adjacent(*agent_id))),
+                    );
+                }
+                Event::OreNodeHarvested {
+                    agent_id,
+                    ore,
+                    source_id,
+                    amount,
+                    ..
+                } => {
+                    let world = &self.world;
+                    let Some(node) = world.qi_sources.iter().find(|n| n.id == *source_id) else {
+                        continue;
+                    };
+                    triggers.extend(
+                        world
+                            .structure_index
+                            .within(
+                                &world.structures,
+                                node.position,
+                                HARVEST_NEARBY_RANGE,
+                                |s| s.position,
+                            )
+                            .map(|s| (s.clone(), Trigger::Harvest(*agent_id, *ore, *amount))),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let mut reactions = Vec::new();
+        let mut ctx = EffectContext {
+            world: &mut self.world,
+            tick,
+        };
+        for (structure, trigger) in &triggers {
+            for effect in self.effects.effects(structure.kind) {
+                reactions.extend(match *trigger {
+                    Trigger::Adjacent(agent_id) => {
+                        effect.on_adjacent_agent(&mut ctx, structure, agent_id)
+                    }
+                    Trigger::Harvest(agent_id, ore, amount) => {
+                        effect.on_harvest_nearby(&mut ctx, structure, agent_id, ore, amount)
+                    }
+                });
+            }
+        }
+        reactions
+    }
+
+    /// Programmable structures run their program once per tick, in structure id order.
+    fn run_structure_programs(&mut self, tick: u64) -> Vec<Event> {
+        let programs: Vec<(usize, Program)> = self
+            .world
+            .structures
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, s)| s.program.clone().map(|p| (idx, p)))
+            .collect();
+
+        let mut events = Vec::new();
+        for (index, program) in programs {
+            let mut host = StructureHost {
+                world: &mut self.world,
+                index,
+                tick,
+                events: Vec::new(),
+            };
+            let result = program.run(&mut host);
+            events.append(&mut host.events);
+            if let Err(err) = result {
+                events.push(Event::StructureScriptFaulted {
+                    structure_id: self.world.structures[index].id,
+                    error: err.to_string(),
+                });
+            }
+        }
+        events
+    }
+
+    /// Put agents with nobody acting nearby to sleep and wake dormant agents that someone
+    /// approached or that were sent a request. `actions` is the tick's batch, path steps
+    /// included.
+    fn update_hibernation(&mut self, tick: u64, actions: &[ActionRequest]) -> Vec<Event> {
+        let Some(rules) = self.world.hibernation else {
+            return Vec::new();
+        };
+        let mut requested: Vec<AgentId> = actions.iter().map(|req| req.agent_id).collect();
+        requested.sort_unstable();
+        requested.dedup();
+        // Where agents acted this tick, as they stand after it.
+        let active: Vec<(AgentId, Position)> = requested
+            .iter()
+            .filter_map(|id| self.world.agents.get(id))
+            .filter(|agent| agent.alive)
+            .map(|agent| (agent.id, agent.position))
+            .collect();
+        let mut index = ZoneIndex::new();
+        for (idx, (_, position)) in active.iter().enumerate() {
+            index.insert(idx, *position);
+        }
+
+        let mut ids: Vec<AgentId> = self
+            .world
+            .agents
+            .values()
+            .filter(|agent| agent.alive)
+            .map(|agent| agent.id)
+            .collect();
+        // Map order varies between processes; sort so seeded runs log the same events.
+        ids.sort_unstable();
+
+        let mut events = Vec::new();
+        for agent_id in ids {
+            let Some(agent) = self.world.agents.get_mut(&agent_id) else {
+                continue;
+            };
+            let company = index
+                .within(&active, agent.position, rules.radius, |(_, position)| {
+                    *position
+                })
+                .find(|(other, _)| *other != agent_id)
+                .map(|(other, _)| *other);
+            let addressed = requested.binary_search(&agent_id).is_ok();
+            if agent.dormant {
+                if company.is_some() || addressed {
+                    agent.dormant = false;
+                    agent.quiet_since = None;
+                    events.push(Event::AgentWoke {
+                        agent_id,
+                        by: company,
+                    });
+                } else if rules.age_every > 0 && tick.is_multiple_of(rules.age_every) {
+                    agent.age += 1;
+                }
+                continue;
+            }
+            if company.is_some() {
+                agent.quiet_since = None;
+                continue;
+            }
+            let since = *agent.quiet_since.get_or_insert(tick);
+            if tick - since + 1 >= rules.after_ticks {
+                agent.dormant = true;
+                self.world.pending_paths.remove(&agent_id);
+                events.push(Event::AgentHibernated { agent_id });
+            }
+        }
+        events
+    }
+
+    /// Count the ticks each awake agent ends with no Qi, warn those close to starving and
+    /// kill those out of time.
+    fn enforce_starvation(&mut self) -> Vec<Event> {
+        let Some(rules) = self.world.starvation else {
+            return Vec::new();
+        };
+        let mut ids: Vec<AgentId> = self
+            .world
+            .agents
+            .values()
+            .filter(|agent| agent.alive && !agent.dormant)
+            .map(|agent| agent.id)
+            .collect();
+        // Map order varies between processes; sort so seeded runs log the same events.
+        ids.sort_unstable();
+
+        let mut events = Vec::new();
+        for agent_id in ids {
+            let Some(agent) = self.world.agents.get_mut(&agent_id) else {
+                continue;
+            };
+            if agent.qi() > 0 {
+                agent.starving_ticks = 0;
+                continue;
+            }
+            agent.starving_ticks += 1;
+            let ticks_left = rules.after_ticks.saturating_sub(agent.starving_ticks);
+            if ticks_left == 0 {
+                events.extend(self.mark_agent_dead(agent_id, DeathReason::Starved));
+            } else if ticks_left <= rules.warn_ticks {
+                events.push(Event::AgentStarving {
+                    agent_id,
+                    ticks_left,
+                });
+            }
+        }
+        events
+    }
+
+    fn enforce_age_limits(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        let mut doomed = Vec::new();
+        for agent in self.world.agents.values() {
+            if agent.alive && agent.age >= agent.max_age {
+                doomed.push(agent.id);
+            }
+        }
+        // Map order varies between processes; sort so seeded runs log deaths identically.
+        doomed.sort_unstable();
+
+        for agent_id in doomed {
+            events.extend(self.mark_agent_dead(agent_id, DeathReason::Age));
+        }
+
+        events
+    }
+
+    /// The agent's death and the remains it drops; nothing for an agent already dead.
+    fn mark_agent_dead(&mut self, agent_id: AgentId, reason: DeathReason) -> Vec<Event> {
+        let Some(agent) = self.world.agents.get_mut(&agent_id) else {
+            return Vec::new();
+        };
+        if !agent.alive {
+            return Vec::new();
+        }
+
+        agent.alive = false;
+        self.world.occupied.remove(&agent.position);
+        self.world.stats.agent_died();
+        let mut events = vec![Event::AgentDied { agent_id, reason }];
+        events.extend(self.world.drop_remains(agent_id));
+        events
+    }
+}
+
+/// The world as seen by one structure's program.
+struct StructureHost<'a> {
+    world: &'a mut World,
+    index: usize,
+    tick: u64,
+    events: Vec<Event>,
+}
+
+impl StructureHost<'_> {
+    fn structure(&self) -> &Structure {
+        &self.world.structures[self.index]
+    }
+
+    /// The owner, if alive and standing next to the structure.
+    fn adjacent_owner(&self) -> Option<AgentId> {
+        let structure = self.structure();
+        self.world
+            .agents
+            .get(&structure.owner)
+            .filter(|a| a.alive && a.position.within_range(structure.position, HARVEST_RANGE))
+            .map(|a| a.id)
+    }
+}
+
+impl ScriptHost for StructureHost<'_> {
+    fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    fn agents_nearby(&self) -> Qi {
+        let position = self.structure().position;
+        self.world
+            .agents
+            .values()
+            .filter(|a| a.alive && a.position.within_range(position, SCAN_RANGE))
+            .count() as Qi
+    }
+
+    fn qi_nearby(&self) -> Qi {
+        let position = self.structure().position;
+        self.world
+            .source_index
+            .within(&self.world.qi_sources, position, HARVEST_RANGE, |s| {
+                s.position
+            })
+            .filter(|s| s.ore == OreKind::QI)
+            .fold(0, |acc: Qi, s| acc.saturating_add(s.current))
+    }
+
+    fn stored(&self, item: ItemKind) -> Qi {
+        self.structure().storage.get(item)
+    }
+
+    fn owner_qi(&self) -> Qi {
+        let owner = self.structure().owner;
+        self.world.agents.get(&owner).map(|a| a.qi()).unwrap_or(0)
+    }
+
+    fn collect(&mut self, amount: Qi) -> Qi {
+        let (structure_id, position) = {
+            let s = self.structure();
+            (s.id, s.position)
+        };
+        let mut collected = 0;
+        for idx in self.world.source_index.candidates(position, HARVEST_RANGE) {
+            let source = &mut self.world.qi_sources[idx];
+            if collected >= amount {
+                break;
+            }
+            if source.ore != OreKind::QI || !source.position.within_range(position, HARVEST_RANGE) {
+                continue;
+            }
+            let take = source.current.min(amount - collected);
+            if take == 0 {
+                continue;
+            }
+            source.current -= take;
+            collected += take;
+            self.world.stats.node_qi = self.world.stats.node_qi.saturating_sub(take as u64);
+            self.events.push(Event::StructureCollected {
+                structure_id,
+                source_id: source.id,
+                amount: take,
+            });
+        }
+        self.world.structures[self.index]
+            .storage
+            .gain_item(ItemKind::Qi, collected);
+        collected
+    }
+
+    fn emit(&mut self, amount: Qi) -> Qi {
+        let Some(owner) = self.adjacent_owner() else {
+            return 0;
+        };
+        let structure = &mut self.world.structures[self.index];
+        let given = amount.min(structure.storage.get(ItemKind::Qi));
+        if given == 0 || structure.storage.spend_item(ItemKind::Qi, given).is_err() {
+            return 0;
+        }
+        let structure_id = structure.id;
+        if let Some(agent) = self.world.agents.get_mut(&owner) {
+            agent.gain_item(ItemKind::Qi, given);
+            self.world.stats.gain(ItemKind::Qi, given);
+        }
+        self.events.push(Event::StructureProduced {
+            structure_id,
+            recipient: owner,
+            item: ItemKind::Qi,
+            amount: given,
+        });
+        given
+    }
+
+    fn store(&mut self, item: ItemKind, amount: Qi) -> Qi {
+        let Some(owner) = self.adjacent_owner() else {
+            return 0;
+        };
+        let Some(agent) = self.world.agents.get_mut(&owner) else {
+            return 0;
+        };
+        let taken = amount.min(agent.item(item));
+        if taken == 0 || agent.spend_item(item, taken).is_err() {
+            return 0;
+        }
+        self.world.stats.spend(item, taken);
+        let structure = &mut self.world.structures[self.index];
+        structure.storage.gain_item(item, taken);
+        self.events.push(Event::StructureStored {
+            structure_id: structure.id,
+            item,
+            amount: taken,
+        });
+        taken
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -1263,7 +5315,7 @@ mod tests {
         assert_eq!(vm.world().tick(), 1);
 
         let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 1); // 2 start - 1 upkeep
+        assert_eq!(agent.qi(), 1); // 2 start - 1 upkeep
         assert!(agent.alive);
     }
 
@@ -1282,7 +5334,7 @@ mod tests {
         )]);
 
         let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 0);
+        assert_eq!(agent.qi(), 0);
         assert!(agent.alive, "agent should remain alive at zero qi");
     }
 
@@ -1302,7 +5354,7 @@ mod tests {
 
         assert!(_tick.rejections.is_empty());
         let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 1); // cost to move
+        assert_eq!(agent.qi(), 1); // cost to move
     }
 
     #[test]
@@ -1310,396 +5362,3933 @@ mod tests {
         let mut vm = Vm::new();
         let agent_id = vm.spawn_agent("Walker", 3, Position::origin());
 
-        let tick = vm.step(&[ActionRequest::new(
-            agent_id,
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::Move {
+                dx: MAX_MOVE_RADIUS,
+                dy: 0,
+                dz: 0,
+            },
+        )]);
+
+        assert!(tick.rejections.is_empty());
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.qi(), 2); // 3 start -1 move
+    }
+
+    #[test]
+    fn move_beyond_limit_is_rejected() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Walker", 4, Position::origin());
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::Move {
+                dx: MAX_MOVE_RADIUS + 1,
+                dy: 0,
+                dz: 0,
+            },
+        )]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::MoveOutOfRange { .. }
+        ));
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.qi(), 4); // no charge on rejection
+    }
+
+    #[test]
+    fn move_into_occupied_is_rejected() {
+        let mut vm = Vm::new();
+        let a1 = vm.spawn_agent("A1", 3, Position::origin());
+        let _a2 = vm.spawn_agent("A2", 3, Position::origin()); // gets shifted to (1,0,0)
+
+        let tick = vm.step(&[ActionRequest::new(
+            a1,
+            Action::Move {
+                dx: 1,
+                dy: 0,
+                dz: 0,
+            },
+        )]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::PositionOccupied { target, .. } if target == Position { x:1, y:0, z:0 }
+        ));
+    }
+
+    #[test]
+    fn dead_agents_cannot_act() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Orchid", 1, Position::origin());
+
+        vm.kill_agent(agent_id, DeathReason::Hazard).unwrap();
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::Move {
+                dx: 1,
+                dy: 0,
+                dz: 0,
+            },
+        )]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::AgentDead(id) if id == agent_id
+        ));
+    }
+
+    #[test]
+    fn scanning_is_free() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Scout", 3, Position::origin());
+
+        let tick = vm.step(&[ActionRequest::new(agent_id, Action::Scan)]);
+
+        assert!(tick.rejections.is_empty());
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.qi(), 3); // scan is free
+        assert!(tick.events.iter().any(|e| matches!(
+            e,
+            Event::ActionObserved { agent_id: id, action: "scan" } if *id == agent_id
+        )));
+    }
+
+    #[test]
+    fn build_structure_costs_qi_and_records() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Builder", 3, Position::origin());
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+
+        assert!(tick.rejections.is_empty());
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.qi(), 2); // 3 start -1 build
+        assert_eq!(vm.world.structures.len(), 1);
+        assert!(
+            tick.events.iter().any(
+                |e| matches!(e, Event::StructureBuilt { agent_id: id, .. } if *id == agent_id)
+            )
+        );
+        assert!(tick.events.iter().any(|e| matches!(
+            e,
+            Event::QiSpent {
+                action: "build_structure",
+                amount: 1,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn cannot_build_on_existing_structure() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Builder", 5, Position::origin());
+
+        let _ = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::StructureSpaceOccupied { .. }
+        ));
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.qi(), 4); // 5 start -1 build (second attempt rejected, no charge)
+    }
+
+    #[test]
+    fn qi_sources_recharge_each_tick() {
+        let mut vm = Vm::new();
+        vm.set_max_qi_supply(10);
+        let source_id = vm.seed_qi_source(Position::origin(), 10, 2);
+        vm.set_ore_source_level(source_id, 1).unwrap();
+
+        let _ = vm.step(&[]);
+        let level_after_first = vm
+            .world
+            .qi_sources
+            .iter()
+            .find(|s| s.id == source_id)
+            .map(|s| s.current)
+            .unwrap();
+        assert_eq!(level_after_first, 3);
+
+        let _ = vm.step(&[]);
+        let level_after_second = vm
+            .world
+            .qi_sources
+            .iter()
+            .find(|s| s.id == source_id)
+            .map(|s| s.current)
+            .unwrap();
+        assert_eq!(level_after_second, 5);
+    }
+
+    #[test]
+    fn qi_recharge_respects_global_cap() {
+        let mut vm = Vm::new();
+        vm.set_max_qi_supply(5);
+        let source_id = vm.seed_qi_source(Position::origin(), 10, 3);
+        vm.set_ore_source_level(source_id, 0).unwrap();
+
+        let _ = vm.step(&[]);
+        let after_first = vm
+            .world
+            .qi_sources
+            .iter()
+            .find(|s| s.id == source_id)
+            .map(|s| s.current)
+            .unwrap();
+        assert_eq!(after_first, 3);
+
+        let _ = vm.step(&[]);
+        let after_second = vm
+            .world
+            .qi_sources
+            .iter()
+            .find(|s| s.id == source_id)
+            .map(|s| s.current)
+            .unwrap();
+        assert_eq!(after_second, 5); // capped by global supply
+    }
+
+    #[test]
+    fn scan_reports_local_state() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Scout", 3, Position::origin());
+        vm.seed_qi_source(Position { x: 1, y: 0, z: 0 }, 5, 0);
+
+        let _ = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+
+        let tick = vm.step(&[ActionRequest::new(agent_id, Action::Scan)]);
+
+        let report = tick.events.iter().find_map(|e| match e {
+            Event::ScanReport {
+                agent_id: id,
+                nearby_qi_sources,
+                nearby_structures,
+                ..
+            } if *id == agent_id => Some((nearby_qi_sources.clone(), nearby_structures.clone())),
+            _ => None,
+        });
+
+        let (sources, structures) = report.expect("scan report missing");
+        assert!(
+            sources
+                .iter()
+                .any(|s| s.position == Position { x: 1, y: 0, z: 0 }),
+            "expected qi source near agent"
+        );
+        assert_eq!(structures.len(), 1);
+
+        let events_before = vm.world().events().len();
+        let preview = vm.world().scan_from(Position::origin(), SCAN_RANGE);
+        assert_eq!(preview.nearby_qi_sources, sources);
+        assert_eq!(preview.nearby_structures, structures);
+        assert_eq!(vm.world().events().len(), events_before);
+        assert_eq!(vm.world().tick(), tick.tick);
+    }
+
+    #[test]
+    fn harvests_qi_from_nearest_node() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Harvester", 3, Position::origin());
+        let src_id = vm.seed_qi_source(Position { x: 1, y: 0, z: 0 }, 5, 0);
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::HarvestOre {
+                ore: OreKind::QI,
+                source_id: 0,
+            },
+        )]);
+
+        assert!(tick.rejections.is_empty());
+        let agent = vm.world().agent(agent_id).unwrap();
+        // cost 1, gain min(5, HARVEST_PER_ACTION=3) = 3
+        assert_eq!(agent.qi(), 5);
+        assert!(tick.events.iter().any(|e| matches!(
+            e,
+            Event::OreNodeHarvested { ore, source_id, amount, .. } if *ore == OreKind::QI && *source_id == src_id && *amount == HARVEST_PER_ACTION
+        )));
+    }
+
+    #[test]
+    fn harvest_fails_without_source() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Harvester", 3, Position::origin());
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::HarvestOre {
+                ore: OreKind::QI,
+                source_id: 42,
+            },
+        )]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::OreSourceUnavailable { .. }
+        ));
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.qi(), 3);
+    }
+
+    #[test]
+    fn resting_beside_an_own_basic_structure_draws_qi_from_the_recycled_pool() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 3, Position::origin())
+            .with_agent("Bo", 3, Position { x: 1, y: 0, z: 0 })
+            .build();
+        let (ada, bo) = (world.agent("Ada"), world.agent("Bo"));
+
+        // Nothing to rest at yet.
+        let idle = world.act("Ada", Action::Rest);
+        assert!(matches!(
+            idle.assert_rejected(ada),
+            ActionError::NoRestingPlace { .. }
+        ));
+
+        // Building pays 1 Qi into the recycled pool.
+        world
+            .act(
+                "Ada",
+                Action::BuildStructure {
+                    kind: StructureKind::Basic,
+                },
+            )
+            .assert_accepted();
+        assert_eq!(world.qi("Ada"), 2);
+        let pool = world.vm.world.recycled_qi;
+        assert!(pool >= 1);
+
+        let rested = world.act("Ada", Action::Rest);
+        rested.assert_accepted().assert_event(|e| {
+            matches!(e, Event::AgentRested { agent_id, amount: REST_QI_PER_TICK, .. } if *agent_id == ada)
+        });
+        assert_eq!(world.qi("Ada"), 2 + REST_QI_PER_TICK);
+        assert_eq!(
+            world.vm.world.recycled_qi,
+            pool - u64::from(REST_QI_PER_TICK)
+        );
+
+        // Bo stands next to the structure but does not own it.
+        let other = world.act("Bo", Action::Rest);
+        assert!(matches!(
+            other.assert_rejected(bo),
+            ActionError::NoRestingPlace { .. }
+        ));
+
+        world.vm.world.recycled_qi = 0;
+        let empty = world.act("Ada", Action::Rest);
+        assert!(matches!(
+            empty.assert_rejected(ada),
+            ActionError::NothingToRecover { .. }
+        ));
+        assert_eq!(
+            "rest"
+                .parse::<crate::modules::agent::ActionArg>()
+                .unwrap()
+                .label(),
+            "rest"
+        );
+    }
+
+    #[test]
+    fn harvest_by_offset_picks_the_node_in_that_cell() {
+        use crate::modules::agent::ActionArg;
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+        use std::str::FromStr;
+
+        assert!(matches!(
+            ActionArg::from_str("harvest:@1,0,0"),
+            Ok(ActionArg::HarvestAt {
+                ore: OreKind::QI,
+                dx: 1,
+                dy: 0,
+                dz: 0
+            })
+        ));
+        let transistor = ActionArg::from_str("harvest:transistor@0,0,-1").unwrap();
+        assert_eq!(transistor.label(), "harvest_transistor");
+        assert!(ActionArg::from_str("harvest:@1,0").is_err());
+
+        // The nearest node is under the agent, but the offset names the one east of it.
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 3, Position::origin())
+            .with_ore(OreKind::QI, Position::origin(), 9, 0)
+            .with_ore(OreKind::QI, Position { x: 1, y: 0, z: 0 }, 9, 0)
+            .build();
+        let (under, east) = (world.ore(0), world.ore(1));
+        let harvest_at = |dx| {
+            ActionArg::from_str(&format!("harvest:@{},0,0", dx))
+                .unwrap()
+                .materialize(0, 0)
+        };
+        world
+            .act("Ada", harvest_at(1))
+            .assert_accepted()
+            .assert_event(
+                |e| matches!(e, Event::OreNodeHarvested { source_id, .. } if *source_id == east),
+            );
+        assert_eq!(world.qi("Ada"), 5);
+        assert_ne!(under, east);
+
+        let tick = world.act("Ada", harvest_at(-1));
+        assert!(matches!(
+            tick.assert_rejected(world.agent("Ada")),
+            ActionError::NoOreNodeAt { ore: OreKind::QI, target, .. }
+                if *target == Position { x: -1, y: 0, z: 0 }
+        ));
+        assert_eq!(world.qi("Ada"), 5);
+    }
+
+    #[test]
+    fn harvest_rejected_when_depleted() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Harvester", 3, Position::origin());
+        let src_id = vm.seed_qi_source(Position::origin(), 10, 1);
+        vm.set_ore_source_level(src_id, 1).unwrap();
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::HarvestOre {
+                ore: OreKind::QI,
+                source_id: src_id,
+            },
+        )]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::OreSourceDepleted {
+                ore,
+                source_id,
+                available,
+                ..
+            } if ore == OreKind::QI && source_id == src_id && available < HARVEST_PER_ACTION
+        ));
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.qi(), 3);
+    }
+
+    #[test]
+    fn ore_sources_can_be_reshaped_at_runtime() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Harvester", 3, Position::origin());
+        let near = vm.seed_ore_source(OreKind::TRANSISTOR, Position::origin(), 10, 0);
+        let far = vm.seed_ore_source(
+            OreKind::TRANSISTOR,
+            Position::origin().offset(100, 0, 0),
+            10,
+            0,
+        );
+
+        assert_eq!(vm.set_ore_source_level(near, 50), Ok(10));
+        assert_eq!(vm.set_ore_source_level(near, 4), Ok(4));
+        assert_eq!(
+            vm.set_ore_source_level(99, 1),
+            Err(ActionError::OreSourceNotFound(99))
+        );
+
+        // Removing the first source shifts the second in the index; moving it must follow.
+        let removed = vm.remove_ore_source(near).unwrap();
+        assert_eq!(removed.current, 4);
+        assert_eq!(
+            vm.remove_ore_source(near).unwrap_err(),
+            ActionError::OreSourceNotFound(near)
+        );
+        vm.move_ore_source(far, Position::origin().offset(1, 0, 0))
+            .unwrap();
+
+        let harvest = |source_id| {
+            ActionRequest::new(
+                agent_id,
+                Action::HarvestOre {
+                    ore: OreKind::TRANSISTOR,
+                    source_id,
+                },
+            )
+        };
+        let tick = vm.step(&[harvest(near)]);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::OreSourceUnavailable { .. }
+        ));
+        // The changes are emitted with the next tick, right after `TickStarted`.
+        assert_eq!(
+            tick.events[1..5],
+            [
+                Event::OreNodeLevelSet {
+                    ore: OreKind::TRANSISTOR,
+                    source_id: near,
+                    from: 10,
+                    to: 10,
+                },
+                Event::OreNodeLevelSet {
+                    ore: OreKind::TRANSISTOR,
+                    source_id: near,
+                    from: 10,
+                    to: 4,
+                },
+                Event::OreNodeRemoved {
+                    ore: OreKind::TRANSISTOR,
+                    source_id: near,
+                    position: Position::origin(),
+                    remaining: 4,
+                },
+                Event::OreNodeMoved {
+                    ore: OreKind::TRANSISTOR,
+                    source_id: far,
+                    from: Position::origin().offset(100, 0, 0),
+                    to: Position::origin().offset(1, 0, 0),
+                },
+            ]
+        );
+        let tick = vm.step(&[harvest(far)]);
+        assert!(tick.rejections.is_empty());
+        assert!(
+            !tick
+                .events
+                .iter()
+                .any(|event| matches!(event, Event::OreNodeLevelSet { .. }))
+        );
+        assert_eq!(vm.world().qi_sources().len(), 1);
+        assert_eq!(vm.world().qi_sources()[0].current, 10 - HARVEST_PER_ACTION);
+    }
+
+    #[test]
+    fn world_stats_track_changes_without_recounting() {
+        let mut vm = Vm::new();
+        let a = vm.spawn_agent("A", 5, Position::origin());
+        let b = vm.spawn_agent("B", 3, Position::origin().offset(3, 0, 0));
+        let node = vm.seed_qi_source(Position::origin().offset(1, 0, 0), 10, 0);
+        vm.seed_ore_source(OreKind::TRANSISTOR, Position::origin(), 4, 0);
+
+        let stats = vm.stats();
+        assert_eq!((stats.alive_agents, stats.dead_agents), (2, 0));
+        assert_eq!((stats.agent_qi, stats.node_qi), (8, 10));
+
+        vm.kill_agent(b, DeathReason::Killed).unwrap();
+        vm.set_ore_source_level(node, 6).unwrap();
+        vm.set_tick(40);
+        let stats = vm.stats().clone();
+        assert_eq!((stats.alive_agents, stats.dead_agents), (1, 1));
+        // B's 3 Qi lie in its remains.
+        assert_eq!((stats.node_qi, stats.tick), (6 + 3, 40));
+        // The running totals match a full recount.
+        assert_eq!(stats, vm.world.recount_stats());
+
+        let tick = vm.step(&[ActionRequest::new(
+            a,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+        assert!(tick.rejections.is_empty());
+        let stats = vm.stats();
+        assert_eq!(stats.tick, 41);
+        assert_eq!(stats.structures.get(&StructureKind::Basic), Some(&1));
+        assert_eq!(stats.agent_qi, 4);
+
+        // Evicting and restoring a zone, or reloading a save, counts only what moves.
+        let before = vm.stats().clone();
+        let cold = vm.world.evict_zone(Position::origin().zone()).unwrap();
+        assert!(vm.stats().structures.is_empty());
+        assert_eq!(vm.stats().node_qi, 0);
+        assert_eq!(vm.stats(), &vm.world.recount_stats());
+        vm.world.restore_zone(cold).unwrap();
+        assert_eq!(vm.stats(), &before);
+        let reloaded = World::from_persistent(vm.world.to_persistent()).unwrap();
+        assert_eq!(reloaded.stats(), &before);
+    }
+
+    #[test]
+    fn registry_profiles_follow_agents_between_runs() {
+        use crate::modules::agents::{self, AgentStore};
+
+        let mut registry = AgentStore::default();
+        let alive = agents::create_agent(&mut registry, String::new())
+            .unwrap()
+            .id;
+        let dead = agents::create_agent(&mut registry, String::new())
+            .unwrap()
+            .id;
+        let absent = agents::create_agent(&mut registry, String::new())
+            .unwrap()
+            .id;
+
+        let mut vm = Vm::new();
+        let a = vm.spawn_agent(alive.clone(), 5, Position::origin().offset(2, 0, 0));
+        let d = vm.spawn_agent(dead.clone(), 1, Position::origin());
+        vm.restore_agent(a, 7, &[(ItemKind::Transistor, 3)])
+            .unwrap();
+        vm.kill_agent(d, DeathReason::Killed).unwrap();
+
+        assert_eq!(agents::sync_from_world(&mut registry, vm.world()), 2);
+        assert_eq!(agents::sync_from_world(&mut registry, vm.world()), 0);
+        let profile = &registry.agents[&alive];
+        assert_eq!((profile.qi, profile.transistors, profile.age), (5, 3, 7));
+        assert_eq!(profile.position, Some(Position::origin().offset(2, 0, 0)));
+        assert!(profile.alive);
+        assert!(!registry.agents[&dead].alive);
+        assert_eq!(registry.agents[&absent].position, None);
+
+        // The defaults stay out of the stored document.
+        let stored = serde_json::to_value(&registry.agents[&absent]).unwrap();
+        assert!(stored.get("alive").is_none() && stored.get("age").is_none());
+        let stored = serde_json::to_value(&registry.agents[&dead]).unwrap();
+        assert_eq!(stored["alive"], false);
+    }
+
+    #[test]
+    fn programmable_structure_requires_transistors() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Builder", 3, Position::origin());
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::BuildStructure {
+                kind: StructureKind::Programmable,
+            },
+        )]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::InsufficientItem {
+                item: ItemKind::Transistor,
+                ..
+            }
+        ));
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.qi(), 3);
+        assert_eq!(agent.item(ItemKind::Transistor), 0);
+    }
+
+    #[test]
+    fn qi_structure_feeds_adjacent_owner_after_upkeep() {
+        let mut vm = Vm::new();
+        let owner = vm.spawn_agent("Keeper", 10, Position::origin());
+        let build = vm.step(&[ActionRequest::new(
+            owner,
+            Action::BuildStructure {
+                kind: StructureKind::Qi,
+            },
+        )]);
+        assert!(build.rejections.is_empty());
+        assert_eq!(vm.world().agent(owner).unwrap().qi(), 9);
+
+        let _ = vm.step(&[]);
+        let cycle = vm.step(&[]);
+
+        assert!(cycle.events.iter().any(|e| matches!(
+            e,
+            Event::StructureUpkeepPaid { owner: o, amount: QI_STRUCTURE_UPKEEP, .. } if *o == owner
+        )));
+        assert!(cycle.events.iter().any(|e| matches!(
+            e,
+            Event::StructureProduced { recipient, amount: QI_STRUCTURE_OUTPUT, .. } if *recipient == owner
+        )));
+        assert_eq!(
+            vm.world().agent(owner).unwrap().qi(),
+            9 - QI_STRUCTURE_UPKEEP + QI_STRUCTURE_OUTPUT
+        );
+    }
+
+    #[test]
+    fn structure_effects_fire_on_arrival_and_nearby_harvests() {
+        use crate::modules::effects::{EffectSpec, StructureEffect};
+
+        /// Hands a harvester a dirt block for every harvest near the structure.
+        #[derive(Debug)]
+        struct Spoil;
+        impl StructureEffect for Spoil {
+            fn on_harvest_nearby(
+                &self,
+                ctx: &mut EffectContext<'_>,
+                structure: &Structure,
+                agent_id: AgentId,
+                _ore: OreKind,
+                _amount: Qi,
+            ) -> Vec<Event> {
+                ctx.grant(agent_id, ItemKind::Dirt, 1);
+                vec![Event::StructureProduced {
+                    structure_id: structure.id,
+                    recipient: agent_id,
+                    item: ItemKind::Dirt,
+                    amount: 1,
+                }]
+            }
+        }
+
+        let mut vm = Vm::new();
+        let owner = vm.spawn_agent("Mason", 10, Position::origin());
+        let visitor = vm.spawn_agent("Visitor", 10, Position { x: 3, y: 0, z: 0 });
+        let source_id = vm.seed_qi_source(Position { x: 2, y: 0, z: 1 }, 9, 0);
+        let build = vm.step(&[ActionRequest::new(
+            owner,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+        assert!(build.rejections.is_empty());
+
+        let spec: EffectSpec = toml::from_str(
+            "kind = \"basic\"\non = \"adjacent_agent\"\nitem = \"stone\"\namount = 2\nupkeep = 1",
+        )
+        .unwrap();
+        assert_eq!(
+            toml::from_str::<EffectSpec>(&toml::to_string(&spec).unwrap()).unwrap(),
+            spec
+        );
+        vm.add_effect_spec(spec);
+        vm.register_structure_effect(StructureKind::Basic, Spoil);
+        let step = Action::Move {
+            dx: -1,
+            dy: 0,
+            dz: 0,
+        };
+        let stone = |vm: &Vm| {
+            vm.world()
+                .agent(visitor)
+                .unwrap()
+                .inventory
+                .get(ItemKind::Stone)
+        };
+
+        // (2,0,0) is still two blocks out.
+        vm.step(&[ActionRequest::new(visitor, step.clone())]);
+        assert_eq!(stone(&vm), 0);
+        let arrive = vm.step(&[ActionRequest::new(visitor, step)]);
+        assert_eq!(stone(&vm), 2);
+        assert!(arrive.events.iter().any(|e| matches!(
+            e,
+            Event::StructureUpkeepPaid { owner: o, amount: 1, .. } if *o == owner
+        )));
+        // Staying next to it is not arriving again.
+        vm.step(&[ActionRequest::new(visitor, Action::Idle)]);
+        assert_eq!(stone(&vm), 2);
+
+        let harvest = vm.step(&[ActionRequest::new(
+            visitor,
+            Action::HarvestOre {
+                ore: OreKind::QI,
+                source_id,
+            },
+        )]);
+        assert!(harvest.rejections.is_empty());
+        assert!(harvest.events.iter().any(|e| matches!(
+            e,
+            Event::StructureProduced { recipient, item: ItemKind::Dirt, .. } if *recipient == visitor
+        )));
+        assert_eq!(
+            vm.world()
+                .agent(visitor)
+                .unwrap()
+                .inventory
+                .get(ItemKind::Dirt),
+            1
+        );
+        assert_eq!(vm.effect_specs().len(), 1);
+        assert_eq!(
+            vm.structure_effects().effects(StructureKind::Basic).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn qi_structure_stalls_when_owner_cannot_pay() {
+        let mut vm = Vm::new();
+        let owner = vm.spawn_agent("Keeper", 1, Position::origin());
+        let _ = vm.step(&[ActionRequest::new(
+            owner,
+            Action::BuildStructure {
+                kind: StructureKind::Qi,
+            },
+        )]);
+        assert_eq!(vm.world().agent(owner).unwrap().qi(), 0);
+
+        let _ = vm.step(&[]);
+        let cycle = vm.step(&[]);
+
+        assert!(
+            cycle
+                .events
+                .iter()
+                .any(|e| matches!(e, Event::StructureStalled { .. }))
+        );
+        assert!(
+            !cycle
+                .events
+                .iter()
+                .any(|e| matches!(e, Event::StructureProduced { .. }))
+        );
+        assert_eq!(vm.world().agent(owner).unwrap().qi(), 0);
+    }
+
+    fn programmable_structure_with(program: &str) -> (Vm, AgentId, u64) {
+        let mut vm = Vm::new();
+        let owner = vm.spawn_agent("Engineer", 5, Position::origin());
+        vm.world
+            .agents
+            .get_mut(&owner)
+            .unwrap()
+            .gain_item(ItemKind::Transistor, 1);
+        let _ = vm.step(&[ActionRequest::new(
+            owner,
+            Action::BuildStructure {
+                kind: StructureKind::Programmable,
+            },
+        )]);
+        let structure_id = vm.world.structures[0].id;
+        vm.attach_program(structure_id, program.parse().unwrap())
+            .unwrap();
+        (vm, owner, structure_id)
+    }
+
+    #[test]
+    fn structure_program_collects_and_emits_to_adjacent_owner() {
+        let (mut vm, owner, structure_id) =
+            programmable_structure_with("push 3\ncollect\npop\npush 2\nemit\npop\n");
+        vm.seed_qi_source(Position::origin(), 10, 0);
+        let qi_before = vm.world().agent(owner).unwrap().qi();
+
+        let tick = vm.step(&[]);
+
+        assert!(tick.events.iter().any(|e| matches!(
+            e,
+            Event::StructureCollected { structure_id: id, amount: 3, .. } if *id == structure_id
+        )));
+        assert_eq!(vm.world().agent(owner).unwrap().qi(), qi_before + 2);
+        assert_eq!(vm.world.structures[0].storage.get(ItemKind::Qi), 1);
+        assert_eq!(vm.world.qi_sources[0].current, 7);
+    }
+
+    #[test]
+    fn structure_program_faults_when_out_of_steps() {
+        let (mut vm, _, structure_id) = programmable_structure_with("spin:\njmp spin\n");
+
+        let tick = vm.step(&[]);
+
+        assert!(tick.events.iter().any(|e| matches!(
+            e,
+            Event::StructureScriptFaulted { structure_id: id, .. } if *id == structure_id
+        )));
+    }
+
+    #[test]
+    fn programs_only_attach_to_programmable_structures() {
+        let mut vm = Vm::new();
+        let owner = vm.spawn_agent("Mason", 5, Position::origin());
+        let _ = vm.step(&[ActionRequest::new(
+            owner,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+        let structure_id = vm.world.structures[0].id;
+
+        assert!(
+            vm.attach_program(structure_id, "halt".parse().unwrap())
+                .is_err()
+        );
+        assert!("jmp nowhere".parse::<Program>().is_err());
+    }
+
+    #[test]
+    fn rejected_build_keeps_whole_inventory() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Builder", 0, Position::origin());
+        vm.world
+            .agents
+            .get_mut(&agent_id)
+            .unwrap()
+            .gain_item(ItemKind::Transistor, 1);
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::BuildStructure {
+                kind: StructureKind::Programmable,
+            },
+        )]);
+
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::InsufficientQi { .. }
+        ));
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.item(ItemKind::Transistor), 1);
+        assert!(vm.world.structures.is_empty());
+    }
+
+    #[test]
+    fn move_into_solid_terrain_is_rejected() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Walker", 5, Position::origin());
+        vm.world_mut()
+            .terrain_mut()
+            .set_block(Position::origin().offset(1, 0, 0), Block::Stone);
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::Move {
+                dx: 1,
+                dy: 0,
+                dz: 0,
+            },
+        )]);
+
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::BlockedByTerrain { .. }
+        ));
+        assert_eq!(
+            vm.world().agent(agent_id).unwrap().position,
+            Position::origin()
+        );
+    }
+
+    #[test]
+    fn dig_then_place_moves_a_block() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Digger", 10, Position::origin());
+        *vm.world_mut().terrain_mut() = Terrain::flat(Position::origin(), 2, 2);
+        let below = Position::origin().offset(0, -1, 0);
+        let beside = Position::origin().offset(1, 0, 0);
+
+        let dig = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::Dig {
+                dx: 0,
+                dy: -1,
+                dz: 0,
+            },
+        )]);
+        assert!(dig.rejections.is_empty());
+        assert_eq!(vm.world().terrain().block_at(below), Block::Air);
+        assert_eq!(vm.world().agent(agent_id).unwrap().item(ItemKind::Dirt), 1);
+
+        let place = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::Place {
+                dx: 1,
+                dy: 0,
+                dz: 0,
+                block: Block::Dirt,
+            },
+        )]);
+        assert!(place.rejections.is_empty());
+        assert_eq!(vm.world().terrain().block_at(beside), Block::Dirt);
+        assert_eq!(vm.world().agent(agent_id).unwrap().item(ItemKind::Dirt), 0);
+
+        let again = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::Place {
+                dx: 0,
+                dy: -1,
+                dz: 0,
+                block: Block::Dirt,
+            },
+        )]);
+        assert!(matches!(
+            again.rejections[0].error,
+            ActionError::InsufficientItem {
+                item: ItemKind::Dirt,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn snapshot_carries_zone_labels() {
+        let mut vm = Vm::new();
+        let mut labels = ZoneStore::default();
+        labels
+            .entry(Position::origin().offset(20, 0, 0).zone())
+            .name = Some("East Ridge".into());
+        vm.world_mut().set_zone_labels(labels);
+
+        let snapshot = vm.snapshot();
+        assert_eq!(snapshot.zones.len(), 1);
+        assert_eq!(snapshot.zones[0].zone, Zone { x: 1, y: 0, z: 0 });
+        assert_eq!(
+            vm.world()
+                .zone_label(Zone { x: 1, y: 0, z: 0 })
+                .and_then(|l| l.name.as_deref()),
+            Some("East Ridge")
+        );
+    }
+
+    #[test]
+    fn move_to_follows_path_around_walls_across_ticks() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Walker", 20, Position::origin());
+        // A wall at x=2 spanning y/z -1..=1 forces a detour.
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                vm.world_mut()
+                    .terrain_mut()
+                    .set_block(Position::origin().offset(2, dy, dz), Block::Stone);
+            }
+        }
+        let target = Position::origin().offset(6, 0, 0);
+
+        let first = vm.step(&[ActionRequest::new(agent_id, Action::MoveTo { target })]);
+        assert!(first.rejections.is_empty());
+        assert!(
+            first
+                .events
+                .iter()
+                .any(|e| matches!(e, Event::PathProgress { .. }))
+        );
+        assert_eq!(vm.world().move_target(agent_id), Some(target));
+
+        // No new request: the agent keeps walking until it arrives.
+        let mut arrived = false;
+        for _ in 0..5 {
+            let tick = vm.step(&[]);
+            assert!(tick.rejections.is_empty());
+            let position = vm.world().agent(agent_id).unwrap().position;
+            assert!(!vm.world().terrain().is_solid(position));
+            if tick
+                .events
+                .iter()
+                .any(|e| matches!(e, Event::PathCompleted { .. }))
+            {
+                arrived = true;
+                break;
+            }
+        }
+        assert!(arrived);
+        assert_eq!(vm.world().agent(agent_id).unwrap().position, target);
+        assert_eq!(vm.world().move_target(agent_id), None);
+    }
+
+    #[test]
+    fn move_to_unreachable_target_is_rejected() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Walker", 5, Position::origin());
+        let target = Position::origin().offset(5, 0, 0);
+        vm.world_mut().terrain_mut().set_block(target, Block::Stone);
+
+        let tick = vm.step(&[ActionRequest::new(agent_id, Action::MoveTo { target })]);
+
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::NoPath { .. }
+        ));
+        assert_eq!(vm.world().agent(agent_id).unwrap().qi(), 5);
+    }
+
+    #[test]
+    fn minimap_summarizes_zones_in_range() {
+        let mut vm = Vm::new();
+        let builder = vm.spawn_agent("Builder", 10, Position::origin());
+        vm.spawn_agent("Far", 1, Position::origin().offset(ZONE_SIZE * 3, 0, 0));
+        vm.seed_ore_source(OreKind::QI, Position::origin().offset(1, 0, 0), 7, 0);
+        vm.step(&[ActionRequest::new(
+            builder,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+
+        let minimap = vm.snapshot().minimap(Position::origin().zone(), 1);
+
+        assert_eq!(minimap.len(), 1);
+        let home = &minimap[0];
+        assert_eq!(home.zone, Zone { x: 0, y: 0, z: 0 });
+        assert_eq!(home.agents, 1);
+        assert_eq!(home.ore_available, 7);
+        assert_eq!(home.structures, 1);
+        assert_eq!(home.owner, Some(builder));
+    }
+
+    #[test]
+    fn zone_summaries_cover_every_occupied_zone_with_ore_capacity() {
+        let mut vm = Vm::new();
+        let far = Position::origin().offset(ZONE_SIZE * 3, 0, 0);
+        let settler = vm.spawn_agent("Settler", 10, far);
+        vm.spawn_agent("Home", 1, Position::origin());
+        vm.seed_ore_source(OreKind::QI, far.offset(1, 0, 0), 7, 0);
+        vm.seed_ore_source(OreKind::TRANSISTOR, far.offset(0, 1, 0), 5, 0);
+        vm.step(&[ActionRequest::new(
+            settler,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+        vm.step(&[ActionRequest::new(
+            settler,
+            Action::HarvestOre {
+                ore: OreKind::QI,
+                source_id: 0,
+            },
+        )]);
+
+        let zones = vm.snapshot().zone_summaries();
+        assert_eq!(zones.len(), 2);
+        assert_eq!(zones[0].zone, Position::origin().zone());
+        assert_eq!((zones[0].agents, zones[0].structures), (1, 0));
+        let outpost = &zones[1];
+        assert_eq!(outpost.zone, far.zone());
+        assert_eq!(outpost.ore_capacity, 12);
+        assert_eq!(outpost.ore_available, 12 - HARVEST_PER_ACTION as u64);
+        assert_eq!(outpost.owner, Some(settler));
+    }
+
+    #[test]
+    fn range_queries_cross_zone_boundaries() {
+        let mut vm = Vm::new();
+        let edge = Position::origin().offset(ZONE_SIZE, 0, 0);
+        let agent_id = vm.spawn_agent("Edge", 5, edge);
+        vm.seed_ore_source(OreKind::QI, edge.offset(-1, 0, 0), 9, 0);
+        vm.seed_ore_source(OreKind::QI, edge.offset(-SCAN_RANGE - 1, 0, 0), 9, 0);
+        vm.seed_ore_source(OreKind::QI, edge.offset(0, 0, -SCAN_RANGE), 9, 0);
+
+        let nearby: Vec<u64> = vm
+            .world
+            .nearby_qi_sources(edge, SCAN_RANGE)
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(nearby, vec![1, 3]);
+
+        let tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::HarvestOre {
+                ore: OreKind::QI,
+                source_id: 0,
+            },
+        )]);
+        assert!(tick.rejections.is_empty());
+        assert_eq!(vm.world.qi_sources[0].current, 9 - HARVEST_PER_ACTION);
+    }
+
+    #[test]
+    fn snapshot_changes_report_deaths_and_new_structures() {
+        let mut vm = Vm::new();
+        let builder = vm.spawn_agent("Builder", 5, Position::origin());
+        let doomed = vm.spawn_agent("Doomed", 1, Position::origin().offset(3, 0, 0));
+        let before = vm.snapshot();
+
+        vm.kill_agent(doomed, DeathReason::Killed).unwrap();
+        vm.step(&[ActionRequest::new(
+            builder,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+        let changes = vm.snapshot().changes_since(&before);
+
+        assert_eq!(changes.died, vec![doomed]);
+        assert_eq!(changes.built.len(), 1);
+        assert_eq!(changes.built[0].owner, builder);
+        assert!(vm.snapshot().changes_since(&vm.snapshot()).built.is_empty());
+    }
+
+    #[test]
+    fn parallel_validation_keeps_request_order() {
+        let mut vm = Vm::new();
+        let ids: Vec<AgentId> = (0..PARALLEL_VALIDATION_MIN as i32 + 6)
+            .map(|i| vm.spawn_agent(format!("A{}", i), 5, Position::origin().offset(0, 0, i * 8)))
+            .collect();
+        vm.kill_agent(ids[3], DeathReason::Killed).unwrap();
+
+        // Every third agent asks for an oversized move; the rest step once.
+        let requests: Vec<ActionRequest> = ids
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, id)| {
+                let dx = if i % 3 == 0 { MAX_MOVE_RADIUS + 1 } else { 1 };
+                ActionRequest::new(*id, Action::Move { dx, dy: 0, dz: 0 })
+            })
+            .collect();
+        let tick = vm.step(&requests);
+
+        let rejected: Vec<AgentId> = tick.rejections.iter().map(|r| r.request.agent_id).collect();
+        let expected: Vec<AgentId> = requests
+            .iter()
+            .map(|r| r.agent_id)
+            .filter(|id| ids.iter().position(|x| x == id).unwrap() % 3 == 0)
+            .collect();
+        assert_eq!(rejected, expected);
+        assert!(matches!(
+            tick.rejections
+                .iter()
+                .find(|r| r.request.agent_id == ids[3])
+                .unwrap()
+                .error,
+            ActionError::AgentDead(_)
+        ));
+        let moved = tick
+            .events
+            .iter()
+            .filter(|e| matches!(e, Event::AgentMoved { .. }))
+            .count();
+        assert_eq!(moved, ids.len() - expected.len());
+    }
+
+    #[test]
+    fn same_seed_and_actions_replay_identically() {
+        let run = |seed: u64| {
+            let mut vm = Vm::with_seed(seed);
+            let ids: Vec<AgentId> = (0..8)
+                .map(|i| vm.spawn_agent_with_age(format!("A{}", i), 5, Position::origin(), 2))
+                .collect();
+            let mut log = Vec::new();
+            for _ in 0..3 {
+                let requests: Vec<ActionRequest> = ids
+                    .iter()
+                    .map(|id| ActionRequest::new(*id, Action::Scan))
+                    .collect();
+                log.push(vm.step(&requests));
+            }
+            let draw: u64 = rand::Rng::r#gen(vm.world_mut().rng());
+            (log, draw, vm.world().seed())
+        };
+
+        let (first, first_draw, seed) = run(42);
+        let (second, second_draw, _) = run(42);
+        assert_eq!(seed, 42);
+        assert_eq!(first, second);
+        assert_eq!(first_draw, second_draw);
+        assert_ne!(run(7).1, first_draw);
+    }
+
+    #[test]
+    fn harvest_transistor_and_build_programmable() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Tinkerer", 4, Position::origin());
+        vm.seed_ore_source(OreKind::TRANSISTOR, Position::origin(), 5, 0);
+
+        let harvest_tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::HarvestOre {
+                ore: OreKind::TRANSISTOR,
+                source_id: 0,
+            },
+        )]);
+
+        assert!(harvest_tick.rejections.is_empty());
+        let agent_after_harvest = vm.world().agent(agent_id).unwrap();
+        assert_eq!(
+            agent_after_harvest.item(ItemKind::Transistor),
+            HARVEST_PER_ACTION
+        );
+        assert_eq!(agent_after_harvest.qi(), 3); // 4 start -1 harvest cost
+
+        let build_tick = vm.step(&[ActionRequest::new(
+            agent_id,
+            Action::BuildStructure {
+                kind: StructureKind::Programmable,
+            },
+        )]);
+
+        assert!(build_tick.rejections.is_empty());
+        let agent_after_build = vm.world().agent(agent_id).unwrap();
+        assert_eq!(
+            agent_after_build.item(ItemKind::Transistor),
+            HARVEST_PER_ACTION - 1
+        );
+        assert_eq!(agent_after_build.qi(), 2); // 3 after harvest -1 build cost
+        assert_eq!(vm.world.structures.len(), 1);
+        assert!(build_tick.events.iter().any(|e| matches!(
+            e,
+            Event::StructureBuilt {
+                kind: StructureKind::Programmable,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn mutual_trade_swaps_holdings() {
+        let mut vm = Vm::new();
+        let a = vm.spawn_agent("Seller", 5, Position::origin());
+        let b = vm.spawn_agent("Buyer", 1, Position::origin());
+        vm.world
+            .agents
+            .get_mut(&b)
+            .unwrap()
+            .gain_item(ItemKind::Transistor, 2);
+
+        let tick = vm.step(&[
+            ActionRequest::new(
+                a,
+                Action::Trade {
+                    partner: b,
+                    offer_ore: OreKind::QI,
+                    offer_amount: 3,
+                    want_ore: OreKind::TRANSISTOR,
+                    want_amount: 1,
+                },
+            ),
+            ActionRequest::new(
+                b,
+                Action::Trade {
+                    partner: a,
+                    offer_ore: OreKind::TRANSISTOR,
+                    offer_amount: 1,
+                    want_ore: OreKind::QI,
+                    want_amount: 3,
+                },
+            ),
+        ]);
+
+        assert!(tick.rejections.is_empty());
+        let seller = vm.world().agent(a).unwrap();
+        assert_eq!((seller.qi(), seller.item(ItemKind::Transistor)), (2, 1));
+        let buyer = vm.world().agent(b).unwrap();
+        assert_eq!((buyer.qi(), buyer.item(ItemKind::Transistor)), (4, 1));
+        let completed = tick
+            .events
+            .iter()
+            .filter(|e| matches!(e, Event::TradeCompleted { .. }))
+            .count();
+        assert_eq!(completed, 1);
+    }
+
+    #[test]
+    fn one_sided_trade_is_declined() {
+        let mut vm = Vm::new();
+        let a = vm.spawn_agent("Seller", 5, Position::origin());
+        let b = vm.spawn_agent("Buyer", 1, Position::origin());
+
+        let tick = vm.step(&[
+            ActionRequest::new(
+                a,
+                Action::Trade {
+                    partner: b,
+                    offer_ore: OreKind::QI,
+                    offer_amount: 3,
+                    want_ore: OreKind::TRANSISTOR,
+                    want_amount: 1,
+                },
+            ),
+            ActionRequest::new(b, Action::Idle),
+        ]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::TradeDeclined { agent_id, partner } if agent_id == a && partner == b
+        ));
+        assert_eq!(vm.world().agent(a).unwrap().qi(), 5);
+    }
+
+    #[test]
+    fn trade_rejected_when_partner_cannot_pay() {
+        let mut vm = Vm::new();
+        let a = vm.spawn_agent("Seller", 5, Position::origin());
+        let b = vm.spawn_agent("Buyer", 1, Position::origin());
+        let offer = |agent, partner, offer_ore, offer_amount, want_ore, want_amount| {
+            ActionRequest::new(
+                agent,
+                Action::Trade {
+                    partner,
+                    offer_ore,
+                    offer_amount,
+                    want_ore,
+                    want_amount,
+                },
+            )
+        };
+
+        let tick = vm.step(&[
+            offer(a, b, OreKind::QI, 3, OreKind::TRANSISTOR, 1),
+            offer(b, a, OreKind::TRANSISTOR, 1, OreKind::QI, 3),
+        ]);
+
+        assert_eq!(tick.rejections.len(), 2);
+        assert!(tick.rejections.iter().all(|r| matches!(
+            r.error,
+            ActionError::InsufficientItem { agent_id, item: ItemKind::Transistor, .. } if agent_id == b
+        )));
+        assert_eq!(vm.world().agent(a).unwrap().qi(), 5);
+        assert_eq!(vm.world().agent(b).unwrap().qi(), 1);
+    }
+
+    #[test]
+    fn attack_damage_scales_with_qi_and_can_kill() {
+        let mut vm = Vm::new();
+        let attacker = vm.spawn_agent("Brute", 20, Position::origin());
+        let target = vm.spawn_agent("Victim", 1, Position::origin()); // shifted to (1,0,0)
+
+        let tick = vm.step(&[ActionRequest::new(attacker, Action::Attack { target })]);
+
+        assert!(tick.rejections.is_empty());
+        let expected = ATTACK_BASE_DAMAGE + 20 / ATTACK_QI_PER_DAMAGE;
+        assert!(tick.events.iter().any(|e| matches!(
+            e,
+            Event::AgentAttacked { damage, remaining_hp, .. }
+                if *damage == expected && *remaining_hp == DEFAULT_AGENT_HP - expected
+        )));
+        assert_eq!(vm.world().agent(attacker).unwrap().qi(), 19);
+
+        vm.world.agents.get_mut(&target).unwrap().hp = 1;
+        let tick = vm.step(&[ActionRequest::new(attacker, Action::Attack { target })]);
+        assert!(tick.events.iter().any(|e| matches!(
+            e,
+            Event::AgentDied { agent_id, reason: DeathReason::Killed } if *agent_id == target
+        )));
+        assert!(!vm.world().agent(target).unwrap().alive);
+    }
+
+    #[test]
+    fn scans_report_recent_attackers_and_lethal_neighbours_as_threats() {
+        let mut vm = Vm::new();
+        let brute = vm.spawn_agent("Brute", 20, Position::origin());
+        let victim = vm.spawn_agent("Victim", 1, Position::origin());
+        let calm = vm.spawn_agent("Calm", 1, Position { x: 0, y: 2, z: 0 });
+
+        vm.step(&[ActionRequest::new(brute, Action::Attack { target: victim })]);
+        let attacked_at = vm.world().tick();
+        let tick = vm.step(&[ActionRequest::new(victim, Action::Scan)]);
+        let threats = tick
+            .events
+            .iter()
+            .find_map(|e| match e {
+                Event::ScanReport { threats, .. } => Some(threats.clone()),
+                _ => None,
+            })
+            .unwrap();
+        // Calm could only deal 1 damage and never attacked.
+        assert_eq!(threats.len(), 1);
+        assert_eq!(threats[0].agent_id, brute);
+        assert_eq!(threats[0].attacked_at, Some(attacked_at));
+        assert_eq!(
+            threats[0].damage,
+            ATTACK_BASE_DAMAGE + 19 / ATTACK_QI_PER_DAMAGE
+        );
+        assert!(!threats[0].lethal);
+        assert!(
+            vm.world()
+                .scan_from(Position::origin(), SCAN_RANGE)
+                .threats
+                .is_empty()
+        );
+
+        // Long after the attack the brute only counts while one hit would kill.
+        for _ in 0..=HOSTILE_MEMORY_TICKS {
+            vm.step(&[]);
+        }
+        vm.world.agents.get_mut(&victim).unwrap().hp = threats[0].damage;
+        let lethal = vm.world().threats_to(victim, SCAN_RANGE);
+        assert!(lethal[0].lethal && lethal[0].attacked_at.is_none());
+        vm.world.agents.get_mut(&victim).unwrap().hp = DEFAULT_AGENT_HP;
+        assert!(vm.world().threats_to(victim, SCAN_RANGE).is_empty());
+        assert!(vm.world().threats_to(calm, SCAN_RANGE).is_empty());
+        assert!(
+            vm.world()
+                .scan_by(victim, SCAN_RANGE)
+                .unwrap()
+                .threats
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn the_last_scan_stays_with_the_agent_and_reaches_its_observations() {
+        use crate::modules::agent::{ActionArg, BrainMemory, plan_with_llm};
+
+        let mut vm = Vm::with_seed(3);
+        let agent_id = vm.spawn_agent("Ada", 5, Position::origin());
+        let node = vm.seed_ore_source(OreKind::QI, Position { x: 2, y: 0, z: 0 }, 9, 0);
+        let observe = |vm: &Vm| {
+            plan_with_llm(
+                vm,
+                agent_id,
+                &[ActionArg::Scan],
+                &mut BrainMemory::default(),
+                None,
+                1,
+            )
+            .observations
+        };
+        assert!(
+            observe(&vm)
+                .iter()
+                .any(|o| o.starts_with("ore nodes unknown"))
+        );
+
+        vm.step(&[ActionRequest::new(agent_id, Action::Scan)]);
+        let scan = vm
+            .world()
+            .agent(agent_id)
+            .unwrap()
+            .last_scan
+            .clone()
+            .unwrap();
+        assert_eq!(scan.tick, vm.world().tick());
+        assert_eq!(scan.qi_sources.len(), 1);
+        assert_eq!(scan.qi_sources[0].id, node);
+        let expected = format!("qi node {} at (2,0,0) had 9/9", node);
+        assert!(
+            observe(&vm).iter().any(|o| o.contains(&expected)),
+            "{:?}",
+            observe(&vm)
+        );
+
+        // It survives a save and load of the world.
+        let json = serde_json::to_string(vm.world().agent(agent_id).unwrap()).unwrap();
+        let restored: Agent = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.last_scan, Some(scan));
+    }
+
+    #[test]
+    fn a_zone_claim_keeps_other_builders_out_until_the_claimant_dies() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", ZONE_CLAIM_COST + 2, Position::origin())
+            .with_agent("Bo", 10, Position { x: 1, y: 0, z: 0 })
+            .build();
+        let (ada, bo) = (world.agent("Ada"), world.agent("Bo"));
+        let zone = Position::origin().zone();
+
+        world
+            .act("Ada", Action::ClaimZone)
+            .assert_accepted()
+            .assert_event(|e| {
+                matches!(e, Event::ZoneClaimed { agent_id, zone: z, cost: ZONE_CLAIM_COST } if *agent_id == ada && *z == zone)
+            });
+        assert_eq!(world.qi("Ada"), 2);
+        assert_eq!(
+            world.vm.world().zone_claim(zone).map(|c| c.owner),
+            Some(ada)
+        );
+
+        for action in [
+            Action::ClaimZone,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        ] {
+            let rejected = world.act("Bo", action);
+            assert_eq!(
+                rejected.assert_rejected(bo),
+                &ActionError::ZoneClaimed {
+                    agent_id: bo,
+                    zone,
+                    owner: ada
+                }
+            );
+        }
+        world
+            .act(
+                "Ada",
+                Action::BuildStructure {
+                    kind: StructureKind::Basic,
+                },
+            )
+            .assert_accepted();
+
+        // The claim is saved with the world.
+        let restored = World::from_persistent(world.vm.world().to_persistent()).unwrap();
+        assert_eq!(restored.territories(), world.vm.world().territories());
+
+        world.vm.kill_agent(ada, DeathReason::Hazard).unwrap();
+        assert!(world.vm.world().zone_claim(zone).is_none());
+        world.act("Bo", Action::ClaimZone).assert_accepted();
+        assert_eq!(world.vm.world().territories().claims.len(), 1);
+        assert_eq!(world.vm.world().zone_claim(zone).map(|c| c.owner), Some(bo));
+    }
+
+    #[test]
+    fn tolls_move_qi_from_visitors_to_the_zone_holder() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let node = Position { x: 1, y: 0, z: 0 };
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", ZONE_CLAIM_COST, Position::origin())
+            .with_agent("Bo", 10, Position { x: 0, y: 1, z: 0 })
+            .with_ore(OreKind::QI, node, 9, 0)
+            .build();
+        let (ada, bo) = (world.agent("Ada"), world.agent("Bo"));
+        let zone = node.zone();
+        let harvest = Action::HarvestOre {
+            ore: OreKind::QI,
+            source_id: world.ore(0),
+        };
+
+        let stranger = world.act(
+            "Bo",
+            Action::SetToll {
+                harvest: 1,
+                build: 1,
+            },
+        );
+        assert_eq!(
+            stranger.assert_rejected(bo),
+            &ActionError::NotZoneOwner { agent_id: bo, zone }
+        );
+        world.act("Ada", Action::ClaimZone).assert_accepted();
+        world
+            .act(
+                "Ada",
+                Action::SetToll {
+                    harvest: 2,
+                    build: 3,
+                },
+            )
+            .assert_accepted();
+        assert_eq!(world.qi("Ada"), 0);
+
+        // Bo pays 1 Qi for the harvest and 2 to Ada, then gains what it harvested.
+        world
+            .act("Bo", harvest.clone())
+            .assert_accepted()
+            .assert_event(|e| matches!(e, Event::TollPaid { agent_id, owner, amount: 2, .. } if *agent_id == bo && *owner == ada))
+            .assert_event(|e| matches!(e, Event::TollCollected { owner, from, amount: 2, .. } if *owner == ada && *from == bo));
+        assert_eq!(world.qi("Ada"), 2);
+        assert_eq!(world.qi("Bo"), 10 - 1 - 2 + HARVEST_PER_ACTION);
+
+        // The holder harvests free, and a build toll opens the zone to other builders.
+        world
+            .act("Ada", harvest)
+            .assert_accepted()
+            .assert_no_event(|e| matches!(e, Event::TollPaid { .. }));
+        let bo_qi = world.qi("Bo");
+        world
+            .act(
+                "Bo",
+                Action::BuildStructure {
+                    kind: StructureKind::Basic,
+                },
+            )
+            .assert_accepted();
+        assert_eq!(world.qi("Bo"), bo_qi - 1 - 3);
+
+        world
+            .act(
+                "Ada",
+                Action::SetToll {
+                    harvest: 0,
+                    build: 0,
+                },
+            )
+            .assert_accepted();
+        let closed = world.act(
+            "Bo",
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        );
+        assert!(matches!(
+            closed.assert_rejected(bo),
+            ActionError::ZoneClaimed { .. }
+        ));
+    }
+
+    #[test]
+    fn market_orders_escrow_fill_at_the_resting_price_and_expire() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 1, Position::origin())
+            .holding(ItemKind::Transistor, 3)
+            .with_agent("Bo", 30, Position { x: 9, y: 0, z: 0 })
+            .build();
+        let (ada, bo) = (world.agent("Ada"), world.agent("Bo"));
+        let order = |side, amount, price| Action::PostOrder {
+            side,
+            ore: OreKind::TRANSISTOR,
+            amount,
+            price,
+        };
+
+        let free = world.act("Bo", order(OrderSide::Buy, 1, 0));
+        assert_eq!(
+            free.assert_rejected(bo),
+            &ActionError::InvalidOrder { agent_id: bo }
+        );
+        world
+            .act("Ada", order(OrderSide::Sell, 2, 5))
+            .assert_accepted();
+        assert_eq!(world.item("Ada", ItemKind::Transistor), 1);
+
+        // The ask rested first, so Bo's higher bid fills at 5 and gets the difference back.
+        world
+            .act("Bo", order(OrderSide::Buy, 3, 8))
+            .assert_accepted()
+            .assert_event(|e| matches!(e, Event::OrderFilled { buyer, seller, amount: 2, price: 5, refund: 6, .. } if *buyer == bo && *seller == ada));
+        assert_eq!(world.qi("Ada"), 1 + 2 * 5);
+        assert_eq!(world.qi("Bo"), 30 - 3 * 8 + 6);
+        assert_eq!(world.item("Bo", ItemKind::Transistor), 2);
+        let market = world.vm.world().market();
+        assert_eq!(market.orders().len(), 1);
+        assert_eq!(market.history().len(), 1);
+        assert_eq!(world.vm.qi_audit().market_qi, 8);
+
+        world.idle(ORDER_TTL_TICKS).unwrap().assert_event(
+            |e| matches!(e, Event::OrderExpired { agent_id, amount: 8, .. } if *agent_id == bo),
+        );
+        assert_eq!(world.qi("Bo"), 30 - 2 * 5);
+        assert_eq!(world.vm.qi_audit().market_qi, 0);
+    }
+
+    #[test]
+    fn crafted_circuits_can_be_required_to_build() {
+        use crate::modules::store::StoreConfig;
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let config: StoreConfig = toml::from_str(
+            r#"
+            [recipes.relay]
+            inputs = { circuit = 1, stone = 2 }
+            output = "transistor"
+            amount = 3
+
+            [build_costs]
+            programmable = { circuit = 1, qi = 1 }
+            "#,
+        )
+        .unwrap();
+        let mut recipes = RecipeBook::default();
+        recipes.extend(config.recipes, config.build_costs).unwrap();
+        assert_eq!(
+            recipes.build_cost(StructureKind::Programmable),
+            vec![(ItemKind::Qi, 1), (ItemKind::Circuit, 1)]
+        );
+        assert!(recipes.get("relay").is_some() && recipes.get("circuit").is_some());
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 5, Position::origin())
+            .holding(ItemKind::Transistor, 3)
+            .build();
+        world.vm.set_recipes(recipes);
+        let ada = world.agent("Ada");
+        let held = world.vm.qi_audit().held();
+        let programmable = Action::BuildStructure {
+            kind: StructureKind::Programmable,
+        };
+
+        let early = world.act("Ada", programmable.clone());
+        assert!(matches!(
+            early.assert_rejected(ada),
+            ActionError::InsufficientItem {
+                item: ItemKind::Circuit,
+                ..
+            }
+        ));
+        let unknown = world.act(
+            "Ada",
+            Action::Craft {
+                recipe: "engine".into(),
+            },
+        );
+        assert!(matches!(
+            unknown.assert_rejected(ada),
+            ActionError::UnknownRecipe { .. }
+        ));
+        world
+            .act(
+                "Ada",
+                Action::Craft {
+                    recipe: "circuit".into(),
+                },
+            )
+            .assert_accepted()
+            .assert_event(|e| {
+                matches!(
+                    e,
+                    Event::ItemCrafted {
+                        item: ItemKind::Circuit,
+                        amount: 1,
+                        ..
+                    }
+                )
+            });
+        assert_eq!(world.item("Ada", ItemKind::Transistor), 1);
+        assert_eq!(world.item("Ada", ItemKind::Circuit), 1);
+        assert_eq!(world.qi("Ada"), 2);
+        assert_eq!(world.vm.qi_audit().held(), held);
+
+        world.act("Ada", programmable).assert_accepted();
+        assert_eq!(world.item("Ada", ItemKind::Circuit), 0);
+        assert_eq!(world.item("Ada", ItemKind::Transistor), 1);
+        assert_eq!(world.qi("Ada"), 1);
+    }
+
+    #[test]
+    fn structures_decay_collapse_and_can_be_repaired() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 10, Position::origin())
+            .build();
+        let ada = world.agent("Ada");
+        world
+            .act(
+                "Ada",
+                Action::BuildStructure {
+                    kind: StructureKind::Basic,
+                },
+            )
+            .assert_accepted();
+        let structure_id = world.vm.world.structures[0].id;
+        let untouched = world.act("Ada", Action::Repair { structure_id: 0 });
+        assert!(matches!(
+            untouched.assert_rejected(ada),
+            ActionError::NothingToRepair { .. }
+        ));
+
+        // Wear is only reported when it reaches another quarter of the maximum.
+        let worn: Vec<Event> = (0..STRUCTURE_DECAY_PERIOD)
+            .flat_map(|_| world.vm.step(&[]).events)
+            .collect();
+        assert!(
+            !worn
+                .iter()
+                .any(|e| matches!(e, Event::StructureDecayed { .. }))
+        );
+        assert_eq!(
+            world.vm.world.structures[0].durability,
+            STRUCTURE_DURABILITY - 1
+        );
+        world.vm.world.structures[0].durability = STRUCTURE_DECAY_NOTICE * 3 + 1;
+        let decayed: Vec<Event> = (0..STRUCTURE_DECAY_PERIOD)
+            .flat_map(|_| world.vm.step(&[]).events)
+            .collect();
+        assert!(decayed.contains(&Event::StructureDecayed {
+            structure_id,
+            durability: STRUCTURE_DECAY_NOTICE * 3,
+        }));
+        world
+            .act("Ada", Action::Repair { structure_id })
+            .assert_accepted()
+            .assert_event(|e| {
+                *e == Event::StructureRepaired {
+                    agent_id: ada,
+                    structure_id,
+                    durability: STRUCTURE_DURABILITY,
+                }
+            });
+
+        world.vm.world.structures[0].durability = 1;
+        world.vm.world.structures[0]
+            .storage
+            .gain_item(ItemKind::Qi, 3);
+        let held = world.vm.qi_audit().held();
+        let events: Vec<Event> = (0..STRUCTURE_DECAY_PERIOD)
+            .flat_map(|_| world.vm.step(&[]).events)
+            .collect();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::StructureCollapsed { structure_id: id, owner, .. }
+                if *id == structure_id && *owner == ada
+        )));
+        assert!(world.vm.world.structures.is_empty());
+        assert_eq!(world.vm.qi_audit().held(), held);
+        let gone = world.act("Ada", Action::Repair { structure_id });
+        assert!(matches!(
+            gone.assert_rejected(ada),
+            ActionError::NothingToRepair { .. }
+        ));
+    }
+
+    #[test]
+    fn owners_demolish_structures_for_part_of_the_cost() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut recipes = RecipeBook::default();
+        recipes
+            .extend(
+                BTreeMap::new(),
+                BTreeMap::from([(
+                    StructureKind::Basic,
+                    BTreeMap::from([(ItemKind::Qi, 4), (ItemKind::Transistor, 3)]),
+                )]),
+            )
+            .unwrap();
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 10, Position::origin())
+            .holding(ItemKind::Transistor, 3)
+            .with_agent("Bo", 5, Position { x: 1, y: 0, z: 0 })
+            .build();
+        world.vm.set_recipes(recipes);
+        let (ada, bo) = (world.agent("Ada"), world.agent("Bo"));
+        world
+            .act(
+                "Ada",
+                Action::BuildStructure {
+                    kind: StructureKind::Basic,
+                },
+            )
+            .assert_accepted();
+        let structure_id = world.vm.world.structures[0].id;
+        world.vm.world.structures[0]
+            .storage
+            .gain_item(ItemKind::Qi, 3);
+
+        let stranger = world.act("Bo", Action::DemolishStructure { structure_id });
+        assert_eq!(
+            stranger.assert_rejected(bo),
+            &ActionError::NotStructureOwner {
+                agent_id: bo,
+                structure_id,
+                owner: ada,
+            }
+        );
+        world.vm.world.agents.get_mut(&ada).unwrap().position = Position { x: 3, y: 0, z: 0 };
+        let far = world.act("Ada", Action::DemolishStructure { structure_id });
+        assert_eq!(
+            far.assert_rejected(ada),
+            &ActionError::StructureNotFound {
+                agent_id: ada,
+                structure_id,
+            }
+        );
+        assert_eq!(world.vm.world.structures.len(), 1);
+        world.vm.world.agents.get_mut(&ada).unwrap().position = Position::origin();
+
+        let held = world.vm.qi_audit().held();
+        world
+            .act("Ada", Action::DemolishStructure { structure_id })
+            .assert_accepted()
+            .assert_event(|e| {
+                *e == Event::StructureDemolished {
+                    agent_id: ada,
+                    structure_id,
+                    kind: StructureKind::Basic,
+                    position: Position::origin(),
+                    refund: vec![(ItemKind::Qi, 5), (ItemKind::Transistor, 1)],
+                }
+            });
+        assert!(world.vm.world.structures.is_empty());
+        assert_eq!(world.qi("Ada"), 6 + 5);
+        assert_eq!(world.item("Ada", ItemKind::Transistor), 1);
+        assert_eq!(world.vm.qi_audit().held(), held);
+        let again = world.act("Ada", Action::DemolishStructure { structure_id });
+        assert!(matches!(
+            again.assert_rejected(ada),
+            ActionError::StructureNotFound { .. }
+        ));
+    }
+
+    #[test]
+    fn agents_give_qi_to_neighbours() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let far = Position {
+            x: ZONE_SIZE * 2,
+            y: 0,
+            z: 0,
+        };
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 10, Position::origin())
+            .with_agent("Bo", 1, Position { x: 1, y: 0, z: 0 })
+            .with_agent("Cy", 1, far)
+            .build();
+        let (ada, bo, cy) = (world.agent("Ada"), world.agent("Bo"), world.agent("Cy"));
+        let held = world.vm.qi_audit().held();
+
+        world
+            .act("Ada", Action::GiveQi { to: bo, amount: 4 })
+            .assert_accepted()
+            .assert_event(|e| {
+                *e == Event::QiGiven {
+                    agent_id: ada,
+                    to: bo,
+                    amount: 4,
+                }
+            })
+            .assert_event(|e| {
+                *e == Event::QiReceived {
+                    agent_id: bo,
+                    from: ada,
+                    amount: 4,
+                }
+            });
+        assert_eq!(world.qi("Ada"), 6);
+        assert_eq!(world.qi("Bo"), 5);
+        assert_eq!(world.vm.qi_audit().held(), held);
+
+        let distant = world.act("Ada", Action::GiveQi { to: cy, amount: 1 });
+        assert_eq!(
+            distant.assert_rejected(ada),
+            &ActionError::RecipientOutOfReach {
+                agent_id: ada,
+                to: cy
+            }
+        );
+        let broke = world.act("Bo", Action::GiveQi { to: ada, amount: 9 });
+        assert!(matches!(
+            broke.assert_rejected(bo),
+            ActionError::InsufficientQi { .. }
+        ));
+        let nothing = world.act("Ada", Action::GiveQi { to: bo, amount: 0 });
+        assert!(matches!(
+            nothing.assert_rejected(ada),
+            ActionError::InvalidGift { .. }
+        ));
+    }
+
+    #[test]
+    fn agents_out_of_qi_are_warned_then_starve() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 0, Position::origin())
+            .with_agent("Bo", 0, Position { x: 1, y: 0, z: 0 })
+            .with_agent("Cy", 5, Position { x: 2, y: 0, z: 0 })
+            .build();
+        world.vm.set_starvation(Some(Starvation {
+            after_ticks: 3,
+            warn_ticks: 2,
+        }));
+        let (ada, bo) = (world.agent("Ada"), world.agent("Bo"));
+
+        world
+            .idle(1)
+            .unwrap()
+            .assert_event(|e| {
+                *e == Event::AgentStarving {
+                    agent_id: ada,
+                    ticks_left: 2,
+                }
+            })
+            .assert_no_event(|e| matches!(e, Event::AgentDied { .. }));
+        world
+            .act("Cy", Action::GiveQi { to: bo, amount: 1 })
+            .assert_accepted()
+            .assert_event(|e| {
+                *e == Event::AgentStarving {
+                    agent_id: ada,
+                    ticks_left: 1,
+                }
+            })
+            .assert_no_event(
+                |e| matches!(e, Event::AgentStarving { agent_id, .. } if *agent_id == bo),
+            );
+        world.idle(1).unwrap().assert_event(|e| {
+            *e == Event::AgentDied {
+                agent_id: ada,
+                reason: DeathReason::Starved,
+            }
+        });
+        assert!(!world.is_alive("Ada"));
+        assert!(world.is_alive("Bo"));
+    }
+
+    #[test]
+    fn dead_agents_leave_harvestable_remains() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 6, Position::origin())
+            .holding(ItemKind::Transistor, 2)
+            .holding(ItemKind::Circuit, 1)
+            .with_agent("Bo", 5, Position { x: 1, y: 0, z: 0 })
+            .build();
+        let ada = world.agent("Ada");
+        let held = world.vm.qi_audit().held();
+        world.vm.kill_agent(ada, DeathReason::Hazard).unwrap();
+
+        let remains: Vec<(OreKind, Qi, u64)> = world
+            .vm
+            .world
+            .qi_sources
+            .iter()
+            .filter(|s| s.remains_of == Some(ada))
+            .map(|s| (s.ore, s.current, s.id))
+            .collect();
+        assert_eq!(remains.len(), 2);
+        assert_eq!((remains[0].0, remains[0].1), (OreKind::QI, 6));
+        assert_eq!((remains[1].0, remains[1].1), (OreKind::TRANSISTOR, 2));
+        assert_eq!(world.qi("Ada"), 0);
+        assert_eq!(world.item("Ada", ItemKind::Circuit), 1);
+        assert_eq!(world.vm.qi_audit().held(), held);
+
+        let qi_node = remains[0].2;
+        world
+            .act(
+                "Bo",
+                Action::HarvestOre {
+                    ore: OreKind::QI,
+                    source_id: qi_node,
+                },
+            )
+            .assert_accepted();
+        world
+            .act(
+                "Bo",
+                Action::HarvestOre {
+                    ore: OreKind::QI,
+                    source_id: qi_node,
+                },
+            )
+            .assert_accepted()
+            .assert_event(
+                |e| matches!(e, Event::OreNodeRemoved { source_id, .. } if *source_id == qi_node),
+            );
+        assert!(world.vm.world.qi_sources.iter().all(|s| s.id != qi_node));
+        assert_eq!(world.vm.qi_audit().held(), held);
+    }
+
+    #[test]
+    fn registered_ores_harvest_at_their_rate_and_save_by_label() {
+        use crate::modules::ore::{OreDef, register_ore};
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let iron = register_ore(OreDef {
+            label: "Iron".into(),
+            harvest_rate: 2,
+            infusion_cost: 20,
+        })
+        .unwrap();
+        assert_eq!("iron".parse::<OreKind>(), Ok(iron));
+        assert_eq!(iron.infusion_cost(), 20);
+        assert!(
+            register_ore(OreDef {
+                label: "stone".into(),
+                harvest_rate: 1,
+                infusion_cost: 1,
+            })
+            .is_err()
+        );
+        assert!("mithril".parse::<OreKind>().is_err());
+
+        let node = Position { x: 1, y: 0, z: 0 };
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 5, Position::origin())
+            .with_ore(iron, node, 5, 0)
+            .build();
+        world
+            .act(
+                "Ada",
+                Action::HarvestOre {
+                    ore: iron,
+                    source_id: world.ore(0),
+                },
+            )
+            .assert_accepted()
+            .assert_event(
+                |e| matches!(e, Event::OreNodeHarvested { ore, amount: 2, .. } if *ore == iron),
+            );
+        assert_eq!(world.item("Ada", ItemKind::Ore(iron)), 2);
+        assert_eq!(ItemKind::from(iron).to_string(), "iron");
+
+        let saved = serde_json::to_string(&world.vm.world().to_persistent()).unwrap();
+        assert!(saved.contains("\"iron\""));
+        let restored = World::from_persistent(serde_json::from_str(&saved).unwrap()).unwrap();
+        let ada = restored.agent(world.agent("Ada")).unwrap();
+        assert_eq!(ada.inventory.get(ItemKind::Ore(iron)), 2);
+        assert!(
+            restored
+                .snapshot()
+                .meta
+                .rules
+                .ores
+                .iter()
+                .any(|def| def.label == "iron" && def.harvest_rate == 2)
+        );
+    }
+
+    #[test]
+    fn said_messages_reach_listeners_in_scan_range_and_their_observations() {
+        use crate::modules::agent::{ActionArg, BrainMemory, plan_with_llm};
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 5, Position::origin())
+            .with_agent("Bo", 5, Position { x: 2, y: 0, z: 0 })
+            .with_agent(
+                "Cy",
+                5,
+                Position {
+                    x: SCAN_RANGE + 1,
+                    y: 0,
+                    z: 0,
+                },
+            )
+            .build();
+        let (ada, bo, cy) = (world.agent("Ada"), world.agent("Bo"), world.agent("Cy"));
+        let say = |to, message: &str| Action::Say {
+            to,
+            message: message.to_string(),
+        };
+
+        let broadcast = world.act("Ada", say(0, "ore at the ridge"));
+        broadcast.assert_accepted();
+        let delivered: Vec<AgentId> = broadcast
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                Event::MessageDelivered { from, to, .. } if *from == ada => Some(*to),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(delivered, vec![bo]);
+        assert_eq!(world.qi("Ada"), 5);
+        let inbox = &world.vm.agent(bo).unwrap().inbox;
+        assert_eq!(inbox.len(), 1);
+        assert_eq!(inbox[0].from, ada);
+        assert_eq!(inbox[0].text, "ore at the ridge");
+
+        let observations = plan_with_llm(
+            &world.vm,
+            bo,
+            &[ActionArg::Scan],
+            &mut BrainMemory::default(),
+            None,
+            1,
+        )
+        .observations;
+        assert!(
+            observations
+                .iter()
+                .any(|o| o.contains(&format!("agent {} said \"ore at the ridge\"", ada))),
+            "{:?}",
+            observations
+        );
+
+        let far = world.act("Ada", say(cy, "hello"));
+        assert!(matches!(
+            far.assert_rejected(ada),
+            ActionError::NoListener { to, .. } if *to == cy
+        ));
+        let long = "x".repeat(MAX_MESSAGE_LEN + 1);
+        for bad in [say(bo, ""), say(ada, "me"), say(bo, &long)] {
+            let rejected = world.act("Ada", bad);
+            assert!(matches!(
+                rejected.assert_rejected(ada),
+                ActionError::InvalidMessage { .. }
+            ));
+        }
+
+        for n in 0..INBOX_LIMIT + 2 {
+            world.act("Ada", say(bo, &n.to_string())).assert_accepted();
+        }
+        let inbox = &world.vm.agent(bo).unwrap().inbox;
+        assert_eq!(inbox.len(), INBOX_LIMIT);
+        assert_eq!(inbox.back().unwrap().text, (INBOX_LIMIT + 1).to_string());
+    }
+
+    #[test]
+    fn attack_out_of_range_is_rejected() {
+        let mut vm = Vm::new();
+        let attacker = vm.spawn_agent("Brute", 5, Position::origin());
+        let target = vm.spawn_agent("Far", 5, Position { x: 5, y: 0, z: 0 });
+
+        let tick = vm.step(&[ActionRequest::new(attacker, Action::Attack { target })]);
+
+        assert_eq!(tick.rejections.len(), 1);
+        assert!(matches!(
+            tick.rejections[0].error,
+            ActionError::TargetOutOfRange { .. }
+        ));
+        assert_eq!(vm.world().agent(attacker).unwrap().qi(), 5);
+        assert_eq!(vm.world().agent(target).unwrap().hp, DEFAULT_AGENT_HP);
+    }
+
+    #[test]
+    fn snapshot_hash_tracks_world_state() {
+        let build = || {
+            let mut vm = Vm::with_seed(7);
+            vm.spawn_agent("Ada", 5, Position::origin());
+            vm
+        };
+        let mut vm = build();
+        let first = vm.world().snapshot();
+        assert_eq!(
+            first.meta.world_hash,
+            build().world().snapshot().meta.world_hash
+        );
+        assert_eq!(first.meta.world_hash, first.content_hash());
+        assert_eq!(first.meta.seed, Some(7));
+        assert_eq!(first.meta.season, "spring");
+        assert_eq!(first.meta.rules.max_move_radius, MAX_MOVE_RADIUS);
+
+        vm.step(&[]);
+        let second = vm.world().snapshot();
+        assert_ne!(first.meta.world_hash, second.meta.world_hash);
+    }
+
+    #[test]
+    fn persistent_world_round_trips() {
+        let mut vm = Vm::with_seed(3);
+        let ada = vm.spawn_agent("Ada", 20, Position::origin());
+        vm.seed_qi_source(Position { x: 1, y: 0, z: 0 }, 10, 1);
+        vm.step(&[ActionRequest::new(
+            ada,
+            Action::BuildStructure {
+                kind: StructureKind::Basic,
+            },
+        )]);
+        vm.step(&[ActionRequest::new(
+            ada,
             Action::Move {
-                dx: MAX_MOVE_RADIUS,
-                dy: 0,
+                dx: 0,
+                dy: 1,
                 dz: 0,
             },
         )]);
 
-        assert!(tick.rejections.is_empty());
-        let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 2); // 3 start -1 move
+        let json = serde_json::to_string(&vm.world().to_persistent()).unwrap();
+        let saved: PersistentWorld = serde_json::from_str(&json).unwrap();
+        let restored = World::from_persistent(saved).unwrap();
+
+        assert_eq!(
+            restored.snapshot().meta.world_hash,
+            vm.world().snapshot().meta.world_hash
+        );
+        assert_eq!(restored.seed(), 3);
+        assert_eq!(restored.structures().len(), 1);
+        assert_eq!(
+            restored
+                .occupied
+                .get(&restored.agent(ada).unwrap().position),
+            Some(&ada)
+        );
+
+        let mut resumed = Vm::with_world(restored);
+        let bob = resumed.spawn_agent("Bob", 5, Position::origin());
+        assert!(bob > ada);
+        assert_eq!(resumed.step(&[]).tick, 3);
     }
 
     #[test]
-    fn move_beyond_limit_is_rejected() {
-        let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Walker", 4, Position::origin());
+    fn events_serialize_tagged_by_variant() {
+        let event = Event::AgentMoved {
+            agent_id: 4,
+            from: Position::origin(),
+            to: Position { x: 1, y: 0, z: 0 },
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["AgentMoved"]["agent_id"], 4);
+        assert_eq!(value["AgentMoved"]["to"]["x"], 1);
+    }
 
-        let tick = vm.step(&[ActionRequest::new(
-            agent_id,
-            Action::Move {
-                dx: MAX_MOVE_RADIUS + 1,
-                dy: 0,
-                dz: 0,
-            },
-        )]);
+    #[test]
+    fn reproduce_with_partner_zero_matches_eligible_agent() {
+        let mut vm = Vm::new();
+        let ada = vm.spawn_agent("Ada", 5, Position::origin());
+        let bob = vm.spawn_agent("Bob", 5, Position { x: 1, y: 0, z: 0 });
+        vm.spawn_agent("Far", 5, Position { x: 40, y: 0, z: 0 });
+        assert_eq!(vm.world().eligible_partners(ada), vec![bob]);
+
+        let anyone = |id| ActionRequest::new(id, Action::Reproduce { partner: 0 });
+        let tick = vm.step(&[anyone(ada), anyone(bob)]);
+
+        assert!(tick.rejections.is_empty(), "{:?}", tick.rejections);
+        assert!(tick.events.contains(&Event::PartnerResolved {
+            agent_id: ada,
+            partner: bob
+        }));
+        assert!(
+            tick.events
+                .iter()
+                .any(|e| matches!(e, Event::AgentReproduced { .. }))
+        );
+        assert!(!vm.world().eligible_partners(ada).contains(&bob));
 
-        assert_eq!(tick.rejections.len(), 1);
+        let again = vm.step(&[
+            ActionRequest::new(ada, Action::Reproduce { partner: bob }),
+            ActionRequest::new(bob, Action::Reproduce { partner: ada }),
+        ]);
         assert!(matches!(
-            tick.rejections[0].error,
-            ActionError::MoveOutOfRange { .. }
+            again.rejections[0].error,
+            ActionError::ReproductionCooldown { ready_at, .. } if ready_at == 1 + REPRODUCE_COOLDOWN
         ));
-        let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 4); // no charge on rejection
     }
 
     #[test]
-    fn move_into_occupied_is_rejected() {
+    fn bundles_apply_together_or_not_at_all() {
         let mut vm = Vm::new();
-        let a1 = vm.spawn_agent("A1", 3, Position::origin());
-        let _a2 = vm.spawn_agent("A2", 3, Position::origin()); // gets shifted to (1,0,0)
+        let agent_id = vm.spawn_agent("Runner", 3, Position::origin());
+        vm.seed_qi_source(Position { x: 3, y: 0, z: 0 }, 5, 0);
+        let step_east = Action::Move {
+            dx: 2,
+            dy: 0,
+            dz: 0,
+        };
+        let harvest = Action::HarvestOre {
+            ore: OreKind::QI,
+            source_id: 0,
+        };
 
-        let tick = vm.step(&[ActionRequest::new(
-            a1,
-            Action::Move {
-                dx: 1,
-                dy: 0,
-                dz: 0,
-            },
+        // Harvesting first is out of range, so the move after it is undone too.
+        let tick = vm.step(&[ActionRequest::bundle(
+            agent_id,
+            [harvest.clone(), step_east.clone()],
         )]);
-
         assert_eq!(tick.rejections.len(), 1);
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.position, Position::origin());
+        assert_eq!(agent.qi(), 3);
+
+        let tick = vm.step(&[ActionRequest::bundle(agent_id, [step_east, harvest])]);
+        assert!(tick.rejections.is_empty(), "{:?}", tick.rejections);
+        let agent = vm.world().agent(agent_id).unwrap();
+        assert_eq!(agent.position, Position { x: 2, y: 0, z: 0 });
+        assert_eq!(agent.qi(), 3 - 2 + HARVEST_PER_ACTION);
+        assert_eq!(agent.age, 1);
+
+        let tick = vm.step(&[ActionRequest::bundle(
+            agent_id,
+            [
+                Action::Scan,
+                Action::MoveTo {
+                    target: Position::origin(),
+                },
+            ],
+        )]);
         assert!(matches!(
             tick.rejections[0].error,
-            ActionError::PositionOccupied { target, .. } if target == Position { x:1, y:0, z:0 }
+            ActionError::InvalidBundle { .. }
         ));
     }
 
     #[test]
-    fn dead_agents_cannot_act() {
-        let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Orchid", 1, Position::origin());
+    fn failed_bundles_undo_what_they_built_and_drained() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let mut world = WorldFixture::new()
+            .with_agent("Ada", 10, Position::origin())
+            .with_ore(OreKind::QI, Position { x: 1, y: 0, z: 0 }, 3, 0)
+            .build();
+        let ada = world.agent("Ada");
+        world.vm.world.qi_sources[0].remains_of = Some(99);
+        let before = serde_json::to_value(world.vm.world.to_persistent()).unwrap();
+
+        // The build and the harvest that drains (and removes) the remains apply before the
+        // repair fails.
+        let request = ActionRequest::bundle(
+            ada,
+            [
+                Action::BuildStructure {
+                    kind: StructureKind::Basic,
+                },
+                Action::HarvestOre {
+                    ore: OreKind::QI,
+                    source_id: 0,
+                },
+                Action::Repair { structure_id: 99 },
+            ],
+        );
+        let result = world.vm.apply_bundle(
+            &request,
+            1,
+            &HashSet::new(),
+            &mut TradeBook::default(),
+            &HashMap::new(),
+        );
+        assert!(matches!(result, Err(ActionError::NothingToRepair { .. })));
+        assert_eq!(
+            serde_json::to_value(world.vm.world.to_persistent()).unwrap(),
+            before
+        );
+        assert_eq!(
+            world
+                .vm
+                .world
+                .source_index
+                .within(&world.vm.world.qi_sources, Position::origin(), 1, |s| {
+                    s.position
+                })
+                .count(),
+            1
+        );
+        world
+            .act(
+                "Ada",
+                Action::HarvestOre {
+                    ore: OreKind::QI,
+                    source_id: 0,
+                },
+            )
+            .assert_accepted();
+    }
 
-        vm.kill_agent(agent_id, DeathReason::Hazard).unwrap();
+    #[test]
+    fn replay_log_reproduces_recorded_ticks() {
+        use crate::modules::replay::{ReplayRecord, Replayer};
+
+        let mut vm = Vm::with_seed(11);
+        let ada = vm.spawn_agent("Ada", 6, Position::origin());
+        vm.seed_qi_source(Position { x: 2, y: 0, z: 0 }, 9, 1);
+        let mut records = vec![ReplayRecord::Start {
+            world: Box::new(vm.world().to_persistent()),
+            firmware: None,
+            structure_effects: Vec::new(),
+            recipes: RecipeBook::default(),
+        }];
+        let turns = [
+            vec![ActionRequest::new(
+                ada,
+                Action::Move {
+                    dx: 1,
+                    dy: 0,
+                    dz: 0,
+                },
+            )],
+            vec![ActionRequest::bundle(
+                ada,
+                [
+                    Action::HarvestOre {
+                        ore: OreKind::QI,
+                        source_id: 0,
+                    },
+                    Action::Scan,
+                ],
+            )],
+            vec![],
+        ];
+        for requests in turns {
+            let result = vm.step(&requests);
+            records.push(ReplayRecord::Tick {
+                tick: result.tick,
+                requests,
+                world_hash: vm.snapshot().meta.world_hash,
+            });
+        }
 
-        let tick = vm.step(&[ActionRequest::new(
-            agent_id,
-            Action::Move {
-                dx: 1,
-                dy: 0,
-                dz: 0,
-            },
-        )]);
+        let json: Vec<String> = records
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap())
+            .collect();
+        let records = json
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let mut replayer = Replayer::new(records).unwrap();
+        let mut ticks = 0;
+        while let Some((_, mismatch)) = replayer.step() {
+            assert_eq!(mismatch, None);
+            ticks += 1;
+        }
+        assert_eq!(ticks, 3);
+        assert_eq!(replayer.vm().world().tick(), vm.world().tick());
+    }
+
+    #[test]
+    fn stakes_decide_contested_cells_and_ore() {
+        let mut vm = Vm::new();
+        let ada = vm.spawn_agent("Ada", 5, Position::origin());
+        let bo = vm.spawn_agent("Bo", 5, Position { x: 2, y: 0, z: 0 });
+        let cell = Position { x: 1, y: 0, z: 0 };
+
+        // Bo is listed second but outbids Ada for the cell between them.
+        let tick = vm.step(&[
+            ActionRequest::new(
+                ada,
+                Action::Move {
+                    dx: 1,
+                    dy: 0,
+                    dz: 0,
+                },
+            ),
+            ActionRequest::new(
+                bo,
+                Action::Move {
+                    dx: -1,
+                    dy: 0,
+                    dz: 0,
+                },
+            )
+            .with_stake(2),
+        ]);
+        assert_eq!(vm.world().agent(bo).unwrap().position, cell);
+        assert_eq!(vm.world().agent(bo).unwrap().qi(), 5 - 1 - 2);
+        assert_eq!(vm.world().agent(ada).unwrap().qi(), 5);
+        assert_eq!(tick.rejections.len(), 1);
+        let error = &tick.rejections[0].error;
+        assert!(matches!(
+            error,
+            ActionError::Outranked {
+                agent_id,
+                contested: Contested::Cell { position },
+                priority: Priority { stake: 0, initiative: 0 },
+                winner,
+                winner_priority: Priority { stake: 2, initiative: 1 },
+                ..
+            } if *agent_id == ada && *position == cell && *winner == bo
+        ));
+        assert!(
+            error
+                .to_string()
+                .contains("stake 2, initiative 1 beats stake 0, initiative 0")
+        );
 
+        // Equal stakes fall back to batch order for the last harvestable units.
+        let source_id = vm.seed_qi_source(Position { x: 0, y: 1, z: 0 }, HARVEST_PER_ACTION, 0);
+        let harvest = Action::HarvestOre {
+            ore: OreKind::QI,
+            source_id,
+        };
+        let tick = vm.step(&[
+            ActionRequest::new(bo, harvest.clone()).with_stake(1),
+            ActionRequest::new(ada, harvest).with_stake(1),
+        ]);
         assert_eq!(tick.rejections.len(), 1);
         assert!(matches!(
             tick.rejections[0].error,
-            ActionError::AgentDead(id) if id == agent_id
+            ActionError::Outranked {
+                agent_id,
+                contested: Contested::OreNode { .. },
+                winner,
+                ..
+            } if agent_id == ada && winner == bo
         ));
+        assert_eq!(vm.world().agent(ada).unwrap().qi(), 5);
     }
 
     #[test]
-    fn scanning_is_free() {
+    fn thinking_cost_is_charged_for_brain_planned_requests() {
         let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Scout", 3, Position::origin());
-
-        let tick = vm.step(&[ActionRequest::new(agent_id, Action::Scan)]);
-
-        assert!(tick.rejections.is_empty());
-        let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 3); // scan is free
+        vm.set_thinking_cost(2);
+        let ada = vm.spawn_agent("Ada", 3, Position::origin());
+        let bo = vm.spawn_agent("Bo", 1, Position { x: 4, y: 0, z: 0 });
+        assert!(vm.world().can_afford_thinking(ada));
+        assert!(!vm.world().can_afford_thinking(bo));
+
+        let tick = vm.step(&[
+            ActionRequest::new(ada, Action::Scan).thought(),
+            ActionRequest::new(bo, Action::Scan),
+        ]);
         assert!(tick.events.iter().any(|e| matches!(
             e,
-            Event::ActionObserved { agent_id: id, action: "scan" } if *id == agent_id
+            Event::QiSpent {
+                agent_id,
+                amount: 2,
+                action: "thinking",
+            } if *agent_id == ada
         )));
+        assert_eq!(vm.world().agent(ada).unwrap().qi(), 1);
+        assert_eq!(vm.world().agent(bo).unwrap().qi(), 1);
+        assert_eq!(vm.snapshot().meta.rules.thinking_cost, 2);
     }
 
     #[test]
-    fn build_structure_costs_qi_and_records() {
+    fn idle_agents_hibernate_and_wake_when_someone_comes_near() {
         let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Builder", 3, Position::origin());
+        vm.set_hibernation(Some(Hibernation {
+            after_ticks: 2,
+            radius: 8,
+            age_every: 0,
+        }));
+        let ada = vm.spawn_agent("Ada", 10, Position::origin());
+        let bo = vm.spawn_agent("Bo", 10, Position { x: 1, y: 0, z: 0 });
+        let cy = vm.spawn_agent("Cy", 10, Position { x: 12, y: 0, z: 0 });
+        let idle = [
+            ActionRequest::new(ada, Action::Idle),
+            ActionRequest::new(bo, Action::Idle),
+        ];
+
+        assert!(
+            !vm.step(&idle)
+                .events
+                .iter()
+                .any(|e| matches!(e, Event::AgentHibernated { .. }))
+        );
+        let tick = vm.step(&idle);
+        let slept: Vec<AgentId> = tick
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                Event::AgentHibernated { agent_id } => Some(*agent_id),
+                _ => None,
+            })
+            .collect();
+        // Ada and Bo keep each other awake.
+        assert_eq!(slept, vec![cy]);
+        assert!(vm.world().is_dormant(cy));
+
+        vm.step(&idle);
+        vm.step(&idle);
+        assert_eq!(vm.world().agent(cy).unwrap().age, 0);
 
         let tick = vm.step(&[ActionRequest::new(
-            agent_id,
-            Action::BuildStructure {
-                kind: StructureKind::Basic,
+            bo,
+            Action::Move {
+                dx: 3,
+                dy: 0,
+                dz: 0,
             },
         )]);
-
-        assert!(tick.rejections.is_empty());
-        let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 2); // 3 start -1 build
-        assert_eq!(vm.world.structures.len(), 1);
-        assert!(
-            tick.events.iter().any(
-                |e| matches!(e, Event::StructureBuilt { agent_id: id, .. } if *id == agent_id)
-            )
-        );
         assert!(tick.events.iter().any(|e| matches!(
             e,
-            Event::QiSpent {
-                action: "build_structure",
-                amount: 1,
-                ..
-            }
+            Event::AgentWoke { agent_id, by: Some(by) } if *agent_id == cy && *by == bo
         )));
+        assert!(!vm.world().is_dormant(cy));
+
+        // The rules survive a restart.
+        let restored = World::from_persistent(vm.world().to_persistent()).unwrap();
+        assert_eq!(restored.hibernation(), vm.world().hibernation());
     }
 
     #[test]
-    fn cannot_build_on_existing_structure() {
-        let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Builder", 5, Position::origin());
+    fn far_zones_are_evicted_and_restored_intact() {
+        let mut vm = Vm::with_seed(3);
+        vm.spawn_agent("Ada", 5, Position::origin());
+        let far = Position { x: 200, y: 0, z: 0 };
+        let source = vm.seed_qi_source(far, 9, 1);
+        vm.world_mut()
+            .terrain_mut()
+            .set_block(far.offset(1, 0, 0), Block::Stone);
+        let before = vm.world().to_persistent();
+        let held = vm.qi_audit().held();
+
+        let (load, evict) = vm.world().paging_plan(1);
+        assert!(load.is_empty());
+        assert_eq!(evict, vec![far.zone()]);
+        let cold = vm.world_mut().evict_zone(far.zone()).unwrap();
+        assert_eq!(cold.qi(), 9);
+        assert!(vm.world().is_cold(far.zone()));
+        assert!(vm.world().qi_sources().is_empty());
+        assert!(!vm.world().terrain().is_solid(far.offset(1, 0, 0)));
+        // Evicted Qi still counts, so nodes elsewhere can't mint it again.
+        assert_eq!(vm.qi_audit().held(), held);
+
+        // An agent arriving nearby makes the zone wanted again.
+        vm.spawn_agent("Bo", 5, far.offset(-20, 0, 0));
+        let (load, _) = vm.world().paging_plan(1);
+        assert_eq!(load, vec![far.zone()]);
+        vm.world_mut().restore_zone(cold).unwrap();
+        assert!(!vm.world().is_cold(far.zone()));
+        assert_eq!(vm.world().qi_sources()[0].id, source);
+        assert!(vm.world().terrain().is_solid(far.offset(1, 0, 0)));
+        assert_eq!(
+            serde_json::to_value(&vm.world().to_persistent().qi_sources).unwrap(),
+            serde_json::to_value(&before.qi_sources).unwrap()
+        );
+    }
 
-        let _ = vm.step(&[ActionRequest::new(
-            agent_id,
-            Action::BuildStructure {
-                kind: StructureKind::Basic,
-            },
-        )]);
-        let tick = vm.step(&[ActionRequest::new(
-            agent_id,
+    #[test]
+    fn snapshots_round_trip_through_every_format() {
+        use crate::modules::view::SnapshotFormat;
+
+        let mut vm = Vm::with_seed(5);
+        let ada = vm.spawn_agent("Ada", 6, Position::origin());
+        vm.seed_qi_source(Position { x: 1, y: 0, z: 0 }, 9, 1);
+        *vm.world_mut().terrain_mut() = Terrain::flat(Position::origin(), 2, 2);
+        vm.step(&[ActionRequest::new(
+            ada,
             Action::BuildStructure {
                 kind: StructureKind::Basic,
             },
         )]);
-
-        assert_eq!(tick.rejections.len(), 1);
-        assert!(matches!(
-            tick.rejections[0].error,
-            ActionError::StructureSpaceOccupied { .. }
-        ));
-        let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 4); // 5 start -1 build (second attempt rejected, no charge)
+        let snapshot = vm.snapshot();
+
+        let json = SnapshotFormat::Json.encode(&snapshot).unwrap();
+        for format in SnapshotFormat::ALL {
+            let bytes = format.encode(&snapshot).unwrap();
+            let decoded = format.decode(&bytes).unwrap();
+            assert_eq!(decoded.content_hash(), snapshot.meta.world_hash);
+            assert_eq!(decoded.meta, snapshot.meta);
+            assert!(bytes.len() <= json.len());
+            decoded.verify().unwrap();
+            // A file caught mid-write does not decode.
+            assert!(format.decode(&bytes[..bytes.len() / 2]).is_err());
+        }
     }
 
     #[test]
-    fn qi_sources_recharge_each_tick() {
-        let mut vm = Vm::new();
-        vm.set_max_qi_supply(10);
-        let source_id = vm.seed_qi_source(Position::origin(), 10, 2);
-        if let Some(src) = vm.world.qi_sources.iter_mut().find(|s| s.id == source_id) {
-            src.current = 1;
-        }
-
-        let _ = vm.step(&[]);
-        let level_after_first = vm
-            .world
-            .qi_sources
-            .iter()
-            .find(|s| s.id == source_id)
-            .map(|s| s.current)
-            .unwrap();
-        assert_eq!(level_after_first, 3);
+    fn mixed_up_or_newer_snapshots_fail_verification() {
+        use crate::modules::view::SNAPSHOT_VERSION;
 
-        let _ = vm.step(&[]);
-        let level_after_second = vm
-            .world
-            .qi_sources
-            .iter()
-            .find(|s| s.id == source_id)
-            .map(|s| s.current)
-            .unwrap();
-        assert_eq!(level_after_second, 5);
+        let mut vm = Vm::new();
+        vm.spawn_agent("Ada", 6, Position::origin());
+        let snapshot = vm.snapshot();
+        assert_eq!(snapshot.meta.version, SNAPSHOT_VERSION);
+        snapshot.verify().unwrap();
+
+        let mut mixed = snapshot.clone();
+        mixed.agents[0].age += 1;
+        assert_eq!(
+            mixed.verify().unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        // Snapshots from before versioning are not held to the current hash.
+        mixed.meta.version = 0;
+        mixed.verify().unwrap();
+
+        let mut newer = snapshot;
+        newer.meta.version = SNAPSHOT_VERSION + 1;
+        assert_eq!(
+            newer.verify().unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
     }
 
     #[test]
-    fn qi_recharge_respects_global_cap() {
-        let mut vm = Vm::new();
-        vm.set_max_qi_supply(5);
-        let source_id = vm.seed_qi_source(Position::origin(), 10, 3);
-        if let Some(src) = vm.world.qi_sources.iter_mut().find(|s| s.id == source_id) {
-            src.current = 0;
-        }
+    fn children_get_seeded_unique_names() {
+        use crate::modules::names;
+
+        let run = || {
+            let mut vm = Vm::with_seed(11);
+            let ada = vm.spawn_agent("Ada", 5, Position::origin());
+            let bob = vm.spawn_agent("Bob", 5, Position { x: 1, y: 0, z: 0 });
+            let tick = vm.step(&[
+                ActionRequest::new(ada, Action::Reproduce { partner: bob }),
+                ActionRequest::new(bob, Action::Reproduce { partner: ada }),
+            ]);
+            let child = tick
+                .events
+                .iter()
+                .find_map(|e| match e {
+                    Event::AgentReproduced { child_id, .. } => Some(*child_id),
+                    _ => None,
+                })
+                .expect("child spawned");
+            vm.world().agent(child).unwrap().name.clone()
+        };
+        let name = run();
+        assert_eq!(name, run());
+        assert!(!name.starts_with("Child-"));
+
+        let seed = names::name_seed([1, 2, 3]);
+        let first = names::unique_name(seed, |_| false);
+        let second = names::unique_name(seed, |n| n == first);
+        assert_ne!(first, second);
+        assert_eq!(
+            names::unique_name(seed, |n| !n.contains(' ')),
+            format!("{} 2", first)
+        );
+    }
 
-        let _ = vm.step(&[]);
-        let after_first = vm
-            .world
-            .qi_sources
-            .iter()
-            .find(|s| s.id == source_id)
-            .map(|s| s.current)
-            .unwrap();
-        assert_eq!(after_first, 3);
+    #[test]
+    fn tick_stream_filters_by_agent_and_event_kind() {
+        use crate::modules::stream::{StreamFilter, TickStream};
+
+        let mut vm = Vm::with_seed(5);
+        let ada = vm.spawn_agent("Ada", 5, Position::origin());
+        let bob = vm.spawn_agent("Bob", 5, Position { x: 4, y: 0, z: 0 });
+        let tick = vm.step(&[
+            ActionRequest::new(ada, Action::Scan),
+            ActionRequest::new(bob, Action::Scan),
+        ]);
+
+        let stream = TickStream::new();
+        let everything = stream.subscribe(StreamFilter::default());
+        let bob_scans = stream.subscribe(
+            StreamFilter::from_query(&format!("agent_id={}&event=ScanReport", bob)).unwrap(),
+        );
+        let moves = stream.subscribe(StreamFilter::from_query("event=AgentMoved").unwrap());
+        stream.publish(&tick);
+
+        let all: serde_json::Value = serde_json::from_str(&everything.recv().unwrap()).unwrap();
+        assert_eq!(all["events"].as_array().unwrap().len(), tick.events.len());
+        let mine: serde_json::Value = serde_json::from_str(&bob_scans.recv().unwrap()).unwrap();
+        let events = mine["events"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["ScanReport"]["agent_id"], bob);
+        assert!(moves.try_recv().is_err(), "no moves this tick");
+
+        drop(everything);
+        stream.publish(&tick);
+        assert_eq!(stream.subscriber_count(), 2);
+        assert!(StreamFilter::from_query("agent_id=x").is_err());
+    }
 
-        let _ = vm.step(&[]);
-        let after_second = vm
-            .world
-            .qi_sources
-            .iter()
-            .find(|s| s.id == source_id)
-            .map(|s| s.current)
-            .unwrap();
-        assert_eq!(after_second, 5); // capped by global supply
+    #[test]
+    fn structure_persister_only_writes_new_structures() {
+        use crate::modules::structure::StructurePersister;
+
+        let built = |structure_id| Event::StructureBuilt {
+            agent_id: 1,
+            kind: StructureKind::Basic,
+            position: Position { x: 2, y: 0, z: 0 },
+            structure_id,
+        };
+        let persister = StructurePersister::default();
+        assert!(
+            persister
+                .new_records(&[Event::TickStarted { tick: 1 }])
+                .is_empty()
+        );
+        let records = persister.new_records(&[built(7), built(7), built(8)]);
+        assert_eq!(records.iter().map(|r| r.id).collect::<Vec<_>>(), vec![7, 8]);
+        assert_eq!(records[0].zone, Position { x: 2, y: 0, z: 0 }.zone());
     }
 
     #[test]
-    fn scan_reports_local_state() {
+    fn phase_plan_switches_actions_by_tick_and_structures() {
+        use crate::modules::scenario::{PhaseConfig, PhasePlan};
+
+        let phase = |name: &str, from_tick, min_structures, actions: &[&str]| PhaseConfig {
+            name: name.into(),
+            from_tick,
+            min_structures,
+            min_agents: None,
+            actions: actions.iter().map(|a| a.to_string()).collect(),
+        };
+        let mut plan = PhasePlan::from_configs(vec![
+            phase("explore", 0, None, &["scan", "move:1,0,0"]),
+            phase("build", 2, None, &["build_basic"]),
+            phase("settle", 2, Some(1), &["idle"]),
+        ])
+        .unwrap();
+        assert!(PhasePlan::from_configs(vec![phase("empty", 0, None, &[])]).is_err());
+        assert!(PhasePlan::from_configs(vec![phase("bad", 0, None, &["fly"])]).is_err());
+
         let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Scout", 3, Position::origin());
-        vm.seed_qi_source(Position { x: 1, y: 0, z: 0 }, 5, 0);
+        let agent_id = vm.spawn_agent("Builder", 10, Position::origin());
+        assert!(plan.advance(vm.world()).is_none());
+        assert_eq!(plan.actions().len(), 2);
 
-        let _ = vm.step(&[ActionRequest::new(
+        vm.step(&[]);
+        vm.step(&[]);
+        assert_eq!(plan.advance(vm.world()).unwrap().name, "build");
+        assert!(plan.advance(vm.world()).is_none());
+
+        vm.step(&[ActionRequest::new(
             agent_id,
             Action::BuildStructure {
                 kind: StructureKind::Basic,
             },
         )]);
+        assert_eq!(plan.advance(vm.world()).unwrap().name, "settle");
+        assert_eq!(plan.current().actions.len(), 1);
+    }
 
-        let tick = vm.step(&[ActionRequest::new(agent_id, Action::Scan)]);
-
-        let report = tick.events.iter().find_map(|e| match e {
-            Event::ScanReport {
-                agent_id: id,
-                nearby_qi_sources,
-                nearby_structures,
-                ..
-            } if *id == agent_id => Some((nearby_qi_sources.clone(), nearby_structures.clone())),
-            _ => None,
-        });
-
-        let (sources, structures) = report.expect("scan report missing");
-        assert!(
-            sources
-                .iter()
-                .any(|s| s.position == Position { x: 1, y: 0, z: 0 }),
-            "expected qi source near agent"
+    #[test]
+    fn analysis_counts_deaths_and_qi_from_recorded_runs() {
+        use crate::modules::analysis::{QiPoint, UNKNOWN_REASON, deaths_by_reason};
+        use crate::modules::journal::{EpochSummary, JournalEntry};
+
+        let mut vm = Vm::new();
+        let old = vm.spawn_agent("Old", 5, Position::origin());
+        let victim = vm.spawn_agent("Victim", 2, Position::origin());
+        vm.seed_ore_source(OreKind::QI, Position::origin().offset(3, 0, 0), 8, 0);
+        vm.seed_ore_source(
+            OreKind::TRANSISTOR,
+            Position::origin().offset(6, 0, 0),
+            4,
+            0,
         );
-        assert_eq!(structures.len(), 1);
+        let point = QiPoint::of(&vm.snapshot());
+        assert_eq!(
+            (point.agent_qi, point.node_qi, point.alive_agents),
+            (7, 8, 2)
+        );
+
+        let died = |agent_id, reason: DeathReason| JournalEntry {
+            tick: 1,
+            timestamp: String::new(),
+            event: serde_json::json!({ "AgentDied": { "agent_id": agent_id, "reason": reason } }),
+        };
+        let entries = [
+            died(old, DeathReason::Age),
+            died(victim, DeathReason::Killed),
+            died(victim + 1, DeathReason::Age),
+        ];
+        let mut pruned = EpochSummary {
+            pruned: true,
+            ..EpochSummary::default()
+        };
+        pruned.by_kind.insert("AgentDied".into(), 4);
+        // Compacted epochs whose lines are still in the journal must not count twice.
+        let mut kept = pruned.clone();
+        kept.pruned = false;
+
+        let deaths = deaths_by_reason(&entries, &[pruned, kept]);
+        assert_eq!(deaths.get("Age"), Some(&2));
+        assert_eq!(deaths.get("Killed"), Some(&1));
+        assert_eq!(deaths.get(UNKNOWN_REASON), Some(&4));
     }
 
     #[test]
-    fn harvests_qi_from_nearest_node() {
+    fn tick_metrics_summarise_population_qi_and_failures() {
+        use crate::modules::metrics::TickMetrics;
+
         let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Harvester", 3, Position::origin());
-        let src_id = vm.seed_qi_source(Position { x: 1, y: 0, z: 0 }, 5, 0);
+        let agent_id = vm.spawn_agent("Counted", 4, Position::origin());
+        let doomed = vm.spawn_agent("Doomed", 2, Position::origin().offset(1, 0, 0));
+        vm.seed_ore_source(OreKind::QI, Position::origin().offset(9, 0, 0), 9, 0);
+        vm.kill_agent(doomed, DeathReason::Hazard).unwrap();
 
-        let tick = vm.step(&[ActionRequest::new(
+        let mut tick = vm.step(&[ActionRequest::new(
             agent_id,
             Action::HarvestOre {
-                ore: OreKind::Qi,
-                source_id: 0,
+                ore: OreKind::QI,
+                source_id: 99,
             },
         )]);
+        // Deaths and births are counted from the tick's own events.
+        tick.events.push(Event::AgentDied {
+            agent_id: doomed,
+            reason: DeathReason::Hazard,
+        });
+        let metrics = TickMetrics::of(vm.stats(), &tick);
+        assert_eq!(metrics.tick, 1);
+        assert_eq!((metrics.population, metrics.dead_agents), (1, 1));
+        // Doomed's 2 Qi lie in its remains.
+        assert_eq!(metrics.node_qi, 9 + 2);
+        assert_eq!(
+            metrics.total_qi,
+            metrics.agent_qi + 9 + 2 + metrics.recycled_qi
+        );
+        assert_eq!((metrics.deaths, metrics.rejections), (1, 1));
+        assert_eq!(metrics.births, 0);
+    }
 
-        assert!(tick.rejections.is_empty());
-        let agent = vm.world().agent(agent_id).unwrap();
-        // cost 1, gain min(5, HARVEST_PER_ACTION=3) = 3
-        assert_eq!(agent.qi, 5);
-        assert!(tick.events.iter().any(|e| matches!(
-            e,
-            Event::OreNodeHarvested { ore, source_id, amount, .. } if *ore == OreKind::Qi && *source_id == src_id && *amount == HARVEST_PER_ACTION
-        )));
+    #[test]
+    fn top_feed_tracks_last_activity_and_busy_zones() {
+        use crate::modules::dashboard::DashboardFeed;
+        use crate::modules::journal::JournalEntry;
+        use std::time::Instant;
+
+        let entry = |tick, event| JournalEntry {
+            tick,
+            timestamp: String::new(),
+            event,
+        };
+        let mut vm = Vm::new();
+        let near = vm.spawn_agent("Near", 5, Position::origin());
+        let far = vm.spawn_agent("Far", 5, Position::origin().offset(ZONE_SIZE * 2, 0, 0));
+        vm.world.tick = 20;
+        let mut feed = DashboardFeed::new(10);
+        feed.apply(
+            Instant::now(),
+            Some(vm.snapshot()),
+            None,
+            vec![
+                entry(2, serde_json::json!({ "AgentMoved": { "agent_id": far } })),
+                entry(
+                    18,
+                    serde_json::json!({ "ActionObserved": { "agent_id": near, "action": "scan" } }),
+                ),
+                entry(
+                    19,
+                    serde_json::json!({ "QiSpent": { "agent_id": near, "amount": 1 } }),
+                ),
+                entry(20, serde_json::json!({ "TickCompleted": { "tick": 20 } })),
+            ],
+        );
+
+        assert_eq!(feed.last_activity(near).unwrap().label, "QiSpent");
+        assert_eq!(feed.last_activity(far).unwrap().tick, 2);
+        let activity = feed.zone_activity(5);
+        assert_eq!(activity.get(&Position::origin().zone()), Some(&2));
+        // The far agent's move is older than the window.
+        assert!(!activity.contains_key(&Position::origin().offset(ZONE_SIZE * 2, 0, 0).zone()));
     }
 
     #[test]
-    fn harvest_fails_without_source() {
+    fn dashboard_feed_keeps_recent_events_and_measures_tick_rate() {
+        use crate::modules::dashboard::DashboardFeed;
+        use crate::modules::journal::JournalEntry;
+        use std::time::{Duration, Instant};
+
+        let entry = |tick| JournalEntry {
+            tick,
+            timestamp: String::new(),
+            event: serde_json::json!({ "TickStarted": { "tick": tick } }),
+        };
         let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Harvester", 3, Position::origin());
+        vm.spawn_agent("Watched", 5, Position::origin());
+        let mut feed = DashboardFeed::new(2);
+        let start = Instant::now();
+        feed.apply(start, Some(vm.snapshot()), None, vec![entry(0)]);
+        assert_eq!(feed.tick(), Some(0));
+        assert!(feed.tick_rate().is_none());
+
+        for _ in 0..4 {
+            vm.step(&[]);
+        }
+        let later = start + Duration::from_secs(2);
+        feed.apply(later, Some(vm.snapshot()), None, vec![entry(1), entry(2)]);
+        assert_eq!(feed.tick_rate(), Some(2.0));
+        let ticks: Vec<u64> = feed.recent_events().map(|e| e.tick).collect();
+        assert_eq!(ticks, vec![2, 1]);
+
+        // A failed read keeps the last snapshot on screen.
+        feed.apply(later, None, None, Vec::new());
+        assert_eq!(feed.snapshot().unwrap().agents.len(), 1);
+        assert!(!feed.is_running());
+    }
 
+    #[test]
+    fn rejections_become_prompt_constraints() {
+        use crate::modules::agent::{ActionArg, BrainMemory, plan_with_llm, rejection_constraints};
+
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Seeker", 5, Position::origin());
+        let far = vm.seed_ore_source(OreKind::QI, Position::origin().offset(6, 0, 0), 9, 0);
+        let near = vm.seed_ore_source(OreKind::QI, Position::origin().offset(0, 0, -4), 9, 0);
         let tick = vm.step(&[ActionRequest::new(
             agent_id,
             Action::HarvestOre {
-                ore: OreKind::Qi,
-                source_id: 42,
+                ore: OreKind::QI,
+                source_id: far,
             },
         )]);
+        let error = &tick.rejections[0].error;
 
-        assert_eq!(tick.rejections.len(), 1);
+        let constraints = rejection_constraints(&vm, agent_id, error);
+        let codes: Vec<&str> = constraints.iter().map(|c| c.code).collect();
+        assert_eq!(codes, vec!["ore_source_unavailable", "nearest_ore_node"]);
+        assert_eq!(constraints[1].data["source_id"], near);
+        assert_eq!(constraints[1].data["distance"], 4);
+
+        let mut memory = BrainMemory {
+            constraints,
+            ..BrainMemory::default()
+        };
+        let decision = plan_with_llm(&vm, agent_id, &[ActionArg::Scan], &mut memory, None, 2);
+        assert!(decision.prompt.contains("constraints"));
+        assert!(decision.prompt.contains("nearest_ore_node"));
+
+        memory.constraints.clear();
+        let decision = plan_with_llm(&vm, agent_id, &[ActionArg::Scan], &mut memory, None, 2);
+        assert!(!decision.prompt.contains("constraints"));
+    }
+
+    #[test]
+    fn brain_swaps_reach_the_run_and_the_next_tick() {
+        use crate::modules::agent::BrainMode;
+        use crate::modules::control::{BrainSwap, ControlRequest, ControlServer, send_control_to};
+
+        let path = std::env::temp_dir().join(format!("harimu-brain-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = ControlServer::bind_at(path.clone()).unwrap();
+        let send = |swap| {
+            send_control_to(&path, &ControlRequest::SetBrain(swap))
+                .unwrap()
+                .unwrap()
+        };
+
+        let swap = BrainSwap {
+            agent_id: 1,
+            mode: Some(BrainMode::Llm),
+            model: Some("small".into()),
+        };
+        assert!(send(swap.clone()).ok);
+        assert!(
+            !send(BrainSwap {
+                agent_id: 1,
+                mode: None,
+                model: None,
+            })
+            .ok
+        );
+        assert_eq!(server.take_brain_swaps(), vec![swap]);
+        assert!(server.take_brain_swaps().is_empty());
+        drop(server);
+
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Switcher", 3, Position::origin());
+        vm.queue_event(Event::BrainSwapped {
+            agent_id,
+            brain: "loop".into(),
+        });
+        let events = vm.step(&[]).events;
+        assert_eq!(events[0], Event::TickStarted { tick: 1 });
+        assert_eq!(
+            events[1],
+            Event::BrainSwapped {
+                agent_id,
+                brain: "loop".into()
+            }
+        );
+        assert!(
+            !vm.step(&[])
+                .events
+                .iter()
+                .any(|event| matches!(event, Event::BrainSwapped { .. }))
+        );
+    }
+
+    #[test]
+    fn admin_actions_change_the_world_and_are_journaled_as_admin_events() {
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Subject", 3, Position::origin());
+        let other = vm.spawn_agent("Bystander", 3, Position { x: 2, y: 0, z: 0 });
+        let to = Position { x: 5, y: 0, z: 5 };
+
+        vm.admin(agent_id, AdminAction::Teleport { to }).unwrap();
+        assert_eq!(vm.agent(agent_id).unwrap().position, to);
+        assert_eq!(vm.world().occupied.get(&to), Some(&agent_id));
+        assert!(!vm.world().occupied.contains_key(&Position::origin()));
         assert!(matches!(
-            tick.rejections[0].error,
-            ActionError::OreSourceUnavailable { .. }
+            vm.admin(agent_id, AdminAction::Teleport { to: Position { x: 2, y: 0, z: 0 } }),
+            Err(ActionError::PositionOccupied { occupied_by, .. }) if occupied_by == other
         ));
-        let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 3);
+
+        vm.admin(agent_id, AdminAction::GrantQi { amount: 7 })
+            .unwrap();
+        assert_eq!(vm.agent(agent_id).unwrap().qi(), 10);
+        let events = vm.step(&[]).events;
+        assert_eq!(events[0], Event::TickStarted { tick: 1 });
+        assert_eq!(
+            events[1],
+            Event::AdminIntervened {
+                agent_id,
+                action: AdminAction::Teleport { to }
+            }
+        );
+
+        assert!(matches!(
+            vm.admin(agent_id, AdminAction::Revive),
+            Err(ActionError::AgentAlive(id)) if id == agent_id
+        ));
+        vm.admin(agent_id, AdminAction::Kill).unwrap();
+        assert_eq!(vm.stats().alive_agents, 1);
+        let events = vm.step(&[]).events;
+        assert_eq!(
+            &events[1..3],
+            &[
+                Event::AdminIntervened {
+                    agent_id,
+                    action: AdminAction::Kill
+                },
+                Event::AgentDied {
+                    agent_id,
+                    reason: DeathReason::Admin
+                }
+            ]
+        );
+        assert!(matches!(
+            vm.admin(agent_id, AdminAction::GrantQi { amount: 1 }),
+            Err(ActionError::AgentDead(_))
+        ));
+
+        vm.admin(agent_id, AdminAction::Revive).unwrap();
+        let revived = vm.agent(agent_id).unwrap();
+        assert!(revived.alive);
+        assert_eq!(revived.hp, DEFAULT_AGENT_HP);
+        assert_eq!(vm.world().occupied.get(&to), Some(&agent_id));
+        assert_eq!(vm.stats().alive_agents, 2);
     }
 
     #[test]
-    fn harvest_rejected_when_depleted() {
+    fn qi_audit_holds_the_world_to_its_infused_and_granted_qi() {
         let mut vm = Vm::new();
+        vm.set_max_qi_supply(10);
         let agent_id = vm.spawn_agent("Harvester", 3, Position::origin());
-        let src_id = vm.seed_qi_source(Position::origin(), 10, 1);
-        if let Some(src) = vm.world.qi_sources.iter_mut().find(|s| s.id == src_id) {
-            src.current = 1;
+        vm.seed_qi_source(Position { x: 1, y: 0, z: 0 }, 10, 3);
+        for _ in 0..5 {
+            vm.step(&[ActionRequest::new(
+                agent_id,
+                Action::HarvestOre {
+                    ore: OreKind::QI,
+                    source_id: 0,
+                },
+            )]);
         }
+        let audit = vm.qi_audit();
+        assert_eq!(audit.granted_qi, 3);
+        assert_eq!(audit.limit(), Some(13));
+        assert_eq!(audit.excess(), 0);
+        assert_eq!(
+            audit.held(),
+            audit.agent_qi + audit.node_qi + audit.recycled_qi
+        );
 
-        let tick = vm.step(&[ActionRequest::new(
-            agent_id,
-            Action::HarvestOre {
-                ore: OreKind::Qi,
-                source_id: src_id,
-            },
-        )]);
+        vm.admin(agent_id, AdminAction::GrantQi { amount: 4 })
+            .unwrap();
+        assert_eq!(vm.qi_audit().limit(), Some(17));
+        assert_eq!(vm.qi_audit().excess(), 0);
+
+        vm.world.recycled_qi += 100;
+        assert_eq!(vm.qi_audit().excess(), vm.qi_audit().held() - 17);
+
+        // Saves without a granted total count the excess as granted.
+        let mut saved = vm.world().to_persistent();
+        saved.granted_qi = None;
+        let world = World::from_persistent(saved).unwrap();
+        assert_eq!(world.qi_audit().excess(), 0);
+        assert_eq!(world.qi_audit().held(), vm.qi_audit().held());
+    }
 
-        assert_eq!(tick.rejections.len(), 1);
+    #[test]
+    fn agendas_inject_scheduled_actions_at_their_ticks() {
+        use crate::modules::agenda::{Agenda, ScheduledAction};
+        use crate::modules::agent::ActionArg;
+        use crate::modules::agents::{self, AgentStore};
+
+        let once = ScheduledAction::new("build:qi", Some(500), None).unwrap();
+        let every = ScheduledAction::new("scan", None, Some(50)).unwrap();
+        assert_eq!(every.at, 50);
+        assert!(once.is_due(500) && !once.is_due(550));
+        assert!(!every.is_due(0) && every.is_due(50) && every.is_due(500) && !every.is_due(501));
+        assert!(ScheduledAction::new("fly", Some(1), None).is_err());
+        assert!(ScheduledAction::new("scan", None, Some(0)).is_err());
+        assert!(ScheduledAction::new("scan", None, None).is_err());
+
+        let mut registry = AgentStore::default();
+        let planner = agents::create_agent(&mut registry, String::new()).unwrap();
+        let idler = agents::create_agent(&mut registry, String::new()).unwrap();
+        agents::schedule_action(&mut registry, &planner.id, once.clone()).unwrap();
+        agents::schedule_action(&mut registry, &planner.id, every).unwrap();
+        assert!(agents::unschedule_action(&mut registry, &idler.id, Some(1)).is_err());
+
+        let mut vm = Vm::new();
+        let planner_id = vm.spawn_agent(planner.id.clone(), 3, Position::origin());
+        let idler_id = vm.spawn_agent(idler.id.clone(), 3, Position::origin());
+        let agenda = Agenda::for_agents(&registry, vm.world(), &[planner_id, idler_id]).unwrap();
+        assert_eq!(agenda.len(), 2);
+        assert!(agenda.due(49).is_empty());
+        assert!(matches!(agenda.due(100)[..], [(id, ActionArg::Scan)] if id == planner_id));
+        // Both are due at 500; the one scheduled first wins.
         assert!(matches!(
-            tick.rejections[0].error,
-            ActionError::OreSourceDepleted {
-                ore,
-                source_id,
-                available,
-                ..
-            } if ore == OreKind::Qi && source_id == src_id && available < HARVEST_PER_ACTION
+            agenda.due(500)[..],
+            [(id, ActionArg::BuildStructure { .. })] if id == planner_id
         ));
-        let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 3);
+
+        assert_eq!(
+            agents::unschedule_action(&mut registry, &planner.id, Some(1)).unwrap(),
+            vec![once]
+        );
+        assert_eq!(
+            agents::unschedule_action(&mut registry, &planner.id, None)
+                .unwrap()
+                .len(),
+            1
+        );
     }
 
     #[test]
-    fn programmable_structure_requires_transistors() {
+    fn brain_memory_keeps_its_notes_across_runs() {
+        use crate::modules::agent::{BrainMemory, rejection_constraints};
+        use crate::modules::memory::{memory_dir, memory_path};
+
         let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Builder", 3, Position::origin());
+        let ada = vm.spawn_agent("Ada", 0, Position::origin());
+        let memory = BrainMemory {
+            notes: vec!["tick 4: action Scan applied (events=3)".into()],
+            constraints: rejection_constraints(
+                &vm,
+                ada,
+                &ActionError::InsufficientQi {
+                    agent_id: ada,
+                    required: 1,
+                    available: 0,
+                },
+            ),
+            ..BrainMemory::default()
+        };
+        assert!(!memory.constraints.is_empty());
 
-        let tick = vm.step(&[ActionRequest::new(
-            agent_id,
-            Action::BuildStructure {
-                kind: StructureKind::Programmable,
-            },
-        )]);
+        // Constraints describe the last tick only, so a new run starts without them.
+        let json = serde_json::to_string(&memory).unwrap();
+        let restored: BrainMemory = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.notes, memory.notes);
+        assert!(restored.constraints.is_empty());
+        assert!(
+            serde_json::from_str::<BrainMemory>("{}")
+                .unwrap()
+                .notes
+                .is_empty()
+        );
 
-        assert_eq!(tick.rejections.len(), 1);
-        assert!(matches!(
-            tick.rejections[0].error,
-            ActionError::InsufficientOre {
-                ore: OreKind::Transistor,
-                ..
-            }
-        ));
-        let agent = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent.qi, 3);
-        assert_eq!(agent.transistors, 0);
+        // Names can't reach outside the memory directory.
+        let path = memory_path("../Ada Lovelace");
+        assert_eq!(path.parent(), Some(memory_dir().as_path()));
+        assert_eq!(path.file_name().unwrap(), "___Ada_Lovelace.json");
     }
 
     #[test]
-    fn harvest_transistor_and_build_programmable() {
+    fn vector_memory_recalls_the_archived_notes_closest_to_the_state() {
+        use crate::modules::agent::{
+            ActionArg, ArchivedNote, BrainMemory, LlmClient, LlmProvider, plan_with_llm,
+        };
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    head.push_str(&line);
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                // Notes about ore point one way, everything else the other; the state
+                // (always the last input) points at the ore.
+                let reply = if head.starts_with("POST /api/embed ") {
+                    let inputs = body["input"].as_array().unwrap();
+                    let vectors: Vec<_> = inputs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, text)| {
+                            if i + 1 == inputs.len() || text.as_str().unwrap().contains("ore") {
+                                [1.0, 0.0]
+                            } else {
+                                [0.0, 1.0]
+                            }
+                        })
+                        .collect();
+                    serde_json::json!({ "embeddings": vectors, "prompt_eval_count": 30 })
+                } else {
+                    serde_json::json!({ "message": { "content": "action: scan" } })
+                }
+                .to_string();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{}",
+                    reply.len(),
+                    reply
+                )
+                .unwrap();
+                requests.push((head, body));
+            }
+            requests
+        });
+
         let mut vm = Vm::new();
-        let agent_id = vm.spawn_agent("Tinkerer", 4, Position::origin());
-        vm.seed_ore_source(OreKind::Transistor, Position::origin(), 5, 0);
+        let agent_id = vm.spawn_agent("Sage", 5, Position::origin());
+        let client = LlmClient::new(
+            host,
+            "llama3",
+            LlmProvider::Ollama,
+            None,
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap()
+        .with_vector_memory(1, None);
+        let archived = |note: &str| ArchivedNote {
+            note: note.into(),
+            vector: Vec::new(),
+        };
+        let mut memory = BrainMemory {
+            notes: (0..5).map(|i| format!("recent note {}", i)).collect(),
+            archive: vec![
+                archived("found an ore node east"),
+                archived("waited a tick"),
+            ],
+            ..BrainMemory::default()
+        };
+        let decision = plan_with_llm(
+            &vm,
+            agent_id,
+            &[ActionArg::Scan],
+            &mut memory,
+            Some(&client),
+            1,
+        );
+        assert!(decision.llm_ok, "{}", decision.response);
+        assert!(decision.prompt.contains("recalled_memory"));
+        assert!(decision.prompt.contains("found an ore node east"));
+        assert!(!decision.prompt.contains("waited a tick"));
+        assert_eq!(decision.usage.requests, 2);
+        assert_eq!(decision.usage.prompt_tokens, 30);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].1["model"], "nomic-embed-text");
+        assert_eq!(requests[0].1["input"].as_array().unwrap().len(), 3);
+
+        // The note pushed out of the rolling memory waits to be embedded before the next prompt.
+        assert_eq!(memory.embedding_model.as_deref(), Some("nomic-embed-text"));
+        assert_eq!(memory.archive.len(), 3);
+        assert_eq!(memory.archive[0].vector, vec![1.0, 0.0]);
+        assert_eq!(memory.archive[2].note, "recent note 0");
+        assert!(memory.archive[2].vector.is_empty());
+
+        // Without vector memory nothing is archived.
+        let mut plain = BrainMemory {
+            notes: (0..5).map(|i| format!("recent note {}", i)).collect(),
+            ..BrainMemory::default()
+        };
+        plan_with_llm(&vm, agent_id, &[ActionArg::Scan], &mut plain, None, 1);
+        assert!(plain.archive.is_empty());
+    }
 
-        let harvest_tick = vm.step(&[ActionRequest::new(
+    #[test]
+    fn agent_brain_settings_merge_into_the_profile() {
+        use crate::modules::agent::LlmProvider;
+        use crate::modules::agents::{self, AgentStore};
+        use crate::modules::templates::BrainConfig;
+
+        let mut registry = AgentStore::default();
+        let id = agents::create_agent(&mut registry, String::new())
+            .unwrap()
+            .id;
+        let gemini = BrainConfig {
+            provider: Some(LlmProvider::Gemini),
+            model: Some("gemini-2.5-flash".into()),
+            api_key_env: Some("GEMINI_KEY".into()),
+            actions: vec!["scan".into(), "move:1,0,0".into()],
+            ..BrainConfig::default()
+        };
+        agents::configure_brain(&mut registry, &id, gemini, Some("Map the valley".into())).unwrap();
+        // A later call only replaces what it sets.
+        let warmer = BrainConfig {
+            temperature: Some(0.7),
+            ..BrainConfig::default()
+        };
+        agents::configure_brain(&mut registry, &id, warmer, None).unwrap();
+
+        let profile = &registry.agents[&id];
+        let brain = profile.brain.as_ref().unwrap();
+        assert_eq!(brain.provider, Some(LlmProvider::Gemini));
+        assert_eq!(brain.model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(brain.temperature, Some(0.7));
+        assert_eq!(brain.actions, ["scan", "move:1,0,0"]);
+        assert_eq!(profile.goal.as_deref(), Some("Map the valley"));
+
+        let bad = BrainConfig {
+            actions: vec!["teleport".into()],
+            ..BrainConfig::default()
+        };
+        assert!(agents::configure_brain(&mut registry, &id, bad, None).is_err());
+        let hot = BrainConfig {
+            temperature: Some(3.0),
+            ..BrainConfig::default()
+        };
+        assert!(agents::configure_brain(&mut registry, &id, hot, None).is_err());
+        assert_eq!(
+            registry.agents[&id].brain.as_ref().unwrap().actions.len(),
+            2
+        );
+    }
+
+    #[test]
+    fn set_goal_replaces_the_goal_and_a_blank_one_restores_the_default() {
+        use crate::modules::agent::{DEFAULT_AGENT_GOAL, LlmClient, LlmProvider};
+        use crate::modules::agents::{self, AgentStore};
+
+        let mut registry = AgentStore::default();
+        let id = agents::create_agent(&mut registry, String::new())
+            .unwrap()
+            .id;
+        assert_eq!(
+            agents::set_goal(&mut registry, &id, "  Guard the well ").unwrap(),
+            None
+        );
+        assert_eq!(registry.agents[&id].goal.as_deref(), Some("Guard the well"));
+        let client = LlmClient::new(
+            "http://localhost:11434",
+            "m",
+            LlmProvider::Ollama,
+            None,
+            std::time::Duration::from_secs(1),
+        )
+        .unwrap()
+        .with_goal(registry.agents[&id].goal.clone());
+        assert_eq!(client.goal(), "Guard the well");
+
+        let previous = agents::set_goal(&mut registry, &id, " ").unwrap();
+        assert_eq!(previous.as_deref(), Some("Guard the well"));
+        assert_eq!(registry.agents[&id].goal, None);
+        assert_eq!(client.with_goal(None).goal(), DEFAULT_AGENT_GOAL);
+        assert!(agents::set_goal(&mut registry, "nobody", "x").is_err());
+    }
+
+    #[test]
+    fn llm_usage_adds_up_per_agent_until_the_budget_runs_out() {
+        use crate::modules::agent::{LlmBudget, LlmUsage};
+        use crate::modules::decisions::{DecisionRecord, usage_by_agent};
+
+        let record = |agent_id, llm_ok, latency_ms, usage| DecisionRecord {
+            tick: 1,
             agent_id,
-            Action::HarvestOre {
-                ore: OreKind::Transistor,
-                source_id: 0,
+            summary: String::new(),
+            prompt_hash: String::new(),
+            action: "idle".into(),
+            llm_ok,
+            latency_ms,
+            provider: "openai".into(),
+            model: "gpt-5-nano".into(),
+            usage,
+        };
+        let call = |requests, prompt_tokens, completion_tokens| LlmUsage {
+            requests,
+            prompt_tokens,
+            completion_tokens,
+        };
+        let records = [
+            record(2, true, 300, call(1, 800, 40)),
+            record(1, true, 100, call(1, 700, 30)),
+            // Three attempts and no reply: counted, but without tokens.
+            record(1, false, 900, call(3, 0, 0)),
+            // On the loop brain; no call.
+            record(2, false, 0, LlmUsage::default()),
+        ];
+        let agents = usage_by_agent(&records);
+        assert_eq!(
+            agents.iter().map(|a| a.agent_id).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(
+            (agents[0].decisions, agents[0].calls, agents[0].failures),
+            (2, 2, 1)
+        );
+        assert_eq!(agents[0].usage, call(4, 700, 30));
+        assert_eq!(
+            (agents[0].mean_latency_ms(), agents[0].max_latency_ms),
+            (500, 900)
+        );
+        assert_eq!(
+            (agents[1].decisions, agents[1].calls, agents[1].failures),
+            (2, 1, 0)
+        );
+
+        let mut spent = LlmUsage::default();
+        for record in &records {
+            spent.add(record.usage);
+        }
+        assert_eq!(spent.tokens(), 1570);
+        assert_eq!(LlmBudget::default().exhausted_by(&spent), None);
+        let budget = LlmBudget {
+            max_requests: Some(10),
+            max_tokens: Some(2_000),
+        };
+        assert_eq!(budget.exhausted_by(&spent), None);
+        spent.add(call(1, 400, 30));
+        assert!(
+            budget
+                .exhausted_by(&spent)
+                .unwrap()
+                .contains("2000 LLM tokens")
+        );
+        let budget = LlmBudget {
+            max_requests: Some(6),
+            max_tokens: None,
+        };
+        assert!(
+            budget
+                .exhausted_by(&spent)
+                .unwrap()
+                .contains("6 of 6 LLM requests")
+        );
+    }
+
+    #[test]
+    fn agents_plan_concurrently_and_keep_their_order() {
+        use crate::modules::agent::{
+            ActionArg, BrainMemory, LlmClient, LlmProvider, PlanJob, plan_many_with_llm,
+        };
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        // Answers only once both requests are open, so it would hang if they were sent in turn.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut streams: Vec<_> = (0..2).map(|_| listener.accept().unwrap().0).collect();
+            for stream in &mut streams {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                let reply = r#"{"message":{"role":"assistant","content":"action: scan"}}"#;
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                    reply.len(),
+                    reply
+                )
+                .unwrap();
+            }
+        });
+
+        let mut vm = Vm::new();
+        let first = vm.spawn_agent("First", 5, Position::origin());
+        let second = vm.spawn_agent("Second", 5, Position::origin());
+        let client = LlmClient::new(
+            host,
+            "llama3",
+            LlmProvider::Ollama,
+            None,
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap();
+        let candidates = [ActionArg::Idle, ActionArg::Scan];
+        let mut memories = [BrainMemory::default(), BrainMemory::default()];
+        let [first_memory, second_memory] = &mut memories;
+        let jobs = vec![
+            PlanJob {
+                agent_id: first,
+                candidates: &candidates,
+                memory: first_memory,
+                client: Some(&client),
             },
-        )]);
+            PlanJob {
+                agent_id: second,
+                candidates: &candidates,
+                memory: second_memory,
+                client: Some(&client),
+            },
+        ];
+        let decisions = plan_many_with_llm(&vm, jobs, 1, 2);
+        server.join().unwrap();
+
+        assert_eq!(decisions.len(), 2);
+        for decision in &decisions {
+            assert!(decision.llm_ok, "{}", decision.response);
+            assert_eq!(decision.action, Action::Scan);
+        }
+        assert!(
+            decisions[0]
+                .summary
+                .starts_with(&format!("Agent #{} ", first))
+        );
+        assert!(
+            decisions[1]
+                .summary
+                .starts_with(&format!("Agent #{} ", second))
+        );
+        assert!(memories.iter().all(|memory| memory.notes.len() == 1));
+    }
 
-        assert!(harvest_tick.rejections.is_empty());
-        let agent_after_harvest = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent_after_harvest.transistors, HARVEST_PER_ACTION);
-        assert_eq!(agent_after_harvest.qi, 3); // 4 start -1 harvest cost
+    #[test]
+    fn llama_provider_without_a_model_falls_back_to_the_loop() {
+        use crate::modules::agent::{
+            ActionArg, BrainMemory, LlmClient, LlmProvider, plan_with_llm,
+        };
 
-        let build_tick = vm.step(&[ActionRequest::new(
+        let mut vm = Vm::new();
+        let agent_id = vm.spawn_agent("Offline", 5, Position::origin());
+        let client = LlmClient::new(
+            "",
+            "/nonexistent/model.gguf",
+            LlmProvider::Llama,
+            None,
+            std::time::Duration::from_secs(1),
+        )
+        .unwrap();
+        let decision = plan_with_llm(
+            &vm,
             agent_id,
-            Action::BuildStructure {
-                kind: StructureKind::Programmable,
-            },
-        )]);
+            &[ActionArg::Scan],
+            &mut BrainMemory::default(),
+            Some(&client),
+            1,
+        );
+        assert!(!decision.llm_ok);
+        assert_eq!(decision.provider, LlmProvider::Llama);
+        assert_eq!(decision.action, Action::Scan);
+        if cfg!(feature = "llama") {
+            assert!(
+                decision.response.contains("open model"),
+                "{}",
+                decision.response
+            );
+        } else {
+            assert!(
+                decision.response.contains("--features llama"),
+                "{}",
+                decision.response
+            );
+        }
+    }
 
-        assert!(build_tick.rejections.is_empty());
-        let agent_after_build = vm.world().agent(agent_id).unwrap();
-        assert_eq!(agent_after_build.transistors, HARVEST_PER_ACTION - 1);
-        assert_eq!(agent_after_build.qi, 2); // 3 after harvest -1 build cost
-        assert_eq!(vm.world.structures.len(), 1);
-        assert!(build_tick.events.iter().any(|e| matches!(
-            e,
-            Event::StructureBuilt {
-                kind: StructureKind::Programmable,
-                ..
-            }
-        )));
+    #[test]
+    fn start_config_layers_flags_over_env_over_file_and_rejects_contradictions() {
+        use crate::modules::agent::{BrainMode, LlmProvider};
+        use crate::modules::config::{ConfigSource, EffectiveConfig, Pacing, StartSettings};
+
+        let file: StartSettings =
+            toml::from_str("brain = \"loop\"\nllm_model = \"file-model\"\ntick_rate = 4.0")
+                .unwrap();
+        let env = StartSettings::from_vars(|name| match name {
+            "HARIMU_LLM_MODEL" => Some("env-model".into()),
+            "HARIMU_LLM_PROVIDER" => Some("gemini".into()),
+            _ => None,
+        })
+        .unwrap();
+        let flags = StartSettings {
+            delay_ms: Some(250),
+            ..StartSettings::default()
+        };
+        let config = EffectiveConfig::resolve(&file, &env, &flags).unwrap();
+        assert_eq!(config.brain.value, BrainMode::Loop);
+        assert_eq!(config.brain.source, ConfigSource::File);
+        assert_eq!(config.llm_model.value, "env-model");
+        assert_eq!(config.llm_model.source, ConfigSource::Env);
+        assert_eq!(config.llm_provider.value, LlmProvider::Gemini);
+        assert_eq!(config.llm_host.value, crate::modules::agent::GEMINI_HOST);
+        assert_eq!(config.pacing.value, Pacing::DelayMs(250));
+        assert_eq!(config.pacing.source, ConfigSource::Flag);
+        assert_eq!(
+            config.tick_interval(),
+            std::time::Duration::from_millis(250)
+        );
+        assert!(config.warnings(false).is_empty());
+
+        let llm = StartSettings {
+            brain: Some(BrainMode::Llm),
+            ..StartSettings::default()
+        };
+        let slow = EffectiveConfig::resolve(&StartSettings::default(), &llm, &flags).unwrap();
+        assert_eq!(slow.warnings(false).len(), 1);
+        assert!(slow.warnings(true).is_empty());
+
+        let both = StartSettings {
+            tick_rate: Some(2.0),
+            delay_ms: Some(100),
+            ..StartSettings::default()
+        };
+        let err = EffectiveConfig::resolve(&StartSettings::default(), &both, &flags);
+        assert!(
+            err.is_ok(),
+            "the flag layer paces the run, so env's pair is never read"
+        );
+        let err =
+            EffectiveConfig::resolve(&both, &StartSettings::default(), &StartSettings::default())
+                .unwrap_err();
+        assert!(err.contains("both set (config.toml)"), "{}", err);
+        let stalled = StartSettings {
+            tick_rate: Some(0.0),
+            ..StartSettings::default()
+        };
+        assert!(
+            EffectiveConfig::resolve(
+                &stalled,
+                &StartSettings::default(),
+                &StartSettings::default()
+            )
+            .is_err()
+        );
+        let no_timeout = StartSettings {
+            llm_timeout_ms: Some(0),
+            ..StartSettings::default()
+        };
+        assert!(EffectiveConfig::resolve(&no_timeout, &llm, &StartSettings::default()).is_err());
+        assert!(StartSettings::from_vars(|_| Some("fast".into())).is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store_appends_to_array_documents() {
+        use crate::modules::store::{SqliteStore, Store};
+
+        let dir = std::env::temp_dir().join(format!("harimu-append-{}", std::process::id()));
+        let store = SqliteStore::open(&dir).unwrap();
+        let item = br#"{"id":2}"#.to_vec();
+        assert!(
+            !store
+                .append_items("doc.json", "items", std::slice::from_ref(&item))
+                .unwrap()
+        );
+
+        store.write("doc.json", br#"{"items":[{"id":1}]}"#).unwrap();
+        assert!(store.append_items("doc.json", "items", &[item]).unwrap());
+        let body: serde_json::Value =
+            serde_json::from_slice(&store.read("doc.json").unwrap().unwrap()).unwrap();
+        assert_eq!(body["items"], serde_json::json!([{ "id": 1 }, { "id": 2 }]));
+        drop(store);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scattered_spawn_points_stay_within_the_spread_and_follow_the_seed() {
+        let center = Position { x: 10, y: 0, z: -4 };
+        let draw = |seed| {
+            let mut vm = Vm::with_seed(seed);
+            (0..32)
+                .map(|_| vm.world_mut().scatter(center, 5))
+                .collect::<Vec<_>>()
+        };
+        let points = draw(11);
+        assert_eq!(points, draw(11));
+        assert!(points.iter().all(|p| p.y == center.y
+            && (p.x - center.x).abs() <= 5
+            && (p.z - center.z).abs() <= 5));
+        assert!(points.iter().any(|p| p.z != center.z));
+
+        let mut vm = Vm::with_seed(11);
+        assert_eq!(vm.world_mut().scatter(center, 0), center);
+    }
+
+    #[test]
+    fn world_fixtures_name_their_agents_and_ore_nodes() {
+        use crate::modules::testing::{TickAssertions, WorldFixture};
+
+        let fixture = || {
+            WorldFixture::new()
+                .at_tick(10)
+                .with_agent("Ada", 3, Position::origin())
+                .holding(ItemKind::Stone, 2)
+                .with_agent("Bo", 3, Position { x: 1, y: 0, z: 0 })
+                .with_ore(OreKind::QI, Position { x: 0, y: 1, z: 0 }, 5, 0)
+                .build()
+        };
+        let mut world = fixture();
+        assert_eq!(world.vm.world().tick(), 10);
+        assert_eq!(world.item("Ada", ItemKind::Stone), 2);
+        assert_eq!(world.agent("Bo"), fixture().agent("Bo"));
+
+        let ada = world.agent("Ada");
+        let tick = world.step(&[
+            ActionRequest::new(
+                ada,
+                Action::HarvestOre {
+                    ore: OreKind::QI,
+                    source_id: world.ore(0),
+                },
+            ),
+            ActionRequest::new(
+                world.agent("Bo"),
+                Action::Move {
+                    dx: -1,
+                    dy: 0,
+                    dz: 0,
+                },
+            ),
+        ]);
+        assert!(matches!(
+            tick.assert_rejected(world.agent("Bo")),
+            ActionError::PositionOccupied { .. }
+        ));
+        tick.assert_event(
+            |e| matches!(e, Event::OreNodeHarvested { agent_id, .. } if *agent_id == ada),
+        )
+        .assert_no_event(|e| matches!(e, Event::AgentMoved { .. }));
+        assert!(tick.event_kinds().iter().any(|k| k == "OreNodeHarvested"));
+        assert_eq!(world.qi("Ada"), 5);
+        assert_eq!(world.position("Bo"), Position { x: 1, y: 0, z: 0 });
+
+        world.idle(3).unwrap().assert_accepted();
+        assert_eq!(world.vm.world().tick(), 14);
+        assert!(world.is_alive("Ada"));
     }
 }
@@ -1,13 +1,12 @@
 use std::collections::HashMap;
-use std::fs;
 use std::io;
-use std::path::PathBuf;
 
 use rand::RngCore;
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::modules::store;
 use crate::modules::vm::{POW_DIFFICULTY_BYTES, POW_REWARD, Qi};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,36 +22,11 @@ pub struct WalletStore {
 
 impl WalletStore {
     pub fn load() -> io::Result<Self> {
-        let path = wallet_path();
-        if !path.exists() {
-            return Ok(WalletStore::default());
-        }
-
-        let data = fs::read(path)?;
-        if data.is_empty() {
-            return Ok(WalletStore::default());
-        }
-
-        let store: WalletStore = serde_json::from_slice(&data).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!(
-                    "failed to parse wallet store {}; delete it to reset: {}",
-                    wallet_path().display(),
-                    e
-                ),
-            )
-        })?;
-
-        Ok(store)
+        store::load_document(WALLETS_DOC, "wallet store")
     }
 
     pub fn save(&self) -> io::Result<()> {
-        let dir = wallet_dir();
-        fs::create_dir_all(&dir)?;
-        let json = serde_json::to_vec_pretty(self)?;
-        fs::write(wallet_path(), json)?;
-        Ok(())
+        store::save_document(WALLETS_DOC, self)
     }
 
     pub fn upsert_wallet(&mut self, wallet: Wallet) {
@@ -72,13 +46,7 @@ impl WalletStore {
     }
 }
 
-fn wallet_dir() -> PathBuf {
-    PathBuf::from(".harimu")
-}
-
-fn wallet_path() -> PathBuf {
-    wallet_dir().join("wallets.json")
-}
+const WALLETS_DOC: &str = "wallets.json";
 
 pub fn create_wallet() -> io::Result<Wallet> {
     let mut bytes = [0u8; 20];
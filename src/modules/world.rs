@@ -58,15 +58,10 @@ impl WorldCommands {
             .try_into()
             .map_err(|_| "total Qi exceeds u32".to_string())?;
 
-        // Non-qi ore is priced in Qi at a flat rate per unit.
-        let cost_multiplier: u64 = match cmd.ore {
-            OreKind::Qi => 1,
-            OreKind::Transistor => 100,
-        };
+        // Each unit of capacity costs the ore's registered infusion cost in Qi.
         let charged = charged
-            .saturating_mul(cost_multiplier as Qi)
-            .try_into()
-            .map_err(|_| "ore cost exceeds u32".to_string())?;
+            .checked_mul(cmd.ore.infusion_cost())
+            .ok_or_else(|| "ore cost exceeds u32".to_string())?;
 
         {
             let wallet = wallet_store
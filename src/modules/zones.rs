@@ -0,0 +1,81 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::store;
+use crate::modules::vm::Zone;
+
+/// A player-given name and free-form notes for one zone.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneLabel {
+    pub zone: Zone,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+}
+
+impl ZoneLabel {
+    pub fn new(zone: Zone) -> Self {
+        Self {
+            zone,
+            name: None,
+            notes: Vec::new(),
+        }
+    }
+
+    /// One-line description used in listings and LLM observations.
+    pub fn describe(&self) -> String {
+        let mut text = format!("zone ({},{},{})", self.zone.x, self.zone.y, self.zone.z);
+        if let Some(name) = &self.name {
+            text.push_str(&format!(" \"{}\"", name));
+        }
+        if !self.notes.is_empty() {
+            text.push_str(&format!(" notes: {}", self.notes.join("; ")));
+        }
+        text
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneStore {
+    pub zones: Vec<ZoneLabel>,
+}
+
+impl ZoneStore {
+    pub fn get(&self, zone: Zone) -> Option<&ZoneLabel> {
+        self.zones.iter().find(|label| label.zone == zone)
+    }
+
+    /// The label for `zone`, created empty if it does not exist yet.
+    pub fn entry(&mut self, zone: Zone) -> &mut ZoneLabel {
+        let idx = match self.zones.iter().position(|label| label.zone == zone) {
+            Some(idx) => idx,
+            None => {
+                self.zones.push(ZoneLabel::new(zone));
+                self.zones
+                    .sort_by_key(|label| (label.zone.x, label.zone.y, label.zone.z));
+                self.zones
+                    .iter()
+                    .position(|label| label.zone == zone)
+                    .unwrap_or_default()
+            }
+        };
+        &mut self.zones[idx]
+    }
+
+    pub fn remove(&mut self, zone: Zone) -> Option<ZoneLabel> {
+        let idx = self.zones.iter().position(|label| label.zone == zone)?;
+        Some(self.zones.remove(idx))
+    }
+}
+
+const ZONES_DOC: &str = "zones.json";
+
+pub fn load_zone_store() -> io::Result<ZoneStore> {
+    store::load_document(ZONES_DOC, "zone store")
+}
+
+pub fn save_zone_store(zones: &ZoneStore) -> io::Result<()> {
+    store::save_document(ZONES_DOC, zones)
+}
@@ -0,0 +1,31 @@
+//! The stable API for embedding a world: everything here keeps its name and meaning across
+//! minor releases, so `use harimu::prelude::*;` is the import to depend on.
+//!
+//! `Action`, `ActionError` and `Event` are `#[non_exhaustive]`: new actions, rejections and
+//! events are added in minor releases, so matches on them need a `_` arm.
+//!
+//! ```
+//! use harimu::prelude::*;
+//!
+//! let mut vm = Vm::with_seed(7);
+//! let ada = vm.spawn_agent("Ada", 3, Position::origin());
+//! let result = vm.step(&[ActionRequest::new(ada, Action::Scan)]);
+//! for event in &result.events {
+//!     match event {
+//!         Event::ScanReport { agent_id, .. } => assert_eq!(*agent_id, ada),
+//!         _ => {}
+//!     }
+//! }
+//! assert!(result.rejections.is_empty());
+//! ```
+
+pub use crate::modules::effects::{StructureEffect, StructureRegistry};
+pub use crate::modules::inventory::{Inventory, ItemKind};
+pub use crate::modules::ore::OreKind;
+pub use crate::modules::structure::{Structure, StructureKind};
+pub use crate::modules::terrain::Block;
+pub use crate::modules::view::WorldSnapshot;
+pub use crate::modules::vm::{
+    Action, ActionError, ActionRejection, ActionRequest, Agent, AgentId, DeathReason,
+    EffectContext, Event, Position, Qi, TickResult, Vm, World, Zone,
+};